@@ -1,84 +1,898 @@
+use std::cell::{Cell, RefCell};
+use std::{cmp, mem};
+use std::marker::PhantomData;
+use std::rc::Rc;
 use std::time::Duration;
+use std::vec;
+use rand::{self, Rng};
 use serde::de::DeserializeOwned;
-use serde_json::Value;
-use futures::{Future, Stream, Poll};
-use tokio_timer::{Timer, Interval, Timeout};
-use web3::{self, api, Transport};
-use web3::api::Namespace;
-use web3::types::{Log, Filter, H256, H520, U256, FilterBuilder, TransactionRequest, Bytes, Address, CallRequest};
-use web3::helpers::CallResult;
-use error::{Error, ErrorKind};
+use serde_json::{self, Value};
+use futures::{Future, Stream, Poll, Async};
+use futures::future::{JoinAll, join_all};
+use futures::stream::FuturesOrdered;
+use futures::task::{self, Task};
+use tokio_timer::{Timer, Interval, Sleep, Timeout};
+use web3::{self, helpers, Transport, BatchTransport, RequestId};
+use web3::transports::{self, batch};
+use web3::types::{Log, Filter, H256, H520, U256, FilterBuilder, Bytes, Address, CallRequest, BlockNumber};
+use config::{RetryPolicy, RetryPolicies, BlockTag, AdaptivePolling, CatchUp};
+use error::{Error, ErrorKind, ErrorClass};
+use transaction::Transaction;
+use transport::PubSub;
+use rpc;
 
 /// Imperative alias for web3 function.
 pub use web3::confirm::send_transaction_with_confirmation;
 
-/// Wrapper type for `CallResult`
-pub struct ApiCall<T, F> {
-	future: CallResult<T, F>,
+/// Every attempt of a retried call times out after the request timeout.
+struct Retry {
+	timer: Timer,
+	timeout: Duration,
+	policies: RetryPolicies,
+}
+
+/// Returns delay before the next attempt of a call, which has already failed `failures` times.
+pub fn retry_delay(policy: &RetryPolicy, failures: u32) -> Duration {
+	// prevent overflow of the backoff multiplier
+	let exponent = cmp::min(failures.saturating_sub(1), 16);
+	let jitter = policy.jitter * rand::thread_rng().gen_range(0, 1_001) / 1_000;
+	policy.backoff * 2u32.pow(exponent) + jitter
+}
+
+enum ApiCallState<F> {
+	/// Waiting for the response.
+	Sending {
+		future: F,
+		timeout: Option<Sleep>,
+	},
+	/// Waiting before the next attempt.
+	Backoff(Sleep),
+}
+
+/// Single api call, optionally retried in case of failures.
+pub struct ApiCall<T, P: Transport> {
+	transport: P,
 	message: &'static str,
+	params: Vec<Value>,
+	retry: Option<Retry>,
+	failures: u32,
+	state: ApiCallState<P::Out>,
+	result: PhantomData<T>,
 }
 
-impl<T, F> ApiCall<T, F> {
+impl<T, P: Transport> ApiCall<T, P> {
+	fn new(transport: P, message: &'static str, params: Vec<Value>) -> Self {
+		ApiCall {
+			state: ApiCallState::Sending {
+				future: transport.execute(message, params.clone()),
+				timeout: None,
+			},
+			transport,
+			message,
+			params,
+			retry: None,
+			failures: 0,
+			result: PhantomData,
+		}
+	}
+
 	pub fn message(&self) -> &'static str {
 		self.message
 	}
+
+	/// Retries the call according to the policy of the error class if it fails or does not respond within `timeout`.
+	/// Should be used only for calls, which are safe to be sent more than once.
+	pub fn retry(mut self, timer: &Timer, timeout: Duration, policies: &RetryPolicies) -> Self {
+		if let ApiCallState::Sending { timeout: ref mut sleep, .. } = self.state {
+			*sleep = Some(timer.sleep(timeout));
+		}
+
+		self.retry = Some(Retry {
+			timer: timer.clone(),
+			timeout,
+			policies: policies.clone(),
+		});
+		self
+	}
+
+	fn send(&self) -> ApiCallState<P::Out> {
+		ApiCallState::Sending {
+			future: self.transport.execute(self.message, self.params.clone()),
+			timeout: self.retry.as_ref().map(|retry| retry.timer.sleep(retry.timeout)),
+		}
+	}
 }
 
-impl<T: DeserializeOwned, F: Future<Item = Value, Error = web3::Error>>Future for ApiCall<T, F> {
+impl<T: DeserializeOwned, P: Transport> Future for ApiCall<T, P> {
 	type Item = T;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-		trace!(target: "bridge", "{}", self.message);
-		self.future.poll().map_err(ErrorKind::Web3).map_err(Into::into)
+		loop {
+			let next_state = match self.state {
+				ApiCallState::Sending { ref mut future, ref mut timeout } => {
+					trace!(target: "bridge", "{}", self.message);
+					let error: Error = match future.poll() {
+						Ok(Async::Ready(value)) => {
+							let result = serde_json::from_value(value).map_err(|err| ErrorKind::Web3(err.into()))?;
+							return Ok(Async::Ready(result));
+						},
+						Ok(Async::NotReady) => match *timeout {
+							Some(ref mut timeout) => {
+								try_ready!(timeout.poll());
+								ErrorKind::Timeout(self.message).into()
+							},
+							None => return Ok(Async::NotReady),
+						},
+						Err(err) => ErrorKind::Web3(err).into(),
+					};
+
+					self.failures += 1;
+					let class = error.class();
+					match self.retry {
+						Some(ref retry) if self.failures < retry.policies.get(class).max_attempts => {
+							let policy = retry.policies.get(class);
+							let delay = retry_delay(policy, self.failures);
+							warn!(target: "bridge", "{} failed ({}/{}, {}): {}, retrying in {:?}", self.message, self.failures, policy.max_attempts, class.name(), error, delay);
+							ApiCallState::Backoff(retry.timer.sleep(delay))
+						},
+						_ => return Err(error),
+					}
+				},
+				ApiCallState::Backoff(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					self.send()
+				},
+			};
+
+			self.state = next_state;
+		}
 	}
 }
 
 /// Imperative wrapper for web3 function.
-pub fn logs<T: Transport>(transport: T, filter: &Filter) -> ApiCall<Vec<Log>, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).logs(filter),
-		message: "eth_getLogs",
-	}
+pub fn logs<T: Transport>(transport: T, filter: &Filter) -> ApiCall<Vec<Log>, T> {
+	ApiCall::new(transport, "eth_getLogs", vec![helpers::serialize(filter)])
 }
 
 /// Imperative wrapper for web3 function.
-pub fn block_number<T: Transport>(transport: T) -> ApiCall<U256, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).block_number(),
-		message: "eth_blockNumber",
-	}
+pub fn block_number<T: Transport>(transport: T) -> ApiCall<U256, T> {
+	ApiCall::new(transport, "eth_blockNumber", vec![])
+}
+
+/// Sends the transaction to be signed by the node.
+pub fn send_transaction<T: Transport>(transport: T, tx: Transaction) -> ApiCall<H256, T> {
+	ApiCall::new(transport, "eth_sendTransaction", vec![helpers::serialize(&tx)])
+}
+
+/// Sends the transaction signed locally.
+pub fn send_raw_transaction<T: Transport>(transport: T, tx: Bytes) -> ApiCall<H256, T> {
+	ApiCall::new(transport, "eth_sendRawTransaction", vec![helpers::serialize(&tx)])
+}
+
+/// Returns number of transactions sent from `address`, including the pending ones,
+/// which is the nonce of its next transaction.
+pub fn transaction_count<T: Transport>(transport: T, address: Address) -> ApiCall<U256, T> {
+	ApiCall::new(transport, "eth_getTransactionCount", vec![helpers::serialize(&address), "pending".into()])
+}
+
+/// Returns the balance of `address` at the latest block, in wei.
+pub fn balance<T: Transport>(transport: T, address: Address) -> ApiCall<U256, T> {
+	ApiCall::new(transport, "eth_getBalance", vec![helpers::serialize(&address), "latest".into()])
 }
 
 /// Imperative wrapper for web3 function.
-pub fn send_transaction<T: Transport>(transport: T, tx: TransactionRequest) -> ApiCall<H256, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).send_transaction(tx),
-		message: "eth_sendTransaction",
-	}
+pub fn code<T: Transport>(transport: T, address: Address) -> ApiCall<Bytes, T> {
+	ApiCall::new(transport, "eth_getCode", vec![helpers::serialize(&address), "latest".into()])
+}
+
+/// Returns the code of `address` at `block`, which requires the state of the block, e.g. of an archive node.
+pub fn code_at<T: Transport>(transport: T, address: Address, block: u64) -> ApiCall<Bytes, T> {
+	ApiCall::new(transport, "eth_getCode", vec![helpers::serialize(&address), helpers::serialize(&BlockNumber::from(block))])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn gas_price<T: Transport>(transport: T) -> ApiCall<U256, T> {
+	ApiCall::new(transport, "eth_gasPrice", vec![])
+}
+
+/// Returns the ID of the chain, which transactions are signed for.
+pub fn chain_id<T: Transport>(transport: T) -> ApiCall<U256, T> {
+	ApiCall::new(transport, "eth_chainId", vec![])
+}
+
+/// Returns the accounts, which transactions the node signs.
+pub fn accounts<T: Transport>(transport: T) -> ApiCall<Vec<Address>, T> {
+	ApiCall::new(transport, "eth_accounts", vec![])
+}
+
+/// Receipt fields used by the bridge.
+#[derive(Debug, Deserialize)]
+pub struct TransactionReceipt {
+	#[serde(rename = "blockNumber")]
+	pub block_number: Option<U256>,
+	/// Zero if the transaction has been reverted, `None` if the node does not report it.
+	pub status: Option<U256>,
+	#[serde(rename = "gasUsed")]
+	pub gas_used: Option<U256>,
+	/// Price paid per gas, `None` if the node does not report it.
+	#[serde(rename = "effectiveGasPrice")]
+	pub effective_gas_price: Option<U256>,
+}
+
+/// Fetches receipt of the transaction. Resolves to `None` if the transaction is not mined yet.
+pub fn transaction_receipt<T: Transport>(transport: T, hash: H256) -> ApiCall<Option<TransactionReceipt>, T> {
+	ApiCall::new(transport, "eth_getTransactionReceipt", vec![helpers::serialize(&hash)])
+}
+
+/// Transaction fields used by the bridge.
+#[derive(Debug, Deserialize)]
+pub struct TransactionInfo {
+	pub from: Address,
+	pub nonce: U256,
+	/// `None` if the transaction is not mined yet.
+	#[serde(rename = "blockNumber")]
+	pub block_number: Option<U256>,
+}
+
+/// Fetches the transaction. Resolves to `None` if the node doesn't know it, e.g. because it has been dropped.
+pub fn transaction<T: Transport>(transport: T, hash: H256) -> ApiCall<Option<TransactionInfo>, T> {
+	ApiCall::new(transport, "eth_getTransactionByHash", vec![helpers::serialize(&hash)])
 }
 
 /// Imperative wrapper for web3 function.
-pub fn call<T: Transport>(transport: T, address: Address, payload: Bytes) -> ApiCall<Bytes, T::Out> {
-	let future = api::Eth::new(transport).call(CallRequest {
-		from: None,
+pub fn call<T: Transport>(transport: T, address: Address, payload: Bytes) -> ApiCall<Bytes, T> {
+	call_request(transport, None, address, payload, BlockNumber::Latest)
+}
+
+/// Calls the contract at `address` on behalf of the account `from`, e.g. to simulate a transaction.
+pub fn call_from<T: Transport>(transport: T, from: Address, address: Address, payload: Bytes) -> ApiCall<Bytes, T> {
+	call_request(transport, Some(from), address, payload, BlockNumber::Latest)
+}
+
+/// Calls the contract at `address` on behalf of the account `from` in the state of the given `block`,
+/// e.g. to replay a transaction mined in that block.
+pub fn call_at<T: Transport>(transport: T, from: Address, address: Address, payload: Bytes, block: u64) -> ApiCall<Bytes, T> {
+	call_request(transport, Some(from), address, payload, block.into())
+}
+
+fn call_request<T: Transport>(transport: T, from: Option<Address>, address: Address, payload: Bytes, block: BlockNumber) -> ApiCall<Bytes, T> {
+	let request = CallRequest {
+		from,
 		to: address,
 		gas: None,
 		gas_price: None,
 		value: None,
 		data: Some(payload),
-	}, None);
+	};
+
+	ApiCall::new(transport, "eth_call", vec![helpers::serialize(&request), helpers::serialize(&block)])
+}
+
+/// Resolves to true if the call has been reverted and to false if it has succeeded.
+pub struct Reverts<T: Transport> {
+	call: ApiCall<Bytes, T>,
+}
+
+pub fn reverts<T: Transport>(call: ApiCall<Bytes, T>) -> Reverts<T> {
+	Reverts { call }
+}
+
+impl<T: Transport> Future for Reverts<T> {
+	type Item = bool;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.call.poll() {
+			Ok(Async::Ready(_)) => Ok(Async::Ready(false)),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(ref err) if err.class() == ErrorClass::Reverted => Ok(Async::Ready(true)),
+			Err(err) => Err(err),
+		}
+	}
+}
+
+/// Resolves to the reason of the revert of the replayed call, if it's reported by the node.
+///
+/// The reason is informational, so the call resolves to `None` rather than failing if it cannot be fetched.
+pub struct RevertReason<T: Transport> {
+	call: ApiCall<Bytes, T>,
+}
+
+pub fn revert_reason<T: Transport>(call: ApiCall<Bytes, T>) -> RevertReason<T> {
+	RevertReason { call }
+}
+
+impl<T: Transport> Future for RevertReason<T> {
+	type Item = Option<String>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.call.poll() {
+			Ok(Async::Ready(_)) => Ok(Async::Ready(None)),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(err) => {
+				if err.class() != ErrorClass::Reverted {
+					debug!(target: "bridge", "Replayed call has failed: {}", err);
+				}
+				Ok(Async::Ready(err.revert_reason()))
+			},
+		}
+	}
+}
+
+/// Subscribes to notifications about new blocks. Resolves to subscription id.
+pub fn subscribe_new_heads<T: Transport>(transport: T) -> ApiCall<String, T> {
+	ApiCall::new(transport, "eth_subscribe", vec!["newHeads".into()])
+}
+
+/// Subscribes to notifications about the logs of new blocks, which match the address and topics of `filter`.
+/// Resolves to subscription id.
+pub fn subscribe_logs<T: Transport>(transport: T, filter: &Filter) -> ApiCall<String, T> {
+	let mut filter = helpers::serialize(filter);
+	if let Value::Object(ref mut filter) = filter {
+		filter.remove("fromBlock");
+		filter.remove("toBlock");
+		filter.remove("limit");
+	}
+	ApiCall::new(transport, "eth_subscribe", vec!["logs".into(), filter])
+}
+
+/// Block header fields used by the bridge.
+#[derive(Debug, Deserialize)]
+pub struct BlockHeader {
+	/// `None` if the block is pending.
+	pub number: Option<U256>,
+}
+
+/// Fetches header of the block with given tag. Resolves to `None` if there is no such block yet.
+pub fn block_by_tag<T: Transport>(transport: T, tag: BlockTag) -> ApiCall<Option<BlockHeader>, T> {
+	ApiCall::new(transport, "eth_getBlockByNumber", vec![tag.as_str().into(), false.into()])
+}
+
+pub fn sign<T: Transport>(transport: T, address: Address, data: Bytes) -> ApiCall<H520, T> {
+	ApiCall::new(transport, "eth_sign", vec![helpers::serialize(&address), helpers::serialize(&data)])
+}
+
+/// Collects calls, which are then sent to the node as a single JSON-RPC batch.
+///
+/// Clones share the same batch, so calls may own the transport they were created with.
+pub struct Batch<T> {
+	transport: Rc<transports::Batch<T>>,
+	calls: Rc<Cell<usize>>,
+}
+
+impl<T> Clone for Batch<T> {
+	fn clone(&self) -> Self {
+		Batch {
+			transport: self.transport.clone(),
+			calls: self.calls.clone(),
+		}
+	}
+}
+
+impl<T: BatchTransport> Batch<T> {
+	pub fn new(transport: T) -> Self {
+		Batch {
+			transport: Rc::new(transports::Batch::new(transport)),
+			calls: Rc::new(Cell::new(0)),
+		}
+	}
+
+	/// Sends all collected calls. Returned future drives the batch request and resolves
+	/// to the result of `calls`, which should be created using this batch.
+	pub fn submit<F>(self, calls: F) -> BatchCall<T::Batch, F> {
+		let batch = match self.calls.get() {
+			// there is no point in sending an empty batch
+			0 => None,
+			_ => Some(self.transport.submit_batch()),
+		};
+
+		BatchCall {
+			batch,
+			calls,
+		}
+	}
+}
+
+impl<T: BatchTransport> Transport for Batch<T> {
+	type Out = batch::SingleResult;
+
+	fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, rpc::Call) {
+		self.transport.prepare(method, params)
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		self.calls.set(self.calls.get() + 1);
+		self.transport.send(id, request)
+	}
+}
+
+/// Future resolving calls sent together in a single batch.
+pub struct BatchCall<B, F> {
+	batch: Option<batch::BatchFuture<B>>,
+	calls: F,
+}
+
+impl<B, F> Future for BatchCall<B, F> where
+	B: Future<Item = Vec<Result<Value, web3::Error>>, Error = web3::Error>,
+	F: Future<Error = Error>,
+{
+	type Item = F::Item;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		if let Some(ref mut batch) = self.batch {
+			let _ = try_ready!(batch.poll().map_err(ErrorKind::Web3));
+		}
+		// all calls have been resolved by the batch
+		self.batch = None;
+		self.calls.poll()
+	}
+}
+
+/// Transport sending `eth_blockNumber` together with every request in a single batch,
+/// so that the best block is learnt without another round trip.
+///
+/// Clones share the last block number.
+#[derive(Clone)]
+pub struct WithBlockNumber<T> {
+	transport: T,
+	block_number: Rc<Cell<Option<u64>>>,
+}
+
+impl<T> WithBlockNumber<T> {
+	pub fn new(transport: T) -> Self {
+		WithBlockNumber {
+			transport,
+			block_number: Default::default(),
+		}
+	}
+
+	/// Returns the best block number, which has been received since the last call.
+	pub fn take_block_number(&self) -> Option<u64> {
+		self.block_number.take()
+	}
+
+	/// Returns the wrapped transport, which sends the requests alone.
+	pub fn transport(&self) -> &T {
+		&self.transport
+	}
+}
+
+impl<T: BatchTransport> Transport for WithBlockNumber<T> {
+	type Out = WithBlockNumberCall<T::Batch>;
+
+	fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, rpc::Call) {
+		self.transport.prepare(method, params)
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		let block_number = self.transport.prepare("eth_blockNumber", vec![]);
+		WithBlockNumberCall {
+			batch: self.transport.send_batch(vec![(id, request), block_number]),
+			block_number: self.block_number.clone(),
+		}
+	}
+}
+
+/// Future resolving to the response of the request sent with `WithBlockNumber`.
+pub struct WithBlockNumberCall<B> {
+	batch: B,
+	block_number: Rc<Cell<Option<u64>>>,
+}
+
+impl<B> Future for WithBlockNumberCall<B> where
+	B: Future<Item = Vec<Result<Value, web3::Error>>, Error = web3::Error>,
+{
+	type Item = Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let mut responses = try_ready!(self.batch.poll()).into_iter();
+		let response = responses.next();
+		// the block number is informational, the `BlockWatcher` fetches it again if it's missing
+		if let Some(Ok(number)) = responses.next() {
+			if let Ok(number) = serde_json::from_value::<U256>(number) {
+				self.block_number.set(Some(number.low_u64()));
+			}
+		}
+
+		match response {
+			Some(response) => response.map(Async::Ready),
+			None => Err(web3::Error::InvalidResponse("missing response in batch".into())),
+		}
+	}
+}
+
+/// Future driving at most `limit` futures at once, so that sending many calls does not flood the node.
+///
+/// Futures are created from `items` only when they are about to be polled,
+/// so that timeouts do not run out while waiting. Resolves to the results in the order of `items`
+/// and fails on the first error.
+pub struct Limited<C, I, F: Future> {
+	context: C,
+	create: fn(&C, I) -> F,
+	items: vec::IntoIter<I>,
+	running: FuturesOrdered<F>,
+	results: Vec<F::Item>,
+	limit: usize,
+}
+
+pub fn limited<C, I, F: Future>(context: C, items: Vec<I>, limit: usize, create: fn(&C, I) -> F) -> Limited<C, I, F> {
+	assert!(limit > 0, "limit is validated when loading config; qed");
+	Limited {
+		context,
+		create,
+		results: Vec::with_capacity(items.len()),
+		items: items.into_iter(),
+		running: FuturesOrdered::new(),
+		limit,
+	}
+}
+
+impl<C, I, F: Future> Limited<C, I, F> {
+	/// Results of the leading items which have already been processed.
+	pub fn completed(&self) -> &[F::Item] {
+		&self.results
+	}
+
+	/// Processes the next item, so that its result may be handled before the rest of them are processed.
+	/// Resolves to `true` once all items have been processed.
+	pub fn poll_item(&mut self) -> Poll<bool, F::Error> {
+		while self.running.len() < self.limit {
+			match self.items.next() {
+				Some(item) => self.running.push((self.create)(&self.context, item)),
+				None => break,
+			}
+		}
+
+		match try_ready!(self.running.poll()) {
+			Some(result) => {
+				self.results.push(result);
+				Ok(Async::Ready(false))
+			},
+			// nothing is running, so all items have been processed
+			None => Ok(Async::Ready(true)),
+		}
+	}
+}
+
+impl<C, I, F: Future> Future for Limited<C, I, F> {
+	type Item = Vec<F::Item>;
+	type Error = F::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		while !try_ready!(self.poll_item()) {}
+		Ok(Async::Ready(self.results.split_off(0)))
+	}
+}
+
+/// Used for `BlockWatcher` initialization.
+pub struct BlockWatcherInit {
+	pub request_timeout: Duration,
+	pub retry: RetryPolicies,
+	pub poll_interval: Duration,
+	/// If specified, the best block is polled less often while the streams find no logs.
+	pub adaptive_polling: Option<AdaptivePolling>,
+	/// Tag of the blocks, after which logs are not considered confirmed.
+	pub block_tag: Option<BlockTag>,
+}
+
+/// Longest delay before fetching the tagged block again after it has failed.
+const MAX_TAGGED_BLOCK_BACKOFF_SECS: u64 = 60;
+
+/// Returns true if the node has rejected the block tag itself, rather than failed to fetch the block.
+fn is_unsupported_tag(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Web3(web3::Error::Rpc(ref err)) => {
+			let message = err.message.to_lowercase();
+			err.code == rpc::ErrorCode::InvalidParams || message.contains("block tag") || message.contains("unknown variant")
+		},
+		_ => false,
+	}
+}
+
+/// Best block of the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BestBlock {
+	pub number: u64,
+	/// Number of the block with configured tag, `None` if not known.
+	pub tagged: Option<u64>,
+}
+
+/// Block watcher state.
+enum BlockWatcherState<T: Transport> {
+	/// Subscribing to new block notifications.
+	Subscribe(Timeout<ApiCall<String, T>>),
+	/// Block watcher is waiting for new block notification or for timer to poll.
+	Wait,
+	/// Fetching best block number.
+	FetchBlockNumber(ApiCall<U256, T>),
+	/// Fetching number of the block with configured tag.
+	FetchTaggedBlock {
+		number: u64,
+		future: ApiCall<Option<BlockHeader>, T>,
+	},
+	/// Waiting before fetching the tagged block again, after it has failed.
+	TaggedBlockBackoff {
+		number: u64,
+		sleep: Sleep,
+	},
+}
+
+struct Watcher<T: PubSub> {
+	transport: T,
+	timer: Timer,
+	poll_interval: Duration,
+	interval: Interval,
+	adaptive_polling: Option<AdaptivePolling>,
+	/// Delay after the last poll, `None` if logs have been found since the previous one.
+	idle_interval: Option<Duration>,
+	/// Set once a stream finds logs, until the next poll.
+	found_logs: bool,
+	state: BlockWatcherState<T>,
+	/// New block notifications, `None` if polling.
+	notifications: Option<T::Notifications>,
+	request_timeout: Duration,
+	retry: RetryPolicies,
+	/// Set to `None` if the node does not support the tag.
+	block_tag: Option<BlockTag>,
+	/// Number of consecutive failures to fetch the tagged block.
+	tag_failures: u32,
+	/// Best block, valid after the first update.
+	block: BestBlock,
+	/// Number of times the best block number has been fetched.
+	updates: usize,
+	/// Tasks waiting for the next update.
+	waiting: Vec<Task>,
+}
+
+impl<T: PubSub + Clone> Watcher<T> {
+	fn subscribe(&self) -> BlockWatcherState<T> {
+		// failed subscription is not retried, the watcher falls back to polling instead
+		BlockWatcherState::Subscribe(self.timer.timeout(subscribe_new_heads(self.transport.clone()), self.request_timeout))
+	}
+
+	fn fetch_block_number(&self) -> BlockWatcherState<T> {
+		let future = block_number(self.transport.clone()).retry(&self.timer, self.request_timeout, &self.retry);
+		BlockWatcherState::FetchBlockNumber(future)
+	}
+
+	fn fetch_tagged_block(&self, number: u64, tag: BlockTag) -> BlockWatcherState<T> {
+		BlockWatcherState::FetchTaggedBlock {
+			number,
+			future: block_by_tag(self.transport.clone(), tag).retry(&self.timer, self.request_timeout, &self.retry),
+		}
+	}
+
+	/// Restarts polling with the longer of the idle interval and the `poll_interval`.
+	fn reset_interval(&mut self) {
+		let interval = match self.idle_interval {
+			Some(idle) => cmp::max(idle, self.poll_interval),
+			None => self.poll_interval,
+		};
+		self.interval = self.timer.interval(interval);
+	}
+
+	/// Stores new best block and wakes up all waiting streams.
+	fn update(&mut self, block: BestBlock) -> BestBlock {
+		self.block = block;
+		self.updates += 1;
+		self.state = BlockWatcherState::Wait;
+		for task in self.waiting.drain(..) {
+			task.notify();
+		}
+		block
+	}
+}
+
+impl<T: PubSub + Clone> Stream for Watcher<T> {
+	type Item = BestBlock;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				BlockWatcherState::Subscribe(ref mut future) => match future.poll() {
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Ok(Async::Ready(subscription)) => {
+						info!(target: "bridge", "Subscribed to new blocks");
+						self.notifications = Some(self.transport.notifications(&subscription));
+						// blocks might have been missed while subscribing
+						self.fetch_block_number()
+					},
+					Err(err) => {
+						warn!(target: "bridge", "Cannot subscribe to new blocks, polling instead: {}", err);
+						BlockWatcherState::Wait
+					},
+				},
+				BlockWatcherState::Wait => {
+					let notification = self.notifications.as_mut().map(Stream::poll);
+
+					match notification {
+						None => {
+							let _ = try_stream!(self.interval.poll());
+							// polls back off together with the streams, which have not found any logs
+							if let Some(polling) = self.adaptive_polling {
+								let idle = idle_interval(&polling, self.idle_interval, mem::take(&mut self.found_logs));
+								if idle != self.idle_interval {
+									self.idle_interval = idle;
+									self.reset_interval();
+								}
+							}
+							self.fetch_block_number()
+						},
+						Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+						Some(Ok(Async::Ready(Some(_)))) => {
+							self.fetch_block_number()
+						},
+						Some(Ok(Async::Ready(None))) | Some(Err(_)) => {
+							info!(target: "bridge", "New blocks subscription lost, resubscribing");
+							self.notifications = None;
+							self.subscribe()
+						},
+					}
+				},
+				BlockWatcherState::FetchBlockNumber(ref mut future) => {
+					let number = try_ready!(future.poll()).low_u64();
+					match self.block_tag {
+						Some(tag) => self.fetch_tagged_block(number, tag),
+						None => {
+							let block = BestBlock {
+								number,
+								tagged: None,
+							};
+							return Ok(Async::Ready(Some(self.update(block))));
+						},
+					}
+				},
+				BlockWatcherState::FetchTaggedBlock { number, ref mut future } => {
+					let tagged = match future.poll() {
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						// nothing is considered final, until the first tagged block is known
+						Ok(Async::Ready(header)) => {
+							self.tag_failures = 0;
+							Some(header.and_then(|header| header.number).map_or(0, |n| n.low_u64()))
+						},
+						Err(ref err) if is_unsupported_tag(err) => {
+							let tag = self.block_tag.take().expect("tagged block is fetched only if there is a tag; qed");
+							warn!(target: "bridge", "Node does not support `{}` block, using confirmations only: {}", tag.as_str(), err);
+							None
+						},
+						// the logs are not confirmed by the confirmations alone, until the tagged block is known again
+						Err(err) => {
+							let tag = self.block_tag.expect("tagged block is fetched only if there is a tag; qed");
+							self.tag_failures += 1;
+							let delay = cmp::min(retry_delay(self.retry.get(err.class()), self.tag_failures), Duration::from_secs(MAX_TAGGED_BLOCK_BACKOFF_SECS));
+							warn!(target: "bridge", "Cannot fetch `{}` block, retrying in {:?}: {}", tag.as_str(), delay, err);
+							self.state = BlockWatcherState::TaggedBlockBackoff {
+								number,
+								sleep: self.timer.sleep(delay),
+							};
+							continue;
+						},
+					};
+
+					let block = BestBlock {
+						number,
+						tagged,
+					};
+					return Ok(Async::Ready(Some(self.update(block))));
+				},
+				BlockWatcherState::TaggedBlockBackoff { number, ref mut sleep } => {
+					try_ready!(sleep.poll());
+					let tag = self.block_tag.expect("tagged block is fetched only if there is a tag; qed");
+					self.fetch_tagged_block(number, tag)
+				},
+			};
 
-	ApiCall {
-		future,
-		message: "eth_call",
+			self.state = next_state;
+		}
 	}
 }
 
-pub fn sign<T: Transport>(transport: T, address: Address, data: Bytes) -> ApiCall<H520, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).sign(address, data),
-		message: "eth_sign",
+/// Watches the best block of the chain, so that it is fetched only once for all `LogStream`s.
+///
+/// If the transport supports subscriptions, the watcher is woken up by new block
+/// notifications. Otherwise, or if the node rejects the subscription, it polls
+/// the node every `poll_interval`, or less often with `adaptive_polling` while the streams find no logs.
+///
+/// Clones share the same watcher. It is driven by the streams waiting for the next block.
+pub struct BlockWatcher<T: PubSub> {
+	watcher: Rc<RefCell<Watcher<T>>>,
+}
+
+impl<T: PubSub> Clone for BlockWatcher<T> {
+	fn clone(&self) -> Self {
+		BlockWatcher {
+			watcher: self.watcher.clone(),
+		}
+	}
+}
+
+impl<T: PubSub + Clone> BlockWatcher<T> {
+	pub fn new(transport: T, timer: Timer, init: BlockWatcherInit) -> Self {
+		let mut watcher = Watcher {
+			poll_interval: init.poll_interval,
+			interval: timer.interval(init.poll_interval),
+			adaptive_polling: init.adaptive_polling,
+			idle_interval: None,
+			found_logs: false,
+			state: BlockWatcherState::Wait,
+			notifications: None,
+			request_timeout: init.request_timeout,
+			retry: init.retry,
+			block_tag: init.block_tag,
+			tag_failures: 0,
+			block: BestBlock::default(),
+			updates: 0,
+			waiting: Vec::new(),
+			transport,
+			timer,
+		};
+
+		if watcher.transport.supports_subscriptions() {
+			watcher.state = watcher.subscribe();
+		}
+
+		BlockWatcher {
+			watcher: Rc::new(RefCell::new(watcher)),
+		}
+	}
+
+	/// Returns the best block as of the last update, which is the default one until the block is fetched.
+	pub fn best_block(&self) -> BestBlock {
+		self.watcher.borrow().block
+	}
+
+	/// Updates the best block with `number` received with other requests, if it is newer,
+	/// so that the streams do not wait for the next poll, which is then delayed by the whole interval.
+	///
+	/// Ignored while the watcher is fetching the block or if it needs the tagged block too.
+	pub fn observe(&self, number: u64) {
+		let mut watcher = self.watcher.borrow_mut();
+		match watcher.state {
+			BlockWatcherState::Wait if watcher.block_tag.is_none() && number > watcher.block.number => {},
+			_ => return,
+		}
+
+		watcher.reset_interval();
+		watcher.update(BestBlock {
+			number,
+			tagged: None,
+		});
+	}
+
+	/// Reports that a stream has found logs, so that the best block is polled every `poll_interval` again.
+	pub fn logs_found(&self) {
+		let mut watcher = self.watcher.borrow_mut();
+		watcher.found_logs = true;
+		if watcher.idle_interval.take().is_some() {
+			watcher.reset_interval();
+		}
+	}
+
+	/// Returns best block if it has been updated since the `seen` update.
+	pub fn poll_block(&self, seen: &mut usize) -> Poll<Option<BestBlock>, Error> {
+		let mut watcher = self.watcher.borrow_mut();
+		if watcher.updates == *seen {
+			match watcher.poll()? {
+				Async::Ready(None) => return Ok(Async::Ready(None)),
+				Async::Ready(Some(_)) => {},
+				Async::NotReady => {
+					// the watcher wakes up only the task which has polled it,
+					// others are notified after the update
+					if !watcher.waiting.iter().any(Task::will_notify_current) {
+						watcher.waiting.push(task::current());
+					}
+					return Ok(Async::NotReady);
+				},
+			}
+		}
+
+		*seen = watcher.updates;
+		Ok(Async::Ready(Some(watcher.block)))
 	}
 }
 
@@ -86,9 +900,22 @@ pub fn sign<T: Transport>(transport: T, address: Address, data: Bytes) -> ApiCal
 pub struct LogStreamInit {
 	pub after: u64,
 	pub filter: FilterBuilder,
-	pub request_timeout: Duration,
-	pub poll_interval: Duration,
+	/// Timeout of `eth_getLogs` requests.
+	pub logs_request_timeout: Duration,
+	pub retry: RetryPolicies,
 	pub confirmations: u64,
+	/// Maximum number of blocks queried by a single `eth_getLogs`. Unlimited if `None`.
+	pub max_blocks_per_query: Option<u64>,
+	/// If specified, logs are fetched less often after fetching no logs.
+	pub adaptive_polling: Option<AdaptivePolling>,
+	/// If specified, logs are fetched at most once per interval, rather than with every new block.
+	pub poll_interval: Option<Duration>,
+	/// If specified, logs are fetched in larger ranges while the stream is far behind the chain.
+	pub catch_up: Option<CatchUp>,
+	/// If true, the stream ends once it has yielded the logs of the blocks confirmed when it was started.
+	pub once: bool,
+	/// If specified, logs are fetched only up to this block, e.g. when the past blocks are replayed.
+	pub until: Option<u64>,
 }
 
 /// Contains all logs matching `LogStream` filter in inclusive range `[from, to]`.
@@ -100,90 +927,388 @@ pub struct LogStreamItem {
 }
 
 /// Log Stream state.
-enum LogStreamState<T: Transport> {
-	/// Log Stream is waiting for timer to poll.
+enum LogStreamState<T: BatchTransport> {
+	/// Subscribing to the logs matching the filter.
+	Subscribe(Timeout<ApiCall<String, T>>),
+	/// Fetching the best block, after which the logs are taken from the notifications.
+	FetchSubscribedBlock(ApiCall<U256, T>),
+	/// Log Stream is waiting for new best block.
 	Wait,
-	/// Fetching best block number.
-	FetchBlockNumber(Timeout<ApiCall<U256, T::Out>>),
-	/// Fetching logs for new best block.
+	/// Fetching logs for new best block, chunk by chunk.
 	FetchLogs {
 		from: u64,
 		to: u64,
-		future: Timeout<ApiCall<Vec<Log>, T::Out>>,
+		/// First block of the chunk being fetched.
+		chunk_from: u64,
+		/// Last block of the chunk being fetched.
+		chunk_to: u64,
+		/// Maximum size of the chunks, reduced when the node refuses to return too many logs.
+		max_blocks: Option<u64>,
+		/// Logs of already fetched chunks.
+		logs: Vec<Log>,
+		future: ApiCall<Vec<Log>, WithBlockNumber<T>>,
 	},
 	/// All logs has been fetched.
 	NextItem(Option<LogStreamItem>),
+	/// Waiting before fetching logs again, because recent fetches returned no logs or because of the `poll_interval`.
+	Idle(Sleep),
 }
 
-/// Creates new `LogStream`.
-pub fn log_stream<T: Transport>(transport: T, timer: Timer, init: LogStreamInit) -> LogStream<T> {
-	LogStream {
-		transport,
-		interval: timer.interval(init.poll_interval),
+/// Returns delay before fetching logs again after the fetch, which returned `has_logs`.
+/// Delays double with every fetch without logs, `None` means that logs are fetched as soon as there is a new block.
+fn idle_interval(polling: &AdaptivePolling, previous: Option<Duration>, has_logs: bool) -> Option<Duration> {
+	if has_logs {
+		return None;
+	}
+
+	let interval = match previous {
+		Some(previous) => cmp::min(previous * 2, polling.max_interval),
+		None => polling.min_interval,
+	};
+	Some(interval)
+}
+
+/// Formats block number with thousands separators, e.g. `1,204,000`.
+fn format_block(block: u64) -> String {
+	if block < 1000 {
+		block.to_string()
+	} else {
+		format!("{},{:03}", format_block(block / 1000), block % 1000)
+	}
+}
+
+/// Creates new `LogStream`, which learns about new blocks from `blocks`.
+///
+/// Logs are fetched together with the best block number in a single batch, which updates `blocks`.
+/// If the transport supports subscriptions, logs of the blocks mined after subscribing are taken from
+/// the `eth_subscribe("logs")` notifications instead.
+pub fn log_stream<T: BatchTransport + PubSub + Clone>(transport: T, timer: Timer, blocks: BlockWatcher<T>, init: LogStreamInit) -> LogStream<T> {
+	// past blocks are fetched only once, so there is nothing to subscribe to
+	let subscribe = transport.supports_subscriptions() && !init.once && init.until.is_none();
+	let mut stream = LogStream {
+		transport: WithBlockNumber::new(transport),
 		timer,
+		blocks,
+		seen_blocks: 0,
 		state: LogStreamState::Wait,
 		after: init.after,
 		filter: init.filter,
 		confirmations: init.confirmations,
-		request_timeout: init.request_timeout,
+		logs_request_timeout: init.logs_request_timeout,
+		retry: init.retry,
+		max_blocks_per_query: init.max_blocks_per_query,
+		adaptive_polling: init.adaptive_polling,
+		poll_interval: init.poll_interval,
+		idle_interval: None,
+		catch_up: init.catch_up,
+		once: init.once,
+		until: init.until,
+		last_confirmed: init.after,
+		catching_up: false,
+		notifications: None,
+		subscribed_after: None,
+		notified: Vec::new(),
+		resubscribe: false,
+	};
+
+	if subscribe {
+		stream.state = stream.subscribe();
 	}
+	stream
 }
 
 /// Stream of confirmed logs.
-pub struct LogStream<T: Transport> {
-	transport: T,
+///
+/// Logs are fetched with `eth_getLogs`, unless the stream is subscribed to them. Logs notified
+/// by the subscription are yielded once their blocks are confirmed. If the node rejects the subscription,
+/// the stream keeps fetching the logs, and if the subscription is lost, the stream subscribes again.
+pub struct LogStream<T: BatchTransport + PubSub> {
+	transport: WithBlockNumber<T>,
 	timer: Timer,
-	interval: Interval,
+	blocks: BlockWatcher<T>,
+	/// Last best block update seen by the stream.
+	seen_blocks: usize,
 	state: LogStreamState<T>,
 	after: u64,
 	filter: FilterBuilder,
 	confirmations: u64,
-	request_timeout: Duration,
+	logs_request_timeout: Duration,
+	retry: RetryPolicies,
+	max_blocks_per_query: Option<u64>,
+	adaptive_polling: Option<AdaptivePolling>,
+	poll_interval: Option<Duration>,
+	/// Delay after the last fetch, `None` if it has returned logs.
+	idle_interval: Option<Duration>,
+	catch_up: Option<CatchUp>,
+	once: bool,
+	until: Option<u64>,
+	/// Last confirmed block of the latest best block seen by the stream.
+	last_confirmed: u64,
+	/// True if the stream is more than `catch_up.threshold` blocks behind `last_confirmed`.
+	catching_up: bool,
+	/// Notifications of the logs subscription, `None` if the logs are fetched.
+	notifications: Option<T::Notifications>,
+	/// Block after which the logs are taken from the notifications, `None` until it is known.
+	subscribed_after: Option<u64>,
+	/// Notified logs of the blocks, which have not been yielded yet.
+	notified: Vec<Log>,
+	/// Set once the subscription is lost, so that the stream subscribes again.
+	resubscribe: bool,
+}
+
+impl<T: BatchTransport + PubSub + Clone> LogStream<T> {
+	fn subscribe(&self) -> LogStreamState<T> {
+		let filter = self.filter.clone().build();
+		// failed subscription is not retried, the stream keeps fetching the logs instead
+		LogStreamState::Subscribe(self.timer.timeout(subscribe_logs(self.transport.transport().clone(), &filter), self.logs_request_timeout))
+	}
+
+	/// Stores the logs notified by the subscription. Returns false if the subscription has been lost.
+	fn poll_notifications(&mut self) -> bool {
+		let notifications = match self.notifications {
+			Some(ref mut notifications) => notifications,
+			None => return true,
+		};
+
+		loop {
+			let notification = match notifications.poll() {
+				Ok(Async::Ready(Some(notification))) => notification,
+				Ok(Async::NotReady) => return true,
+				Ok(Async::Ready(None)) | Err(_) => return false,
+			};
+
+			// logs removed by a reorganization are flagged by geth and have the `removed` type in parity
+			let removed = notification.get("removed") == Some(&Value::Bool(true));
+			let log: Log = match serde_json::from_value(notification) {
+				Ok(log) => log,
+				Err(err) => {
+					warn!(target: "bridge", "Invalid log notification: {}", err);
+					continue;
+				},
+			};
+
+			if removed || log.log_type == "removed" {
+				self.notified.retain(|notified| notified.transaction_hash != log.transaction_hash || notified.log_index != log.log_index);
+				continue;
+			}
+
+			// logs of the blocks up to the subscribed one are fetched
+			match (log.block_number, self.subscribed_after) {
+				(Some(block), Some(after)) if block.low_u64() <= after => {},
+				(Some(_), _) => self.notified.push(log),
+				// pending logs are notified again once they are mined
+				(None, _) => {},
+			}
+		}
+	}
+
+	/// Takes the notified logs of the blocks up to `to`, in the order of `eth_getLogs`.
+	fn take_notified(&mut self, to: u64) -> Vec<Log> {
+		let (mut logs, notified): (Vec<_>, Vec<_>) = mem::take(&mut self.notified).into_iter()
+			.partition(|log| log.block_number.map_or(false, |block| block.low_u64() <= to));
+		self.notified = notified;
+		logs.sort_by_key(|log| (log.block_number.map(|block| block.low_u64()), log.log_index.map(|index| index.low_u64())));
+		logs
+	}
+
+	/// Starts fetching logs of confirmed blocks, which have not been checked yet.
+	fn next_range(&mut self) -> LogStreamState<T> {
+		if self.last_confirmed <= self.after {
+			return LogStreamState::Wait;
+		}
+
+		let from = self.after + 1;
+		let behind = self.last_confirmed - self.after;
+		match self.catch_up {
+			Some(catch_up) if behind > catch_up.threshold => {
+				if !self.catching_up {
+					info!(target: "bridge", "Catching up {} blocks after block {}", behind, format_block(self.after));
+					self.catching_up = true;
+				}
+
+				let max_blocks = catch_up.max_blocks_per_query.or(self.max_blocks_per_query);
+				// deferred logs are yielded at once, after fetching the whole range
+				let to = match max_blocks {
+					Some(max) if !catch_up.defer_relays => cmp::min(self.last_confirmed, from + max - 1),
+					_ => self.last_confirmed,
+				};
+				self.fetch_logs(from, to, from, max_blocks, Vec::new())
+			},
+			_ => {
+				if self.catching_up {
+					info!(target: "bridge", "Caught up at block {}", format_block(self.after));
+					self.catching_up = false;
+				}
+
+				self.fetch_logs(from, self.last_confirmed, from, self.max_blocks_per_query, Vec::new())
+			},
+		}
+	}
+
+	/// Returns delay before fetching logs again, the longer of the adaptive one and the `poll_interval`.
+	fn next_interval(&self) -> Option<Duration> {
+		match (self.idle_interval, self.poll_interval) {
+			(Some(idle), Some(poll)) => Some(cmp::max(idle, poll)),
+			(idle, poll) => idle.or(poll),
+		}
+	}
+
+	/// Fetches logs of the next chunk of range `[from, to]`, starting at `chunk_from`.
+	/// Logs of the blocks after the subscribed one are taken from the notifications.
+	fn fetch_logs(&mut self, from: u64, to: u64, chunk_from: u64, max_blocks: Option<u64>, mut fetched: Vec<Log>) -> LogStreamState<T> {
+		let chunk_to = match max_blocks {
+			Some(max) => cmp::min(to, chunk_from + max - 1),
+			None => to,
+		};
+		let chunk_to = match self.subscribed_after {
+			Some(after) if chunk_from > after => {
+				fetched.extend(self.take_notified(to));
+				return self.next_item(from, to, fetched);
+			},
+			Some(after) => cmp::min(chunk_to, after),
+			None => chunk_to,
+		};
+
+		let filter = self.filter.clone()
+			.from_block(chunk_from.into())
+			.to_block(chunk_to.into())
+			.build();
+
+		LogStreamState::FetchLogs {
+			from,
+			to,
+			chunk_from,
+			chunk_to,
+			max_blocks,
+			logs: fetched,
+			future: logs(self.transport.clone(), &filter).retry(&self.timer, self.logs_request_timeout, &self.retry),
+		}
+	}
+
+	/// Yields logs of range `[from, to]`.
+	fn next_item(&mut self, from: u64, to: u64, logs: Vec<Log>) -> LogStreamState<T> {
+		let item = LogStreamItem {
+			from,
+			to,
+			logs,
+		};
+
+		self.after = to;
+		if !item.logs.is_empty() {
+			self.blocks.logs_found();
+		}
+		self.idle_interval = match self.adaptive_polling {
+			// remaining blocks are fetched without delay
+			Some(_) if self.catching_up => None,
+			Some(ref polling) => idle_interval(polling, self.idle_interval, !item.logs.is_empty()),
+			None => None,
+		};
+		LogStreamState::NextItem(Some(item))
+	}
 }
 
-impl<T: Transport> Stream for LogStream<T> {
+impl<T: BatchTransport + PubSub + Clone> Stream for LogStream<T> {
 	type Item = LogStreamItem;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
 		loop {
+			if !self.poll_notifications() {
+				info!(target: "bridge", "Logs subscription lost, resubscribing");
+				self.notifications = None;
+				self.subscribed_after = None;
+				self.notified.clear();
+				self.resubscribe = true;
+			}
+
 			let next_state = match self.state {
-				LogStreamState::Wait => {
-					let _ = try_stream!(self.interval.poll());
-					LogStreamState::FetchBlockNumber(self.timer.timeout(block_number(&self.transport), self.request_timeout))
-				},
-				LogStreamState::FetchBlockNumber(ref mut future) => {
-					let last_block = try_ready!(future.poll()).low_u64();
-					let last_confirmed_block = last_block.saturating_sub(self.confirmations);
-					if last_confirmed_block > self.after {
-						let from = self.after + 1;
-						let filter = self.filter.clone()
-							.from_block(from.into())
-							.to_block(last_confirmed_block.into())
-							.build();
-						LogStreamState::FetchLogs {
-							from: from,
-							to: last_confirmed_block,
-							future: self.timer.timeout(logs(&self.transport, &filter), self.request_timeout),
-						}
-					} else {
+				LogStreamState::Subscribe(ref mut future) => match future.poll() {
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Ok(Async::Ready(subscription)) => {
+						info!(target: "bridge", "Subscribed to logs");
+						self.notifications = Some(self.transport.transport().notifications(&subscription));
+						// logs of the blocks mined while subscribing are fetched
+						let future = block_number(self.transport.transport().clone()).retry(&self.timer, self.logs_request_timeout, &self.retry);
+						LogStreamState::FetchSubscribedBlock(future)
+					},
+					Err(err) => {
+						warn!(target: "bridge", "Cannot subscribe to logs, fetching them instead: {}", err);
 						LogStreamState::Wait
+					},
+				},
+				LogStreamState::FetchSubscribedBlock(ref mut future) => {
+					let number = try_ready!(future.poll()).low_u64();
+					// the subscription might have been lost in the meantime
+					if self.notifications.is_some() {
+						self.subscribed_after = Some(number);
+						self.notified.retain(|log| log.block_number.map_or(false, |block| block.low_u64() > number));
 					}
+					LogStreamState::Wait
 				},
-				LogStreamState::FetchLogs { ref mut future, from, to } => {
-					let logs = try_ready!(future.poll());
-					let item = LogStreamItem {
-						from,
-						to,
-						logs,
+				LogStreamState::Wait if self.resubscribe => {
+					self.resubscribe = false;
+					self.subscribe()
+				},
+				LogStreamState::Wait => {
+					// the stream ends instead of waiting for a new block, once it has fetched the first range
+					if self.once && self.seen_blocks > 0 {
+						return Ok(Async::Ready(None));
+					}
+
+					let block = try_stream!(self.blocks.poll_block(&mut self.seen_blocks));
+					let last_confirmed_block = match block.tagged {
+						Some(tagged) => cmp::min(tagged, block.number.saturating_sub(self.confirmations)),
+						None => block.number.saturating_sub(self.confirmations),
 					};
+					self.last_confirmed = match self.until {
+						Some(until) => cmp::min(until, last_confirmed_block),
+						None => last_confirmed_block,
+					};
+					self.next_range()
+				},
+				LogStreamState::FetchLogs { ref mut future, ref mut logs, from, to, chunk_from, chunk_to, max_blocks } => {
+					let result = future.poll();
+					if let Some(number) = self.transport.take_block_number() {
+						self.blocks.observe(number);
+					}
 
-					self.after = to;
-					LogStreamState::NextItem(Some(item))
+					match result {
+						Ok(Async::Ready(chunk)) => logs.extend(chunk),
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						// the chunk is split in halves until the node is able to return all of its logs
+						Err(ref err) if err.class() == ErrorClass::TooManyResults && chunk_from < chunk_to => {
+							let blocks = chunk_to - chunk_from + 1;
+							let half = blocks / 2;
+							warn!(target: "bridge", "Too many logs in blocks {}-{}, querying at most {} blocks at once", chunk_from, chunk_to, half);
+							let logs = mem::take(logs);
+							self.state = self.fetch_logs(from, to, chunk_from, Some(half), logs);
+							continue;
+						},
+						Err(err) => return Err(err),
+					}
+
+					if self.catching_up {
+						info!(target: "bridge", "Catching up, scanned block {} / {}", format_block(chunk_to), format_block(self.last_confirmed));
+					}
+
+					let logs = mem::take(logs);
+					if chunk_to < to {
+						self.fetch_logs(from, to, chunk_to + 1, max_blocks, logs)
+					} else {
+						self.next_item(from, to, logs)
+					}
+				},
+				LogStreamState::NextItem(ref mut item) => match (item.take(), self.next_interval()) {
+					// the rest of the range is fetched without waiting for a new block
+					(None, _) if self.catching_up || self.once => self.next_range(),
+					(None, Some(interval)) => LogStreamState::Idle(self.timer.sleep(interval)),
+					(None, None) => LogStreamState::Wait,
+					(some, _) => return Ok(some.into()),
 				},
-				LogStreamState::NextItem(ref mut item) => match item.take() {
-					None => LogStreamState::Wait,
-					some => return Ok(some.into()),
+				LogStreamState::Idle(ref mut sleep) => {
+					// blocks mined in the meantime are fetched together after waking up
+					try_ready!(sleep.poll());
+					LogStreamState::Wait
 				},
 			};
 
@@ -191,3 +1316,369 @@ impl<T: Transport> Stream for LogStream<T> {
 		}
 	}
 }
+
+/// Used for `ReceiptConfirmations` initialization.
+pub struct ReceiptConfirmationsInit {
+	pub request_timeout: Duration,
+	pub retry: RetryPolicies,
+	/// Number of blocks, which have to be mined on top of the block with the transaction.
+	pub confirmations: u64,
+}
+
+/// Receipt confirmations state.
+enum ReceiptConfirmationsState<T: Transport> {
+	/// Waiting for new best block.
+	Wait,
+	/// Fetching receipts of the transactions, which are not confirmed yet.
+	FetchReceipts {
+		block: u64,
+		future: JoinAll<Vec<ApiCall<Option<TransactionReceipt>, T>>>,
+	},
+}
+
+/// Transaction, which has been mined, but reverted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RevertedTransaction {
+	pub hash: H256,
+	/// Block in which the transaction has been mined.
+	pub block: u64,
+}
+
+/// Creates new `ReceiptConfirmations` of the transactions with given `hashes`, which checks their receipts with every new block from `blocks`.
+pub fn receipt_confirmations<T: PubSub + Clone>(transport: T, timer: Timer, blocks: BlockWatcher<T>, hashes: Vec<H256>, init: ReceiptConfirmationsInit) -> ReceiptConfirmations<T> {
+	ReceiptConfirmations {
+		transport,
+		timer,
+		blocks,
+		seen_blocks: 0,
+		state: ReceiptConfirmationsState::Wait,
+		pending: hashes,
+		reverted: Vec::new(),
+		request_timeout: init.request_timeout,
+		retry: init.retry,
+		confirmations: init.confirmations,
+	}
+}
+
+/// Resolves once all the transactions are mined and confirmed, to those of them, which have been reverted.
+pub struct ReceiptConfirmations<T: PubSub> {
+	transport: T,
+	timer: Timer,
+	blocks: BlockWatcher<T>,
+	/// Last best block update seen by the future.
+	seen_blocks: usize,
+	state: ReceiptConfirmationsState<T>,
+	/// Transactions, which are not confirmed yet.
+	pending: Vec<H256>,
+	/// Confirmed transactions, which have been reverted.
+	reverted: Vec<RevertedTransaction>,
+	request_timeout: Duration,
+	retry: RetryPolicies,
+	confirmations: u64,
+}
+
+impl<T: PubSub + Clone> Future for ReceiptConfirmations<T> {
+	type Item = Vec<RevertedTransaction>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				ReceiptConfirmationsState::Wait => {
+					if self.pending.is_empty() {
+						return Ok(Async::Ready(mem::take(&mut self.reverted)));
+					}
+
+					let block = match try_ready!(self.blocks.poll_block(&mut self.seen_blocks)) {
+						Some(block) => block,
+						None => return Err("Block watcher has stopped before the transactions were confirmed".into()),
+					};
+					let receipts = self.pending.iter()
+						.map(|hash| transaction_receipt(self.transport.clone(), *hash).retry(&self.timer, self.request_timeout, &self.retry))
+						.collect();
+					ReceiptConfirmationsState::FetchReceipts {
+						block: block.number,
+						future: join_all(receipts),
+					}
+				},
+				ReceiptConfirmationsState::FetchReceipts { block, ref mut future } => {
+					let receipts = try_ready!(future.poll());
+					let mut pending = Vec::new();
+					for (hash, receipt) in self.pending.iter().zip(receipts) {
+						let (mined, status) = match receipt {
+							Some(TransactionReceipt { block_number: Some(number), status, .. }) => (number.low_u64(), status),
+							// not mined yet or pending
+							_ => {
+								pending.push(*hash);
+								continue;
+							},
+						};
+
+						if block < mined + self.confirmations {
+							pending.push(*hash);
+						} else if status == Some(0.into()) {
+							self.reverted.push(RevertedTransaction {
+								hash: *hash,
+								block: mined,
+							});
+						}
+					}
+
+					self.pending = pending;
+					ReceiptConfirmationsState::Wait
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::{Cell, RefCell};
+	use std::rc::Rc;
+	use std::time::Duration;
+	use futures::{self, Future, Poll, Async};
+	use futures::task;
+	use web3::{self, Transport, BatchTransport, RequestId};
+	use web3::types::{U256, FilterBuilder};
+	use config::{RetryPolicy, RetryPolicies};
+	use error::{Error, ErrorKind};
+	use rpc;
+	use config::AdaptivePolling;
+	use super::{block_number, logs, retry_delay, limited, idle_interval, format_block, is_unsupported_tag, WithBlockNumber};
+
+	/// Transport failing first `failures` requests with `error`.
+	struct FlakyTransport {
+		failures: usize,
+		error: web3::Error,
+		requests: Cell<usize>,
+	}
+
+	impl<'a> Transport for &'a FlakyTransport {
+		type Out = web3::Result<rpc::Value>;
+
+		fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+			(0, web3::helpers::build_request(0, method, params))
+		}
+
+		fn send(&self, _id: RequestId, _request: rpc::Call) -> Self::Out {
+			self.requests.set(self.requests.get() + 1);
+			if self.requests.get() <= self.failures {
+				Box::new(futures::failed(self.error.clone()))
+			} else {
+				Box::new(futures::finished(rpc::Value::String("0x10".into())))
+			}
+		}
+	}
+
+	fn connection_reset() -> web3::Error {
+		web3::Error::Transport("connection reset".into())
+	}
+
+	fn policy(max_attempts: u32) -> RetryPolicies {
+		RetryPolicies::new(RetryPolicy {
+			max_attempts,
+			backoff: Duration::from_millis(0),
+			jitter: Duration::from_millis(0),
+		})
+	}
+
+	#[test]
+	fn test_api_call_is_retried_after_failure() {
+		let transport = FlakyTransport { failures: 2, error: connection_reset(), requests: Cell::new(0) };
+		let result = block_number(&transport).retry(&Default::default(), Duration::from_secs(5), &policy(3)).wait().unwrap();
+		assert_eq!(U256::from(0x10), result);
+		assert_eq!(3, transport.requests.get());
+	}
+
+	#[test]
+	fn test_api_call_fails_after_max_attempts() {
+		let transport = FlakyTransport { failures: 3, error: connection_reset(), requests: Cell::new(0) };
+		let result = block_number(&transport).retry(&Default::default(), Duration::from_secs(5), &policy(3)).wait();
+		match result.unwrap_err().kind() {
+			&ErrorKind::Web3(web3::Error::Transport(_)) => {},
+			kind => panic!("unexpected error: {:?}", kind),
+		}
+		assert_eq!(3, transport.requests.get());
+	}
+
+	#[test]
+	fn test_api_call_is_not_retried_after_fatal_error() {
+		let error = web3::Error::Rpc(rpc::Error {
+			code: rpc::ErrorCode::ServerError(-32000),
+			message: "insufficient funds for gas * price + value".into(),
+			data: None,
+		});
+		let transport = FlakyTransport { failures: 1, error, requests: Cell::new(0) };
+		assert!(block_number(&transport).retry(&Default::default(), Duration::from_secs(5), &policy(3)).wait().is_err());
+		assert_eq!(1, transport.requests.get());
+	}
+
+	#[test]
+	fn test_api_call_is_not_retried_by_default() {
+		let transport = FlakyTransport { failures: 1, error: connection_reset(), requests: Cell::new(0) };
+		assert!(block_number(&transport).wait().is_err());
+		assert_eq!(1, transport.requests.get());
+	}
+
+	#[test]
+	fn test_retry_delay_backs_off_exponentially() {
+		let policy = RetryPolicy {
+			max_attempts: 5,
+			backoff: Duration::from_millis(100),
+			jitter: Duration::from_millis(50),
+		};
+
+		for &(failures, min) in &[(1, 100), (2, 200), (3, 400)] {
+			let delay = retry_delay(&policy, failures);
+			assert!(delay >= Duration::from_millis(min), "{:?} is shorter than {}ms", delay, min);
+			assert!(delay <= Duration::from_millis(min + 50), "{:?} is longer than {}ms", delay, min + 50);
+		}
+	}
+
+	/// Transport recording the methods of the requests sent together.
+	#[derive(Default)]
+	struct BatchRecorder {
+		batches: RefCell<Vec<Vec<String>>>,
+	}
+
+	fn method(request: &rpc::Call) -> String {
+		match *request {
+			rpc::Call::MethodCall(ref call) => call.method.clone(),
+			_ => panic!("expected method call"),
+		}
+	}
+
+	fn response(method: &str) -> rpc::Value {
+		match method {
+			"eth_getLogs" => rpc::Value::Array(vec![]),
+			_ => rpc::Value::String("0x10".into()),
+		}
+	}
+
+	impl<'a> Transport for &'a BatchRecorder {
+		type Out = web3::Result<rpc::Value>;
+
+		fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+			(0, web3::helpers::build_request(0, method, params))
+		}
+
+		fn send(&self, _id: RequestId, request: rpc::Call) -> Self::Out {
+			let method = method(&request);
+			let result = response(&method);
+			self.batches.borrow_mut().push(vec![method]);
+			Box::new(futures::finished(result))
+		}
+	}
+
+	impl<'a> BatchTransport for &'a BatchRecorder {
+		type Batch = web3::Result<Vec<Result<rpc::Value, web3::Error>>>;
+
+		fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (RequestId, rpc::Call)> {
+			let methods = requests.into_iter().map(|(_, request)| method(&request)).collect::<Vec<_>>();
+			let results = methods.iter().map(|method| Ok(response(method))).collect();
+			self.batches.borrow_mut().push(methods);
+			Box::new(futures::finished(results))
+		}
+	}
+
+	#[test]
+	fn test_logs_are_fetched_with_block_number_in_single_batch() {
+		let recorder = BatchRecorder::default();
+		let transport = WithBlockNumber::new(&recorder);
+		let fetched = logs(transport.clone(), &FilterBuilder::default().build()).wait().unwrap();
+
+		assert!(fetched.is_empty());
+		assert_eq!(Some(0x10), transport.take_block_number());
+		assert_eq!(None, transport.take_block_number());
+		assert_eq!(vec![vec!["eth_getLogs".to_owned(), "eth_blockNumber".to_owned()]], *recorder.batches.borrow());
+	}
+
+	#[derive(Default)]
+	struct Concurrency {
+		running: Cell<usize>,
+		max: Cell<usize>,
+	}
+
+	/// Future resolving to `value` after being polled twice.
+	struct Call {
+		concurrency: Rc<Concurrency>,
+		value: usize,
+		started: bool,
+	}
+
+	impl Future for Call {
+		type Item = usize;
+		type Error = Error;
+
+		fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+			if self.started {
+				self.concurrency.running.set(self.concurrency.running.get() - 1);
+				return Ok(Async::Ready(self.value));
+			}
+
+			self.started = true;
+			let running = self.concurrency.running.get() + 1;
+			self.concurrency.running.set(running);
+			self.concurrency.max.set(::std::cmp::max(running, self.concurrency.max.get()));
+			task::current().notify();
+			Ok(Async::NotReady)
+		}
+	}
+
+	#[test]
+	fn test_limited_runs_at_most_limit_futures_at_once() {
+		let concurrency = Rc::new(Concurrency::default());
+		let future = limited(concurrency.clone(), (0..10).collect(), 3, |concurrency, value| Call {
+			concurrency: concurrency.clone(),
+			value,
+			started: false,
+		});
+
+		assert_eq!((0..10).collect::<Vec<_>>(), future.wait().unwrap());
+		assert_eq!(3, concurrency.max.get());
+		assert_eq!(0, concurrency.running.get());
+	}
+
+	#[test]
+	fn test_idle_interval_backs_off_until_logs_are_found() {
+		let polling = AdaptivePolling {
+			min_interval: Duration::from_secs(1),
+			max_interval: Duration::from_secs(5),
+		};
+
+		let mut interval = None;
+		let mut intervals = Vec::new();
+		for &has_logs in &[false, false, false, false, true, false] {
+			interval = idle_interval(&polling, interval, has_logs);
+			intervals.push(interval.map(|interval| interval.as_secs()));
+		}
+
+		assert_eq!(vec![Some(1), Some(2), Some(4), Some(5), None, Some(1)], intervals);
+	}
+
+	#[test]
+	fn test_is_unsupported_tag() {
+		let rpc_error = |code, message: &str| Error::from(ErrorKind::Web3(web3::Error::Rpc(rpc::Error {
+			code,
+			message: message.into(),
+			data: None,
+		})));
+
+		assert!(is_unsupported_tag(&rpc_error(rpc::ErrorCode::InvalidParams, "Invalid params: unknown variant `finalized`")));
+		assert!(is_unsupported_tag(&rpc_error(rpc::ErrorCode::ServerError(-32000), "unsupported block tag")));
+		assert!(!is_unsupported_tag(&rpc_error(rpc::ErrorCode::ServerError(-32000), "header not found")));
+		assert!(!is_unsupported_tag(&Error::from(ErrorKind::Timeout("eth_getBlockByNumber"))));
+	}
+
+	#[test]
+	fn test_format_block() {
+		assert_eq!("0", format_block(0));
+		assert_eq!("999", format_block(999));
+		assert_eq!("1,000", format_block(1000));
+		assert_eq!("1,204,000", format_block(1_204_000));
+		assert_eq!("15,600,000", format_block(15_600_000));
+	}
+}
@@ -1,78 +1,184 @@
-use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio_core::reactor::{Handle};
 use tokio_timer::Timer;
 use web3::Transport;
-use web3::transports::ipc::Ipc;
-use error::{Error, ResultExt, ErrorKind};
-use config::Config;
-use contracts::{mainnet, testnet};
+use api::{BlockWatcher, BlockWatcherInit};
+use bridge::{PauseWatcher, GasPriceGuard, Funds, Nonces, SentPayloads};
+use error::{Error, ResultExt};
+use metrics::{Metrics, RequestMetrics};
+use config::{Config, Direction, Node};
+use database::Chain;
+use contracts::{home, foreign, message};
+use transport::{self, NodeTransport, PubSub};
 
-pub struct App<T> where T: Transport {
+pub struct App<T> where T: PubSub {
 	pub config: Config,
-	pub database_path: PathBuf,
 	pub connections: Connections<T>,
-	pub mainnet_bridge: mainnet::EthereumBridge,
-	pub testnet_bridge: testnet::KovanBridge,
+	/// Best block of the home chain, shared by all its streams.
+	pub home_blocks: BlockWatcher<T>,
+	/// Best block of the foreign chain, shared by all its streams.
+	pub foreign_blocks: BlockWatcher<T>,
+	pub home_bridge: home::EthereumBridge,
+	pub foreign_bridge: foreign::KovanBridge,
+	/// Contract passing arbitrary messages, deployed on both chains.
+	pub message_bridge: message::MessageBridge,
+	/// Pause switch, shared by all relay submissions.
+	pub pause: PauseWatcher<T>,
+	/// Gas prices of the chains, shared by all relay submissions.
+	pub gas_prices: GasPriceGuard<T>,
+	/// Balances of the authorities, shared by all relay submissions.
+	pub funds: Funds,
+	/// Nonces of the transactions signed locally, shared by all relay submissions.
+	pub nonces: Nonces,
+	/// Payloads of the relay transactions sent recently, shared by all relay submissions.
+	pub payloads: SentPayloads,
 	pub timer: Timer,
+	/// Statistics of requests sent to the nodes.
+	pub metrics: Metrics,
+	/// Relay transactions are only logged, if it's set.
+	pub dry_run: Option<DryRun>,
+	/// If true, every relay stream checks the blocks confirmed when it's started and ends,
+	/// so that the bridge can be run by a scheduler.
+	pub once: bool,
+	/// Last block checked by the streams, if the past blocks are replayed. The checkpoints are not changed then.
+	pub until: Option<u64>,
+}
+
+/// Mode, in which the bridge runs all streams, but logs the relay transactions instead of sending them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DryRun {
+	/// Whether the relay transactions are simulated with `eth_call` before they are logged.
+	pub simulate: bool,
 }
 
 pub struct Connections<T> where T: Transport {
-	pub mainnet: T,
-	pub testnet: T,
+	/// Used to query the chains.
+	pub home: T,
+	pub foreign: T,
+	/// Used to send transactions and sign messages.
+	pub home_submission: T,
+	pub foreign_submission: T,
 }
 
-impl Connections<Ipc> {
-	pub fn new_ipc<P: AsRef<Path>>(handle: &Handle, mainnet: P, testnet: P) -> Result<Self, Error> {
-		let mainnet = Ipc::with_event_loop(mainnet, handle)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(|| "Cannot connect to mainnet node ipc")?;
-		let testnet = Ipc::with_event_loop(testnet, handle)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(|| "Cannot connect to testnet node ipc")?;
+/// Connects to the submission endpoints of the node, unless it uses the same endpoints for everything.
+fn connect_submission(node: &Node, queries: &Arc<NodeTransport>, handle: &Handle, timer: &Timer, metrics: Arc<RequestMetrics>) -> Result<Arc<NodeTransport>, Error> {
+	if node.submission_endpoints.is_empty() {
+		return Ok(queries.clone());
+	}
+
+	transport::connect(node, &node.submission_endpoints, handle, timer, metrics).map(Arc::new)
+}
+
+impl Connections<Arc<NodeTransport>> {
+	pub fn new(handle: &Handle, timer: &Timer, config: &Config, metrics: &Metrics) -> Result<Self, Error> {
+		let connect = |chain: Chain| -> Result<(Arc<NodeTransport>, Arc<NodeTransport>), Error> {
+			let node = config.node(chain);
+			let metrics = match chain {
+				Chain::Mainnet => metrics.mainnet.clone(),
+				Chain::Testnet => metrics.testnet.clone(),
+			};
+			let queries = transport::connect(node, &node.endpoints, handle, timer, metrics.clone())
+				.map(Arc::new)
+				.chain_err(|| format!("Cannot connect to {} node", chain.as_str()))?;
+			let submission = connect_submission(node, &queries, handle, timer, metrics)
+				.chain_err(|| format!("Cannot connect to {} submission node", chain.as_str()))?;
+			Ok((queries, submission))
+		};
+		let (home, home_submission) = connect(config.direction.home())?;
+		let (foreign, foreign_submission) = connect(config.direction.foreign())?;
 
 		let result = Connections {
-			mainnet,
-			testnet,
+			home,
+			foreign,
+			home_submission,
+			foreign_submission,
 		};
 		Ok(result)
 	}
 }
 
+impl<T: PubSub> App<T> {
+	pub fn node(&self, chain: Chain) -> &Node {
+		self.config.node(chain)
+	}
+
+	/// Transport used to query `chain`.
+	pub fn connection(&self, chain: Chain) -> &T {
+		if chain == self.config.direction.home() {
+			&self.connections.home
+		} else {
+			&self.connections.foreign
+		}
+	}
+
+	/// Transport used to send transactions to `chain`.
+	pub fn submission(&self, chain: Chain) -> &T {
+		if chain == self.config.direction.home() {
+			&self.connections.home_submission
+		} else {
+			&self.connections.foreign_submission
+		}
+	}
+
+	pub fn blocks(&self, chain: Chain) -> &BlockWatcher<T> {
+		if chain == self.config.direction.home() {
+			&self.home_blocks
+		} else {
+			&self.foreign_blocks
+		}
+	}
+}
+
 impl<T: Transport> Connections<T> {
 	pub fn as_ref(&self) -> Connections<&T> {
 		Connections {
-			mainnet: &self.mainnet,
-			testnet: &self.testnet,
+			home: &self.home,
+			foreign: &self.foreign,
+			home_submission: &self.home_submission,
+			foreign_submission: &self.foreign_submission,
 		}
 	}
 }
 
-impl App<Ipc> {
-	pub fn new_ipc<P: AsRef<Path>>(config: Config, database_path: P, handle: &Handle) -> Result<Self, Error> {
-		let connections = Connections::new_ipc(handle, &config.mainnet.ipc, &config.testnet.ipc)?;
+fn block_watcher<T: PubSub + Clone>(transport: T, timer: &Timer, node: &Node) -> BlockWatcher<T> {
+	let init = BlockWatcherInit {
+		request_timeout: node.request_timeout,
+		retry: node.retry.clone(),
+		poll_interval: node.poll_interval,
+		adaptive_polling: node.adaptive_polling,
+		block_tag: node.block_tag,
+	};
+	BlockWatcher::new(transport, timer.clone(), init)
+}
+
+impl App<Arc<NodeTransport>> {
+	pub fn new(config: Config, handle: &Handle) -> Result<Self, Error> {
+		let timer = Timer::default();
+		let metrics = Metrics::default();
+		let connections = Connections::new(handle, &timer, &config, &metrics)?;
+		let (mainnet, testnet) = match config.direction {
+			Direction::MainnetToTestnet => (connections.home.clone(), connections.foreign.clone()),
+			Direction::TestnetToMainnet => (connections.foreign.clone(), connections.home.clone()),
+		};
 		let result = App {
+			pause: PauseWatcher::new(&config, mainnet.clone(), testnet.clone(), timer.clone()),
+			gas_prices: GasPriceGuard::new(&config, mainnet, testnet, timer.clone(), &metrics),
+			funds: Funds::new(&config),
+			nonces: Nonces::default(),
+			payloads: SentPayloads::new(&config),
+			home_blocks: block_watcher(connections.home.clone(), &timer, &config.home),
+			foreign_blocks: block_watcher(connections.foreign.clone(), &timer, &config.foreign),
 			config,
-			database_path: database_path.as_ref().to_path_buf(),
 			connections,
-			mainnet_bridge: mainnet::EthereumBridge::default(),
-			testnet_bridge: testnet::KovanBridge::default(),
-			timer: Timer::default(),
+			home_bridge: home::EthereumBridge::default(),
+			foreign_bridge: foreign::KovanBridge::default(),
+			message_bridge: message::MessageBridge::default(),
+			timer,
+			metrics,
+			dry_run: None,
+			once: false,
+			until: None,
 		};
 		Ok(result)
 	}
 }
-
-impl<T: Transport> App<T> {
-	pub fn as_ref(&self) -> App<&T> {
-		App {
-			config: self.config.clone(),
-			connections: self.connections.as_ref(),
-			database_path: self.database_path.clone(),
-			mainnet_bridge: mainnet::EthereumBridge::default(),
-			testnet_bridge: testnet::KovanBridge::default(),
-			timer: self.timer.clone(),
-		}
-	}
-}
@@ -0,0 +1,344 @@
+use std::cell::Cell;
+use std::cmp;
+use std::sync::Arc;
+use std::time::Duration;
+use std::vec;
+use futures::{Future, Stream, Poll, Async};
+use futures::future::{Join, JoinAll, join_all};
+use tokio_timer::Sleep;
+use web3::types::{Address, U256};
+use api::{self, ApiCall};
+use app::App;
+use config::{Config, TransactionConfig};
+use database::{Chain, JournalEntry};
+use error::Error;
+use transport::PubSub;
+
+/// Converts `value` to `u128`, saturating at `u128::MAX`.
+pub fn saturating_u128(value: U256) -> u128 {
+	if value.0[..16].iter().any(|byte| *byte != 0) {
+		return u128::MAX;
+	}
+	value.0[16..].iter().fold(0, |result, byte| result << 8 | u128::from(*byte))
+}
+
+/// Relay transactions, which are submitted to `chain` by the enabled streams.
+fn relay_transactions(config: &Config, chain: Chain) -> Vec<TransactionConfig> {
+	let streams = &config.streams;
+	let home = config.direction.home();
+	let mut txs = Vec::new();
+	if chain == home && streams.withdraw_relay {
+		txs.push(config.txs.withdraw_relay.clone());
+	}
+	if chain != home && streams.deposit_relay {
+		txs.push(config.txs.deposit_relay.clone());
+	}
+	if chain != home && streams.withdraw_confirm {
+		txs.push(config.txs.withdraw_confirm.clone());
+	}
+	// messages are relayed to the other chain
+	let messages = match chain {
+		Chain::Mainnet => streams.testnet_message_relay,
+		Chain::Testnet => streams.mainnet_message_relay,
+	};
+	if messages && config.message_bridge.is_some() {
+		txs.push(config.txs.message_relay.clone());
+	}
+	txs
+}
+
+/// Returns the highest cost of any of the relay transactions, which is paid at `gas_price` unless they are configured with a higher one.
+fn relay_cost(txs: &[TransactionConfig], gas_price: u128) -> u128 {
+	txs.iter()
+		.map(|tx| u128::from(tx.gas).saturating_mul(cmp::max(u128::from(tx.gas_price), gas_price)))
+		.max()
+		.unwrap_or(0)
+}
+
+#[derive(Default)]
+struct ChainFunds {
+	/// Whether the balance is sufficient, `None` until checked.
+	sufficient: Cell<Option<bool>>,
+	/// Last checked balance, less the worst-case cost of the transactions submitted since, in wei.
+	available: Cell<u128>,
+}
+
+/// Whether the authority accounts can pay for the relay transactions, as last checked by the `BalanceMonitor`.
+///
+/// Transactions are not submitted to a chain until its balance has been checked and found sufficient.
+/// Submissions of several transactions at once reserve their worst-case cost from the balance first,
+/// so that a burst exceeding the balance doesn't end up in a cascade of failed transactions.
+pub struct Funds {
+	/// `None` if the balances are not monitored.
+	chains: Option<[ChainFunds; 2]>,
+}
+
+impl Funds {
+	pub fn new(config: &Config) -> Self {
+		Funds {
+			chains: config.min_balance.map(|_| Default::default()),
+		}
+	}
+
+	fn get(&self, chain: Chain) -> Option<&ChainFunds> {
+		self.chains.as_ref().map(|chains| match chain {
+			Chain::Mainnet => &chains[0],
+			Chain::Testnet => &chains[1],
+		})
+	}
+
+	/// Records the checked `balance` on `chain` and whether it's `sufficient`, returning the previous state.
+	fn update(&self, chain: Chain, balance: u128, sufficient: bool) -> Option<bool> {
+		self.get(chain).and_then(|funds| {
+			funds.available.set(balance);
+			funds.sufficient.replace(Some(sufficient))
+		})
+	}
+
+	/// Reserves the balance on `chain` for the transactions with worst-case `costs`, in order,
+	/// returning the number of them it covers. If it doesn't cover all of them, the balance is
+	/// considered insufficient until it's checked again.
+	pub fn reserve(&self, chain: Chain, costs: &[u128]) -> usize {
+		let funds = match self.get(chain) {
+			Some(funds) => funds,
+			None => return costs.len(),
+		};
+
+		let mut available = funds.available.get();
+		let mut affordable = 0;
+		for cost in costs {
+			if *cost > available {
+				funds.sufficient.set(Some(false));
+				break;
+			}
+			available -= cost;
+			affordable += 1;
+		}
+		funds.available.set(available);
+		affordable
+	}
+
+	/// Splits off the relay `entries` to `chain`, which the balance doesn't cover at their journaled gas prices,
+	/// reserving it for the rest.
+	pub fn split_unaffordable(&self, chain: Chain, entries: &mut Vec<JournalEntry>) -> Vec<JournalEntry> {
+		let costs: Vec<_> = entries.iter()
+			.map(|entry| u128::from(entry.gas).saturating_mul(u128::from(entry.gas_price)))
+			.collect();
+		let affordable = self.reserve(chain, &costs);
+		entries.split_off(affordable)
+	}
+
+	/// Resolves if the authority can pay for the transactions submitted to `chain`.
+	///
+	/// The submissions are woken up by the `BalanceMonitor`, which is polled by the same task before them.
+	pub fn poll_sufficient(&self, chain: Chain) -> Async<()> {
+		match self.get(chain).map(|funds| funds.sufficient.get()) {
+			None | Some(Some(true)) => Async::Ready(()),
+			Some(_) => Async::NotReady,
+		}
+	}
+}
+
+/// Balance of the authority account on a chain.
+#[derive(Debug, PartialEq)]
+pub struct AccountBalance {
+	pub chain: Chain,
+	pub account: Address,
+	/// Wei.
+	pub balance: u128,
+	/// Cost of `min_balance.relays` relay transactions at the current gas price, in wei.
+	pub required: u128,
+}
+
+impl AccountBalance {
+	pub fn is_sufficient(&self) -> bool {
+		self.balance >= self.required
+	}
+}
+
+/// Balances of the authority accounts and gas prices of the chains.
+type BalanceCheck<T> = JoinAll<Vec<Join<ApiCall<U256, T>, ApiCall<U256, T>>>>;
+
+/// Fetches the balance of the authority account and the gas price of every chain.
+fn check_balances<T: PubSub + Clone>(app: &App<T>, chains: &[(Chain, Vec<TransactionConfig>)]) -> BalanceCheck<T> {
+	let calls = chains.iter()
+		.map(|&(chain, _)| {
+			let node = app.node(chain);
+			let transport = app.connection(chain);
+			let balance = api::balance(transport.clone(), node.account)
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			let gas_price = api::gas_price(transport.clone())
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			balance.join(gas_price)
+		})
+		.collect();
+	join_all(calls)
+}
+
+enum BalanceState<T: PubSub> {
+	/// Fetching the balances and gas prices of the chains.
+	Check(BalanceCheck<T>),
+	/// Yielding the checked balances.
+	Checked(vec::IntoIter<AccountBalance>),
+	/// Waiting before the balances are checked again.
+	Wait(Sleep),
+}
+
+/// Creates monitor of the balances of the authority accounts on the chains, which relay transactions are submitted to.
+pub fn create_balance_monitor<T: PubSub + Clone>(app: Arc<App<T>>) -> BalanceMonitor<T> {
+	let min_balance = app.config.min_balance.expect("balance monitor is created only if `min_balance` is configured; qed");
+	let chains = [Chain::Mainnet, Chain::Testnet].iter()
+		.map(|chain| (*chain, relay_transactions(&app.config, *chain)))
+		.filter(|(_, txs)| !txs.is_empty())
+		.collect::<Vec<_>>();
+	BalanceMonitor {
+		relays: min_balance.relays,
+		poll_interval: min_balance.poll_interval,
+		state: BalanceState::Check(check_balances(&app, &chains)),
+		chains,
+		app,
+	}
+}
+
+/// Checks the balances of the authority accounts every `min_balance.poll_interval`, yielding them once checked.
+///
+/// Relay transactions to a chain are suspended while the balance cannot cover `min_balance.relays` of them,
+/// which is reported again after every check.
+pub struct BalanceMonitor<T: PubSub> {
+	app: Arc<App<T>>,
+	relays: u64,
+	poll_interval: Duration,
+	/// Chains, which balances are checked, with the relay transactions submitted to them.
+	chains: Vec<(Chain, Vec<TransactionConfig>)>,
+	state: BalanceState<T>,
+}
+
+impl<T: PubSub + Clone> Stream for BalanceMonitor<T> {
+	type Item = AccountBalance;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				BalanceState::Check(ref mut future) => {
+					let results = try_ready!(future.poll());
+					let balances = self.chains.iter().zip(results)
+						.map(|(&(chain, ref txs), (balance, gas_price))| AccountBalance {
+							chain,
+							account: self.app.node(chain).account,
+							balance: saturating_u128(balance),
+							required: relay_cost(txs, saturating_u128(gas_price)).saturating_mul(u128::from(self.relays)),
+						})
+						.collect::<Vec<_>>();
+
+					for balance in &balances {
+						let sufficient = balance.is_sufficient();
+						let previous = self.app.funds.update(balance.chain, balance.balance, sufficient);
+						if !sufficient {
+							error!(target: "bridge", "Balance of {} account {:?} is {} wei, but {} relays require {} wei, suspending relays to {}", balance.chain.as_str(), balance.account, balance.balance, self.relays, balance.required, balance.chain.as_str());
+						} else if previous == Some(false) {
+							info!(target: "bridge", "Balance of {} account {:?} is sufficient again, resuming relays", balance.chain.as_str(), balance.account);
+						}
+					}
+					BalanceState::Checked(balances.into_iter())
+				},
+				BalanceState::Checked(ref mut balances) => match balances.next() {
+					Some(balance) => return Ok(Async::Ready(Some(balance))),
+					None => BalanceState::Wait(self.app.timer.sleep(self.poll_interval)),
+				},
+				BalanceState::Wait(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					BalanceState::Check(check_balances(&self.app, &self.chains))
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::Async;
+	use web3::types::U256;
+	use config::TransactionConfig;
+	use database::{Chain, JournalEntry, ProcessedEvent};
+	use super::{Funds, saturating_u128, relay_cost};
+
+	#[test]
+	fn test_saturating_u128() {
+		assert_eq!(0, saturating_u128(0.into()));
+		assert_eq!(0x1234, saturating_u128(0x1234.into()));
+		let mut value = U256::from(u64::MAX);
+		value.0[16] = 1;
+		assert_eq!(1 << 120 | u128::from(u64::MAX), saturating_u128(value));
+		value.0[15] = 1;
+		assert_eq!(u128::MAX, saturating_u128(value));
+	}
+
+	#[test]
+	fn test_relay_cost() {
+		let tx = |gas, gas_price| TransactionConfig {
+			gas,
+			gas_price,
+			..Default::default()
+		};
+
+		assert_eq!(0, relay_cost(&[], 10));
+		// configured gas price is used if it's higher than the current one
+		assert_eq!(200, relay_cost(&[tx(10, 20), tx(5, 1)], 10));
+		assert_eq!(300, relay_cost(&[tx(10, 20), tx(5, 60)], 10));
+		assert_eq!(u128::MAX, relay_cost(&[tx(u64::MAX, 0)], u128::MAX));
+	}
+
+	#[test]
+	fn test_funds_reserve() {
+		let funds = Funds {
+			chains: Some(Default::default()),
+		};
+		funds.update(Chain::Mainnet, 100, true);
+		assert_eq!(2, funds.reserve(Chain::Mainnet, &[30, 30]));
+		// only the transactions covered by the rest of the balance are submitted
+		assert_eq!(1, funds.reserve(Chain::Mainnet, &[30, 30, 10]));
+		assert_eq!(Async::NotReady, funds.poll_sufficient(Chain::Mainnet));
+		assert_eq!(0, funds.reserve(Chain::Mainnet, &[30]));
+		// the balance is available again once it's checked
+		funds.update(Chain::Mainnet, 100, true);
+		assert_eq!(Async::Ready(()), funds.poll_sufficient(Chain::Mainnet));
+		assert_eq!(1, funds.reserve(Chain::Mainnet, &[100]));
+
+		// balances, which are not monitored, cover any transactions
+		let funds = Funds {
+			chains: None,
+		};
+		assert_eq!(3, funds.reserve(Chain::Testnet, &[u128::MAX, u128::MAX, u128::MAX]));
+	}
+
+	#[test]
+	fn test_funds_split_unaffordable() {
+		let entry = |log_index| JournalEntry {
+			chain: Chain::Mainnet,
+			to: 2.into(),
+			data: vec![].into(),
+			gas: 10,
+			gas_price: 3,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: 1.into(),
+				log_index,
+			},
+		};
+
+		let funds = Funds {
+			chains: Some(Default::default()),
+		};
+		funds.update(Chain::Mainnet, 70, true);
+		let mut entries = vec![entry(0), entry(1), entry(2)];
+		assert_eq!(vec![entry(2)], funds.split_unaffordable(Chain::Mainnet, &mut entries));
+		assert_eq!(vec![entry(0), entry(1)], entries);
+
+		// every entry is held, if the balance doesn't cover the first one
+		let mut entries = vec![entry(3), entry(4)];
+		assert_eq!(vec![entry(3), entry(4)], funds.split_unaffordable(Chain::Mainnet, &mut entries));
+		assert!(entries.is_empty());
+	}
+}
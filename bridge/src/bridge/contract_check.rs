@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use futures::{Future, Poll};
+use futures::future::{Join3, JoinAll, join_all};
+use web3::types::{Address, Bytes, U256};
+use api::{self, ApiCall};
+use app::App;
+use config::Authorities;
+use database::{Database, Chain};
+use error::Error;
+use transport::PubSub;
+use super::deploy::verify_code;
+use super::withdraw_relay::{ContractAuthorities, contract_authorities, required_signatures};
+
+/// Bridge contract deployed to a chain, compared with the configuration.
+#[derive(Debug, PartialEq)]
+pub struct ContractCheck {
+	pub chain: Chain,
+	pub address: Address,
+	/// Differences between the deployed and the configured contract, empty if they match.
+	pub problems: Vec<String>,
+}
+
+/// Compares the `code`, the number of `required` signatures and the `authorities` of the contract deployed to `chain`
+/// with the contract created by `bin` with the configured `expected` authorities.
+fn check_contract(chain: Chain, bin: &[u8], code: &[u8], required: u32, authorities: &[Address], expected: &Authorities) -> Vec<String> {
+	let mut problems = Vec::new();
+	if let Err(err) = verify_code(chain, bin, code) {
+		problems.push(err.to_string());
+	}
+	if required != expected.required_signatures {
+		problems.push(format!("Contract requires {} signatures, but `required_signatures` is {}", required, expected.required_signatures));
+	}
+	for authority in authorities.iter().filter(|authority| !expected.accounts.contains(authority)) {
+		problems.push(format!("Authority {:?} of the contract is not configured", authority));
+	}
+	for authority in expected.accounts.iter().filter(|authority| !authorities.contains(authority)) {
+		problems.push(format!("Configured authority {:?} is not an authority of the contract", authority));
+	}
+	problems
+}
+
+/// Code, required signatures and authorities of the contract on a chain.
+type ContractQuery<T> = JoinAll<Vec<Join3<ApiCall<Bytes, T>, ApiCall<Bytes, T>, ContractAuthorities<T>>>>;
+
+/// Checks the contracts recorded in `database` against the contracts and authorities configured for the chains.
+pub fn verify_contracts<T: PubSub + Clone>(app: Arc<App<T>>, database: &Database) -> VerifyContracts<T> {
+	let direction = app.config.direction;
+	let chains = [Chain::Mainnet, Chain::Testnet].iter()
+		.map(|&chain| {
+			let node = app.node(chain);
+			let contract = database.contract_address(direction, chain);
+			let code = api::code(app.connection(chain).clone(), contract)
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			code.join3(required_signatures(&app, chain, contract), contract_authorities(&app, chain, contract))
+		})
+		.collect();
+
+	VerifyContracts {
+		addresses: [database.contract_address(direction, Chain::Mainnet), database.contract_address(direction, Chain::Testnet)],
+		query: join_all(chains),
+		app,
+	}
+}
+
+/// Queries the contracts and resolves to the results of their checks.
+pub struct VerifyContracts<T: PubSub> {
+	app: Arc<App<T>>,
+	addresses: [Address; 2],
+	query: ContractQuery<T>,
+}
+
+impl<T: PubSub + Clone> Future for VerifyContracts<T> {
+	type Item = Vec<ContractCheck>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let results = try_ready!(self.query.poll());
+		let mut checks = Vec::new();
+		for ((&chain, &address), (code, required, authorities)) in [Chain::Mainnet, Chain::Testnet].iter().zip(&self.addresses).zip(results) {
+			let required = self.app.foreign_bridge.functions().required_signatures().output(&required.0)?;
+			let required = U256::from(&required[..]).low_u64() as u32;
+			let bin = &self.app.node(chain).contract.bin.0;
+			checks.push(ContractCheck {
+				chain,
+				address,
+				problems: check_contract(chain, bin, &code.0, required, &authorities, &self.app.config.authorities),
+			});
+		}
+		Ok(checks.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::Address;
+	use config::{Authorities, WithdrawRelayAssignment};
+	use database::Chain;
+	use super::check_contract;
+
+	#[test]
+	fn test_check_contract() {
+		let expected = Authorities {
+			accounts: vec![1.into(), 2.into()],
+			required_signatures: 2,
+			withdraw_relay_assignment: WithdrawRelayAssignment::Contract,
+			withdraw_relay_fallback: None,
+		};
+		let bin = [0x60, 0x80, 0x01, 0x02];
+		let code = [0x01, 0x02];
+
+		assert!(check_contract(Chain::Mainnet, &bin, &code, 2, &[2.into(), 1.into()], &expected).is_empty());
+		assert_eq!(1, check_contract(Chain::Mainnet, &bin, &[0x03], 2, &[1.into(), 2.into()], &expected).len());
+		assert_eq!(1, check_contract(Chain::Mainnet, &bin, &code, 1, &[1.into(), 2.into()], &expected).len());
+		let problems = check_contract(Chain::Testnet, &bin, &code, 2, &[1.into(), 3.into()], &expected);
+		assert_eq!(vec![
+			format!("Authority {:?} of the contract is not configured", Address::from(3)),
+			format!("Configured authority {:?} is not an authority of the contract", Address::from(2)),
+		], problems);
+	}
+}
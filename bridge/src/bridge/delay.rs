@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use web3::types::{Address, U256};
+use database::{Database, ProcessedEvent, JournalEntry, HeldRelay, Chain};
+
+/// Relays of large transfers, which are held for `relay_delay.delay` before they are submitted,
+/// so that the operators have time to intervene, e.g. by pausing the bridge.
+///
+/// Held relays are persisted, so that they are released after restart too. They are released
+/// together with the next logs, which are checked after their release time.
+pub struct DelayedRelays {
+	/// Transfers of at least this value are held, none are if `None`.
+	min_value: Option<U256>,
+	/// Seconds.
+	delay: u64,
+	/// Relays, which are held until their release time.
+	held: Vec<HeldRelay>,
+	/// Relays, which have been held, but have not been reported yet.
+	new: Vec<HeldRelay>,
+	/// Events of the released relays, which are being submitted.
+	releasing: Vec<ProcessedEvent>,
+	/// Events of the released relays, which have been journaled or skipped, but have not been reported yet.
+	released: Vec<ProcessedEvent>,
+}
+
+impl DelayedRelays {
+	/// Restores relays from `event_chain` to contract `to` on `chain` held before restart, adding their events to `already_processed`.
+	/// Relays of the events, which are already processed, are released.
+	pub fn new(min_value: Option<U256>, delay: Option<Duration>, init: &Database, event_chain: Chain, chain: Chain, to: Address, already_processed: &mut HashSet<ProcessedEvent>) -> Self {
+		let (released, held): (Vec<HeldRelay>, Vec<HeldRelay>) = init.held_relays.iter()
+			.filter(|relay| relay.entry.event.chain == event_chain && relay.entry.chain == chain && relay.entry.to == to)
+			.cloned()
+			.partition(|relay| already_processed.contains(&relay.entry.event));
+		already_processed.extend(held.iter().map(|relay| relay.entry.event));
+
+		DelayedRelays {
+			min_value,
+			delay: delay.map(|delay| delay.as_secs()).unwrap_or(0),
+			held,
+			new: Vec::new(),
+			releasing: Vec::new(),
+			released: released.into_iter().map(|relay| relay.entry.event).collect(),
+		}
+	}
+
+	/// Holds `entry` transferring `value`, if it's large, at unix time `now`. Otherwise, returns it to be relayed.
+	pub fn hold(&mut self, entry: JournalEntry, value: U256, now: u64) -> Option<JournalEntry> {
+		match self.min_value {
+			Some(min_value) if value >= min_value => {
+				warn!(target: "bridge", "Transfer {:?} of {} is large, holding its relay for {} seconds", entry.event.transaction_hash, value, self.delay);
+				let relay = HeldRelay {
+					release: now + self.delay,
+					entry,
+				};
+				self.new.push(relay.clone());
+				self.held.push(relay);
+				None
+			},
+			_ => Some(entry),
+		}
+	}
+
+	/// Returns relays, which are due at unix time `now`. They are reported as released once they are `submitted`.
+	pub fn release(&mut self, now: u64) -> Vec<JournalEntry> {
+		let (due, held): (Vec<HeldRelay>, Vec<HeldRelay>) = self.held.drain(..).partition(|relay| relay.release <= now);
+		self.held = held;
+		due.into_iter()
+			.map(|relay| {
+				info!(target: "bridge", "Relay of {:?} is released", relay.entry.event.transaction_hash);
+				self.releasing.push(relay.entry.event);
+				relay.entry
+			})
+			.collect()
+	}
+
+	/// Records that the released relays have been journaled or skipped.
+	pub fn submitted(&mut self) {
+		self.released.append(&mut self.releasing);
+	}
+
+	/// Returns relays, which have been held since the previous call.
+	pub fn take_held(&mut self) -> Vec<HeldRelay> {
+		self.new.split_off(0)
+	}
+
+	/// Returns events of the relays, which have been released since the previous call.
+	pub fn take_released(&mut self) -> Vec<ProcessedEvent> {
+		self.released.split_off(0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+	use std::time::Duration;
+	use database::{Database, ProcessedEvent, JournalEntry, HeldRelay, Chain};
+	use super::DelayedRelays;
+
+	fn entry(log_index: u64) -> JournalEntry {
+		JournalEntry {
+			chain: Chain::Testnet,
+			to: 3.into(),
+			data: vec![1, 2, 3].into(),
+			gas: 0,
+			gas_price: 0,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index,
+			},
+		}
+	}
+
+	#[test]
+	fn test_delayed_relays() {
+		let init = Database {
+			held_relays: vec![
+				HeldRelay { release: 100, entry: entry(0) },
+				HeldRelay { release: 200, entry: entry(1) },
+				// relayed before restart
+				HeldRelay { release: 100, entry: entry(2) },
+				// relayed to other contract
+				HeldRelay { release: 100, entry: JournalEntry { to: 4.into(), ..entry(3) } },
+			],
+			..Database::default()
+		};
+		let mut already_processed: HashSet<_> = vec![entry(2).event].into_iter().collect();
+		let mut delayed = DelayedRelays::new(Some(0x100.into()), Some(Duration::from_secs(60)), &init, Chain::Mainnet, Chain::Testnet, 3.into(), &mut already_processed);
+		assert!(already_processed.contains(&entry(0).event));
+		assert!(already_processed.contains(&entry(1).event));
+		assert!(!already_processed.contains(&entry(3).event));
+		assert_eq!(vec![entry(2).event], delayed.take_released());
+
+		assert_eq!(Some(entry(4)), delayed.hold(entry(4), 0xff.into(), 120));
+		assert_eq!(None, delayed.hold(entry(5), 0x100.into(), 120));
+		assert_eq!(vec![HeldRelay { release: 180, entry: entry(5) }], delayed.take_held());
+		assert!(delayed.take_held().is_empty());
+
+		assert_eq!(vec![entry(0)], delayed.release(120));
+		// relays are reported as released only once they are submitted
+		assert!(delayed.take_released().is_empty());
+		delayed.submitted();
+		assert_eq!(vec![entry(0).event], delayed.take_released());
+
+		assert!(delayed.release(179).is_empty());
+		assert_eq!(vec![entry(1), entry(5)], delayed.release(200));
+	}
+
+	#[test]
+	fn test_delayed_relays_disabled() {
+		let mut delayed = DelayedRelays::new(None, None, &Database::default(), Chain::Mainnet, Chain::Testnet, 3.into(), &mut HashSet::new());
+		assert_eq!(Some(entry(0)), delayed.hold(entry(0), 0x100.into(), 120));
+		assert!(delayed.take_held().is_empty());
+	}
+}
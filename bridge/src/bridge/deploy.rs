@@ -1,108 +1,235 @@
 use std::sync::Arc;
 use futures::{Future, Poll, future};
-use web3::Transport;
+use futures::future::{JoinAll, join_all};
 use web3::confirm::SendTransactionWithConfirmation;
-use web3::types::{TransactionRequest};
+use web3::types::{Address, Bytes, TransactionRequest, TransactionReceipt};
+use api::ApiCall;
 use app::App;
-use database::Database;
+use transport::PubSub;
+use config::{Direction, TransactionConfig};
+use database::{Database, Chain};
 use error::{Error, ErrorKind};
 use {api, ethabi};
+use super::BridgeChecked;
+use super::discovery::{DiscoverDeployment, discover_deployment};
+use super::status::checkpoints;
 
 pub enum Deployed {
 	/// No existing database found. Deployed new contracts.
 	New(Database),
 	/// Reusing existing contracts.
 	Existing(Database),
+	/// Reusing existing contracts, with the checkpoints, which have not been set, moved to the deployments
+	/// of the contracts. Deployments, which have not been recorded, have been discovered.
+	Discovered(Database),
 }
 
-enum DeployState<T: Transport + Clone> {
+/// Contract deployed to a chain.
+#[derive(Clone, Copy)]
+struct DeployedContract {
+	address: Address,
+	block: u64,
+}
+
+impl DeployedContract {
+	fn from_receipt(chain: Chain, receipt: &TransactionReceipt) -> Result<Self, Error> {
+		match receipt.contract_address {
+			Some(address) => Ok(DeployedContract {
+				address,
+				block: receipt.block_number.low_u64(),
+			}),
+			None => Err(format!("Deployment {:?} to {} has not created a contract", receipt.transaction_hash, chain.as_str()).into()),
+		}
+	}
+}
+
+/// Checks that the `code` deployed to `chain` is the runtime code of the contract created by `bin`.
+///
+/// The creation code returns the runtime code, which it contains, unless the deployment has failed.
+pub fn verify_code(chain: Chain, bin: &[u8], code: &[u8]) -> Result<(), Error> {
+	if code.is_empty() {
+		return Err(format!("No code has been deployed to {}, the deployment has failed", chain.as_str()).into());
+	}
+	if !bin.windows(code.len()).any(|window| window == code) {
+		return Err(format!("Code deployed to {} doesn't match the configured contract", chain.as_str()).into());
+	}
+	Ok(())
+}
+
+enum DeployState<T: PubSub + Clone> {
 	CheckIfNeeded,
+	/// Discovering the blocks, in which the existing contracts have been deployed to `chains`.
+	Discovering {
+		chains: Vec<Chain>,
+		future: JoinAll<Vec<DiscoverDeployment<T>>>,
+	},
+	/// Waiting for the receipts of the deployments.
 	Deploying(future::Join<SendTransactionWithConfirmation<T>, SendTransactionWithConfirmation<T>>),
+	/// Fetching the code of the deployed contracts.
+	Verifying {
+		mainnet: DeployedContract,
+		testnet: DeployedContract,
+		code: future::Join<ApiCall<Bytes, T>, ApiCall<Bytes, T>>,
+	},
 }
 
-pub fn create_deploy<T: Transport + Clone>(app: Arc<App<T>>) -> Deploy<T> {
+/// Deploys new contracts, unless the `existing` database has been loaded.
+///
+/// Resolves to the new database once both deployments are confirmed and their code is verified,
+/// with the checkpoints set to the blocks of the deployments.
+/// Checkpoints of the existing database, which have not been set, are set to the blocks of the deployments,
+/// which are discovered unless they are recorded, so that the events are not looked for from the first block.
+pub fn create_deploy<T: PubSub + Clone>(app: Arc<App<T>>, existing: Option<Database>) -> Deploy<T> {
 	Deploy {
 		app,
+		existing,
 		state: DeployState::CheckIfNeeded,
 	}
 }
 
-pub struct Deploy<T: Transport + Clone> {
+pub struct Deploy<T: PubSub + Clone> {
 	app: Arc<App<T>>,
+	existing: Option<Database>,
 	state: DeployState<T>,
 }
 
-impl<T: Transport + Clone> Future for Deploy<T> {
+impl<T: PubSub + Clone> Future for Deploy<T> {
 	type Item = Deployed;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
 		loop {
 			let next_state = match self.state {
-				DeployState::CheckIfNeeded => match Database::load(&self.app.database_path).map_err(ErrorKind::from) {
-					Ok(database) => return Ok(Deployed::Existing(database).into()),
-					Err(ErrorKind::MissingFile(_)) => {
-						let main_data = self.app.mainnet_bridge.constructor(
-							self.app.config.mainnet.contract.bin.clone().0,
-							ethabi::util::pad_u32(self.app.config.authorities.required_signatures),
-							self.app.config.authorities.accounts.iter().map(|a| a.0.clone()).collect::<Vec<_>>()
-						);
-						let test_data = self.app.testnet_bridge.constructor(
-							self.app.config.testnet.contract.bin.clone().0,
-							ethabi::util::pad_u32(self.app.config.authorities.required_signatures),
-							self.app.config.authorities.accounts.iter().map(|a| a.0.clone()).collect::<Vec<_>>()
-						);
-
-						let main_tx_request = TransactionRequest {
-							from: self.app.config.mainnet.account,
-							to: None,
-							gas: Some(self.app.config.txs.mainnet_deploy.gas.into()),
-							gas_price: Some(self.app.config.txs.mainnet_deploy.gas_price.into()),
-							value: None,
-							data: Some(main_data.into()),
-							nonce: None,
-							condition: None,
-						};
+				DeployState::CheckIfNeeded => match self.existing.take() {
+					Some(database) => {
+						let checkpoints = checkpoints(&self.app.config, &database);
+						if checkpoints.iter().all(|&(checkpoint, _)| checkpoint.block() != 0) {
+							return Ok(Deployed::Existing(database).into());
+						}
 
-						let test_tx_request = TransactionRequest {
-							from: self.app.config.testnet.account,
-							to: None,
-							gas: Some(self.app.config.txs.testnet_deploy.gas.into()),
-							gas_price: Some(self.app.config.txs.testnet_deploy.gas_price.into()),
-							value: None,
-							data: Some(test_data.into()),
-							nonce: None,
-							condition: None,
-						};
+						// the deployments are discovered only for the chains, which are not checked from them yet
+						let direction = self.app.config.direction;
+						let chains = [Chain::Mainnet, Chain::Testnet].iter().cloned()
+							.filter(|&chain| database.deploy(direction, chain) == 0 && checkpoints.iter().any(|&(checkpoint, checked)| checked == chain && checkpoint.block() == 0))
+							.collect::<Vec<_>>();
+						let discoveries = chains.iter()
+							.map(|&chain| discover_deployment(self.app.clone(), chain, database.contract_address(direction, chain)))
+							.collect::<Vec<_>>();
+						self.existing = Some(database);
+						DeployState::Discovering {
+							chains,
+							future: join_all(discoveries),
+						}
+					},
+					None => {
+						let app = &self.app;
+						let home = app.config.direction.home();
+						// the bins are configured per node, so the home bridge is built from the bin of the home node
+						let deploy = |chain: Chain, tx: &TransactionConfig| {
+							let node = app.node(chain);
+							let bin = node.contract.bin.clone().0;
+							let required_signatures = ethabi::util::pad_u32(app.config.authorities.required_signatures);
+							let authorities = app.config.authorities.accounts.iter().map(|a| a.0.clone()).collect::<Vec<_>>();
+							let data = if chain == home {
+								app.home_bridge.constructor(bin, required_signatures, authorities)
+							} else {
+								app.foreign_bridge.constructor(bin, required_signatures, authorities)
+							};
 
-						let main_future = api::send_transaction_with_confirmation(
-							self.app.connections.mainnet.clone(), 
-							main_tx_request, 
-							self.app.config.mainnet.poll_interval, 
-							self.app.config.mainnet.required_confirmations
-						);
+							let tx_request = TransactionRequest {
+								from: node.account,
+								to: None,
+								gas: Some(tx.gas.into()),
+								gas_price: Some(tx.gas_price.into()),
+								value: None,
+								data: Some(data.into()),
+								nonce: None,
+								condition: None,
+							};
 
-						let test_future = api::send_transaction_with_confirmation(
-							self.app.connections.testnet.clone(), 
-							test_tx_request, 
-							self.app.config.testnet.poll_interval, 
-							self.app.config.testnet.required_confirmations
-						);
+							api::send_transaction_with_confirmation(
+								app.submission(chain).clone(),
+								tx_request,
+								node.poll_interval,
+								node.required_confirmations
+							)
+						};
+
+						let main_future = deploy(Chain::Mainnet, &app.config.txs.mainnet_deploy);
+						let test_future = deploy(Chain::Testnet, &app.config.txs.testnet_deploy);
 
 						DeployState::Deploying(main_future.join(test_future))
 					},
-					Err(err) => return Err(err.into()),
+				},
+				DeployState::Discovering { ref chains, ref mut future } => {
+					let blocks = try_ready!(future.poll());
+					let mut database = self.existing.take().expect("the existing database is kept during the discovery; qed");
+					let direction = self.app.config.direction;
+					for (&chain, block) in chains.iter().zip(blocks) {
+						if chain == direction.home() {
+							database.home_deploy = block;
+						} else {
+							database.foreign_deploy = block;
+						}
+					}
+
+					for (checkpoint, chain) in checkpoints(&self.app.config, &database) {
+						if checkpoint.block() == 0 {
+							let deploy = database.deploy(direction, chain);
+							info!(target: "bridge", "{} has no checkpoint, checking blocks after the deployment to {} in block {}", checkpoint.stream(), chain.as_str(), deploy);
+							database.update_checkpoint(BridgeChecked::from_stream(checkpoint.stream(), deploy).expect("checkpoints of the components are known; qed"));
+						}
+					}
+					return Ok(Deployed::Discovered(database).into());
 				},
 				DeployState::Deploying(ref mut future) => {
 					let (main_receipt, test_receipt) = try_ready!(future.poll().map_err(ErrorKind::Web3));
+					let mainnet = DeployedContract::from_receipt(Chain::Mainnet, &main_receipt)?;
+					let testnet = DeployedContract::from_receipt(Chain::Testnet, &test_receipt)?;
+					info!(target: "bridge", "Deployed contracts to mainnet {:?} and testnet {:?}, verifying their code", mainnet.address, testnet.address);
+
+					let app = &self.app;
+					let code = |chain: Chain, address: Address| {
+						let node = app.node(chain);
+						api::code(app.connection(chain).clone(), address)
+							.retry(&app.timer, node.request_timeouts.call, &node.retry)
+					};
+					DeployState::Verifying {
+						mainnet,
+						testnet,
+						code: code(Chain::Mainnet, mainnet.address).join(code(Chain::Testnet, testnet.address)),
+					}
+				},
+				DeployState::Verifying { mainnet, testnet, ref mut code } => {
+					let (main_code, test_code) = try_ready!(code.poll());
+					verify_code(Chain::Mainnet, &self.app.node(Chain::Mainnet).contract.bin.0, &main_code.0)?;
+					verify_code(Chain::Testnet, &self.app.node(Chain::Testnet).contract.bin.0, &test_code.0)?;
+
+					let (home, foreign) = match self.app.config.direction {
+						Direction::MainnetToTestnet => (mainnet, testnet),
+						Direction::TestnetToMainnet => (testnet, mainnet),
+					};
 					let database = Database {
-						mainnet_contract_address: main_receipt.contract_address.expect("contract creation receipt must have an address; qed"),
-						testnet_contract_address: test_receipt.contract_address.expect("contract creation receipt must have an address; qed"),
-						mainnet_deploy: main_receipt.block_number.low_u64(),
-						testnet_deploy: test_receipt.block_number.low_u64(),
-						checked_deposit_relay: main_receipt.block_number.low_u64(),
-						checked_withdraw_relay: test_receipt.block_number.low_u64(),
-						checked_withdraw_confirm: test_receipt.block_number.low_u64(),
+						home_contract_address: home.address,
+						foreign_contract_address: foreign.address,
+						home_deploy: home.block,
+						foreign_deploy: foreign.block,
+						checked_deposit_relay: home.block,
+						checked_withdraw_relay: foreign.block,
+						checked_withdraw_confirm: foreign.block,
+						checked_mainnet_message_relay: mainnet.block,
+						checked_testnet_message_relay: testnet.block,
+						relays: vec![],
+						journal: vec![],
+						quarantine: vec![],
+						filtered_deposits: vec![],
+						discrepancies: vec![],
+						pending_assignments: vec![],
+						held_relays: vec![],
+						reverted_relays: vec![],
+						relay_fees: vec![],
+						sent_payloads: vec![],
+						checkpoint_changes: vec![],
 					};
 					return Ok(Deployed::New(database).into())
 				},
@@ -112,3 +239,18 @@ impl<T: Transport + Clone> Future for Deploy<T> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use database::Chain;
+	use super::verify_code;
+
+	#[test]
+	fn test_verify_code() {
+		let bin = [0x60, 0x80, 0x01, 0x02, 0x03, 0x00];
+		assert!(verify_code(Chain::Mainnet, &bin, &[0x01, 0x02, 0x03]).is_ok());
+		assert!(verify_code(Chain::Mainnet, &bin, &[]).is_err());
+		assert!(verify_code(Chain::Mainnet, &bin, &[0x01, 0x03]).is_err());
+		assert!(verify_code(Chain::Testnet, &bin, &[0x60, 0x80, 0x01, 0x02, 0x03, 0x00, 0x00]).is_err());
+	}
+}
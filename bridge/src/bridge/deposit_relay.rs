@@ -1,130 +1,313 @@
+use std::cmp;
+use std::collections::HashSet;
+use std::mem;
 use std::sync::Arc;
-use futures::{Future, Stream, Poll};
-use futures::future::{JoinAll, join_all};
-use tokio_timer::Timeout;
-use web3::Transport;
-use web3::types::{TransactionRequest, H256, Address, Bytes, Log, FilterBuilder};
+use futures::{Stream, Poll, Async};
+use web3::BatchTransport;
+use web3::types::{Address, Bytes, H256, U256, Log, FilterBuilder};
 use ethabi::RawLog;
-use api::{LogStream, self, ApiCall};
+use api::{LogStream, self};
+use config::{Config, LogFilter};
 use error::{Error, Result};
-use database::Database;
-use contracts::{mainnet, testnet};
-use util::web3_filter;
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, HeldRelay, RevertedRelay};
+use contracts::{home, foreign, indexed};
+use util::{web3_filter, restrict_topics};
 use app::App;
+use transport::PubSub;
+use super::{Relay, RelaySubmission, BatchProgress, RetryQueue, journaled, parse_logs, unix_time};
+use super::delay::DelayedRelays;
 
-fn deposits_filter(mainnet: &mainnet::EthereumBridge, address: Address) -> FilterBuilder {
-	let filter = mainnet.events().deposit().create_filter();
-	web3_filter(filter, address)
+fn deposits_filter(home_bridge: &home::EthereumBridge, address: Address, log_filter: &LogFilter) -> FilterBuilder {
+	let filter = home_bridge.events().deposit().create_filter();
+	web3_filter(restrict_topics(filter, log_filter), address)
 }
 
-fn deposit_relay_payload(mainnet: &mainnet::EthereumBridge, testnet: &testnet::KovanBridge, log: Log) -> Result<Bytes> {
+/// Returns recipient and value of the deposit.
+fn parse_deposit(home_bridge: &home::EthereumBridge, log: Log) -> Result<(Address, U256)> {
 	let raw_log = RawLog {
 		topics: log.topics.into_iter().map(|t| t.0).collect(),
 		data: log.data.0,
 	};
-	let deposit_log = mainnet.events().deposit().parse_log(raw_log)?;
-	let hash = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
-	let payload = testnet.functions().deposit().input(deposit_log.recipient, deposit_log.value, hash.0);
-	Ok(payload.into())
+	let deposit_log = home_bridge.events().deposit().parse_log(raw_log)?;
+	Ok((Address::from(&deposit_log.recipient[..]), U256::from(&deposit_log.value[..])))
+}
+
+fn deposit_relay_payload(foreign_bridge: &foreign::KovanBridge, recipient: Address, value: U256, hash: H256) -> Bytes {
+	foreign_bridge.functions().deposit().input(recipient.0, value.0, hash.0).into()
+}
+
+/// Payload of contracts telling apart deposits of the same transaction by their `log_index`.
+fn indexed_deposit_relay_payload(recipient: Address, value: U256, hash: H256, log_index: u64) -> Bytes {
+	indexed::IndexedDeposits::default().functions().deposit().input(recipient.0, value.0, hash.0, U256::from(log_index).0).into()
+}
+
+/// Returns journal entry relaying the deposit of `value` to `recipient` emitted by `event` to the `foreign_contract`.
+pub fn deposit_relay_entry(config: &Config, foreign_bridge: &foreign::KovanBridge, foreign_contract: Address, recipient: Address, value: U256, event: ProcessedEvent) -> JournalEntry {
+	let data = if config.deposit_log_index {
+		indexed_deposit_relay_payload(recipient, value, event.transaction_hash, event.log_index)
+	} else {
+		deposit_relay_payload(foreign_bridge, recipient, value, event.transaction_hash)
+	};
+	JournalEntry {
+		chain: config.direction.foreign(),
+		to: foreign_contract,
+		data,
+		gas: config.txs.deposit_relay.gas,
+		gas_price: config.txs.deposit_relay.gas_price,
+		event,
+	}
+}
+
+/// Returns why the deposit of `value` should not be relayed, if it should not.
+fn deposit_filter(min: Option<U256>, max: Option<U256>, value: U256) -> Option<DepositFilter> {
+	match (min, max) {
+		(Some(min), _) if value < min => Some(DepositFilter::BelowMinimum),
+		(_, Some(max)) if value > max => Some(DepositFilter::AboveMaximum),
+		_ => None,
+	}
 }
 
 /// State of deposits relay.
-enum DepositRelayState<T: Transport> {
+enum DepositRelayState<T: PubSub> {
 	/// Deposit relay is waiting for logs.
 	Wait,
-	/// Relaying deposits in progress.
-	RelayDeposits {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
-		block: u64,
+	/// Waiting until the best block is `until`, so that large deposits have enough confirmations.
+	/// The deposits found together with them are held as well, so that the checkpoint does not pass them.
+	Confirm {
+		until: u64,
+		entries: Vec<JournalEntry>,
+		progress: BatchProgress,
 	},
-	/// All deposits till given block has been relayed.
-	Yield(Option<u64>),
+	/// Relaying deposits in progress, yielding the last block, which deposits have all been relayed.
+	/// Deposits from the journal are relayed before any block is checked.
+	RelayDeposits(Box<RelaySubmission<T>>),
 }
 
-pub fn create_deposit_relay<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> DepositRelay<T> {
+pub fn create_deposit_relay<T: BatchTransport + PubSub + Clone>(app: Arc<App<T>>, init: &Database) -> DepositRelay<T> {
+	let (home, foreign) = (app.config.direction.home(), app.config.direction.foreign());
+	let node = app.node(home);
+	let confirmations = app.config.confirmations.deposit_relay.unwrap_or(node.required_confirmations);
 	let logs_init = api::LogStreamInit {
 		after: init.checked_deposit_relay,
-		request_timeout: app.config.mainnet.request_timeout,
-		poll_interval: app.config.mainnet.poll_interval,
-		confirmations: app.config.mainnet.required_confirmations,
-		filter: deposits_filter(&app.mainnet_bridge, init.mainnet_contract_address.clone()),
+		logs_request_timeout: node.request_timeouts.logs,
+		retry: node.retry.clone(),
+		confirmations,
+		max_blocks_per_query: node.max_blocks_per_query,
+		adaptive_polling: node.adaptive_polling,
+		poll_interval: app.config.poll_intervals.deposit_relay,
+		catch_up: node.catch_up,
+		once: app.once,
+		until: app.until,
+		filter: deposits_filter(&app.home_bridge, init.home_contract_address, &app.config.log_filters.deposit_relay),
+	};
+	let replayed = journaled(init, home, foreign, init.foreign_contract_address);
+	let mut already_processed = init.relays.iter().map(|relay| relay.event)
+		.chain(replayed.iter().map(|entry| entry.event))
+		.chain(init.quarantine.iter().map(|log| log.event))
+		.chain(init.filtered_deposits.iter().map(|deposit| deposit.event))
+		.collect();
+	let relay_delay = app.config.relay_delay;
+	let delayed = DelayedRelays::new(relay_delay.and_then(|delay| delay.min_deposit), relay_delay.map(|delay| delay.delay), init, home, foreign, init.foreign_contract_address, &mut already_processed);
+	let state = if replayed.is_empty() {
+		DepositRelayState::Wait
+	} else {
+		DepositRelayState::RelayDeposits(Box::new(RelaySubmission::new(&app, foreign, replayed, true, app.config.txs.deposit_relay.preflight, app.config.txs.deposit_relay.receipt_confirmations, false)))
 	};
 	DepositRelay {
-		logs: api::log_stream(app.connections.mainnet.clone(), app.timer.clone(), logs_init),
-		testnet_contract: init.testnet_contract_address.clone(),
-		state: DepositRelayState::Wait,
+		logs: api::log_stream(app.connection(home).clone(), app.timer.clone(), app.blocks(home).clone(), logs_init),
+		foreign_contract: init.foreign_contract_address,
+		state,
+		already_processed,
+		relays: Vec::new(),
+		journal: Vec::new(),
+		discarded: Vec::new(),
+		reverted: Vec::new(),
+		quarantined: Vec::new(),
+		filtered: Vec::new(),
+		delayed,
+		retries: RetryQueue::new(&app, foreign),
+		confirmations,
+		seen_blocks: 0,
+		best_block: 0,
 		app,
 	}
 }
 
-pub struct DepositRelay<T: Transport> {
+pub struct DepositRelay<T: BatchTransport + PubSub> {
 	app: Arc<App<T>>,
 	logs: LogStream<T>,
 	state: DepositRelayState<T>,
-	testnet_contract: Address,
+	/// Contract to which the deposits are relayed.
+	foreign_contract: Address,
+	/// Events relayed before restart.
+	already_processed: HashSet<ProcessedEvent>,
+	/// Transactions which relayed events, but have not been reported yet.
+	relays: Vec<RelayOutcome>,
+	/// Transactions which are about to be submitted, but have not been reported yet.
+	journal: Vec<JournalEntry>,
+	/// Events from the journal, which have already been relayed, but have not been reported yet.
+	discarded: Vec<ProcessedEvent>,
+	/// Relay transactions, which have been reverted, but have not been reported yet.
+	reverted: Vec<RevertedRelay>,
+	/// Logs, which could not be parsed, but have not been reported yet.
+	quarantined: Vec<QuarantinedLog>,
+	/// Deposits, which have not been relayed because of their value, but have not been reported yet.
+	filtered: Vec<FilteredDeposit>,
+	/// Relays of large deposits, which are held.
+	delayed: DelayedRelays,
+	/// Relays, which have failed and are retried.
+	retries: RetryQueue<T>,
+	/// Confirmations of the logs yielded by `logs`.
+	confirmations: u64,
+	/// Last best block update seen by the relay.
+	seen_blocks: usize,
+	/// Best block, when it has been last seen by the relay.
+	best_block: u64,
 }
 
-impl<T: Transport> Stream for DepositRelay<T> {
+impl<T: BatchTransport + PubSub + Clone> DepositRelay<T> {
+	fn relay(&self, entries: Vec<JournalEntry>, progress: BatchProgress) -> DepositRelayState<T> {
+		let submission = RelaySubmission::new(&self.app, self.app.config.direction.foreign(), entries, false, self.app.config.txs.deposit_relay.preflight, self.app.config.txs.deposit_relay.receipt_confirmations, false);
+		DepositRelayState::RelayDeposits(Box::new(submission.with_progress(progress)))
+	}
+}
+
+impl<T: BatchTransport + PubSub + Clone> Stream for DepositRelay<T> {
 	type Item = u64;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.retries.poll(&mut self.relays, &mut self.journal, &mut self.reverted)?;
 		loop {
 			let next_state = match self.state {
 				DepositRelayState::Wait => {
 					let item = try_stream!(self.logs.poll());
-					let deposits = item.logs
-						.into_iter()
-						.map(|log| deposit_relay_payload(&self.app.mainnet_bridge, &self.app.testnet_bridge, log))
-						.collect::<Result<Vec<_>>>()?
-						.into_iter()
-						.map(|payload| TransactionRequest {
-							from: self.app.config.testnet.account.clone(),
-							to: Some(self.testnet_contract.clone()),
-							gas: Some(self.app.config.txs.deposit_relay.gas.into()),
-							gas_price: Some(self.app.config.txs.deposit_relay.gas_price.into()),
-							value: None,
-							data: Some(payload),
-							nonce: None,
-							condition: None,
-						})
-						.map(|request| {
-							self.app.timer.timeout(
-								api::send_transaction(&self.app.connections.testnet, request),
-								self.app.config.testnet.request_timeout)
-						})
-						.collect::<Vec<_>>();
-
-					DepositRelayState::RelayDeposits {
-						future: join_all(deposits),
-						block: item.to,
+					let app = &self.app;
+					let progress = BatchProgress::new(app.config.direction.home(), &item);
+					let deposits = parse_logs(app.config.direction.home(), item.logs, &self.already_processed, &mut self.quarantined, |log| {
+						let block = log.block_number.map(|block| block.low_u64());
+						parse_deposit(&app.home_bridge, log).map(|deposit| (block, deposit))
+					})?;
+
+					let now = unix_time();
+					let mut entries = Vec::new();
+					// block, after which all of the deposits have enough confirmations
+					let mut confirmed_at = 0;
+					for (event, (block, (recipient, value))) in deposits {
+						match deposit_filter(app.config.min_deposit, app.config.max_deposit, value) {
+							None => {
+								let confirmations = app.config.confirmations.deposit(self.confirmations, value);
+								if let (true, Some(block)) = (confirmations > self.confirmations, block) {
+									info!(target: "bridge", "Deposit {:?} of {} requires {} confirmations, relaying it after block {}", event.transaction_hash, value, confirmations, block + confirmations);
+									confirmed_at = cmp::max(confirmed_at, block + confirmations);
+								}
+								let entry = deposit_relay_entry(&app.config, &app.foreign_bridge, self.foreign_contract, recipient, value, event);
+								entries.extend(self.delayed.hold(entry, value, now));
+							},
+							Some(filter) => {
+								match filter {
+									DepositFilter::BelowMinimum => info!(target: "bridge", "Deposit {:?} of {} is below `min_deposit`, skipping it", event.transaction_hash, value),
+									DepositFilter::AboveMaximum => warn!(target: "bridge", "Deposit {:?} of {} is above `max_deposit`, holding it until it's approved", event.transaction_hash, value),
+								}
+								self.filtered.push(FilteredDeposit {
+									recipient,
+									value,
+									filter,
+									event,
+								});
+							},
+						}
+					}
+
+					// held deposits are relayed with the batch, which is checked after their release
+					entries.extend(self.delayed.release(now));
+
+					if confirmed_at > self.best_block {
+						DepositRelayState::Confirm {
+							until: confirmed_at,
+							entries,
+							progress,
+						}
+					} else {
+						self.relay(entries, progress)
+					}
+				},
+				DepositRelayState::Confirm { until, ref mut entries, ref mut progress } => {
+					if self.best_block < until {
+						let block = try_stream!(self.app.blocks(self.app.config.direction.home()).poll_block(&mut self.seen_blocks));
+						self.best_block = block.number;
+						continue;
 					}
+
+					let entries = mem::take(entries);
+					let progress = mem::take(progress);
+					self.relay(entries, progress)
 				},
-				DepositRelayState::RelayDeposits { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					DepositRelayState::Yield(Some(block))
+				DepositRelayState::RelayDeposits(ref mut submission) => {
+					match try_ready!(submission.poll_checked(&mut self.journal, &mut self.relays, &mut self.discarded, &mut self.reverted, &mut self.retries)) {
+						Some(block) => return Ok(Async::Ready(Some(block))),
+						None => {
+							self.delayed.submitted();
+							DepositRelayState::Wait
+						},
+					}
 				},
-				DepositRelayState::Yield(ref mut block) => match block.take() {
-					None => DepositRelayState::Wait,
-					some => return Ok(some.into()),
-				}
 			};
 			self.state = next_state;
 		}
 	}
 }
 
+impl<T: BatchTransport + PubSub + Clone> Relay for DepositRelay<T> {
+	fn take_relays(&mut self) -> Vec<RelayOutcome> {
+		self.relays.split_off(0)
+	}
+
+	fn take_journal(&mut self) -> Vec<JournalEntry> {
+		self.journal.split_off(0)
+	}
+
+	fn take_discarded(&mut self) -> Vec<ProcessedEvent> {
+		self.discarded.split_off(0)
+	}
+
+	fn take_quarantined(&mut self) -> Vec<QuarantinedLog> {
+		self.quarantined.split_off(0)
+	}
+
+	fn take_reverted(&mut self) -> Vec<RevertedRelay> {
+		self.reverted.split_off(0)
+	}
+
+	fn failed_relays(&self) -> usize {
+		self.retries.len()
+	}
+
+	fn take_filtered_deposits(&mut self) -> Vec<FilteredDeposit> {
+		self.filtered.split_off(0)
+	}
+
+	fn take_held(&mut self) -> Vec<HeldRelay> {
+		self.delayed.take_held()
+	}
+
+	fn take_released(&mut self) -> Vec<ProcessedEvent> {
+		self.delayed.take_released()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use rustc_hex::FromHex;
-	use web3::types::{Log, Bytes};
-	use contracts::{mainnet, testnet};
-	use super::deposit_relay_payload;
+	use web3::types::{Address, Log, Bytes, U256};
+	use contracts::{home, foreign};
+	use database::DepositFilter;
+	use super::{parse_deposit, deposit_relay_payload, indexed_deposit_relay_payload, deposit_filter};
 
 	#[test]
 	fn test_deposit_relay_payload() {
-		let mainnet = mainnet::EthereumBridge::default();
-		let testnet = testnet::KovanBridge::default();
+		let home_bridge = home::EthereumBridge::default();
+		let foreign_bridge = foreign::KovanBridge::default();
 
 		let data = "000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap();
 		let log = Log {
@@ -134,8 +317,27 @@ mod tests {
 			..Default::default()
 		};
 
-		let payload = deposit_relay_payload(&mainnet, &testnet, log).unwrap();
+		let hash = log.transaction_hash.unwrap();
+		let (recipient, value) = parse_deposit(&home_bridge, log).unwrap();
+		assert_eq!("0xaff3454fce5edbc8cca8697c15331677e6ebcccc".parse::<Address>().unwrap(), recipient);
+		assert_eq!(U256::from(0xf0), value);
+		let payload = deposit_relay_payload(&foreign_bridge, recipient, value, hash);
 		let expected: Bytes = "26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".from_hex().unwrap().into();
 		assert_eq!(expected, payload);
+
+		let payload = indexed_deposit_relay_payload(recipient, value, hash, 2);
+		let expected: Bytes = "ec2ac54e000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a94243640000000000000000000000000000000000000000000000000000000000000002".from_hex().unwrap().into();
+		assert_eq!(expected, payload);
+	}
+
+	#[test]
+	fn test_deposit_filter() {
+		let (min, max) = (Some(U256::from(10)), Some(U256::from(20)));
+		assert_eq!(None, deposit_filter(None, None, 0.into()));
+		assert_eq!(Some(DepositFilter::BelowMinimum), deposit_filter(min, max, 9.into()));
+		assert_eq!(None, deposit_filter(min, max, 10.into()));
+		assert_eq!(None, deposit_filter(min, max, 20.into()));
+		assert_eq!(Some(DepositFilter::AboveMaximum), deposit_filter(min, max, 21.into()));
+		assert_eq!(Some(DepositFilter::AboveMaximum), deposit_filter(None, max, 21.into()));
 	}
 }
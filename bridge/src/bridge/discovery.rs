@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use web3::types::{Address, Bytes, U256};
+use api::{self, ApiCall};
+use app::App;
+use database::Chain;
+use error::{Error, ResultExt};
+use transport::PubSub;
+
+/// Binary search for the first block, in which the contract has code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BlockSearch {
+	/// First block, which may be the deployment.
+	low: u64,
+	/// Block, in which the contract is known to have code, or the one after the head until the head is checked.
+	high: u64,
+}
+
+impl BlockSearch {
+	/// Returns the block, which code is fetched next, `None` once the deployment is found.
+	fn next(&self) -> Option<u64> {
+		if self.low < self.high {
+			Some(self.low + (self.high - self.low) / 2)
+		} else {
+			None
+		}
+	}
+
+	/// Narrows the search by the code of the contract at `block`.
+	fn update(&mut self, block: u64, has_code: bool) {
+		if has_code {
+			self.high = block;
+		} else {
+			self.low = block + 1;
+		}
+	}
+}
+
+enum DiscoveryState<T: PubSub> {
+	/// Fetching the head block and the code of the contract at it.
+	Head(ApiCall<U256, T>),
+	/// Fetching the code at the block in the middle of the search.
+	Code {
+		search: BlockSearch,
+		block: u64,
+		future: ApiCall<Bytes, T>,
+	},
+}
+
+/// Creates the discovery of the block, in which `contract` has been deployed to `chain`.
+pub fn discover_deployment<T: PubSub + Clone>(app: Arc<App<T>>, chain: Chain, contract: Address) -> DiscoverDeployment<T> {
+	let node = app.node(chain);
+	let head = api::block_number(app.connection(chain).clone())
+		.retry(&app.timer, node.request_timeouts.call, &node.retry);
+	DiscoverDeployment {
+		state: DiscoveryState::Head(head),
+		app,
+		chain,
+		contract,
+		head: 0,
+	}
+}
+
+/// Finds the deployment block of the contract by binary search over its code in the past blocks,
+/// so that the events are not looked for in the blocks before it.
+///
+/// The node has to keep the state of the past blocks, e.g. it has to be an archive node.
+pub struct DiscoverDeployment<T: PubSub> {
+	app: Arc<App<T>>,
+	chain: Chain,
+	contract: Address,
+	/// Head block of the chain, when the discovery has started.
+	head: u64,
+	state: DiscoveryState<T>,
+}
+
+impl<T: PubSub + Clone> DiscoverDeployment<T> {
+	fn fetch_code(&self, search: BlockSearch, block: u64) -> DiscoveryState<T> {
+		let node = self.app.node(self.chain);
+		let future = api::code_at(self.app.connection(self.chain).clone(), self.contract, block)
+			.retry(&self.app.timer, node.request_timeouts.call, &node.retry);
+		DiscoveryState::Code {
+			search,
+			block,
+			future,
+		}
+	}
+}
+
+impl<T: PubSub + Clone> Future for DiscoverDeployment<T> {
+	type Item = u64;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				DiscoveryState::Head(ref mut future) => {
+					let head = try_ready!(future.poll()).low_u64();
+					self.head = head;
+					info!(target: "bridge", "Discovering the block, in which {} contract {:?} has been deployed", self.chain.as_str(), self.contract);
+					// the head is searched as well, so that a contract without code is reported
+					let search = BlockSearch {
+						low: 0,
+						high: head + 1,
+					};
+					self.fetch_code(search, head)
+				},
+				DiscoveryState::Code { mut search, block, ref mut future } => {
+					let chain = self.chain;
+					let code = try_ready!(future.poll()
+						.chain_err(|| format!("Cannot fetch code at block {} of {}, discovering the deployment requires an archive node", block, chain.as_str())));
+					search.update(block, !code.0.is_empty());
+					match search.next() {
+						Some(block) => self.fetch_code(search, block),
+						None if search.high > self.head => {
+							return Err(format!("{} contract {:?} has no code", chain.as_str(), self.contract).into());
+						},
+						None => {
+							info!(target: "bridge", "{} contract {:?} has been deployed in block {}", chain.as_str(), self.contract, search.high);
+							return Ok(Async::Ready(search.high));
+						},
+					}
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::BlockSearch;
+
+	/// Searches for the deployment in `deploy`, returning the blocks, which code has been fetched.
+	fn search(head: u64, deploy: u64) -> (u64, Vec<u64>) {
+		let mut search = BlockSearch {
+			low: 0,
+			high: head + 1,
+		};
+		let mut fetched = vec![head];
+		search.update(head, head >= deploy);
+		while let Some(block) = search.next() {
+			fetched.push(block);
+			search.update(block, block >= deploy);
+		}
+		(search.high, fetched)
+	}
+
+	#[test]
+	fn test_block_search() {
+		assert_eq!((600, vec![1000, 500, 750, 625, 563, 594, 610, 602, 598, 600, 599]), search(1000, 600));
+		assert_eq!((0, vec![10, 5, 2, 1, 0]), search(10, 0));
+		assert_eq!((10, vec![10, 5, 8, 9]), search(10, 10));
+		// the contract has no code at the head
+		assert_eq!((11, vec![10]), search(10, 11));
+	}
+}
@@ -0,0 +1,264 @@
+use std::mem;
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::Join;
+use tokio_timer::Timeout;
+use web3::types::{Address, Bytes, H256, U256};
+use api::{self, ApiCall, Reverts, TransactionInfo, TransactionReceipt};
+use app::App;
+use database::{JournalEntry, RevertedRelay, Chain};
+use error::Error;
+use signer::Signing;
+use transaction::{Transaction, Fees, FeeModel};
+use transport::PubSub;
+use super::{node, simulate_relay, updated_block, unix_time};
+
+/// Gas of the transfer cancelling the relay transaction.
+const CANCEL_GAS: u64 = 21_000;
+
+/// Builds the transfer of no value from `from` to itself, which replaces the relay transaction with `nonce`,
+/// which has paid at most `gas_price` per gas. Nodes accept the replacement only if it pays at least 10% more.
+fn cancel_request(fee_model: FeeModel, from: Address, nonce: U256, gas_price: u64) -> Transaction {
+	let gas_price = U256::from(gas_price) + U256::from(gas_price / 10 + 1);
+	let fees = match fee_model {
+		FeeModel::Legacy => Fees::Legacy {
+			gas_price,
+		},
+		// the priority fee has to be raised as well
+		FeeModel::Eip1559 { .. } => Fees::Eip1559 {
+			max_fee_per_gas: gas_price,
+			max_priority_fee_per_gas: gas_price,
+		},
+	};
+
+	Transaction {
+		from,
+		to: Some(from),
+		gas: CANCEL_GAS.into(),
+		fees,
+		value: Some(0.into()),
+		data: Bytes::default(),
+		nonce: Some(nonce),
+	}
+}
+
+enum ExpiryState<T: PubSub> {
+	/// Waiting until the transaction expires.
+	Pending,
+	/// Fetching the transaction, once it has expired.
+	Fetch(ApiCall<Option<TransactionInfo>, T>),
+	/// Signing the cancellation locally.
+	Sign(Signing<Bytes>),
+	/// Sending the cancellation.
+	Send(Timeout<ApiCall<H256, T>>),
+	/// Waiting for the transaction or its cancellation to be mined, fetching their receipts with every new block.
+	Cancelled {
+		cancellation: H256,
+		receipts: Option<Join<ApiCall<Option<TransactionReceipt>, T>, ApiCall<Option<TransactionReceipt>, T>>>,
+	},
+	/// Simulating the relay again, once the transaction has been cancelled or dropped.
+	Simulate(Reverts<T>),
+}
+
+struct ExpiringRelay<T: PubSub> {
+	entry: JournalEntry,
+	hash: H256,
+	/// Block after which the transaction expires, known once the first block is seen after the submission.
+	due: Option<u64>,
+	state: ExpiryState<T>,
+}
+
+/// Relay transactions sent to a chain, which are cancelled unless they are mined within `expire_relays_after` blocks,
+/// so that a transaction stuck e.g. because of its gas price doesn't hold back the transactions following it.
+///
+/// Expired transaction is replaced by a transfer of no value with the same nonce, paying a higher gas price.
+/// Once the replacement is mined, or if the node has dropped the transaction, the relay is simulated again,
+/// and it's submitted again only if it would still take effect, e.g. unless another authority has relayed it meanwhile.
+pub struct ExpiringRelays<T: PubSub> {
+	context: (Arc<App<T>>, Chain),
+	blocks: u64,
+	relays: Vec<ExpiringRelay<T>>,
+	/// Last best block update seen by the relays.
+	seen: usize,
+}
+
+impl<T: PubSub + Clone> ExpiringRelays<T> {
+	pub fn new(app: &Arc<App<T>>, chain: Chain, blocks: u64) -> Self {
+		ExpiringRelays {
+			context: (app.clone(), chain),
+			blocks,
+			relays: Vec::new(),
+			seen: 0,
+		}
+	}
+
+	/// Watches the transaction with `hash`, which has relayed `entry`.
+	pub fn watch(&mut self, entry: JournalEntry, hash: H256) {
+		self.relays.push(ExpiringRelay {
+			entry,
+			hash,
+			due: None,
+			state: ExpiryState::Pending,
+		});
+	}
+
+	fn receipt(&self, hash: H256) -> ApiCall<Option<TransactionReceipt>, T> {
+		let (ref app, chain) = self.context;
+		let node = node(&self.context);
+		api::transaction_receipt(app.connection(chain).clone(), hash)
+			.retry(&app.timer, node.request_timeouts.call, &node.retry)
+	}
+
+	/// Sends the cancellation of the relay `transaction`, either signed by the node or locally.
+	fn cancel(&self, relay: &ExpiringRelay<T>, transaction: &TransactionInfo) -> ExpiryState<T> {
+		let (ref app, chain) = self.context;
+		let node = node(&self.context);
+		let request = cancel_request(node.fee_model, transaction.from, transaction.nonce, relay.entry.gas_price);
+		match node.account_signer(transaction.from) {
+			Some(signer) => ExpiryState::Sign(signer.sign_transaction(&app.timer, &request, transaction.nonce)),
+			None => {
+				let future = api::send_transaction(app.submission(chain).clone(), request);
+				ExpiryState::Send(app.timer.timeout(future, node.request_timeouts.send_transaction))
+			},
+		}
+	}
+
+	/// Polls the watched transactions, returning the entries of the expired ones, which should be relayed again,
+	/// with the records of the transactions.
+	pub fn poll(&mut self) -> Result<Vec<(JournalEntry, RevertedRelay)>, Error> {
+		let mut expired = Vec::new();
+		if self.relays.is_empty() {
+			return Ok(expired);
+		}
+
+		let block = updated_block(self.context.0.blocks(self.context.1), &mut self.seen)?;
+		for mut relay in mem::take(&mut self.relays) {
+			let done = loop {
+				let next_state = match relay.state {
+					ExpiryState::Pending => match block {
+						Some(block) if *relay.due.get_or_insert(block + self.blocks) <= block => {
+							let (ref app, chain) = self.context;
+							let node = node(&self.context);
+							ExpiryState::Fetch(api::transaction(app.connection(chain).clone(), relay.hash)
+								.retry(&app.timer, node.request_timeouts.call, &node.retry))
+						},
+						_ => break false,
+					},
+					ExpiryState::Fetch(ref mut future) => match future.poll()? {
+						Async::NotReady => break false,
+						Async::Ready(None) => {
+							warn!(target: "bridge", "Relay of {:?} by {:?} has been dropped by the node, checking it again", relay.entry.event.transaction_hash, relay.hash);
+							ExpiryState::Simulate(simulate_relay(&self.context, relay.entry.clone()))
+						},
+						Async::Ready(Some(ref transaction)) if transaction.block_number.is_some() => break true,
+						Async::Ready(Some(ref transaction)) => {
+							warn!(target: "bridge", "Relay of {:?} by {:?} has not been mined on {} after {} blocks, cancelling it", relay.entry.event.transaction_hash, relay.hash, self.context.1.as_str(), self.blocks);
+							self.cancel(&relay, transaction)
+						},
+					},
+					ExpiryState::Sign(ref mut future) => match future.poll() {
+						Ok(Async::Ready(raw)) => {
+							let (ref app, chain) = self.context;
+							let future = api::send_raw_transaction(app.submission(chain).clone(), raw);
+							ExpiryState::Send(app.timer.timeout(future, node(&self.context).request_timeouts.send_transaction))
+						},
+						Ok(Async::NotReady) => break false,
+						Err(err) => {
+							warn!(target: "bridge", "Cancellation of {:?} could not be signed: {}, trying again with the next block", relay.hash, err);
+							ExpiryState::Pending
+						},
+					},
+					ExpiryState::Send(ref mut future) => match future.poll() {
+						Ok(Async::Ready(cancellation)) => {
+							info!(target: "bridge", "Relay of {:?} by {:?} is cancelled by {:?}", relay.entry.event.transaction_hash, relay.hash, cancellation);
+							ExpiryState::Cancelled {
+								cancellation,
+								receipts: None,
+							}
+						},
+						Ok(Async::NotReady) => break false,
+						Err(err) => {
+							// e.g. the transaction has been mined meanwhile
+							let err: Error = err.into();
+							warn!(target: "bridge", "Cancellation of {:?} could not be sent: {}, trying again with the next block", relay.hash, err);
+							ExpiryState::Pending
+						},
+					},
+					ExpiryState::Cancelled { cancellation, ref mut receipts } => {
+						if receipts.is_none() {
+							if block.is_none() {
+								break false;
+							}
+							*receipts = Some(self.receipt(relay.hash).join(self.receipt(cancellation)));
+						}
+
+						let (transaction, cancelled) = match receipts.as_mut().expect("receipts are fetched above; qed").poll()? {
+							Async::Ready(receipts) => receipts,
+							Async::NotReady => break false,
+						};
+						let mined = |receipt: &Option<TransactionReceipt>| receipt.as_ref().map_or(false, |receipt| receipt.block_number.is_some());
+						if mined(&transaction) {
+							info!(target: "bridge", "Relay of {:?} by {:?} has been mined before its cancellation", relay.entry.event.transaction_hash, relay.hash);
+							break true;
+						}
+						if !mined(&cancelled) {
+							*receipts = None;
+							break false;
+						}
+						ExpiryState::Simulate(simulate_relay(&self.context, relay.entry.clone()))
+					},
+					ExpiryState::Simulate(ref mut future) => {
+						let reverts = match future.poll()? {
+							Async::Ready(reverts) => reverts,
+							Async::NotReady => break false,
+						};
+						if reverts {
+							info!(target: "bridge", "Relay of {:?} has taken effect meanwhile, it's not relayed again", relay.entry.event.transaction_hash);
+						} else {
+							expired.push((relay.entry.clone(), RevertedRelay {
+								chain: self.context.1,
+								transaction_hash: relay.hash,
+								reason: Some(format!("expired after {} blocks", self.blocks)),
+								timestamp: unix_time(),
+								event: relay.entry.event,
+							}));
+						}
+						break true;
+					},
+				};
+				relay.state = next_state;
+			};
+
+			if !done {
+				self.relays.push(relay);
+			}
+		}
+		Ok(expired)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use transaction::{Fees, FeeModel};
+	use super::cancel_request;
+
+	#[test]
+	fn test_cancel_request() {
+		let from = 1.into();
+		let request = cancel_request(FeeModel::Legacy, from, 5.into(), 100);
+		assert_eq!(Some(from), request.to);
+		assert_eq!(Some(5.into()), request.nonce);
+		assert_eq!(Some(0.into()), request.value);
+		assert!(request.data.0.is_empty());
+		assert_eq!(Fees::Legacy { gas_price: 111.into() }, request.fees);
+
+		let fee_model = FeeModel::Eip1559 {
+			max_priority_fee_per_gas: 2,
+		};
+		let expected = Fees::Eip1559 {
+			max_fee_per_gas: 111.into(),
+			max_priority_fee_per_gas: 111.into(),
+		};
+		assert_eq!(expected, cancel_request(fee_model, from, 5.into(), 100).fees);
+	}
+}
@@ -0,0 +1,331 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::Join;
+use web3::types::{Address, Filter, H256, U256, Log};
+use ethabi::RawLog;
+use serde_json;
+use api::{self, ApiCall, Limited};
+use app::App;
+use database::{Database, ProcessedEvent, Chain};
+use error::{Error, ResultExt};
+use transport::PubSub;
+use util::web3_filter;
+use super::node;
+
+/// Kind of the bridge event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+	/// Value deposited to the home contract.
+	Deposit,
+	/// Value withdrawn from the foreign contract, which is signed by the authorities.
+	Withdraw,
+	/// Signatures of a withdraw collected by the foreign contract, once it can be relayed to the home chain.
+	CollectedSignatures,
+	/// Deposit relayed to the foreign contract, once it has been confirmed by the authorities.
+	DepositRelayed,
+	/// Withdraw relayed to the home contract with the collected signatures.
+	WithdrawRelayed,
+}
+
+impl EventKind {
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			EventKind::Deposit => "deposit",
+			EventKind::Withdraw => "withdraw",
+			EventKind::CollectedSignatures => "collected_signatures",
+			EventKind::DepositRelayed => "deposit_relayed",
+			EventKind::WithdrawRelayed => "withdraw_relayed",
+		}
+	}
+
+	/// Describes the state of the transfer, once the event has been emitted.
+	pub fn status(&self) -> &'static str {
+		match *self {
+			EventKind::Deposit => "waiting for the relay to foreign",
+			EventKind::Withdraw => "waiting for the signatures of the authorities",
+			EventKind::CollectedSignatures => "waiting for the relay to home",
+			EventKind::DepositRelayed | EventKind::WithdrawRelayed => "completed",
+		}
+	}
+}
+
+/// Event emitted by a bridge contract, as exported for accounting.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportedEvent {
+	pub kind: EventKind,
+	pub chain: Chain,
+	pub block: u64,
+	pub transaction_hash: H256,
+	pub log_index: u64,
+	/// Recipient of the deposit or withdraw, or the authority, which has submitted the last signature.
+	pub account: Address,
+	/// Value of the deposit or withdraw in wei.
+	pub value: Option<U256>,
+	/// Hash of the withdraw message, which signatures have been collected.
+	pub message_hash: Option<H256>,
+	/// Transaction relaying the event, if it's recorded in the database.
+	pub relay_transaction: Option<H256>,
+}
+
+/// Blocks, which events are exported.
+#[derive(Debug, Clone, Default)]
+pub struct ExportRange {
+	/// First block, the one in which the contract has been deployed by default.
+	pub from: Option<u64>,
+	/// Last block, the head of the chain by default.
+	pub to: Option<u64>,
+	/// Chain, which events are exported, both of them by default.
+	pub chain: Option<Chain>,
+}
+
+/// Splits blocks `[from, to]` into ranges of at most `max_blocks`.
+fn block_ranges(from: u64, to: u64, max_blocks: Option<u64>) -> Vec<(u64, u64)> {
+	let mut ranges = Vec::new();
+	let mut chunk_from = from;
+	while chunk_from <= to {
+		let chunk_to = match max_blocks {
+			Some(max) => cmp::min(to, chunk_from + max - 1),
+			None => to,
+		};
+		ranges.push((chunk_from, chunk_to));
+		chunk_from = chunk_to + 1;
+	}
+	ranges
+}
+
+fn fetch_logs<T: PubSub + Clone>(context: &(Arc<App<T>>, Chain), filter: Filter) -> ApiCall<Vec<Log>, T> {
+	let (ref app, chain) = *context;
+	let node = node(context);
+	api::logs(app.connection(chain).clone(), &filter)
+		.retry(&app.timer, node.request_timeouts.logs, &node.retry)
+}
+
+/// Logs of a chain, fetched one range of blocks after another.
+type ChainLogs<T> = Limited<(Arc<App<T>>, Chain), Filter, ApiCall<Vec<Log>, T>>;
+
+enum ExportState<T: PubSub> {
+	/// Fetching the head blocks of mainnet and testnet.
+	Heads(Join<ApiCall<U256, T>, ApiCall<U256, T>>),
+	/// Fetching the logs of mainnet and testnet.
+	Logs(Join<ChainLogs<T>, ChainLogs<T>>),
+}
+
+/// Creates export of the events emitted by the contracts recorded in `database` within `range`.
+pub fn export_events<T: PubSub + Clone>(app: Arc<App<T>>, database: Database, range: ExportRange) -> ExportEvents<T> {
+	let head = |chain| {
+		let node = app.node(chain);
+		api::block_number(app.connection(chain).clone())
+			.retry(&app.timer, node.request_timeouts.call, &node.retry)
+	};
+	let heads = head(Chain::Mainnet).join(head(Chain::Testnet));
+
+	ExportEvents {
+		state: ExportState::Heads(heads),
+		kinds: [Vec::new(), Vec::new()],
+		app,
+		database,
+		range,
+	}
+}
+
+/// Replays the history of the chains and resolves to the deposits, collected signatures and withdraws,
+/// ordered by their chain and position. Events relayed by this bridge refer to the relay transactions.
+pub struct ExportEvents<T: PubSub> {
+	app: Arc<App<T>>,
+	database: Database,
+	range: ExportRange,
+	/// Kinds of the events fetched by the queries of mainnet and testnet, in the order of the queries.
+	kinds: [Vec<EventKind>; 2],
+	state: ExportState<T>,
+}
+
+impl<T: PubSub + Clone> ExportEvents<T> {
+	/// Starts fetching the events of `chain` up to its `head` block, recording their kinds at `index`.
+	fn fetch_chain(&mut self, index: usize, chain: Chain, head: u64) -> ChainLogs<T> {
+		let mut filters = Vec::new();
+		if self.range.chain.map_or(true, |exported| exported == chain) {
+			let from = self.range.from.unwrap_or_else(|| self.database.deploy(self.app.config.direction, chain));
+			let to = self.range.to.map_or(head, |to| cmp::min(to, head));
+			let contract = self.database.contract_address(self.app.config.direction, chain);
+			let events = if chain == self.app.config.direction.home() {
+				vec![(EventKind::Deposit, self.app.home_bridge.events().deposit().create_filter())]
+			} else {
+				let events = self.app.foreign_bridge.events();
+				vec![
+					(EventKind::Withdraw, events.withdraw().create_filter()),
+					(EventKind::CollectedSignatures, events.collected_signatures().create_filter()),
+				]
+			};
+
+			for (kind, filter) in events {
+				let filter = web3_filter(filter, contract);
+				for (chunk_from, chunk_to) in block_ranges(from, to, self.app.node(chain).max_blocks_per_query) {
+					self.kinds[index].push(kind);
+					filters.push(filter.clone().from_block(chunk_from.into()).to_block(chunk_to.into()).build());
+				}
+			}
+		}
+
+		let limit = self.app.node(chain).max_concurrent_requests;
+		api::limited((self.app.clone(), chain), filters, limit, fetch_logs)
+	}
+}
+
+/// Parses the `log` of an event of `kind` emitted to `chain`, referring to its relay if it's in `relays`.
+pub fn parse_event<T: PubSub>(app: &App<T>, kind: EventKind, chain: Chain, log: Log, relays: &HashMap<ProcessedEvent, H256>) -> Result<ExportedEvent, Error> {
+	let event = ProcessedEvent::from_log(chain, &log)?;
+	// logs of mined transactions are always in a block
+	let block = log.block_number.map_or(0, |block| block.low_u64());
+	let raw_log = RawLog {
+		topics: log.topics.into_iter().map(|t| t.0).collect(),
+		data: log.data.0,
+	};
+	let (account, value, message_hash) = match kind {
+		EventKind::Deposit => {
+			let deposit = app.home_bridge.events().deposit().parse_log(raw_log)?;
+			(Address::from(&deposit.recipient[..]), Some(U256::from(&deposit.value[..])), None)
+		},
+		EventKind::Withdraw => {
+			let withdraw = app.foreign_bridge.events().withdraw().parse_log(raw_log)?;
+			(Address::from(&withdraw.recipient[..]), Some(U256::from(&withdraw.value[..])), None)
+		},
+		EventKind::CollectedSignatures => {
+			let collected = app.foreign_bridge.events().collected_signatures().parse_log(raw_log)?;
+			(Address::from(&collected.authority[..]), None, Some(H256::from(&collected.message_hash[..])))
+		},
+		EventKind::DepositRelayed => {
+			let deposit = app.foreign_bridge.events().deposit().parse_log(raw_log)?;
+			(Address::from(&deposit.recipient[..]), Some(U256::from(&deposit.value[..])), None)
+		},
+		EventKind::WithdrawRelayed => {
+			let withdraw = app.home_bridge.events().withdraw().parse_log(raw_log)?;
+			(Address::from(&withdraw.recipient[..]), Some(U256::from(&withdraw.value[..])), None)
+		},
+	};
+
+	Ok(ExportedEvent {
+		kind,
+		chain,
+		block,
+		transaction_hash: event.transaction_hash,
+		log_index: event.log_index,
+		account,
+		value,
+		message_hash,
+		relay_transaction: relays.get(&event).cloned(),
+	})
+}
+
+impl<T: PubSub + Clone> Future for ExportEvents<T> {
+	type Item = Vec<ExportedEvent>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				ExportState::Heads(ref mut future) => {
+					let (mainnet, testnet) = try_ready!(future.poll());
+					let mainnet = self.fetch_chain(0, Chain::Mainnet, mainnet.low_u64());
+					let testnet = self.fetch_chain(1, Chain::Testnet, testnet.low_u64());
+					ExportState::Logs(mainnet.join(testnet))
+				},
+				ExportState::Logs(ref mut future) => {
+					let (mainnet, testnet) = try_ready!(future.poll());
+					let relays = self.database.relays.iter()
+						.map(|relay| (relay.event, relay.transaction_hash))
+						.collect::<HashMap<_, _>>();
+
+					let mut events = Vec::new();
+					let chains = vec![(Chain::Mainnet, &self.kinds[0], mainnet), (Chain::Testnet, &self.kinds[1], testnet)];
+					for (chain, kinds, results) in chains {
+						for (kind, logs) in kinds.iter().zip(results) {
+							for log in logs {
+								let event = ProcessedEvent::from_log(chain, &log)?;
+								let exported = parse_event(&self.app, *kind, chain, log, &relays)
+									.chain_err(|| format!("Log {} of {:?} cannot be parsed", event.log_index, event.transaction_hash))?;
+								events.push(exported);
+							}
+						}
+					}
+					events.sort_by_key(|event| (event.chain.as_str(), event.block, event.log_index));
+					return Ok(Async::Ready(events));
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+/// Formats the events as CSV with a header, values in wei.
+pub fn events_csv(events: &[ExportedEvent]) -> String {
+	let mut lines = vec!["kind,chain,block,transaction_hash,log_index,account,value,message_hash,relay_transaction".to_owned()];
+	lines.extend(events.iter().map(|event| format!(
+		"{},{},{},{:?},{},{:?},{},{},{}",
+		event.kind.as_str(),
+		event.chain.as_str(),
+		event.block,
+		event.transaction_hash,
+		event.log_index,
+		event.account,
+		event.value.map_or_else(String::new, |value| value.to_string()),
+		event.message_hash.map_or_else(String::new, |hash| format!("{:?}", hash)),
+		event.relay_transaction.map_or_else(String::new, |hash| format!("{:?}", hash)),
+	)));
+	lines.join("\n")
+}
+
+/// Formats the events as JSON array.
+pub fn events_json(events: &[ExportedEvent]) -> String {
+	serde_json::to_string_pretty(events).expect("serialization can't fail; qed")
+}
+
+#[cfg(test)]
+mod tests {
+	use database::Chain;
+	use super::{ExportedEvent, EventKind, block_ranges, events_csv};
+
+	#[test]
+	fn test_block_ranges() {
+		assert_eq!(vec![(10, 20)], block_ranges(10, 20, None));
+		assert_eq!(vec![(10, 14), (15, 19), (20, 20)], block_ranges(10, 20, Some(5)));
+		assert_eq!(vec![(10, 10)], block_ranges(10, 10, Some(5)));
+		// nothing is fetched if the range starts after the head
+		assert!(block_ranges(21, 20, None).is_empty());
+	}
+
+	#[test]
+	fn test_events_csv() {
+		let events = vec![
+			ExportedEvent {
+				kind: EventKind::Deposit,
+				chain: Chain::Mainnet,
+				block: 100,
+				transaction_hash: 1.into(),
+				log_index: 2,
+				account: 3.into(),
+				value: Some(1_000_000_000u64.into()),
+				message_hash: None,
+				relay_transaction: Some(4.into()),
+			},
+			ExportedEvent {
+				kind: EventKind::CollectedSignatures,
+				chain: Chain::Testnet,
+				block: 200,
+				transaction_hash: 5.into(),
+				log_index: 0,
+				account: 6.into(),
+				value: None,
+				message_hash: Some(7.into()),
+				relay_transaction: None,
+			},
+		];
+		let expected = "\
+kind,chain,block,transaction_hash,log_index,account,value,message_hash,relay_transaction
+deposit,mainnet,100,0x0000000000000000000000000000000000000000000000000000000000000001,2,0x0000000000000000000000000000000000000003,1000000000,,0x0000000000000000000000000000000000000000000000000000000000000004
+collected_signatures,testnet,200,0x0000000000000000000000000000000000000000000000000000000000000005,0,0x0000000000000000000000000000000000000006,,0x0000000000000000000000000000000000000000000000000000000000000007,";
+		assert_eq!(expected, events_csv(&events));
+	}
+}
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+use futures::{Future, Stream, Poll, Async};
+use web3::types::U256;
+use api::{self, ApiCall, TransactionReceipt};
+use app::App;
+use transport::PubSub;
+use database::{RelayOutcome, RelayFees, Chain};
+use error::Error;
+use super::updated_block;
+
+/// Number of blocks, after which the receipt of a relay transaction is no longer fetched,
+/// e.g. because the transaction has been dropped.
+const MAX_RECEIPT_BLOCKS: u64 = 100;
+
+/// Relays recorded by the bridge components, which are handed over to the fee accounting.
+#[derive(Default)]
+pub struct Relayed {
+	relays: Vec<(&'static str, RelayOutcome)>,
+}
+
+impl Relayed {
+	/// Relays recorded by the component with given name, e.g. `deposit_relay`.
+	pub fn record(&mut self, stream: &'static str, relays: &[RelayOutcome]) {
+		self.relays.extend(relays.iter().map(|relay| (stream, *relay)));
+	}
+}
+
+struct PendingFees<T: PubSub> {
+	stream: &'static str,
+	relay: RelayOutcome,
+	/// Number of blocks seen since the submission.
+	blocks: u64,
+	/// Receipt of the transaction, while it's fetched.
+	receipt: Option<ApiCall<Option<TransactionReceipt>, T>>,
+}
+
+/// Creates accounting of the fees paid for the relay transactions.
+pub fn create_fee_accounting<T: PubSub + Clone>(app: Arc<App<T>>) -> FeeAccounting<T> {
+	FeeAccounting {
+		app,
+		relayed: Relayed::default(),
+		pending: Vec::new(),
+		mainnet_seen: 0,
+		testnet_seen: 0,
+	}
+}
+
+/// Fetches receipts of the relay transactions with every new block until they are mined,
+/// and yields the fees paid for them, including the reverted ones.
+///
+/// Fees of the transactions, which are not mined when the bridge stops, are not accounted.
+pub struct FeeAccounting<T: PubSub> {
+	app: Arc<App<T>>,
+	relayed: Relayed,
+	pending: Vec<PendingFees<T>>,
+	/// Last best block updates seen by the accounting.
+	mainnet_seen: usize,
+	testnet_seen: usize,
+}
+
+impl<T: PubSub + Clone> FeeAccounting<T> {
+	/// Relays, which fees are about to be accounted.
+	pub fn relayed(&mut self) -> &mut Relayed {
+		&mut self.relayed
+	}
+
+	fn has_pending(&self, chain: Chain) -> bool {
+		self.pending.iter().any(|pending| pending.relay.chain == chain)
+	}
+
+	fn receipt(&self, relay: &RelayOutcome) -> ApiCall<Option<TransactionReceipt>, T> {
+		let (transport, node) = (self.app.connection(relay.chain), self.app.node(relay.chain));
+		api::transaction_receipt(transport.clone(), relay.transaction_hash)
+			.retry(&self.app.timer, node.request_timeouts.call, &node.retry)
+	}
+}
+
+/// Fees paid for the mined `relay` transaction. Nodes, which don't report the effective gas price,
+/// have charged the gas price of the transaction.
+fn relay_fees(stream: &'static str, relay: &RelayOutcome, receipt: &TransactionReceipt) -> RelayFees {
+	let gas_used = receipt.gas_used.unwrap_or_default();
+	let gas_price = receipt.effective_gas_price.unwrap_or_else(|| U256::from(relay.gas_price));
+	RelayFees {
+		stream: stream.to_owned(),
+		chain: relay.chain,
+		transactions: 1,
+		gas_used: gas_used.low_u64(),
+		fees: gas_used * gas_price,
+	}
+}
+
+impl<T: PubSub + Clone> Stream for FeeAccounting<T> {
+	type Item = RelayFees;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		for (stream, relay) in self.relayed.relays.drain(..) {
+			self.pending.push(PendingFees {
+				stream,
+				relay,
+				blocks: 0,
+				receipt: None,
+			});
+		}
+
+		// blocks are watched only while there are receipts to fetch
+		let mainnet_block = if self.has_pending(Chain::Mainnet) {
+			updated_block(self.app.blocks(Chain::Mainnet), &mut self.mainnet_seen)?
+		} else {
+			None
+		};
+		let testnet_block = if self.has_pending(Chain::Testnet) {
+			updated_block(self.app.blocks(Chain::Testnet), &mut self.testnet_seen)?
+		} else {
+			None
+		};
+
+		for index in 0..self.pending.len() {
+			let block = match self.pending[index].relay.chain {
+				Chain::Mainnet => mainnet_block,
+				Chain::Testnet => testnet_block,
+			};
+			if block.is_some() && self.pending[index].receipt.is_none() {
+				let receipt = self.receipt(&self.pending[index].relay);
+				let pending = &mut self.pending[index];
+				pending.blocks += 1;
+				pending.receipt = Some(receipt);
+			}
+		}
+
+		let mut index = 0;
+		while index < self.pending.len() {
+			let receipt = match self.pending[index].receipt {
+				Some(ref mut receipt) => match receipt.poll()? {
+					Async::Ready(receipt) => receipt,
+					Async::NotReady => {
+						index += 1;
+						continue;
+					},
+				},
+				None => {
+					index += 1;
+					continue;
+				},
+			};
+
+			match receipt {
+				Some(ref receipt) if receipt.block_number.is_some() => {
+					let pending = self.pending.remove(index);
+					return Ok(Async::Ready(Some(relay_fees(pending.stream, &pending.relay, receipt))));
+				},
+				// not mined yet
+				_ if self.pending[index].blocks < MAX_RECEIPT_BLOCKS => {
+					self.pending[index].receipt = None;
+					index += 1;
+				},
+				_ => {
+					let pending = self.pending.remove(index);
+					warn!(target: "bridge", "Fees of {:?} are not accounted, because it has not been mined on {} after {} blocks", pending.relay.transaction_hash, pending.relay.chain.as_str(), MAX_RECEIPT_BLOCKS);
+				},
+			}
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use database::{ProcessedEvent, RelayOutcome, RelayFees, Chain};
+	use api::TransactionReceipt;
+	use super::relay_fees;
+
+	#[test]
+	fn test_relay_fees() {
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0xa0,
+			timestamp: 1_500_000_000,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		let receipt = |effective_gas_price: Option<u64>| TransactionReceipt {
+			block_number: Some(0x10.into()),
+			status: Some(1.into()),
+			gas_used: Some(50_000.into()),
+			effective_gas_price: effective_gas_price.map(Into::into),
+		};
+		let fees = |fees: u64| RelayFees {
+			stream: "deposit_relay".into(),
+			chain: Chain::Testnet,
+			transactions: 1,
+			gas_used: 50_000,
+			fees: fees.into(),
+		};
+
+		assert_eq!(fees(50_000 * 0x90), relay_fees("deposit_relay", &relay, &receipt(Some(0x90))));
+		// the gas price of the transaction is charged, if the effective one is not reported
+		assert_eq!(fees(50_000 * 0xa0), relay_fees("deposit_relay", &relay, &receipt(None)));
+	}
+}
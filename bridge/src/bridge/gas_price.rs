@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{Future, Poll, Async};
+use tokio_timer::{Sleep, Timer};
+use web3::types::U256;
+use api::{self, ApiCall};
+use config::{Config, Node, RetryPolicies};
+use database::Chain;
+use error::Error;
+use metrics::{Metrics, GasPriceMetrics};
+use transport::PubSub;
+
+/// Converts `value` to `u64`, saturating at `u64::MAX`.
+fn saturating_u64(value: U256) -> u64 {
+	if value > U256::from(u64::MAX) {
+		u64::MAX
+	} else {
+		value.low_u64()
+	}
+}
+
+enum GasPriceState<T: PubSub> {
+	/// Waiting for the submissions to check the gas price again.
+	Idle,
+	/// Fetching the current gas price of the chain.
+	Check(ApiCall<U256, T>),
+	/// Waiting before the gas price, which exceeds the maximum, is checked again.
+	Deferring(Sleep),
+}
+
+struct Guard<T: PubSub> {
+	chain: Chain,
+	transport: T,
+	/// Wei per gas.
+	max_gas_price: u64,
+	poll_interval: Duration,
+	request_timeout: Duration,
+	retry: RetryPolicies,
+	state: GasPriceState<T>,
+	/// Time of the last check, after which the gas price didn't exceed the maximum.
+	accepted_at: Option<Instant>,
+	deferring: bool,
+	metrics: Arc<GasPriceMetrics>,
+	timer: Timer,
+}
+
+impl<T: PubSub + Clone> Guard<T> {
+	fn new(chain: Chain, node: &Node, transport: T, timer: Timer, metrics: Arc<GasPriceMetrics>) -> Option<Self> {
+		let max_gas_price = node.max_gas_price?;
+		let guard = Guard {
+			chain,
+			transport,
+			max_gas_price,
+			poll_interval: node.poll_interval,
+			request_timeout: node.request_timeouts.call,
+			retry: node.retry.clone(),
+			state: GasPriceState::Idle,
+			accepted_at: None,
+			deferring: false,
+			metrics,
+			timer,
+		};
+		Some(guard)
+	}
+
+	fn check(&self) -> ApiCall<U256, T> {
+		api::gas_price(self.transport.clone())
+			.retry(&self.timer, self.request_timeout, &self.retry)
+	}
+
+	fn poll_acceptable(&mut self) -> Poll<(), Error> {
+		loop {
+			let next_state = match self.state {
+				GasPriceState::Idle => match self.accepted_at {
+					Some(at) if at.elapsed() < self.poll_interval => return Ok(Async::Ready(())),
+					_ => GasPriceState::Check(self.check()),
+				},
+				GasPriceState::Check(ref mut future) => {
+					let gas_price = saturating_u64(try_ready!(future.poll()));
+					let deferring = gas_price > self.max_gas_price;
+					if deferring && !self.deferring {
+						warn!(target: "bridge", "Gas price of {} is {} wei, which exceeds the maximum of {} wei, deferring relays", self.chain.as_str(), gas_price, self.max_gas_price);
+					} else if !deferring && self.deferring {
+						info!(target: "bridge", "Gas price of {} has dropped to {} wei, resuming relays", self.chain.as_str(), gas_price);
+					}
+					self.metrics.record(gas_price, deferring);
+					self.deferring = deferring;
+
+					if !deferring {
+						self.accepted_at = Some(Instant::now());
+						self.state = GasPriceState::Idle;
+						return Ok(Async::Ready(()));
+					}
+					GasPriceState::Deferring(self.timer.sleep(self.poll_interval))
+				},
+				GasPriceState::Deferring(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					GasPriceState::Check(self.check())
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+/// Watches the gas prices of the chains with `max_gas_price` configured.
+///
+/// The gas price is not fetched more often than every `poll_interval` of the node, and only when
+/// a transaction is about to be submitted. The guard is driven by the submissions waiting for it.
+pub struct GasPriceGuard<T: PubSub> {
+	/// `None` if the chain has no `max_gas_price`.
+	mainnet: Option<RefCell<Guard<T>>>,
+	testnet: Option<RefCell<Guard<T>>>,
+}
+
+impl<T: PubSub + Clone> GasPriceGuard<T> {
+	/// Deferrals are recorded in the gas price `metrics` of the chains.
+	pub fn new(config: &Config, mainnet: T, testnet: T, timer: Timer, metrics: &Metrics) -> Self {
+		GasPriceGuard {
+			mainnet: Guard::new(Chain::Mainnet, config.node(Chain::Mainnet), mainnet, timer.clone(), metrics.mainnet_gas_price.clone()).map(RefCell::new),
+			testnet: Guard::new(Chain::Testnet, config.node(Chain::Testnet), testnet, timer, metrics.testnet_gas_price.clone()).map(RefCell::new),
+		}
+	}
+
+	/// Resolves once the gas price of `chain` doesn't exceed its maximum. Submissions are deferred until then.
+	pub fn poll_acceptable(&self, chain: Chain) -> Poll<(), Error> {
+		let guard = match chain {
+			Chain::Mainnet => &self.mainnet,
+			Chain::Testnet => &self.testnet,
+		};
+		match *guard {
+			Some(ref guard) => guard.borrow_mut().poll_acceptable(),
+			None => Ok(Async::Ready(())),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::U256;
+	use super::saturating_u64;
+
+	#[test]
+	fn test_saturating_u64() {
+		assert_eq!(0x1234, saturating_u64(0x1234.into()));
+		assert_eq!(u64::MAX, saturating_u64(u64::MAX.into()));
+		assert_eq!(u64::MAX, saturating_u64(U256::from(u64::MAX) + U256::from(1)));
+	}
+}
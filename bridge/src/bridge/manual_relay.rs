@@ -0,0 +1,248 @@
+use std::mem;
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use web3::BatchTransport;
+use web3::types::{Address, Bytes, H256, U256, Log};
+use ethabi::RawLog;
+use api::{self, ApiCall, Reverts, TransactionReceipt};
+use app::App;
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, Chain};
+use error::{Error, ResultExt};
+use transport::PubSub;
+use util::web3_filter;
+use super::{node, relay_request, simulate_relay, submit_relay, unix_time, SubmitRelay};
+use super::deposit_relay::deposit_relay_entry;
+use super::withdraw_relay::{FetchWithdraws, relay_assignment, fetch_withdraws, required_signatures, withdraw_relay_payload};
+
+/// Event relayed on request of the operator, regardless of the checkpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManualRelay {
+	/// Deposits to the home contract made by the transaction with the hash.
+	Deposit(H256),
+	/// Withdraw with `message_hash`, which signatures have been collected by the foreign contract in `transaction`.
+	Withdraw {
+		message_hash: H256,
+		transaction: H256,
+	},
+}
+
+impl ManualRelay {
+	fn transaction(&self) -> H256 {
+		match *self {
+			ManualRelay::Deposit(transaction) => transaction,
+			ManualRelay::Withdraw { transaction, .. } => transaction,
+		}
+	}
+}
+
+fn raw_log(log: Log) -> RawLog {
+	RawLog {
+		topics: log.topics.into_iter().map(|t| t.0).collect(),
+		data: log.data.0,
+	}
+}
+
+enum ManualRelayState<T: PubSub + BatchTransport> {
+	/// Fetching the receipt of the transaction, to find the block of its logs.
+	Receipt(ApiCall<Option<TransactionReceipt>, T>),
+	/// Fetching the logs of the block, which are emitted by the transaction.
+	Logs(ApiCall<Vec<Log>, T>),
+	/// Reading the number of signatures required by the foreign contract.
+	RequiredSignatures {
+		future: ApiCall<Bytes, T>,
+		event: ProcessedEvent,
+		message_hash: H256,
+	},
+	/// Fetching the message and signatures of the withdraw.
+	FetchWithdraw {
+		future: FetchWithdraws<T>,
+		event: ProcessedEvent,
+	},
+	/// Simulating the next relay, so that events relayed already are skipped.
+	Simulate(Reverts<T>),
+	/// Submitting the next relay.
+	Submit(SubmitRelay<T>),
+}
+
+/// Creates the relay of the event requested by the operator, between the contracts recorded in `database`.
+pub fn manual_relay<T: PubSub + BatchTransport + Clone>(app: Arc<App<T>>, database: &Database, relay: ManualRelay) -> ManualRelayFuture<T> {
+	let (home, foreign) = (app.config.direction.home(), app.config.direction.foreign());
+	let chain = match relay {
+		ManualRelay::Deposit(_) => home,
+		ManualRelay::Withdraw { .. } => foreign,
+	};
+	let node = app.node(chain);
+	let receipt = api::transaction_receipt(app.connection(chain).clone(), relay.transaction())
+		.retry(&app.timer, node.request_timeouts.call, &node.retry);
+
+	ManualRelayFuture {
+		home_contract: database.home_contract_address,
+		foreign_contract: database.foreign_contract_address,
+		entries: Vec::new(),
+		relays: Vec::new(),
+		state: ManualRelayState::Receipt(receipt),
+		app,
+		relay,
+	}
+}
+
+/// Finds the event emitted by the requested transaction and relays it once, even if the bridge components
+/// have already checked its block or haven't reached it yet. Events, which would be reverted,
+/// e.g. because they have been relayed already, are skipped.
+///
+/// Resolves to the submitted relay transactions.
+pub struct ManualRelayFuture<T: PubSub + BatchTransport> {
+	app: Arc<App<T>>,
+	relay: ManualRelay,
+	home_contract: Address,
+	foreign_contract: Address,
+	/// Relays, which are not submitted yet, in the reverse order.
+	entries: Vec<JournalEntry>,
+	relays: Vec<RelayOutcome>,
+	state: ManualRelayState<T>,
+}
+
+impl<T: PubSub + BatchTransport + Clone> ManualRelayFuture<T> {
+	fn context(&self, chain: Chain) -> (Arc<App<T>>, Chain) {
+		(self.app.clone(), chain)
+	}
+
+	/// Simulates the next relay, or resolves to the submitted ones if there are none left.
+	fn next_relay(&mut self) -> Option<ManualRelayState<T>> {
+		let entry = self.entries.last()?.clone();
+		Some(ManualRelayState::Simulate(simulate_relay(&self.context(entry.chain), entry)))
+	}
+
+	/// Builds the relays of the logs emitted by the requested transaction.
+	fn relay_logs(&mut self, logs: Vec<Log>) -> Result<Option<ManualRelayState<T>>, Error> {
+		let (home, foreign) = (self.app.config.direction.home(), self.app.config.direction.foreign());
+		let transaction = self.relay.transaction();
+		let logs = logs.into_iter()
+			.filter(|log| log.transaction_hash == Some(transaction))
+			.collect::<Vec<_>>();
+
+		match self.relay {
+			ManualRelay::Deposit(_) => {
+				for log in logs.into_iter().rev() {
+					let event = ProcessedEvent::from_log(home, &log)?;
+					let deposit = self.app.home_bridge.events().deposit().parse_log(raw_log(log))
+						.chain_err(|| format!("Log {} of {:?} cannot be parsed", event.log_index, event.transaction_hash))?;
+					let recipient = Address::from(&deposit.recipient[..]);
+					let value = U256::from(&deposit.value[..]);
+					self.entries.push(deposit_relay_entry(&self.app.config, &self.app.foreign_bridge, self.foreign_contract, recipient, value, event));
+				}
+				if self.entries.is_empty() {
+					return Err(format!("Transaction {:?} has not deposited to the {} contract", transaction, home.as_str()).into());
+				}
+				Ok(self.next_relay())
+			},
+			ManualRelay::Withdraw { message_hash, .. } => {
+				for log in logs {
+					let event = ProcessedEvent::from_log(foreign, &log)?;
+					let collected = self.app.foreign_bridge.events().collected_signatures().parse_log(raw_log(log))
+						.chain_err(|| format!("Log {} of {:?} cannot be parsed", event.log_index, event.transaction_hash))?;
+					if H256::from(&collected.message_hash[..]) == message_hash {
+						let future = required_signatures(&self.app, foreign, self.foreign_contract);
+						return Ok(Some(ManualRelayState::RequiredSignatures { future, event, message_hash }));
+					}
+				}
+				Err(format!("Transaction {:?} has not collected signatures of {:?}", transaction, message_hash).into())
+			},
+		}
+	}
+}
+
+impl<T: PubSub + BatchTransport + Clone> Future for ManualRelayFuture<T> {
+	type Item = Vec<RelayOutcome>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				ManualRelayState::Receipt(ref mut future) => {
+					let transaction = self.relay.transaction();
+					let block = try_ready!(future.poll())
+						.and_then(|receipt| receipt.block_number)
+						.ok_or_else(|| Error::from(format!("Transaction {:?} has not been mined", transaction)))?;
+					let (chain, contract, filter) = match self.relay {
+						ManualRelay::Deposit(_) => (self.app.config.direction.home(), self.home_contract, self.app.home_bridge.events().deposit().create_filter()),
+						ManualRelay::Withdraw { .. } => (self.app.config.direction.foreign(), self.foreign_contract, self.app.foreign_bridge.events().collected_signatures().create_filter()),
+					};
+					let filter = web3_filter(filter, contract)
+						.from_block(block.low_u64().into())
+						.to_block(block.low_u64().into())
+						.build();
+					let node = self.app.node(chain);
+					ManualRelayState::Logs(api::logs(self.app.connection(chain).clone(), &filter)
+						.retry(&self.app.timer, node.request_timeouts.logs, &node.retry))
+				},
+				ManualRelayState::Logs(ref mut future) => {
+					let logs = try_ready!(future.poll());
+					match self.relay_logs(logs)? {
+						Some(state) => state,
+						None => return Ok(Async::Ready(mem::take(&mut self.relays))),
+					}
+				},
+				ManualRelayState::RequiredSignatures { ref mut future, event, message_hash } => {
+					let output = try_ready!(future.poll());
+					let required = self.app.foreign_bridge.functions().required_signatures().output(&output.0)?;
+					// the authority is used only to assign the relay
+					let assignment = relay_assignment(&self.app.foreign_bridge, U256::from(&required[..]).low_u64() as u32, message_hash, Address::default());
+					ManualRelayState::FetchWithdraw {
+						future: fetch_withdraws(&self.app, self.foreign_contract, vec![assignment]),
+						event,
+					}
+				},
+				ManualRelayState::FetchWithdraw { ref mut future, event } => {
+					let (message, signatures) = try_ready!(future.poll()).pop().expect("a single withdraw is fetched; qed");
+					let data = withdraw_relay_payload(&self.app.home_bridge, signatures, message)
+						.chain_err(|| format!("Withdraw {:?} is malformed", event.transaction_hash))?;
+					self.entries.push(JournalEntry {
+						chain: self.app.config.direction.home(),
+						to: self.home_contract,
+						data,
+						gas: self.app.config.txs.withdraw_relay.gas,
+						gas_price: self.app.config.txs.withdraw_relay.gas_price,
+						event,
+					});
+					self.next_relay().expect("the withdraw relay has been added above; qed")
+				},
+				ManualRelayState::Simulate(ref mut future) => {
+					let reverts = try_ready!(future.poll());
+					let entry = self.entries.last().expect("only the pending relays are simulated; qed").clone();
+					if reverts {
+						warn!(target: "bridge", "Relay of {:?} would be reverted, e.g. because it has been relayed already, skipping it", entry.event.transaction_hash);
+						self.entries.pop();
+						match self.next_relay() {
+							Some(state) => state,
+							None => return Ok(Async::Ready(mem::take(&mut self.relays))),
+						}
+					} else {
+						let context = self.context(entry.chain);
+						let request = relay_request(node(&context), &entry);
+						ManualRelayState::Submit(submit_relay(&context, request))
+					}
+				},
+				ManualRelayState::Submit(ref mut future) => {
+					let result = try_ready!(future.poll());
+					let entry = self.entries.pop().expect("only the pending relays are submitted; qed");
+					let submitted = result.map_err(|failure| Error::from(format!("Relay of {:?} could not be submitted: {}", entry.event.transaction_hash, failure.message)))?;
+					info!(target: "bridge", "Relayed {:?} to {} by {:?}", entry.event.transaction_hash, entry.chain.as_str(), submitted.hash);
+					self.relays.push(RelayOutcome {
+						chain: entry.chain,
+						transaction_hash: submitted.hash,
+						gas_price: submitted.gas_price,
+						timestamp: unix_time(),
+						block: None,
+						event: entry.event,
+					});
+					match self.next_relay() {
+						Some(state) => state,
+						None => return Ok(Async::Ready(mem::take(&mut self.relays))),
+					}
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
@@ -0,0 +1,226 @@
+use std::cmp;
+use std::collections::HashSet;
+use std::sync::Arc;
+use futures::{Stream, Poll, Async};
+use web3::BatchTransport;
+use web3::types::{Address, Bytes, H256, Log, FilterBuilder};
+use ethabi::RawLog;
+use api::{LogStream, self};
+use error::{Error, Result};
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, RevertedRelay, Chain};
+use contracts::message;
+use util::web3_filter;
+use app::App;
+use transport::PubSub;
+use super::{Relay, RelaySubmission, BatchProgress, RetryQueue, journaled, parse_logs};
+
+fn messages_filter(message_bridge: &message::MessageBridge, address: Address) -> FilterBuilder {
+	let filter = message_bridge.events().send_message().create_filter();
+	web3_filter(filter, address)
+}
+
+/// Returns the opaque payload of the message.
+fn parse_message(message_bridge: &message::MessageBridge, log: Log) -> Result<Bytes> {
+	let raw_log = RawLog {
+		topics: log.topics.into_iter().map(|t| t.0).collect(),
+		data: log.data.0,
+	};
+	let message_log = message_bridge.events().send_message().parse_log(raw_log)?;
+	Ok(message_log.message.into())
+}
+
+fn message_relay_payload(message_bridge: &message::MessageBridge, message: Bytes, hash: H256) -> Bytes {
+	message_bridge.functions().execute_message().input(message.0, hash.0).into()
+}
+
+/// Returns the chain, to which messages sent from `chain` are relayed.
+fn destination(chain: Chain) -> Chain {
+	match chain {
+		Chain::Mainnet => Chain::Testnet,
+		Chain::Testnet => Chain::Mainnet,
+	}
+}
+
+/// State of message relay.
+enum MessageRelayState<T: PubSub> {
+	/// Message relay is waiting for logs.
+	Wait,
+	/// Relaying messages in progress, yielding the last block, which messages have all been relayed.
+	/// Messages from the journal are relayed before any block is checked.
+	RelayMessages(Box<RelaySubmission<T>>),
+}
+
+/// Creates relay of messages sent from `chain` to the other one.
+///
+/// Messages are relayed only if `message_bridge` is configured.
+pub fn create_message_relay<T: BatchTransport + PubSub + Clone>(app: Arc<App<T>>, init: &Database, chain: Chain) -> MessageRelay<T> {
+	let contracts = app.config.message_bridge.expect("message relays are created only if `message_bridge` is configured; qed");
+	let (node, transport, blocks) = (app.node(chain), app.connection(chain), app.blocks(chain));
+	let deploy = init.deploy(app.config.direction, chain);
+	let (contract, checked) = match chain {
+		Chain::Mainnet => (contracts.mainnet, init.checked_mainnet_message_relay),
+		Chain::Testnet => (contracts.testnet, init.checked_testnet_message_relay),
+	};
+	let destination_contract = match chain {
+		Chain::Mainnet => contracts.testnet,
+		Chain::Testnet => contracts.mainnet,
+	};
+
+	let logs_init = api::LogStreamInit {
+		// databases created before messages were relayed have no checkpoint
+		after: cmp::max(checked, deploy),
+		logs_request_timeout: node.request_timeouts.logs,
+		retry: node.retry.clone(),
+		confirmations: node.required_confirmations,
+		max_blocks_per_query: node.max_blocks_per_query,
+		adaptive_polling: node.adaptive_polling,
+		poll_interval: None,
+		catch_up: node.catch_up,
+		once: app.once,
+		until: app.until,
+		filter: messages_filter(&app.message_bridge, contract),
+	};
+	let replayed = journaled(init, chain, destination(chain), destination_contract);
+	let already_processed = init.relays.iter().map(|relay| relay.event)
+		.chain(replayed.iter().map(|entry| entry.event))
+		.chain(init.quarantine.iter().map(|log| log.event))
+		.collect();
+	let state = if replayed.is_empty() {
+		MessageRelayState::Wait
+	} else {
+		MessageRelayState::RelayMessages(Box::new(RelaySubmission::new(&app, destination(chain), replayed, true, app.config.txs.message_relay.preflight, app.config.txs.message_relay.receipt_confirmations, false)))
+	};
+	MessageRelay {
+		logs: api::log_stream(transport.clone(), app.timer.clone(), blocks.clone(), logs_init),
+		chain,
+		destination_contract,
+		state,
+		already_processed,
+		relays: Vec::new(),
+		journal: Vec::new(),
+		discarded: Vec::new(),
+		reverted: Vec::new(),
+		quarantined: Vec::new(),
+		retries: RetryQueue::new(&app, destination(chain)),
+		app,
+	}
+}
+
+pub struct MessageRelay<T: BatchTransport + PubSub> {
+	app: Arc<App<T>>,
+	logs: LogStream<T>,
+	state: MessageRelayState<T>,
+	/// Chain from which the messages are relayed.
+	chain: Chain,
+	/// Contract executing the messages on the other chain.
+	destination_contract: Address,
+	/// Events relayed before restart.
+	already_processed: HashSet<ProcessedEvent>,
+	/// Transactions which relayed events, but have not been reported yet.
+	relays: Vec<RelayOutcome>,
+	/// Transactions which are about to be submitted, but have not been reported yet.
+	journal: Vec<JournalEntry>,
+	/// Events from the journal, which have already been relayed, but have not been reported yet.
+	discarded: Vec<ProcessedEvent>,
+	/// Relay transactions, which have been reverted, but have not been reported yet.
+	reverted: Vec<RevertedRelay>,
+	/// Logs, which could not be parsed, but have not been reported yet.
+	quarantined: Vec<QuarantinedLog>,
+	/// Relays, which have failed and are retried.
+	retries: RetryQueue<T>,
+}
+
+impl<T: BatchTransport + PubSub + Clone> Stream for MessageRelay<T> {
+	type Item = u64;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.retries.poll(&mut self.relays, &mut self.journal, &mut self.reverted)?;
+		loop {
+			let next_state = match self.state {
+				MessageRelayState::Wait => {
+					let item = try_stream!(self.logs.poll());
+					let progress = BatchProgress::new(self.chain, &item);
+					let app = &self.app;
+					let messages = parse_logs(self.chain, item.logs, &self.already_processed, &mut self.quarantined, |log| parse_message(&app.message_bridge, log))?;
+
+					let entries = messages.into_iter()
+						.map(|(event, message)| JournalEntry {
+							chain: destination(self.chain),
+							to: self.destination_contract,
+							data: message_relay_payload(&app.message_bridge, message, event.transaction_hash),
+							gas: app.config.txs.message_relay.gas,
+							gas_price: app.config.txs.message_relay.gas_price,
+							event,
+						})
+						.collect();
+
+					let submission = RelaySubmission::new(&self.app, destination(self.chain), entries, false, self.app.config.txs.message_relay.preflight, self.app.config.txs.message_relay.receipt_confirmations, false);
+					MessageRelayState::RelayMessages(Box::new(submission.with_progress(progress)))
+				},
+				MessageRelayState::RelayMessages(ref mut submission) => {
+					match try_ready!(submission.poll_checked(&mut self.journal, &mut self.relays, &mut self.discarded, &mut self.reverted, &mut self.retries)) {
+						Some(block) => return Ok(Async::Ready(Some(block))),
+						None => MessageRelayState::Wait,
+					}
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+impl<T: BatchTransport + PubSub + Clone> Relay for MessageRelay<T> {
+	fn take_relays(&mut self) -> Vec<RelayOutcome> {
+		self.relays.split_off(0)
+	}
+
+	fn take_journal(&mut self) -> Vec<JournalEntry> {
+		self.journal.split_off(0)
+	}
+
+	fn take_discarded(&mut self) -> Vec<ProcessedEvent> {
+		self.discarded.split_off(0)
+	}
+
+	fn take_quarantined(&mut self) -> Vec<QuarantinedLog> {
+		self.quarantined.split_off(0)
+	}
+
+	fn take_reverted(&mut self) -> Vec<RevertedRelay> {
+		self.reverted.split_off(0)
+	}
+
+	fn failed_relays(&self) -> usize {
+		self.retries.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_hex::FromHex;
+	use web3::types::{Log, Bytes};
+	use contracts::message;
+	use super::{parse_message, message_relay_payload};
+
+	#[test]
+	fn test_message_relay_payload() {
+		let message_bridge = message::MessageBridge::default();
+
+		let data = "000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000".from_hex().unwrap();
+		let log = Log {
+			data: data.into(),
+			topics: vec!["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953".parse().unwrap()],
+			transaction_hash: Some("0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap()),
+			..Default::default()
+		};
+
+		let hash = log.transaction_hash.unwrap();
+		let message = parse_message(&message_bridge, log).unwrap();
+		let expected: Bytes = "123456".from_hex().unwrap().into();
+		assert_eq!(expected, message);
+		let payload = message_relay_payload(&message_bridge, message, hash);
+		let expected: Bytes = "ef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000".from_hex().unwrap().into();
+		assert_eq!(expected, payload);
+	}
+}
@@ -1,99 +1,1305 @@
+mod balance;
+mod contract_check;
+mod delay;
 mod deploy;
 mod deposit_relay;
+mod discovery;
+mod expiry;
+mod export;
+mod fees;
+mod gas_price;
+mod manual_relay;
+mod message_relay;
+mod nonce;
+mod pause;
+mod payloads;
+mod probe;
+mod status;
+mod verification;
+mod watch;
 mod withdraw_confirm;
 mod withdraw_relay;
 
-use std::fs;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::sync::Arc;
-use std::path::PathBuf;
-use futures::{Stream, Poll, Async};
-use web3::Transport;
+use std::time::{SystemTime, UNIX_EPOCH};
+use futures::{Future, Stream, Poll, Async, task};
+use futures::future::{JoinAll, join_all};
+use tokio_timer::{Sleep, Timeout};
+use web3::{BatchTransport, Transport};
+use web3::types::{Address, Bytes, H256, U256, Log};
+use api::{self, ApiCall, BlockWatcher, Limited, Reverts, RevertReason, ReceiptConfirmations, ReceiptConfirmationsInit, RevertedTransaction, LogStreamItem};
 use app::App;
-use database::Database;
-use error::{Error, Result};
+use transport::PubSub;
+use database::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, PendingAssignment, HeldRelay, RevertedRelay, Chain};
+use config::{Node, RetentionPolicy};
+use error::{Error, ErrorClass};
+use metrics::BridgeMetrics;
+use signer::Signing;
+use transaction::Transaction;
 
+pub use self::balance::{BalanceMonitor, AccountBalance, Funds, create_balance_monitor};
+pub use self::contract_check::{ContractCheck, VerifyContracts, verify_contracts};
 pub use self::deploy::{Deploy, Deployed, create_deploy};
-pub use self::deposit_relay::{DepositRelay, create_deposit_relay};
+pub use self::deposit_relay::{DepositRelay, create_deposit_relay, deposit_relay_entry};
+pub use self::export::{ExportEvents, ExportedEvent, ExportRange, EventKind, export_events, events_csv, events_json};
+pub use self::fees::{FeeAccounting, Relayed, create_fee_accounting};
+pub use self::gas_price::GasPriceGuard;
+pub use self::manual_relay::{ManualRelay, ManualRelayFuture, manual_relay};
+pub use self::nonce::Nonces;
+pub use self::payloads::SentPayloads;
+use self::nonce::Assignment;
+use self::expiry::ExpiringRelays;
+use self::balance::saturating_u128;
+pub use self::pause::PauseWatcher;
+pub use self::probe::{NodeProbe, ProbeNodes, probe_nodes};
+pub use self::status::{Status, ChainStatus, StreamStatus, FetchStatus, fetch_status, checkpoints, checked_blocks};
 pub use self::withdraw_relay::{WithdrawRelay, create_withdraw_relay};
 pub use self::withdraw_confirm::{WithdrawConfirm, create_withdraw_confirm};
+pub use self::message_relay::{MessageRelay, create_message_relay};
+pub use self::verification::{RelayVerification, Submitted, Verified, create_relay_verification};
+pub use self::watch::{Watch, create_watch};
 
 /// Last block checked by the bridge components.
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(tag = "stream", content = "block", rename_all = "snake_case")]
 pub enum BridgeChecked {
 	DepositRelay(u64),
 	WithdrawRelay(u64),
 	WithdrawConfirm(u64),
+	/// Relays of messages sent from mainnet.
+	MainnetMessageRelay(u64),
+	/// Relays of messages sent from testnet.
+	TestnetMessageRelay(u64),
 }
 
-pub trait BridgeBackend {
-	fn save(&mut self, checks: Vec<BridgeChecked>) -> Result<()>;
+impl BridgeChecked {
+	/// Returns checkpoint of the component with given name, e.g. `deposit_relay`.
+	pub fn from_stream(stream: &str, block: u64) -> Option<Self> {
+		match stream {
+			"deposit_relay" => Some(BridgeChecked::DepositRelay(block)),
+			"withdraw_relay" => Some(BridgeChecked::WithdrawRelay(block)),
+			"withdraw_confirm" => Some(BridgeChecked::WithdrawConfirm(block)),
+			"mainnet_message_relay" => Some(BridgeChecked::MainnetMessageRelay(block)),
+			"testnet_message_relay" => Some(BridgeChecked::TestnetMessageRelay(block)),
+			_ => None,
+		}
+	}
+
+	/// Name of the component.
+	pub fn stream(&self) -> &'static str {
+		match *self {
+			BridgeChecked::DepositRelay(_) => "deposit_relay",
+			BridgeChecked::WithdrawRelay(_) => "withdraw_relay",
+			BridgeChecked::WithdrawConfirm(_) => "withdraw_confirm",
+			BridgeChecked::MainnetMessageRelay(_) => "mainnet_message_relay",
+			BridgeChecked::TestnetMessageRelay(_) => "testnet_message_relay",
+		}
+	}
+
+	pub fn block(&self) -> u64 {
+		match *self {
+			BridgeChecked::DepositRelay(n) | BridgeChecked::WithdrawRelay(n) | BridgeChecked::WithdrawConfirm(n) |
+			BridgeChecked::MainnetMessageRelay(n) | BridgeChecked::TestnetMessageRelay(n) => n,
+		}
+	}
+}
+
+/// Relay stream reporting which events it has relayed.
+pub trait Relay: Stream<Item = u64, Error = Error> {
+	/// Returns transactions which relayed events since the previous call.
+	fn take_relays(&mut self) -> Vec<RelayOutcome>;
+
+	/// Returns relay transactions, which have to be written to the journal before they are submitted.
+	fn take_journal(&mut self) -> Vec<JournalEntry>;
+
+	/// Returns events from the journal, which turned out not to need relaying.
+	fn take_discarded(&mut self) -> Vec<ProcessedEvent>;
+
+	/// Returns logs, which could not be parsed and have been skipped since the previous call.
+	fn take_quarantined(&mut self) -> Vec<QuarantinedLog>;
+
+	/// Returns deposits, which have not been relayed because of their value since the previous call.
+	fn take_filtered_deposits(&mut self) -> Vec<FilteredDeposit> {
+		Vec::new()
+	}
+
+	/// Returns assignments, which are pending and have been created or updated since the previous call.
+	fn take_assignments(&mut self) -> Vec<PendingAssignment> {
+		Vec::new()
+	}
+
+	/// Returns events, which assignments are no longer pending since the previous call.
+	fn take_discarded_assignments(&mut self) -> Vec<ProcessedEvent> {
+		Vec::new()
+	}
+
+	/// Returns relays of large transfers, which have been held since the previous call.
+	fn take_held(&mut self) -> Vec<HeldRelay> {
+		Vec::new()
+	}
+
+	/// Returns events, which relays have been released and journaled or skipped since the previous call.
+	fn take_released(&mut self) -> Vec<ProcessedEvent> {
+		Vec::new()
+	}
+
+	/// Returns relay transactions, which have been reverted since the previous call.
+	/// Their events are journaled again and retried.
+	fn take_reverted(&mut self) -> Vec<RevertedRelay> {
+		Vec::new()
+	}
+
+	/// Returns the number of relays, which have failed or which the authority cannot pay for, and are waiting to be submitted again.
+	/// They stay in the journal, so they are submitted after restart as well.
+	fn failed_relays(&self) -> usize {
+		0
+	}
+}
+
+/// Relays, which should not be retained, are pruned at most once per this interval.
+const PRUNE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Unix time in seconds.
+fn unix_time() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
+
+/// Returns outcome of the relay of the journal `entry` by the `submitted` transaction.
+fn relayed(entry: &JournalEntry, submitted: Submitted, timestamp: u64) -> RelayOutcome {
+	RelayOutcome {
+		chain: entry.chain,
+		transaction_hash: submitted.hash,
+		gas_price: submitted.gas_price,
+		timestamp,
+		// the block is known only to the submissions of batches of logs
+		block: None,
+		event: entry.event,
+	}
+}
+
+/// Events, which are being relayed.
+struct PendingRelays {
+	entries: Vec<JournalEntry>,
+	recorded: usize,
+}
+
+impl PendingRelays {
+	fn new(entries: Vec<JournalEntry>) -> Self {
+		PendingRelays {
+			entries,
+			recorded: 0,
+		}
+	}
+
+	/// Polls `future` submitting relay transactions of the events, moves every submitted transaction
+	/// to `relays` and every failed one to `failed`. Resolves to hashes of the submitted transactions.
+	///
+	/// Returns after every submitted or failed transaction, so that it's reported before the next one.
+	fn poll<C, I, F>(&mut self, future: &mut Limited<C, I, F>, relays: &mut Vec<RelayOutcome>, failed: &mut Vec<(JournalEntry, SubmitFailure)>) -> Poll<Vec<H256>, F::Error>
+		where F: Future<Item = Result<Submitted, SubmitFailure>>
+	{
+		let result = future.poll_item();
+		let submitted = future.completed();
+		let timestamp = unix_time();
+		let entries = &self.entries[self.recorded..submitted.len()];
+		for (entry, result) in entries.iter().zip(&submitted[self.recorded..]) {
+			match *result {
+				Ok(submitted) => relays.push(relayed(entry, submitted, timestamp)),
+				Err(ref failure) => failed.push((entry.clone(), failure.clone())),
+			}
+		}
+		self.recorded = submitted.len();
+
+		if !try_ready!(result) {
+			// the transaction is reported before this relay is polled again
+			task::current().notify();
+			return Ok(Async::NotReady);
+		}
+		Ok(Async::Ready(submitted.iter().filter_map(|result| result.as_ref().ok().map(|submitted| submitted.hash)).collect()))
+	}
 }
 
-pub struct FileBackend {
-	path: PathBuf,
-	database: Database,
+/// Parses `logs` of `chain`, which have not been `processed` yet.
+///
+/// Logs which cannot be parsed are moved to `quarantine` instead of failing the whole batch.
+/// Fails if any of the logs has not been mined, so it cannot be told apart from the other events.
+fn parse_logs<R, F>(chain: Chain, logs: Vec<Log>, processed: &HashSet<ProcessedEvent>, quarantine: &mut Vec<QuarantinedLog>, mut parse: F) -> Result<Vec<(ProcessedEvent, R)>, Error>
+	where F: FnMut(Log) -> Result<R, Error>
+{
+	let mut result = Vec::new();
+	for log in logs {
+		let event = ProcessedEvent::from_log(chain, &log)?;
+		if processed.contains(&event) {
+			continue;
+		}
+
+		match parse(log.clone()) {
+			Ok(parsed) => result.push((event, parsed)),
+			Err(err) => {
+				warn!(target: "bridge", "Log {} of {:?} cannot be parsed, quarantining it: {}", event.log_index, event.transaction_hash, err);
+				quarantine.push(QuarantinedLog {
+					event,
+					address: log.address,
+					topics: log.topics,
+					data: log.data,
+					error: err.to_string(),
+				});
+			},
+		}
+	}
+	Ok(result)
+}
+
+/// Progress of relaying events of a batch of logs.
+#[derive(Default)]
+struct BatchProgress {
+	/// Blocks of the events of the batch.
+	blocks: HashMap<ProcessedEvent, u64>,
+	/// Last block of the batch.
+	to: u64,
+	/// Last block, which has been reported as checked.
+	checked: u64,
 }
 
-impl BridgeBackend for FileBackend {
-	fn save(&mut self, checks: Vec<BridgeChecked>) -> Result<()> {
-		for check in checks {
-			match check {
-				BridgeChecked::DepositRelay(n) => {
-					self.database.checked_deposit_relay = n;
+impl BatchProgress {
+	fn new(chain: Chain, item: &LogStreamItem) -> Self {
+		let blocks = item.logs.iter()
+			.filter_map(|log| Some((ProcessedEvent::from_log(chain, log).ok()?, log.block_number?.low_u64())))
+			.collect();
+
+		BatchProgress {
+			blocks,
+			to: item.to,
+			checked: item.from.saturating_sub(1),
+		}
+	}
+
+	/// Returns the last block, which events have all been relayed, if it has advanced since it has been last reported.
+	///
+	/// Checkpoint is held if the block of any `pending` event is not known, e.g. because it's from an earlier batch.
+	fn advance(&mut self, pending: &[JournalEntry]) -> Option<u64> {
+		let checked = pending.iter().try_fold(self.to, |checked, entry| {
+			self.blocks.get(&entry.event).map(|block| cmp::min(checked, block.saturating_sub(1)))
+		})?;
+
+		if checked > self.checked {
+			self.checked = checked;
+			Some(checked)
+		} else {
+			None
+		}
+	}
+}
+
+/// Returns entries of the journal, which were not relayed from `event_chain` to contract `to` on `chain` before restart.
+fn journaled(init: &Database, event_chain: Chain, chain: Chain, to: Address) -> Vec<JournalEntry> {
+	init.journal.iter()
+		.filter(|entry| entry.event.chain == event_chain && entry.chain == chain && entry.to == to)
+		.cloned()
+		.collect()
+}
+
+type SimulateRelays<T> = Limited<(Arc<App<T>>, Chain), JournalEntry, Reverts<T>>;
+type SubmitRelays<T> = Limited<(Arc<App<T>>, Chain), Transaction, SubmitRelay<T>>;
+/// Reverted relay transactions along with the replays finding the reasons of the reverts.
+type ReplayReverts<T> = (Vec<(JournalEntry, RevertedTransaction)>, JoinAll<Vec<RevertReason<T>>>);
+
+/// Relay transaction accepted by the node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Submitted {
+	hash: H256,
+	/// Gas price of the sent transaction, its max fee per gas if it pays EIP-1559 fees.
+	gas_price: u64,
+}
+
+/// Failed submission of a relay transaction.
+#[derive(Debug, Clone, PartialEq)]
+struct SubmitFailure {
+	class: ErrorClass,
+	message: String,
+}
+
+enum SubmitState<T: Transport> {
+	/// Waiting for another submission to synchronize the nonces.
+	Assign,
+	/// Synchronizing the nonces with the pending transaction count of the account.
+	Sync(ApiCall<U256, T>),
+	/// Sending the transaction with the nonce assigned by the bridge.
+	Assigned(U256),
+	/// Signing the transaction locally.
+	Sign(Signing<Bytes>),
+	/// Waiting for the node to accept the transaction.
+	Send(Timeout<ApiCall<H256, T>>),
+}
+
+/// Creates the request of the pending transaction count of the account sending transactions to the chain.
+type NextNonce<T> = fn(&(Arc<App<T>>, Chain), Address) -> ApiCall<U256, T>;
+
+/// Resolves to the result of the relay transaction submission,
+/// so that its failure doesn't stop submissions of the other relays.
+///
+/// If the node `manages_nonces`, the transaction is sent with the nonce assigned by the bridge,
+/// from the account of its submission pool, which is picked once the transaction is submitted.
+/// If the account has a `signer`, the transaction is signed locally, on its Ledger or by its remote signer, and sent with `eth_sendRawTransaction`.
+/// Otherwise it's sent with `eth_sendTransaction` and signed by the node.
+struct SubmitRelay<T: PubSub> {
+	context: (Arc<App<T>>, Chain),
+	request: Transaction,
+	/// Used to send the transaction once its nonce is assigned.
+	transport: Option<T>,
+	/// Creates the request of the pending transaction count, since the future itself doesn't require `T: Clone`.
+	next_nonce: NextNonce<T>,
+	/// Nonce assigned by the bridge, which is reported once the transaction is sent.
+	nonce: Option<U256>,
+	state: SubmitState<T>,
+}
+
+impl<T: PubSub> SubmitRelay<T> {
+	fn poll_submit(&mut self) -> Poll<H256, Error> {
+		let (ref app, chain) = self.context;
+		let account = self.request.from;
+		loop {
+			let next_state = match self.state {
+				SubmitState::Assign => match app.nonces.assign(chain, account) {
+					Assignment::Assigned(nonce) => SubmitState::Assigned(nonce),
+					Assignment::Sync => SubmitState::Sync((self.next_nonce)(&self.context, account)),
+					Assignment::Wait => return Ok(Async::NotReady),
 				},
-				BridgeChecked::WithdrawRelay(n) => {
-					self.database.checked_withdraw_relay = n;
+				SubmitState::Sync(ref mut future) => {
+					let pending = try_ready!(future.poll());
+					SubmitState::Assigned(app.nonces.sync(chain, account, pending))
 				},
-				BridgeChecked::WithdrawConfirm(n) => {
-					self.database.checked_withdraw_confirm = n;
+				SubmitState::Assigned(nonce) => {
+					self.nonce = Some(nonce);
+					let node = node(&self.context);
+					match node.account_signer(account) {
+						Some(signer) => SubmitState::Sign(signer.sign_transaction(&app.timer, &self.request, nonce)),
+						None => {
+							let transport = self.transport.take().expect("nonce is assigned only once; qed");
+							let future = api::send_transaction(transport, Transaction {
+								nonce: Some(nonce),
+								..self.request.clone()
+							});
+							SubmitState::Send(app.timer.timeout(future, node.request_timeouts.send_transaction))
+						},
+					}
+				},
+				SubmitState::Sign(ref mut future) => {
+					let raw = try_ready!(future.poll());
+					let transport = self.transport.take().expect("transaction is signed only once; qed");
+					let future = api::send_raw_transaction(transport, raw);
+					SubmitState::Send(app.timer.timeout(future, node(&self.context).request_timeouts.send_transaction))
+				},
+				SubmitState::Send(ref mut future) => {
+					let hash = try_ready!(future.poll());
+					if let Some(nonce) = self.nonce {
+						app.nonces.sent(chain, account, nonce);
+					}
+					if let Some(to) = self.request.to {
+						app.payloads.sent(chain, to, &self.request.data, hash, unix_time());
+					}
+					return Ok(Async::Ready(hash));
 				},
+			};
+			self.state = next_state;
+		}
+	}
+}
+
+impl<T: PubSub> Future for SubmitRelay<T> {
+	type Item = Result<Submitted, SubmitFailure>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.poll_submit() {
+			Ok(Async::Ready(hash)) => Ok(Async::Ready(Ok(Submitted {
+				hash,
+				gas_price: self.request.fees.max_gas_price().low_u64(),
+			}))),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(err) => {
+				if let SubmitState::Sync(_) = self.state {
+					self.state = SubmitState::Assign;
+					self.context.0.nonces.sync_failed(self.context.1, self.request.from);
+				}
+				if let Some(nonce) = self.nonce.take() {
+					self.context.0.nonces.failed(self.context.1, self.request.from, nonce, err.class());
+				}
+				Ok(Async::Ready(Err(SubmitFailure {
+					class: err.class(),
+					message: err.to_string(),
+				})))
+			},
+		}
+	}
+}
+
+impl<T: PubSub> Drop for SubmitRelay<T> {
+	fn drop(&mut self) {
+		// don't leave the other submissions waiting for the nonces
+		if let SubmitState::Sync(_) = self.state {
+			self.context.0.nonces.sync_failed(self.context.1, self.request.from);
+		}
+	}
+}
+
+fn submit_relay<T: PubSub + Clone>(context: &(Arc<App<T>>, Chain), mut request: Transaction) -> SubmitRelay<T> {
+	let (ref app, chain) = *context;
+	let node = node(context);
+	let transport = app.submission(chain).clone();
+	let (transport, state) = if !node.manages_nonces() {
+		let future = api::send_transaction(transport, request.clone());
+		(None, SubmitState::Send(app.timer.timeout(future, node.request_timeouts.send_transaction)))
+	} else {
+		// retried transactions may be sent from another account of the pool
+		request.from = app.nonces.pick(chain, &node.submission_accounts());
+		// nonces are assigned in the order the relays are submitted, rather than polled
+		match app.nonces.assign(chain, request.from) {
+			Assignment::Assigned(nonce) => (Some(transport), SubmitState::Assigned(nonce)),
+			Assignment::Sync => (Some(transport), SubmitState::Sync(next_nonce(context, request.from))),
+			Assignment::Wait => (Some(transport), SubmitState::Assign),
+		}
+	};
+
+	SubmitRelay {
+		context: context.clone(),
+		request,
+		transport,
+		next_nonce: next_nonce::<T>,
+		nonce: None,
+		state,
+	}
+}
+
+fn node<T: PubSub>(context: &(Arc<App<T>>, Chain)) -> &Node {
+	context.0.node(context.1)
+}
+
+/// Fetches nonce of the next transaction sent from `account` to the chain.
+fn next_nonce<T: PubSub + Clone>(context: &(Arc<App<T>>, Chain), account: Address) -> ApiCall<U256, T> {
+	let (ref app, chain) = *context;
+	let transport = app.submission(chain);
+	let node = node(context);
+	api::transaction_count(transport.clone(), account)
+		.retry(&app.timer, node.request_timeouts.call, &node.retry)
+}
+
+/// Builds the relay transaction according to the fee model of the chain, paying at most the journaled gas price.
+fn relay_request(node: &Node, entry: &JournalEntry) -> Transaction {
+	node.fee_model.transaction(node.account, Some(entry.to), entry.data.clone(), entry.gas, entry.gas_price)
+}
+
+/// Simulates the relay transaction. Resolves to true if it would be reverted.
+fn simulate_relay<T: PubSub + Clone>(context: &(Arc<App<T>>, Chain), entry: JournalEntry) -> Reverts<T> {
+	let (ref app, chain) = *context;
+	let (transport, node) = (app.connection(chain), app.node(chain));
+	let call = api::call_from(transport.clone(), node.account, entry.to, entry.data)
+		.retry(&app.timer, node.request_timeouts.call, &node.retry);
+	api::reverts(call)
+}
+
+/// Returns number of the best block of `blocks`, if it has been updated since the `seen` update.
+fn updated_block<T: PubSub + Clone>(blocks: &BlockWatcher<T>, seen: &mut usize) -> Result<Option<u64>, Error> {
+	match blocks.poll_block(seen)? {
+		Async::Ready(Some(block)) => {
+			// the stream is woken up by the next update only after it has polled the watcher again
+			task::current().notify();
+			Ok(Some(block.number))
+		},
+		Async::Ready(None) | Async::NotReady => Ok(None),
+	}
+}
+
+/// Relay transactions submitted only after they are written to the journal,
+/// so that none of them is lost if the bridge stops before they are relayed.
+///
+/// Transactions may be simulated first, so that those which would be reverted,
+/// e.g. because they have already been submitted before a crash, don't waste gas.
+/// During a dry run, the transactions are logged instead of being submitted.
+/// Submission may complete only once the transactions are confirmed. Confirmed transactions,
+/// which have been reverted, are recorded, written back to the journal and retried.
+///
+/// Several transactions may be given consecutive nonces and submitted at once,
+/// so that they don't wait for each other to be assigned nonces by the node.
+///
+/// Progress of the batch of logs, which events are relayed, may be tracked,
+/// so that it's checkpointed after every relay transaction.
+struct RelaySubmission<T: PubSub> {
+	context: (Arc<App<T>>, Chain),
+	entries: Vec<JournalEntry>,
+	journaled: bool,
+	/// Simulations of the transactions, which have not completed yet.
+	simulation: Option<SimulateRelays<T>>,
+	/// Whether the transactions are given consecutive nonces.
+	sequence_nonces: bool,
+	/// Nonce of the first transaction, which is fetched once they are simulated.
+	nonce: Option<ApiCall<U256, T>>,
+	/// Submission of the transactions, once they are written to the journal.
+	submission: Option<(SubmitRelays<T>, PendingRelays)>,
+	/// Number of blocks confirming the submitted transactions, which are waited for.
+	receipt_confirmations: Option<u64>,
+	/// Confirmation of the submitted transactions.
+	confirmation: Option<ReceiptConfirmations<T>>,
+	/// Replays of the confirmed transactions, which have been reverted.
+	reverts: Option<ReplayReverts<T>>,
+	/// Whether the bridge has been unpaused, the gas price is acceptable and the authority can pay for the transactions,
+	/// once they are about to be submitted.
+	unpaused: bool,
+	/// Progress of the batch of logs, which events are relayed.
+	progress: Option<BatchProgress>,
+	completed: bool,
+}
+
+impl<T: PubSub + Clone> RelaySubmission<T> {
+	/// Creates submission of relay transactions to `chain`, which are first simulated if `simulate` is true,
+	/// or if the dry run simulates them.
+	/// Entries, which are not `journaled` yet, are written to the journal first.
+	/// If `receipt_confirmations` are specified, submission completes once the transactions are confirmed
+	/// and the reverted ones are retried.
+	/// If `sequence_nonces` is true, the transactions are given consecutive nonces and submitted at once.
+	fn new(app: &Arc<App<T>>, chain: Chain, entries: Vec<JournalEntry>, journaled: bool, simulate: bool, receipt_confirmations: Option<u64>, sequence_nonces: bool) -> Self {
+		let context = (app.clone(), chain);
+		let simulate = simulate || app.dry_run.map_or(false, |dry_run| dry_run.simulate);
+		let simulation = if simulate && !entries.is_empty() {
+			let limit = node(&context).max_concurrent_requests;
+			Some(api::limited(context.clone(), entries.clone(), limit, simulate_relay))
+		} else {
+			None
+		};
+
+		RelaySubmission {
+			context,
+			entries,
+			journaled,
+			simulation,
+			sequence_nonces,
+			nonce: None,
+			submission: None,
+			receipt_confirmations,
+			confirmation: None,
+			reverts: None,
+			unpaused: false,
+			progress: None,
+			completed: false,
+		}
+	}
+
+	/// Tracks `progress` of the batch, which events are relayed.
+	fn with_progress(mut self, progress: BatchProgress) -> Self {
+		self.progress = Some(progress);
+		self
+	}
+
+	/// Entries, which have not been relayed yet. Submitted transactions are not relayed until they are confirmed.
+	fn pending(&self) -> &[JournalEntry] {
+		match (&self.submission, &self.confirmation) {
+			_ if self.completed => &[],
+			(&None, _) => &self.entries,
+			(&Some((_, ref pending)), &None) => &pending.entries[pending.recorded..],
+			(&Some((_, ref pending)), &Some(_)) => &pending.entries,
+		}
+	}
+
+	/// Splits off the entries, which the balance of the account sending them doesn't cover at their journaled gas prices.
+	/// Balance is checked only if it's monitored, and the transactions are not sent from the submission pool.
+	fn hold_unaffordable(&mut self) -> Vec<JournalEntry> {
+		let (ref app, chain) = self.context;
+		if !node(&self.context).pool.is_empty() {
+			return Vec::new();
+		}
+
+		let held = app.funds.split_unaffordable(chain, &mut self.entries);
+		if !held.is_empty() {
+			error!(target: "bridge", "Balance of {} account {:?} covers only {} of {} relays, holding the rest until it is sufficient", chain.as_str(), node(&self.context).account, self.entries.len(), self.entries.len() + held.len());
+		}
+		held
+	}
+
+	fn submit(&mut self, nonce: Option<U256>) -> (SubmitRelays<T>, PendingRelays) {
+		let node = node(&self.context);
+		let mut requests: Vec<_> = self.entries.iter().map(|entry| relay_request(node, entry)).collect();
+		let limit = match nonce {
+			Some(nonce) => {
+				info!(target: "bridge", "Submitting {} relays with nonces starting at {}", requests.len(), nonce.low_u64());
+				for (index, request) in requests.iter_mut().enumerate() {
+					request.nonce = Some((nonce.low_u64() + index as u64).into());
+				}
+				// transactions with assigned nonces don't need to wait for each other,
+				// the limit must be positive even if every entry has been held
+				cmp::max(requests.len(), 1)
+			},
+			// nonces managed by the bridge are assigned without waiting for the node
+			None if self.sequence_nonces && node.manages_nonces() => cmp::max(requests.len(), 1),
+			None => node.max_concurrent_requests,
+		};
+
+		let future = api::limited(self.context.clone(), requests, limit, submit_relay);
+		(future, PendingRelays::new(self.entries.split_off(0)))
+	}
+
+	fn confirm(&self, hashes: Vec<H256>, confirmations: u64) -> ReceiptConfirmations<T> {
+		let (ref app, chain) = self.context;
+		let (transport, blocks) = (app.connection(chain), app.blocks(chain));
+		let node = node(&self.context);
+		let init = ReceiptConfirmationsInit {
+			request_timeout: node.request_timeout,
+			retry: node.retry.clone(),
+			confirmations,
+		};
+		api::receipt_confirmations(transport.clone(), app.timer.clone(), blocks.clone(), hashes, init)
+	}
+
+	/// Replays the `reverted` transactions in the blocks they have been mined in, to find the reasons of the reverts.
+	fn replay(&self, reverted: Vec<RevertedTransaction>) -> ReplayReverts<T> {
+		let (ref app, chain) = self.context;
+		let node = node(&self.context);
+		let (ref future, ref pending) = *self.submission.as_ref().expect("transactions are confirmed once submitted; qed");
+		let mut submitted: HashMap<H256, JournalEntry> = future.completed().iter()
+			.zip(&pending.entries)
+			.filter_map(|(result, entry)| result.as_ref().ok().map(|submitted| (submitted.hash, entry.clone())))
+			.collect();
+
+		let reverted: Vec<_> = reverted.into_iter()
+			.map(|transaction| (submitted.remove(&transaction.hash).expect("only submitted transactions are confirmed; qed"), transaction))
+			.collect();
+		let replays = reverted.iter()
+			.map(|&(ref entry, ref transaction)| {
+				let call = api::call_at(app.connection(chain).clone(), node.account, entry.to, entry.data.clone(), transaction.block)
+					.retry(&app.timer, node.request_timeouts.call, &node.retry);
+				api::revert_reason(call)
+			})
+			.collect();
+		(reverted, join_all(replays))
+	}
+
+	/// Skips the entries, which would be reverted or have the same payload as a transaction sent recently,
+	/// moving journaled ones to `discarded`. Then moves the entries to `journal` and submits them once they are persisted,
+	/// holding those the authority cannot pay for in `retries`.
+	/// Every submitted transaction is moved to `relays`, even before it is confirmed,
+	/// and every failed one to `retries`. Confirmed transactions, which have been reverted,
+	/// are moved to `reverted`, and their entries back to `journal` and to `retries`.
+	fn poll(&mut self, journal: &mut Vec<JournalEntry>, relays: &mut Vec<RelayOutcome>, discarded: &mut Vec<ProcessedEvent>, reverted: &mut Vec<RevertedRelay>, retries: &mut RetryQueue<T>) -> Poll<(), Error> {
+		if let Some(ref mut simulation) = self.simulation {
+			let reverted = try_ready!(simulation.poll());
+			let mut reverted = reverted.into_iter();
+			let journaled = self.journaled;
+			self.entries.retain(|entry| {
+				if !reverted.next().expect("every entry is simulated; qed") {
+					return true;
+				}
+
+				info!(target: "bridge", "Relay of {:?} would be reverted, it has probably been submitted already, skipping", entry.event.transaction_hash);
+				if journaled {
+					discarded.push(entry.event);
+				}
+				false
+			});
+		}
+		self.simulation = None;
+
+		if self.submission.is_none() {
+			let (ref app, chain) = self.context;
+			let now = unix_time();
+			let journaled = self.journaled;
+			self.entries.retain(|entry| {
+				let sent = match app.payloads.duplicate(chain, entry.to, &entry.data, now) {
+					Some(sent) => sent,
+					None => return true,
+				};
+
+				warn!(target: "bridge", "Relay of {:?} has the same payload as {:?} sent to {} {} seconds ago, skipping", entry.event.transaction_hash, sent.transaction_hash, chain.as_str(), now.saturating_sub(sent.timestamp));
+				if journaled {
+					discarded.push(entry.event);
+				}
+				false
+			});
+
+			if app.dry_run.is_some() {
+				for entry in self.entries.drain(..) {
+					info!(target: "bridge", "Dry run: relay of {:?} to {} contract {:?} with {} gas at gas price {} is not sent, data: {:?}", entry.event.transaction_hash, chain.as_str(), entry.to, entry.gas, entry.gas_price, entry.data);
+					if journaled {
+						discarded.push(entry.event);
+					}
+				}
+				return Ok(Async::Ready(()));
+			}
+
+			// every entry may have been skipped, or the batch may have had none
+			if self.entries.is_empty() {
+				return Ok(Async::Ready(()));
 			}
+
+			if !self.unpaused {
+				try_ready!(poll_submittable(&self.context));
+				self.unpaused = true;
+			}
+			if self.sequence_nonces && self.entries.len() > 1 && self.nonce.is_none() && !node(&self.context).manages_nonces() {
+				self.nonce = Some(next_nonce(&self.context, node(&self.context).account));
+			}
+			let nonce = match self.nonce {
+				Some(ref mut nonce) => Some(try_ready!(nonce.poll())),
+				None => None,
+			};
+
+			let held = self.hold_unaffordable();
+			if !self.journaled {
+				journal.extend(self.entries.iter().cloned());
+				journal.extend(held.iter().cloned());
+			}
+			for entry in held {
+				retries.resubmit(entry);
+			}
+			// the held entries are submitted by the retries, once the balance is sufficient
+			if self.entries.is_empty() {
+				return Ok(Async::Ready(()));
+			}
+
+			if !self.journaled {
+				self.submission = Some(self.submit(nonce));
+				// the journal is written before this relay is polled again
+				task::current().notify();
+				return Ok(Async::NotReady);
+			}
+			self.submission = Some(self.submit(nonce));
 		}
 
-		let file = fs::OpenOptions::new()
-			.write(true)
-			.create(true)
-			.open(&self.path)?;
+		if self.confirmation.is_none() {
+			let mut failed = Vec::new();
+			let submitted = relays.len();
+			let result = {
+				let (ref mut future, ref mut pending) = *self.submission.as_mut().expect("submission is created above; qed");
+				let result = pending.poll(future, relays, &mut failed);
+				// transactions, which are waited for to be confirmed, don't expire
+				if self.receipt_confirmations.is_none() {
+					for relay in &relays[submitted..] {
+						let entry = pending.entries.iter().find(|entry| entry.event == relay.event).expect("relays are recorded for pending entries; qed");
+						retries.watch(entry, relay.transaction_hash);
+					}
+				}
+				result
+			};
+			if let Some(ref progress) = self.progress {
+				for relay in &mut relays[submitted..] {
+					relay.block = progress.blocks.get(&relay.event).cloned();
+				}
+			}
+			for (entry, failure) in failed {
+				retries.push(entry, 1, failure)?;
+			}
+			let hashes = try_ready!(result);
+
+			match self.receipt_confirmations {
+				Some(confirmations) => self.confirmation = Some(self.confirm(hashes, confirmations)),
+				None => return Ok(Async::Ready(())),
+			}
+		}
+
+		if self.reverts.is_none() {
+			let transactions = try_ready!(self.confirmation.as_mut().expect("confirmation is created above; qed").poll());
+			self.reverts = Some(self.replay(transactions));
+		}
+
+		let (ref transactions, ref mut replays) = *self.reverts.as_mut().expect("replays are created above; qed");
+		let reasons = try_ready!(replays.poll());
+		let timestamp = unix_time();
+		for (&(ref entry, transaction), reason) in transactions.iter().zip(reasons) {
+			let message = match reason {
+				Some(ref reason) => format!("transaction {:?} has been reverted: {}", transaction.hash, reason),
+				None => format!("transaction {:?} has been reverted", transaction.hash),
+			};
+			reverted.push(RevertedRelay {
+				chain: self.context.1,
+				transaction_hash: transaction.hash,
+				reason,
+				timestamp,
+				event: entry.event,
+			});
+			// the relay is removed once the revert is recorded, so the entry is journaled again
+			journal.push(entry.clone());
+			retries.push(entry.clone(), 1, SubmitFailure {
+				class: ErrorClass::Reverted,
+				message,
+			})?;
+		}
+		Ok(Async::Ready(()))
+	}
+
+	/// Polls the submission, returning the last block of the batch, which events have all been relayed,
+	/// every time it advances. Resolves to `None` once the submission has completed.
+	///
+	/// Failed transactions are checked, since they are retried from the journal.
+	fn poll_checked(&mut self, journal: &mut Vec<JournalEntry>, relays: &mut Vec<RelayOutcome>, discarded: &mut Vec<ProcessedEvent>, reverted: &mut Vec<RevertedRelay>, retries: &mut RetryQueue<T>) -> Poll<Option<u64>, Error> {
+		if !self.completed {
+			self.completed = self.poll(journal, relays, discarded, reverted, retries)?.is_ready();
+		}
 
-		self.database.save(file)
+		let mut progress = self.progress.take();
+		let checked = progress.as_mut().and_then(|progress| progress.advance(self.pending()));
+		self.progress = progress;
+
+		match checked {
+			Some(block) => Ok(Async::Ready(Some(block))),
+			None if self.completed => Ok(Async::Ready(None)),
+			None => Ok(Async::NotReady),
+		}
 	}
 }
 
-enum BridgeStatus {
-	Wait,
-	NextItem(Option<()>),
+/// Resolves once transactions may be submitted to the chain of the `context`, i.e. the bridge is unpaused,
+/// the gas price of the chain doesn't exceed its `max_gas_price` and the authority has enough funds on the chain.
+fn poll_submittable<T: PubSub + Clone>(context: &(Arc<App<T>>, Chain)) -> Poll<(), Error> {
+	try_ready!(context.0.pause.poll_unpaused());
+	try_ready!(context.0.gas_prices.poll_acceptable(context.1));
+	Ok(context.0.funds.poll_sufficient(context.1))
+}
+
+/// State of the relay transaction, which has failed to be submitted or has been held.
+enum RetryState<T: PubSub> {
+	/// Waiting before the next attempt.
+	Backoff(Sleep),
+	/// Waiting until the bridge is unpaused, the gas price is acceptable and the authority can pay for the transaction.
+	Paused,
+	/// Submitting the transaction again.
+	Submit(Box<SubmitRelay<T>>),
+}
+
+struct FailedRelay<T: PubSub> {
+	entry: JournalEntry,
+	failures: u32,
+	state: RetryState<T>,
+}
+
+/// Relay transactions, which have failed to be submitted. They are retried with backoff
+/// according to the retry policies of the node, while the relay stream continues with new blocks.
+/// Relays, which are held because the authority cannot pay for them, are submitted once it can.
+///
+/// Failed transactions stay in the journal until they are relayed, so they are retried after restart as well.
+///
+/// If `expire_relays_after` is configured, the submitted transactions are watched until they are mined,
+/// and the relays of the expired ones are submitted again.
+struct RetryQueue<T: PubSub> {
+	context: (Arc<App<T>>, Chain),
+	failed: Vec<FailedRelay<T>>,
+	expiring: Option<ExpiringRelays<T>>,
+}
+
+impl<T: PubSub + Clone> RetryQueue<T> {
+	fn new(app: &Arc<App<T>>, chain: Chain) -> Self {
+		RetryQueue {
+			context: (app.clone(), chain),
+			failed: Vec::new(),
+			expiring: app.config.expire_relays_after.map(|blocks| ExpiringRelays::new(app, chain, blocks)),
+		}
+	}
+
+	/// Number of relays waiting to be submitted again.
+	fn len(&self) -> usize {
+		self.failed.len()
+	}
+
+	/// Watches the transaction with `hash`, which has relayed `entry`, until it's mined or expires.
+	fn watch(&mut self, entry: &JournalEntry, hash: H256) {
+		if let Some(ref mut expiring) = self.expiring {
+			expiring.watch(entry.clone(), hash);
+		}
+	}
+
+	/// Schedules the next attempt of the relay, which has failed `failures` times.
+	/// Fails if the relay should not be attempted again.
+	fn push(&mut self, entry: JournalEntry, failures: u32, failure: SubmitFailure) -> Result<(), Error> {
+		let policy = node(&self.context).retry.get(failure.class);
+		if failures >= policy.max_attempts {
+			return Err(format!("Relay of {:?} failed {} times ({}): {}", entry.event.transaction_hash, failures, failure.class.name(), failure.message).into());
+		}
+
+		let delay = api::retry_delay(policy, failures);
+		warn!(target: "bridge", "Relay of {:?} failed ({}/{}, {}): {}, retrying in {:?}", entry.event.transaction_hash, failures, policy.max_attempts, failure.class.name(), failure.message, delay);
+		self.failed.push(FailedRelay {
+			entry,
+			failures,
+			state: RetryState::Backoff(self.context.0.timer.sleep(delay)),
+		});
+		Ok(())
+	}
+
+	/// Submits the relay again once transactions may be submitted, e.g. the relay which the authority
+	/// cannot pay for until the balance is sufficient.
+	fn resubmit(&mut self, entry: JournalEntry) {
+		self.failed.push(FailedRelay {
+			entry,
+			failures: 0,
+			state: RetryState::Paused,
+		});
+	}
+
+	/// Retries the failed relays, which are due, and moves the submitted transactions to `relays`.
+	/// Expired transactions are moved to `reverted`, and their entries back to `journal` and submitted again.
+	fn poll(&mut self, relays: &mut Vec<RelayOutcome>, journal: &mut Vec<JournalEntry>, reverted: &mut Vec<RevertedRelay>) -> Result<(), Error> {
+		for mut relay in mem::take(&mut self.failed) {
+			let done = loop {
+				let next_state = match relay.state {
+					RetryState::Backoff(ref mut sleep) => match sleep.poll()? {
+						Async::Ready(()) => RetryState::Paused,
+						Async::NotReady => break false,
+					},
+					RetryState::Paused => match poll_submittable(&self.context)? {
+						Async::Ready(()) => RetryState::Submit(Box::new(submit_relay(&self.context, relay_request(node(&self.context), &relay.entry)))),
+						Async::NotReady => break false,
+					},
+					RetryState::Submit(ref mut future) => match future.poll()? {
+						Async::Ready(Ok(submitted)) => {
+							if relay.failures == 0 {
+								info!(target: "bridge", "Relay of {:?} submitted again", relay.entry.event.transaction_hash);
+							} else {
+								info!(target: "bridge", "Relay of {:?} submitted after {} failures", relay.entry.event.transaction_hash, relay.failures);
+							}
+							relays.push(relayed(&relay.entry, submitted, unix_time()));
+							self.watch(&relay.entry, submitted.hash);
+							break true;
+						},
+						Async::Ready(Err(failure)) => {
+							let failures = relay.failures + 1;
+							self.push(relay.entry.clone(), failures, failure)?;
+							break true;
+						},
+						Async::NotReady => break false,
+					},
+				};
+				relay.state = next_state;
+			};
+
+			if !done {
+				self.failed.push(relay);
+			}
+		}
+
+		let expired = match self.expiring {
+			Some(ref mut expiring) => expiring.poll()?,
+			None => return Ok(()),
+		};
+		if !expired.is_empty() {
+			// the entries are journaled again before they are submitted
+			task::current().notify();
+		}
+		for (entry, relay) in expired {
+			reverted.push(relay);
+			journal.push(entry.clone());
+			self.resubmit(entry);
+		}
+		Ok(())
+	}
+}
+
+/// Relay stream persisting its checkpoint as soon as it yields a checked block,
+/// so that it is never held back by the other streams.
+///
+/// Checkpoints of the streams replaying the past blocks are not persisted.
+/// Checkpoints and relays are recorded in the `metrics` of the bridge.
+struct CheckpointWriter<S> {
+	stream: S,
+	checkpoint: fn(u64) -> BridgeChecked,
+	persist: bool,
+	metrics: Arc<BridgeMetrics>,
+}
+
+impl<S: Relay> CheckpointWriter<S> {
+	fn new(stream: S, checkpoint: fn(u64) -> BridgeChecked, persist: bool, metrics: Arc<BridgeMetrics>) -> Self {
+		CheckpointWriter {
+			stream,
+			checkpoint,
+			persist,
+			metrics,
+		}
+	}
+
+	/// Name of the component.
+	fn stream(&self) -> &'static str {
+		(self.checkpoint)(0).stream()
+	}
+
+	/// Relays are handed over to the verification, if they are `submitted` to it,
+	/// and to the fee accounting.
+	fn poll<B: DatabaseBackend>(&mut self, backend: &mut B, mut submitted: Option<&mut Submitted>, relayed: &mut Relayed) -> Poll<Option<BridgeChecked>, Error> {
+		let result = self.stream.poll();
+		let assignments = self.stream.take_assignments();
+		if !assignments.is_empty() {
+			backend.write_assignments(&assignments)?;
+		}
+
+		let held = self.stream.take_held();
+		if !held.is_empty() {
+			backend.hold_relays(&held)?;
+		}
+
+		// relays of the reverted transactions are removed before their events are journaled again
+		let reverted = self.stream.take_reverted();
+		if !reverted.is_empty() {
+			backend.record_reverts(&reverted)?;
+			self.metrics.record_failures(self.stream(), &reverted);
+		}
+
+		let journal = self.stream.take_journal();
+		if !journal.is_empty() {
+			backend.write_journal(&journal)?;
+			if let Some(ref mut submitted) = submitted {
+				submitted.journal(&journal);
+			}
+		}
+
+		// the assignments are discarded only once their relays are journaled or held
+		let discarded_assignments = self.stream.take_discarded_assignments();
+		if !discarded_assignments.is_empty() {
+			backend.discard_assignments(&discarded_assignments)?;
+		}
+
+		let released = self.stream.take_released();
+		if !released.is_empty() {
+			backend.release_relays(&released)?;
+		}
+
+		// relays are persisted even if the relay of the next event failed
+		let relays = self.stream.take_relays();
+		if !relays.is_empty() {
+			backend.record_relays(&relays)?;
+			if let Some(ref mut submitted) = submitted {
+				submitted.relayed(&relays);
+			}
+			relayed.record(self.stream(), &relays);
+			self.metrics.record_relays(self.stream(), &relays);
+		}
+
+		let discarded = self.stream.take_discarded();
+		if !discarded.is_empty() {
+			backend.discard_journal(&discarded)?;
+			if let Some(ref mut submitted) = submitted {
+				submitted.discard(&discarded);
+			}
+		}
+
+		let quarantined = self.stream.take_quarantined();
+		if !quarantined.is_empty() {
+			backend.quarantine_logs(&quarantined)?;
+		}
+
+		let filtered = self.stream.take_filtered_deposits();
+		if !filtered.is_empty() {
+			backend.filter_deposits(&filtered)?;
+		}
+
+		let checked = (self.checkpoint)(try_stream!(result));
+		if self.persist {
+			backend.update_checkpoints(&[checked])?;
+		}
+		self.metrics.update_checkpoint(checked.stream(), checked.block());
+		Ok(Async::Ready(Some(checked)))
+	}
 }
 
-/// Creates new bridge.
-pub fn create_bridge<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> Bridge<T, FileBackend> {
-	let backend = FileBackend {
-		path: app.database_path.clone(),
-		database: init.clone(),
+/// Polls the relay stream, unless it's disabled or has ended.
+///
+/// If the bridge runs `once`, the stream, which has ended, is dropped and its failed relays
+/// are added to `failed`. Otherwise the bridge ends with it.
+fn poll_relay<S: Relay, B: DatabaseBackend>(relay: &mut Option<CheckpointWriter<S>>, backend: &mut B, submitted: Option<&mut Submitted>, relayed: &mut Relayed, failed: Option<&mut usize>) -> Poll<Option<BridgeChecked>, Error> {
+	let result = match *relay {
+		Some(ref mut writer) => writer.poll(backend, submitted, relayed)?,
+		None => return Ok(Async::NotReady),
 	};
 
-	create_bridge_backed_by(app, init, backend)
+	match (result, failed) {
+		(Async::Ready(None), Some(failed)) => {
+			let writer = relay.take().expect("only enabled streams are polled; qed");
+			let failures = writer.stream.failed_relays();
+			if failures > 0 {
+				warn!(target: "bridge", "{} has ended with {} relays waiting to be submitted again, they are retried on the next run", writer.stream(), failures);
+			} else {
+				info!(target: "bridge", "{} has ended", writer.stream());
+			}
+			*failed += failures;
+			Ok(Async::NotReady)
+		},
+		(result, _) => Ok(result),
+	}
 }
 
-/// Creates new bridge writing to custom backend.
-pub fn create_bridge_backed_by<T: Transport + Clone, F: BridgeBackend>(app: Arc<App<T>>, init: &Database, backend: F) -> Bridge<T, F> {
+enum BridgeStatus {
+	Wait,
+	NextItem(Option<()>),
+}
+
+/// Creates new bridge writing checked blocks to the database `backend`.
+/// Only the streams enabled in the config are run.
+/// Deposit and withdraw relays are verified if `verify_relays_after` is configured.
+/// Balances of the authorities are monitored if `min_balance` is configured.
+/// Fees of the relay transactions are accounted once they are mined.
+/// Payloads of the relay transactions are recorded if `duplicate_window` is configured.
+/// If the app runs `once`, the bridge ends once all streams have ended, failing if any relay has failed.
+/// Checkpoints are not written if the streams replay the blocks `until` a past one.
+/// Progress of the streams, their relays and fees, and the balances are recorded in the metrics of the app.
+pub fn create_bridge<T: BatchTransport + PubSub + Clone, B: DatabaseBackend>(app: Arc<App<T>>, init: &Database, backend: B) -> Bridge<T, B> {
+	app.payloads.load(&init.sent_payloads);
+	let streams = &app.config.streams;
+	let persist = app.until.is_none();
+	let metrics = app.metrics.bridge.clone();
+	let checked = checkpoints(&app.config, init);
+	for &(checkpoint, chain) in &checked {
+		metrics.record_checkpoint(checkpoint.stream(), chain, checkpoint.block());
+	}
 	Bridge {
-		deposit_relay: create_deposit_relay(app.clone(), init),
-		withdraw_relay: create_withdraw_relay(app.clone(), init),
-		withdraw_confirm: create_withdraw_confirm(app.clone(), init),
+		deposit_relay: if streams.deposit_relay {
+			Some(CheckpointWriter::new(create_deposit_relay(app.clone(), init), BridgeChecked::DepositRelay, persist, metrics.clone()))
+		} else {
+			None
+		},
+		withdraw_relay: if streams.withdraw_relay {
+			Some(CheckpointWriter::new(create_withdraw_relay(app.clone(), init), BridgeChecked::WithdrawRelay, persist, metrics.clone()))
+		} else {
+			None
+		},
+		withdraw_confirm: if streams.withdraw_confirm {
+			Some(CheckpointWriter::new(create_withdraw_confirm(app.clone(), init), BridgeChecked::WithdrawConfirm, persist, metrics.clone()))
+		} else {
+			None
+		},
+		mainnet_message_relay: if streams.mainnet_message_relay {
+			Some(CheckpointWriter::new(create_message_relay(app.clone(), init, Chain::Mainnet), BridgeChecked::MainnetMessageRelay, persist, metrics.clone()))
+		} else {
+			None
+		},
+		testnet_message_relay: if streams.testnet_message_relay {
+			Some(CheckpointWriter::new(create_message_relay(app.clone(), init, Chain::Testnet), BridgeChecked::TestnetMessageRelay, persist, metrics.clone()))
+		} else {
+			None
+		},
+		verification: if app.config.verify_relays_after.is_some() {
+			Some(create_relay_verification(app.clone(), init))
+		} else {
+			None
+		},
+		balances: if app.config.min_balance.is_some() {
+			Some(create_balance_monitor(app.clone()))
+		} else {
+			None
+		},
+		fees: create_fee_accounting(app.clone()),
 		state: BridgeStatus::Wait,
+		retention: app.config.database_retention.clone(),
+		checked,
+		pruned_at: 0,
+		failed: if app.once {
+			Some(0)
+		} else {
+			None
+		},
 		backend,
+		app,
 	}
 }
 
-pub struct Bridge<T: Transport, F> {
-	deposit_relay: DepositRelay<T>,
-	withdraw_relay: WithdrawRelay<T>,
-	withdraw_confirm: WithdrawConfirm<T>,
+pub struct Bridge<T: BatchTransport + PubSub + Clone, B> {
+	/// Streams, which are disabled, are `None`.
+	deposit_relay: Option<CheckpointWriter<DepositRelay<T>>>,
+	withdraw_relay: Option<CheckpointWriter<WithdrawRelay<T>>>,
+	withdraw_confirm: Option<CheckpointWriter<WithdrawConfirm<T>>>,
+	mainnet_message_relay: Option<CheckpointWriter<MessageRelay<T>>>,
+	testnet_message_relay: Option<CheckpointWriter<MessageRelay<T>>>,
+	verification: Option<RelayVerification<T>>,
+	balances: Option<BalanceMonitor<T>>,
+	fees: FeeAccounting<T>,
 	state: BridgeStatus,
-	backend: F,
+	retention: RetentionPolicy,
+	/// Checkpoints of the enabled streams with the chains they read events from.
+	checked: Vec<(BridgeChecked, Chain)>,
+	/// Unix time of the last pruning.
+	pruned_at: u64,
+	/// Relays failed by the streams, which have ended, if the bridge runs `once`.
+	failed: Option<usize>,
+	backend: B,
+	app: Arc<App<T>>,
+}
+
+impl<T: BatchTransport + PubSub + Clone, B: DatabaseBackend> Bridge<T, B> {
+	fn prune(&mut self) -> Result<(), Error> {
+		let now = unix_time();
+		if self.retention.is_empty() || now < self.pruned_at + PRUNE_INTERVAL_SECS {
+			return Ok(());
+		}
+
+		let pruned = self.backend.prune(&self.retention, now, &checked_blocks(&self.checked))?;
+		if pruned > 0 {
+			info!(target: "bridge", "Pruned {} relays", pruned);
+		}
+		self.pruned_at = now;
+		Ok(())
+	}
+
+	/// Updates the balances of the authorities, which are required by the relay submissions.
+	fn monitor_balances(&mut self) -> Result<(), Error> {
+		let balances = match self.balances {
+			Some(ref mut balances) => balances,
+			None => return Ok(()),
+		};
+
+		while let Async::Ready(Some(balance)) = balances.poll()? {
+			self.app.metrics.bridge.record_balance(balance.chain, balance.account, balance.balance);
+		}
+		Ok(())
+	}
+
+	/// Records relays, which have not taken effect.
+	fn verify(&mut self) -> Result<(), Error> {
+		let verification = match self.verification {
+			Some(ref mut verification) => verification,
+			None => return Ok(()),
+		};
+
+		while let Async::Ready(Some(verified)) = verification.poll()? {
+			if let Verified::Discrepancy(discrepancy) = verified {
+				self.backend.record_discrepancies(&[discrepancy])?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Records payloads of the relay transactions sent since the last call, and forgets the expired ones.
+	fn record_payloads(&mut self) -> Result<(), Error> {
+		let (payloads, expired) = self.app.payloads.take_sent(unix_time());
+		if !payloads.is_empty() {
+			self.backend.record_payloads(&payloads, expired)?;
+		}
+		Ok(())
+	}
+
+	/// Returns true if all streams are disabled or have ended.
+	fn ended(&self) -> bool {
+		self.deposit_relay.is_none() && self.withdraw_relay.is_none() && self.withdraw_confirm.is_none() &&
+			self.mainnet_message_relay.is_none() && self.testnet_message_relay.is_none()
+	}
+
+	/// Records fees of the mined relay transactions.
+	fn account_fees(&mut self) -> Result<(), Error> {
+		while let Async::Ready(Some(fees)) = self.fees.poll()? {
+			self.app.metrics.bridge.record_fees(&fees.stream, fees.chain, fees.gas_used, saturating_u128(fees.fees));
+			self.backend.record_fees(&[fees])?;
+		}
+		Ok(())
+	}
+
+	/// Records the best blocks of the chains, so that the lag of the streams is known.
+	fn record_heads(&self) {
+		for chain in &[Chain::Mainnet, Chain::Testnet] {
+			let head = self.app.blocks(*chain).best_block().number;
+			// the block is not known until it's fetched
+			if head > 0 {
+				self.app.metrics.bridge.record_head(*chain, head);
+			}
+		}
+	}
 }
 
-impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
+impl<T: BatchTransport + PubSub + Clone, B: DatabaseBackend> Stream for Bridge<T, B> {
 	type Item = ();
 	type Error = Error;
 
@@ -101,19 +1307,36 @@ impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
 		loop {
 			let next_state = match self.state {
 				BridgeStatus::Wait => {
-					let d_relay = try_bridge!(self.deposit_relay.poll()).map(BridgeChecked::DepositRelay);
-					let w_relay = try_bridge!(self.withdraw_relay.poll()).map(BridgeChecked::WithdrawRelay);
-					let w_confirm = try_bridge!(self.withdraw_confirm.poll()).map(BridgeChecked::WithdrawConfirm);
+					// balances are updated before the relays, which are suspended until the balances are sufficient
+					self.monitor_balances()?;
+					// every checkpoint is already persisted when the next stream is polled
+					let d_relay = try_bridge!(poll_relay(&mut self.deposit_relay, &mut self.backend, self.verification.as_mut().map(RelayVerification::submitted), self.fees.relayed(), self.failed.as_mut()));
+					let w_relay = try_bridge!(poll_relay(&mut self.withdraw_relay, &mut self.backend, self.verification.as_mut().map(RelayVerification::submitted), self.fees.relayed(), self.failed.as_mut()));
+					let w_confirm = try_bridge!(poll_relay(&mut self.withdraw_confirm, &mut self.backend, None, self.fees.relayed(), self.failed.as_mut()));
+					// messages are not verified, because contracts passing them may accept them repeatedly
+					let m_relay = try_bridge!(poll_relay(&mut self.mainnet_message_relay, &mut self.backend, None, self.fees.relayed(), self.failed.as_mut()));
+					let t_relay = try_bridge!(poll_relay(&mut self.testnet_message_relay, &mut self.backend, None, self.fees.relayed(), self.failed.as_mut()));
+					for checkpoint in [d_relay, w_relay, w_confirm, m_relay, t_relay].iter().filter_map(|checkpoint| *checkpoint) {
+						if let Some(checked) = self.checked.iter_mut().find(|&&mut (known, _)| known.stream() == checkpoint.stream()) {
+							checked.0 = checkpoint;
+						}
+					}
+					self.record_payloads()?;
+					self.verify()?;
+					self.account_fees()?;
+					self.record_heads();
+					self.prune()?;
 
-					let result: Vec<_> = [d_relay, w_relay, w_confirm]
-						.into_iter()
-						.filter_map(|c| *c)
-						.collect();
+					// relays, which have not been verified or which fees have not been accounted yet, are dropped
+					match self.failed {
+						Some(0) if self.ended() => return Ok(Async::Ready(None)),
+						Some(failed) if self.ended() => return Err(format!("{} relays have failed, they are retried on the next run", failed).into()),
+						_ => {},
+					}
 
-					if result.is_empty() {
+					if d_relay.or(w_relay).or(w_confirm).or(m_relay).or(t_relay).is_none() {
 						return Ok(Async::NotReady);
 					} else {
-						self.backend.save(result)?;
 						BridgeStatus::NextItem(Some(()))
 					}
 				},
@@ -130,31 +1353,254 @@ impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
 
 #[cfg(test)]
 mod tests {
-	extern crate tempdir;
-	use self::tempdir::TempDir;
-	use database::Database;
-	use super::{BridgeBackend, FileBackend, BridgeChecked};
+	use futures::{self, Async, Future, Poll, Stream, stream};
+	use web3::types::{H256, Log};
+	use api::{limited, LogStreamItem};
+	use database::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain};
+	use config::RetentionPolicy;
+	use error::{Error, ErrorClass};
+	use super::{BridgeChecked, CheckpointWriter, Relay, Relayed, PendingRelays, BatchProgress, SubmitFailure, Submitted, poll_relay};
+
+	#[derive(Default)]
+	struct Checkpoints(Vec<BridgeChecked>, Vec<RelayOutcome>);
+
+	/// Relays single event with every checked block.
+	struct TestRelay<S>(S, Vec<RelayOutcome>);
+
+	impl<S: Stream<Item = u64, Error = Error>> Stream for TestRelay<S> {
+		type Item = u64;
+		type Error = Error;
+
+		fn poll(&mut self) -> Poll<Option<u64>, Error> {
+			let result = self.0.poll();
+			if let Ok(Async::Ready(Some(block))) = result {
+				self.1.push(relay(block));
+			}
+			result
+		}
+	}
+
+	impl<S: Stream<Item = u64, Error = Error>> Relay for TestRelay<S> {
+		fn take_relays(&mut self) -> Vec<RelayOutcome> {
+			self.1.split_off(0)
+		}
+
+		fn take_journal(&mut self) -> Vec<JournalEntry> {
+			vec![]
+		}
+
+		fn take_discarded(&mut self) -> Vec<ProcessedEvent> {
+			vec![]
+		}
+
+		fn take_quarantined(&mut self) -> Vec<QuarantinedLog> {
+			vec![]
+		}
+	}
+
+	fn event(log_index: u64) -> ProcessedEvent {
+		ProcessedEvent {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			log_index,
+		}
+	}
+
+	fn entry(log_index: u64) -> JournalEntry {
+		JournalEntry {
+			chain: Chain::Mainnet,
+			to: 2.into(),
+			data: vec![].into(),
+			gas: 0,
+			gas_price: 0,
+			event: event(log_index),
+		}
+	}
+
+	fn relay(log_index: u64) -> RelayOutcome {
+		RelayOutcome {
+			chain: Chain::Mainnet,
+			transaction_hash: log_index.into(),
+			gas_price: 0,
+			timestamp: 0,
+			block: None,
+			event: event(log_index),
+		}
+	}
+
+	impl DatabaseBackend for Checkpoints {
+		fn load(&mut self) -> Result<Option<Database>, Error> {
+			Ok(None)
+		}
+
+		fn save(&mut self, _database: &Database) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+			self.0.extend_from_slice(checks);
+			Ok(())
+		}
+
+		fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+			self.0.extend(change.checkpoint());
+			Ok(())
+		}
+
+		fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+			self.1.extend_from_slice(relays);
+			Ok(())
+		}
+
+		fn write_journal(&mut self, _entries: &[JournalEntry]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn discard_journal(&mut self, _events: &[ProcessedEvent]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn quarantine_logs(&mut self, _logs: &[QuarantinedLog]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn filter_deposits(&mut self, _deposits: &[FilteredDeposit]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn record_discrepancies(&mut self, _discrepancies: &[Discrepancy]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn record_reverts(&mut self, _reverts: &[RevertedRelay]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn record_fees(&mut self, _fees: &[RelayFees]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn record_payloads(&mut self, _payloads: &[SentPayload], _expired: u64) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn write_assignments(&mut self, _assignments: &[PendingAssignment]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn discard_assignments(&mut self, _events: &[ProcessedEvent]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn hold_relays(&mut self, _relays: &[HeldRelay]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn release_relays(&mut self, _events: &[ProcessedEvent]) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn prune(&mut self, _policy: &RetentionPolicy, _now: u64, _checked: &[(Chain, u64)]) -> Result<usize, Error> {
+			Ok(0)
+		}
+	}
+
+	#[test]
+	fn test_checkpoint_writer_persists_every_checked_block() {
+		let mut backend = Checkpoints::default();
+		let checked = stream::iter_result(vec![Ok(1), Ok(5), Err("relay failed".into())]);
+		let mut writer = CheckpointWriter::new(TestRelay(checked, vec![]), BridgeChecked::WithdrawConfirm, true, Default::default());
+		let mut relayed = Relayed::default();
+
+		assert_eq!(Async::Ready(Some(BridgeChecked::WithdrawConfirm(1))), writer.poll(&mut backend, None, &mut relayed).unwrap());
+		assert_eq!(Async::Ready(Some(BridgeChecked::WithdrawConfirm(5))), writer.poll(&mut backend, None, &mut relayed).unwrap());
+		assert!(writer.poll(&mut backend, None, &mut relayed).is_err());
+		assert_eq!(vec![BridgeChecked::WithdrawConfirm(1), BridgeChecked::WithdrawConfirm(5)], backend.0);
+		assert_eq!(vec![relay(1), relay(5)], backend.1);
+	}
+
+	#[test]
+	fn test_ended_relay_is_dropped_only_if_run_once() {
+		let mut backend = Checkpoints::default();
+		let mut relayed = Relayed::default();
+		let mut failed = 0;
+		let mut relay = Some(CheckpointWriter::new(TestRelay(stream::iter_result(vec![Ok(1)]), vec![]), BridgeChecked::DepositRelay, true, Default::default()));
+		assert_eq!(Async::Ready(Some(BridgeChecked::DepositRelay(1))), poll_relay(&mut relay, &mut backend, None, &mut relayed, Some(&mut failed)).unwrap());
+		assert_eq!(Async::NotReady, poll_relay(&mut relay, &mut backend, None, &mut relayed, Some(&mut failed)).unwrap());
+		assert!(relay.is_none());
+		assert_eq!(0, failed);
+
+		let mut relay = Some(CheckpointWriter::new(TestRelay(stream::iter_result(vec![Ok(1)]), vec![]), BridgeChecked::DepositRelay, true, Default::default()));
+		assert_eq!(Async::Ready(Some(BridgeChecked::DepositRelay(1))), poll_relay(&mut relay, &mut backend, None, &mut relayed, None).unwrap());
+		assert_eq!(Async::Ready(None), poll_relay(&mut relay, &mut backend, None, &mut relayed, None).unwrap());
+		assert!(relay.is_some());
+	}
 
 	#[test]
-	fn test_file_backend() {
-		let tempdir = TempDir::new("test_file_backend").unwrap();
-		let mut path = tempdir.path().to_owned();
-		path.push("db");
-		let mut backend = FileBackend {
-			path: path.clone(),
-			database: Database::default(),
+	fn test_pending_relays_are_recorded_in_order() {
+		// the second relay is submitted only after the third one has failed
+		let (second, submit_second) = futures::sync::oneshot::channel::<()>();
+		let failure = SubmitFailure {
+			class: ErrorClass::Network,
+			message: "connection reset".into(),
+		};
+		// the gas prices of the submitted transactions are recorded, rather than the journaled ones
+		let submitted = |hash: u64, gas_price: u64| Submitted {
+			hash: hash.into(),
+			gas_price,
+		};
+		let submissions: Vec<Box<Future<Item = Result<Submitted, SubmitFailure>, Error = ()>>> = vec![
+			Box::new(futures::finished(Ok(submitted(0, 0xa0)))),
+			Box::new(submit_second.map(move |_| Ok(submitted(1, 0xb0))).map_err(|_| ())),
+			Box::new(futures::finished(Err(failure.clone()))),
+		];
+		let mut future = limited((), submissions, 3, |_, submission| submission);
+		let mut pending = PendingRelays::new(vec![entry(0), entry(1), entry(2)]);
+		let mut relays = Vec::new();
+		let mut failed = Vec::new();
+
+		futures::lazy(|| {
+			assert_eq!(Ok(Async::NotReady), pending.poll(&mut future, &mut relays, &mut failed));
+			assert_eq!(vec![event(0)], relays.iter().map(|relay| relay.event).collect::<Vec<_>>());
+			assert!(failed.is_empty());
+
+			second.send(()).unwrap();
+			assert_eq!(Ok(Async::NotReady), pending.poll(&mut future, &mut relays, &mut failed));
+			assert_eq!(vec![event(0), event(1)], relays.iter().map(|relay| relay.event).collect::<Vec<_>>());
+			assert!(failed.is_empty());
+
+			assert_eq!(Ok(Async::NotReady), pending.poll(&mut future, &mut relays, &mut failed));
+			assert_eq!(Ok(Async::Ready(vec![0.into(), 1.into()])), pending.poll(&mut future, &mut relays, &mut failed));
+			Ok::<_, ()>(())
+		}).wait().unwrap();
+		assert_eq!(vec![event(0), event(1)], relays.iter().map(|relay| relay.event).collect::<Vec<_>>());
+		let hashes: Vec<H256> = vec![0.into(), 1.into()];
+		assert_eq!(hashes, relays.iter().map(|relay| relay.transaction_hash).collect::<Vec<_>>());
+		assert_eq!(vec![0xa0, 0xb0], relays.iter().map(|relay| relay.gas_price).collect::<Vec<_>>());
+		assert_eq!(vec![(entry(2), failure)], failed);
+	}
+
+	#[test]
+	fn test_batch_progress_is_checked_before_pending_events() {
+		let log = |log_index: u64, block: u64| Log {
+			transaction_hash: Some(1.into()),
+			log_index: Some(log_index.into()),
+			block_number: Some(block.into()),
+			..Default::default()
+		};
+		let item = LogStreamItem {
+			from: 0x10,
+			to: 0x30,
+			logs: vec![log(0, 0x10), log(1, 0x20), log(2, 0x20)],
 		};
+		let mut progress = BatchProgress::new(Chain::Testnet, &item);
 
-		backend.save(vec![BridgeChecked::DepositRelay(1)]).unwrap();
-		assert_eq!(1, backend.database.checked_deposit_relay);
-		assert_eq!(0, backend.database.checked_withdraw_confirm);
-		assert_eq!(0, backend.database.checked_withdraw_relay);
-		backend.save(vec![BridgeChecked::DepositRelay(2), BridgeChecked::WithdrawConfirm(3), BridgeChecked::WithdrawRelay(2)]).unwrap();
-		assert_eq!(2, backend.database.checked_deposit_relay);
-		assert_eq!(3, backend.database.checked_withdraw_confirm);
-		assert_eq!(2, backend.database.checked_withdraw_relay);
-
-		let loaded = Database::load(path).unwrap();
-		assert_eq!(backend.database, loaded);
+		assert_eq!(None, progress.advance(&[entry(0), entry(1), entry(2)]));
+		assert_eq!(Some(0x1f), progress.advance(&[entry(1), entry(2)]));
+		assert_eq!(None, progress.advance(&[entry(2)]));
+		// events from earlier batches hold the checkpoint
+		assert_eq!(None, progress.advance(&[entry(3)]));
+		assert_eq!(Some(0x30), progress.advance(&[]));
+		assert_eq!(None, progress.advance(&[]));
 	}
 }
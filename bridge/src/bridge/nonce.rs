@@ -0,0 +1,271 @@
+use std::cell::{Cell, RefCell};
+use std::{cmp, mem};
+use std::collections::{BTreeSet, HashMap};
+use futures::task::{self, Task};
+use web3::types::{Address, U256};
+use database::Chain;
+use error::ErrorClass;
+
+#[derive(Default)]
+struct AccountNonces {
+	/// Nonce of the next transaction, `None` until synchronized with the node.
+	next: Option<u64>,
+	/// Nonces of the transactions, which are being sent.
+	sending: BTreeSet<u64>,
+	/// Nonces of the transactions, which have failed to be sent. They are reused first, lowest one first,
+	/// since the node doesn't include the transactions following a gap in blocks.
+	gaps: BTreeSet<u64>,
+	/// Whether the nonces are synchronized with the node before the next one is assigned.
+	stale: bool,
+	/// Whether a submission is synchronizing the nonces.
+	syncing: bool,
+	/// Submissions waiting for the nonces to be synchronized.
+	waiting: Vec<Task>,
+}
+
+impl AccountNonces {
+	fn wake_waiting(&mut self) {
+		for task in mem::take(&mut self.waiting) {
+			task.notify();
+		}
+	}
+}
+
+/// Result of an attempt to assign a nonce.
+#[derive(Debug, PartialEq)]
+pub enum Assignment {
+	Assigned(U256),
+	/// Nonces should be synchronized by the caller first.
+	Sync,
+	/// Nonces are being synchronized by another submission, which wakes up the current task once done.
+	Wait,
+}
+
+/// Nonces of the transactions sent from the authority accounts, shared by all relay submissions.
+///
+/// Nonces are assigned by the bridge instead of the node, so that transactions submitted at once
+/// don't race for the same nonce. They are synchronized with the pending transaction count reported
+/// by the node first, and then assigned sequentially without asking the node again.
+///
+/// Only one submission synchronizes the nonces of an account at a time, the others wait for it,
+/// so that transactions submitted at once get their nonces in order.
+///
+/// Nonces are synchronized again after any transaction fails to be sent. Once no transaction is being sent,
+/// the node reporting fewer transactions than the bridge has assigned nonces to reveals gaps left by
+/// transactions, which have been dropped, and the nonces are assigned from the reported count again.
+///
+/// Every account of the submission pool of a chain has nonces of its own.
+#[derive(Default)]
+pub struct Nonces {
+	accounts: RefCell<HashMap<(Chain, Address), AccountNonces>>,
+	/// Index of the pool account, which is picked first by the next submission to the chain.
+	turns: [Cell<usize>; 2],
+}
+
+impl Nonces {
+	fn update<F, R>(&self, chain: Chain, account: Address, f: F) -> R where F: FnOnce(&mut AccountNonces) -> R {
+		let mut accounts = self.accounts.borrow_mut();
+		f(accounts.entry((chain, account)).or_insert_with(AccountNonces::default))
+	}
+
+	/// Picks the account of the `pool`, which sends the next transaction to `chain`.
+	/// Accounts take turns, but those with fewer transactions being sent go first,
+	/// so that transactions which are stuck don't hold back the others.
+	pub fn pick(&self, chain: Chain, pool: &[Address]) -> Address {
+		assert!(!pool.is_empty(), "pool contains at least the authority account; qed");
+		let turn = &self.turns[match chain {
+			Chain::Mainnet => 0,
+			Chain::Testnet => 1,
+		}];
+		let accounts = self.accounts.borrow();
+		let sending = |account: &Address| accounts.get(&(chain, *account)).map(|nonces| nonces.sending.len()).unwrap_or(0);
+		let first = turn.get() % pool.len();
+		let index = (0..pool.len())
+			.map(|offset| (first + offset) % pool.len())
+			.min_by_key(|index| sending(&pool[*index]))
+			.expect("pool is not empty; qed");
+		turn.set(index + 1);
+		pool[index]
+	}
+
+	/// Assigns the nonce of the next transaction sent from `account` to `chain`, unless the nonces should be synchronized first.
+	/// The current task is woken up once they are, if it's asked to `Wait`.
+	pub fn assign(&self, chain: Chain, account: Address) -> Assignment {
+		self.update(chain, account, |account| {
+			if account.syncing {
+				account.waiting.push(task::current());
+				return Assignment::Wait;
+			}
+			if account.stale || (account.next.is_none() && account.gaps.is_empty()) {
+				account.syncing = true;
+				return Assignment::Sync;
+			}
+
+			let nonce = match account.gaps.iter().next().cloned() {
+				Some(gap) => {
+					account.gaps.remove(&gap);
+					gap
+				},
+				None => {
+					let next = account.next.expect("nonces are synchronized; qed");
+					account.next = Some(next + 1);
+					next
+				},
+			};
+			account.sending.insert(nonce);
+			Assignment::Assigned(nonce.into())
+		})
+	}
+
+	/// Synchronizes the nonces of `account` on `chain` with the `pending` transaction count reported by the node,
+	/// then assigns the nonce of the next transaction.
+	pub fn sync(&self, chain: Chain, account: Address, pending: U256) -> U256 {
+		self.update(chain, account, |nonces| {
+			let pending = pending.low_u64();
+			nonces.stale = false;
+			nonces.syncing = false;
+			nonces.wake_waiting();
+			if nonces.sending.is_empty() {
+				if let Some(next) = nonces.next {
+					let dropped = (pending..next).filter(|nonce| !nonces.gaps.contains(nonce)).count();
+					if dropped > 0 {
+						warn!(target: "bridge", "Nonce gap of {:?} on {}: {} transactions before nonce {} have been dropped by the node, assigning nonces from {} again", account, chain.as_str(), dropped, next, pending);
+					}
+				}
+				nonces.next = Some(pending);
+				nonces.gaps.clear();
+			} else {
+				// transactions which are being sent may not be counted by the node yet
+				nonces.next = Some(cmp::max(nonces.next.unwrap_or(0), pending));
+				nonces.gaps = nonces.gaps.split_off(&pending);
+			}
+		});
+
+		match self.assign(chain, account) {
+			Assignment::Assigned(nonce) => nonce,
+			_ => unreachable!("nonces have just been synchronized; qed"),
+		}
+	}
+
+	/// Records that the nonces of `account` on `chain` have failed to be synchronized, so that another submission may try again.
+	pub fn sync_failed(&self, chain: Chain, account: Address) {
+		self.update(chain, account, |account| {
+			account.syncing = false;
+			account.wake_waiting();
+		})
+	}
+
+	/// Records that the transaction with `nonce` has been sent from `account` to `chain`.
+	pub fn sent(&self, chain: Chain, account: Address, nonce: U256) {
+		self.update(chain, account, |account| account.sending.remove(&nonce.low_u64()));
+	}
+
+	/// Records that the transaction with `nonce` has failed to be sent from `account` to `chain` with error of `class`.
+	/// Its nonce is reused, unless it has already been used by another transaction.
+	pub fn failed(&self, chain: Chain, account: Address, nonce: U256, class: ErrorClass) {
+		self.update(chain, account, |account| {
+			let nonce = nonce.low_u64();
+			account.sending.remove(&nonce);
+			if class != ErrorClass::NonceTooLow {
+				account.gaps.insert(nonce);
+			}
+			account.stale = true;
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::{Future, future};
+	use web3::types::Address;
+	use database::Chain;
+	use error::ErrorClass;
+	use super::{Nonces, Assignment};
+
+	const ACCOUNT: Address = Address([1; 20]);
+
+	#[test]
+	fn test_nonces_assigned_sequentially() {
+		let nonces = Nonces::default();
+		assert_eq!(Assignment::Sync, nonces.assign(Chain::Mainnet, ACCOUNT));
+		assert_eq!(5, nonces.sync(Chain::Mainnet, ACCOUNT, 5.into()).low_u64());
+		assert_eq!(Assignment::Assigned(6.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+		assert_eq!(Assignment::Sync, nonces.assign(Chain::Testnet, ACCOUNT));
+		assert_eq!(0, nonces.sync(Chain::Testnet, ACCOUNT, 0.into()).low_u64());
+		nonces.sent(Chain::Mainnet, ACCOUNT, 5.into());
+		nonces.sent(Chain::Mainnet, ACCOUNT, 6.into());
+		assert_eq!(Assignment::Assigned(7.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+	}
+
+	#[test]
+	fn test_nonces_synchronized_once() {
+		let nonces = Nonces::default();
+		assert_eq!(Assignment::Sync, nonces.assign(Chain::Mainnet, ACCOUNT));
+		let assignment = future::lazy(|| Ok::<_, ()>(nonces.assign(Chain::Mainnet, ACCOUNT))).wait().unwrap();
+		assert_eq!(Assignment::Wait, assignment);
+		nonces.sync_failed(Chain::Mainnet, ACCOUNT);
+		assert_eq!(Assignment::Sync, nonces.assign(Chain::Mainnet, ACCOUNT));
+		assert_eq!(3, nonces.sync(Chain::Mainnet, ACCOUNT, 3.into()).low_u64());
+		assert_eq!(Assignment::Assigned(4.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+	}
+
+	#[test]
+	fn test_nonces_reuse_gaps() {
+		let nonces = Nonces::default();
+		assert_eq!(5, nonces.sync(Chain::Mainnet, ACCOUNT, 5.into()).low_u64());
+		assert_eq!(Assignment::Assigned(6.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+		assert_eq!(Assignment::Assigned(7.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+		nonces.failed(Chain::Mainnet, ACCOUNT, 5.into(), ErrorClass::Timeout);
+		nonces.sent(Chain::Mainnet, ACCOUNT, 7.into());
+		// nonces are synchronized after failures
+		assert_eq!(Assignment::Sync, nonces.assign(Chain::Mainnet, ACCOUNT));
+		// the gap is filled first, while 6 is still being sent
+		assert_eq!(5, nonces.sync(Chain::Mainnet, ACCOUNT, 5.into()).low_u64());
+		assert_eq!(Assignment::Assigned(8.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+
+		// nonce taken by another transaction is not reused
+		nonces.failed(Chain::Mainnet, ACCOUNT, 8.into(), ErrorClass::NonceTooLow);
+		assert_eq!(9, nonces.sync(Chain::Mainnet, ACCOUNT, 9.into()).low_u64());
+	}
+
+	#[test]
+	fn test_nonces_detect_dropped_transactions() {
+		let nonces = Nonces::default();
+		assert_eq!(5, nonces.sync(Chain::Mainnet, ACCOUNT, 5.into()).low_u64());
+		assert_eq!(Assignment::Assigned(6.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+		assert_eq!(Assignment::Assigned(7.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+		nonces.sent(Chain::Mainnet, ACCOUNT, 5.into());
+		nonces.sent(Chain::Mainnet, ACCOUNT, 6.into());
+		nonces.failed(Chain::Mainnet, ACCOUNT, 7.into(), ErrorClass::Other);
+		// transactions 6 and 7 are not counted by the node, so 6 has been dropped
+		assert_eq!(6, nonces.sync(Chain::Mainnet, ACCOUNT, 6.into()).low_u64());
+		assert_eq!(Assignment::Assigned(7.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+
+		// transactions sent from the same account by someone else
+		nonces.failed(Chain::Mainnet, ACCOUNT, 6.into(), ErrorClass::NonceTooLow);
+		nonces.failed(Chain::Mainnet, ACCOUNT, 7.into(), ErrorClass::NonceTooLow);
+		assert_eq!(12, nonces.sync(Chain::Mainnet, ACCOUNT, 12.into()).low_u64());
+	}
+
+	#[test]
+	fn test_nonces_of_pool_accounts() {
+		let nonces = Nonces::default();
+		let other = Address::from([2; 20]);
+		let pool = [ACCOUNT, other];
+		// accounts take turns
+		assert_eq!(ACCOUNT, nonces.pick(Chain::Mainnet, &pool));
+		assert_eq!(5, nonces.sync(Chain::Mainnet, ACCOUNT, 5.into()).low_u64());
+		assert_eq!(other, nonces.pick(Chain::Mainnet, &pool));
+		assert_eq!(0, nonces.sync(Chain::Mainnet, other, 0.into()).low_u64());
+		assert_eq!(ACCOUNT, nonces.pick(Chain::Testnet, &pool));
+
+		// the account without transactions being sent goes first
+		nonces.sent(Chain::Mainnet, other, 0.into());
+		assert_eq!(other, nonces.pick(Chain::Mainnet, &pool));
+		assert_eq!(Assignment::Assigned(1.into()), nonces.assign(Chain::Mainnet, other));
+		nonces.sent(Chain::Mainnet, ACCOUNT, 5.into());
+		assert_eq!(ACCOUNT, nonces.pick(Chain::Mainnet, &pool));
+		// nonces of the accounts are independent
+		assert_eq!(Assignment::Assigned(6.into()), nonces.assign(Chain::Mainnet, ACCOUNT));
+	}
+}
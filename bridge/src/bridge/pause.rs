@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+use futures::{Future, Poll, Async};
+use futures::future::{JoinAll, join_all};
+use tokio_timer::{Sleep, Timer};
+use web3::types::{Address, Bytes};
+use api::{self, ApiCall};
+use config::{Config, Node, RetryPolicies};
+use contracts::pause;
+use database::Chain;
+use error::Error;
+use transport::PubSub;
+
+/// Contract of the pause switch on one of the chains.
+struct SwitchContract<T> {
+	transport: T,
+	address: Address,
+	request_timeout: Duration,
+	retry: RetryPolicies,
+}
+
+enum PauseState<T: PubSub> {
+	/// Waiting for the submissions to check the contracts again.
+	Idle,
+	/// Reading the flags of the contracts.
+	Check(JoinAll<Vec<ApiCall<Bytes, T>>>),
+	/// Waiting before the contracts, some of which are paused, are checked again.
+	Paused(Sleep),
+}
+
+struct Watcher<T: PubSub> {
+	contracts: Vec<SwitchContract<T>>,
+	poll_interval: Duration,
+	state: PauseState<T>,
+	/// Time of the last check, after which all of the contracts were unpaused.
+	unpaused_at: Option<Instant>,
+	paused: bool,
+	timer: Timer,
+}
+
+impl<T: PubSub + Clone> Watcher<T> {
+	fn check(&self) -> JoinAll<Vec<ApiCall<Bytes, T>>> {
+		let payload: Bytes = pause::Pausable::default().functions().paused().input().into();
+		let calls = self.contracts.iter()
+			.map(|contract| {
+				api::call(contract.transport.clone(), contract.address, payload.clone())
+					.retry(&self.timer, contract.request_timeout, &contract.retry)
+			})
+			.collect();
+		join_all(calls)
+	}
+}
+
+/// Watches the `paused()` flags of the configured `pause_switch` contracts.
+///
+/// The flags are not read more often than every `poll_interval`, and only when a transaction is about
+/// to be submitted. The watcher is driven by the submissions waiting for it.
+pub struct PauseWatcher<T: PubSub> {
+	/// `None` if no switch is configured.
+	watcher: Option<RefCell<Watcher<T>>>,
+}
+
+impl<T: PubSub + Clone> PauseWatcher<T> {
+	pub fn new(config: &Config, mainnet: T, testnet: T, timer: Timer) -> Self {
+		let switch = match config.pause_switch {
+			Some(switch) => switch,
+			None => return PauseWatcher { watcher: None },
+		};
+
+		let contract = |address: Address, transport: T, node: &Node| SwitchContract {
+			transport,
+			address,
+			request_timeout: node.request_timeouts.call,
+			retry: node.retry.clone(),
+		};
+		let contracts = switch.mainnet.map(|address| contract(address, mainnet, config.node(Chain::Mainnet))).into_iter()
+			.chain(switch.testnet.map(|address| contract(address, testnet, config.node(Chain::Testnet))))
+			.collect();
+
+		let watcher = Watcher {
+			contracts,
+			poll_interval: switch.poll_interval,
+			state: PauseState::Idle,
+			unpaused_at: None,
+			paused: false,
+			timer,
+		};
+		PauseWatcher {
+			watcher: Some(RefCell::new(watcher)),
+		}
+	}
+
+	/// Resolves once none of the contracts is paused. Submissions are suspended until then.
+	pub fn poll_unpaused(&self) -> Poll<(), Error> {
+		let mut watcher = match self.watcher {
+			Some(ref watcher) => watcher.borrow_mut(),
+			None => return Ok(Async::Ready(())),
+		};
+		let watcher = &mut *watcher;
+
+		loop {
+			let next_state = match watcher.state {
+				PauseState::Idle => match watcher.unpaused_at {
+					Some(at) if at.elapsed() < watcher.poll_interval => return Ok(Async::Ready(())),
+					_ => PauseState::Check(watcher.check()),
+				},
+				PauseState::Check(ref mut future) => {
+					let outputs = try_ready!(future.poll());
+					let mut paused = false;
+					for output in outputs {
+						paused |= pause::Pausable::default().functions().paused().output(&output.0)?;
+					}
+
+					if paused && !watcher.paused {
+						warn!(target: "bridge", "Bridge is paused, suspending relays until it's unpaused");
+					} else if !paused && watcher.paused {
+						info!(target: "bridge", "Bridge is unpaused, resuming relays");
+					}
+					watcher.paused = paused;
+
+					if !paused {
+						watcher.unpaused_at = Some(Instant::now());
+						watcher.state = PauseState::Idle;
+						return Ok(Async::Ready(()));
+					}
+					PauseState::Paused(watcher.timer.sleep(watcher.poll_interval))
+				},
+				PauseState::Paused(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					PauseState::Check(watcher.check())
+				},
+			};
+			watcher.state = next_state;
+		}
+	}
+}
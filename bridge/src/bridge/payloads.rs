@@ -0,0 +1,148 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use tiny_keccak::Keccak;
+use web3::types::{Address, Bytes, H256};
+use config::Config;
+use database::{SentPayload, Chain};
+
+fn data_hash(data: &Bytes) -> H256 {
+	let mut keccak = Keccak::new_keccak256();
+	keccak.update(&data.0);
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+	H256(hash)
+}
+
+/// Payloads of the relay transactions sent recently, shared by all relay submissions.
+///
+/// Relays with the same recipient and data as a transaction sent to the chain within `duplicate_window`
+/// are skipped, e.g. when the relay streams are restarted or poll overlapping ranges of blocks.
+/// Only new relays are checked, while retries of the failed and reverted ones are sent anyway.
+///
+/// Payloads are persisted, so that duplicates are recognized after restart as well.
+pub struct SentPayloads {
+	/// Seconds, `None` if duplicates are sent.
+	window: Option<u64>,
+	/// Duplicates are sent, but their payloads are still recorded.
+	forced: Cell<bool>,
+	sent: RefCell<HashMap<(Chain, Address, H256), SentPayload>>,
+	/// Payloads sent since the previous call of `take_sent`.
+	unrecorded: RefCell<Vec<SentPayload>>,
+}
+
+impl SentPayloads {
+	pub fn new(config: &Config) -> Self {
+		SentPayloads {
+			window: config.duplicate_window.map(|window| window.as_secs()),
+			forced: Cell::new(false),
+			sent: RefCell::new(HashMap::new()),
+			unrecorded: RefCell::new(Vec::new()),
+		}
+	}
+
+	/// Sends duplicates, e.g. when the operator relays them again on purpose.
+	pub fn force_duplicates(&self) {
+		self.forced.set(true);
+	}
+
+	/// Loads payloads recorded in the database.
+	pub fn load(&self, payloads: &[SentPayload]) {
+		if self.window.is_none() {
+			return;
+		}
+
+		let mut sent = self.sent.borrow_mut();
+		for payload in payloads {
+			sent.insert((payload.chain, payload.to, payload.data_hash), *payload);
+		}
+	}
+
+	/// Returns the transaction sent to `chain` with the same recipient and data within the window before unix time `now`.
+	pub fn duplicate(&self, chain: Chain, to: Address, data: &Bytes, now: u64) -> Option<SentPayload> {
+		let window = self.window?;
+		if self.forced.get() {
+			return None;
+		}
+
+		self.sent.borrow().get(&(chain, to, data_hash(data)))
+			.filter(|payload| payload.timestamp + window > now)
+			.cloned()
+	}
+
+	/// Records the payload of the transaction with `transaction_hash` sent to `chain` at unix time `timestamp`.
+	pub fn sent(&self, chain: Chain, to: Address, data: &Bytes, transaction_hash: H256, timestamp: u64) {
+		let window = match self.window {
+			Some(window) => window,
+			None => return,
+		};
+
+		let payload = SentPayload {
+			chain,
+			to,
+			data_hash: data_hash(data),
+			transaction_hash,
+			timestamp,
+		};
+		let mut sent = self.sent.borrow_mut();
+		sent.retain(|_, sent| sent.timestamp + window > timestamp);
+		sent.insert((chain, to, payload.data_hash), payload);
+		self.unrecorded.borrow_mut().push(payload);
+	}
+
+	/// Returns payloads, which have been sent since the previous call, and unix time before which
+	/// the payloads have expired at unix time `now`.
+	pub fn take_sent(&self, now: u64) -> (Vec<SentPayload>, u64) {
+		let expired = now.saturating_sub(self.window.unwrap_or_default());
+		(self.unrecorded.borrow_mut().split_off(0), expired)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::{Cell, RefCell};
+	use web3::types::Bytes;
+	use database::Chain;
+	use super::SentPayloads;
+
+	fn payloads(window: Option<u64>) -> SentPayloads {
+		SentPayloads {
+			window,
+			forced: Cell::new(false),
+			sent: RefCell::default(),
+			unrecorded: RefCell::default(),
+		}
+	}
+
+	#[test]
+	fn test_sent_payloads() {
+		let payloads = payloads(Some(600));
+		let data = Bytes(vec![1, 2, 3]);
+		assert_eq!(None, payloads.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_000));
+		payloads.sent(Chain::Mainnet, 1.into(), &data, 0xa.into(), 1_700_000_000);
+		assert_eq!(Some(0xa.into()), payloads.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_599).map(|sent| sent.transaction_hash));
+		// transactions with other payloads, or sent to the other chain, are not duplicates
+		assert_eq!(None, payloads.duplicate(Chain::Mainnet, 1.into(), &Bytes(vec![1, 2]), 1_700_000_001));
+		assert_eq!(None, payloads.duplicate(Chain::Mainnet, 2.into(), &data, 1_700_000_001));
+		assert_eq!(None, payloads.duplicate(Chain::Testnet, 1.into(), &data, 1_700_000_001));
+		// the payload expires after the window
+		assert_eq!(None, payloads.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_600));
+
+		let (sent, expired) = payloads.take_sent(1_700_000_600);
+		assert_eq!(1, sent.len());
+		assert_eq!(1_700_000_000, expired);
+		assert!(payloads.take_sent(1_700_000_600).0.is_empty());
+
+		// the payloads loaded from the database are duplicates as well
+		let loaded = self::payloads(Some(600));
+		loaded.load(&sent);
+		assert!(loaded.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_001).is_some());
+		loaded.force_duplicates();
+		assert_eq!(None, loaded.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_001));
+
+		// nothing is recorded without the window
+		let payloads = self::payloads(None);
+		payloads.sent(Chain::Mainnet, 1.into(), &data, 0xa.into(), 1_700_000_000);
+		assert_eq!(None, payloads.duplicate(Chain::Mainnet, 1.into(), &data, 1_700_000_001));
+		assert!(payloads.take_sent(1_700_000_001).0.is_empty());
+	}
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::{Join3, JoinAll, join_all};
+use web3::types::{Address, U256};
+use api::{self, ApiCall};
+use app::App;
+use database::Chain;
+use error::Error;
+use transport::PubSub;
+
+/// Node of a chain, as seen before the bridge is started.
+#[derive(Debug, PartialEq)]
+pub struct NodeProbe {
+	pub chain: Chain,
+	pub chain_id: u64,
+	pub head: u64,
+	/// Whether the node signs transactions of the configured account, which is required unless it has a `signer`.
+	pub account_unlocked: bool,
+}
+
+/// Chain ID, head block and accounts of every node.
+type NodeQuery<T> = JoinAll<Vec<Join3<ApiCall<U256, T>, ApiCall<U256, T>, ApiCall<Vec<Address>, T>>>>;
+
+/// Creates the probe of the nodes configured for mainnet and testnet.
+pub fn probe_nodes<T: PubSub + Clone>(app: Arc<App<T>>) -> ProbeNodes<T> {
+	let nodes = [Chain::Mainnet, Chain::Testnet].iter()
+		.map(|&chain| {
+			let node = app.node(chain);
+			let transport = app.connection(chain);
+			let chain_id = api::chain_id(transport.clone())
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			let head = api::block_number(transport.clone())
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			let accounts = api::accounts(transport.clone())
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			chain_id.join3(head, accounts)
+		})
+		.collect();
+
+	ProbeNodes {
+		query: join_all(nodes),
+		app,
+	}
+}
+
+/// Queries the nodes and resolves to what they report about the chains and the accounts.
+pub struct ProbeNodes<T: PubSub> {
+	app: Arc<App<T>>,
+	query: NodeQuery<T>,
+}
+
+impl<T: PubSub + Clone> Future for ProbeNodes<T> {
+	type Item = Vec<NodeProbe>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let results = try_ready!(self.query.poll());
+		let probes = [Chain::Mainnet, Chain::Testnet].iter().zip(results)
+			.map(|(&chain, (chain_id, head, accounts))| NodeProbe {
+				chain,
+				chain_id: chain_id.low_u64(),
+				head: head.low_u64(),
+				account_unlocked: accounts.contains(&self.app.node(chain).account),
+			})
+			.collect();
+		Ok(Async::Ready(probes))
+	}
+}
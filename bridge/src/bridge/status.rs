@@ -0,0 +1,152 @@
+use std::cmp;
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::{Join, JoinAll, join_all};
+use web3::types::{Address, U256};
+use api::{self, ApiCall};
+use app::App;
+use config::Config;
+use database::{Database, DepositFilter, Chain};
+use error::Error;
+use transport::PubSub;
+use super::BridgeChecked;
+
+/// Checkpoint of a bridge component, which reads events from `chain`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StreamStatus {
+	#[serde(flatten)]
+	pub checkpoint: BridgeChecked,
+	pub chain: Chain,
+	/// Number of blocks between the checkpoint and the head of the chain, including the required confirmations.
+	pub lag: u64,
+}
+
+/// State of a chain and the accounts of the authority on it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChainStatus {
+	pub chain: Chain,
+	pub head: u64,
+	/// Address of the bridge contract.
+	pub contract: Address,
+	/// Balances of the accounts sending transactions to the chain, in wei.
+	pub balances: Vec<(Address, U256)>,
+	/// Relays to the chain written to the journal, which are not known to be relayed yet.
+	pub journaled: usize,
+}
+
+/// State of the bridge, as recorded in the database and seen on the chains.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Status {
+	pub chains: Vec<ChainStatus>,
+	/// Enabled bridge components.
+	pub streams: Vec<StreamStatus>,
+	/// Relays held because of `relay_delay`.
+	pub held: usize,
+	/// Withdraws assigned to this authority, which have not been relayed yet.
+	pub assignments: usize,
+	/// Deposits above `max_deposit`, which are waiting to be approved.
+	pub unapproved_deposits: usize,
+	/// Logs, which could not be parsed.
+	pub quarantined: usize,
+}
+
+/// Checkpoints of the enabled bridge components with the chains they read events from.
+pub fn checkpoints(config: &Config, database: &Database) -> Vec<(BridgeChecked, Chain)> {
+	let streams = &config.streams;
+	let home = config.direction.home();
+	let foreign = config.direction.foreign();
+	let checkpoints = [
+		(streams.deposit_relay, BridgeChecked::DepositRelay(database.checked_deposit_relay), home),
+		(streams.withdraw_relay, BridgeChecked::WithdrawRelay(database.checked_withdraw_relay), foreign),
+		(streams.withdraw_confirm, BridgeChecked::WithdrawConfirm(database.checked_withdraw_confirm), foreign),
+		(streams.mainnet_message_relay, BridgeChecked::MainnetMessageRelay(database.checked_mainnet_message_relay), Chain::Mainnet),
+		(streams.testnet_message_relay, BridgeChecked::TestnetMessageRelay(database.checked_testnet_message_relay), Chain::Testnet),
+	];
+	checkpoints.iter()
+		.filter(|&&(enabled, _, _)| enabled)
+		.map(|&(_, checkpoint, chain)| (checkpoint, chain))
+		.collect()
+}
+
+/// Returns the last block of every chain, which has been checked by all of the `checkpoints` reading events from it.
+pub fn checked_blocks(checkpoints: &[(BridgeChecked, Chain)]) -> Vec<(Chain, u64)> {
+	let mut checked: Vec<(Chain, u64)> = Vec::new();
+	for &(checkpoint, chain) in checkpoints {
+		match checked.iter_mut().find(|&&mut (known, _)| known == chain) {
+			Some(&mut (_, ref mut block)) => *block = cmp::min(*block, checkpoint.block()),
+			None => checked.push((chain, checkpoint.block())),
+		}
+	}
+	checked
+}
+
+/// Head block and balances of the accounts of every chain.
+type ChainQuery<T> = JoinAll<Vec<Join<ApiCall<U256, T>, JoinAll<Vec<ApiCall<U256, T>>>>>>;
+
+/// Creates the status of the bridge recorded in `database`, once the chains are queried.
+pub fn fetch_status<T: PubSub + Clone>(app: Arc<App<T>>, database: Database) -> FetchStatus<T> {
+	let chains = [Chain::Mainnet, Chain::Testnet].iter()
+		.map(|&chain| {
+			let node = app.node(chain);
+			let transport = app.connection(chain);
+			let head = api::block_number(transport.clone())
+				.retry(&app.timer, node.request_timeouts.call, &node.retry);
+			let balances = node.submission_accounts().into_iter()
+				.map(|account| api::balance(transport.clone(), account)
+					.retry(&app.timer, node.request_timeouts.call, &node.retry))
+				.collect();
+			head.join(join_all(balances))
+		})
+		.collect();
+
+	FetchStatus {
+		query: join_all(chains),
+		app,
+		database,
+	}
+}
+
+/// Queries the chains and resolves to the status of the bridge.
+pub struct FetchStatus<T: PubSub> {
+	app: Arc<App<T>>,
+	database: Database,
+	query: ChainQuery<T>,
+}
+
+impl<T: PubSub + Clone> Future for FetchStatus<T> {
+	type Item = Status;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let results = try_ready!(self.query.poll());
+		let database = &self.database;
+		let chains: Vec<_> = [Chain::Mainnet, Chain::Testnet].iter().zip(results)
+			.map(|(&chain, (head, balances))| ChainStatus {
+				chain,
+				head: head.low_u64(),
+				contract: database.contract_address(self.app.config.direction, chain),
+				balances: self.app.node(chain).submission_accounts().into_iter().zip(balances).collect(),
+				journaled: database.journal.iter().filter(|entry| entry.chain == chain).count(),
+			})
+			.collect();
+
+		let streams = checkpoints(&self.app.config, database).into_iter()
+			.map(|(checkpoint, chain)| StreamStatus {
+				checkpoint,
+				chain,
+				lag: chains.iter()
+					.find(|status| status.chain == chain)
+					.map_or(0, |status| status.head.saturating_sub(checkpoint.block())),
+			})
+			.collect();
+
+		Ok(Async::Ready(Status {
+			chains,
+			streams,
+			held: database.held_relays.len(),
+			assignments: database.pending_assignments.len(),
+			unapproved_deposits: database.filtered_deposits.iter().filter(|deposit| deposit.filter == DepositFilter::AboveMaximum).count(),
+			quarantined: database.quarantine.len(),
+		}))
+	}
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use futures::{Future, Stream, Poll, Async};
+use api::Reverts;
+use app::App;
+use transport::PubSub;
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, Discrepancy, Chain};
+use error::Error;
+use super::{simulate_relay, updated_block, unix_time};
+
+/// Relays journaled and submitted by the bridge components, which are handed over to the verification.
+pub struct Submitted {
+	/// Entries written to the journal, which have not been relayed yet.
+	journal: HashMap<ProcessedEvent, JournalEntry>,
+	/// Relayed entries, which are not verified yet.
+	relays: Vec<(JournalEntry, RelayOutcome)>,
+}
+
+impl Submitted {
+	/// Entries of the journal are relayed after restart, so they are verified too.
+	fn new(init: &Database) -> Self {
+		Submitted {
+			journal: init.journal.iter().map(|entry| (entry.event, entry.clone())).collect(),
+			relays: Vec::new(),
+		}
+	}
+
+	pub fn journal(&mut self, entries: &[JournalEntry]) {
+		self.journal.extend(entries.iter().map(|entry| (entry.event, entry.clone())));
+	}
+
+	pub fn discard(&mut self, events: &[ProcessedEvent]) {
+		for event in events {
+			self.journal.remove(event);
+		}
+	}
+
+	/// Relays of events, which have not been journaled, are not verified.
+	pub fn relayed(&mut self, relays: &[RelayOutcome]) {
+		for relay in relays {
+			if let Some(entry) = self.journal.remove(&relay.event) {
+				self.relays.push((entry, *relay));
+			}
+		}
+	}
+}
+
+/// Result of the verification of a relay.
+#[derive(Debug, PartialEq)]
+pub enum Verified {
+	/// Relay has taken effect.
+	Relayed(RelayOutcome),
+	/// Relay has not taken effect.
+	Discrepancy(Discrepancy),
+}
+
+struct PendingVerification<T: PubSub> {
+	entry: JournalEntry,
+	relay: RelayOutcome,
+	/// Block after which the relay is verified, known once the first block is seen after the submission.
+	due: Option<u64>,
+	/// Simulation of the relay, once it's due.
+	simulation: Option<Reverts<T>>,
+}
+
+/// Creates verification of relays, which are checked once `config.verify_relays_after` blocks are mined after their submission.
+pub fn create_relay_verification<T: PubSub + Clone>(app: Arc<App<T>>, init: &Database) -> RelayVerification<T> {
+	RelayVerification {
+		blocks: app.config.verify_relays_after.expect("verification is created only if `verify_relays_after` is configured; qed"),
+		submitted: Submitted::new(init),
+		pending: Vec::new(),
+		mainnet_seen: 0,
+		testnet_seen: 0,
+		app,
+	}
+}
+
+/// Verifies that the submitted relays have taken effect on the chain.
+///
+/// The relay is simulated again, and it's expected to be reverted, because it has already been relayed.
+/// Otherwise, e.g. if the transaction has been dropped or the relay has been rejected, it's reported as a discrepancy.
+pub struct RelayVerification<T: PubSub> {
+	app: Arc<App<T>>,
+	blocks: u64,
+	submitted: Submitted,
+	pending: Vec<PendingVerification<T>>,
+	/// Last best block updates seen by the verification.
+	mainnet_seen: usize,
+	testnet_seen: usize,
+}
+
+impl<T: PubSub + Clone> RelayVerification<T> {
+	/// Relays, which are about to be verified.
+	pub fn submitted(&mut self) -> &mut Submitted {
+		&mut self.submitted
+	}
+
+	fn has_pending(&self, chain: Chain) -> bool {
+		self.pending.iter().any(|pending| pending.entry.chain == chain)
+	}
+}
+
+impl<T: PubSub + Clone> Stream for RelayVerification<T> {
+	type Item = Verified;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		for (entry, relay) in self.submitted.relays.drain(..) {
+			self.pending.push(PendingVerification {
+				entry,
+				relay,
+				due: None,
+				simulation: None,
+			});
+		}
+
+		// blocks are watched only while there are relays to verify
+		let mainnet_block = if self.has_pending(Chain::Mainnet) {
+			updated_block(self.app.blocks(Chain::Mainnet), &mut self.mainnet_seen)?
+		} else {
+			None
+		};
+		let testnet_block = if self.has_pending(Chain::Testnet) {
+			updated_block(self.app.blocks(Chain::Testnet), &mut self.testnet_seen)?
+		} else {
+			None
+		};
+
+		for pending in &mut self.pending {
+			let block = match pending.entry.chain {
+				Chain::Mainnet => mainnet_block,
+				Chain::Testnet => testnet_block,
+			};
+			let block = match block {
+				Some(block) => block,
+				None => continue,
+			};
+
+			let due = *pending.due.get_or_insert(block + self.blocks);
+			if pending.simulation.is_none() && due <= block {
+				pending.simulation = Some(simulate_relay(&(self.app.clone(), pending.entry.chain), pending.entry.clone()));
+			}
+		}
+
+		for index in 0..self.pending.len() {
+			let reverted = match self.pending[index].simulation {
+				Some(ref mut simulation) => match simulation.poll()? {
+					Async::Ready(reverted) => reverted,
+					Async::NotReady => continue,
+				},
+				None => continue,
+			};
+
+			let pending = self.pending.remove(index);
+			let verified = if reverted {
+				Verified::Relayed(pending.relay)
+			} else {
+				error!(target: "bridge", "Relay of {:?} by {:?} has not taken effect on {} after {} blocks", pending.relay.event.transaction_hash, pending.relay.transaction_hash, pending.relay.chain.as_str(), self.blocks);
+				Verified::Discrepancy(Discrepancy {
+					chain: pending.relay.chain,
+					transaction_hash: pending.relay.transaction_hash,
+					timestamp: unix_time(),
+					event: pending.relay.event,
+				})
+			};
+			return Ok(Async::Ready(Some(verified)));
+		}
+
+		Ok(Async::NotReady)
+	}
+}
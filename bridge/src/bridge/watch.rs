@@ -0,0 +1,157 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use futures::{Future, Stream, Poll, Async};
+use futures::future::Join;
+use web3::BatchTransport;
+use web3::types::{H256, U256};
+use api::{self, ApiCall, LogStream, LogStreamInit};
+use app::App;
+use database::{Database, ProcessedEvent, Chain};
+use error::{Error, ResultExt};
+use transport::PubSub;
+use util::web3_filter;
+use super::export::{EventKind, ExportedEvent, parse_event};
+
+/// Stream of the logs of a single kind of events.
+struct EventLogs<T: BatchTransport + PubSub> {
+	kind: EventKind,
+	chain: Chain,
+	logs: LogStream<T>,
+}
+
+enum WatchState<T: BatchTransport + PubSub> {
+	/// Fetching the head blocks of mainnet and testnet, unless the first block is given.
+	Heads(Join<ApiCall<U256, T>, ApiCall<U256, T>>),
+	/// Watching the logs of the contracts.
+	Watch(Vec<EventLogs<T>>),
+}
+
+/// Creates the watch of the events emitted by the contracts recorded in `database`, from block `from`
+/// of both chains, or from the blocks confirmed once it's started.
+pub fn create_watch<T: BatchTransport + PubSub + Clone>(app: Arc<App<T>>, database: Database, from: Option<u64>) -> Watch<T> {
+	let head = |chain| {
+		let node = app.node(chain);
+		api::block_number(app.connection(chain).clone())
+			.retry(&app.timer, node.request_timeouts.call, &node.retry)
+	};
+	let state = match from {
+		Some(from) => WatchState::Watch(watch_logs(&app, &database, [from.saturating_sub(1); 2])),
+		None => WatchState::Heads(head(Chain::Mainnet).join(head(Chain::Testnet))),
+	};
+	let relays = database.relays.iter()
+		.map(|relay| (relay.event, relay.transaction_hash))
+		.collect();
+
+	Watch {
+		app,
+		database,
+		relays,
+		observed: VecDeque::new(),
+		state,
+	}
+}
+
+/// Creates the streams of the watched events, which start after the blocks `after` of mainnet and testnet.
+fn watch_logs<T: BatchTransport + PubSub + Clone>(app: &Arc<App<T>>, database: &Database, after: [u64; 2]) -> Vec<EventLogs<T>> {
+	let (home, foreign) = (app.config.direction.home(), app.config.direction.foreign());
+	let home_events = app.home_bridge.events();
+	let foreign_events = app.foreign_bridge.events();
+	let events = vec![
+		(EventKind::Deposit, home, home_events.deposit().create_filter()),
+		(EventKind::WithdrawRelayed, home, home_events.withdraw().create_filter()),
+		(EventKind::DepositRelayed, foreign, foreign_events.deposit().create_filter()),
+		(EventKind::Withdraw, foreign, foreign_events.withdraw().create_filter()),
+		(EventKind::CollectedSignatures, foreign, foreign_events.collected_signatures().create_filter()),
+	];
+
+	events.into_iter()
+		.map(|(kind, chain, filter)| {
+			let node = app.node(chain);
+			let init = LogStreamInit {
+				after: match chain {
+					Chain::Mainnet => after[0],
+					Chain::Testnet => after[1],
+				},
+				filter: web3_filter(filter, database.contract_address(app.config.direction, chain)),
+				logs_request_timeout: node.request_timeouts.logs,
+				retry: node.retry.clone(),
+				confirmations: node.required_confirmations,
+				max_blocks_per_query: node.max_blocks_per_query,
+				adaptive_polling: node.adaptive_polling,
+				poll_interval: None,
+				catch_up: node.catch_up,
+				once: false,
+				until: None,
+			};
+			EventLogs {
+				kind,
+				chain,
+				logs: api::log_stream(app.connection(chain).clone(), app.timer.clone(), app.blocks(chain).clone(), init),
+			}
+		})
+		.collect()
+}
+
+/// Yields the deposits and withdraws, and the events of their relays, as they are confirmed,
+/// without signing or submitting any transaction. Events relayed by this bridge refer to the relay transactions
+/// recorded in the database when the watch has been started.
+pub struct Watch<T: BatchTransport + PubSub> {
+	app: Arc<App<T>>,
+	database: Database,
+	relays: HashMap<ProcessedEvent, H256>,
+	/// Events, which have been observed, but have not been yielded yet.
+	observed: VecDeque<ExportedEvent>,
+	state: WatchState<T>,
+}
+
+impl<T: BatchTransport + PubSub + Clone> Stream for Watch<T> {
+	type Item = ExportedEvent;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				WatchState::Heads(ref mut future) => {
+					let (mainnet, testnet) = try_ready!(future.poll());
+					// only the events confirmed after the start are watched
+					let after = [
+						mainnet.low_u64().saturating_sub(self.app.node(Chain::Mainnet).required_confirmations),
+						testnet.low_u64().saturating_sub(self.app.node(Chain::Testnet).required_confirmations),
+					];
+					WatchState::Watch(watch_logs(&self.app, &self.database, after))
+				},
+				WatchState::Watch(ref mut streams) => {
+					if let Some(event) = self.observed.pop_front() {
+						return Ok(Async::Ready(Some(event)));
+					}
+
+					let mut observed = Vec::new();
+					for stream in streams.iter_mut() {
+						loop {
+							let item = match stream.logs.poll()? {
+								Async::Ready(Some(item)) => item,
+								// the logs end only if the best blocks can no longer be fetched
+								Async::Ready(None) => return Ok(Async::Ready(None)),
+								Async::NotReady => break,
+							};
+							for log in item.logs {
+								let event = ProcessedEvent::from_log(stream.chain, &log)?;
+								let parsed = parse_event(&self.app, stream.kind, stream.chain, log, &self.relays)
+									.chain_err(|| format!("Log {} of {:?} cannot be parsed", event.log_index, event.transaction_hash))?;
+								observed.push(parsed);
+							}
+						}
+					}
+					observed.sort_by_key(|event| (event.chain.as_str(), event.block, event.log_index));
+					self.observed.extend(observed);
+
+					match self.observed.pop_front() {
+						Some(event) => return Ok(Async::Ready(Some(event))),
+						None => return Ok(Async::NotReady),
+					}
+				},
+			};
+			self.state = next_state;
+		}
+	}
+}
@@ -1,29 +1,33 @@
+use std::collections::HashSet;
+use std::mem;
 use std::sync::Arc;
 use std::ops;
-use futures::{Future, Stream, Poll};
-use futures::future::{JoinAll, join_all};
-use tokio_timer::Timeout;
+use futures::{Future, Stream, Poll, Async};
+use futures::future::Either;
 use ethabi::RawLog;
-use web3::Transport;
-use web3::types::{H256, H520, Address, TransactionRequest, Log, Bytes, FilterBuilder};
-use api::{self, LogStream, ApiCall};
+use web3::BatchTransport;
+use web3::types::{H520, Address, Log, Bytes, FilterBuilder};
+use api::{self, LogStream, ApiCall, Limited};
 use app::App;
-use contracts::testnet;
+use transport::PubSub;
+use contracts::foreign;
 use util::web3_filter;
-use database::Database;
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, RevertedRelay};
 use error::Error;
+use signer::Signing;
+use super::{Relay, RelaySubmission, BatchProgress, RetryQueue, journaled, parse_logs};
 
-fn withdraws_filter(testnet: &testnet::KovanBridge, address: Address) -> FilterBuilder {
-	let filter = testnet.events().withdraw().create_filter();
+fn withdraws_filter(foreign_bridge: &foreign::KovanBridge, address: Address) -> FilterBuilder {
+	let filter = foreign_bridge.events().withdraw().create_filter();
 	web3_filter(filter, address)
 }
 
-fn withdraw_confirm_sign_payload(testnet: &testnet::KovanBridge, log: Log) -> Result<Bytes, Error> {
+fn withdraw_confirm_sign_payload(foreign_bridge: &foreign::KovanBridge, log: Log) -> Result<Bytes, Error> {
 	let raw_log = RawLog {
 		topics: log.topics.into_iter().map(|t| t.0).collect(),
 		data: log.data.0,
 	};
-	let withdraw_log = testnet.events().withdraw().parse_log(raw_log)?;
+	let withdraw_log = foreign_bridge.events().withdraw().parse_log(raw_log)?;
 	let hash = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
 	let mut result = vec![0u8; 84];
 	result[0..20].copy_from_slice(&withdraw_log.recipient);
@@ -32,137 +36,203 @@ fn withdraw_confirm_sign_payload(testnet: &testnet::KovanBridge, log: Log) -> Re
 	Ok(result.into())
 }
 
-fn withdraw_submit_signature_payload(testnet: &testnet::KovanBridge, withdraw_payload: Bytes, signature: H520) -> Bytes {
-	testnet.functions().submit_signature().input(signature.to_vec(), withdraw_payload.0).into()
+fn withdraw_submit_signature_payload(foreign_bridge: &foreign::KovanBridge, withdraw_payload: Bytes, signature: H520) -> Bytes {
+	foreign_bridge.functions().submit_signature().input(signature.to_vec(), withdraw_payload.0).into()
 }
 
+/// Signature of the withdraw, either by the node or by the local signer.
+type SignWithdraw<T> = Either<ApiCall<H520, T>, Signing<H520>>;
+
 /// State of withdraw confirmation.
-enum WithdrawConfirmState<T: Transport> {
+enum WithdrawConfirmState<T: PubSub> {
 	/// Withdraw confirm is waiting for logs.
 	Wait,
 	/// Signing withdraws.
 	SignWithdraws {
 		withdraws: Vec<Bytes>,
-		future: JoinAll<Vec<Timeout<ApiCall<H520, T::Out>>>>,
-		block: u64,
+		events: Vec<ProcessedEvent>,
+		future: Box<Limited<Arc<App<T>>, Bytes, SignWithdraw<T>>>,
+		progress: BatchProgress,
 	},
 	/// Confirming withdraws.
-	ConfirmWithdraws {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
-		block: u64,
-	},
-	/// All withdraws till given block has been confirmed.
-	Yield(Option<u64>),
+	/// Confirmations from the journal are submitted before any block is checked.
+	/// Every confirmation is simulated first, so that withdraws which this authority
+	/// has already confirmed, e.g. before a crash, are skipped.
+	/// Yields the last block, which withdraws have all been confirmed.
+	ConfirmWithdraws(Box<RelaySubmission<T>>),
 }
 
-pub fn create_withdraw_confirm<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawConfirm<T> {
+pub fn create_withdraw_confirm<T: BatchTransport + PubSub + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawConfirm<T> {
+	let foreign = app.config.direction.foreign();
+	let node = app.node(foreign);
 	let logs_init = api::LogStreamInit {
 		after: init.checked_withdraw_confirm,
-		request_timeout: app.config.testnet.request_timeout,
-		poll_interval: app.config.testnet.poll_interval,
-		confirmations: app.config.testnet.required_confirmations,
-		filter: withdraws_filter(&app.testnet_bridge, init.testnet_contract_address.clone()),
+		logs_request_timeout: node.request_timeouts.logs,
+		retry: node.retry.clone(),
+		confirmations: app.config.confirmations.withdraw_confirm.unwrap_or(node.required_confirmations),
+		max_blocks_per_query: node.max_blocks_per_query,
+		adaptive_polling: node.adaptive_polling,
+		poll_interval: app.config.poll_intervals.withdraw_confirm,
+		catch_up: node.catch_up,
+		once: app.once,
+		until: app.until,
+		filter: withdraws_filter(&app.foreign_bridge, init.foreign_contract_address),
+	};
+
+	let replayed = journaled(init, foreign, foreign, init.foreign_contract_address);
+	let already_processed = init.relays.iter().map(|relay| relay.event)
+		.chain(replayed.iter().map(|entry| entry.event))
+		.chain(init.quarantine.iter().map(|log| log.event))
+		.collect();
+	let state = if replayed.is_empty() {
+		WithdrawConfirmState::Wait
+	} else {
+		WithdrawConfirmState::ConfirmWithdraws(Box::new(RelaySubmission::new(&app, foreign, replayed, true, true, app.config.txs.withdraw_confirm.receipt_confirmations, false)))
 	};
 
 	WithdrawConfirm {
-		logs: api::log_stream(app.connections.testnet.clone(), app.timer.clone(), logs_init),
-		testnet_contract: init.testnet_contract_address.clone(),
-		state: WithdrawConfirmState::Wait,
+		logs: api::log_stream(app.connection(foreign).clone(), app.timer.clone(), app.blocks(foreign).clone(), logs_init),
+		foreign_contract: init.foreign_contract_address,
+		state,
+		already_processed,
+		relays: Vec::new(),
+		journal: Vec::new(),
+		discarded: Vec::new(),
+		reverted: Vec::new(),
+		quarantined: Vec::new(),
+		retries: RetryQueue::new(&app, foreign),
 		app,
 	}
 }
 
-pub struct WithdrawConfirm<T: Transport> {
+
+pub struct WithdrawConfirm<T: BatchTransport + PubSub> {
 	app: Arc<App<T>>,
 	logs: LogStream<T>,
 	state: WithdrawConfirmState<T>,
-	testnet_contract: Address,
+	/// Contract collecting the signatures.
+	foreign_contract: Address,
+	/// Events confirmed before restart.
+	already_processed: HashSet<ProcessedEvent>,
+	/// Transactions which relayed events, but have not been reported yet.
+	relays: Vec<RelayOutcome>,
+	/// Transactions which are about to be submitted, but have not been reported yet.
+	journal: Vec<JournalEntry>,
+	/// Events from the journal, which have already been confirmed, but have not been reported yet.
+	discarded: Vec<ProcessedEvent>,
+	/// Relay transactions, which have been reverted, but have not been reported yet.
+	reverted: Vec<RevertedRelay>,
+	/// Logs, which could not be parsed, but have not been reported yet.
+	quarantined: Vec<QuarantinedLog>,
+	/// Relays, which have failed and are retried.
+	retries: RetryQueue<T>,
 }
 
-impl<T: Transport> Stream for WithdrawConfirm<T> {
+impl<T: BatchTransport + PubSub + Clone> Stream for WithdrawConfirm<T> {
 	type Item = u64;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.retries.poll(&mut self.relays, &mut self.journal, &mut self.reverted)?;
 		loop {
 			let next_state = match self.state {
 				WithdrawConfirmState::Wait => {
 					let item = try_stream!(self.logs.poll());
-					let withdraws = item.logs
+					let foreign = self.app.config.direction.foreign();
+					let progress = BatchProgress::new(foreign, &item);
+					let foreign_bridge = &self.app.foreign_bridge;
+					let (events, withdraws): (Vec<_>, Vec<_>) = parse_logs(foreign, item.logs, &self.already_processed, &mut self.quarantined, |log| withdraw_confirm_sign_payload(foreign_bridge, log))?
 						.into_iter()
-						.map(|log| withdraw_confirm_sign_payload(&self.app.testnet_bridge, log))
-						.collect::<Result<Vec<_>, _>>()?;
+						.unzip();
 
-					let requests = withdraws.clone()
-						.into_iter()
-						.map(|bytes| {
-							self.app.timer.timeout(
-								api::sign(&self.app.connections.testnet, self.app.config.testnet.account.clone(), bytes),
-								self.app.config.testnet.request_timeout)
-						})
-						.collect::<Vec<_>>();
+					let requests = api::limited(self.app.clone(), withdraws.clone(), self.app.node(foreign).max_concurrent_requests, |app, bytes| {
+						// signing is safe to be retried, unlike submitting the signature
+						let foreign = app.config.direction.foreign();
+						let node = app.node(foreign);
+						match node.signer {
+							Some(ref signer) => Either::B(signer.sign_message(&app.timer, &bytes.0)),
+							None => Either::A(api::sign(app.submission(foreign).clone(), node.account, bytes)
+								.retry(&app.timer, node.request_timeout, &node.retry)),
+						}
+					});
 
 					WithdrawConfirmState::SignWithdraws {
-						future: join_all(requests),
+						future: Box::new(requests),
 						withdraws: withdraws,
-						block: item.to,
+						events,
+						progress,
 					}
 				},
-				WithdrawConfirmState::SignWithdraws { ref mut future, ref mut withdraws, block } => {
+				WithdrawConfirmState::SignWithdraws { ref mut future, ref mut withdraws, ref mut events, ref mut progress } => {
 					let signatures = try_ready!(future.poll());
 					// borrow checker...
 					let app = &self.app;
-					let testnet_contract = &self.testnet_contract;
-					let confirmations = withdraws
+					let foreign_contract = &self.foreign_contract;
+					let entries = withdraws
 						.drain(ops::RangeFull)
 						.zip(signatures.into_iter())
-						.map(|(withdraw, signature)| withdraw_submit_signature_payload(&app.testnet_bridge, withdraw, signature))
-						.map(|payload| TransactionRequest {
-							from: app.config.testnet.account.clone(),
-							to: Some(testnet_contract.clone()),
-							gas: Some(app.config.txs.withdraw_confirm.gas.into()),
-							gas_price: Some(app.config.txs.withdraw_confirm.gas_price.into()),
-							value: None,
-							data: Some(payload),
-							nonce: None,
-							condition: None,
-						})
-						.map(|request| {
-							app.timer.timeout(
-								api::send_transaction(&app.connections.testnet, request),
-								app.config.testnet.request_timeout)
+						.zip(events.drain(ops::RangeFull))
+						.map(|((withdraw, signature), event)| JournalEntry {
+							chain: app.config.direction.foreign(),
+							to: *foreign_contract,
+							data: withdraw_submit_signature_payload(&app.foreign_bridge, withdraw, signature),
+							gas: app.config.txs.withdraw_confirm.gas,
+							gas_price: app.config.txs.withdraw_confirm.gas_price,
+							event,
 						})
 						.collect::<Vec<_>>();
 
-					WithdrawConfirmState::ConfirmWithdraws {
-						future: join_all(confirmations),
-						block,
-					}
+					let submission = RelaySubmission::new(app, app.config.direction.foreign(), entries, false, true, app.config.txs.withdraw_confirm.receipt_confirmations, false);
+					WithdrawConfirmState::ConfirmWithdraws(Box::new(submission.with_progress(mem::take(progress))))
 				},
-				WithdrawConfirmState::ConfirmWithdraws { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					WithdrawConfirmState::Yield(Some(block))
+				WithdrawConfirmState::ConfirmWithdraws(ref mut submission) => {
+					match try_ready!(submission.poll_checked(&mut self.journal, &mut self.relays, &mut self.discarded, &mut self.reverted, &mut self.retries)) {
+						Some(block) => return Ok(Async::Ready(Some(block))),
+						None => WithdrawConfirmState::Wait,
+					}
 				},
-				WithdrawConfirmState::Yield(ref mut block) => match block.take() {
-					None => WithdrawConfirmState::Wait,
-					some => return Ok(some.into()),
-				}
 			};
 			self.state = next_state;
 		}
 	}
 }
 
+impl<T: BatchTransport + PubSub + Clone> Relay for WithdrawConfirm<T> {
+	fn take_relays(&mut self) -> Vec<RelayOutcome> {
+		self.relays.split_off(0)
+	}
+
+	fn take_journal(&mut self) -> Vec<JournalEntry> {
+		self.journal.split_off(0)
+	}
+
+	fn take_discarded(&mut self) -> Vec<ProcessedEvent> {
+		self.discarded.split_off(0)
+	}
+
+	fn take_quarantined(&mut self) -> Vec<QuarantinedLog> {
+		self.quarantined.split_off(0)
+	}
+
+	fn take_reverted(&mut self) -> Vec<RevertedRelay> {
+		self.reverted.split_off(0)
+	}
+
+	fn failed_relays(&self) -> usize {
+		self.retries.len()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use rustc_hex::FromHex;
 	use web3::types::{Log, Bytes};
-	use contracts::testnet;
+	use contracts::foreign;
 	use super::{withdraw_confirm_sign_payload, withdraw_submit_signature_payload};
 
 	#[test]
 	fn test_withdraw_confirm_sign_payload() {
-		let testnet = testnet::KovanBridge::default();
+		let foreign_bridge = foreign::KovanBridge::default();
 
 		let data = "000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap();
 		let log = Log {
@@ -172,19 +242,19 @@ mod tests {
 			..Default::default()
 		};
 
-		let payload = withdraw_confirm_sign_payload(&testnet, log).unwrap();
+		let payload = withdraw_confirm_sign_payload(&foreign_bridge, log).unwrap();
 		let expected: Bytes = "aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".from_hex().unwrap().into();
 		assert_eq!(expected, payload);
 	}
 
 	#[test]
 	fn test_withdraw_submit_signature_payload() {
-		let testnet = testnet::KovanBridge::default();
+		let foreign_bridge = foreign::KovanBridge::default();
 
 		let message: Bytes = "aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".from_hex().unwrap().into();
 		let signature = "0x8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc".parse().unwrap();
 
-		let payload = withdraw_submit_signature_payload(&testnet, message, signature);
+		let payload = withdraw_submit_signature_payload(&foreign_bridge, message, signature);
 		let expected: Bytes = "630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000".from_hex().unwrap().into();
 		assert_eq!(expected, payload);
 	}
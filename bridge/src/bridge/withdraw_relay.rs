@@ -1,58 +1,105 @@
+use std::collections::HashSet;
+use std::mem;
 use std::sync::Arc;
-use futures::{Future, Stream, Poll};
+use std::time::Duration;
+use futures::{Future, Stream, Poll, Async};
 use futures::future::{JoinAll, join_all, Join};
-use tokio_timer::Timeout;
-use web3::Transport;
-use web3::types::{H256, Address, FilterBuilder, Log, Bytes, TransactionRequest};
+use tokio_timer::{Sleep, Timeout};
+use web3::BatchTransport;
+use web3::types::{Address, FilterBuilder, Log, Bytes, H256, U256};
 use ethabi::{RawLog, self};
 use app::App;
-use api::{self, LogStream, ApiCall};
-use contracts::{mainnet, testnet};
-use util::web3_filter;
-use database::Database;
-use error::{self, Error};
-
-fn collected_signatures_filter(testnet: &testnet::KovanBridge, address: Address) -> FilterBuilder {
-	let filter = testnet.events().collected_signatures().create_filter();
-	web3_filter(filter, address)
+use config::{WithdrawRelayAssignment, LogFilter};
+use transport::PubSub;
+use api::{self, LogStream, ApiCall, BatchCall};
+use contracts::{home, foreign};
+use util::{web3_filter, restrict_topics, recover_signer};
+use database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, PendingAssignment, HeldRelay, RevertedRelay, Chain};
+use error::{self, Error, ErrorClass};
+use super::{Relay, RelaySubmission, BatchProgress, RetryQueue, journaled, parse_logs, unix_time};
+use super::delay::DelayedRelays;
+
+fn collected_signatures_filter(foreign_bridge: &foreign::KovanBridge, address: Address, log_filter: &LogFilter) -> FilterBuilder {
+	let filter = foreign_bridge.events().collected_signatures().create_filter();
+	web3_filter(restrict_topics(filter, log_filter), address)
 }
 
-#[derive(Debug, PartialEq)]
-struct RelayAssignment {
+#[derive(Debug, PartialEq, Clone)]
+pub struct RelayAssignment {
 	signature_payloads: Vec<Bytes>,
 	message_payload: Bytes,
+	/// Authority, which has submitted the last signature.
+	authority: Address,
+	message_hash: H256,
 }
 
-fn signatures_payload(testnet: &testnet::KovanBridge, signatures: u32, my_address: Address, log: Log) -> error::Result<Option<RelayAssignment>> {
+fn signatures_payload(foreign_bridge: &foreign::KovanBridge, signatures: u32, log: Log) -> error::Result<RelayAssignment> {
 	let raw_log = RawLog {
 		topics: log.topics.into_iter().map(|t| t.0).collect(),
 		data: log.data.0,
 	};
-	let collected_signatures = testnet.events().collected_signatures().parse_log(raw_log)?;
-	if collected_signatures.authority != my_address.0 {
-		// someone else will relay this transaction to mainnet
-		return Ok(None);
-	}
+	let collected_signatures = foreign_bridge.events().collected_signatures().parse_log(raw_log)?;
+	let message_hash = H256::from(&collected_signatures.message_hash[..]);
+	Ok(relay_assignment(foreign_bridge, signatures, message_hash, Address::from(&collected_signatures.authority[..])))
+}
+
+/// Returns assignment of the withdraw with message `message_hash`, which last signature has been submitted by `authority`.
+pub fn relay_assignment(foreign_bridge: &foreign::KovanBridge, signatures: u32, message_hash: H256, authority: Address) -> RelayAssignment {
 	let signature_payloads = (0..signatures).into_iter()
 		.map(|index| ethabi::util::pad_u32(index))
-		.map(|index| testnet.functions().signature().input(collected_signatures.message_hash, index))
+		.map(|index| foreign_bridge.functions().signature().input(message_hash.0, index))
 		.map(Into::into)
 		.collect();
-	let message_payload = testnet.functions().message().input(collected_signatures.message_hash).into();
+	let message_payload = foreign_bridge.functions().message().input(message_hash.0).into();
 
-	Ok(Some(RelayAssignment {
+	RelayAssignment {
 		signature_payloads,
 		message_payload,
-	}))
+		authority,
+		message_hash,
+	}
 }
 
-fn withdraw_relay_payload(mainnet: &mainnet::EthereumBridge, signatures: Vec<Bytes>, message: Bytes) -> Bytes {
-	assert_eq!(message.0.len(), 84, "KovanBridge never accepts messages with len != 84 bytes; qed");
+/// Returns the turn of `my_address` to relay the withdraw, 0 if it's assigned to it,
+/// or `None` if it's relayed by someone else.
+fn relay_rank(scheme: WithdrawRelayAssignment, authorities: &[Address], my_address: Address, assignment: &RelayAssignment, fallback: bool) -> Option<usize> {
+	let assigned = match scheme {
+		WithdrawRelayAssignment::Contract if assignment.authority == my_address => return Some(0),
+		WithdrawRelayAssignment::Contract => authorities.iter().position(|authority| *authority == assignment.authority),
+		WithdrawRelayAssignment::RoundRobin if authorities.is_empty() => return None,
+		WithdrawRelayAssignment::RoundRobin => {
+			let len = authorities.len() as u64;
+			let index = assignment.message_hash.iter().fold(0, |index, byte| (index * 256 + *byte as u64) % len);
+			Some(index as usize)
+		},
+	};
+
+	let me = authorities.iter().position(|authority| *authority == my_address)?;
+	let rank = match assigned {
+		Some(assigned) => (me + authorities.len() - assigned) % authorities.len(),
+		// the assigned authority is not known, so it's not waited for
+		None => me + 1,
+	};
+
+	match rank {
+		0 => Some(0),
+		rank if fallback => Some(rank),
+		_ => None,
+	}
+}
+
+/// Fails if the foreign contract has returned a malformed message or signature, which it never accepts.
+pub fn withdraw_relay_payload(home_bridge: &home::EthereumBridge, signatures: Vec<Bytes>, message: Bytes) -> error::Result<Bytes> {
+	if message.0.len() != 84 {
+		return Err(format!("Message has {} bytes instead of 84", message.0.len()).into());
+	}
 	let mut v_vec = Vec::new();
 	let mut r_vec = Vec::new();
 	let mut s_vec = Vec::new();
 	for signature in signatures {
-		assert_eq!(signature.0.len(), 65, "KovanBridge never accepts signatures with len != 65 bytes; qed");
+		if signature.0.len() != 65 {
+			return Err(format!("Signature has {} bytes instead of 65", signature.0.len()).into());
+		}
 		let mut r = [0u8; 32];
 		let mut s= [0u8; 32];
 		let mut v = [0u8; 32];
@@ -63,151 +110,535 @@ fn withdraw_relay_payload(mainnet: &mainnet::EthereumBridge, signatures: Vec<Byt
 		s_vec.push(s);
 		r_vec.push(r);
 	}
-	mainnet.functions().withdraw().input(v_vec, r_vec, s_vec, message.0).into()
+	Ok(home_bridge.functions().withdraw().input(v_vec, r_vec, s_vec, message.0).into())
+}
+
+/// Returns the `CollectedSignatures` log of the `withdraw`, which can't be relayed, so that it's quarantined
+/// like the logs, which can't be parsed.
+fn quarantined_withdraw(foreign_bridge: &foreign::KovanBridge, contract: Address, withdraw: &PendingWithdraw, error: &Error) -> QuarantinedLog {
+	let topic = match foreign_bridge.events().collected_signatures().create_filter().topic0 {
+		ethabi::Topic::This(topic) => H256(topic),
+		_ => unreachable!("filter of the event matches its signature; qed"),
+	};
+	let data = ethabi::encode(&[
+		ethabi::Token::Address(withdraw.assignment.authority.0),
+		ethabi::Token::FixedBytes(withdraw.assignment.message_hash.to_vec()),
+	]);
+
+	QuarantinedLog {
+		address: contract,
+		topics: vec![topic],
+		data: data.into(),
+		error: error.to_string(),
+		event: withdraw.event,
+	}
+}
+
+/// Checks that each of the `signatures` of the `message` has been made by a different authority,
+/// so that the withdraw isn't rejected by the home contract.
+fn verify_signatures(authorities: &[Address], message: &Bytes, signatures: &[Bytes]) -> error::Result<()> {
+	let mut signers = HashSet::new();
+	for signature in signatures {
+		let signer = recover_signer(&signature.0, &message.0)?;
+		if !authorities.contains(&signer) {
+			return Err(format!("Message has been signed by {:?}, which is not an authority", signer).into());
+		}
+		if !signers.insert(signer) {
+			return Err(format!("Message has been signed by {:?} more than once", signer).into());
+		}
+	}
+	Ok(())
+}
+
+/// Withdraw assigned to this authority, which is about to be fetched.
+struct PendingWithdraw {
+	event: ProcessedEvent,
+	/// Block of the event, if known.
+	block: Option<u64>,
+	assignment: RelayAssignment,
+	/// Number of times the withdraw has been fetched before signatures were available.
+	failures: u32,
+}
+
+impl PendingWithdraw {
+	/// Returns the assignment persisted until the withdraw is relayed, which is fetched again at unix time `due`.
+	fn pending(&self, due: u64) -> PendingAssignment {
+		PendingAssignment {
+			message_hash: self.assignment.message_hash,
+			authority: self.assignment.authority,
+			block: self.block,
+			failures: self.failures,
+			due,
+			event: self.event,
+		}
+	}
+}
+
+/// Withdraw, which message or signatures are not stored yet, or which is assigned to other
+/// authority, fetched again after backoff.
+struct DeferredWithdraw {
+	withdraw: PendingWithdraw,
+	backoff: Sleep,
 }
 
-pub enum WithdrawRelayState<T: Transport> {
+/// Defers fetching the `withdraw` according to the retry policy of the foreign node, updating its pending `assignments`.
+/// Fails if the withdraw should not be fetched again.
+fn defer<T: PubSub>(app: &App<T>, deferred: &mut Vec<DeferredWithdraw>, assignments: &mut Vec<PendingAssignment>, mut withdraw: PendingWithdraw) -> error::Result<()> {
+	withdraw.failures += 1;
+	let policy = app.node(app.config.direction.foreign()).retry.get(ErrorClass::Other);
+	if withdraw.failures >= policy.max_attempts {
+		return Err(format!("Signatures of withdraw {:?} are missing after {} attempts", withdraw.event.transaction_hash, withdraw.failures).into());
+	}
+
+	let delay = api::retry_delay(policy, withdraw.failures);
+	warn!(target: "bridge", "Signatures of withdraw {:?} are missing ({}/{}), fetching them again in {:?}", withdraw.event.transaction_hash, withdraw.failures, policy.max_attempts, delay);
+	assignments.push(withdraw.pending(unix_time() + delay.as_secs()));
+	deferred.push(DeferredWithdraw {
+		withdraw,
+		backoff: app.timer.sleep(delay),
+	});
+	Ok(())
+}
+
+/// Fetches number of signatures required by the contract on `chain`.
+pub fn required_signatures<T: PubSub + Clone>(app: &App<T>, chain: Chain, contract: Address) -> ApiCall<Bytes, T> {
+	let payload = app.foreign_bridge.functions().required_signatures().input().into();
+	api::call(app.connection(chain).clone(), contract, payload)
+		.retry(&app.timer, app.node(chain).request_timeouts.call, &app.node(chain).retry)
+}
+
+/// Reads authorities of the contract on a chain one by one, until the index is out of range.
+pub struct ContractAuthorities<T: PubSub> {
+	app: Arc<App<T>>,
+	chain: Chain,
+	contract: Address,
+	authorities: Vec<Address>,
+	call: ApiCall<Bytes, T>,
+}
+
+fn contract_authority<T: PubSub + Clone>(app: &App<T>, chain: Chain, contract: Address, index: usize) -> ApiCall<Bytes, T> {
+	let payload = app.home_bridge.functions().authorities().input(ethabi::util::pad_u32(index as u32)).into();
+	api::call(app.connection(chain).clone(), contract, payload)
+		.retry(&app.timer, app.node(chain).request_timeouts.call, &app.node(chain).retry)
+}
+
+pub fn contract_authorities<T: PubSub + Clone>(app: &Arc<App<T>>, chain: Chain, contract: Address) -> ContractAuthorities<T> {
+	ContractAuthorities {
+		app: app.clone(),
+		chain,
+		contract,
+		authorities: Vec::new(),
+		call: contract_authority(app, chain, contract, 0),
+	}
+}
+
+impl<T: PubSub + Clone> Future for ContractAuthorities<T> {
+	type Item = Vec<Address>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let output = match self.call.poll() {
+				Ok(Async::Ready(ref output)) if output.0.is_empty() => None,
+				Ok(Async::Ready(output)) => Some(output),
+				Ok(Async::NotReady) => return Ok(Async::NotReady),
+				// reading past the end of the array is reverted
+				Err(ref err) if err.class() == ErrorClass::Reverted => None,
+				Err(err) => return Err(err),
+			};
+
+			match output {
+				Some(output) => {
+					let authority = self.app.home_bridge.functions().authorities().output(&output.0)?;
+					self.authorities.push(Address::from(&authority[..]));
+					self.call = contract_authority(&self.app, self.chain, self.contract, self.authorities.len());
+				},
+				None => return Ok(Async::Ready(mem::take(&mut self.authorities))),
+			}
+		}
+	}
+}
+
+/// Messages and signatures of withdraws.
+pub type FetchWithdraws<T> = Timeout<BatchCall<<T as BatchTransport>::Batch, JoinAll<Vec<Join<ApiCall<Bytes, api::Batch<T>>, JoinAll<Vec<ApiCall<Bytes, api::Batch<T>>>>>>>>>;
+
+/// Fetches messages and signatures of all the withdraws with a single batch request.
+pub fn fetch_withdraws<T: BatchTransport + PubSub + Clone>(app: &App<T>, foreign_contract: Address, assignments: Vec<RelayAssignment>) -> FetchWithdraws<T> {
+	let foreign = app.config.direction.foreign();
+	let batch = api::Batch::new(app.connection(foreign).clone());
+	let withdraws = assignments.into_iter()
+		.map(|assignment| {
+			let message = api::call(batch.clone(), foreign_contract, assignment.message_payload);
+			let signatures = assignment.signature_payloads.into_iter()
+				.map(|payload| api::call(batch.clone(), foreign_contract, payload))
+				.collect::<Vec<_>>();
+			message.join(join_all(signatures))
+		})
+		.collect::<Vec<_>>();
+
+	app.timer.timeout(batch.submit(join_all(withdraws)), app.node(foreign).request_timeouts.call)
+}
+
+enum WithdrawRelayState<T: BatchTransport + PubSub + Clone> {
 	Wait,
-	Fetch {
-		future: Join<JoinAll<Vec<Timeout<ApiCall<Bytes, T::Out>>>>, JoinAll<Vec<JoinAll<Vec<Timeout<ApiCall<Bytes, T::Out>>>>>>>,
-		block: u64,
+	/// Authorities and the number of required signatures are read from the contracts, before the signatures are fetched.
+	ReadContracts {
+		future: Box<Join<ApiCall<Bytes, T>, ContractAuthorities<T>>>,
+		logs: Vec<Log>,
+		progress: BatchProgress,
 	},
-	RelayWithdraws {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
-		block: u64,
+	Fetch {
+		future: FetchWithdraws<T>,
+		withdraws: Vec<PendingWithdraw>,
+		progress: BatchProgress,
 	},
-	Yield(Option<u64>),
+	/// Yields the last block, which withdraws have all been relayed or deferred.
+	/// Withdraws from the journal are relayed before any block is checked.
+	RelayWithdraws(Box<RelaySubmission<T>>),
 }
 
-pub fn create_withdraw_relay<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawRelay<T> {
+pub fn create_withdraw_relay<T: BatchTransport + PubSub + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawRelay<T> {
+	let (home, foreign) = (app.config.direction.home(), app.config.direction.foreign());
+	let node = app.node(foreign);
 	let logs_init = api::LogStreamInit {
 		after: init.checked_withdraw_relay,
-		request_timeout: app.config.testnet.request_timeout,
-		poll_interval: app.config.testnet.poll_interval,
-		confirmations: app.config.testnet.required_confirmations,
-		filter: collected_signatures_filter(&app.testnet_bridge, init.testnet_contract_address.clone()),
+		logs_request_timeout: node.request_timeouts.logs,
+		retry: node.retry.clone(),
+		confirmations: app.config.confirmations.withdraw_relay.unwrap_or(node.required_confirmations),
+		max_blocks_per_query: node.max_blocks_per_query,
+		adaptive_polling: node.adaptive_polling,
+		poll_interval: app.config.poll_intervals.withdraw_relay,
+		catch_up: node.catch_up,
+		once: app.once,
+		until: app.until,
+		filter: collected_signatures_filter(&app.foreign_bridge, init.foreign_contract_address, &app.config.log_filters.withdraw_relay),
+	};
+
+	let replayed = journaled(init, foreign, home, init.home_contract_address);
+	let mut already_processed: HashSet<_> = init.relays.iter().map(|relay| relay.event)
+		.chain(replayed.iter().map(|entry| entry.event))
+		.chain(init.quarantine.iter().map(|log| log.event))
+		.collect();
+	let relay_delay = app.config.relay_delay;
+	let delayed = DelayedRelays::new(relay_delay.and_then(|delay| delay.min_withdraw), relay_delay.map(|delay| delay.delay), init, foreign, home, init.home_contract_address, &mut already_processed);
+	// assignments of withdraws, which have been journaled, held or relayed since, are no longer pending
+	let (discarded_assignments, restored): (Vec<PendingAssignment>, Vec<PendingAssignment>) = init.pending_assignments.iter().cloned()
+		.filter(|assignment| assignment.event.chain == foreign)
+		.partition(|assignment| already_processed.contains(&assignment.event));
+	let discarded_assignments = discarded_assignments.into_iter().map(|assignment| assignment.event).collect();
+	already_processed.extend(restored.iter().map(|assignment| assignment.event));
+	let state = if replayed.is_empty() {
+		WithdrawRelayState::Wait
+	} else {
+		WithdrawRelayState::RelayWithdraws(Box::new(RelaySubmission::new(&app, home, replayed, true, true, app.config.txs.withdraw_relay.receipt_confirmations, app.config.txs.withdraw_relay.sequence_nonces)))
 	};
 
 	WithdrawRelay {
-		logs: api::log_stream(app.connections.testnet.clone(), app.timer.clone(), logs_init),
-		mainnet_contract: init.mainnet_contract_address.clone(),
-		testnet_contract: init.testnet_contract_address.clone(),
-		state: WithdrawRelayState::Wait,
+		logs: api::log_stream(app.connection(foreign).clone(), app.timer.clone(), app.blocks(foreign).clone(), logs_init),
+		home_contract: init.home_contract_address,
+		foreign_contract: init.foreign_contract_address,
+		state,
+		already_processed,
+		relays: Vec::new(),
+		journal: Vec::new(),
+		discarded: Vec::new(),
+		reverted: Vec::new(),
+		quarantined: Vec::new(),
+		retries: RetryQueue::new(&app, home),
+		deferred: Vec::new(),
+		restored,
+		assignments: Vec::new(),
+		discarded_assignments,
+		relaying: Vec::new(),
+		delayed,
+		required_signatures: app.config.authorities.required_signatures,
+		authorities: app.config.authorities.accounts.clone(),
 		app,
 	}
 }
 
-pub struct WithdrawRelay<T: Transport> {
+pub struct WithdrawRelay<T: BatchTransport + PubSub + Clone> {
 	app: Arc<App<T>>,
 	logs: LogStream<T>,
 	state: WithdrawRelayState<T>,
-	testnet_contract: Address,
-	mainnet_contract: Address,
+	/// Contract collecting the signatures of withdraws.
+	foreign_contract: Address,
+	/// Contract to which the withdraws are relayed.
+	home_contract: Address,
+	/// Events relayed before restart.
+	already_processed: HashSet<ProcessedEvent>,
+	/// Transactions which relayed events, but have not been reported yet.
+	relays: Vec<RelayOutcome>,
+	/// Transactions which are about to be submitted, but have not been reported yet.
+	journal: Vec<JournalEntry>,
+	/// Events from the journal, which have already been relayed, but have not been reported yet.
+	discarded: Vec<ProcessedEvent>,
+	/// Relay transactions, which have been reverted, but have not been reported yet.
+	reverted: Vec<RevertedRelay>,
+	/// Logs, which could not be parsed, but have not been reported yet.
+	quarantined: Vec<QuarantinedLog>,
+	/// Relays, which have failed and are retried.
+	retries: RetryQueue<T>,
+	/// Withdraws, which signatures are missing or which are relayed by this authority only if
+	/// the assigned one fails to. They are persisted as pending assignments, so that they are
+	/// fetched again after restart.
+	deferred: Vec<DeferredWithdraw>,
+	/// Assignments pending before restart, which are fetched once the contracts have been read.
+	restored: Vec<PendingAssignment>,
+	/// Assignments, which have been created or updated, but have not been reported yet.
+	assignments: Vec<PendingAssignment>,
+	/// Events, which assignments are no longer pending, but have not been reported yet.
+	discarded_assignments: Vec<ProcessedEvent>,
+	/// Events of the withdraws being relayed, which assignments are discarded once the submission has completed.
+	relaying: Vec<ProcessedEvent>,
+	/// Relays of large withdraws, which are held.
+	delayed: DelayedRelays,
+	/// Number of signatures required by the foreign contract, when it has been last checked.
+	required_signatures: u32,
+	/// Authorities of the home contract, when it has been last checked.
+	authorities: Vec<Address>,
+}
+
+impl<T: BatchTransport + PubSub + Clone> WithdrawRelay<T> {
+	/// Starts fetching withdraws of `logs` assigned to this authority and the deferred ones, which are due.
+	fn fetch(&mut self, logs: Vec<Log>, progress: BatchProgress) -> error::Result<WithdrawRelayState<T>> {
+		let (app, required_signatures) = (&self.app, self.required_signatures);
+		let now = unix_time();
+		let mut withdraws = Vec::new();
+		for restored in self.restored.drain(..) {
+			let withdraw = PendingWithdraw {
+				event: restored.event,
+				block: restored.block,
+				assignment: relay_assignment(&app.foreign_bridge, required_signatures, restored.message_hash, restored.authority),
+				failures: restored.failures,
+			};
+			if restored.due <= now {
+				withdraws.push(withdraw);
+			} else {
+				self.deferred.push(DeferredWithdraw {
+					withdraw,
+					backoff: app.timer.sleep(Duration::from_secs(restored.due - now)),
+				});
+			}
+		}
+
+		let assignments = parse_logs(app.config.direction.foreign(), logs, &self.already_processed, &mut self.quarantined, |log| {
+			let block = log.block_number.map(|block| block.low_u64());
+			signatures_payload(&app.foreign_bridge, required_signatures, log).map(|assignment| (block, assignment))
+		})?;
+
+		let scheme = app.config.authorities.withdraw_relay_assignment;
+		let fallback = app.config.authorities.withdraw_relay_fallback;
+		for (event, (block, assignment)) in assignments {
+			let rank = match relay_rank(scheme, &self.authorities, app.node(app.config.direction.foreign()).account, &assignment, fallback.is_some()) {
+				Some(rank) => rank,
+				// someone else will relay this transaction to the home chain
+				None => continue,
+			};
+
+			let withdraw = PendingWithdraw {
+				event,
+				block,
+				assignment,
+				failures: 0,
+			};
+			match fallback {
+				Some(fallback) if rank > 0 => {
+					let delay = fallback * rank as u32;
+					info!(target: "bridge", "Withdraw {:?} is assigned to other authority, relaying it in {:?} unless it has been relayed", withdraw.event.transaction_hash, delay);
+					self.assignments.push(withdraw.pending(now + delay.as_secs()));
+					self.deferred.push(DeferredWithdraw {
+						withdraw,
+						backoff: app.timer.sleep(delay),
+					});
+				},
+				_ => {
+					self.assignments.push(withdraw.pending(now));
+					withdraws.push(withdraw);
+				},
+			}
+		}
+
+		// deferred withdraws, which are due, are fetched again with the new ones
+		for mut deferred in mem::take(&mut self.deferred) {
+			match deferred.backoff.poll()? {
+				Async::Ready(()) => withdraws.push(deferred.withdraw),
+				Async::NotReady => self.deferred.push(deferred),
+			}
+		}
+
+		let assignments = withdraws.iter().map(|withdraw| withdraw.assignment.clone()).collect();
+		Ok(WithdrawRelayState::Fetch {
+			future: fetch_withdraws(&self.app, self.foreign_contract, assignments),
+			withdraws,
+			progress,
+		})
+	}
 }
 
-impl<T: Transport> Stream for WithdrawRelay<T> {
+impl<T: BatchTransport + PubSub + Clone> Stream for WithdrawRelay<T> {
 	type Item = u64;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.retries.poll(&mut self.relays, &mut self.journal, &mut self.reverted)?;
 		loop {
 			let next_state = match self.state {
 				WithdrawRelayState::Wait => {
 					let item = try_stream!(self.logs.poll());
-					let assignments = item.logs
-						.into_iter()
-						.map(|log| signatures_payload(
-								&self.app.testnet_bridge,
-								self.app.config.authorities.required_signatures,
-								self.app.config.testnet.account.clone(),
-								log))
-						.collect::<error::Result<Vec<_>>>()?;
-
-					let (signatures, messages): (Vec<_>, Vec<_>) = assignments.into_iter()
-						.filter_map(|a| a)
-						.map(|assignment| (assignment.signature_payloads, assignment.message_payload))
-						.unzip();
-
-					let message_calls = messages.into_iter()
-						.map(|payload| {
-							self.app.timer.timeout(
-								api::call(&self.app.connections.testnet, self.testnet_contract.clone(), payload),
-								self.app.config.testnet.request_timeout)
-						})
-						.collect::<Vec<_>>();
-
-					let signature_calls = signatures.into_iter()
-						.map(|payloads| {
-							payloads.into_iter()
-								.map(|payload| {
-									self.app.timer.timeout(
-										api::call(&self.app.connections.testnet, self.testnet_contract.clone(), payload),
-										self.app.config.testnet.request_timeout)
-								})
-								.collect::<Vec<_>>()
-						})
-						.map(|calls| join_all(calls))
-						.collect::<Vec<_>>();
-
-					WithdrawRelayState::Fetch {
-						future: join_all(message_calls).join(join_all(signature_calls)),
-						block: item.to,
+					let progress = BatchProgress::new(self.app.config.direction.foreign(), &item);
+					if item.logs.is_empty() && self.restored.is_empty() {
+						self.fetch(item.logs, progress)?
+					} else {
+						// authorities and the number of required signatures may have changed since the contracts have been deployed
+						WithdrawRelayState::ReadContracts {
+							future: Box::new(required_signatures(&self.app, self.app.config.direction.foreign(), self.foreign_contract)
+								.join(contract_authorities(&self.app, self.app.config.direction.home(), self.home_contract))),
+							logs: item.logs,
+							progress,
+						}
+					}
+				},
+				WithdrawRelayState::ReadContracts { ref mut future, ref mut logs, ref mut progress } => {
+					let (output, authorities) = try_ready!(future.poll());
+					let required = self.app.foreign_bridge.functions().required_signatures().output(&output.0)?;
+					let logs = mem::take(logs);
+					let progress = mem::take(progress);
+					self.required_signatures = U256::from(&required[..]).low_u64() as u32;
+					if authorities != self.authorities {
+						info!(target: "bridge", "Home contract authorities: {:?}", authorities);
+						self.authorities = authorities;
 					}
+					self.fetch(logs, progress)?
 				},
-				WithdrawRelayState::Fetch { ref mut future, block } => {
-					let (messages, signatures) = try_ready!(future.poll());
-					assert_eq!(messages.len(), signatures.len());
+				WithdrawRelayState::Fetch { ref mut future, ref mut withdraws, ref mut progress } => {
+					let fetched = try_ready!(future.poll());
 					let app = &self.app;
-					let mainnet_contract = &self.mainnet_contract;
-
-					let relays = messages.into_iter().zip(signatures.into_iter())
-						.map(|(message, signatures)| withdraw_relay_payload(&app.mainnet_bridge, signatures, message))
-						.map(|payload| TransactionRequest {
-							from: app.config.mainnet.account.clone(),
-							to: Some(mainnet_contract.clone()),
-							gas: Some(app.config.txs.withdraw_relay.gas.into()),
-							gas_price: Some(app.config.txs.withdraw_relay.gas_price.into()),
-							value: None,
-							data: Some(payload),
-							nonce: None,
-							condition: None,
-						})
-						.map(|request| {
-							app.timer.timeout(
-								api::send_transaction(&app.connections.mainnet, request),
-								app.config.mainnet.request_timeout)
-						})
-						.collect::<Vec<_>>();
-					WithdrawRelayState::RelayWithdraws {
-						future: join_all(relays),
-						block,
+
+					let now = unix_time();
+					let mut entries = Vec::new();
+					for ((message, signatures), withdraw) in fetched.into_iter().zip(withdraws.drain(..)) {
+						// the message or some of the signatures are not stored yet
+						if message.0.is_empty() || signatures.iter().any(|signature| signature.0.is_empty()) {
+							defer(app, &mut self.deferred, &mut self.assignments, withdraw)?;
+							continue;
+						}
+
+						if let Err(err) = verify_signatures(&self.authorities, &message, &signatures) {
+							warn!(target: "bridge", "Signatures of withdraw {:?} are invalid, skipping it: {}", withdraw.event.transaction_hash, err);
+							self.discarded_assignments.push(withdraw.event);
+							continue;
+						}
+
+						// the value follows the recipient in the message
+						let value = message.0.get(20..52).map(U256::from);
+						let data = match withdraw_relay_payload(&app.home_bridge, signatures, message) {
+							Ok(data) => data,
+							Err(err) => {
+								warn!(target: "bridge", "Withdraw {:?} is malformed, quarantining it: {}", withdraw.event.transaction_hash, err);
+								self.quarantined.push(quarantined_withdraw(&app.foreign_bridge, self.foreign_contract, &withdraw, &err));
+								self.discarded_assignments.push(withdraw.event);
+								continue;
+							},
+						};
+
+						let entry = JournalEntry {
+							chain: app.config.direction.home(),
+							to: self.home_contract,
+							data,
+							gas: app.config.txs.withdraw_relay.gas,
+							gas_price: app.config.txs.withdraw_relay.gas_price,
+							event: withdraw.event,
+						};
+						let value = value.expect("payload is built only from messages of 84 bytes; qed");
+						match self.delayed.hold(entry, value, now) {
+							Some(entry) => {
+								entries.push(entry);
+								self.relaying.push(withdraw.event);
+							},
+							// the assignment is discarded once the relay is held
+							None => self.discarded_assignments.push(withdraw.event),
+						}
 					}
+					// held withdraws are relayed with the ones, which are fetched after their release
+					entries.extend(self.delayed.release(now));
+
+					// processed withdraws are not public, but the home contract reverts duplicates,
+					// so withdraws which have already been processed are skipped after simulating them
+					let submission = RelaySubmission::new(app, app.config.direction.home(), entries, false, true, app.config.txs.withdraw_relay.receipt_confirmations, app.config.txs.withdraw_relay.sequence_nonces);
+					WithdrawRelayState::RelayWithdraws(Box::new(submission.with_progress(mem::take(progress))))
 				},
-				WithdrawRelayState::RelayWithdraws { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					WithdrawRelayState::Yield(Some(block))
+				WithdrawRelayState::RelayWithdraws(ref mut submission) => {
+					match try_ready!(submission.poll_checked(&mut self.journal, &mut self.relays, &mut self.discarded, &mut self.reverted, &mut self.retries)) {
+						Some(block) => return Ok(Async::Ready(Some(block))),
+						None => {
+							// the withdraws have been journaled or skipped by now
+							self.discarded_assignments.append(&mut self.relaying);
+							self.delayed.submitted();
+							WithdrawRelayState::Wait
+						},
+					}
 				},
-				WithdrawRelayState::Yield(ref mut block) => match block.take() {
-					None => WithdrawRelayState::Wait,
-					some => return Ok(some.into()),
-				}
 			};
 			self.state = next_state;
 		}
 	}
 }
 
+impl<T: BatchTransport + PubSub + Clone> Relay for WithdrawRelay<T> {
+	fn take_relays(&mut self) -> Vec<RelayOutcome> {
+		self.relays.split_off(0)
+	}
+
+	fn take_journal(&mut self) -> Vec<JournalEntry> {
+		self.journal.split_off(0)
+	}
+
+	fn take_discarded(&mut self) -> Vec<ProcessedEvent> {
+		self.discarded.split_off(0)
+	}
+
+	fn take_quarantined(&mut self) -> Vec<QuarantinedLog> {
+		self.quarantined.split_off(0)
+	}
+
+	fn take_reverted(&mut self) -> Vec<RevertedRelay> {
+		self.reverted.split_off(0)
+	}
+
+	fn failed_relays(&self) -> usize {
+		self.retries.len()
+	}
+
+	fn take_assignments(&mut self) -> Vec<PendingAssignment> {
+		self.assignments.split_off(0)
+	}
+
+	fn take_discarded_assignments(&mut self) -> Vec<ProcessedEvent> {
+		self.discarded_assignments.split_off(0)
+	}
+
+	fn take_held(&mut self) -> Vec<HeldRelay> {
+		self.delayed.take_held()
+	}
+
+	fn take_released(&mut self) -> Vec<ProcessedEvent> {
+		self.delayed.take_released()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use rustc_hex::FromHex;
-	use web3::types::{Log, Bytes};
-	use contracts::{mainnet, testnet};
-	use super::{signatures_payload, withdraw_relay_payload};
+	use web3::types::{Address, Log, Bytes, H256};
+	use contracts::{home, foreign};
+	use config::WithdrawRelayAssignment;
+	use database::{ProcessedEvent, Chain};
+	use super::{PendingWithdraw, signatures_payload, relay_rank, withdraw_relay_payload, verify_signatures, quarantined_withdraw};
 
 	#[test]
 	fn test_signatures_payload() {
-		let testnet = testnet::KovanBridge::default();
-		let my_address = "0xaff3454fce5edbc8cca8697c15331677e6ebcccc".parse().unwrap();
+		let foreign_bridge = foreign::KovanBridge::default();
 
 		let data = "000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap();
 
@@ -218,7 +649,7 @@ mod tests {
 			..Default::default()
 		};
 
-		let assignment = signatures_payload(&testnet, 2, my_address, log).unwrap().unwrap();
+		let assignment = signatures_payload(&foreign_bridge, 2, log).unwrap();
 		let expected_message: Bytes = "490a32c600000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap().into();
 		let expected_signatures: Vec<Bytes> = vec![
 			"1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000".from_hex().unwrap().into(),
@@ -226,12 +657,50 @@ mod tests {
 		];
 		assert_eq!(expected_message, assignment.message_payload);
 		assert_eq!(expected_signatures, assignment.signature_payloads);
+		assert_eq!("0xaff3454fce5edbc8cca8697c15331677e6ebcccc".parse::<Address>().unwrap(), assignment.authority);
+		assert_eq!(H256::from(0xf0), assignment.message_hash);
+	}
+
+	#[test]
+	fn test_quarantined_withdraw() {
+		let foreign_bridge = foreign::KovanBridge::default();
+		let home_bridge = home::EthereumBridge::default();
+		let data = "000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap();
+		let topic: H256 = "0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c".parse().unwrap();
+		let log = Log {
+			data: data.clone().into(),
+			topics: vec![topic],
+			..Default::default()
+		};
+		let withdraw = PendingWithdraw {
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: 0x10.into(),
+				log_index: 2,
+			},
+			block: Some(0x100),
+			assignment: signatures_payload(&foreign_bridge, 2, log).unwrap(),
+			failures: 0,
+		};
+		let contract = Address::from(0x20);
+
+		let err = withdraw_relay_payload(&home_bridge, vec![vec![0x11; 65].into()], vec![0x33; 83].into()).unwrap_err();
+		let quarantined = quarantined_withdraw(&foreign_bridge, contract, &withdraw, &err);
+		assert_eq!(withdraw.event, quarantined.event);
+		assert_eq!(contract, quarantined.address);
+		assert_eq!(vec![topic], quarantined.topics);
+		assert_eq!(Bytes::from(data), quarantined.data);
+		assert_eq!("Message has 83 bytes instead of 84", quarantined.error);
 	}
 
 	#[test]
-	fn test_signatures_payload_not_ours() {
-		let testnet = testnet::KovanBridge::default();
-		let my_address = "0xaff3454fce5edbc8cca8697c15331677e6ebcccd".parse().unwrap();
+	fn test_relay_rank() {
+		let foreign_bridge = foreign::KovanBridge::default();
+		let authorities: Vec<Address> = vec![
+			"0xaff3454fce5edbc8cca8697c15331677e6ebccca".parse().unwrap(),
+			"0xaff3454fce5edbc8cca8697c15331677e6ebcccb".parse().unwrap(),
+			"0xaff3454fce5edbc8cca8697c15331677e6ebcccc".parse().unwrap(),
+		];
 
 		let data = "000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0".from_hex().unwrap();
 
@@ -241,22 +710,62 @@ mod tests {
 			transaction_hash: Some("0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap()),
 			..Default::default()
 		};
+		let assignment = signatures_payload(&foreign_bridge, 2, log).unwrap();
+		let not_authority = "0xaff3454fce5edbc8cca8697c15331677e6ebcccd".parse().unwrap();
 
-		let assignment = signatures_payload(&testnet, 2, my_address, log).unwrap();
-		assert_eq!(None, assignment);
+		// the withdraw is relayed by the authority, which has submitted the last signature
+		let contract = WithdrawRelayAssignment::Contract;
+		assert_eq!(Some(0), relay_rank(contract, &authorities, authorities[2], &assignment, false));
+		assert_eq!(None, relay_rank(contract, &authorities, authorities[0], &assignment, false));
+		assert_eq!(Some(1), relay_rank(contract, &authorities, authorities[0], &assignment, true));
+		assert_eq!(Some(2), relay_rank(contract, &authorities, authorities[1], &assignment, true));
+		assert_eq!(None, relay_rank(contract, &authorities, not_authority, &assignment, true));
+
+		// 0xf0 % 3 == 0
+		let round_robin = WithdrawRelayAssignment::RoundRobin;
+		assert_eq!(Some(0), relay_rank(round_robin, &authorities, authorities[0], &assignment, false));
+		assert_eq!(None, relay_rank(round_robin, &authorities, authorities[2], &assignment, false));
+		assert_eq!(Some(1), relay_rank(round_robin, &authorities, authorities[1], &assignment, true));
+		assert_eq!(Some(2), relay_rank(round_robin, &authorities, authorities[2], &assignment, true));
+		assert_eq!(None, relay_rank(round_robin, &authorities, not_authority, &assignment, true));
+		assert_eq!(None, relay_rank(round_robin, &[], authorities[0], &assignment, true));
 	}
 
 	#[test]
 	fn test_withdraw_relay_payload() {
-		let mainnet = mainnet::EthereumBridge::default();
+		let home_bridge = home::EthereumBridge::default();
 		let signatures: Vec<Bytes> = vec![
 			vec![0x11; 65].into(),
 			vec![0x22; 65].into(),
 		];
 		let message: Bytes = vec![0x33; 84].into();
 
-		let payload = withdraw_relay_payload(&mainnet, signatures, message);
+		let payload = withdraw_relay_payload(&home_bridge, signatures.clone(), message.clone()).unwrap();
 		let expected: Bytes = "9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001100000000000000000000000000000000000000000000000000000000000000220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000".from_hex().unwrap().into();
 		assert_eq!(expected, payload);
+
+		// malformed values returned by the contract are rejected
+		assert!(withdraw_relay_payload(&home_bridge, signatures.clone(), vec![0x33; 83].into()).is_err());
+		assert!(withdraw_relay_payload(&home_bridge, vec![signatures[0].clone(), vec![0x22; 64].into()], message).is_err());
+	}
+
+	#[test]
+	fn test_verify_signatures() {
+		let authorities = vec![
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1".parse().unwrap(),
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c".parse().unwrap(),
+		];
+		let first: Bytes = "ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b".from_hex().unwrap().into();
+		let second: Bytes = "a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b".from_hex().unwrap().into();
+		let message: Bytes = vec![0x33; 84].into();
+
+		assert!(verify_signatures(&authorities, &message, &[first.clone(), second.clone()]).is_ok());
+		// signatures of other authorities
+		assert!(verify_signatures(&authorities[..1], &message, &[first.clone(), second.clone()]).is_err());
+		// signatures of the same authority
+		assert!(verify_signatures(&authorities, &message, &[first.clone(), first.clone()]).is_err());
+		// signatures of other message
+		assert!(verify_signatures(&authorities, &vec![0x44; 84].into(), &[first.clone(), second]).is_err());
+		assert!(verify_signatures(&authorities, &message, &[vec![0x11; 65].into()]).is_err());
 	}
 }
@@ -1,2 +1,10 @@
-use_contract!(mainnet, "EthereumBridge", "../contracts/EthereumBridge.abi");
-use_contract!(testnet, "KovanBridge", "../contracts/KovanBridge.abi");
+use_contract!(home, "EthereumBridge", "../contracts/EthereumBridge.abi");
+use_contract!(foreign, "KovanBridge", "../contracts/KovanBridge.abi");
+use_contract!(message, "MessageBridge", "../contracts/MessageBridge.abi");
+use_contract!(pause, "Pausable", "../contracts/Pausable.abi");
+use_contract!(indexed, "IndexedDeposits", "../contracts/IndexedDeposits.abi");
+
+/// Former name of the home bridge contract, which is deployed to mainnet unless `direction` is `testnet_to_mainnet`.
+pub use self::home as mainnet;
+/// Former name of the foreign bridge contract.
+pub use self::foreign as testnet;
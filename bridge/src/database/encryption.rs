@@ -0,0 +1,67 @@
+//! Encryption of the database at rest.
+//!
+//! Encrypted database is prefixed with `MAGIC`, followed by random nonce,
+//! the ciphertext and authentication tag of AES-256-GCM.
+
+use openssl::rand::rand_bytes;
+use openssl::symm::{Cipher, encrypt_aead, decrypt_aead};
+use config::DatabaseKey;
+use error::Error;
+
+const MAGIC: &[u8] = b"bridge-aes256gcm";
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+pub fn encrypt(key: &DatabaseKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+	let mut nonce = [0u8; NONCE_LEN];
+	let mut tag = [0u8; TAG_LEN];
+	rand_bytes(&mut nonce).map_err(|err| format!("Cannot generate nonce: {}", err))?;
+	let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key.0, Some(&nonce), MAGIC, data, &mut tag)
+		.map_err(|err| format!("Cannot encrypt database: {}", err))?;
+
+	let mut result = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len() + TAG_LEN);
+	result.extend_from_slice(MAGIC);
+	result.extend_from_slice(&nonce);
+	result.extend_from_slice(&ciphertext);
+	result.extend_from_slice(&tag);
+	Ok(result)
+}
+
+pub fn decrypt(key: &DatabaseKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+	if !data.starts_with(MAGIC) {
+		return Err("Database is not encrypted".into());
+	}
+
+	let data = &data[MAGIC.len()..];
+	if data.len() < NONCE_LEN + TAG_LEN {
+		return Err("Encrypted database is truncated".into());
+	}
+
+	let (nonce, data) = data.split_at(NONCE_LEN);
+	let (ciphertext, tag) = data.split_at(data.len() - TAG_LEN);
+	// authentication fails both for a wrong key and for modified data
+	decrypt_aead(Cipher::aes_256_gcm(), &key.0, Some(nonce), MAGIC, ciphertext, tag)
+		.map_err(|_| "Cannot decrypt database, the key is wrong or the database is corrupted".into())
+}
+
+#[cfg(test)]
+mod tests {
+	use config::DatabaseKey;
+	use super::{encrypt, decrypt};
+
+	#[test]
+	fn test_encrypt_and_decrypt() {
+		let key = DatabaseKey([1u8; 32]);
+		let encrypted = encrypt(&key, b"checked_deposit_relay = 120").unwrap();
+		assert!(!encrypted.windows(7).any(|window| window == b"checked"));
+		assert_eq!(b"checked_deposit_relay = 120".to_vec(), decrypt(&key, &encrypted).unwrap());
+
+		assert!(decrypt(&DatabaseKey([2u8; 32]), &encrypted).is_err());
+		assert!(decrypt(&key, b"checked_deposit_relay = 120").is_err());
+		let mut modified = encrypted.clone();
+		let last = modified.len() - 1;
+		modified[last] ^= 1;
+		assert!(decrypt(&key, &modified).is_err());
+		assert!(decrypt(&key, &encrypted[..20]).is_err());
+	}
+}
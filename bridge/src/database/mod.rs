@@ -0,0 +1,1771 @@
+mod encryption;
+mod postgres;
+mod sqlite;
+mod watch;
+
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{io, str, fs, fmt};
+use std::io::{Read, Write};
+use fs2::{FileExt, lock_contended_error};
+use serde::{Deserialize, Deserializer};
+use web3::types::{Address, Bytes, H256, U256, Log};
+use {toml, serde_json};
+use bridge::BridgeChecked;
+use config::{Config, DatabaseBackendKind, DatabaseKey, Direction, RetentionPolicy};
+use error::{Error, ResultExt, ErrorKind};
+
+pub use self::postgres::PostgresBackend;
+pub use self::sqlite::SqliteBackend;
+pub use self::watch::{WatchedBackend, DatabaseWatch, DatabaseChange};
+
+/// Application "database".
+#[derive(Debug, PartialEq, Deserialize, Serialize, Default, Clone)]
+pub struct Database {
+	/// Address of home contract.
+	#[serde(alias = "mainnet_contract_address")]
+	pub home_contract_address: Address,
+	/// Address of foreign contract.
+	#[serde(alias = "testnet_contract_address")]
+	pub foreign_contract_address: Address,
+	/// Number of block at which home contract has been deployed.
+	#[serde(alias = "mainnet_deploy")]
+	pub home_deploy: u64,
+	/// Number of block at which foreign contract has been deployed.
+	#[serde(alias = "testnet_deploy")]
+	pub foreign_deploy: u64,
+	/// Number of last block which has been checked for deposit relays.
+	pub checked_deposit_relay: u64,
+	/// Number of last block which has been checked for withdraw relays.
+	pub checked_withdraw_relay: u64,
+	/// Number of last block which has been checked for withdraw confirms.
+	pub checked_withdraw_confirm: u64,
+	/// Number of last mainnet block which has been checked for message relays.
+	/// Databases created before messages were relayed are checked from the contract deployment.
+	#[serde(default)]
+	pub checked_mainnet_message_relay: u64,
+	/// Number of last testnet block which has been checked for message relays.
+	#[serde(default)]
+	pub checked_testnet_message_relay: u64,
+	/// Transactions which relayed the events, so that the events are not relayed again after restart.
+	/// Events of databases created before the transactions were recorded are loaded from `processed_events`.
+	#[serde(default, alias = "processed_events", deserialize_with = "deserialize_relays", skip_serializing_if = "Vec::is_empty")]
+	pub relays: Vec<RelayOutcome>,
+	/// Relay transactions, which have been submitted, but are not known to be relayed.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub journal: Vec<JournalEntry>,
+	/// Logs, which could not be parsed and have been skipped.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub quarantine: Vec<QuarantinedLog>,
+	/// Deposits, which have not been relayed, because their value is out of the configured limits.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub filtered_deposits: Vec<FilteredDeposit>,
+	/// Relays, which have not taken effect on the chain to which they have been submitted.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub discrepancies: Vec<Discrepancy>,
+	/// Withdraws assigned to this authority, which have not been relayed or skipped yet.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub pending_assignments: Vec<PendingAssignment>,
+	/// Relays of large transfers, which are held until they are released by `relay_delay`.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub held_relays: Vec<HeldRelay>,
+	/// Relay transactions, which have been mined, but reverted.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub reverted_relays: Vec<RevertedRelay>,
+	/// Fees paid for the relay transactions by each bridge component.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub relay_fees: Vec<RelayFees>,
+	/// Payloads of the relay transactions sent recently, which are not sent again.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub sent_payloads: Vec<SentPayload>,
+	/// Checkpoints changed manually, e.g. to check the blocks again.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub checkpoint_changes: Vec<CheckpointChange>,
+}
+
+/// Chain on which an event has been emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chain {
+	Mainnet,
+	Testnet,
+}
+
+impl Chain {
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			Chain::Mainnet => "mainnet",
+			Chain::Testnet => "testnet",
+		}
+	}
+}
+
+/// Event handled by one of the bridge components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ProcessedEvent {
+	pub chain: Chain,
+	pub transaction_hash: H256,
+	pub log_index: u64,
+}
+
+/// Transaction submitted to relay an event.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct RelayOutcome {
+	/// Chain to which the event has been relayed.
+	pub chain: Chain,
+	pub transaction_hash: H256,
+	pub gas_price: u64,
+	/// Unix time of the submission in seconds.
+	pub timestamp: u64,
+	/// Block of the event, if it has been relayed from a batch of logs.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub block: Option<u64>,
+	pub event: ProcessedEvent,
+}
+
+/// Relay recorded in the database, or an event relayed before the transactions were recorded.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecordedRelay {
+	Relay(RelayOutcome),
+	Event(ProcessedEvent),
+}
+
+/// Loads the relays, keeping the events recorded without their transactions, so that they are not relayed again.
+fn deserialize_relays<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<RelayOutcome>, D::Error> {
+	let relays = Vec::<RecordedRelay>::deserialize(deserializer)?;
+	let relays = relays.into_iter()
+		.map(|relay| match relay {
+			RecordedRelay::Relay(relay) => relay,
+			// neither the transaction nor the chain it has been sent to are known
+			RecordedRelay::Event(event) => RelayOutcome {
+				chain: event.chain,
+				transaction_hash: H256::zero(),
+				gas_price: 0,
+				timestamp: 0,
+				block: None,
+				event,
+			},
+		})
+		.collect();
+	Ok(relays)
+}
+
+impl RelayOutcome {
+	/// Returns true if the block of the event is known and it has been `checked` on the chain of the event,
+	/// so the event is never read again, unless the checkpoint is rewound.
+	pub fn is_checked(&self, checked: &[(Chain, u64)]) -> bool {
+		self.block.map_or(false, |block| checked.iter().any(|&(chain, last)| chain == self.event.chain && block <= last))
+	}
+}
+
+/// Relay transaction written to the journal before it is submitted.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct JournalEntry {
+	/// Chain to which the event is relayed.
+	pub chain: Chain,
+	/// Address of the contract receiving the transaction.
+	pub to: Address,
+	pub data: Bytes,
+	pub gas: u64,
+	pub gas_price: u64,
+	pub event: ProcessedEvent,
+}
+
+/// Log, which could not be parsed, e.g. because it has been emitted by an unexpected event with the same topic.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QuarantinedLog {
+	/// Address of the contract, which emitted the log.
+	pub address: Address,
+	pub topics: Vec<H256>,
+	pub data: Bytes,
+	/// Reason why the log could not be parsed.
+	pub error: String,
+	pub event: ProcessedEvent,
+}
+
+/// Reason why a deposit has not been relayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepositFilter {
+	/// Deposit is lower than `min_deposit` and it has been skipped.
+	BelowMinimum,
+	/// Deposit is higher than `max_deposit` and it's held until it's approved.
+	AboveMaximum,
+}
+
+impl DepositFilter {
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			DepositFilter::BelowMinimum => "below_minimum",
+			DepositFilter::AboveMaximum => "above_maximum",
+		}
+	}
+}
+
+/// Deposit, which has not been relayed, because its value is out of the configured limits.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct FilteredDeposit {
+	pub recipient: Address,
+	pub value: U256,
+	pub filter: DepositFilter,
+	pub event: ProcessedEvent,
+}
+
+/// Relay, which has been submitted, but which has not taken effect on the chain by the time it was verified.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Discrepancy {
+	/// Chain to which the event has been relayed.
+	pub chain: Chain,
+	/// Relay transaction.
+	pub transaction_hash: H256,
+	/// Unix time of the verification in seconds.
+	pub timestamp: u64,
+	pub event: ProcessedEvent,
+}
+
+/// Relay transaction, which has been mined, but reverted, or which has expired before it was mined.
+/// The event is written back to the journal and relayed again.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RevertedRelay {
+	/// Chain to which the event has been relayed.
+	pub chain: Chain,
+	/// Reverted relay transaction.
+	pub transaction_hash: H256,
+	/// Reason of the revert, if it has been reported by the node, or of the expiry.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub reason: Option<String>,
+	/// Unix time of the detection of the revert in seconds.
+	pub timestamp: u64,
+	pub event: ProcessedEvent,
+}
+
+/// Fees paid for the mined relay transactions of a bridge component to a chain.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RelayFees {
+	/// Name of the component, e.g. `deposit_relay`.
+	pub stream: String,
+	/// Chain to which the transactions have been sent.
+	pub chain: Chain,
+	/// Number of the mined transactions.
+	pub transactions: u64,
+	pub gas_used: u64,
+	/// Total fees in wei.
+	pub fees: U256,
+}
+
+impl RelayFees {
+	/// Returns `true` if the fees are accounted under the same component and chain.
+	pub fn same_account(&self, other: &RelayFees) -> bool {
+		self.stream == other.stream && self.chain == other.chain
+	}
+
+	/// Adds `other` fees to the totals.
+	pub fn add(&mut self, other: &RelayFees) {
+		self.transactions += other.transactions;
+		self.gas_used += other.gas_used;
+		self.fees = self.fees + other.fees;
+	}
+}
+
+/// Payload of the relay transaction, which has been sent.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct SentPayload {
+	/// Chain to which the transaction has been sent.
+	pub chain: Chain,
+	pub to: Address,
+	/// Keccak-256 hash of the data of the transaction.
+	pub data_hash: H256,
+	pub transaction_hash: H256,
+	/// Unix time of the submission.
+	pub timestamp: u64,
+}
+
+impl SentPayload {
+	/// Returns `true` if the transactions have been sent to the same contract of the same chain with the same data.
+	pub fn same_payload(&self, other: &SentPayload) -> bool {
+		self.chain == other.chain && self.to == other.to && self.data_hash == other.data_hash
+	}
+}
+
+/// Manual change of the last block checked by a bridge component.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CheckpointChange {
+	/// Name of the component, e.g. `deposit_relay`.
+	pub stream: String,
+	/// Last checked block before the change.
+	pub from: u64,
+	pub to: u64,
+	/// Unix time of the change.
+	pub timestamp: u64,
+}
+
+impl CheckpointChange {
+	/// Returns the checkpoint set by the change, `None` if the component is unknown.
+	pub fn checkpoint(&self) -> Option<BridgeChecked> {
+		BridgeChecked::from_stream(&self.stream, self.to)
+	}
+}
+
+/// Withdraw assigned to this authority, which is fetched and relayed once its signatures are collected.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct PendingAssignment {
+	/// Hash of the withdraw message, which signatures are collected.
+	pub message_hash: H256,
+	/// Authority, which has submitted the last signature.
+	pub authority: Address,
+	/// Block of the event, if known.
+	pub block: Option<u64>,
+	/// Number of times the withdraw has been fetched before its signatures were available.
+	pub failures: u32,
+	/// Unix time in seconds, at which the withdraw is fetched again.
+	pub due: u64,
+	pub event: ProcessedEvent,
+}
+
+/// Relay of a large transfer, which is written to the journal and submitted once it's released.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct HeldRelay {
+	/// Unix time in seconds, at which the relay is released.
+	pub release: u64,
+	pub entry: JournalEntry,
+}
+
+impl ProcessedEvent {
+	/// Fails if the `log` is pending, i.e. it has no transaction hash or index identifying the event.
+	pub fn from_log(chain: Chain, log: &Log) -> Result<Self, Error> {
+		let transaction_hash = log.transaction_hash
+			.ok_or_else(|| Error::from("Log has no transaction hash, it has not been mined"))?;
+		let log_index = log.log_index
+			.ok_or_else(|| Error::from(format!("Log of {:?} has no index, it has not been mined", transaction_hash)))?;
+		Ok(ProcessedEvent {
+			chain,
+			transaction_hash,
+			log_index: log_index.low_u64(),
+		})
+	}
+}
+
+impl str::FromStr for Database {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		toml::from_str(s).chain_err(|| "Cannot parse database")
+	}
+}
+
+impl fmt::Display for Database {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&toml::to_string(self).expect("serialization can't fail; qed"))
+	}
+}
+
+impl Database {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Database, Error> {
+		let mut file = match fs::File::open(&path) {
+			Ok(file) => file,
+			Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Err(ErrorKind::MissingFile(format!("{:?}", path.as_ref())).into()),
+			Err(err) => return Err(err).chain_err(|| "Cannot open database"),
+		};
+
+		let mut buffer = String::new();
+		file.read_to_string(&mut buffer)?;
+		buffer.parse()
+	}
+
+	pub fn save<W: Write>(&self, mut write: W) -> Result<(), Error> {
+		write.write_all(self.to_string().as_bytes())?;
+		Ok(())
+	}
+
+	/// Serializes the database, so that it can be imported to any backend.
+	pub fn to_json(&self) -> String {
+		serde_json::to_string_pretty(self).expect("serialization can't fail; qed")
+	}
+
+	pub fn from_json(json: &str) -> Result<Database, Error> {
+		serde_json::from_str(json).chain_err(|| "Cannot parse database")
+	}
+
+	/// Address of the bridge contract on `chain` of the bridge in `direction`.
+	pub fn contract_address(&self, direction: Direction, chain: Chain) -> Address {
+		if chain == direction.home() {
+			self.home_contract_address
+		} else {
+			self.foreign_contract_address
+		}
+	}
+
+	/// Number of block at which the bridge contract on `chain` of the bridge in `direction` has been deployed.
+	pub fn deploy(&self, direction: Direction, chain: Chain) -> u64 {
+		if chain == direction.home() {
+			self.home_deploy
+		} else {
+			self.foreign_deploy
+		}
+	}
+
+	/// Checks that the database is consistent, e.g. after it has been edited by hand.
+	pub fn verify(&self) -> Result<(), Error> {
+		if self.home_contract_address == Address::default() || self.foreign_contract_address == Address::default() {
+			return Err("Contract addresses are not set".into());
+		}
+
+		let checkpoints = [
+			(BridgeChecked::DepositRelay(self.checked_deposit_relay), self.home_deploy),
+			(BridgeChecked::WithdrawRelay(self.checked_withdraw_relay), self.foreign_deploy),
+			(BridgeChecked::WithdrawConfirm(self.checked_withdraw_confirm), self.foreign_deploy),
+		];
+		for &(check, deploy) in &checkpoints {
+			if check.block() < deploy {
+				return Err(format!("Checkpoint of {} is before the contract deployment in block {}", check.stream(), deploy).into());
+			}
+		}
+
+		let mut relayed = HashSet::new();
+		for relay in &self.relays {
+			if !relayed.insert(relay.event) {
+				return Err(format!("Event {:?} is relayed more than once", relay.event).into());
+			}
+		}
+
+		let mut journaled = HashSet::new();
+		for entry in &self.journal {
+			if relayed.contains(&entry.event) {
+				return Err(format!("Event {:?} is journaled, but it is already relayed", entry.event).into());
+			}
+			if !journaled.insert(entry.event) {
+				return Err(format!("Event {:?} is journaled more than once", entry.event).into());
+			}
+		}
+
+		for deposit in &self.filtered_deposits {
+			if relayed.contains(&deposit.event) || journaled.contains(&deposit.event) {
+				return Err(format!("Deposit {:?} is filtered, but it is also relayed", deposit.event).into());
+			}
+		}
+
+		let mut held = HashSet::new();
+		for relay in &self.held_relays {
+			if relayed.contains(&relay.entry.event) || journaled.contains(&relay.entry.event) {
+				return Err(format!("Relay of {:?} is held, but it is also relayed", relay.entry.event).into());
+			}
+			if !held.insert(relay.entry.event) {
+				return Err(format!("Relay of {:?} is held more than once", relay.entry.event).into());
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Records transactions which relayed the events and removes them from the journal.
+	pub fn record_relays(&mut self, relays: &[RelayOutcome]) {
+		self.journal.retain(|entry| relays.iter().all(|relay| relay.event != entry.event));
+		self.relays.extend_from_slice(relays);
+	}
+
+	/// Removes journal entries of the events, which turned out not to need relaying.
+	pub fn discard_journal(&mut self, events: &[ProcessedEvent]) {
+		self.journal.retain(|entry| !events.contains(&entry.event));
+	}
+
+	/// Records logs, which could not be parsed. Logs, which are already quarantined, are replaced.
+	pub fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) {
+		self.quarantine.retain(|quarantined| logs.iter().all(|log| log.event != quarantined.event));
+		self.quarantine.extend_from_slice(logs);
+	}
+
+	/// Records deposits, which have not been relayed. Deposits, which are already filtered, are replaced.
+	pub fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) {
+		self.filtered_deposits.retain(|filtered| deposits.iter().all(|deposit| deposit.event != filtered.event));
+		self.filtered_deposits.extend_from_slice(deposits);
+	}
+
+	/// Records relays, which have not taken effect. Discrepancies of the same events are replaced.
+	pub fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) {
+		self.discrepancies.retain(|recorded| discrepancies.iter().all(|discrepancy| discrepancy.event != recorded.event));
+		self.discrepancies.extend_from_slice(discrepancies);
+	}
+
+	/// Records relay transactions, which have been reverted, and removes relays of their events,
+	/// so that they are journaled again. Reverts of the same events are replaced.
+	pub fn record_reverts(&mut self, reverts: &[RevertedRelay]) {
+		self.relays.retain(|relay| reverts.iter().all(|revert| revert.event != relay.event));
+		self.reverted_relays.retain(|recorded| reverts.iter().all(|revert| revert.event != recorded.event));
+		self.reverted_relays.extend_from_slice(reverts);
+	}
+
+	/// Adds fees of the relay transactions to the totals of their components and chains.
+	pub fn record_fees(&mut self, fees: &[RelayFees]) {
+		for fee in fees {
+			match self.relay_fees.iter_mut().find(|recorded| recorded.same_account(fee)) {
+				Some(recorded) => recorded.add(fee),
+				None => self.relay_fees.push(fee.clone()),
+			}
+		}
+	}
+
+	/// Records payloads of the sent transactions, replacing the same ones,
+	/// and removes those sent before unix time `expired`.
+	pub fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) {
+		self.sent_payloads.retain(|sent| sent.timestamp >= expired && payloads.iter().all(|payload| !payload.same_payload(sent)));
+		self.sent_payloads.extend_from_slice(payloads);
+	}
+
+	/// Records withdraws, which are about to be fetched. Assignments of the same events are replaced.
+	pub fn write_assignments(&mut self, assignments: &[PendingAssignment]) {
+		self.pending_assignments.retain(|pending| assignments.iter().all(|assignment| assignment.event != pending.event));
+		self.pending_assignments.extend_from_slice(assignments);
+	}
+
+	/// Removes assignments of the withdraws, which have been journaled or skipped.
+	pub fn discard_assignments(&mut self, events: &[ProcessedEvent]) {
+		self.pending_assignments.retain(|assignment| !events.contains(&assignment.event));
+	}
+
+	/// Records relays, which are held until they are released. Relays of the same events are replaced.
+	pub fn hold_relays(&mut self, relays: &[HeldRelay]) {
+		self.held_relays.retain(|held| relays.iter().all(|relay| relay.entry.event != held.entry.event));
+		self.held_relays.extend_from_slice(relays);
+	}
+
+	/// Removes held relays of the events, which have been released or cancelled.
+	pub fn release_relays(&mut self, events: &[ProcessedEvent]) {
+		self.held_relays.retain(|relay| !events.contains(&relay.entry.event));
+	}
+
+	/// Writes `entry` relaying the deposit, which is held until it's approved, to the journal,
+	/// so that it's relayed once the bridge is started.
+	pub fn approve_deposit(&mut self, entry: JournalEntry) -> Result<(), Error> {
+		let index = self.filtered_deposits.iter()
+			.position(|deposit| deposit.event == entry.event && deposit.filter == DepositFilter::AboveMaximum)
+			.ok_or_else(|| Error::from(format!("Deposit {:?} is not held for approval", entry.event)))?;
+		self.filtered_deposits.remove(index);
+		self.journal.push(entry);
+		Ok(())
+	}
+
+	/// Removes relays, which should not be retained at unix time `now`.
+	/// `checked` are the last blocks of the chains, which have been checked by every component reading them.
+	/// Returns number of removed relays.
+	pub fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> usize {
+		let len = self.relays.len();
+		if policy.prune_checked {
+			self.relays.retain(|relay| !relay.is_checked(checked));
+		}
+		if let Some(max_age) = policy.max_age {
+			let oldest = now.saturating_sub(max_age.as_secs());
+			self.relays.retain(|relay| relay.timestamp >= oldest);
+		}
+		if let Some(max_relays) = policy.max_relays {
+			// relays are ordered from the oldest one
+			let excess = self.relays.len().saturating_sub(max_relays);
+			self.relays.drain(..excess);
+		}
+		len - self.relays.len()
+	}
+
+	/// Returns last block checked by the component of `check`.
+	pub fn checked(&self, check: BridgeChecked) -> u64 {
+		match check {
+			BridgeChecked::DepositRelay(_) => self.checked_deposit_relay,
+			BridgeChecked::WithdrawRelay(_) => self.checked_withdraw_relay,
+			BridgeChecked::WithdrawConfirm(_) => self.checked_withdraw_confirm,
+			BridgeChecked::MainnetMessageRelay(_) => self.checked_mainnet_message_relay,
+			BridgeChecked::TestnetMessageRelay(_) => self.checked_testnet_message_relay,
+		}
+	}
+
+	/// Sets the checkpoint changed manually and records the change.
+	pub fn change_checkpoint(&mut self, change: &CheckpointChange) {
+		if let Some(check) = change.checkpoint() {
+			self.update_checkpoint(check);
+		}
+		self.checkpoint_changes.push(change.clone());
+	}
+
+	/// Records last block checked by one of the bridge components.
+	pub fn update_checkpoint(&mut self, check: BridgeChecked) {
+		match check {
+			BridgeChecked::DepositRelay(n) => {
+				self.checked_deposit_relay = n;
+			},
+			BridgeChecked::WithdrawRelay(n) => {
+				self.checked_withdraw_relay = n;
+			},
+			BridgeChecked::WithdrawConfirm(n) => {
+				self.checked_withdraw_confirm = n;
+			},
+			BridgeChecked::MainnetMessageRelay(n) => {
+				self.checked_mainnet_message_relay = n;
+			},
+			BridgeChecked::TestnetMessageRelay(n) => {
+				self.checked_testnet_message_relay = n;
+			},
+		}
+	}
+}
+
+/// Storage of the application database.
+pub trait DatabaseBackend {
+	/// Prevents other bridges from writing the database until the backend is dropped.
+	/// Backends shared with a standby bridge wait until the lock is released, the others fail if it's held.
+	fn lock(&mut self) -> Result<(), Error> {
+		Ok(())
+	}
+
+	/// Loads the database. Returns `None` if it has not been saved yet.
+	fn load(&mut self) -> Result<Option<Database>, Error>;
+
+	/// Replaces the whole database, e.g. after deploying new contracts.
+	fn save(&mut self, database: &Database) -> Result<(), Error>;
+
+	/// Records last blocks checked by the bridge components.
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error>;
+
+	/// Sets the checkpoint changed manually and records the change.
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error>;
+
+	/// Records transactions which relayed the events and removes them from the journal.
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error>;
+
+	/// Records relay transactions, which are about to be submitted.
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error>;
+
+	/// Removes journal entries of the events, which turned out not to need relaying.
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error>;
+
+	/// Records logs, which could not be parsed.
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error>;
+
+	/// Records deposits, which have not been relayed because of their value.
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error>;
+
+	/// Records relays, which have not taken effect on the chain.
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error>;
+
+	/// Records relay transactions, which have been reverted, and removes relays of their events.
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error>;
+
+	/// Adds fees of the relay transactions to the totals.
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error>;
+
+	/// Records payloads of the sent relay transactions, replacing the same ones,
+	/// and removes those sent before unix time `expired`.
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error>;
+
+	/// Records withdraws assigned to this authority, which are about to be fetched.
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error>;
+
+	/// Removes assignments of the withdraws, which have been journaled or skipped.
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error>;
+
+	/// Records relays, which are held until they are released.
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error>;
+
+	/// Removes held relays of the events, which have been released or cancelled.
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error>;
+
+	/// Removes relays, which should not be retained at unix time `now`.
+	/// `checked` are the last blocks of the chains, which have been checked by every component reading them.
+	/// Returns number of removed relays.
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error>;
+
+	/// Reclaims space freed by pruning.
+	fn compact(&mut self) -> Result<(), Error> {
+		Ok(())
+	}
+}
+
+impl<B: DatabaseBackend + ?Sized> DatabaseBackend for Box<B> {
+	fn lock(&mut self) -> Result<(), Error> {
+		(**self).lock()
+	}
+
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		(**self).load()
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		(**self).save(database)
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		(**self).update_checkpoints(checks)
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		(**self).change_checkpoint(change)
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		(**self).record_relays(relays)
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		(**self).write_journal(entries)
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		(**self).discard_journal(events)
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		(**self).quarantine_logs(logs)
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		(**self).filter_deposits(deposits)
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		(**self).record_discrepancies(discrepancies)
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		(**self).record_reverts(reverts)
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		(**self).record_fees(fees)
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		(**self).record_payloads(payloads, expired)
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		(**self).write_assignments(assignments)
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		(**self).discard_assignments(events)
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		(**self).hold_relays(relays)
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		(**self).release_relays(events)
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		(**self).prune(policy, now, checked)
+	}
+
+	fn compact(&mut self) -> Result<(), Error> {
+		(**self).compact()
+	}
+}
+
+/// Opens the database at `path` stored by the backend chosen in `config`.
+/// If there is a namespace, only the bridge stored under it is accessed.
+pub fn open<P: AsRef<Path>>(config: &Config, path: P) -> Result<Box<DatabaseBackend>, Error> {
+	let namespace = config.database_namespace.as_deref();
+	let result: Box<DatabaseBackend> = match config.database_backend {
+		DatabaseBackendKind::Toml => Box::new(TomlFileBackend::new(path, namespace).with_key(config.database_key.clone())),
+		DatabaseBackendKind::Sqlite => Box::new(SqliteBackend::open(path, namespace)?),
+		DatabaseBackendKind::Postgres => {
+			let params = path.as_ref().to_str().ok_or_else(|| Error::from("Invalid database connection string"))?;
+			Box::new(PostgresBackend::connect(params, namespace)?)
+		},
+	};
+
+	Ok(result)
+}
+
+/// Returns path of the file locked by the bridge using database at `path` under `namespace`.
+fn lock_path(path: &Path, namespace: Option<&str>) -> PathBuf {
+	let mut name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+	if let Some(namespace) = namespace {
+		name.push(".");
+		name.push(namespace);
+	}
+	name.push(".lock");
+	path.with_file_name(name)
+}
+
+/// Returns path of the file locked while the database at `path` is written, so that the bridges sharing it
+/// under other namespaces don't overwrite each other's changes.
+fn write_lock_path(path: &Path) -> PathBuf {
+	let mut name = OsString::from(".");
+	name.push(path.file_name().unwrap_or_default());
+	name.push(".write.lock");
+	path.with_file_name(name)
+}
+
+/// Path of the file, which the database at `path` is written to before it replaces it.
+fn temp_path(path: &Path) -> PathBuf {
+	let mut name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+	name.push(".tmp");
+	path.with_file_name(name)
+}
+
+fn open_lock_file(path: &Path) -> Result<fs::File, Error> {
+	fs::OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(false)
+		.open(path)
+		.chain_err(|| format!("Cannot open lock file {}", path.display()))
+}
+
+/// Locks the file at `path` until it's closed, waiting until the other processes release it.
+fn wait_for_lock(path: &Path) -> Result<fs::File, Error> {
+	let file = open_lock_file(path)?;
+	file.lock_exclusive().chain_err(|| format!("Cannot lock {}", path.display()))?;
+	Ok(file)
+}
+
+/// Locks the file at `path` until it's closed. Fails if the lock is already held by another process.
+fn lock_file(path: &Path) -> Result<fs::File, Error> {
+	let file = open_lock_file(path)?;
+	match file.try_lock_exclusive() {
+		Ok(()) => Ok(file),
+		Err(ref err) if err.raw_os_error() == lock_contended_error().raw_os_error() =>
+			Err(format!("Database is used by another bridge, lock file {} is held", path.display()).into()),
+		Err(err) => Err(err).chain_err(|| format!("Cannot lock {}", path.display())),
+	}
+}
+
+/// Database stored in a single toml file.
+///
+/// Databases of namespaced bridges are stored in tables named after the namespace,
+/// while the database of a bridge without a namespace is the whole file.
+pub struct TomlFileBackend {
+	path: PathBuf,
+	namespace: Option<String>,
+	/// Whole file is encrypted if there is a key.
+	key: Option<DatabaseKey>,
+	/// Last loaded or saved database.
+	database: Option<Database>,
+	/// Open lock file, once the database is locked.
+	lock: Option<fs::File>,
+}
+
+impl TomlFileBackend {
+	pub fn new<P: AsRef<Path>>(path: P, namespace: Option<&str>) -> Self {
+		TomlFileBackend {
+			path: path.as_ref().to_path_buf(),
+			namespace: namespace.map(Into::into),
+			key: None,
+			database: None,
+			lock: None,
+		}
+	}
+
+	/// Encrypts the file with `key`.
+	pub fn with_key(mut self, key: Option<DatabaseKey>) -> Self {
+		self.key = key;
+		self
+	}
+
+	/// Returns contents of the file or `None` if it does not exist.
+	fn read_file(&self) -> Result<Option<String>, Error> {
+		let mut file = match fs::File::open(&self.path) {
+			Ok(file) => file,
+			Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(err).chain_err(|| "Cannot open database"),
+		};
+
+		let mut buffer = Vec::new();
+		file.read_to_end(&mut buffer)?;
+		if let Some(ref key) = self.key {
+			buffer = encryption::decrypt(key, &buffer)?;
+		}
+		String::from_utf8(buffer).map(Some).chain_err(|| "Database is not valid UTF-8")
+	}
+
+	fn write_file(&self, contents: String) -> Result<(), Error> {
+		let contents = match self.key {
+			Some(ref key) => encryption::encrypt(key, contents.as_bytes())?,
+			None => contents.into_bytes(),
+		};
+		// the database is replaced only once it's written completely, so it's never left truncated
+		let path = temp_path(&self.path);
+		let mut file = fs::File::create(&path).chain_err(|| "Cannot create database")?;
+		file.write_all(&contents)?;
+		file.sync_all()?;
+		fs::rename(&path, &self.path).chain_err(|| "Cannot replace database")?;
+		Ok(())
+	}
+
+	/// Reads databases of all namespaced bridges.
+	fn read_namespaces(&self) -> Result<BTreeMap<String, Database>, Error> {
+		match self.read_file()? {
+			Some(contents) => toml::from_str(&contents).map_err(Into::into),
+			None => Ok(BTreeMap::new()),
+		}
+	}
+
+	fn write(&self, database: &Database) -> Result<(), Error> {
+		// the file is read and replaced by one bridge at a time, the lock is released once it's dropped
+		let _lock = wait_for_lock(&write_lock_path(&self.path))?;
+		let namespace = match self.namespace {
+			Some(ref namespace) => namespace,
+			None => return self.write_file(database.to_string()),
+		};
+
+		// databases of the other bridges might have been changed since they were read
+		let mut namespaces = self.read_namespaces()?;
+		namespaces.insert(namespace.clone(), database.clone());
+		self.write_file(toml::to_string(&namespaces).expect("serialization can't fail; qed"))
+	}
+
+	/// Applies `f` to the last loaded or saved database and writes the result.
+	fn update<F: FnOnce(&mut Database)>(&mut self, f: F) -> Result<(), Error> {
+		let mut database = match self.database.take() {
+			Some(database) => database,
+			None => self.load()?.ok_or_else(|| ErrorKind::MissingFile(format!("{:?}", self.path)))?,
+		};
+
+		f(&mut database);
+		let result = self.write(&database);
+		self.database = Some(database);
+		result
+	}
+}
+
+impl DatabaseBackend for TomlFileBackend {
+	fn lock(&mut self) -> Result<(), Error> {
+		if self.lock.is_none() {
+			self.lock = Some(lock_file(&lock_path(&self.path, self.namespace.as_deref()))?);
+		}
+		Ok(())
+	}
+
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		self.database = match self.namespace {
+			Some(ref namespace) => self.read_namespaces()?.remove(namespace),
+			None => match self.read_file()? {
+				Some(contents) => Some(contents.parse()?),
+				None => None,
+			},
+		};
+		Ok(self.database.clone())
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		self.write(database)?;
+		self.database = Some(database.clone());
+		Ok(())
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		self.update(|database| for check in checks {
+			database.update_checkpoint(*check);
+		})
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		self.update(|database| database.change_checkpoint(change))
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		self.update(|database| database.record_relays(relays))
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		self.update(|database| database.journal.extend_from_slice(entries))
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.discard_journal(events))
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		self.update(|database| database.quarantine_logs(logs))
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		self.update(|database| database.filter_deposits(deposits))
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		self.update(|database| database.record_discrepancies(discrepancies))
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		self.update(|database| database.record_reverts(reverts))
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		self.update(|database| database.record_fees(fees))
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		self.update(|database| database.record_payloads(payloads, expired))
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		self.update(|database| database.write_assignments(assignments))
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.discard_assignments(events))
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		self.update(|database| database.hold_relays(relays))
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.release_relays(events))
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		let mut pruned = 0;
+		// the whole file is rewritten, so it never contains pruned relays
+		self.update(|database| pruned = database.prune(policy, now, checked))?;
+		Ok(pruned)
+	}
+}
+
+/// Database kept only in memory, e.g. by a dry run, which must not change the stored database.
+#[derive(Default)]
+pub struct MemoryBackend {
+	database: Option<Database>,
+}
+
+impl MemoryBackend {
+	pub fn new(database: Option<Database>) -> Self {
+		MemoryBackend {
+			database,
+		}
+	}
+
+	fn update<F: FnOnce(&mut Database)>(&mut self, f: F) -> Result<(), Error> {
+		let database = self.database.as_mut().ok_or_else(|| Error::from("Database is empty"))?;
+		f(database);
+		Ok(())
+	}
+}
+
+impl DatabaseBackend for MemoryBackend {
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		Ok(self.database.clone())
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		self.database = Some(database.clone());
+		Ok(())
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		self.update(|database| for check in checks {
+			database.update_checkpoint(*check);
+		})
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		self.update(|database| database.change_checkpoint(change))
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		self.update(|database| database.record_relays(relays))
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		self.update(|database| database.journal.extend_from_slice(entries))
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.discard_journal(events))
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		self.update(|database| database.quarantine_logs(logs))
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		self.update(|database| database.filter_deposits(deposits))
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		self.update(|database| database.record_discrepancies(discrepancies))
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		self.update(|database| database.record_reverts(reverts))
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		self.update(|database| database.record_fees(fees))
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		self.update(|database| database.record_payloads(payloads, expired))
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		self.update(|database| database.write_assignments(assignments))
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.discard_assignments(events))
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		self.update(|database| database.hold_relays(relays))
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.update(|database| database.release_relays(events))
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		let mut pruned = 0;
+		self.update(|database| pruned = database.prune(policy, now, checked))?;
+		Ok(pruned)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	use self::tempdir::TempDir;
+	use bridge::BridgeChecked;
+	use std::thread;
+	use std::time::Duration;
+	use web3::types::{Address, Log};
+	use config::{DatabaseKey, RetentionPolicy};
+	use super::{Database, DatabaseBackend, TomlFileBackend, MemoryBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, Chain, temp_path};
+
+	#[test]
+	fn databse_to_and_from_str() {
+		let toml =
+r#"home_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7"
+foreign_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
+home_deploy = 100
+foreign_deploy = 101
+checked_deposit_relay = 120
+checked_withdraw_relay = 121
+checked_withdraw_confirm = 121
+checked_mainnet_message_relay = 122
+checked_testnet_message_relay = 123
+"#;
+
+		let expected = Database {
+			home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			home_deploy: 100,
+			foreign_deploy: 101,
+			checked_deposit_relay: 120,
+			checked_withdraw_relay: 121,
+			checked_withdraw_confirm: 121,
+			checked_mainnet_message_relay: 122,
+			checked_testnet_message_relay: 123,
+			relays: vec![],
+			journal: vec![],
+			quarantine: vec![],
+			filtered_deposits: vec![],
+			discrepancies: vec![],
+			pending_assignments: vec![],
+			held_relays: vec![],
+			reverted_relays: vec![],
+			relay_fees: vec![],
+			sent_payloads: vec![],
+			checkpoint_changes: vec![],
+		};
+
+		let database = toml.parse().unwrap();
+		assert_eq!(expected, database);
+		let s = database.to_string();
+		assert_eq!(s, toml);
+
+		// databases created before messages were relayed have no message relay checkpoints
+		let database: Database = toml.replace("checked_mainnet_message_relay = 122\n", "").parse().unwrap();
+		assert_eq!(0, database.checked_mainnet_message_relay);
+	}
+
+	#[test]
+	fn database_from_str_with_mainnet_and_testnet_names() {
+		let database: Database = r#"mainnet_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7"
+testnet_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
+mainnet_deploy = 100
+testnet_deploy = 101
+checked_deposit_relay = 120
+checked_withdraw_relay = 121
+checked_withdraw_confirm = 121
+"#.parse().unwrap();
+
+		assert_eq!("0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse::<Address>().unwrap(), database.home_contract_address);
+		assert_eq!("0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse::<Address>().unwrap(), database.foreign_contract_address);
+		assert_eq!(100, database.home_deploy);
+		assert_eq!(101, database.foreign_deploy);
+		// the database is saved with the new names
+		assert!(database.to_string().starts_with("home_contract_address = "));
+	}
+
+	#[test]
+	fn database_from_str_with_processed_events() {
+		let database: Database = r#"home_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7"
+foreign_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
+home_deploy = 100
+foreign_deploy = 101
+checked_deposit_relay = 120
+checked_withdraw_relay = 121
+checked_withdraw_confirm = 121
+
+[[processed_events]]
+chain = "testnet"
+transaction_hash = "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+log_index = 2
+"#.parse().unwrap();
+
+		let event = ProcessedEvent {
+			chain: Chain::Testnet,
+			transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+			log_index: 2,
+		};
+		assert_eq!(vec![event], database.relays.iter().map(|relay| relay.event).collect::<Vec<_>>());
+		// the relays are saved with their transactions, which are not known for the former events
+		assert_eq!(database, database.to_string().parse::<Database>().unwrap());
+	}
+
+	#[test]
+	fn database_to_and_from_json() {
+		let database: Database = r#"home_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7"
+foreign_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
+home_deploy = 100
+foreign_deploy = 101
+checked_deposit_relay = 120
+checked_withdraw_relay = 121
+checked_withdraw_confirm = 121
+"#.parse().unwrap();
+
+		assert_eq!(database, Database::from_json(&database.to_json()).unwrap());
+		assert!(Database::from_json("{}").is_err());
+		assert!(database.verify().is_ok());
+	}
+
+	#[test]
+	fn verify_database() {
+		let database = Database {
+			home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			home_deploy: 100,
+			foreign_deploy: 101,
+			checked_deposit_relay: 100,
+			checked_withdraw_relay: 101,
+			checked_withdraw_confirm: 101,
+			..Database::default()
+		};
+		assert!(database.verify().is_ok());
+		assert!(Database::default().verify().is_err());
+		// deposits are relayed from home, which contract has been deployed before the checked block
+		assert!(Database { home_deploy: 101, ..database.clone() }.verify().is_err());
+
+		let before_deploy = Database {
+			checked_withdraw_confirm: 99,
+			..database.clone()
+		};
+		assert!(before_deploy.verify().is_err());
+
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		let relayed_twice = Database {
+			relays: vec![relay, relay],
+			..database.clone()
+		};
+		assert!(relayed_twice.verify().is_err());
+
+		let journaled = Database {
+			relays: vec![relay],
+			journal: vec![JournalEntry {
+				chain: Chain::Testnet,
+				to: 3.into(),
+				data: vec![].into(),
+				gas: 0,
+				gas_price: 0,
+				event: relay.event,
+			}],
+			..database.clone()
+		};
+		assert!(journaled.verify().is_err());
+
+		let held = Database {
+			relays: vec![relay],
+			held_relays: vec![HeldRelay {
+				release: 0,
+				entry: journaled.journal[0].clone(),
+			}],
+			..database.clone()
+		};
+		assert!(held.verify().is_err());
+
+		let filtered = Database {
+			relays: vec![relay],
+			filtered_deposits: vec![FilteredDeposit {
+				recipient: 4.into(),
+				value: 0xf0.into(),
+				filter: DepositFilter::BelowMinimum,
+				event: relay.event,
+			}],
+			..database
+		};
+		assert!(filtered.verify().is_err());
+	}
+
+	#[test]
+	fn approve_deposit() {
+		let deposit = |filter| FilteredDeposit {
+			recipient: 4.into(),
+			value: 0xf0.into(),
+			filter,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		let entry = JournalEntry {
+			chain: Chain::Testnet,
+			to: 3.into(),
+			data: vec![1, 2, 3].into(),
+			gas: 0,
+			gas_price: 0,
+			event: deposit(DepositFilter::AboveMaximum).event,
+		};
+
+		let mut database = Database {
+			filtered_deposits: vec![deposit(DepositFilter::AboveMaximum)],
+			..Database::default()
+		};
+		database.approve_deposit(entry.clone()).unwrap();
+		assert!(database.filtered_deposits.is_empty());
+		assert_eq!(vec![entry.clone()], database.journal);
+		// deposit is approved only once
+		assert!(database.approve_deposit(entry.clone()).is_err());
+
+		// skipped deposits are not approved
+		let mut database = Database {
+			filtered_deposits: vec![deposit(DepositFilter::BelowMinimum)],
+			..Database::default()
+		};
+		assert!(database.approve_deposit(entry).is_err());
+	}
+
+	#[test]
+	fn change_checkpoint() {
+		let change = CheckpointChange {
+			stream: "withdraw_confirm".into(),
+			from: 120,
+			to: 100,
+			timestamp: 1_500_000_000,
+		};
+		let mut database = Database {
+			checked_withdraw_confirm: 120,
+			..Database::default()
+		};
+		assert_eq!(120, database.checked(BridgeChecked::WithdrawConfirm(0)));
+		database.change_checkpoint(&change);
+		assert_eq!(100, database.checked_withdraw_confirm);
+		assert_eq!(vec![change.clone()], database.checkpoint_changes);
+
+		// changes are kept in the database file
+		let database: Database = database.to_string().parse().unwrap();
+		assert_eq!(vec![change], database.checkpoint_changes);
+	}
+
+	#[test]
+	fn test_toml_file_backend() {
+		let tempdir = TempDir::new("test_toml_file_backend").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db");
+		let mut backend = TomlFileBackend::new(&path, None);
+		assert_eq!(None, backend.load().unwrap());
+		assert!(backend.update_checkpoints(&[BridgeChecked::DepositRelay(1)]).is_err());
+
+		let database = Database {
+			home_deploy: 100,
+			..Database::default()
+		};
+		backend.save(&database).unwrap();
+		// the database is written to a temporary file first, which replaces it
+		assert!(!temp_path(&path).exists());
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(1)]).unwrap();
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(2), BridgeChecked::WithdrawConfirm(3), BridgeChecked::WithdrawRelay(2)]).unwrap();
+		let relay = RelayOutcome {
+			chain: Chain::Mainnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0xa0,
+			timestamp: 1_500_000_000,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 2,
+			},
+		};
+		let entry = |log_index| JournalEntry {
+			chain: Chain::Mainnet,
+			to: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			data: vec![1, 2, 3].into(),
+			gas: 0xfd,
+			gas_price: 0xa0,
+			event: ProcessedEvent {
+				log_index,
+				..relay.event
+			},
+		};
+		backend.write_journal(&[entry(2), entry(3)]).unwrap();
+		backend.record_relays(&[relay]).unwrap();
+
+		let expected = Database {
+			home_deploy: 100,
+			checked_deposit_relay: 2,
+			checked_withdraw_relay: 2,
+			checked_withdraw_confirm: 3,
+			relays: vec![relay],
+			journal: vec![entry(3)],
+			..Database::default()
+		};
+		assert_eq!(expected, Database::load(&path).unwrap());
+		assert_eq!(Some(expected.clone()), TomlFileBackend::new(&path, None).load().unwrap());
+
+		backend.discard_journal(&[entry(3).event]).unwrap();
+		assert!(TomlFileBackend::new(&path, None).load().unwrap().unwrap().journal.is_empty());
+
+		let logs = vec![QuarantinedLog {
+			event: relay.event,
+			address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			topics: vec![1.into(), 2.into()],
+			data: vec![1, 2, 3].into(),
+			error: "Invalid data".into(),
+		}];
+		backend.quarantine_logs(&logs).unwrap();
+		// quarantining the log again replaces it
+		backend.quarantine_logs(&logs).unwrap();
+		assert_eq!(logs, TomlFileBackend::new(&path, None).load().unwrap().unwrap().quarantine);
+
+		let deposits = vec![FilteredDeposit {
+			recipient: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			value: 0xf0.into(),
+			filter: DepositFilter::AboveMaximum,
+			event: relay.event,
+		}];
+		backend.filter_deposits(&deposits).unwrap();
+		// filtering the deposit again replaces it
+		backend.filter_deposits(&deposits).unwrap();
+		assert_eq!(deposits, TomlFileBackend::new(&path, None).load().unwrap().unwrap().filtered_deposits);
+
+		let discrepancies = vec![Discrepancy {
+			chain: Chain::Testnet,
+			transaction_hash: 5.into(),
+			timestamp: 1_700_000_000,
+			event: relay.event,
+		}];
+		backend.record_discrepancies(&discrepancies).unwrap();
+		// recording the discrepancy again replaces it
+		backend.record_discrepancies(&discrepancies).unwrap();
+		assert_eq!(discrepancies, TomlFileBackend::new(&path, None).load().unwrap().unwrap().discrepancies);
+
+		let assignment = |failures| PendingAssignment {
+			message_hash: 0xf0.into(),
+			authority: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			block: Some(0x1000),
+			failures,
+			due: 1_700_000_000,
+			event: relay.event,
+		};
+		backend.write_assignments(&[assignment(0)]).unwrap();
+		// writing the assignment again replaces it
+		backend.write_assignments(&[assignment(1)]).unwrap();
+		assert_eq!(vec![assignment(1)], TomlFileBackend::new(&path, None).load().unwrap().unwrap().pending_assignments);
+		backend.discard_assignments(&[relay.event]).unwrap();
+		assert!(TomlFileBackend::new(&path, None).load().unwrap().unwrap().pending_assignments.is_empty());
+
+		let held = |release| HeldRelay {
+			release,
+			entry: entry(4),
+		};
+		backend.hold_relays(&[held(1_700_000_000)]).unwrap();
+		// holding the relay again replaces it
+		backend.hold_relays(&[held(1_700_003_600)]).unwrap();
+		assert_eq!(vec![held(1_700_003_600)], TomlFileBackend::new(&path, None).load().unwrap().unwrap().held_relays);
+		backend.release_relays(&[entry(4).event]).unwrap();
+		assert!(TomlFileBackend::new(&path, None).load().unwrap().unwrap().held_relays.is_empty());
+
+		let reverted = |reason: Option<&str>| RevertedRelay {
+			chain: Chain::Mainnet,
+			transaction_hash: relay.transaction_hash,
+			reason: reason.map(Into::into),
+			timestamp: 1_700_000_000,
+			event: relay.event,
+		};
+		backend.record_reverts(&[reverted(None)]).unwrap();
+		// recording the revert again replaces it
+		backend.record_reverts(&[reverted(Some("already relayed"))]).unwrap();
+		let database = TomlFileBackend::new(&path, None).load().unwrap().unwrap();
+		assert_eq!(vec![reverted(Some("already relayed"))], database.reverted_relays);
+		// the reverted relay is removed
+		assert!(database.relays.is_empty());
+
+		let fees = |stream: &str, chain, gas_used| RelayFees {
+			stream: stream.into(),
+			chain,
+			transactions: 1,
+			gas_used,
+			fees: (gas_used * 0xa0).into(),
+		};
+		backend.record_fees(&[fees("deposit_relay", Chain::Testnet, 50_000), fees("withdraw_relay", Chain::Mainnet, 40_000)]).unwrap();
+		// fees of the same component and chain are added up
+		backend.record_fees(&[fees("deposit_relay", Chain::Testnet, 30_000)]).unwrap();
+		assert_eq!(vec![RelayFees {
+			transactions: 2,
+			..fees("deposit_relay", Chain::Testnet, 80_000)
+		}, fees("withdraw_relay", Chain::Mainnet, 40_000)], TomlFileBackend::new(&path, None).load().unwrap().unwrap().relay_fees);
+
+		let sent = |to: u64, transaction_hash: u64, timestamp| SentPayload {
+			chain: Chain::Testnet,
+			to: to.into(),
+			data_hash: 0xd0.into(),
+			transaction_hash: transaction_hash.into(),
+			timestamp,
+		};
+		backend.record_payloads(&[sent(1, 1, 1_700_000_000), sent(2, 2, 1_700_000_000)], 0).unwrap();
+		// the same payload sent again replaces the previous one, and the expired ones are removed
+		backend.record_payloads(&[sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], 1_700_000_050).unwrap();
+		assert_eq!(vec![sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], TomlFileBackend::new(&path, None).load().unwrap().unwrap().sent_payloads);
+	}
+
+	#[test]
+	fn test_memory_backend() {
+		let mut backend = MemoryBackend::default();
+		assert_eq!(None, backend.load().unwrap());
+		assert!(backend.update_checkpoints(&[BridgeChecked::DepositRelay(1)]).is_err());
+
+		let database = Database {
+			home_deploy: 100,
+			..Database::default()
+		};
+		let mut backend = MemoryBackend::new(Some(database.clone()));
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(2), BridgeChecked::WithdrawRelay(3)]).unwrap();
+		assert_eq!(Some(Database {
+			checked_deposit_relay: 2,
+			checked_withdraw_relay: 3,
+			..database
+		}), backend.load().unwrap());
+	}
+
+	#[test]
+	fn database_with_relays_to_and_from_str() {
+		let toml =
+r#"home_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7"
+foreign_contract_address = "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
+home_deploy = 100
+foreign_deploy = 101
+checked_deposit_relay = 120
+checked_withdraw_relay = 121
+checked_withdraw_confirm = 121
+checked_mainnet_message_relay = 120
+checked_testnet_message_relay = 121
+
+[[relays]]
+chain = "testnet"
+transaction_hash = "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"
+gas_price = 160
+timestamp = 1500000000
+
+[relays.event]
+chain = "mainnet"
+transaction_hash = "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+log_index = 0
+"#;
+
+		let database: Database = toml.parse().unwrap();
+		assert_eq!(vec![RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 160,
+			timestamp: 1_500_000_000,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}], database.relays);
+		assert_eq!(database.to_string(), toml);
+	}
+
+	#[test]
+	fn test_toml_file_backend_namespaces() {
+		let tempdir = TempDir::new("test_toml_file_backend_namespaces").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db");
+		let mut first = TomlFileBackend::new(&path, Some("first"));
+		let mut second = TomlFileBackend::new(&path, Some("second"));
+		assert_eq!(None, first.load().unwrap());
+
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		let database = Database {
+			home_deploy: 100,
+			relays: vec![relay],
+			..Database::default()
+		};
+		first.save(&database).unwrap();
+		second.save(&Database::default()).unwrap();
+		second.update_checkpoints(&[BridgeChecked::DepositRelay(120)]).unwrap();
+
+		assert_eq!(Some(database), TomlFileBackend::new(&path, Some("first")).load().unwrap());
+		assert_eq!(120, TomlFileBackend::new(&path, Some("second")).load().unwrap().unwrap().checked_deposit_relay);
+	}
+
+	#[test]
+	fn test_toml_file_backend_namespaces_write_concurrently() {
+		let tempdir = TempDir::new("test_toml_file_backend_namespaces_write_concurrently").unwrap();
+		let path = tempdir.path().join("db");
+		let writers = ["first", "second"].iter()
+			.map(|namespace| {
+				let mut backend = TomlFileBackend::new(&path, Some(namespace));
+				backend.save(&Database::default()).unwrap();
+				thread::spawn(move || for block in 1..51 {
+					backend.update_checkpoints(&[BridgeChecked::DepositRelay(block)]).unwrap();
+				})
+			})
+			.collect::<Vec<_>>();
+		for writer in writers {
+			writer.join().unwrap();
+		}
+
+		// every write has merged the latest database of the other bridge
+		assert_eq!(50, TomlFileBackend::new(&path, Some("first")).load().unwrap().unwrap().checked_deposit_relay);
+		assert_eq!(50, TomlFileBackend::new(&path, Some("second")).load().unwrap().unwrap().checked_deposit_relay);
+	}
+
+	#[test]
+	fn test_toml_file_backend_lock() {
+		let tempdir = TempDir::new("test_toml_file_backend_lock").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db");
+		let mut first = TomlFileBackend::new(&path, None);
+		first.lock().unwrap();
+		first.lock().unwrap();
+		assert!(TomlFileBackend::new(&path, None).lock().is_err());
+		// bridges in other namespaces are not locked out, they write the file one at a time
+		TomlFileBackend::new(&path, Some("other")).lock().unwrap();
+
+		drop(first);
+		TomlFileBackend::new(&path, None).lock().unwrap();
+	}
+
+	#[test]
+	fn test_encrypted_toml_file_backend() {
+		let tempdir = TempDir::new("test_encrypted_toml_file_backend").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db");
+		let key = DatabaseKey([7u8; 32]);
+		let mut backend = TomlFileBackend::new(&path, None).with_key(Some(key.clone()));
+
+		let database = Database {
+			home_deploy: 100,
+			..Database::default()
+		};
+		backend.save(&database).unwrap();
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(120)]).unwrap();
+
+		let expected = Database {
+			checked_deposit_relay: 120,
+			..database
+		};
+		assert_eq!(Some(expected), TomlFileBackend::new(&path, None).with_key(Some(key)).load().unwrap());
+		assert!(TomlFileBackend::new(&path, None).load().is_err());
+		assert!(TomlFileBackend::new(&path, None).with_key(Some(DatabaseKey([8u8; 32]))).load().is_err());
+	}
+
+	#[test]
+	fn processed_event_from_log() {
+		let log = Log {
+			transaction_hash: Some(1.into()),
+			log_index: Some(2.into()),
+			..Default::default()
+		};
+		let expected = ProcessedEvent {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			log_index: 2,
+		};
+		assert_eq!(expected, ProcessedEvent::from_log(Chain::Testnet, &log).unwrap());
+		// pending logs can't be told apart from the other events of their transactions
+		assert!(ProcessedEvent::from_log(Chain::Testnet, &Log { log_index: None, ..log.clone() }).is_err());
+		assert!(ProcessedEvent::from_log(Chain::Testnet, &Log { transaction_hash: None, ..log }).is_err());
+	}
+
+	#[test]
+	fn prune_database() {
+		let relay = |timestamp| RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp,
+			block: None,
+			block: Some(timestamp / 100),
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: timestamp,
+			},
+		};
+		let unknown_block = RelayOutcome {
+			block: None,
+			..relay(500)
+		};
+		let mut database = Database {
+			relays: vec![relay(100), relay(200), relay(300), relay(400), unknown_block],
+			..Database::default()
+		};
+
+		assert_eq!(0, database.prune(&RetentionPolicy::default(), 1000, &[(Chain::Mainnet, 10)]));
+		let prune_checked = RetentionPolicy {
+			max_age: None,
+			max_relays: None,
+			prune_checked: true,
+		};
+		// only the relays of the events in the blocks checked on their chain are pruned
+		assert_eq!(0, database.prune(&prune_checked, 1000, &[(Chain::Testnet, 10)]));
+		assert_eq!(1, database.prune(&prune_checked, 1000, &[(Chain::Mainnet, 1)]));
+		assert_eq!(vec![relay(200), relay(300), relay(400), unknown_block], database.relays);
+		let max_age = RetentionPolicy {
+			max_age: Some(Duration::from_secs(750)),
+			max_relays: None,
+			prune_checked: false,
+		};
+		assert_eq!(1, database.prune(&max_age, 1000, &[]));
+		assert_eq!(vec![relay(300), relay(400), unknown_block], database.relays);
+		let max_relays = RetentionPolicy {
+			max_age: None,
+			max_relays: Some(2),
+			prune_checked: false,
+		};
+		assert_eq!(1, database.prune(&max_relays, 1000, &[]));
+		assert_eq!(vec![relay(400), unknown_block], database.relays);
+		// relays, which blocks are not known, are never pruned as checked
+		assert_eq!(1, database.prune(&prune_checked, 1000, &[(Chain::Mainnet, 10)]));
+		assert_eq!(vec![unknown_block], database.relays);
+	}
+}
@@ -0,0 +1,1228 @@
+//! Database stored in Postgres, shared by an active and a standby bridge.
+//!
+//! Only the bridge holding the advisory lock of the namespace is active. The lock belongs
+//! to the connection, so the server releases it as soon as the active bridge dies and the
+//! standby, which waits for it in `lock`, takes over from the last written checkpoints.
+//! Every update is a single transaction locking the deployment row of the namespace,
+//! so that other writers, e.g. `bridge db` commands, never interleave with the bridge.
+
+use postgres::{Client, NoTls, Row, Transaction};
+use web3::types::{Address, H256, U256};
+use bridge::BridgeChecked;
+use config::RetentionPolicy;
+use error::{Error, ResultExt};
+use super::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain};
+
+const SCHEMA: &str = r#"
+-- columns of the deployment keep their former names: mainnet_* hold the home contract and testnet_* the foreign one
+CREATE TABLE IF NOT EXISTS deployment (
+	namespace TEXT PRIMARY KEY,
+	mainnet_contract_address BYTEA NOT NULL,
+	testnet_contract_address BYTEA NOT NULL,
+	mainnet_deploy BIGINT NOT NULL,
+	testnet_deploy BIGINT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checkpoints (
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	block BIGINT NOT NULL,
+	PRIMARY KEY (namespace, stream)
+);
+CREATE TABLE IF NOT EXISTS relays (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BYTEA NOT NULL,
+	gas_price BIGINT NOT NULL,
+	timestamp BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+-- blocks of the events are recorded since the relays of the checked blocks can be pruned
+ALTER TABLE relays ADD COLUMN IF NOT EXISTS block BIGINT;
+CREATE INDEX IF NOT EXISTS relays_timestamp ON relays (namespace, timestamp);
+CREATE TABLE IF NOT EXISTS journal (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BYTEA NOT NULL,
+	data BYTEA NOT NULL,
+	gas BIGINT NOT NULL,
+	gas_price BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS quarantine (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	address BYTEA NOT NULL,
+	topics BYTEA NOT NULL,
+	data BYTEA NOT NULL,
+	error TEXT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS filtered_deposits (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	recipient BYTEA NOT NULL,
+	value BYTEA NOT NULL,
+	filter TEXT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS discrepancies (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BYTEA NOT NULL,
+	timestamp BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS pending_assignments (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	message_hash BYTEA NOT NULL,
+	authority BYTEA NOT NULL,
+	block BIGINT,
+	failures BIGINT NOT NULL,
+	due BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS held_relays (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BYTEA NOT NULL,
+	data BYTEA NOT NULL,
+	gas BIGINT NOT NULL,
+	gas_price BIGINT NOT NULL,
+	release BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS reverted_relays (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BYTEA NOT NULL,
+	event_log_index BIGINT NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BYTEA NOT NULL,
+	reason TEXT,
+	timestamp BIGINT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS relay_fees (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	chain TEXT NOT NULL,
+	transactions BIGINT NOT NULL,
+	gas_used BIGINT NOT NULL,
+	fees BYTEA NOT NULL,
+	PRIMARY KEY (namespace, stream, chain)
+);
+CREATE TABLE IF NOT EXISTS sent_payloads (
+	id BIGSERIAL,
+	namespace TEXT NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BYTEA NOT NULL,
+	data_hash BYTEA NOT NULL,
+	transaction_hash BYTEA NOT NULL,
+	timestamp BIGINT NOT NULL,
+	PRIMARY KEY (namespace, chain, recipient, data_hash)
+);
+CREATE TABLE IF NOT EXISTS checkpoint_changes (
+	id BIGSERIAL PRIMARY KEY,
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	from_block BIGINT NOT NULL,
+	to_block BIGINT NOT NULL,
+	timestamp BIGINT NOT NULL
+);
+"#;
+
+/// First key of the advisory locks taken by the bridge, the second one is the hash of the namespace.
+const LOCK_CLASS: i32 = 0x6272_6467;
+
+fn address(bytes: Vec<u8>) -> Result<Address, Error> {
+	if bytes.len() != 20 {
+		return Err("Invalid address stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+fn hash(bytes: Vec<u8>) -> Result<H256, Error> {
+	if bytes.len() != 32 {
+		return Err("Invalid transaction hash stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+/// Topics are stored concatenated.
+fn topics(bytes: Vec<u8>) -> Result<Vec<H256>, Error> {
+	if bytes.chunks(32).any(|chunk| chunk.len() != 32) {
+		return Err("Invalid log topics stored in database".into());
+	}
+	Ok(bytes.chunks(32).map(Into::into).collect())
+}
+
+fn value(bytes: Vec<u8>) -> Result<U256, Error> {
+	if bytes.len() != 32 {
+		return Err("Invalid value stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+fn deposit_filter(name: &str) -> Result<DepositFilter, Error> {
+	match name {
+		"below_minimum" => Ok(DepositFilter::BelowMinimum),
+		"above_maximum" => Ok(DepositFilter::AboveMaximum),
+		_ => Err(format!("Invalid deposit filter `{}` stored in database", name).into()),
+	}
+}
+
+fn chain(name: &str) -> Result<Chain, Error> {
+	match name {
+		"mainnet" => Ok(Chain::Mainnet),
+		"testnet" => Ok(Chain::Testnet),
+		_ => Err(format!("Invalid chain `{}` stored in database", name).into()),
+	}
+}
+
+/// Waits until concurrent writers of the namespace commit.
+fn lock_namespace(transaction: &mut Transaction, namespace: &str) -> Result<(), Error> {
+	transaction.execute("SELECT namespace FROM deployment WHERE namespace = $1 FOR UPDATE", &[&namespace])?;
+	Ok(())
+}
+
+fn insert_checkpoints(transaction: &mut Transaction, namespace: &str, checks: &[BridgeChecked]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO checkpoints (namespace, stream, block) VALUES ($1, $2, $3) \
+		ON CONFLICT (namespace, stream) DO UPDATE SET block = excluded.block"
+	)?;
+	for check in checks {
+		transaction.execute(&statement, &[&namespace, &check.stream(), &(check.block() as i64)])?;
+	}
+	Ok(())
+}
+
+fn insert_relays(transaction: &mut Transaction, namespace: &str, relays: &[RelayOutcome]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, gas_price, timestamp, block) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT DO NOTHING"
+	)?;
+	for relay in relays {
+		transaction.execute(&statement, &[
+			&namespace,
+			&relay.event.chain.as_str(),
+			&&relay.event.transaction_hash.0[..],
+			&(relay.event.log_index as i64),
+			&relay.chain.as_str(),
+			&&relay.transaction_hash.0[..],
+			&(relay.gas_price as i64),
+			&(relay.timestamp as i64),
+			&relay.block.map(|block| block as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_journal(transaction: &mut Transaction, namespace: &str, entries: &[JournalEntry]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO journal (namespace, event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		chain = excluded.chain, recipient = excluded.recipient, data = excluded.data, gas = excluded.gas, gas_price = excluded.gas_price"
+	)?;
+	for entry in entries {
+		transaction.execute(&statement, &[
+			&namespace,
+			&entry.event.chain.as_str(),
+			&&entry.event.transaction_hash.0[..],
+			&(entry.event.log_index as i64),
+			&entry.chain.as_str(),
+			&&entry.to.0[..],
+			&entry.data.0,
+			&(entry.gas as i64),
+			&(entry.gas_price as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_quarantine(transaction: &mut Transaction, namespace: &str, logs: &[QuarantinedLog]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO quarantine (namespace, event_chain, event_transaction_hash, event_log_index, address, topics, data, error) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		address = excluded.address, topics = excluded.topics, data = excluded.data, error = excluded.error"
+	)?;
+	for log in logs {
+		let topics = log.topics.iter().flat_map(|topic| topic.0.to_vec()).collect::<Vec<u8>>();
+		transaction.execute(&statement, &[
+			&namespace,
+			&log.event.chain.as_str(),
+			&&log.event.transaction_hash.0[..],
+			&(log.event.log_index as i64),
+			&&log.address.0[..],
+			&topics,
+			&log.data.0,
+			&log.error,
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_filtered_deposits(transaction: &mut Transaction, namespace: &str, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO filtered_deposits (namespace, event_chain, event_transaction_hash, event_log_index, recipient, value, filter) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		recipient = excluded.recipient, value = excluded.value, filter = excluded.filter"
+	)?;
+	for deposit in deposits {
+		transaction.execute(&statement, &[
+			&namespace,
+			&deposit.event.chain.as_str(),
+			&&deposit.event.transaction_hash.0[..],
+			&(deposit.event.log_index as i64),
+			&&deposit.recipient.0[..],
+			&&deposit.value.0[..],
+			&deposit.filter.as_str(),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_discrepancies(transaction: &mut Transaction, namespace: &str, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO discrepancies (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, timestamp) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		chain = excluded.chain, transaction_hash = excluded.transaction_hash, timestamp = excluded.timestamp"
+	)?;
+	for discrepancy in discrepancies {
+		transaction.execute(&statement, &[
+			&namespace,
+			&discrepancy.event.chain.as_str(),
+			&&discrepancy.event.transaction_hash.0[..],
+			&(discrepancy.event.log_index as i64),
+			&discrepancy.chain.as_str(),
+			&&discrepancy.transaction_hash.0[..],
+			&(discrepancy.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_reverted_relays(transaction: &mut Transaction, namespace: &str, reverts: &[RevertedRelay]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO reverted_relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, reason, timestamp) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		chain = excluded.chain, transaction_hash = excluded.transaction_hash, reason = excluded.reason, timestamp = excluded.timestamp"
+	)?;
+	for revert in reverts {
+		transaction.execute(&statement, &[
+			&namespace,
+			&revert.event.chain.as_str(),
+			&&revert.event.transaction_hash.0[..],
+			&(revert.event.log_index as i64),
+			&revert.chain.as_str(),
+			&&revert.transaction_hash.0[..],
+			&revert.reason,
+			&(revert.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_relay_fees(transaction: &mut Transaction, namespace: &str, fees: &[RelayFees]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO relay_fees (namespace, stream, chain, transactions, gas_used, fees) VALUES ($1, $2, $3, $4, $5, $6) \
+		ON CONFLICT (namespace, stream, chain) DO UPDATE SET \
+		transactions = excluded.transactions, gas_used = excluded.gas_used, fees = excluded.fees"
+	)?;
+	for fee in fees {
+		transaction.execute(&statement, &[
+			&namespace,
+			&fee.stream,
+			&fee.chain.as_str(),
+			&(fee.transactions as i64),
+			&(fee.gas_used as i64),
+			&&fee.fees.0[..],
+		])?;
+	}
+	Ok(())
+}
+
+/// Rows selected by `SELECT stream, chain, transactions, gas_used, fees FROM relay_fees`.
+fn relay_fees(rows: Vec<Row>) -> Result<Vec<RelayFees>, Error> {
+	let mut fees = Vec::new();
+	for row in rows {
+		fees.push(RelayFees {
+			stream: row.try_get(0)?,
+			chain: chain(row.try_get(1)?)?,
+			transactions: row.try_get::<_, i64>(2)? as u64,
+			gas_used: row.try_get::<_, i64>(3)? as u64,
+			fees: value(row.try_get(4)?)?,
+		});
+	}
+	Ok(fees)
+}
+
+/// Payloads sent again are moved to the end, as if they were removed and inserted.
+fn insert_sent_payloads(transaction: &mut Transaction, namespace: &str, payloads: &[SentPayload]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO sent_payloads (namespace, chain, recipient, data_hash, transaction_hash, timestamp) VALUES ($1, $2, $3, $4, $5, $6) \
+		ON CONFLICT (namespace, chain, recipient, data_hash) DO UPDATE SET \
+		id = excluded.id, transaction_hash = excluded.transaction_hash, timestamp = excluded.timestamp"
+	)?;
+	for payload in payloads {
+		transaction.execute(&statement, &[
+			&namespace,
+			&payload.chain.as_str(),
+			&&payload.to.0[..],
+			&&payload.data_hash.0[..],
+			&&payload.transaction_hash.0[..],
+			&(payload.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_checkpoint_changes(transaction: &mut Transaction, namespace: &str, changes: &[CheckpointChange]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO checkpoint_changes (namespace, stream, from_block, to_block, timestamp) VALUES ($1, $2, $3, $4, $5)"
+	)?;
+	for change in changes {
+		transaction.execute(&statement, &[
+			&namespace,
+			&change.stream,
+			&(change.from as i64),
+			&(change.to as i64),
+			&(change.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_assignments(transaction: &mut Transaction, namespace: &str, assignments: &[PendingAssignment]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO pending_assignments (namespace, event_chain, event_transaction_hash, event_log_index, message_hash, authority, block, failures, due) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		message_hash = excluded.message_hash, authority = excluded.authority, block = excluded.block, failures = excluded.failures, due = excluded.due"
+	)?;
+	for assignment in assignments {
+		transaction.execute(&statement, &[
+			&namespace,
+			&assignment.event.chain.as_str(),
+			&&assignment.event.transaction_hash.0[..],
+			&(assignment.event.log_index as i64),
+			&&assignment.message_hash.0[..],
+			&&assignment.authority.0[..],
+			&assignment.block.map(|block| block as i64),
+			&i64::from(assignment.failures),
+			&(assignment.due as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_held_relays(transaction: &mut Transaction, namespace: &str, relays: &[HeldRelay]) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"INSERT INTO held_relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price, release) \
+		VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+		ON CONFLICT (namespace, event_chain, event_transaction_hash, event_log_index) DO UPDATE SET \
+		chain = excluded.chain, recipient = excluded.recipient, data = excluded.data, gas = excluded.gas, gas_price = excluded.gas_price, release = excluded.release"
+	)?;
+	for relay in relays {
+		let entry = &relay.entry;
+		transaction.execute(&statement, &[
+			&namespace,
+			&entry.event.chain.as_str(),
+			&&entry.event.transaction_hash.0[..],
+			&(entry.event.log_index as i64),
+			&entry.chain.as_str(),
+			&&entry.to.0[..],
+			&entry.data.0,
+			&(entry.gas as i64),
+			&(entry.gas_price as i64),
+			&(relay.release as i64),
+		])?;
+	}
+	Ok(())
+}
+
+/// Removes relays of the events, e.g. because their transactions have been reverted.
+fn remove_relays<'a, I: IntoIterator<Item = &'a ProcessedEvent>>(transaction: &mut Transaction, namespace: &str, events: I) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"DELETE FROM relays WHERE namespace = $1 AND event_chain = $2 AND event_transaction_hash = $3 AND event_log_index = $4"
+	)?;
+	for event in events {
+		transaction.execute(&statement, &[
+			&namespace,
+			&event.chain.as_str(),
+			&&event.transaction_hash.0[..],
+			&(event.log_index as i64),
+		])?;
+	}
+	Ok(())
+}
+
+/// Removes journal entries of the events.
+fn complete_journal<'a, I: IntoIterator<Item = &'a ProcessedEvent>>(transaction: &mut Transaction, namespace: &str, events: I) -> Result<(), Error> {
+	let statement = transaction.prepare(
+		"DELETE FROM journal WHERE namespace = $1 AND event_chain = $2 AND event_transaction_hash = $3 AND event_log_index = $4"
+	)?;
+	for event in events {
+		transaction.execute(&statement, &[
+			&namespace,
+			&event.chain.as_str(),
+			&&event.transaction_hash.0[..],
+			&(event.log_index as i64),
+		])?;
+	}
+	Ok(())
+}
+
+pub struct PostgresBackend {
+	client: Client,
+	/// Rows of the bridges stored without a namespace belong to the empty one.
+	namespace: String,
+}
+
+impl PostgresBackend {
+	/// Connects to the database described by connection string `params`,
+	/// e.g. `postgresql://bridge@localhost/bridge`, and creates the tables if needed.
+	pub fn connect(params: &str, namespace: Option<&str>) -> Result<Self, Error> {
+		let mut client = Client::connect(params, NoTls).chain_err(|| "Cannot connect to database")?;
+		client.batch_execute(SCHEMA).chain_err(|| "Cannot create database tables")?;
+		Ok(PostgresBackend {
+			client,
+			namespace: namespace.unwrap_or_default().to_owned(),
+		})
+	}
+}
+
+impl DatabaseBackend for PostgresBackend {
+	fn lock(&mut self) -> Result<(), Error> {
+		let locked: bool = self.client.query_one(
+			"SELECT pg_try_advisory_lock($1, hashtext($2))",
+			&[&LOCK_CLASS, &self.namespace],
+		)?.get(0);
+
+		if !locked {
+			info!(target: "bridge", "Database is locked by another bridge, waiting until it stops");
+			self.client.execute("SELECT pg_advisory_lock($1, hashtext($2))", &[&LOCK_CLASS, &self.namespace])?;
+			info!(target: "bridge", "Database has been unlocked, taking over");
+		}
+		Ok(())
+	}
+
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		let deployment = self.client.query_opt(
+			"SELECT mainnet_contract_address, testnet_contract_address, mainnet_deploy, testnet_deploy FROM deployment WHERE namespace = $1",
+			&[&self.namespace],
+		)?;
+
+		let deployment = match deployment {
+			Some(deployment) => deployment,
+			None => return Ok(None),
+		};
+
+		let mut database = Database {
+			home_contract_address: address(deployment.try_get(0)?)?,
+			foreign_contract_address: address(deployment.try_get(1)?)?,
+			home_deploy: deployment.try_get::<_, i64>(2)? as u64,
+			foreign_deploy: deployment.try_get::<_, i64>(3)? as u64,
+			..Database::default()
+		};
+
+		for row in self.client.query("SELECT stream, block FROM checkpoints WHERE namespace = $1", &[&self.namespace])? {
+			let stream: String = row.try_get(0)?;
+			let check = BridgeChecked::from_stream(&stream, row.try_get::<_, i64>(1)? as u64)
+				.ok_or_else(|| Error::from(format!("Invalid checkpoint `{}` stored in database", stream)))?;
+			database.update_checkpoint(check);
+		}
+
+		let relays = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, gas_price, timestamp, block FROM relays WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in relays {
+			database.relays.push(RelayOutcome {
+				chain: chain(row.try_get(3)?)?,
+				transaction_hash: hash(row.try_get(4)?)?,
+				gas_price: row.try_get::<_, i64>(5)? as u64,
+				timestamp: row.try_get::<_, i64>(6)? as u64,
+				block: row.try_get::<_, Option<i64>>(7)?.map(|block| block as u64),
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let entries = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price FROM journal WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in entries {
+			database.journal.push(JournalEntry {
+				chain: chain(row.try_get(3)?)?,
+				to: address(row.try_get(4)?)?,
+				data: row.try_get::<_, Vec<u8>>(5)?.into(),
+				gas: row.try_get::<_, i64>(6)? as u64,
+				gas_price: row.try_get::<_, i64>(7)? as u64,
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let logs = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, address, topics, data, error FROM quarantine WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in logs {
+			database.quarantine.push(QuarantinedLog {
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+				address: address(row.try_get(3)?)?,
+				topics: topics(row.try_get(4)?)?,
+				data: row.try_get::<_, Vec<u8>>(5)?.into(),
+				error: row.try_get(6)?,
+			});
+		}
+
+		let deposits = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, recipient, value, filter FROM filtered_deposits WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in deposits {
+			database.filtered_deposits.push(FilteredDeposit {
+				recipient: address(row.try_get(3)?)?,
+				value: value(row.try_get(4)?)?,
+				filter: deposit_filter(row.try_get(5)?)?,
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let discrepancies = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, timestamp FROM discrepancies WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in discrepancies {
+			database.discrepancies.push(Discrepancy {
+				chain: chain(row.try_get(3)?)?,
+				transaction_hash: hash(row.try_get(4)?)?,
+				timestamp: row.try_get::<_, i64>(5)? as u64,
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let assignments = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, message_hash, authority, block, failures, due FROM pending_assignments WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in assignments {
+			database.pending_assignments.push(PendingAssignment {
+				message_hash: hash(row.try_get(3)?)?,
+				authority: address(row.try_get(4)?)?,
+				block: row.try_get::<_, Option<i64>>(5)?.map(|block| block as u64),
+				failures: row.try_get::<_, i64>(6)? as u32,
+				due: row.try_get::<_, i64>(7)? as u64,
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let relays = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price, release FROM held_relays WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in relays {
+			database.held_relays.push(HeldRelay {
+				release: row.try_get::<_, i64>(8)? as u64,
+				entry: JournalEntry {
+					chain: chain(row.try_get(3)?)?,
+					to: address(row.try_get(4)?)?,
+					data: row.try_get::<_, Vec<u8>>(5)?.into(),
+					gas: row.try_get::<_, i64>(6)? as u64,
+					gas_price: row.try_get::<_, i64>(7)? as u64,
+					event: ProcessedEvent {
+						chain: chain(row.try_get(0)?)?,
+						transaction_hash: hash(row.try_get(1)?)?,
+						log_index: row.try_get::<_, i64>(2)? as u64,
+					},
+				},
+			});
+		}
+
+		let reverts = self.client.query(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, reason, timestamp FROM reverted_relays WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in reverts {
+			database.reverted_relays.push(RevertedRelay {
+				chain: chain(row.try_get(3)?)?,
+				transaction_hash: hash(row.try_get(4)?)?,
+				reason: row.try_get(5)?,
+				timestamp: row.try_get::<_, i64>(6)? as u64,
+				event: ProcessedEvent {
+					chain: chain(row.try_get(0)?)?,
+					transaction_hash: hash(row.try_get(1)?)?,
+					log_index: row.try_get::<_, i64>(2)? as u64,
+				},
+			});
+		}
+
+		let fees = self.client.query(
+			"SELECT stream, chain, transactions, gas_used, fees FROM relay_fees WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		database.relay_fees = relay_fees(fees)?;
+
+		let payloads = self.client.query(
+			"SELECT chain, recipient, data_hash, transaction_hash, timestamp FROM sent_payloads WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in payloads {
+			database.sent_payloads.push(SentPayload {
+				chain: chain(row.try_get(0)?)?,
+				to: address(row.try_get(1)?)?,
+				data_hash: hash(row.try_get(2)?)?,
+				transaction_hash: hash(row.try_get(3)?)?,
+				timestamp: row.try_get::<_, i64>(4)? as u64,
+			});
+		}
+
+		let changes = self.client.query(
+			"SELECT stream, from_block, to_block, timestamp FROM checkpoint_changes WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		for row in changes {
+			database.checkpoint_changes.push(CheckpointChange {
+				stream: row.try_get(0)?,
+				from: row.try_get::<_, i64>(1)? as u64,
+				to: row.try_get::<_, i64>(2)? as u64,
+				timestamp: row.try_get::<_, i64>(3)? as u64,
+			});
+		}
+
+		Ok(Some(database))
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		for table in &["deployment", "checkpoints", "relays", "journal", "quarantine", "filtered_deposits", "discrepancies", "pending_assignments", "held_relays", "reverted_relays", "relay_fees", "sent_payloads", "checkpoint_changes"] {
+			transaction.execute(&*format!("DELETE FROM {} WHERE namespace = $1", table), &[&self.namespace])?;
+		}
+		transaction.execute(
+			"INSERT INTO deployment (namespace, mainnet_contract_address, testnet_contract_address, mainnet_deploy, testnet_deploy) VALUES ($1, $2, $3, $4, $5)",
+			&[
+				&self.namespace,
+				&&database.home_contract_address.0[..],
+				&&database.foreign_contract_address.0[..],
+				&(database.home_deploy as i64),
+				&(database.foreign_deploy as i64),
+			],
+		)?;
+		insert_checkpoints(&mut transaction, &self.namespace, &[
+			BridgeChecked::DepositRelay(database.checked_deposit_relay),
+			BridgeChecked::WithdrawRelay(database.checked_withdraw_relay),
+			BridgeChecked::WithdrawConfirm(database.checked_withdraw_confirm),
+			BridgeChecked::MainnetMessageRelay(database.checked_mainnet_message_relay),
+			BridgeChecked::TestnetMessageRelay(database.checked_testnet_message_relay),
+		])?;
+		insert_relays(&mut transaction, &self.namespace, &database.relays)?;
+		insert_journal(&mut transaction, &self.namespace, &database.journal)?;
+		insert_quarantine(&mut transaction, &self.namespace, &database.quarantine)?;
+		insert_filtered_deposits(&mut transaction, &self.namespace, &database.filtered_deposits)?;
+		insert_discrepancies(&mut transaction, &self.namespace, &database.discrepancies)?;
+		insert_assignments(&mut transaction, &self.namespace, &database.pending_assignments)?;
+		insert_held_relays(&mut transaction, &self.namespace, &database.held_relays)?;
+		insert_reverted_relays(&mut transaction, &self.namespace, &database.reverted_relays)?;
+		insert_relay_fees(&mut transaction, &self.namespace, &database.relay_fees)?;
+		insert_sent_payloads(&mut transaction, &self.namespace, &database.sent_payloads)?;
+		insert_checkpoint_changes(&mut transaction, &self.namespace, &database.checkpoint_changes)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_checkpoints(&mut transaction, &self.namespace, checks)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		let check = change.checkpoint().ok_or_else(|| Error::from(format!("Invalid stream `{}`", change.stream)))?;
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_checkpoints(&mut transaction, &self.namespace, &[check])?;
+		insert_checkpoint_changes(&mut transaction, &self.namespace, &[change.clone()])?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_relays(&mut transaction, &self.namespace, relays)?;
+		complete_journal(&mut transaction, &self.namespace, relays.iter().map(|relay| &relay.event))?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_journal(&mut transaction, &self.namespace, entries)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		complete_journal(&mut transaction, &self.namespace, events)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_quarantine(&mut transaction, &self.namespace, logs)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_filtered_deposits(&mut transaction, &self.namespace, deposits)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_discrepancies(&mut transaction, &self.namespace, discrepancies)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		remove_relays(&mut transaction, &self.namespace, reverts.iter().map(|revert| &revert.event))?;
+		insert_reverted_relays(&mut transaction, &self.namespace, reverts)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		// fees are stored as 256-bit values, so they are added up outside of the database
+		let rows = transaction.query(
+			"SELECT stream, chain, transactions, gas_used, fees FROM relay_fees WHERE namespace = $1 ORDER BY id",
+			&[&self.namespace],
+		)?;
+		let mut totals = relay_fees(rows)?;
+		for fee in fees {
+			match totals.iter_mut().find(|total| total.same_account(fee)) {
+				Some(total) => total.add(fee),
+				None => totals.push(fee.clone()),
+			}
+		}
+		insert_relay_fees(&mut transaction, &self.namespace, &totals)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		transaction.execute(
+			"DELETE FROM sent_payloads WHERE namespace = $1 AND timestamp < $2",
+			&[&self.namespace, &(expired as i64)],
+		)?;
+		insert_sent_payloads(&mut transaction, &self.namespace, payloads)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_assignments(&mut transaction, &self.namespace, assignments)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		let statement = transaction.prepare(
+			"DELETE FROM pending_assignments WHERE namespace = $1 AND event_chain = $2 AND event_transaction_hash = $3 AND event_log_index = $4"
+		)?;
+		for event in events {
+			transaction.execute(&statement, &[
+				&self.namespace,
+				&event.chain.as_str(),
+				&&event.transaction_hash.0[..],
+				&(event.log_index as i64),
+			])?;
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		insert_held_relays(&mut transaction, &self.namespace, relays)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		let statement = transaction.prepare(
+			"DELETE FROM held_relays WHERE namespace = $1 AND event_chain = $2 AND event_transaction_hash = $3 AND event_log_index = $4"
+		)?;
+		for event in events {
+			transaction.execute(&statement, &[
+				&self.namespace,
+				&event.chain.as_str(),
+				&&event.transaction_hash.0[..],
+				&(event.log_index as i64),
+			])?;
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		let mut transaction = self.client.transaction()?;
+		lock_namespace(&mut transaction, &self.namespace)?;
+		let mut pruned = 0;
+		if policy.prune_checked {
+			for &(chain, block) in checked {
+				pruned += transaction.execute(
+					"DELETE FROM relays WHERE namespace = $1 AND event_chain = $2 AND block <= $3",
+					&[&self.namespace, &chain.as_str(), &(block as i64)],
+				)?;
+			}
+		}
+		if let Some(max_age) = policy.max_age {
+			pruned += transaction.execute(
+				"DELETE FROM relays WHERE namespace = $1 AND timestamp < $2",
+				&[&self.namespace, &(now.saturating_sub(max_age.as_secs()) as i64)],
+			)?;
+		}
+		if let Some(max_relays) = policy.max_relays {
+			pruned += transaction.execute(
+				"DELETE FROM relays WHERE namespace = $1 AND id NOT IN \
+				(SELECT id FROM relays WHERE namespace = $1 ORDER BY id DESC LIMIT $2)",
+				&[&self.namespace, &(max_relays as i64)],
+			)?;
+		}
+		transaction.commit()?;
+		Ok(pruned as usize)
+	}
+
+	fn compact(&mut self) -> Result<(), Error> {
+		self.client.batch_execute("VACUUM relays, journal;").chain_err(|| "Cannot compact database")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+	use bridge::BridgeChecked;
+	use config::RetentionPolicy;
+	use database::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain};
+	use super::PostgresBackend;
+
+	/// Connects to the server given by `BRIDGE_TEST_POSTGRES` env variable.
+	/// Tests are skipped if it's not set.
+	fn connect(namespace: &str) -> Option<PostgresBackend> {
+		let params = env::var("BRIDGE_TEST_POSTGRES").ok()?;
+		let mut backend = PostgresBackend::connect(&params, Some(namespace)).unwrap();
+		for table in &["deployment", "checkpoints", "relays", "journal", "quarantine", "filtered_deposits", "discrepancies", "pending_assignments", "held_relays", "reverted_relays", "relay_fees", "sent_payloads", "checkpoint_changes"] {
+			backend.client.execute(&*format!("DELETE FROM {} WHERE namespace = $1", table), &[&namespace]).unwrap();
+		}
+		Some(backend)
+	}
+
+	#[test]
+	fn test_postgres_backend() {
+		let mut backend = match connect("test_postgres_backend") {
+			Some(backend) => backend,
+			None => return,
+		};
+		assert_eq!(None, backend.load().unwrap());
+
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0xa0,
+			timestamp: 1_500_000_000,
+			block: Some(0x10),
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 1,
+			},
+		};
+		let database = Database {
+			home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			home_deploy: 100,
+			foreign_deploy: 101,
+			checked_deposit_relay: 100,
+			checked_withdraw_relay: 101,
+			checked_withdraw_confirm: 101,
+			checked_mainnet_message_relay: 100,
+			checked_testnet_message_relay: 101,
+			relays: vec![relay],
+			journal: vec![],
+			quarantine: vec![],
+			filtered_deposits: vec![],
+			discrepancies: vec![],
+			pending_assignments: vec![],
+			held_relays: vec![],
+			reverted_relays: vec![],
+			relay_fees: vec![],
+			sent_payloads: vec![],
+			checkpoint_changes: vec![],
+		};
+		backend.save(&database).unwrap();
+		assert_eq!(Some(database.clone()), backend.load().unwrap());
+
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(120), BridgeChecked::WithdrawConfirm(121), BridgeChecked::TestnetMessageRelay(122)]).unwrap();
+		let other_relay = RelayOutcome {
+			chain: Chain::Mainnet,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				..relay.event
+			},
+			..relay
+		};
+		let entry = |event| JournalEntry {
+			chain: Chain::Mainnet,
+			to: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			data: vec![1, 2, 3].into(),
+			gas: 0xfd,
+			gas_price: 0xa0,
+			event,
+		};
+		let pending_event = ProcessedEvent {
+			log_index: 2,
+			..other_relay.event
+		};
+		backend.write_journal(&[entry(other_relay.event), entry(pending_event)]).unwrap();
+		// events are relayed only once
+		backend.record_relays(&[relay, other_relay]).unwrap();
+
+		let expected = Database {
+			checked_deposit_relay: 120,
+			checked_withdraw_confirm: 121,
+			checked_testnet_message_relay: 122,
+			relays: vec![relay, other_relay],
+			journal: vec![entry(pending_event)],
+			..database
+		};
+		assert_eq!(Some(expected), backend.load().unwrap());
+
+		backend.discard_journal(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().journal.is_empty());
+
+		let logs = vec![QuarantinedLog {
+			event: pending_event,
+			address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			topics: vec![1.into(), 2.into()],
+			data: vec![1, 2, 3].into(),
+			error: "Invalid data".into(),
+		}];
+		backend.quarantine_logs(&logs).unwrap();
+		// quarantining the log again replaces it
+		backend.quarantine_logs(&logs).unwrap();
+		assert_eq!(logs, backend.load().unwrap().unwrap().quarantine);
+
+		let deposits = vec![FilteredDeposit {
+			recipient: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			value: 0xf0.into(),
+			filter: DepositFilter::BelowMinimum,
+			event: pending_event,
+		}];
+		backend.filter_deposits(&deposits).unwrap();
+		// filtering the deposit again replaces it
+		backend.filter_deposits(&deposits).unwrap();
+		assert_eq!(deposits, backend.load().unwrap().unwrap().filtered_deposits);
+
+		let discrepancies = vec![Discrepancy {
+			chain: Chain::Testnet,
+			transaction_hash: 5.into(),
+			timestamp: 1_700_000_000,
+			event: pending_event,
+		}];
+		backend.record_discrepancies(&discrepancies).unwrap();
+		// recording the discrepancy again replaces it
+		backend.record_discrepancies(&discrepancies).unwrap();
+		assert_eq!(discrepancies, backend.load().unwrap().unwrap().discrepancies);
+
+		let assignment = |block| PendingAssignment {
+			message_hash: 0xf0.into(),
+			authority: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			block,
+			failures: 2,
+			due: 1_700_000_000,
+			event: pending_event,
+		};
+		backend.write_assignments(&[assignment(None)]).unwrap();
+		// writing the assignment again replaces it
+		backend.write_assignments(&[assignment(Some(0x1000))]).unwrap();
+		assert_eq!(vec![assignment(Some(0x1000))], backend.load().unwrap().unwrap().pending_assignments);
+		backend.discard_assignments(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().pending_assignments.is_empty());
+
+		let held = |release| HeldRelay {
+			release,
+			entry: entry(pending_event),
+		};
+		backend.hold_relays(&[held(1_700_000_000)]).unwrap();
+		// holding the relay again replaces it
+		backend.hold_relays(&[held(1_700_003_600)]).unwrap();
+		assert_eq!(vec![held(1_700_003_600)], backend.load().unwrap().unwrap().held_relays);
+		backend.release_relays(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().held_relays.is_empty());
+
+		let policy = RetentionPolicy {
+			max_age: None,
+			max_relays: Some(1),
+			prune_checked: false,
+		};
+		assert_eq!(1, backend.prune(&policy, 0, &[]).unwrap());
+		backend.compact().unwrap();
+		assert_eq!(vec![other_relay], backend.load().unwrap().unwrap().relays);
+
+		let reverted = |reason: Option<&str>| RevertedRelay {
+			chain: Chain::Testnet,
+			transaction_hash: other_relay.transaction_hash,
+			reason: reason.map(Into::into),
+			timestamp: 1_700_000_000,
+			event: other_relay.event,
+		};
+		backend.record_reverts(&[reverted(None)]).unwrap();
+		// recording the revert again replaces it
+		backend.record_reverts(&[reverted(Some("already relayed"))]).unwrap();
+		let database = backend.load().unwrap().unwrap();
+		assert_eq!(vec![reverted(Some("already relayed"))], database.reverted_relays);
+		// the reverted relay is removed
+		assert!(database.relays.is_empty());
+
+		let fees = |chain, transactions, gas_used| RelayFees {
+			stream: "withdraw_relay".into(),
+			chain,
+			transactions,
+			gas_used,
+			fees: (gas_used * 0xa0).into(),
+		};
+		backend.record_fees(&[fees(Chain::Mainnet, 1, 50_000), fees(Chain::Testnet, 1, 40_000)]).unwrap();
+		// fees of the same component and chain are added up
+		backend.record_fees(&[fees(Chain::Mainnet, 1, 30_000)]).unwrap();
+		assert_eq!(vec![fees(Chain::Mainnet, 2, 80_000), fees(Chain::Testnet, 1, 40_000)], backend.load().unwrap().unwrap().relay_fees);
+
+		let sent = |to: u64, transaction_hash: u64, timestamp| SentPayload {
+			chain: Chain::Mainnet,
+			to: to.into(),
+			data_hash: 0xd0.into(),
+			transaction_hash: transaction_hash.into(),
+			timestamp,
+		};
+		backend.record_payloads(&[sent(1, 1, 1_700_000_000), sent(2, 2, 1_700_000_000)], 0).unwrap();
+		// the same payload sent again replaces the previous one, and the expired ones are removed
+		backend.record_payloads(&[sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], 1_700_000_050).unwrap();
+		assert_eq!(vec![sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], backend.load().unwrap().unwrap().sent_payloads);
+
+		let change = CheckpointChange {
+			stream: "deposit_relay".into(),
+			from: 120,
+			to: 100,
+			timestamp: 1_700_000_200,
+		};
+		backend.change_checkpoint(&change).unwrap();
+		let database = backend.load().unwrap().unwrap();
+		assert_eq!(100, database.checked_deposit_relay);
+		assert_eq!(vec![change], database.checkpoint_changes);
+	}
+
+	#[test]
+	fn test_postgres_backend_lock() {
+		let (mut active, mut standby) = match (connect("test_postgres_backend_lock"), connect("test_postgres_backend_lock")) {
+			(Some(active), Some(standby)) => (active, standby),
+			_ => return,
+		};
+		active.lock().unwrap();
+		let locked = |backend: &mut PostgresBackend| -> bool {
+			backend.client.query_one("SELECT pg_try_advisory_lock($1, hashtext($2))", &[&super::LOCK_CLASS, &backend.namespace]).unwrap().get(0)
+		};
+		// the lock is reentrant for the active bridge only
+		assert!(locked(&mut active));
+		assert!(!locked(&mut standby));
+
+		active.save(&Database::default()).unwrap();
+		active.update_checkpoints(&[BridgeChecked::DepositRelay(120)]).unwrap();
+		// the lock is released once the active bridge is gone
+		drop(active);
+		standby.lock().unwrap();
+		assert_eq!(120, standby.load().unwrap().unwrap().checked_deposit_relay);
+	}
+}
@@ -0,0 +1,1295 @@
+//! Database stored in SQLite.
+//!
+//! Every update is a single transaction, so the database is never left half-written
+//! and it may be read by other processes while the bridge is running.
+//! Every row belongs to a namespace, so that a single database may hold several bridges.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use rusqlite::{self, Connection};
+use web3::types::{Address, H256, U256};
+use bridge::BridgeChecked;
+use config::RetentionPolicy;
+use error::{Error, ResultExt};
+use super::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain, lock_path, lock_file};
+
+const SCHEMA: &str = r#"
+-- columns of the deployment keep their former names: mainnet_* hold the home contract and testnet_* the foreign one
+CREATE TABLE IF NOT EXISTS deployment (
+	namespace TEXT PRIMARY KEY,
+	mainnet_contract_address BLOB NOT NULL,
+	testnet_contract_address BLOB NOT NULL,
+	mainnet_deploy INTEGER NOT NULL,
+	testnet_deploy INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checkpoints (
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	block INTEGER NOT NULL,
+	PRIMARY KEY (namespace, stream)
+);
+CREATE TABLE IF NOT EXISTS relays (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BLOB NOT NULL,
+	gas_price INTEGER NOT NULL,
+	timestamp INTEGER NOT NULL,
+	block INTEGER,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE INDEX IF NOT EXISTS relays_timestamp ON relays (namespace, timestamp);
+CREATE TABLE IF NOT EXISTS journal (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BLOB NOT NULL,
+	data BLOB NOT NULL,
+	gas INTEGER NOT NULL,
+	gas_price INTEGER NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS quarantine (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	address BLOB NOT NULL,
+	topics BLOB NOT NULL,
+	data BLOB NOT NULL,
+	error TEXT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS filtered_deposits (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	recipient BLOB NOT NULL,
+	value BLOB NOT NULL,
+	filter TEXT NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS discrepancies (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BLOB NOT NULL,
+	timestamp INTEGER NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS pending_assignments (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	message_hash BLOB NOT NULL,
+	authority BLOB NOT NULL,
+	block INTEGER,
+	failures INTEGER NOT NULL,
+	due INTEGER NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS held_relays (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BLOB NOT NULL,
+	data BLOB NOT NULL,
+	gas INTEGER NOT NULL,
+	gas_price INTEGER NOT NULL,
+	release INTEGER NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS reverted_relays (
+	namespace TEXT NOT NULL,
+	event_chain TEXT NOT NULL,
+	event_transaction_hash BLOB NOT NULL,
+	event_log_index INTEGER NOT NULL,
+	chain TEXT NOT NULL,
+	transaction_hash BLOB NOT NULL,
+	reason TEXT,
+	timestamp INTEGER NOT NULL,
+	PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+);
+CREATE TABLE IF NOT EXISTS relay_fees (
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	chain TEXT NOT NULL,
+	transactions INTEGER NOT NULL,
+	gas_used INTEGER NOT NULL,
+	fees BLOB NOT NULL,
+	PRIMARY KEY (namespace, stream, chain)
+);
+CREATE TABLE IF NOT EXISTS sent_payloads (
+	namespace TEXT NOT NULL,
+	chain TEXT NOT NULL,
+	recipient BLOB NOT NULL,
+	data_hash BLOB NOT NULL,
+	transaction_hash BLOB NOT NULL,
+	timestamp INTEGER NOT NULL,
+	PRIMARY KEY (namespace, chain, recipient, data_hash)
+);
+CREATE TABLE IF NOT EXISTS checkpoint_changes (
+	namespace TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	from_block INTEGER NOT NULL,
+	to_block INTEGER NOT NULL,
+	timestamp INTEGER NOT NULL
+);
+"#;
+
+fn address(bytes: Vec<u8>) -> Result<Address, Error> {
+	if bytes.len() != 20 {
+		return Err("Invalid address stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+fn hash(bytes: Vec<u8>) -> Result<H256, Error> {
+	if bytes.len() != 32 {
+		return Err("Invalid transaction hash stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+/// Topics are stored concatenated.
+fn topics(bytes: Vec<u8>) -> Result<Vec<H256>, Error> {
+	if bytes.chunks(32).any(|chunk| chunk.len() != 32) {
+		return Err("Invalid log topics stored in database".into());
+	}
+	Ok(bytes.chunks(32).map(Into::into).collect())
+}
+
+fn value(bytes: Vec<u8>) -> Result<U256, Error> {
+	if bytes.len() != 32 {
+		return Err("Invalid value stored in database".into());
+	}
+	Ok(bytes[..].into())
+}
+
+fn deposit_filter(name: &str) -> Result<DepositFilter, Error> {
+	match name {
+		"below_minimum" => Ok(DepositFilter::BelowMinimum),
+		"above_maximum" => Ok(DepositFilter::AboveMaximum),
+		_ => Err(format!("Invalid deposit filter `{}` stored in database", name).into()),
+	}
+}
+
+fn chain(name: &str) -> Result<Chain, Error> {
+	match name {
+		"mainnet" => Ok(Chain::Mainnet),
+		"testnet" => Ok(Chain::Testnet),
+		_ => Err(format!("Invalid chain `{}` stored in database", name).into()),
+	}
+}
+
+fn insert_checkpoints(connection: &Connection, namespace: &str, checks: &[BridgeChecked]) -> Result<(), Error> {
+	let mut statement = connection.prepare("INSERT OR REPLACE INTO checkpoints (namespace, stream, block) VALUES (?, ?, ?)")?;
+	for check in checks {
+		statement.execute(&[&namespace, &check.stream(), &(check.block() as i64)])?;
+	}
+	Ok(())
+}
+
+/// Adds the blocks of the events to the relays table of the databases created before they were recorded.
+fn add_relay_blocks(connection: &Connection) -> Result<(), Error> {
+	let mut statement = connection.prepare("PRAGMA table_info(relays)")?;
+	let columns = statement.query_map(&[], |row| row.get::<_, String>(1))?
+		.collect::<Result<Vec<_>, _>>()?;
+	if !columns.iter().any(|column| column == "block") {
+		connection.execute_batch("ALTER TABLE relays ADD COLUMN block INTEGER;")?;
+	}
+	Ok(())
+}
+
+fn insert_relays(connection: &Connection, namespace: &str, relays: &[RelayOutcome]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR IGNORE INTO relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, gas_price, timestamp, block) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for relay in relays {
+		statement.execute(&[
+			&namespace,
+			&relay.event.chain.as_str(),
+			&relay.event.transaction_hash.0.to_vec(),
+			&(relay.event.log_index as i64),
+			&relay.chain.as_str(),
+			&relay.transaction_hash.0.to_vec(),
+			&(relay.gas_price as i64),
+			&(relay.timestamp as i64),
+			&relay.block.map(|block| block as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_journal(connection: &Connection, namespace: &str, entries: &[JournalEntry]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO journal (namespace, event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for entry in entries {
+		statement.execute(&[
+			&namespace,
+			&entry.event.chain.as_str(),
+			&entry.event.transaction_hash.0.to_vec(),
+			&(entry.event.log_index as i64),
+			&entry.chain.as_str(),
+			&entry.to.0.to_vec(),
+			&entry.data.0,
+			&(entry.gas as i64),
+			&(entry.gas_price as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_quarantine(connection: &Connection, namespace: &str, logs: &[QuarantinedLog]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO quarantine (namespace, event_chain, event_transaction_hash, event_log_index, address, topics, data, error) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for log in logs {
+		statement.execute(&[
+			&namespace,
+			&log.event.chain.as_str(),
+			&log.event.transaction_hash.0.to_vec(),
+			&(log.event.log_index as i64),
+			&log.address.0.to_vec(),
+			&log.topics.iter().flat_map(|topic| topic.0.to_vec()).collect::<Vec<u8>>(),
+			&log.data.0,
+			&log.error,
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_filtered_deposits(connection: &Connection, namespace: &str, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO filtered_deposits (namespace, event_chain, event_transaction_hash, event_log_index, recipient, value, filter) \
+		VALUES (?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for deposit in deposits {
+		statement.execute(&[
+			&namespace,
+			&deposit.event.chain.as_str(),
+			&deposit.event.transaction_hash.0.to_vec(),
+			&(deposit.event.log_index as i64),
+			&deposit.recipient.0.to_vec(),
+			&deposit.value.0.to_vec(),
+			&deposit.filter.as_str(),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_discrepancies(connection: &Connection, namespace: &str, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO discrepancies (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, timestamp) \
+		VALUES (?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for discrepancy in discrepancies {
+		statement.execute(&[
+			&namespace,
+			&discrepancy.event.chain.as_str(),
+			&discrepancy.event.transaction_hash.0.to_vec(),
+			&(discrepancy.event.log_index as i64),
+			&discrepancy.chain.as_str(),
+			&discrepancy.transaction_hash.0.to_vec(),
+			&(discrepancy.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_reverted_relays(connection: &Connection, namespace: &str, reverts: &[RevertedRelay]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO reverted_relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, reason, timestamp) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for revert in reverts {
+		statement.execute(&[
+			&namespace,
+			&revert.event.chain.as_str(),
+			&revert.event.transaction_hash.0.to_vec(),
+			&(revert.event.log_index as i64),
+			&revert.chain.as_str(),
+			&revert.transaction_hash.0.to_vec(),
+			&revert.reason,
+			&(revert.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_relay_fees(connection: &Connection, namespace: &str, fees: &[RelayFees]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO relay_fees (namespace, stream, chain, transactions, gas_used, fees) VALUES (?, ?, ?, ?, ?, ?)"
+	)?;
+	for fee in fees {
+		statement.execute(&[
+			&namespace,
+			&fee.stream,
+			&fee.chain.as_str(),
+			&(fee.transactions as i64),
+			&(fee.gas_used as i64),
+			&fee.fees.0.to_vec(),
+		])?;
+	}
+	Ok(())
+}
+
+/// Loads fees recorded under `namespace`.
+fn select_relay_fees(connection: &Connection, namespace: &str) -> Result<Vec<RelayFees>, Error> {
+	let mut statement = connection.prepare(
+		"SELECT stream, chain, transactions, gas_used, fees FROM relay_fees WHERE namespace = ? ORDER BY rowid"
+	)?;
+	let rows = statement.query_map(&[&namespace], |row| (
+		row.get::<_, String>(0), row.get::<_, String>(1), row.get::<_, i64>(2), row.get::<_, i64>(3), row.get::<_, Vec<u8>>(4),
+	))?;
+	let mut fees = Vec::new();
+	for row in rows {
+		let (stream, fee_chain, transactions, gas_used, total) = row?;
+		fees.push(RelayFees {
+			stream,
+			chain: chain(&fee_chain)?,
+			transactions: transactions as u64,
+			gas_used: gas_used as u64,
+			fees: value(total)?,
+		});
+	}
+	Ok(fees)
+}
+
+fn insert_sent_payloads(connection: &Connection, namespace: &str, payloads: &[SentPayload]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO sent_payloads (namespace, chain, recipient, data_hash, transaction_hash, timestamp) VALUES (?, ?, ?, ?, ?, ?)"
+	)?;
+	for payload in payloads {
+		statement.execute(&[
+			&namespace,
+			&payload.chain.as_str(),
+			&payload.to.0.to_vec(),
+			&payload.data_hash.0.to_vec(),
+			&payload.transaction_hash.0.to_vec(),
+			&(payload.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_checkpoint_changes(connection: &Connection, namespace: &str, changes: &[CheckpointChange]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT INTO checkpoint_changes (namespace, stream, from_block, to_block, timestamp) VALUES (?, ?, ?, ?, ?)"
+	)?;
+	for change in changes {
+		statement.execute(&[
+			&namespace,
+			&change.stream,
+			&(change.from as i64),
+			&(change.to as i64),
+			&(change.timestamp as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_assignments(connection: &Connection, namespace: &str, assignments: &[PendingAssignment]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO pending_assignments (namespace, event_chain, event_transaction_hash, event_log_index, message_hash, authority, block, failures, due) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for assignment in assignments {
+		statement.execute(&[
+			&namespace,
+			&assignment.event.chain.as_str(),
+			&assignment.event.transaction_hash.0.to_vec(),
+			&(assignment.event.log_index as i64),
+			&assignment.message_hash.0.to_vec(),
+			&assignment.authority.0.to_vec(),
+			&assignment.block.map(|block| block as i64),
+			&assignment.failures,
+			&(assignment.due as i64),
+		])?;
+	}
+	Ok(())
+}
+
+fn insert_held_relays(connection: &Connection, namespace: &str, relays: &[HeldRelay]) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"INSERT OR REPLACE INTO held_relays (namespace, event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price, release) \
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+	)?;
+	for relay in relays {
+		let entry = &relay.entry;
+		statement.execute(&[
+			&namespace,
+			&entry.event.chain.as_str(),
+			&entry.event.transaction_hash.0.to_vec(),
+			&(entry.event.log_index as i64),
+			&entry.chain.as_str(),
+			&entry.to.0.to_vec(),
+			&entry.data.0,
+			&(entry.gas as i64),
+			&(entry.gas_price as i64),
+			&(relay.release as i64),
+		])?;
+	}
+	Ok(())
+}
+
+/// Removes relays of the events, e.g. because their transactions have been reverted.
+fn remove_relays<'a, I: IntoIterator<Item = &'a ProcessedEvent>>(connection: &Connection, namespace: &str, events: I) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"DELETE FROM relays WHERE namespace = ? AND event_chain = ? AND event_transaction_hash = ? AND event_log_index = ?"
+	)?;
+	for event in events {
+		statement.execute(&[
+			&namespace,
+			&event.chain.as_str(),
+			&event.transaction_hash.0.to_vec(),
+			&(event.log_index as i64),
+		])?;
+	}
+	Ok(())
+}
+
+/// Removes journal entries of the events.
+fn complete_journal<'a, I: IntoIterator<Item = &'a ProcessedEvent>>(connection: &Connection, namespace: &str, events: I) -> Result<(), Error> {
+	let mut statement = connection.prepare(
+		"DELETE FROM journal WHERE namespace = ? AND event_chain = ? AND event_transaction_hash = ? AND event_log_index = ?"
+	)?;
+	for event in events {
+		statement.execute(&[
+			&namespace,
+			&event.chain.as_str(),
+			&event.transaction_hash.0.to_vec(),
+			&(event.log_index as i64),
+		])?;
+	}
+	Ok(())
+}
+
+pub struct SqliteBackend {
+	connection: Connection,
+	/// Rows of the bridges stored without a namespace belong to the empty one.
+	namespace: String,
+	lock_path: PathBuf,
+	/// Open lock file, once the database is locked.
+	lock: Option<fs::File>,
+}
+
+impl SqliteBackend {
+	/// Opens the database at `path` holding the bridge stored under `namespace`, creating it if needed.
+	pub fn open<P: AsRef<Path>>(path: P, namespace: Option<&str>) -> Result<Self, Error> {
+		let lock_path = lock_path(path.as_ref(), namespace);
+		let connection = Connection::open(path).chain_err(|| "Cannot open database")?;
+		// readers don't block the bridge and vice versa
+		connection.execute_batch("PRAGMA journal_mode = WAL;")?;
+		connection.execute_batch(SCHEMA).chain_err(|| "Cannot create database tables")?;
+		add_relay_blocks(&connection).chain_err(|| "Cannot add blocks of the events to the relays table")?;
+		Ok(SqliteBackend {
+			connection,
+			namespace: namespace.unwrap_or_default().to_owned(),
+			lock_path,
+			lock: None,
+		})
+	}
+}
+
+impl DatabaseBackend for SqliteBackend {
+	fn lock(&mut self) -> Result<(), Error> {
+		if self.lock.is_none() {
+			self.lock = Some(lock_file(&self.lock_path)?);
+		}
+		Ok(())
+	}
+
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		let deployment = self.connection.query_row(
+			"SELECT mainnet_contract_address, testnet_contract_address, mainnet_deploy, testnet_deploy FROM deployment WHERE namespace = ?",
+			&[&self.namespace],
+			|row| (row.get::<_, Vec<u8>>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2), row.get::<_, i64>(3)),
+		);
+
+		let (home_contract, foreign_contract, home_deploy, foreign_deploy) = match deployment {
+			Ok(deployment) => deployment,
+			Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+			Err(err) => return Err(err.into()),
+		};
+
+		let mut database = Database {
+			home_contract_address: address(home_contract)?,
+			foreign_contract_address: address(foreign_contract)?,
+			home_deploy: home_deploy as u64,
+			foreign_deploy: foreign_deploy as u64,
+			..Database::default()
+		};
+
+		let mut statement = self.connection.prepare("SELECT stream, block FROM checkpoints WHERE namespace = ?")?;
+		let checkpoints = statement.query_map(&[&self.namespace], |row| (row.get::<_, String>(0), row.get::<_, i64>(1)))?;
+		for checkpoint in checkpoints {
+			let (stream, block) = checkpoint?;
+			let check = BridgeChecked::from_stream(&stream, block as u64)
+				.ok_or_else(|| Error::from(format!("Invalid checkpoint `{}` stored in database", stream)))?;
+			database.update_checkpoint(check);
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, gas_price, timestamp, block FROM relays WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let relays = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, String>(3), row.get::<_, Vec<u8>>(4), row.get::<_, i64>(5), row.get::<_, i64>(6), row.get::<_, Option<i64>>(7)),
+		))?;
+		for relay in relays {
+			let ((event_chain, event_transaction_hash, event_log_index), (relay_chain, transaction_hash, gas_price, timestamp, block)) = relay?;
+			database.relays.push(RelayOutcome {
+				chain: chain(&relay_chain)?,
+				transaction_hash: hash(transaction_hash)?,
+				gas_price: gas_price as u64,
+				timestamp: timestamp as u64,
+				block: block.map(|block| block as u64),
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price FROM journal WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let entries = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, String>(3), row.get::<_, Vec<u8>>(4), row.get::<_, Vec<u8>>(5), row.get::<_, i64>(6), row.get::<_, i64>(7)),
+		))?;
+		for entry in entries {
+			let ((event_chain, event_transaction_hash, event_log_index), (entry_chain, to, data, gas, gas_price)) = entry?;
+			database.journal.push(JournalEntry {
+				chain: chain(&entry_chain)?,
+				to: address(to)?,
+				data: data.into(),
+				gas: gas as u64,
+				gas_price: gas_price as u64,
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, address, topics, data, error FROM quarantine WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let logs = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, Vec<u8>>(3), row.get::<_, Vec<u8>>(4), row.get::<_, Vec<u8>>(5), row.get::<_, String>(6)),
+		))?;
+		for log in logs {
+			let ((event_chain, event_transaction_hash, event_log_index), (log_address, log_topics, data, error)) = log?;
+			database.quarantine.push(QuarantinedLog {
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+				address: address(log_address)?,
+				topics: topics(log_topics)?,
+				data: data.into(),
+				error,
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, recipient, value, filter FROM filtered_deposits WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let deposits = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, Vec<u8>>(3), row.get::<_, Vec<u8>>(4), row.get::<_, String>(5)),
+		))?;
+		for deposit in deposits {
+			let ((event_chain, event_transaction_hash, event_log_index), (recipient, deposit_value, filter)) = deposit?;
+			database.filtered_deposits.push(FilteredDeposit {
+				recipient: address(recipient)?,
+				value: value(deposit_value)?,
+				filter: deposit_filter(&filter)?,
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, timestamp FROM discrepancies WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let discrepancies = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, String>(3), row.get::<_, Vec<u8>>(4), row.get::<_, i64>(5)),
+		))?;
+		for discrepancy in discrepancies {
+			let ((event_chain, event_transaction_hash, event_log_index), (relay_chain, transaction_hash, timestamp)) = discrepancy?;
+			database.discrepancies.push(Discrepancy {
+				chain: chain(&relay_chain)?,
+				transaction_hash: hash(transaction_hash)?,
+				timestamp: timestamp as u64,
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, message_hash, authority, block, failures, due FROM pending_assignments WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let assignments = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, Vec<u8>>(3), row.get::<_, Vec<u8>>(4), row.get::<_, Option<i64>>(5), row.get::<_, u32>(6), row.get::<_, i64>(7)),
+		))?;
+		for assignment in assignments {
+			let ((event_chain, event_transaction_hash, event_log_index), (message_hash, authority, block, failures, due)) = assignment?;
+			database.pending_assignments.push(PendingAssignment {
+				message_hash: hash(message_hash)?,
+				authority: address(authority)?,
+				block: block.map(|block| block as u64),
+				failures,
+				due: due as u64,
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, recipient, data, gas, gas_price, release FROM held_relays WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let relays = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, String>(3), row.get::<_, Vec<u8>>(4), row.get::<_, Vec<u8>>(5), row.get::<_, i64>(6), row.get::<_, i64>(7)),
+			row.get::<_, i64>(8),
+		))?;
+		for relay in relays {
+			let ((event_chain, event_transaction_hash, event_log_index), (entry_chain, to, data, gas, gas_price), release) = relay?;
+			database.held_relays.push(HeldRelay {
+				release: release as u64,
+				entry: JournalEntry {
+					chain: chain(&entry_chain)?,
+					to: address(to)?,
+					data: data.into(),
+					gas: gas as u64,
+					gas_price: gas_price as u64,
+					event: ProcessedEvent {
+						chain: chain(&event_chain)?,
+						transaction_hash: hash(event_transaction_hash)?,
+						log_index: event_log_index as u64,
+					},
+				},
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT event_chain, event_transaction_hash, event_log_index, chain, transaction_hash, reason, timestamp FROM reverted_relays WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let reverts = statement.query_map(&[&self.namespace], |row| (
+			(row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, i64>(2)),
+			(row.get::<_, String>(3), row.get::<_, Vec<u8>>(4), row.get::<_, Option<String>>(5), row.get::<_, i64>(6)),
+		))?;
+		for revert in reverts {
+			let ((event_chain, event_transaction_hash, event_log_index), (relay_chain, transaction_hash, reason, timestamp)) = revert?;
+			database.reverted_relays.push(RevertedRelay {
+				chain: chain(&relay_chain)?,
+				transaction_hash: hash(transaction_hash)?,
+				reason,
+				timestamp: timestamp as u64,
+				event: ProcessedEvent {
+					chain: chain(&event_chain)?,
+					transaction_hash: hash(event_transaction_hash)?,
+					log_index: event_log_index as u64,
+				},
+			});
+		}
+
+		database.relay_fees = select_relay_fees(&self.connection, &self.namespace)?;
+
+		let mut statement = self.connection.prepare(
+			"SELECT chain, recipient, data_hash, transaction_hash, timestamp FROM sent_payloads WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let payloads = statement.query_map(&[&self.namespace], |row| (
+			row.get::<_, String>(0), row.get::<_, Vec<u8>>(1), row.get::<_, Vec<u8>>(2), row.get::<_, Vec<u8>>(3), row.get::<_, i64>(4),
+		))?;
+		for payload in payloads {
+			let (payload_chain, to, data_hash, transaction_hash, timestamp) = payload?;
+			database.sent_payloads.push(SentPayload {
+				chain: chain(&payload_chain)?,
+				to: address(to)?,
+				data_hash: hash(data_hash)?,
+				transaction_hash: hash(transaction_hash)?,
+				timestamp: timestamp as u64,
+			});
+		}
+
+		let mut statement = self.connection.prepare(
+			"SELECT stream, from_block, to_block, timestamp FROM checkpoint_changes WHERE namespace = ? ORDER BY rowid"
+		)?;
+		let changes = statement.query_map(&[&self.namespace], |row| (
+			row.get::<_, String>(0), row.get::<_, i64>(1), row.get::<_, i64>(2), row.get::<_, i64>(3),
+		))?;
+		for change in changes {
+			let (stream, from, to, timestamp) = change?;
+			database.checkpoint_changes.push(CheckpointChange {
+				stream,
+				from: from as u64,
+				to: to as u64,
+				timestamp: timestamp as u64,
+			});
+		}
+
+		Ok(Some(database))
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		for table in &["deployment", "checkpoints", "relays", "journal", "quarantine", "filtered_deposits", "discrepancies", "pending_assignments", "held_relays", "reverted_relays", "relay_fees", "sent_payloads", "checkpoint_changes"] {
+			transaction.execute(&format!("DELETE FROM {} WHERE namespace = ?", table), &[&self.namespace])?;
+		}
+		transaction.execute(
+			"INSERT INTO deployment (namespace, mainnet_contract_address, testnet_contract_address, mainnet_deploy, testnet_deploy) VALUES (?, ?, ?, ?, ?)",
+			&[
+				&self.namespace,
+				&database.home_contract_address.0.to_vec(),
+				&database.foreign_contract_address.0.to_vec(),
+				&(database.home_deploy as i64),
+				&(database.foreign_deploy as i64),
+			],
+		)?;
+		insert_checkpoints(&transaction, &self.namespace, &[
+			BridgeChecked::DepositRelay(database.checked_deposit_relay),
+			BridgeChecked::WithdrawRelay(database.checked_withdraw_relay),
+			BridgeChecked::WithdrawConfirm(database.checked_withdraw_confirm),
+			BridgeChecked::MainnetMessageRelay(database.checked_mainnet_message_relay),
+			BridgeChecked::TestnetMessageRelay(database.checked_testnet_message_relay),
+		])?;
+		insert_relays(&transaction, &self.namespace, &database.relays)?;
+		insert_journal(&transaction, &self.namespace, &database.journal)?;
+		insert_quarantine(&transaction, &self.namespace, &database.quarantine)?;
+		insert_filtered_deposits(&transaction, &self.namespace, &database.filtered_deposits)?;
+		insert_discrepancies(&transaction, &self.namespace, &database.discrepancies)?;
+		insert_assignments(&transaction, &self.namespace, &database.pending_assignments)?;
+		insert_held_relays(&transaction, &self.namespace, &database.held_relays)?;
+		insert_reverted_relays(&transaction, &self.namespace, &database.reverted_relays)?;
+		insert_relay_fees(&transaction, &self.namespace, &database.relay_fees)?;
+		insert_sent_payloads(&transaction, &self.namespace, &database.sent_payloads)?;
+		insert_checkpoint_changes(&transaction, &self.namespace, &database.checkpoint_changes)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_checkpoints(&transaction, &self.namespace, checks)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		let check = change.checkpoint().ok_or_else(|| Error::from(format!("Invalid stream `{}`", change.stream)))?;
+		let transaction = self.connection.transaction()?;
+		insert_checkpoints(&transaction, &self.namespace, &[check])?;
+		insert_checkpoint_changes(&transaction, &self.namespace, &[change.clone()])?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_relays(&transaction, &self.namespace, relays)?;
+		complete_journal(&transaction, &self.namespace, relays.iter().map(|relay| &relay.event))?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_journal(&transaction, &self.namespace, entries)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		complete_journal(&transaction, &self.namespace, events)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_quarantine(&transaction, &self.namespace, logs)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_filtered_deposits(&transaction, &self.namespace, deposits)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_discrepancies(&transaction, &self.namespace, discrepancies)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		remove_relays(&transaction, &self.namespace, reverts.iter().map(|revert| &revert.event))?;
+		insert_reverted_relays(&transaction, &self.namespace, reverts)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		// fees are stored as 256-bit values, so they are added up outside of the database
+		let mut totals = select_relay_fees(&transaction, &self.namespace)?;
+		for fee in fees {
+			match totals.iter_mut().find(|total| total.same_account(fee)) {
+				Some(total) => total.add(fee),
+				None => totals.push(fee.clone()),
+			}
+		}
+		insert_relay_fees(&transaction, &self.namespace, &totals)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		transaction.execute("DELETE FROM sent_payloads WHERE namespace = ? AND timestamp < ?", &[&self.namespace, &(expired as i64)])?;
+		insert_sent_payloads(&transaction, &self.namespace, payloads)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_assignments(&transaction, &self.namespace, assignments)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		{
+			let mut statement = transaction.prepare(
+				"DELETE FROM pending_assignments WHERE namespace = ? AND event_chain = ? AND event_transaction_hash = ? AND event_log_index = ?"
+			)?;
+			for event in events {
+				statement.execute(&[
+					&self.namespace,
+					&event.chain.as_str(),
+					&event.transaction_hash.0.to_vec(),
+					&(event.log_index as i64),
+				])?;
+			}
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		insert_held_relays(&transaction, &self.namespace, relays)?;
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		let transaction = self.connection.transaction()?;
+		{
+			let mut statement = transaction.prepare(
+				"DELETE FROM held_relays WHERE namespace = ? AND event_chain = ? AND event_transaction_hash = ? AND event_log_index = ?"
+			)?;
+			for event in events {
+				statement.execute(&[
+					&self.namespace,
+					&event.chain.as_str(),
+					&event.transaction_hash.0.to_vec(),
+					&(event.log_index as i64),
+				])?;
+			}
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		let transaction = self.connection.transaction()?;
+		let mut pruned = 0;
+		if policy.prune_checked {
+			for &(chain, block) in checked {
+				pruned += transaction.execute(
+					"DELETE FROM relays WHERE namespace = ? AND event_chain = ? AND block <= ?",
+					&[&self.namespace, &chain.as_str(), &(block as i64)],
+				)?;
+			}
+		}
+		if let Some(max_age) = policy.max_age {
+			pruned += transaction.execute(
+				"DELETE FROM relays WHERE namespace = ? AND timestamp < ?",
+				&[&self.namespace, &(now.saturating_sub(max_age.as_secs()) as i64)],
+			)?;
+		}
+		if let Some(max_relays) = policy.max_relays {
+			pruned += transaction.execute(
+				"DELETE FROM relays WHERE namespace = ? AND rowid NOT IN \
+				(SELECT rowid FROM relays WHERE namespace = ? ORDER BY rowid DESC LIMIT ?)",
+				&[&self.namespace, &self.namespace, &(max_relays as i64)],
+			)?;
+		}
+		transaction.commit()?;
+		Ok(pruned)
+	}
+
+	fn compact(&mut self) -> Result<(), Error> {
+		self.connection.execute_batch("VACUUM;").chain_err(|| "Cannot compact database")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	use self::tempdir::TempDir;
+	use std::time::Duration;
+	use rusqlite::Connection;
+	use bridge::BridgeChecked;
+	use config::RetentionPolicy;
+	use database::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, DepositFilter, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain};
+	use super::SqliteBackend;
+
+	#[test]
+	fn test_sqlite_backend() {
+		let tempdir = TempDir::new("test_sqlite_backend").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db.sqlite");
+		let mut backend = SqliteBackend::open(&path, None).unwrap();
+		assert_eq!(None, backend.load().unwrap());
+
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0xa0,
+			timestamp: 1_500_000_000,
+			block: Some(0x10),
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 1,
+			},
+		};
+		let database = Database {
+			home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			home_deploy: 100,
+			foreign_deploy: 101,
+			checked_deposit_relay: 100,
+			checked_withdraw_relay: 101,
+			checked_withdraw_confirm: 101,
+			checked_mainnet_message_relay: 100,
+			checked_testnet_message_relay: 101,
+			relays: vec![relay],
+			journal: vec![],
+			quarantine: vec![],
+			filtered_deposits: vec![],
+			discrepancies: vec![],
+			pending_assignments: vec![],
+			held_relays: vec![],
+			reverted_relays: vec![],
+			relay_fees: vec![],
+			sent_payloads: vec![],
+			checkpoint_changes: vec![],
+		};
+		backend.save(&database).unwrap();
+		assert_eq!(Some(database.clone()), backend.load().unwrap());
+
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(120), BridgeChecked::WithdrawConfirm(121), BridgeChecked::TestnetMessageRelay(122)]).unwrap();
+		let other_relay = RelayOutcome {
+			chain: Chain::Mainnet,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				..relay.event
+			},
+			..relay
+		};
+		let entry = |event| JournalEntry {
+			chain: Chain::Mainnet,
+			to: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			data: vec![1, 2, 3].into(),
+			gas: 0xfd,
+			gas_price: 0xa0,
+			event,
+		};
+		let pending_event = ProcessedEvent {
+			log_index: 2,
+			..other_relay.event
+		};
+		backend.write_journal(&[entry(other_relay.event), entry(pending_event)]).unwrap();
+		// events are relayed only once
+		backend.record_relays(&[relay, other_relay]).unwrap();
+
+		let expected = Database {
+			checked_deposit_relay: 120,
+			checked_withdraw_confirm: 121,
+			checked_testnet_message_relay: 122,
+			relays: vec![relay, other_relay],
+			journal: vec![entry(pending_event)],
+			..database
+		};
+		drop(backend);
+		let mut backend = SqliteBackend::open(&path, None).unwrap();
+		assert_eq!(Some(expected), backend.load().unwrap());
+
+		backend.discard_journal(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().journal.is_empty());
+
+		let logs = vec![QuarantinedLog {
+			event: pending_event,
+			address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+			topics: vec![1.into(), 2.into()],
+			data: vec![1, 2, 3].into(),
+			error: "Invalid data".into(),
+		}];
+		backend.quarantine_logs(&logs).unwrap();
+		// quarantining the log again replaces it
+		backend.quarantine_logs(&logs).unwrap();
+		assert_eq!(logs, backend.load().unwrap().unwrap().quarantine);
+
+		let deposits = vec![FilteredDeposit {
+			recipient: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			value: 0xf0.into(),
+			filter: DepositFilter::BelowMinimum,
+			event: pending_event,
+		}];
+		backend.filter_deposits(&deposits).unwrap();
+		// filtering the deposit again replaces it
+		backend.filter_deposits(&deposits).unwrap();
+		assert_eq!(deposits, backend.load().unwrap().unwrap().filtered_deposits);
+
+		let discrepancies = vec![Discrepancy {
+			chain: Chain::Testnet,
+			transaction_hash: 5.into(),
+			timestamp: 1_700_000_000,
+			event: pending_event,
+		}];
+		backend.record_discrepancies(&discrepancies).unwrap();
+		// recording the discrepancy again replaces it
+		backend.record_discrepancies(&discrepancies).unwrap();
+		assert_eq!(discrepancies, backend.load().unwrap().unwrap().discrepancies);
+
+		let assignment = |block| PendingAssignment {
+			message_hash: 0xf0.into(),
+			authority: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+			block,
+			failures: 2,
+			due: 1_700_000_000,
+			event: pending_event,
+		};
+		backend.write_assignments(&[assignment(None)]).unwrap();
+		// writing the assignment again replaces it
+		backend.write_assignments(&[assignment(Some(0x1000))]).unwrap();
+		assert_eq!(vec![assignment(Some(0x1000))], backend.load().unwrap().unwrap().pending_assignments);
+		backend.discard_assignments(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().pending_assignments.is_empty());
+
+		let held = |release| HeldRelay {
+			release,
+			entry: entry(pending_event),
+		};
+		backend.hold_relays(&[held(1_700_000_000)]).unwrap();
+		// holding the relay again replaces it
+		backend.hold_relays(&[held(1_700_003_600)]).unwrap();
+		assert_eq!(vec![held(1_700_003_600)], backend.load().unwrap().unwrap().held_relays);
+		backend.release_relays(&[pending_event]).unwrap();
+		assert!(backend.load().unwrap().unwrap().held_relays.is_empty());
+
+		let reverted = |reason: Option<&str>| RevertedRelay {
+			chain: Chain::Testnet,
+			transaction_hash: other_relay.transaction_hash,
+			reason: reason.map(Into::into),
+			timestamp: 1_700_000_000,
+			event: other_relay.event,
+		};
+		backend.record_reverts(&[reverted(None)]).unwrap();
+		// recording the revert again replaces it
+		backend.record_reverts(&[reverted(Some("already relayed"))]).unwrap();
+		let database = backend.load().unwrap().unwrap();
+		assert_eq!(vec![reverted(Some("already relayed"))], database.reverted_relays);
+		// the reverted relay is removed
+		assert_eq!(vec![relay], database.relays);
+
+		let fees = |chain, transactions, gas_used| RelayFees {
+			stream: "withdraw_relay".into(),
+			chain,
+			transactions,
+			gas_used,
+			fees: (gas_used * 0xa0).into(),
+		};
+		backend.record_fees(&[fees(Chain::Mainnet, 1, 50_000), fees(Chain::Testnet, 1, 40_000)]).unwrap();
+		// fees of the same component and chain are added up
+		backend.record_fees(&[fees(Chain::Mainnet, 1, 30_000)]).unwrap();
+		assert_eq!(vec![fees(Chain::Mainnet, 2, 80_000), fees(Chain::Testnet, 1, 40_000)], backend.load().unwrap().unwrap().relay_fees);
+
+		let sent = |to: u64, transaction_hash: u64, timestamp| SentPayload {
+			chain: Chain::Mainnet,
+			to: to.into(),
+			data_hash: 0xd0.into(),
+			transaction_hash: transaction_hash.into(),
+			timestamp,
+		};
+		backend.record_payloads(&[sent(1, 1, 1_700_000_000), sent(2, 2, 1_700_000_000)], 0).unwrap();
+		// the same payload sent again replaces the previous one, and the expired ones are removed
+		backend.record_payloads(&[sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], 1_700_000_050).unwrap();
+		assert_eq!(vec![sent(1, 3, 1_700_000_100), sent(3, 4, 1_700_000_100)], backend.load().unwrap().unwrap().sent_payloads);
+
+		let change = CheckpointChange {
+			stream: "deposit_relay".into(),
+			from: 120,
+			to: 100,
+			timestamp: 1_700_000_200,
+		};
+		backend.change_checkpoint(&change).unwrap();
+		let database = backend.load().unwrap().unwrap();
+		assert_eq!(100, database.checked_deposit_relay);
+		assert_eq!(vec![change], database.checkpoint_changes);
+	}
+
+	#[test]
+	fn test_sqlite_backend_namespaces() {
+		let tempdir = TempDir::new("test_sqlite_backend_namespaces").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db.sqlite");
+		let mut first = SqliteBackend::open(&path, Some("first")).unwrap();
+		let mut second = SqliteBackend::open(&path, Some("second")).unwrap();
+
+		let database = Database {
+			home_deploy: 100,
+			..Database::default()
+		};
+		first.save(&database).unwrap();
+		second.save(&Database::default()).unwrap();
+		second.update_checkpoints(&[BridgeChecked::DepositRelay(120)]).unwrap();
+
+		assert_eq!(Some(database), first.load().unwrap());
+		assert_eq!(120, second.load().unwrap().unwrap().checked_deposit_relay);
+		assert_eq!(None, SqliteBackend::open(&path, None).unwrap().load().unwrap());
+
+		// bridges lock only their own namespace
+		first.lock().unwrap();
+		second.lock().unwrap();
+		assert!(SqliteBackend::open(&path, Some("first")).unwrap().lock().is_err());
+	}
+
+	#[test]
+	fn test_sqlite_backend_prune() {
+		let tempdir = TempDir::new("test_sqlite_backend_prune").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db.sqlite");
+		let mut backend = SqliteBackend::open(&path, None).unwrap();
+		let mut other = SqliteBackend::open(&path, Some("other")).unwrap();
+
+		let relay = |timestamp| RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp,
+			block: Some(timestamp),
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: timestamp,
+			},
+		};
+		let relays = vec![relay(100), relay(200), relay(300), relay(400)];
+		backend.save(&Database::default()).unwrap();
+		backend.record_relays(&relays).unwrap();
+		other.save(&Database::default()).unwrap();
+		other.record_relays(&relays).unwrap();
+
+		let policy = RetentionPolicy {
+			max_age: Some(Duration::from_secs(850)),
+			max_relays: Some(2),
+			prune_checked: false,
+		};
+		assert_eq!(2, backend.prune(&policy, 1000, &[]).unwrap());
+		backend.compact().unwrap();
+		assert_eq!(vec![relay(300), relay(400)], backend.load().unwrap().unwrap().relays);
+		let prune_checked = RetentionPolicy {
+			max_age: None,
+			max_relays: None,
+			prune_checked: true,
+		};
+		// only the relays of the events in the blocks checked on their chain are pruned
+		assert_eq!(1, backend.prune(&prune_checked, 1000, &[(Chain::Testnet, 400), (Chain::Mainnet, 300)]).unwrap());
+		assert_eq!(vec![relay(400)], backend.load().unwrap().unwrap().relays);
+		// other namespaces are not pruned
+		assert_eq!(relays, other.load().unwrap().unwrap().relays);
+	}
+
+	#[test]
+	fn test_sqlite_backend_adds_relay_blocks() {
+		let tempdir = TempDir::new("test_sqlite_backend_adds_relay_blocks").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db.sqlite");
+		// relays table of the databases created before the blocks of the events were recorded
+		Connection::open(&path).unwrap().execute_batch("CREATE TABLE relays (
+			namespace TEXT NOT NULL,
+			event_chain TEXT NOT NULL,
+			event_transaction_hash BLOB NOT NULL,
+			event_log_index INTEGER NOT NULL,
+			chain TEXT NOT NULL,
+			transaction_hash BLOB NOT NULL,
+			gas_price INTEGER NOT NULL,
+			timestamp INTEGER NOT NULL,
+			PRIMARY KEY (namespace, event_chain, event_transaction_hash, event_log_index)
+		);").unwrap();
+
+		let mut backend = SqliteBackend::open(&path, None).unwrap();
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp: 100,
+			block: Some(0x10),
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		backend.save(&Database::default()).unwrap();
+		backend.record_relays(&[relay]).unwrap();
+		assert_eq!(vec![relay], backend.load().unwrap().unwrap().relays);
+		// the column is added once
+		assert!(SqliteBackend::open(&path, None).is_ok());
+	}
+}
@@ -0,0 +1,264 @@
+//! Notifications of database changes.
+
+use std::sync::{Arc, Mutex};
+use futures::sync::mpsc;
+use bridge::BridgeChecked;
+use config::RetentionPolicy;
+use error::Error;
+use super::{Database, DatabaseBackend, ProcessedEvent, RelayOutcome, JournalEntry, QuarantinedLog, FilteredDeposit, Discrepancy, PendingAssignment, HeldRelay, RevertedRelay, RelayFees, SentPayload, CheckpointChange, Chain};
+
+/// Change written to the database.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseChange {
+	/// Whole database has been replaced, e.g. after deploying the contracts.
+	Saved,
+	/// Bridge component has checked the block.
+	Checkpoint(BridgeChecked),
+	/// Event has been relayed.
+	Relay(RelayOutcome),
+}
+
+#[derive(Default)]
+struct WatchState {
+	database: Option<Database>,
+	subscribers: Vec<mpsc::UnboundedSender<DatabaseChange>>,
+}
+
+impl WatchState {
+	fn notify(&mut self, change: DatabaseChange) {
+		// receivers of dropped subscriptions are gone
+		self.subscribers.retain(|subscriber| subscriber.unbounded_send(change.clone()).is_ok());
+	}
+}
+
+/// Handle observing the database written by `WatchedBackend`.
+#[derive(Clone, Default)]
+pub struct DatabaseWatch {
+	state: Arc<Mutex<WatchState>>,
+}
+
+impl DatabaseWatch {
+	/// Returns the database as it has been last loaded or written.
+	pub fn database(&self) -> Option<Database> {
+		self.state.lock().expect("lock is never poisoned; qed").database.clone()
+	}
+
+	/// Returns stream of changes written after this call.
+	pub fn subscribe(&self) -> mpsc::UnboundedReceiver<DatabaseChange> {
+		let (sender, receiver) = mpsc::unbounded();
+		self.state.lock().expect("lock is never poisoned; qed").subscribers.push(sender);
+		receiver
+	}
+
+	fn update<F: FnOnce(&mut Database)>(&self, f: F, changes: Vec<DatabaseChange>) {
+		let mut state = self.state.lock().expect("lock is never poisoned; qed");
+		if let Some(ref mut database) = state.database {
+			f(database);
+		}
+		for change in changes {
+			state.notify(change);
+		}
+	}
+}
+
+/// Database backend notifying `DatabaseWatch` about every successful write.
+pub struct WatchedBackend<B> {
+	backend: B,
+	watch: DatabaseWatch,
+}
+
+impl<B: DatabaseBackend> WatchedBackend<B> {
+	pub fn new(backend: B) -> Self {
+		WatchedBackend {
+			backend,
+			watch: DatabaseWatch::default(),
+		}
+	}
+
+	/// Returns handle observing this backend.
+	pub fn watch(&self) -> DatabaseWatch {
+		self.watch.clone()
+	}
+}
+
+impl<B: DatabaseBackend> DatabaseBackend for WatchedBackend<B> {
+	fn lock(&mut self) -> Result<(), Error> {
+		self.backend.lock()
+	}
+
+	fn load(&mut self) -> Result<Option<Database>, Error> {
+		let database = self.backend.load()?;
+		self.watch.state.lock().expect("lock is never poisoned; qed").database = database.clone();
+		Ok(database)
+	}
+
+	fn save(&mut self, database: &Database) -> Result<(), Error> {
+		self.backend.save(database)?;
+		let mut state = self.watch.state.lock().expect("lock is never poisoned; qed");
+		state.database = Some(database.clone());
+		state.notify(DatabaseChange::Saved);
+		Ok(())
+	}
+
+	fn update_checkpoints(&mut self, checks: &[BridgeChecked]) -> Result<(), Error> {
+		self.backend.update_checkpoints(checks)?;
+		self.watch.update(
+			|database| for check in checks {
+				database.update_checkpoint(*check);
+			},
+			checks.iter().cloned().map(DatabaseChange::Checkpoint).collect(),
+		);
+		Ok(())
+	}
+
+	fn change_checkpoint(&mut self, change: &CheckpointChange) -> Result<(), Error> {
+		self.backend.change_checkpoint(change)?;
+		self.watch.update(
+			|database| database.change_checkpoint(change),
+			change.checkpoint().into_iter().map(DatabaseChange::Checkpoint).collect(),
+		);
+		Ok(())
+	}
+
+	fn record_relays(&mut self, relays: &[RelayOutcome]) -> Result<(), Error> {
+		self.backend.record_relays(relays)?;
+		self.watch.update(
+			|database| database.record_relays(relays),
+			relays.iter().cloned().map(DatabaseChange::Relay).collect(),
+		);
+		Ok(())
+	}
+
+	fn write_journal(&mut self, entries: &[JournalEntry]) -> Result<(), Error> {
+		self.backend.write_journal(entries)?;
+		self.watch.update(|database| database.journal.extend_from_slice(entries), vec![]);
+		Ok(())
+	}
+
+	fn discard_journal(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.backend.discard_journal(events)?;
+		self.watch.update(|database| database.discard_journal(events), vec![]);
+		Ok(())
+	}
+
+	fn quarantine_logs(&mut self, logs: &[QuarantinedLog]) -> Result<(), Error> {
+		self.backend.quarantine_logs(logs)?;
+		self.watch.update(|database| database.quarantine_logs(logs), vec![]);
+		Ok(())
+	}
+
+	fn filter_deposits(&mut self, deposits: &[FilteredDeposit]) -> Result<(), Error> {
+		self.backend.filter_deposits(deposits)?;
+		self.watch.update(|database| database.filter_deposits(deposits), vec![]);
+		Ok(())
+	}
+
+	fn record_discrepancies(&mut self, discrepancies: &[Discrepancy]) -> Result<(), Error> {
+		self.backend.record_discrepancies(discrepancies)?;
+		self.watch.update(|database| database.record_discrepancies(discrepancies), vec![]);
+		Ok(())
+	}
+
+	fn record_reverts(&mut self, reverts: &[RevertedRelay]) -> Result<(), Error> {
+		self.backend.record_reverts(reverts)?;
+		self.watch.update(|database| database.record_reverts(reverts), vec![]);
+		Ok(())
+	}
+
+	fn record_fees(&mut self, fees: &[RelayFees]) -> Result<(), Error> {
+		self.backend.record_fees(fees)?;
+		self.watch.update(|database| database.record_fees(fees), vec![]);
+		Ok(())
+	}
+
+	fn record_payloads(&mut self, payloads: &[SentPayload], expired: u64) -> Result<(), Error> {
+		self.backend.record_payloads(payloads, expired)?;
+		self.watch.update(|database| database.record_payloads(payloads, expired), vec![]);
+		Ok(())
+	}
+
+	fn write_assignments(&mut self, assignments: &[PendingAssignment]) -> Result<(), Error> {
+		self.backend.write_assignments(assignments)?;
+		self.watch.update(|database| database.write_assignments(assignments), vec![]);
+		Ok(())
+	}
+
+	fn discard_assignments(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.backend.discard_assignments(events)?;
+		self.watch.update(|database| database.discard_assignments(events), vec![]);
+		Ok(())
+	}
+
+	fn hold_relays(&mut self, relays: &[HeldRelay]) -> Result<(), Error> {
+		self.backend.hold_relays(relays)?;
+		self.watch.update(|database| database.hold_relays(relays), vec![]);
+		Ok(())
+	}
+
+	fn release_relays(&mut self, events: &[ProcessedEvent]) -> Result<(), Error> {
+		self.backend.release_relays(events)?;
+		self.watch.update(|database| database.release_relays(events), vec![]);
+		Ok(())
+	}
+
+	fn prune(&mut self, policy: &RetentionPolicy, now: u64, checked: &[(Chain, u64)]) -> Result<usize, Error> {
+		let pruned = self.backend.prune(policy, now, checked)?;
+		self.watch.update(|database| { database.prune(policy, now, checked); }, vec![]);
+		Ok(pruned)
+	}
+
+	fn compact(&mut self) -> Result<(), Error> {
+		self.backend.compact()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	use self::tempdir::TempDir;
+	use futures::{Future, Stream};
+	use bridge::BridgeChecked;
+	use database::{Database, DatabaseBackend, TomlFileBackend, ProcessedEvent, RelayOutcome, Chain};
+	use super::{WatchedBackend, DatabaseChange};
+
+	#[test]
+	fn test_watched_backend_notifies_about_changes() {
+		let tempdir = TempDir::new("test_watched_backend_notifies_about_changes").unwrap();
+		let mut path = tempdir.path().to_owned();
+		path.push("db");
+		let mut backend = WatchedBackend::new(TomlFileBackend::new(&path, None));
+		let watch = backend.watch();
+		assert_eq!(None, backend.load().unwrap());
+		let changes = watch.subscribe();
+		// dropped subscriptions are not notified
+		drop(watch.subscribe());
+
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 0,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		backend.save(&Database::default()).unwrap();
+		backend.update_checkpoints(&[BridgeChecked::DepositRelay(120)]).unwrap();
+		backend.record_relays(&[relay]).unwrap();
+
+		let expected = vec![
+			DatabaseChange::Saved,
+			DatabaseChange::Checkpoint(BridgeChecked::DepositRelay(120)),
+			DatabaseChange::Relay(relay),
+		];
+		assert_eq!(expected, changes.take(3).collect().wait().unwrap());
+
+		let database = watch.database().unwrap();
+		assert_eq!(120, database.checked_deposit_relay);
+		assert_eq!(vec![relay], database.relays);
+		assert_eq!(Some(database), TomlFileBackend::new(&path, None).load().unwrap());
+	}
+}
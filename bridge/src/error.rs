@@ -1,9 +1,12 @@
 #![allow(unknown_lints)]
 
 use std::io;
-use api::ApiCall;
+use rustc_hex::FromHex;
+use api::{ApiCall, BatchCall};
+use signer::ExternalSignature;
 use tokio_timer::{TimerError, TimeoutError};
-use {web3, toml, ethabi};
+use web3::Transport;
+use {web3, toml, ethabi, rpc, rusqlite, postgres};
 
 error_chain! {
 	types {
@@ -15,6 +18,8 @@ error_chain! {
 		Toml(toml::de::Error);
 		Ethabi(ethabi::Error);
 		Timer(TimerError);
+		Sqlite(rusqlite::Error);
+		Postgres(postgres::Error);
 	}
 
 	errors {
@@ -37,8 +42,8 @@ error_chain! {
 	}
 }
 
-impl<T, F> From<TimeoutError<ApiCall<T, F>>> for Error {
-	fn from(err: TimeoutError<ApiCall<T, F>>) -> Self {
+impl<T, P: Transport> From<TimeoutError<ApiCall<T, P>>> for Error {
+	fn from(err: TimeoutError<ApiCall<T, P>>) -> Self {
 		match err {
 			TimeoutError::Timer(call, _) | TimeoutError::TimedOut(call) => {
 				ErrorKind::Timeout(call.message()).into()
@@ -46,3 +51,175 @@ impl<T, F> From<TimeoutError<ApiCall<T, F>>> for Error {
 		}
 	}
 }
+
+impl<B, F> From<TimeoutError<BatchCall<B, F>>> for Error {
+	fn from(_err: TimeoutError<BatchCall<B, F>>) -> Self {
+		ErrorKind::Timeout("batch").into()
+	}
+}
+
+impl From<TimeoutError<ExternalSignature>> for Error {
+	fn from(_err: TimeoutError<ExternalSignature>) -> Self {
+		ErrorKind::Timeout("external signature").into()
+	}
+}
+
+/// Class of the error, used to decide whether the failed request should be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorClass {
+	/// Node could not be reached.
+	Network,
+	/// Node did not respond in time.
+	Timeout,
+	/// Transaction nonce has already been used.
+	NonceTooLow,
+	/// Account cannot pay for the transaction.
+	InsufficientFunds,
+	/// Call or transaction has been reverted.
+	Reverted,
+	/// Node is not synchronized with the chain yet.
+	Syncing,
+	/// Response would exceed the limit of the provider, e.g. too many logs are matching the filter.
+	TooManyResults,
+	/// Any other error.
+	Other,
+}
+
+impl ErrorClass {
+	/// Name of the class used in config.
+	pub fn name(&self) -> &'static str {
+		match *self {
+			ErrorClass::Network => "network",
+			ErrorClass::Timeout => "timeout",
+			ErrorClass::NonceTooLow => "nonce_too_low",
+			ErrorClass::InsufficientFunds => "insufficient_funds",
+			ErrorClass::Reverted => "reverted",
+			ErrorClass::Syncing => "syncing",
+			ErrorClass::TooManyResults => "too_many_results",
+			ErrorClass::Other => "other",
+		}
+	}
+}
+
+/// Code of the error returned by geth for reverted calls.
+const REVERTED_ERROR_CODE: i64 = 3;
+
+/// Parts of the messages returned by providers limiting the size of `eth_getLogs` responses.
+const TOO_MANY_RESULTS_MESSAGES: [&str; 5] = [
+	"query returned more than",
+	"response size exceeded",
+	"response size should not",
+	"block range is too wide",
+	"exceed maximum block range",
+];
+
+fn rpc_error_class(err: &rpc::Error) -> ErrorClass {
+	let message = err.message.to_lowercase();
+	if message.contains("nonce too low") || message.contains("nonce is too low") {
+		ErrorClass::NonceTooLow
+	} else if message.contains("insufficient funds") {
+		ErrorClass::InsufficientFunds
+	} else if err.code.code() == REVERTED_ERROR_CODE || message.contains("revert") || message.contains("vm execution error") {
+		ErrorClass::Reverted
+	} else if message.contains("syncing") || message.contains("not synced") || message.contains("header not found") {
+		ErrorClass::Syncing
+	} else if TOO_MANY_RESULTS_MESSAGES.iter().any(|part| message.contains(part)) {
+		ErrorClass::TooManyResults
+	} else {
+		ErrorClass::Other
+	}
+}
+
+/// Selector of `Error(string)`, which is returned by calls reverted with a reason.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Decodes the reason of the revert from the data returned by the reverted call.
+fn decode_revert_reason(data: &[u8]) -> Option<String> {
+	if data.len() < 4 || data[..4] != ERROR_SELECTOR {
+		return None;
+	}
+
+	match ethabi::decode(&[ethabi::ParamType::String], &data[4..]).ok()?.pop() {
+		Some(ethabi::Token::String(reason)) => Some(reason),
+		_ => None,
+	}
+}
+
+/// Returns data of the reverted call. Geth reports it hex encoded, parity prefixes it with `Reverted`.
+fn revert_data(err: &rpc::Error) -> Option<Vec<u8>> {
+	let data = err.data.as_ref()?.as_str()?;
+	data.trim_start_matches("Reverted ").trim_start_matches("0x").from_hex().ok()
+}
+
+impl Error {
+	/// Reason of the revert of the call, if it has been reported by the node.
+	pub fn revert_reason(&self) -> Option<String> {
+		match *self.kind() {
+			ErrorKind::Web3(web3::Error::Rpc(ref err)) if rpc_error_class(err) == ErrorClass::Reverted => {
+				revert_data(err).and_then(|data| decode_revert_reason(&data))
+			},
+			_ => None,
+		}
+	}
+
+	pub fn class(&self) -> ErrorClass {
+		match *self.kind() {
+			ErrorKind::Timeout(_) => ErrorClass::Timeout,
+			ErrorKind::Web3(web3::Error::Transport(_)) | ErrorKind::Web3(web3::Error::Unreachable) => ErrorClass::Network,
+			ErrorKind::Web3(web3::Error::Rpc(ref err)) => rpc_error_class(err),
+			_ => ErrorClass::Other,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use {web3, rpc};
+	use super::{Error, ErrorKind, ErrorClass};
+
+	fn rpc_error(code: i64, message: &str) -> Error {
+		ErrorKind::Web3(web3::Error::Rpc(rpc::Error {
+			code: rpc::ErrorCode::ServerError(code),
+			message: message.into(),
+			data: None,
+		})).into()
+	}
+
+	#[test]
+	fn test_error_class() {
+		assert_eq!(ErrorClass::Timeout, Error::from(ErrorKind::Timeout("eth_getLogs")).class());
+		assert_eq!(ErrorClass::Network, Error::from(ErrorKind::Web3(web3::Error::Transport("broken pipe".into()))).class());
+		assert_eq!(ErrorClass::NonceTooLow, rpc_error(-32000, "nonce too low").class());
+		assert_eq!(ErrorClass::NonceTooLow, rpc_error(-32010, "Transaction nonce is too low. Try incrementing the nonce.").class());
+		assert_eq!(ErrorClass::InsufficientFunds, rpc_error(-32000, "insufficient funds for gas * price + value").class());
+		assert_eq!(ErrorClass::Reverted, rpc_error(3, "execution reverted").class());
+		assert_eq!(ErrorClass::Reverted, rpc_error(-32015, "VM execution error.").class());
+		assert_eq!(ErrorClass::Syncing, rpc_error(-32000, "header not found").class());
+		assert_eq!(ErrorClass::TooManyResults, rpc_error(-32005, "query returned more than 10000 results").class());
+		assert_eq!(ErrorClass::TooManyResults, rpc_error(-32602, "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range").class());
+		assert_eq!(ErrorClass::Other, rpc_error(-32601, "Method not found").class());
+		assert_eq!(ErrorClass::Other, Error::from("invalid config").class());
+	}
+
+	#[test]
+	fn test_revert_reason() {
+		let reason = "0x08c379a0\
+			0000000000000000000000000000000000000000000000000000000000000020\
+			000000000000000000000000000000000000000000000000000000000000000f\
+			616c72656164792072656c617965640000000000000000000000000000000000";
+		let reverted = |data: &str| -> Error {
+			ErrorKind::Web3(web3::Error::Rpc(rpc::Error {
+				code: rpc::ErrorCode::ServerError(3),
+				message: "execution reverted".into(),
+				data: Some(data.into()),
+			})).into()
+		};
+
+		assert_eq!(Some("already relayed".to_owned()), reverted(reason).revert_reason());
+		assert_eq!(Some("already relayed".to_owned()), reverted(&reason.replace("0x", "Reverted 0x")).revert_reason());
+		// custom errors are not decoded
+		assert_eq!(None, reverted("0x2f5f8e2a").revert_reason());
+		assert_eq!(None, rpc_error(3, "execution reverted").revert_reason());
+		assert_eq!(None, rpc_error(-32000, "nonce too low").revert_reason());
+	}
+}
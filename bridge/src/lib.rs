@@ -16,6 +16,19 @@ extern crate ethabi_derive;
 #[macro_use]
 extern crate ethabi_contract;
 extern crate rustc_hex;
+extern crate jsonrpc_core as rpc;
+extern crate websocket;
+extern crate openssl;
+extern crate tokio_openssl;
+extern crate rand;
+extern crate hyper;
+extern crate tokio_io;
+extern crate tokio_service;
+extern crate rusqlite;
+extern crate postgres;
+extern crate fs2;
+extern crate tiny_keccak;
+extern crate secp256k1;
 #[macro_use]
 extern crate log;
 
@@ -29,5 +42,11 @@ pub mod bridge;
 pub mod contracts;
 pub mod database;
 pub mod error;
+pub mod metrics;
+pub mod server;
+pub mod signer;
+pub mod systemd;
+pub mod transaction;
+pub mod transport;
 pub mod util;
 
@@ -0,0 +1,534 @@
+//! Accounting of requests sent to the nodes, of the gas prices deferring relays and of the progress of the bridge,
+//! which is logged and exposed to Prometheus.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use futures::{Future, Stream};
+use tokio_timer::Timer;
+use web3::types::Address;
+use database::{Chain, RelayOutcome, RevertedRelay};
+
+/// Number of the most recent latencies used to calculate percentiles.
+const LATENCY_SAMPLES: usize = 1024;
+
+/// Result of a single request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+	Success,
+	Error,
+	/// Request has been dropped before receiving the response.
+	Timeout,
+}
+
+#[derive(Default)]
+struct MethodStats {
+	requests: u64,
+	errors: u64,
+	timeouts: u64,
+	latencies: VecDeque<Duration>,
+}
+
+/// Statistics of requests sent to the node of a single chain.
+#[derive(Default)]
+pub struct RequestMetrics {
+	methods: Mutex<BTreeMap<String, MethodStats>>,
+}
+
+impl RequestMetrics {
+	pub fn record(&self, method: &str, outcome: Outcome, latency: Duration) {
+		let mut methods = self.methods.lock().expect("lock is never poisoned; qed");
+		let stats = methods.entry(method.to_owned()).or_default();
+		stats.requests += 1;
+		match outcome {
+			Outcome::Success => {},
+			Outcome::Error => stats.errors += 1,
+			Outcome::Timeout => stats.timeouts += 1,
+		}
+
+		// timed out requests would skew the latency
+		if outcome != Outcome::Timeout {
+			if stats.latencies.len() == LATENCY_SAMPLES {
+				stats.latencies.pop_front();
+			}
+			stats.latencies.push_back(latency);
+		}
+	}
+
+	/// Returns statistics of all methods, ordered by method name.
+	pub fn snapshot(&self) -> Vec<MethodSnapshot> {
+		let methods = self.methods.lock().expect("lock is never poisoned; qed");
+		methods.iter().map(|(method, stats)| {
+			let mut latencies = stats.latencies.iter().cloned().collect::<Vec<_>>();
+			latencies.sort();
+			MethodSnapshot {
+				method: method.clone(),
+				requests: stats.requests,
+				errors: stats.errors,
+				timeouts: stats.timeouts,
+				p50: percentile(&latencies, 50),
+				p90: percentile(&latencies, 90),
+				p99: percentile(&latencies, 99),
+			}
+		}).collect()
+	}
+
+	/// Total number of requests sent to the node.
+	pub fn total_requests(&self) -> u64 {
+		let methods = self.methods.lock().expect("lock is never poisoned; qed");
+		methods.values().map(|stats| stats.requests).sum()
+	}
+}
+
+/// Returns `p`-th percentile of sorted `latencies`.
+fn percentile(latencies: &[Duration], p: usize) -> Option<Duration> {
+	if latencies.is_empty() {
+		return None;
+	}
+
+	// nearest-rank method
+	let rank = (latencies.len() as f64 * p as f64 / 100.0).ceil() as usize;
+	Some(latencies[rank.saturating_sub(1)])
+}
+
+/// Statistics of a single method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSnapshot {
+	pub method: String,
+	pub requests: u64,
+	pub errors: u64,
+	pub timeouts: u64,
+	/// Latency percentiles of the most recent responses, `None` if there were none.
+	pub p50: Option<Duration>,
+	pub p90: Option<Duration>,
+	pub p99: Option<Duration>,
+}
+
+fn as_millis(latency: Option<Duration>) -> u64 {
+	latency.map_or(0, |latency| latency.as_secs() * 1_000 + latency.subsec_nanos() as u64 / 1_000_000)
+}
+
+impl fmt::Display for MethodSnapshot {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}: {} requests, {} errors, {} timeouts, latency p50 {}ms p90 {}ms p99 {}ms",
+			self.method, self.requests, self.errors, self.timeouts,
+			as_millis(self.p50), as_millis(self.p90), as_millis(self.p99))
+	}
+}
+
+/// Gas price of a chain, as last checked by the guard deferring relays while it exceeds `max_gas_price`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GasPriceSnapshot {
+	/// Wei per gas, `None` until it's checked.
+	pub gas_price: Option<u64>,
+	/// Whether relays are being deferred.
+	pub deferring: bool,
+	/// Number of times relays have been deferred.
+	pub deferrals: u64,
+}
+
+impl fmt::Display for GasPriceSnapshot {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.gas_price {
+			Some(gas_price) => write!(f, "gas price {} wei", gas_price)?,
+			None => write!(f, "gas price not checked")?,
+		}
+		write!(f, ", {} deferrals", self.deferrals)?;
+		if self.deferring {
+			write!(f, ", deferring relays")?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Default)]
+pub struct GasPriceMetrics {
+	snapshot: Mutex<GasPriceSnapshot>,
+}
+
+impl GasPriceMetrics {
+	/// Records the checked `gas_price` and whether relays are `deferring` because of it.
+	pub fn record(&self, gas_price: u64, deferring: bool) {
+		let mut snapshot = self.snapshot.lock().expect("lock is never poisoned; qed");
+		if deferring && !snapshot.deferring {
+			snapshot.deferrals += 1;
+		}
+		snapshot.gas_price = Some(gas_price);
+		snapshot.deferring = deferring;
+	}
+
+	pub fn snapshot(&self) -> GasPriceSnapshot {
+		*self.snapshot.lock().expect("lock is never poisoned; qed")
+	}
+}
+
+/// Relays of a bridge component to a chain.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RelaySnapshot {
+	/// Submitted relay transactions.
+	pub relays: u64,
+	/// Relay transactions, which have been reverted or which have expired.
+	pub failures: u64,
+	/// Gas used by the mined relay transactions.
+	pub gas_used: u64,
+	/// Fees paid for the mined relay transactions, in wei.
+	pub fees: u128,
+}
+
+/// Checkpoint of a bridge component, which reads events from `chain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointSnapshot {
+	pub chain: Chain,
+	pub block: u64,
+	/// Time of the last update, or of the start of the component.
+	pub updated: Instant,
+}
+
+#[derive(Default)]
+struct Progress {
+	heads: BTreeMap<&'static str, u64>,
+	checkpoints: BTreeMap<&'static str, CheckpointSnapshot>,
+	relays: BTreeMap<(String, &'static str), RelaySnapshot>,
+	balances: BTreeMap<&'static str, (Address, u128)>,
+}
+
+/// Progress of the bridge components, their relays and the balances of the authority accounts.
+#[derive(Default)]
+pub struct BridgeMetrics {
+	progress: Mutex<Progress>,
+}
+
+impl BridgeMetrics {
+	fn progress(&self) -> MutexGuard<Progress> {
+		self.progress.lock().expect("lock is never poisoned; qed")
+	}
+
+	/// Records the best block of `chain`.
+	pub fn record_head(&self, chain: Chain, block: u64) {
+		self.progress().heads.insert(chain.as_str(), block);
+	}
+
+	/// Records the checkpoint of the component with given name, e.g. `deposit_relay`, which reads events from `chain`.
+	pub fn record_checkpoint(&self, stream: &'static str, chain: Chain, block: u64) {
+		self.progress().checkpoints.insert(stream, CheckpointSnapshot {
+			chain,
+			block,
+			updated: Instant::now(),
+		});
+	}
+
+	/// Updates the checkpoint of the component, which has been recorded before.
+	pub fn update_checkpoint(&self, stream: &'static str, block: u64) {
+		if let Some(checkpoint) = self.progress().checkpoints.get_mut(stream) {
+			checkpoint.block = block;
+			checkpoint.updated = Instant::now();
+		}
+	}
+
+	/// Returns the components, which checkpoints have not been updated for `staleness` at `now`.
+	pub fn stale_streams(&self, now: Instant, staleness: Duration) -> Vec<&'static str> {
+		self.progress().checkpoints.iter()
+			.filter(|&(_, checkpoint)| now.duration_since(checkpoint.updated) >= staleness)
+			.map(|(&stream, _)| stream)
+			.collect()
+	}
+
+	fn update_relays<F: FnMut(&mut RelaySnapshot)>(&self, stream: &str, chain: Chain, mut update: F) {
+		let mut progress = self.progress();
+		update(progress.relays.entry((stream.to_owned(), chain.as_str())).or_default());
+	}
+
+	/// Records the relay transactions submitted by the component.
+	pub fn record_relays(&self, stream: &str, relays: &[RelayOutcome]) {
+		for relay in relays {
+			self.update_relays(stream, relay.chain, |snapshot| snapshot.relays += 1);
+		}
+	}
+
+	/// Records the relay transactions of the component, which have been reverted or which have expired.
+	pub fn record_failures(&self, stream: &str, reverted: &[RevertedRelay]) {
+		for relay in reverted {
+			self.update_relays(stream, relay.chain, |snapshot| snapshot.failures += 1);
+		}
+	}
+
+	/// Records the gas used by a mined relay transaction of the component and the fee paid for it in wei.
+	pub fn record_fees(&self, stream: &str, chain: Chain, gas_used: u64, fees: u128) {
+		self.update_relays(stream, chain, |snapshot| {
+			snapshot.gas_used = snapshot.gas_used.saturating_add(gas_used);
+			snapshot.fees = snapshot.fees.saturating_add(fees);
+		});
+	}
+
+	/// Records the balance of the authority `account` on `chain` in wei.
+	pub fn record_balance(&self, chain: Chain, account: Address, balance: u128) {
+		self.progress().balances.insert(chain.as_str(), (account, balance));
+	}
+}
+
+/// Request statistics and gas prices of both chains, and the progress of the bridge.
+#[derive(Default, Clone)]
+pub struct Metrics {
+	pub mainnet: Arc<RequestMetrics>,
+	pub testnet: Arc<RequestMetrics>,
+	pub mainnet_gas_price: Arc<GasPriceMetrics>,
+	pub testnet_gas_price: Arc<GasPriceMetrics>,
+	pub bridge: Arc<BridgeMetrics>,
+}
+
+impl Metrics {
+	/// Logs the statistics, prefixed with the namespace of the `bridge`, if it has one.
+	fn log(&self, bridge: Option<&str>) {
+		let label = |chain: &str| match bridge {
+			Some(bridge) => format!("{} {}", bridge, chain),
+			None => chain.to_owned(),
+		};
+		for &(chain, metrics) in &[("mainnet", &self.mainnet), ("testnet", &self.testnet)] {
+			let chain = label(chain);
+			info!(target: "bridge", "{} requests: {}", chain, metrics.total_requests());
+			for method in metrics.snapshot() {
+				info!(target: "bridge", "{} {}", chain, method);
+			}
+		}
+
+		for &(chain, gas_price) in &[("mainnet", &self.mainnet_gas_price), ("testnet", &self.testnet_gas_price)] {
+			let chain = label(chain);
+			let snapshot = gas_price.snapshot();
+			// gas prices of chains without `max_gas_price` are never checked
+			if snapshot.gas_price.is_some() {
+				info!(target: "bridge", "{} {}", chain, snapshot);
+			}
+		}
+	}
+}
+
+/// Logs request statistics every `interval`, prefixed with the namespace of the `bridge`, if there is one.
+pub fn report(metrics: Metrics, bridge: Option<String>, timer: &Timer, interval: Duration) -> Box<Future<Item = (), Error = ()>> {
+	let report = timer.interval(interval)
+		.for_each(move |_| {
+			metrics.log(bridge.as_deref());
+			Ok(())
+		})
+		.map_err(|err| warn!(target: "bridge", "Metrics reporting stopped: {}", err));
+	Box::new(report)
+}
+
+/// Samples of a single metric in the text format of Prometheus.
+struct Family {
+	name: &'static str,
+	lines: Vec<String>,
+}
+
+impl Family {
+	fn new(name: &'static str, kind: &'static str, help: &'static str) -> Self {
+		Family {
+			name,
+			lines: vec![format!("# HELP {} {}", name, help), format!("# TYPE {} {}", name, kind)],
+		}
+	}
+
+	/// Adds the sample of the `bridge`, which is labelled if it has a namespace.
+	fn sample<V: fmt::Display>(&mut self, bridge: Option<&str>, labels: &[(&str, &str)], value: V) {
+		let labels = bridge.map(|bridge| ("bridge", bridge)).iter().chain(labels)
+			.map(|&(name, value)| format!("{}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+			.collect::<Vec<_>>();
+		self.lines.push(format!("{}{{{}}} {}", self.name, labels.join(","), value));
+	}
+}
+
+/// Formats the metrics of the bridges, labelled with their namespaces if they have any, in the text format of Prometheus.
+pub fn prometheus(bridges: &[(Option<String>, Metrics)]) -> String {
+	let mut heads = Family::new("bridge_head_block", "gauge", "Best block of the chain.");
+	let mut checkpoints = Family::new("bridge_checkpoint_block", "gauge", "Last block checked by the bridge component.");
+	let mut lags = Family::new("bridge_lag_blocks", "gauge", "Blocks between the checkpoint of the bridge component and the best block of the chain it reads events from.");
+	let mut relays = Family::new("bridge_relays_total", "counter", "Relay transactions submitted by the bridge component.");
+	let mut failures = Family::new("bridge_relay_failures_total", "counter", "Relay transactions of the bridge component, which have been reverted or which have expired.");
+	let mut gas_used = Family::new("bridge_relay_gas_used_total", "counter", "Gas used by the mined relay transactions of the bridge component.");
+	let mut fees = Family::new("bridge_relay_fees_wei_total", "counter", "Fees paid for the mined relay transactions of the bridge component.");
+	let mut balances = Family::new("bridge_balance_wei", "gauge", "Balance of the authority account, if `min_balance` is configured.");
+	let mut gas_prices = Family::new("bridge_gas_price_wei", "gauge", "Gas price of the chain, if `max_gas_price` is configured.");
+	let mut requests = Family::new("bridge_rpc_requests_total", "counter", "Requests sent to the node.");
+	let mut errors = Family::new("bridge_rpc_errors_total", "counter", "Requests, which the node has failed.");
+	let mut timeouts = Family::new("bridge_rpc_timeouts_total", "counter", "Requests, which the node has not answered in time.");
+
+	for &(ref namespace, ref metrics) in bridges {
+		let bridge = namespace.as_ref().map(String::as_str);
+		let progress = metrics.bridge.progress();
+		for (&chain, head) in &progress.heads {
+			heads.sample(bridge, &[("chain", chain)], head);
+		}
+		for (&stream, checkpoint) in &progress.checkpoints {
+			let chain = checkpoint.chain.as_str();
+			checkpoints.sample(bridge, &[("stream", stream), ("chain", chain)], &checkpoint.block);
+			if let Some(head) = progress.heads.get(chain) {
+				lags.sample(bridge, &[("stream", stream), ("chain", chain)], &head.saturating_sub(checkpoint.block));
+			}
+		}
+		for (&(ref stream, chain), snapshot) in &progress.relays {
+			let labels = [("stream", stream.as_str()), ("chain", chain)];
+			relays.sample(bridge, &labels, &snapshot.relays);
+			failures.sample(bridge, &labels, &snapshot.failures);
+			gas_used.sample(bridge, &labels, &snapshot.gas_used);
+			fees.sample(bridge, &labels, &snapshot.fees);
+		}
+		for (&chain, &(account, balance)) in &progress.balances {
+			let account = format!("{:?}", account);
+			balances.sample(bridge, &[("chain", chain), ("account", account.as_str())], &balance);
+		}
+
+		for &(chain, gas_price) in &[("mainnet", &metrics.mainnet_gas_price), ("testnet", &metrics.testnet_gas_price)] {
+			if let Some(gas_price) = gas_price.snapshot().gas_price {
+				gas_prices.sample(bridge, &[("chain", chain)], &gas_price);
+			}
+		}
+		for &(chain, metrics) in &[("mainnet", &metrics.mainnet), ("testnet", &metrics.testnet)] {
+			for method in metrics.snapshot() {
+				let labels = [("chain", chain), ("method", method.method.as_str())];
+				requests.sample(bridge, &labels, &method.requests);
+				errors.sample(bridge, &labels, &method.errors);
+				timeouts.sample(bridge, &labels, &method.timeouts);
+			}
+		}
+	}
+
+	let families = vec![heads, checkpoints, lags, relays, failures, gas_used, fees, balances, gas_prices, requests, errors, timeouts];
+	let mut lines = families.into_iter().flat_map(|family| family.lines).collect::<Vec<_>>();
+	// the format requires the last line to end with a line feed
+	lines.push(String::new());
+	lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+	use std::time::{Duration, Instant};
+	use database::{Chain, RelayOutcome, ProcessedEvent};
+	use super::{RequestMetrics, GasPriceMetrics, GasPriceSnapshot, BridgeMetrics, Metrics, Outcome, percentile, prometheus};
+
+	#[test]
+	fn test_percentile() {
+		let latencies = (1..101).map(Duration::from_millis).collect::<Vec<_>>();
+		assert_eq!(None, percentile(&[], 50));
+		assert_eq!(Some(Duration::from_millis(50)), percentile(&latencies, 50));
+		assert_eq!(Some(Duration::from_millis(99)), percentile(&latencies, 99));
+		assert_eq!(Some(Duration::from_millis(7)), percentile(&latencies[6..7], 90));
+	}
+
+	#[test]
+	fn test_request_metrics_count_outcomes_per_method() {
+		let metrics = RequestMetrics::default();
+		metrics.record("eth_getLogs", Outcome::Success, Duration::from_millis(30));
+		metrics.record("eth_getLogs", Outcome::Timeout, Duration::from_secs(5));
+		metrics.record("eth_blockNumber", Outcome::Error, Duration::from_millis(10));
+		metrics.record("eth_getLogs", Outcome::Success, Duration::from_millis(10));
+
+		let snapshot = metrics.snapshot();
+		assert_eq!(4, metrics.total_requests());
+		assert_eq!(2, snapshot.len());
+		assert_eq!("eth_blockNumber", snapshot[0].method);
+		assert_eq!((1, 1, 0), (snapshot[0].requests, snapshot[0].errors, snapshot[0].timeouts));
+		assert_eq!("eth_getLogs", snapshot[1].method);
+		assert_eq!((3, 0, 1), (snapshot[1].requests, snapshot[1].errors, snapshot[1].timeouts));
+		// timeouts are not included in latency
+		assert_eq!(Some(Duration::from_millis(10)), snapshot[1].p50);
+		assert_eq!(Some(Duration::from_millis(30)), snapshot[1].p99);
+	}
+
+	#[test]
+	fn test_gas_price_metrics_count_deferrals() {
+		let metrics = GasPriceMetrics::default();
+		assert_eq!(GasPriceSnapshot::default(), metrics.snapshot());
+
+		metrics.record(30, true);
+		metrics.record(40, true);
+		metrics.record(10, false);
+		metrics.record(50, true);
+		assert_eq!(GasPriceSnapshot { gas_price: Some(50), deferring: true, deferrals: 2 }, metrics.snapshot());
+		assert_eq!("gas price 50 wei, 2 deferrals, deferring relays", metrics.snapshot().to_string());
+	}
+
+	#[test]
+	fn test_prometheus() {
+		let metrics = Metrics::default();
+		metrics.bridge.record_head(Chain::Mainnet, 110);
+		metrics.bridge.record_checkpoint("deposit_relay", Chain::Mainnet, 90);
+		metrics.bridge.update_checkpoint("deposit_relay", 100);
+		// checkpoints of the components, which are not recorded, are not updated
+		metrics.bridge.update_checkpoint("withdraw_relay", 100);
+		metrics.bridge.record_checkpoint("withdraw_confirm", Chain::Testnet, 50);
+		let relay = RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: 1.into(),
+			gas_price: 10,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: 2.into(),
+				log_index: 0,
+			},
+		};
+		metrics.bridge.record_relays("deposit_relay", &[relay, relay]);
+		metrics.bridge.record_fees("deposit_relay", Chain::Testnet, 21_000, 210_000);
+		metrics.bridge.record_balance(Chain::Testnet, 0xa.into(), 1_000_000);
+		metrics.testnet.record("eth_getLogs", Outcome::Error, Duration::from_millis(10));
+
+		let expected = "\
+# HELP bridge_head_block Best block of the chain.
+# TYPE bridge_head_block gauge
+bridge_head_block{bridge=\"first\",chain=\"mainnet\"} 110
+# HELP bridge_checkpoint_block Last block checked by the bridge component.
+# TYPE bridge_checkpoint_block gauge
+bridge_checkpoint_block{bridge=\"first\",stream=\"deposit_relay\",chain=\"mainnet\"} 100
+bridge_checkpoint_block{bridge=\"first\",stream=\"withdraw_confirm\",chain=\"testnet\"} 50
+# HELP bridge_lag_blocks Blocks between the checkpoint of the bridge component and the best block of the chain it reads events from.
+# TYPE bridge_lag_blocks gauge
+bridge_lag_blocks{bridge=\"first\",stream=\"deposit_relay\",chain=\"mainnet\"} 10
+# HELP bridge_relays_total Relay transactions submitted by the bridge component.
+# TYPE bridge_relays_total counter
+bridge_relays_total{bridge=\"first\",stream=\"deposit_relay\",chain=\"testnet\"} 2
+# HELP bridge_relay_failures_total Relay transactions of the bridge component, which have been reverted or which have expired.
+# TYPE bridge_relay_failures_total counter
+bridge_relay_failures_total{bridge=\"first\",stream=\"deposit_relay\",chain=\"testnet\"} 0
+# HELP bridge_relay_gas_used_total Gas used by the mined relay transactions of the bridge component.
+# TYPE bridge_relay_gas_used_total counter
+bridge_relay_gas_used_total{bridge=\"first\",stream=\"deposit_relay\",chain=\"testnet\"} 21000
+# HELP bridge_relay_fees_wei_total Fees paid for the mined relay transactions of the bridge component.
+# TYPE bridge_relay_fees_wei_total counter
+bridge_relay_fees_wei_total{bridge=\"first\",stream=\"deposit_relay\",chain=\"testnet\"} 210000
+# HELP bridge_balance_wei Balance of the authority account, if `min_balance` is configured.
+# TYPE bridge_balance_wei gauge
+bridge_balance_wei{bridge=\"first\",chain=\"testnet\",account=\"0x000000000000000000000000000000000000000a\"} 1000000
+# HELP bridge_gas_price_wei Gas price of the chain, if `max_gas_price` is configured.
+# TYPE bridge_gas_price_wei gauge
+# HELP bridge_rpc_requests_total Requests sent to the node.
+# TYPE bridge_rpc_requests_total counter
+bridge_rpc_requests_total{bridge=\"first\",chain=\"testnet\",method=\"eth_getLogs\"} 1
+# HELP bridge_rpc_errors_total Requests, which the node has failed.
+# TYPE bridge_rpc_errors_total counter
+bridge_rpc_errors_total{bridge=\"first\",chain=\"testnet\",method=\"eth_getLogs\"} 1
+# HELP bridge_rpc_timeouts_total Requests, which the node has not answered in time.
+# TYPE bridge_rpc_timeouts_total counter
+bridge_rpc_timeouts_total{bridge=\"first\",chain=\"testnet\",method=\"eth_getLogs\"} 0
+";
+		assert_eq!(expected, prometheus(&[(Some("first".into()), metrics.clone())]));
+		// bridges without namespace are not labelled
+		assert!(prometheus(&[(None, metrics)]).contains("bridge_head_block{chain=\"mainnet\"} 110\n"));
+	}
+
+	#[test]
+	fn test_stale_streams() {
+		let metrics = BridgeMetrics::default();
+		let staleness = Duration::from_secs(600);
+		metrics.record_checkpoint("deposit_relay", Chain::Mainnet, 10);
+		metrics.record_checkpoint("withdraw_confirm", Chain::Testnet, 10);
+		assert!(metrics.stale_streams(Instant::now(), staleness).is_empty());
+		assert_eq!(vec!["deposit_relay", "withdraw_confirm"], metrics.stale_streams(Instant::now() + staleness, staleness));
+
+		thread::sleep(Duration::from_millis(10));
+		let checked = Instant::now();
+		metrics.update_checkpoint("withdraw_confirm", 11);
+		assert_eq!(vec!["deposit_relay"], metrics.stale_streams(checked + staleness - Duration::from_millis(5), staleness));
+	}
+}
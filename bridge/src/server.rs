@@ -0,0 +1,134 @@
+//! HTTP server exposing the metrics of the bridges to Prometheus, and their health and readiness to the supervisor.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use futures::{Future, Stream, future};
+use hyper::{self, Method, StatusCode};
+use hyper::header::{ContentLength, ContentType};
+use hyper::server::{Http, Request, Response, Service};
+use tokio_core::reactor::Handle;
+use error::{Error, ResultExt};
+use metrics::{Metrics, prometheus};
+
+/// Bridges served by the server, shared with the bridges being started.
+#[derive(Clone, Default)]
+pub struct Registry {
+	/// Metrics of the bridges with their namespaces and staleness windows.
+	bridges: Arc<Mutex<Vec<(Option<String>, Metrics, Duration)>>>,
+	/// Set once every bridge has been started.
+	ready: Arc<AtomicBool>,
+}
+
+impl Registry {
+	/// Serves the metrics and the health of the started bridge.
+	pub fn register(&self, namespace: Option<String>, metrics: Metrics, staleness: Duration) {
+		self.bridges.lock().expect("lock is never poisoned; qed").push((namespace, metrics, staleness));
+	}
+
+	/// Reports that the database of every bridge is loaded and both chains are reachable.
+	pub fn set_ready(&self) {
+		self.ready.store(true, Ordering::SeqCst);
+	}
+
+	fn metrics(&self) -> String {
+		let bridges = self.bridges.lock().expect("lock is never poisoned; qed").iter()
+			.map(|&(ref namespace, ref metrics, _)| (namespace.clone(), metrics.clone()))
+			.collect::<Vec<_>>();
+		prometheus(&bridges)
+	}
+
+	/// Returns the components, which haven't checked a new block within the staleness window of their bridge.
+	fn stale(&self, now: Instant) -> Vec<String> {
+		let bridges = self.bridges.lock().expect("lock is never poisoned; qed");
+		bridges.iter()
+			.flat_map(|&(ref namespace, ref metrics, staleness)| metrics.bridge.stale_streams(now, staleness).into_iter()
+				.map(move |stream| match *namespace {
+					Some(ref namespace) => format!("{} {}", namespace, stream),
+					None => stream.to_owned(),
+				}))
+			.collect()
+	}
+}
+
+fn text(status: StatusCode, body: String) -> Response {
+	Response::new()
+		.with_status(status)
+		.with_header(ContentType::plaintext())
+		.with_header(ContentLength(body.len() as u64))
+		.with_body(body)
+}
+
+/// Answers the requests of the server.
+#[derive(Clone)]
+struct Endpoints {
+	registry: Registry,
+}
+
+impl Service for Endpoints {
+	type Request = Request;
+	type Response = Response;
+	type Error = hyper::Error;
+	type Future = future::FutureResult<Response, hyper::Error>;
+
+	fn call(&self, request: Request) -> Self::Future {
+		let response = match (request.method(), request.path()) {
+			(&Method::Get, "/metrics") => text(StatusCode::Ok, self.registry.metrics()),
+			(&Method::Get, "/health") => {
+				let stale = self.registry.stale(Instant::now());
+				if stale.is_empty() {
+					text(StatusCode::Ok, "OK\n".into())
+				} else {
+					text(StatusCode::ServiceUnavailable, format!("Stale: {}\n", stale.join(", ")))
+				}
+			},
+			(&Method::Get, "/ready") if self.registry.ready.load(Ordering::SeqCst) => text(StatusCode::Ok, "OK\n".into()),
+			(&Method::Get, "/ready") => text(StatusCode::ServiceUnavailable, "Starting\n".into()),
+			_ => Response::new().with_status(StatusCode::NotFound),
+		};
+		future::ok(response)
+	}
+}
+
+/// Serves the bridges of `registry` at `address` until the event loop of `handle` stops:
+/// their metrics under `/metrics`, their health under `/health` and whether they have started under `/ready`.
+pub fn serve(address: &SocketAddr, handle: &Handle, registry: Registry) -> Result<(), Error> {
+	let endpoints = Endpoints {
+		registry,
+	};
+	let connections = Http::new()
+		.serve_addr_handle(address, handle, move || Ok(endpoints.clone()))
+		.chain_err(|| format!("Cannot serve metrics at {}", address))?;
+
+	let spawner = handle.clone();
+	let server = connections
+		.for_each(move |connection| {
+			spawner.spawn(connection.map(|_| ()).map_err(|err| debug!(target: "bridge", "Connection to the metrics server failed: {}", err)));
+			Ok(())
+		})
+		.map_err(|err| warn!(target: "bridge", "Metrics server stopped: {}", err));
+	handle.spawn(server);
+	info!(target: "bridge", "Serving metrics at http://{}/metrics, health at /health and readiness at /ready", address);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use database::Chain;
+	use metrics::Metrics;
+	use super::Registry;
+
+	#[test]
+	fn test_registry_reports_stale_components() {
+		let registry = Registry::default();
+		let metrics = Metrics::default();
+		metrics.bridge.record_checkpoint("deposit_relay", Chain::Mainnet, 10);
+		registry.register(Some("a".into()), metrics, Duration::from_secs(60));
+		registry.register(None, Metrics::default(), Duration::from_secs(60));
+
+		assert!(registry.stale(Instant::now()).is_empty());
+		assert_eq!(vec!["a deposit_relay".to_owned()], registry.stale(Instant::now() + Duration::from_secs(60)));
+	}
+}
@@ -0,0 +1,376 @@
+//! Signing with a secp256k1 key held by AWS KMS, which never leaves the service.
+//!
+//! Hashes are signed with `ECDSA_SHA_256` by the `Sign` action, authenticated with AWS Signature Version 4,
+//! along with the session token of temporary credentials, e.g. of an IAM role.
+//! Requests are handled one at a time by a dedicated thread with its own event loop.
+//! KMS doesn't return the recovery id, so it's found by recovering the address of the account,
+//! which verifies that the key belongs to it.
+
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::{Future, Stream, future};
+use hyper::{self, Client, Method, Uri};
+use hyper::header::ContentLength;
+use openssl::base64;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign;
+use secp256k1;
+use serde_json;
+use tokio_core::reactor::Core;
+use tokio_timer::Timer;
+use web3::types::Address;
+use error::{Error, ErrorKind, ResultExt};
+use transport::HttpsConnector;
+use super::{Signature, Request, recovery_id};
+
+const SERVICE: &str = "kms";
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+const SIGN_TARGET: &str = "TrentService.Sign";
+
+/// Credentials of the AWS principal, which is allowed to sign with the key.
+#[derive(PartialEq, Clone)]
+pub struct AwsCredentials {
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	/// Required by temporary credentials.
+	pub session_token: Option<String>,
+}
+
+impl fmt::Debug for AwsCredentials {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// the secret must never end up in the logs
+		f.debug_struct("AwsCredentials")
+			.field("access_key_id", &self.access_key_id)
+			.finish()
+	}
+}
+
+/// Asymmetric `ECC_SECG_P256K1` key of AWS KMS holding the key of the account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KmsSigner {
+	/// Id, alias or ARN of the key.
+	pub key_id: String,
+	pub region: String,
+	/// Either `http://` or `https://` url, e.g. of a VPC endpoint.
+	pub endpoint: String,
+	pub credentials: AwsCredentials,
+	/// Signatures, which are not made within the timeout, fail.
+	pub timeout: Duration,
+}
+
+impl KmsSigner {
+	/// Public endpoint of KMS in `region`.
+	pub fn default_endpoint(region: &str) -> String {
+		format!("https://kms.{}.amazonaws.com", region)
+	}
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SignRequest<'a> {
+	key_id: &'a str,
+	/// Base64 encoded hash.
+	message: String,
+	message_type: &'static str,
+	signing_algorithm: &'static str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SignResponse {
+	/// Base64 encoded DER signature.
+	signature: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Result<Vec<u8>, Error> {
+	let key = PKey::hmac(key).chain_err(|| "Cannot create hmac key")?;
+	let mut signer = sign::Signer::new(MessageDigest::sha256(), &key).chain_err(|| "Cannot create hmac")?;
+	signer.update(data.as_bytes()).chain_err(|| "Cannot compute hmac")?;
+	signer.sign_to_vec().chain_err(|| "Cannot compute hmac")
+}
+
+/// Formats the unix `timestamp` as `YYYYMMDD'T'HHMMSS'Z'`.
+fn amz_date(timestamp: u64) -> String {
+	let days = (timestamp / 86400) as i64;
+	let seconds = timestamp % 86400;
+	// civil date of the days since the epoch, in the proleptic Gregorian calendar
+	let z = days + 719_468;
+	let era = z / 146_097;
+	let day_of_era = z - era * 146_097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+/// Returns headers of the request of `target` with `body` sent to `host` at `date`,
+/// including the `Authorization` header of AWS Signature Version 4.
+fn signed_headers(credentials: &AwsCredentials, region: &str, host: &str, target: &str, body: &str, date: &str) -> Result<Vec<(&'static str, String)>, Error> {
+	let mut headers = vec![
+		("content-type", CONTENT_TYPE.to_owned()),
+		("host", host.to_owned()),
+		("x-amz-date", date.to_owned()),
+	];
+	if let Some(ref token) = credentials.session_token {
+		headers.push(("x-amz-security-token", token.clone()));
+	}
+	// headers are signed in alphabetical order
+	headers.push(("x-amz-target", target.to_owned()));
+
+	let names = headers.iter().map(|&(name, _)| name).collect::<Vec<_>>().join(";");
+	let canonical_headers = headers.iter().map(|&(name, ref value)| format!("{}:{}\n", name, value)).collect::<String>();
+	let canonical_request = format!("POST\n/\n\n{}\n{}\n{}", canonical_headers, names, hex(&sha256(body.as_bytes())));
+
+	let scope = format!("{}/{}/{}/aws4_request", &date[..8], region, SERVICE);
+	let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", date, scope, hex(&sha256(canonical_request.as_bytes())));
+
+	let key = hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), &date[..8])?;
+	let key = hmac_sha256(&key, region)?;
+	let key = hmac_sha256(&key, SERVICE)?;
+	let key = hmac_sha256(&key, "aws4_request")?;
+	let signature = hex(&hmac_sha256(&key, &string_to_sign)?);
+
+	headers.push(("authorization", format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", credentials.access_key_id, scope, names, signature)));
+	Ok(headers)
+}
+
+/// Returns the compact signature of the DER encoded one. Ethereum accepts only signatures with low `s`.
+fn compact_signature(der: &[u8]) -> Result<[u8; 64], Error> {
+	let mut signature = secp256k1::Signature::from_der(der).map_err(|_| "KMS has returned invalid signature")?;
+	signature.normalize_s();
+	Ok(signature.serialize_compact())
+}
+
+/// KMS key holding the key of `address`.
+struct Kms {
+	signer: KmsSigner,
+	address: Address,
+	uri: Uri,
+	core: Core,
+	timer: Timer,
+	client: Client<HttpsConnector>,
+}
+
+impl Kms {
+	fn new(signer: KmsSigner, address: Address) -> Result<Self, Error> {
+		let uri: Uri = signer.endpoint.parse().chain_err(|| format!("Invalid KMS endpoint {}", signer.endpoint))?;
+		match uri.scheme() {
+			Some("http") | Some("https") => {},
+			_ => return Err(format!("Unsupported KMS endpoint {}", signer.endpoint).into()),
+		}
+
+		let core = Core::new().chain_err(|| "Cannot start the event loop of the KMS signer")?;
+		let client = Client::configure()
+			.connector(HttpsConnector::new(None, &[], &core.handle())?)
+			.build(&core.handle());
+		let result = Kms {
+			signer,
+			address,
+			uri,
+			core,
+			timer: Timer::default(),
+			client,
+		};
+		Ok(result)
+	}
+
+	fn host(&self) -> String {
+		let host = self.uri.host().unwrap_or_default();
+		match self.uri.port() {
+			Some(port) => format!("{}:{}", host, port),
+			None => host.to_owned(),
+		}
+	}
+
+	fn call(&mut self, target: &str, body: String) -> Result<Vec<u8>, Error> {
+		let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+		let headers = signed_headers(&self.signer.credentials, &self.signer.region, &self.host(), target, &body, &amz_date(timestamp))?;
+
+		let mut request = hyper::Request::new(Method::Post, self.uri.clone());
+		for (name, value) in headers {
+			request.headers_mut().set_raw(name, value);
+		}
+		request.headers_mut().set(ContentLength(body.len() as u64));
+		request.set_body(body);
+
+		let call = self.client.request(request)
+			.and_then(|response| {
+				let status = response.status();
+				response.body().concat2().map(move |body| (status, body))
+			})
+			.map_err(|err| Error::from(format!("KMS request has failed: {}", err)))
+			.and_then(|(status, body)| {
+				if !status.is_success() {
+					return future::err(format!("KMS has responded with {}: {}", status, String::from_utf8_lossy(&body)).into());
+				}
+				future::ok(body.to_vec())
+			});
+		let timeout = self.timer.sleep(self.signer.timeout)
+			.then(|_| -> Result<Vec<u8>, Error> { Err(ErrorKind::Timeout("KMS signature").into()) });
+		self.core.run(call.select(timeout).map(|(result, _)| result).map_err(|(err, _)| err))
+	}
+
+	fn sign(&mut self, hash: &[u8; 32]) -> Result<Signature, Error> {
+		let request = SignRequest {
+			key_id: &self.signer.key_id,
+			message: base64::encode_block(hash),
+			message_type: "DIGEST",
+			signing_algorithm: "ECDSA_SHA_256",
+		};
+		let body = serde_json::to_string(&request).expect("request is serializable; qed");
+		info!(target: "bridge", "Requesting signature from KMS key {}", self.signer.key_id);
+
+		let response = self.call(SIGN_TARGET, body)?;
+		let response: SignResponse = serde_json::from_slice(&response)
+			.map_err(|err| format!("Unexpected response of KMS: {}", err))?;
+		let der = base64::decode_block(&response.signature)
+			.map_err(|_| "KMS has returned invalid signature")?;
+		let signature = compact_signature(&der)?;
+		Ok((recovery_id(hash, &signature, self.address)?, signature))
+	}
+}
+
+/// Starts the thread signing the requests with the KMS key of `address`.
+pub fn spawn(signer: KmsSigner, address: Address) -> Result<mpsc::Sender<Request>, Error> {
+	let (sender, receiver) = mpsc::channel::<Request>();
+	thread::Builder::new()
+		.name("kms-signer".into())
+		.spawn(move || {
+			let mut kms = match Kms::new(signer, address) {
+				Ok(kms) => kms,
+				Err(err) => {
+					error!(target: "bridge", "Cannot start the KMS signer: {}", err);
+					return;
+				},
+			};
+
+			for request in receiver {
+				let result = kms.sign(&request.hash);
+				if let Err(ref err) = result {
+					error!(target: "bridge", "KMS has failed to sign: {}", err);
+				}
+				if request.response.send(result).is_err() {
+					warn!(target: "bridge", "KMS signature has been made after the timeout and is discarded");
+				}
+			}
+		})
+		.chain_err(|| "Cannot start the KMS signer thread")?;
+	Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+	use std::net::TcpListener;
+	use std::thread;
+	use std::time::Duration;
+	use futures::Future;
+	use openssl::base64;
+	use secp256k1::{Secp256k1, Message, SecretKey};
+	use tokio_timer::Timer;
+	use transaction::FeeModel;
+	use super::super::Signer;
+	use super::{KmsSigner, AwsCredentials, amz_date, signed_headers};
+
+	/// Responds to a single request with the `signature` and returns the request.
+	fn serve_once(listener: TcpListener, signature: Vec<u8>) -> thread::JoinHandle<String> {
+		thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = Vec::new();
+			let mut buffer = [0u8; 1024];
+			while !request.ends_with(b"}") {
+				let read = stream.read(&mut buffer).unwrap();
+				request.extend_from_slice(&buffer[..read]);
+			}
+
+			let response = format!(r#"{{"KeyId":"alias/bridge","Signature":"{}","SigningAlgorithm":"ECDSA_SHA_256"}}"#, base64::encode_block(&signature));
+			write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/x-amz-json-1.1\r\nContent-Length: {}\r\n\r\n{}", response.len(), response).unwrap();
+			String::from_utf8(request).unwrap()
+		})
+	}
+
+	fn credentials() -> AwsCredentials {
+		AwsCredentials {
+			access_key_id: "AKIDEXAMPLE".into(),
+			secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+			session_token: None,
+		}
+	}
+
+	#[test]
+	fn test_amz_date() {
+		assert_eq!("19700101T000000Z", amz_date(0));
+		assert_eq!("20170714T024000Z", amz_date(1_500_000_000));
+		assert_eq!("20240229T235959Z", amz_date(1_709_251_199));
+	}
+
+	#[test]
+	fn test_signed_headers() {
+		let headers = signed_headers(&credentials(), "us-east-1", "kms.us-east-1.amazonaws.com", "TrentService.Sign", "{}", "20150830T123600Z").unwrap();
+		assert_eq!(("authorization", "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/kms/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-target, Signature=024bbd3018256514009812d412dceaf10b4ae10f123a299c3fe53dd6c092edb1".to_owned()), headers[4]);
+		assert_eq!("AwsCredentials { access_key_id: \"AKIDEXAMPLE\" }", format!("{:?}", credentials()));
+	}
+
+	#[test]
+	fn test_signed_headers_with_session_token() {
+		let temporary = AwsCredentials {
+			session_token: Some("FQoGZXIvYXdzEXAMPLE".into()),
+			..credentials()
+		};
+		let headers = signed_headers(&temporary, "us-east-1", "kms.us-east-1.amazonaws.com", "TrentService.Sign", "{}", "20150830T123600Z").unwrap();
+		assert_eq!(("x-amz-security-token", "FQoGZXIvYXdzEXAMPLE".to_owned()), headers[3]);
+		// the token is signed along with the other headers
+		assert_eq!("authorization", headers[5].0);
+		assert!(headers[5].1.contains("SignedHeaders=content-type;host;x-amz-date;x-amz-security-token;x-amz-target,"));
+		let permanent = signed_headers(&credentials(), "us-east-1", "kms.us-east-1.amazonaws.com", "TrentService.Sign", "{}", "20150830T123600Z").unwrap();
+		assert!(headers[5].1 != permanent[4].1);
+		assert!(!format!("{:?}", temporary).contains("FQoGZXIvYXdzEXAMPLE"));
+	}
+
+	#[test]
+	fn test_kms_signer() {
+		let secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+		let local = Signer::new(&[1u8; 32], 42).unwrap();
+		let timer = Timer::default();
+		let transaction = FeeModel::Legacy.transaction(local.address, Some(1.into()), vec![2].into(), 100_000, 1_000_000_000);
+		let raw = local.sign_transaction(&timer, &transaction, 3.into()).wait().unwrap();
+		let hash = super::super::keccak256(&local.unsigned_transaction(&transaction, 3.into()));
+		let der = Secp256k1::signing_only().sign(&Message::from_slice(&hash).unwrap(), &secret).serialize_der().to_vec();
+
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let kms = KmsSigner {
+			key_id: "alias/bridge".into(),
+			region: "us-east-1".into(),
+			endpoint: format!("http://{}", listener.local_addr().unwrap()),
+			credentials: credentials(),
+			timeout: Duration::from_secs(5),
+		};
+		let signer = Signer::kms(local.address, kms.clone(), 42).unwrap();
+		let request = serve_once(listener, der.clone());
+		assert_eq!(raw, signer.sign_transaction(&timer, &transaction, 3.into()).wait().unwrap());
+		let request = request.join().unwrap();
+		assert!(request.contains("x-amz-target: TrentService.Sign"));
+		assert!(request.contains(&format!(r#""Message":"{}""#, base64::encode_block(&hash))));
+
+		// signature of another key is rejected
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let other = Signer::new(&[2u8; 32], 42).unwrap();
+		let signer = Signer::kms(other.address, KmsSigner {
+			endpoint: format!("http://{}", listener.local_addr().unwrap()),
+			..kms
+		}, 42).unwrap();
+		serve_once(listener, der);
+		assert!(signer.sign_transaction(&timer, &transaction, 3.into()).wait().is_err());
+	}
+}
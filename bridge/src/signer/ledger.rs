@@ -0,0 +1,353 @@
+//! Signing with the Ethereum app of a Ledger connected over USB, using the Linux `hidraw` interface.
+//!
+//! Requests are handled one at a time by a dedicated thread, since the device blocks until
+//! the user confirms or rejects the signature, which must not stall the event loop.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use web3::types::Address;
+use error::{Error, ResultExt};
+use super::{Payload, Signature, Request, public_address, recovery_id};
+
+/// Size of the HID report.
+const PACKET_SIZE: usize = 64;
+const CHANNEL: u16 = 0x0101;
+const TAG_APDU: u8 = 0x05;
+/// Maximum size of the data of a single APDU.
+const CHUNK_SIZE: usize = 255;
+const LEDGER_VENDOR_ID: &str = ":00002C97:";
+
+const CLA: u8 = 0xe0;
+const INS_GET_ADDRESS: u8 = 0x02;
+const INS_SIGN_TRANSACTION: u8 = 0x04;
+const INS_SIGN_MESSAGE: u8 = 0x08;
+const P1_FIRST_CHUNK: u8 = 0x00;
+const P1_NEXT_CHUNK: u8 = 0x80;
+
+const STATUS_OK: u16 = 0x9000;
+const STATUS_REJECTED: u16 = 0x6985;
+
+/// Parses BIP-32 derivation path, e.g. `m/44'/60'/0'/0/0`.
+pub fn parse_derivation_path(path: &str) -> Result<Vec<u32>, Error> {
+	let invalid = || -> Error { format!("Invalid derivation path `{}`", path).into() };
+	let mut parts = path.split('/');
+	if parts.next() != Some("m") {
+		return Err(invalid());
+	}
+
+	let result = parts
+		.map(|part| {
+			let (index, hardened) = match part.strip_suffix('\'') {
+				Some(index) => (index, true),
+				None => (part, false),
+			};
+			match index.parse::<u32>() {
+				Ok(index) if index < 0x8000_0000 => Ok(if hardened { index | 0x8000_0000 } else { index }),
+				_ => Err(invalid()),
+			}
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+
+	if result.is_empty() || result.len() > 10 {
+		return Err(invalid());
+	}
+	Ok(result)
+}
+
+fn encode_path(path: &[u32]) -> Vec<u8> {
+	let mut result = vec![path.len() as u8];
+	for index in path {
+		result.extend_from_slice(&index.to_be_bytes());
+	}
+	result
+}
+
+/// Splits `data` into APDUs of instruction `ins`, sent one after another.
+fn chunked_apdus(ins: u8, data: &[u8]) -> Vec<Vec<u8>> {
+	data.chunks(CHUNK_SIZE)
+		.enumerate()
+		.map(|(index, chunk)| {
+			let p1 = if index == 0 { P1_FIRST_CHUNK } else { P1_NEXT_CHUNK };
+			let mut apdu = vec![CLA, ins, p1, 0x00, chunk.len() as u8];
+			apdu.extend_from_slice(chunk);
+			apdu
+		})
+		.collect()
+}
+
+/// Splits `apdu` into HID packets. The first one is prefixed with the length of the `apdu`.
+fn frame_apdu(apdu: &[u8]) -> Vec<[u8; PACKET_SIZE]> {
+	let mut data = (apdu.len() as u16).to_be_bytes().to_vec();
+	data.extend_from_slice(apdu);
+	data.chunks(PACKET_SIZE - 5)
+		.enumerate()
+		.map(|(sequence, chunk)| {
+			let mut packet = [0u8; PACKET_SIZE];
+			packet[..2].copy_from_slice(&CHANNEL.to_be_bytes());
+			packet[2] = TAG_APDU;
+			packet[3..5].copy_from_slice(&(sequence as u16).to_be_bytes());
+			packet[5..5 + chunk.len()].copy_from_slice(chunk);
+			packet
+		})
+		.collect()
+}
+
+/// Response assembled from HID packets.
+#[derive(Default)]
+struct Response {
+	length: Option<usize>,
+	data: Vec<u8>,
+	sequence: u16,
+}
+
+impl Response {
+	/// Appends the `packet`, returning the response data once complete.
+	fn push(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		if packet.len() < 7 || packet[..2] != CHANNEL.to_be_bytes() || packet[2] != TAG_APDU {
+			return Err("Unexpected packet received from the Ledger".into());
+		}
+		if packet[3..5] != self.sequence.to_be_bytes() {
+			return Err("Packet received from the Ledger out of sequence".into());
+		}
+		self.sequence += 1;
+
+		let data = match self.length {
+			None => {
+				self.length = Some(usize::from(u16::from_be_bytes([packet[5], packet[6]])));
+				&packet[7..]
+			},
+			Some(_) => &packet[5..],
+		};
+		self.data.extend_from_slice(data);
+
+		let length = self.length.expect("length is read from the first packet; qed");
+		if self.data.len() < length {
+			return Ok(None);
+		}
+		self.data.truncate(length);
+		Ok(Some(self.data.split_off(0)))
+	}
+}
+
+/// Splits the status word off the response of the app.
+fn check_status(mut response: Vec<u8>) -> Result<Vec<u8>, Error> {
+	if response.len() < 2 {
+		return Err("Response of the Ledger is too short".into());
+	}
+	let data_len = response.len() - 2;
+	let status = u16::from_be_bytes([response[data_len], response[data_len + 1]]);
+	response.truncate(data_len);
+	match status {
+		STATUS_OK => Ok(response),
+		STATUS_REJECTED => Err("Signature has been rejected on the Ledger".into()),
+		0x6d00 | 0x6e00 | 0x6e01 => Err("Ethereum app is not open on the Ledger".into()),
+		0x5515 => Err("Ledger is locked".into()),
+		status => Err(format!("Ledger responded with status 0x{:04x}", status).into()),
+	}
+}
+
+/// Finds the `hidraw` device of the first Ledger connected.
+fn find_device() -> Result<PathBuf, Error> {
+	let mut devices = fs::read_dir("/sys/class/hidraw")
+		.chain_err(|| "Cannot list hidraw devices")?
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.file_name())
+		.collect::<Vec<_>>();
+	devices.sort();
+
+	devices.into_iter()
+		.find(|name| {
+			let uevent = fs::read_to_string(Path::new("/sys/class/hidraw").join(name).join("device/uevent")).unwrap_or_default();
+			// the generic interface of the Ledger is the first one
+			uevent.lines().any(|line| line.starts_with("HID_ID=") && line.contains(LEDGER_VENDOR_ID)) &&
+				uevent.lines().any(|line| line.starts_with("HID_PHYS=") && line.ends_with("input0"))
+		})
+		.map(|name| Path::new("/dev").join(name))
+		.ok_or_else(|| "No Ledger is connected".into())
+}
+
+struct Device {
+	file: fs::File,
+}
+
+impl Device {
+	fn open(path: &Path) -> Result<Self, Error> {
+		let file = fs::OpenOptions::new().read(true).write(true).open(path)
+			.chain_err(|| format!("Cannot open Ledger {:?}", path))?;
+		Ok(Device { file })
+	}
+
+	fn exchange(&mut self, apdu: &[u8]) -> Result<Vec<u8>, Error> {
+		for packet in frame_apdu(apdu) {
+			// the report number is 0, since the device doesn't number its reports
+			let mut report = [0u8; PACKET_SIZE + 1];
+			report[1..].copy_from_slice(&packet);
+			self.file.write_all(&report)?;
+		}
+
+		let mut response = Response::default();
+		loop {
+			let mut packet = [0u8; PACKET_SIZE];
+			let read = self.file.read(&mut packet)?;
+			if let Some(data) = response.push(&packet[..read])? {
+				return check_status(data);
+			}
+		}
+	}
+
+	/// Exchanges `apdus` one after another, returning the response to the last one.
+	fn exchange_all(&mut self, apdus: Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
+		let mut response = Vec::new();
+		for apdu in apdus {
+			response = self.exchange(&apdu)?;
+		}
+		Ok(response)
+	}
+
+	fn address(&mut self, path: &[u32]) -> Result<Address, Error> {
+		let encoded = encode_path(path);
+		let mut apdu = vec![CLA, INS_GET_ADDRESS, 0x00, 0x00, encoded.len() as u8];
+		apdu.extend_from_slice(&encoded);
+		let response = self.exchange(&apdu)?;
+		// public key is prefixed with its length
+		match response.first() {
+			Some(&65) if response.len() > 65 => Ok(public_address(&response[1..66])),
+			_ => Err("Unexpected public key received from the Ledger".into()),
+		}
+	}
+}
+
+/// Ledger holding the key of `address` derived along `path`.
+struct Ledger {
+	path: Vec<u32>,
+	/// Device path, the first Ledger connected is used if `None`.
+	device_path: Option<PathBuf>,
+	address: Address,
+	/// Device, which holds the key of `address`.
+	device: Option<Device>,
+}
+
+impl Ledger {
+	fn device(&mut self) -> Result<&mut Device, Error> {
+		if self.device.is_none() {
+			let path = match self.device_path {
+				Some(ref path) => path.clone(),
+				None => find_device()?,
+			};
+			let mut device = Device::open(&path)?;
+			let address = device.address(&self.path)?;
+			if address != self.address {
+				return Err(format!("Ledger {:?} holds the key of {:?}, not of the account {:?}", path, address, self.address).into());
+			}
+			info!(target: "bridge", "Signing with Ledger {:?}", path);
+			self.device = Some(device);
+		}
+		Ok(self.device.as_mut().expect("device has just been opened; qed"))
+	}
+
+	fn sign(&mut self, payload: &Payload, data: &[u8], hash: &[u8; 32]) -> Result<Signature, Error> {
+		let mut encoded = encode_path(&self.path);
+		let apdus = match *payload {
+			Payload::Transaction(_) => {
+				warn!(target: "bridge", "Confirm the transaction from {:?} on the Ledger", self.address);
+				encoded.extend_from_slice(data);
+				chunked_apdus(INS_SIGN_TRANSACTION, &encoded)
+			},
+			Payload::Message => {
+				warn!(target: "bridge", "Confirm the message signed by {:?} on the Ledger", self.address);
+				encoded.extend_from_slice(&(data.len() as u32).to_be_bytes());
+				encoded.extend_from_slice(data);
+				chunked_apdus(INS_SIGN_MESSAGE, &encoded)
+			},
+		};
+
+		let address = self.address;
+		let result = self.device().and_then(|device| device.exchange_all(apdus));
+		if result.is_err() {
+			// the device is opened again, since it may have been disconnected
+			self.device = None;
+		}
+		// `v` is ignored, since its encoding depends on the app version
+		let response = result?;
+		if response.len() != 65 {
+			return Err("Unexpected signature received from the Ledger".into());
+		}
+		let mut signature = [0u8; 64];
+		signature.copy_from_slice(&response[1..]);
+		Ok((recovery_id(hash, &signature, address)?, signature))
+	}
+}
+
+/// Starts the thread signing the requests with the Ledger holding the key of `address`.
+pub fn spawn(path: Vec<u32>, device_path: Option<PathBuf>, address: Address) -> Result<mpsc::Sender<Request>, Error> {
+	let (sender, receiver) = mpsc::channel::<Request>();
+	let mut ledger = Ledger {
+		path,
+		device_path,
+		address,
+		device: None,
+	};
+
+	thread::Builder::new()
+		.name("ledger".into())
+		.spawn(move || for request in receiver {
+			let result = ledger.sign(&request.payload, &request.data, &request.hash);
+			if let Err(ref err) = result {
+				error!(target: "bridge", "Ledger has failed to sign: {}", err);
+			}
+			if request.response.send(result).is_err() {
+				warn!(target: "bridge", "Ledger signature has been confirmed after the timeout and is discarded");
+			}
+		})
+		.chain_err(|| "Cannot start the Ledger thread")?;
+	Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{parse_derivation_path, encode_path, chunked_apdus, frame_apdu, check_status, Response};
+
+	#[test]
+	fn test_parse_derivation_path() {
+		assert_eq!(vec![0x8000_002c, 0x8000_003c, 0x8000_0000, 0, 1], parse_derivation_path("m/44'/60'/0'/0/1").unwrap());
+		assert_eq!(vec![2, 0x80, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x01], encode_path(&[0x8000_002c, 1]));
+		assert!(parse_derivation_path("44'/60'/0'/0/0").is_err());
+		assert!(parse_derivation_path("m").is_err());
+		assert!(parse_derivation_path("m/44''/60'").is_err());
+		assert!(parse_derivation_path("m/2147483648").is_err());
+	}
+
+	#[test]
+	fn test_chunked_apdus() {
+		let data = vec![1u8; 300];
+		let apdus = chunked_apdus(0x04, &data);
+		assert_eq!(2, apdus.len());
+		assert_eq!(vec![0xe0, 0x04, 0x00, 0x00, 0xff], apdus[0][..5].to_vec());
+		assert_eq!(260, apdus[0].len());
+		assert_eq!(vec![0xe0, 0x04, 0x80, 0x00, 45], apdus[1][..5].to_vec());
+		assert_eq!(50, apdus[1].len());
+	}
+
+	#[test]
+	fn test_hid_framing() {
+		let apdu = (0..100u8).collect::<Vec<_>>();
+		let packets = frame_apdu(&apdu);
+		assert_eq!(2, packets.len());
+		assert_eq!([0x01, 0x01, 0x05, 0x00, 0x00, 0x00, 100, 0, 1], packets[0][..9]);
+		assert_eq!([0x01, 0x01, 0x05, 0x00, 0x01, 57, 58], packets[1][..7]);
+
+		// the response is framed the same way
+		let mut response = Response::default();
+		assert_eq!(None, response.push(&packets[0]).unwrap());
+		assert_eq!(Some(apdu.clone()), response.push(&packets[1]).unwrap());
+		let mut response = Response::default();
+		assert!(response.push(&packets[1]).is_err());
+
+		assert_eq!(vec![1, 2], check_status(vec![1, 2, 0x90, 0x00]).unwrap());
+		assert!(check_status(vec![0x69, 0x85]).is_err());
+		assert!(check_status(vec![0x90]).is_err());
+	}
+}
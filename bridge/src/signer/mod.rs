@@ -0,0 +1,589 @@
+//! Local signing of transactions and messages, so that the account doesn't need to be unlocked on the node.
+//!
+//! Legacy transactions are signed according to EIP-155, EIP-1559 ones as type 2 transactions of EIP-2718,
+//! and sent with `eth_sendRawTransaction`.
+//! The key is loaded either as raw hex or from an encrypted keystore (version 3) file,
+//! or it's held by a Ledger, which asks the user to confirm every signature,
+//! or by a remote signer, e.g. Web3Signer or Clef, or by AWS KMS, so that it never enters the bridge process.
+
+mod kms;
+mod ledger;
+mod remote;
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use futures::sync::oneshot;
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::{pbkdf2_hmac, scrypt};
+use openssl::symm::{Cipher, decrypt};
+use rustc_hex::FromHex;
+use secp256k1::{Secp256k1, Message, SecretKey, PublicKey};
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use serde_json;
+use tiny_keccak::Keccak;
+use tokio_timer::{Timer, Timeout};
+use web3::types::{Address, Bytes, H520, U256};
+use error::Error;
+use transaction::{Transaction, Fees};
+use util::signed_message_hash;
+
+pub use self::kms::{KmsSigner, AwsCredentials};
+pub use self::ledger::parse_derivation_path;
+pub use self::remote::{RemoteSigner, RemoteProtocol};
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+	let mut keccak = Keccak::new_keccak256();
+	keccak.update(data);
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+	hash
+}
+
+/// Address of the uncompressed `public` key.
+fn public_address(public: &[u8]) -> Address {
+	Address::from(&keccak256(&public[1..])[12..])
+}
+
+/// Appends the RLP prefix of a string or list of `len` bytes.
+fn rlp_append_length(out: &mut Vec<u8>, len: usize, offset: u8) {
+	if len < 56 {
+		out.push(offset + len as u8);
+		return;
+	}
+
+	let bytes = (len as u64).to_be_bytes();
+	let first = bytes.iter().position(|byte| *byte != 0).expect("length is not 0; qed");
+	out.push(offset + 55 + (bytes.len() - first) as u8);
+	out.extend_from_slice(&bytes[first..]);
+}
+
+fn rlp_append_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+	if bytes.len() != 1 || bytes[0] >= 0x80 {
+		rlp_append_length(out, bytes.len(), 0x80);
+	}
+	out.extend_from_slice(bytes);
+}
+
+/// Integers are encoded without leading zeros.
+fn rlp_append_u256(out: &mut Vec<u8>, value: U256) {
+	let first = value.0.iter().position(|byte| *byte != 0).unwrap_or(32);
+	rlp_append_bytes(out, &value.0[first..]);
+}
+
+fn rlp_list(items: &[u8]) -> Vec<u8> {
+	let mut result = Vec::with_capacity(items.len() + 9);
+	rlp_append_length(&mut result, items.len(), 0xc0);
+	result.extend_from_slice(items);
+	result
+}
+
+/// What is signed.
+#[derive(Debug, Clone, PartialEq)]
+enum Payload {
+	/// Transaction with its nonce. The data is the unsigned transaction, encoded as it's hashed.
+	Transaction(Transaction),
+	/// Message signed as `eth_sign` does.
+	Message,
+}
+
+/// Recovery id and compact signature.
+type Signature = (u8, [u8; 64]);
+
+/// Request handled by the thread of the external signer.
+struct Request {
+	payload: Payload,
+	data: Vec<u8>,
+	/// Hash signed by the key, which verifies the signature.
+	hash: [u8; 32],
+	response: oneshot::Sender<Result<Signature, Error>>,
+}
+
+/// Returns recovery id of the `signature` of `hash` by `address`.
+fn recovery_id(hash: &[u8; 32], signature: &[u8], address: Address) -> Result<u8, Error> {
+	let message = Message::from_slice(hash).expect("hash is 32 bytes long; qed");
+	let secp = Secp256k1::verification_only();
+	(0..2)
+		.find(|id| {
+			let id = RecoveryId::from_i32(*id).expect("recovery id is either 0 or 1; qed");
+			RecoverableSignature::from_compact(signature, id)
+				.and_then(|signature| secp.recover(&message, &signature))
+				.map(|public| public_address(&public.serialize_uncompressed()) == address)
+				.unwrap_or(false)
+		})
+		.map(|id| id as u8)
+		.ok_or_else(|| format!("Signature doesn't belong to {:?}", address).into())
+}
+
+/// Ledger holding the key, which signs the requests sent to its thread.
+#[derive(Clone)]
+struct LedgerKey {
+	path: Vec<u32>,
+	device: Option<PathBuf>,
+	timeout: Duration,
+	requests: mpsc::Sender<Request>,
+}
+
+impl PartialEq for LedgerKey {
+	fn eq(&self, other: &Self) -> bool {
+		self.path == other.path && self.device == other.device && self.timeout == other.timeout
+	}
+}
+
+/// Remote signer holding the key, which is called by its thread.
+#[derive(Clone)]
+struct RemoteKey {
+	signer: RemoteSigner,
+	requests: mpsc::Sender<Request>,
+}
+
+impl PartialEq for RemoteKey {
+	fn eq(&self, other: &Self) -> bool {
+		self.signer == other.signer
+	}
+}
+
+/// KMS key holding the key, which is called by its thread.
+#[derive(Clone)]
+struct KmsKey {
+	signer: KmsSigner,
+	requests: mpsc::Sender<Request>,
+}
+
+impl PartialEq for KmsKey {
+	fn eq(&self, other: &Self) -> bool {
+		self.signer == other.signer
+	}
+}
+
+#[derive(PartialEq, Clone)]
+enum Key {
+	Local(SecretKey),
+	Ledger(LedgerKey),
+	Remote(RemoteKey),
+	Kms(KmsKey),
+}
+
+/// Key of the account, which signs transactions sent to the chain `chain_id`.
+#[derive(PartialEq, Clone)]
+pub struct Signer {
+	key: Key,
+	pub address: Address,
+	pub chain_id: u64,
+}
+
+impl fmt::Debug for Signer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// the key must never end up in the logs
+		let mut result = f.debug_struct("Signer");
+		result.field("address", &self.address);
+		result.field("chain_id", &self.chain_id);
+		match self.key {
+			Key::Local(_) => {},
+			Key::Ledger(ref ledger) => {
+				result.field("ledger", &ledger.device);
+			},
+			Key::Remote(ref remote) => {
+				result.field("remote", &remote.signer.endpoint);
+			},
+			Key::Kms(ref kms) => {
+				result.field("kms", &kms.signer.key_id);
+			},
+		}
+		result.finish()
+	}
+}
+
+/// Signature made by the hardware wallet, the remote signer or KMS.
+pub struct ExternalSignature {
+	response: oneshot::Receiver<Result<Signature, Error>>,
+}
+
+impl Future for ExternalSignature {
+	type Item = Signature;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.response.poll() {
+			Ok(Async::Ready(result)) => result.map(Async::Ready),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(_) => Err("Signer thread has stopped".into()),
+		}
+	}
+}
+
+/// Builds the signed result from the recovery id and compact signature.
+type Finish<T> = Box<dyn FnOnce(u8, [u8; 64]) -> T>;
+
+enum SigningState<T> {
+	Signed(Option<T>),
+	Confirming(Timeout<ExternalSignature>, Option<Finish<T>>),
+}
+
+/// Resolves to the signature, once it's confirmed if the key is held by a hardware wallet, a remote signer or KMS.
+pub struct Signing<T> {
+	state: SigningState<T>,
+}
+
+impl<T> Future for Signing<T> {
+	type Item = T;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.state {
+			SigningState::Signed(ref mut result) => Ok(Async::Ready(result.take().expect("signature is polled once; qed"))),
+			SigningState::Confirming(ref mut future, ref mut finish) => {
+				let (recovery_id, signature) = try_ready!(future.poll());
+				let finish = finish.take().expect("signature is polled once; qed");
+				Ok(Async::Ready(finish(recovery_id, signature)))
+			},
+		}
+	}
+}
+
+impl Signer {
+	pub fn new(secret: &[u8], chain_id: u64) -> Result<Self, Error> {
+		let secret = SecretKey::from_slice(secret).map_err(|_| "Invalid private key")?;
+		let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret);
+		let result = Signer {
+			key: Key::Local(secret),
+			address: public_address(&public.serialize_uncompressed()),
+			chain_id,
+		};
+		Ok(result)
+	}
+
+	/// Parses 32 bytes of the private key encoded as hex.
+	pub fn from_hex(key: &str, chain_id: u64) -> Result<Self, Error> {
+		let key = key.trim();
+		let key = key.strip_prefix("0x").unwrap_or(key);
+		match key.from_hex() {
+			Ok(ref bytes) if bytes.len() == 32 => Signer::new(bytes, chain_id),
+			_ => Err("Invalid private key, expected 32 bytes encoded as hex".into()),
+		}
+	}
+
+	/// Decrypts the private key from the keystore `json` with `password`.
+	pub fn from_keystore(json: &str, password: &str, chain_id: u64) -> Result<Self, Error> {
+		let keystore: Keystore = serde_json::from_str(json).map_err(|err| format!("Invalid keystore: {}", err))?;
+		let secret = keystore.crypto.decrypt(password.as_bytes())?;
+		Signer::new(&secret, chain_id)
+	}
+
+	/// Signs with the key of `address` derived along `path` on the Ledger at `device`, or the first one connected.
+	/// The device is opened once the first signature is requested, which fails unless it holds the key of `address`.
+	/// Signatures, which are not confirmed within `timeout`, fail.
+	pub fn ledger(address: Address, path: Vec<u32>, device: Option<PathBuf>, timeout: Duration, chain_id: u64) -> Result<Self, Error> {
+		let requests = ledger::spawn(path.clone(), device.clone(), address)?;
+		let result = Signer {
+			key: Key::Ledger(LedgerKey { path, device, timeout, requests }),
+			address,
+			chain_id,
+		};
+		Ok(result)
+	}
+
+	/// Signs with the key of `address` held by the `remote` signer.
+	/// It's called once the first signature is requested, and its signatures are verified to belong to `address`.
+	pub fn remote(address: Address, remote: RemoteSigner, chain_id: u64) -> Result<Self, Error> {
+		let requests = remote::spawn(remote.clone(), address, chain_id)?;
+		let result = Signer {
+			key: Key::Remote(RemoteKey { signer: remote, requests }),
+			address,
+			chain_id,
+		};
+		Ok(result)
+	}
+
+	/// Signs with the key of `address` held by the `kms` key, which must be of `ECC_SECG_P256K1` spec.
+	/// It's called once the first signature is requested, and its signatures are verified to belong to `address`.
+	pub fn kms(address: Address, kms: KmsSigner, chain_id: u64) -> Result<Self, Error> {
+		let requests = kms::spawn(kms.clone(), address)?;
+		let result = Signer {
+			key: Key::Kms(KmsKey { signer: kms, requests }),
+			address,
+			chain_id,
+		};
+		Ok(result)
+	}
+
+	/// Returns recovery id and compact signature of the `hash`.
+	fn sign_hash(secret: &SecretKey, hash: &[u8]) -> (u8, [u8; 64]) {
+		let message = Message::from_slice(hash).expect("hash is 32 bytes long; qed");
+		let (recovery_id, signature) = Secp256k1::signing_only().sign_recoverable(&message, secret).serialize_compact();
+		(recovery_id.to_i32() as u8, signature)
+	}
+
+	/// Signs `data` hashed to `hash`, waiting for the external signer at most for its timeout.
+	fn sign<T, F>(&self, timer: &Timer, payload: Payload, data: Vec<u8>, hash: [u8; 32], finish: F) -> Signing<T>
+		where F: FnOnce(u8, [u8; 64]) -> T + 'static
+	{
+		let (requests, timeout) = match self.key {
+			Key::Local(ref secret) => {
+				let (recovery_id, signature) = Signer::sign_hash(secret, &hash);
+				return Signing { state: SigningState::Signed(Some(finish(recovery_id, signature))) };
+			},
+			Key::Ledger(ref ledger) => (&ledger.requests, ledger.timeout),
+			Key::Remote(ref remote) => (&remote.requests, remote.signer.timeout),
+			Key::Kms(ref kms) => (&kms.requests, kms.signer.timeout),
+		};
+
+		let (response, receiver) = oneshot::channel();
+		// the thread stops only if the request can't be handled, which is reported by the receiver
+		let _ = requests.send(Request { payload, data, hash, response });
+		let future = timer.timeout(ExternalSignature { response: receiver }, timeout);
+		Signing { state: SigningState::Confirming(future, Some(Box::new(finish))) }
+	}
+
+	/// Signs the `message` as `eth_sign` does, with `v` either 27 or 28.
+	pub fn sign_message(&self, timer: &Timer, message: &[u8]) -> Signing<H520> {
+		let hash = signed_message_hash(message).0;
+		self.sign(timer, Payload::Message, message.to_vec(), hash, |recovery_id, signature| {
+			let mut result = [0u8; 65];
+			result[..64].copy_from_slice(&signature);
+			result[64] = recovery_id + 27;
+			H520(result)
+		})
+	}
+
+	/// Returns payload of the `transaction` with `nonce`, which is hashed and signed.
+	fn unsigned_transaction(&self, transaction: &Transaction, nonce: U256) -> Vec<u8> {
+		let mut fields = Vec::new();
+		match transaction.fees {
+			Fees::Legacy { .. } => {
+				append_transaction_fields(&mut fields, transaction, nonce, self.chain_id);
+				rlp_append_u256(&mut fields, self.chain_id.into());
+				rlp_append_bytes(&mut fields, &[]);
+				rlp_append_bytes(&mut fields, &[]);
+				rlp_list(&fields)
+			},
+			Fees::Eip1559 { .. } => {
+				append_transaction_fields(&mut fields, transaction, nonce, self.chain_id);
+				// transaction type is prepended to the payload
+				let mut result = vec![2];
+				result.extend_from_slice(&rlp_list(&fields));
+				result
+			},
+		}
+	}
+
+	/// Returns the raw transaction with `nonce`, which is sent with `eth_sendRawTransaction`.
+	pub fn sign_transaction(&self, timer: &Timer, transaction: &Transaction, nonce: U256) -> Signing<Bytes> {
+		let unsigned = self.unsigned_transaction(transaction, nonce);
+		let hash = keccak256(&unsigned);
+		let transaction = Transaction {
+			nonce: Some(nonce),
+			..transaction.clone()
+		};
+		let chain_id = self.chain_id;
+		self.sign(timer, Payload::Transaction(transaction.clone()), unsigned, hash, move |recovery_id, signature| {
+			let mut fields = Vec::new();
+			append_transaction_fields(&mut fields, &transaction, nonce, chain_id);
+			let (v, envelope) = match transaction.fees {
+				Fees::Legacy { .. } => (u64::from(recovery_id) + 35 + chain_id * 2, None),
+				Fees::Eip1559 { .. } => (u64::from(recovery_id), Some(2)),
+			};
+			rlp_append_u256(&mut fields, v.into());
+			rlp_append_u256(&mut fields, U256::from(&signature[..32]));
+			rlp_append_u256(&mut fields, U256::from(&signature[32..]));
+			envelope.into_iter().chain(rlp_list(&fields)).collect::<Vec<_>>().into()
+		})
+	}
+}
+
+/// Appends fields of the `transaction` preceding its signature. Type 2 transactions have an empty access list.
+fn append_transaction_fields(fields: &mut Vec<u8>, transaction: &Transaction, nonce: U256, chain_id: u64) {
+	let to = transaction.to.as_ref().map(|to| &to.0[..]).unwrap_or(&[]);
+	let value = transaction.value.unwrap_or_default();
+	match transaction.fees {
+		Fees::Legacy { gas_price } => {
+			rlp_append_u256(fields, nonce);
+			rlp_append_u256(fields, gas_price);
+			rlp_append_u256(fields, transaction.gas);
+			rlp_append_bytes(fields, to);
+			rlp_append_u256(fields, value);
+			rlp_append_bytes(fields, &transaction.data.0);
+		},
+		Fees::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+			rlp_append_u256(fields, chain_id.into());
+			rlp_append_u256(fields, nonce);
+			rlp_append_u256(fields, max_priority_fee_per_gas);
+			rlp_append_u256(fields, max_fee_per_gas);
+			rlp_append_u256(fields, transaction.gas);
+			rlp_append_bytes(fields, to);
+			rlp_append_u256(fields, value);
+			rlp_append_bytes(fields, &transaction.data.0);
+			fields.extend_from_slice(&rlp_list(&[]));
+		},
+	}
+}
+
+/// Keystore file of version 3, e.g. created by `geth account new`.
+#[derive(Deserialize)]
+struct Keystore {
+	#[serde(alias = "Crypto")]
+	crypto: KeystoreCrypto,
+}
+
+#[derive(Deserialize)]
+struct KeystoreCrypto {
+	cipher: String,
+	cipherparams: CipherParams,
+	ciphertext: String,
+	kdf: String,
+	kdfparams: KdfParams,
+	mac: String,
+}
+
+#[derive(Deserialize)]
+struct CipherParams {
+	iv: String,
+}
+
+/// Parameters of either `pbkdf2` or `scrypt`.
+#[derive(Deserialize)]
+struct KdfParams {
+	dklen: usize,
+	salt: String,
+	c: Option<usize>,
+	prf: Option<String>,
+	n: Option<u64>,
+	r: Option<u64>,
+	p: Option<u64>,
+}
+
+fn keystore_hex(value: &str, name: &str) -> Result<Vec<u8>, Error> {
+	value.from_hex().map_err(|_| format!("Invalid keystore `{}`", name).into())
+}
+
+impl KeystoreCrypto {
+	fn decrypt(&self, password: &[u8]) -> Result<Vec<u8>, Error> {
+		if self.cipher != "aes-128-ctr" {
+			return Err(format!("Unsupported keystore cipher `{}`", self.cipher).into());
+		}
+		let params = &self.kdfparams;
+		if params.dklen < 32 {
+			return Err("Keystore `dklen` should be at least 32".into());
+		}
+
+		let salt = keystore_hex(&params.salt, "salt")?;
+		let mut derived = vec![0u8; params.dklen];
+		match (self.kdf.as_str(), params.c, params.prf.as_deref(), params.n, params.r, params.p) {
+			("pbkdf2", Some(c), Some("hmac-sha256"), _, _, _) => {
+				pbkdf2_hmac(password, &salt, c, MessageDigest::sha256(), &mut derived)
+					.map_err(|err| format!("Cannot derive keystore key: {}", err))?;
+			},
+			("scrypt", _, _, Some(n), Some(r), Some(p)) => {
+				let maxmem = 128u64.saturating_mul(r).saturating_mul(n.saturating_add(p).saturating_add(2));
+				scrypt(password, &salt, n, r, p, maxmem, &mut derived)
+					.map_err(|err| format!("Cannot derive keystore key: {}", err))?;
+			},
+			_ => return Err(format!("Unsupported keystore key derivation `{}`", self.kdf).into()),
+		}
+
+		let ciphertext = keystore_hex(&self.ciphertext, "ciphertext")?;
+		let mut mac = derived[16..32].to_vec();
+		mac.extend_from_slice(&ciphertext);
+		if keccak256(&mac)[..] != keystore_hex(&self.mac, "mac")?[..] {
+			return Err("Cannot decrypt keystore, the password is probably wrong".into());
+		}
+
+		let iv = keystore_hex(&self.cipherparams.iv, "iv")?;
+		decrypt(Cipher::aes_128_ctr(), &derived[..16], Some(&iv), &ciphertext)
+			.map_err(|err| format!("Cannot decrypt keystore: {}", err).into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use futures::Future;
+	use tokio_timer::Timer;
+	use rustc_hex::FromHex;
+	use web3::types::{Address, Bytes};
+	use transaction::FeeModel;
+	use util::recover_signer;
+	use super::Signer;
+
+	const PBKDF2_KEYSTORE: &str = r#"{
+	"crypto": {
+		"cipher": "aes-128-ctr",
+		"cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+		"ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+		"kdf": "pbkdf2",
+		"kdfparams": { "c": 262144, "dklen": 32, "prf": "hmac-sha256", "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd" },
+		"mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+	},
+	"id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+	"version": 3
+}"#;
+
+	const SCRYPT_KEYSTORE: &str = r#"{
+	"crypto": {
+		"cipher": "aes-128-ctr",
+		"cipherparams": { "iv": "101112131415161718191a1b1c1d1e1f" },
+		"ciphertext": "f581b73459d0dd8cde650f6d9d2dca685819560e046ff672a77ca6fac493503e",
+		"kdf": "scrypt",
+		"kdfparams": { "dklen": 32, "n": 1024, "r": 8, "p": 1, "salt": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f" },
+		"mac": "0dd1cdd81ddae7b25d306d1586ec3101ced8d4f8e78932b9e65d40bd34cec441"
+	},
+	"version": 3
+}"#;
+
+	#[test]
+	fn test_sign_transaction() {
+		// example of EIP-155
+		let signer = Signer::from_hex("0x4646464646464646464646464646464646464646464646464646464646464646", 1).unwrap();
+		assert_eq!("0x9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".parse::<Address>().unwrap(), signer.address);
+		let to = Some("0x3535353535353535353535353535353535353535".parse().unwrap());
+		let mut transaction = FeeModel::Legacy.transaction(signer.address, to, Bytes(Vec::new()), 21000, 20_000_000_000);
+		transaction.value = Some(1_000_000_000_000_000_000u64.into());
+
+		let expected: Bytes = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83".from_hex().unwrap().into();
+		assert_eq!(expected, signer.sign_transaction(&Timer::default(), &transaction, 9.into()).wait().unwrap());
+	}
+
+	#[test]
+	fn test_sign_eip1559_transaction() {
+		let signer = Signer::from_hex("0x4646464646464646464646464646464646464646464646464646464646464646", 1).unwrap();
+		let to = Some("0x3535353535353535353535353535353535353535".parse().unwrap());
+		let fee_model = FeeModel::Eip1559 { max_priority_fee_per_gas: 2_000_000_000 };
+		let mut transaction = fee_model.transaction(signer.address, to, Bytes(Vec::new()), 21000, 20_000_000_000);
+		transaction.value = Some(1_000_000_000_000_000_000u64.into());
+
+		let expected: Bytes = "02f873010984773594008504a817c800825208943535353535353535353535353535353535353535880de0b6b3a764000080c001a079b7c7f86885a63564d32f1970e457e0d5e904f6e3373aaf114ac871d96aee31a0461db850185ce53f5923ea61800444727d21e2cbe15393e774a8e6a6ef495681".from_hex().unwrap().into();
+		assert_eq!(expected, signer.sign_transaction(&Timer::default(), &transaction, 9.into()).wait().unwrap());
+	}
+
+	#[test]
+	fn test_sign_message() {
+		let signer = Signer::new(&[1u8; 32], 1).unwrap();
+		let signature = signer.sign_message(&Timer::default(), b"withdraw").wait().unwrap();
+		assert!(signature.0[64] == 27 || signature.0[64] == 28);
+		assert_eq!(signer.address, recover_signer(&signature.0, b"withdraw").unwrap());
+	}
+
+	#[test]
+	fn test_ledger_signer() {
+		let address = "0x1a642f0e3c3af545e7acbd38b07251b3990914f1".parse().unwrap();
+		let path = vec![0x8000_002c, 0x8000_003c, 0x8000_0000, 0, 0];
+		let signer = Signer::ledger(address, path, Some("/nonexistent/hidraw0".into()), Duration::from_secs(5), 1).unwrap();
+		assert_eq!(format!("Signer {{ address: {:?}, chain_id: 1, ledger: Some(\"/nonexistent/hidraw0\") }}", address), format!("{:?}", signer));
+		// the signature fails, rather than waits for the device
+		assert!(signer.sign_message(&Timer::default(), b"withdraw").wait().is_err());
+	}
+
+	#[test]
+	fn test_signer_from_keystore() {
+		let signer = Signer::from_keystore(PBKDF2_KEYSTORE, "testpassword", 1).unwrap();
+		assert_eq!(Signer::from_hex("7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d", 1).unwrap(), signer);
+		assert_eq!(format!("Signer {{ address: {:?}, chain_id: 1 }}", signer.address), format!("{:?}", signer));
+		assert!(Signer::from_keystore(PBKDF2_KEYSTORE, "wrong", 1).is_err());
+
+		let signer = Signer::from_keystore(SCRYPT_KEYSTORE, "bridge", 42).unwrap();
+		assert_eq!(Signer::new(&[0x46; 32], 42).unwrap(), signer);
+		assert!(Signer::from_keystore(&SCRYPT_KEYSTORE.replace("scrypt", "bcrypt"), "bridge", 42).is_err());
+	}
+}
@@ -0,0 +1,341 @@
+//! Signing by a remote signer over JSON-RPC, e.g. Web3Signer or Clef, which holds the key of the account.
+//!
+//! Requests are handled one at a time by a dedicated thread with its own event loop, since the signer
+//! may wait for the operator to approve the signature. Signatures are verified before they are used,
+//! so a misconfigured signer can't make the bridge send transactions it didn't build.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use futures::Future;
+use serde_json;
+use tokio_core::reactor::Core;
+use tokio_timer::Timer;
+use web3::{self, Transport};
+use web3::helpers;
+use web3::transports::ipc::Ipc;
+use web3::types::{Address, Bytes, H520, U256};
+use config::{Endpoint, HttpAuth};
+use error::{Error, ErrorKind, ResultExt};
+use transaction::{Transaction, Fees};
+use transport::Http;
+use rpc;
+use super::{Payload, Signature, Request, recovery_id, append_transaction_fields};
+
+/// JSON-RPC methods used to sign.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemoteProtocol {
+	/// `eth_signTransaction` and `eth_sign`, e.g. of Web3Signer.
+	Eth,
+	/// `account_signTransaction` and `account_signData` of Clef.
+	Clef,
+}
+
+/// Remote signer holding the key of the account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteSigner {
+	/// Either `http://` or `https://` url, or path to the ipc socket.
+	pub endpoint: Endpoint,
+	pub protocol: RemoteProtocol,
+	/// Credentials of the http endpoint.
+	pub http_auth: HttpAuth,
+	/// Signatures, which are not made within the timeout, fail.
+	pub timeout: Duration,
+}
+
+/// Connection to the remote signer.
+enum Connection {
+	Http(Http),
+	Ipc(Ipc),
+}
+
+impl Connection {
+	fn open(signer: &RemoteSigner, core: &Core) -> Result<Self, Error> {
+		let result = match signer.endpoint {
+			Endpoint::Http(ref url) => Connection::Http(Http::with_event_loop(url, &signer.http_auth, None, &[], &core.handle())?),
+			Endpoint::Ipc(ref path) => Connection::Ipc(Ipc::with_event_loop(path, &core.handle())
+				.map_err(ErrorKind::Web3)
+				.chain_err(|| format!("Cannot connect to remote signer {:?}", path))?),
+			Endpoint::Ws(ref url) => return Err(format!("Remote signer {} should be either http url or ipc path", url).into()),
+		};
+		Ok(result)
+	}
+
+	fn execute(&self, method: &str, params: Vec<rpc::Value>) -> Box<Future<Item = rpc::Value, Error = web3::Error>> {
+		match *self {
+			Connection::Http(ref http) => http.execute(method, params),
+			Connection::Ipc(ref ipc) => Box::new(ipc.execute(method, params)),
+		}
+	}
+}
+
+/// Takes the next RLP string from `data`. Fields of the signature are always shorter than 56 bytes.
+fn rlp_take_bytes<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+	let (start, len) = match data.first() {
+		Some(&byte) if byte < 0x80 => (0, 1),
+		Some(&byte) if byte < 0xb8 => (1, usize::from(byte - 0x80)),
+		_ => return Err("Unexpected signature field in the signed transaction".into()),
+	};
+	if data.len() < start + len {
+		return Err("Signed transaction is too short".into());
+	}
+	let result = &data[start..start + len];
+	*data = &data[start + len..];
+	Ok(result)
+}
+
+/// Returns the payload of the RLP list, which is the whole `data`.
+fn rlp_list_payload(data: &[u8]) -> Result<&[u8], Error> {
+	let (start, len) = match data.first() {
+		Some(&byte) if byte >= 0xc0 && byte < 0xf8 => (1, usize::from(byte - 0xc0)),
+		Some(&byte) if byte >= 0xf8 => {
+			let len_of_len = usize::from(byte - 0xf7);
+			if data.len() < 1 + len_of_len || len_of_len > 8 {
+				return Err("Signed transaction is too short".into());
+			}
+			let len = data[1..1 + len_of_len].iter().fold(0u64, |len, byte| len << 8 | u64::from(*byte));
+			(1 + len_of_len, len as usize)
+		},
+		_ => return Err("Signed transaction is not an RLP list".into()),
+	};
+	if data.len() != start + len {
+		return Err("Unexpected length of the signed transaction".into());
+	}
+	Ok(&data[start..])
+}
+
+/// Returns the compact signature of the `raw` transaction, which must be the `transaction` sent to the chain `chain_id`.
+fn transaction_signature(raw: &[u8], transaction: &Transaction, chain_id: u64) -> Result<[u8; 64], Error> {
+	let list = match transaction.fees {
+		Fees::Legacy { .. } => raw,
+		Fees::Eip1559 { .. } => match raw.split_first() {
+			Some((&2, list)) => list,
+			_ => return Err("Signed transaction is not of type 2".into()),
+		},
+	};
+
+	let nonce = transaction.nonce.expect("transactions are signed with the nonce assigned by the bridge; qed");
+	let mut fields = Vec::new();
+	append_transaction_fields(&mut fields, transaction, nonce, chain_id);
+	let payload = rlp_list_payload(list)?;
+	if !payload.starts_with(&fields) {
+		return Err("Remote signer has signed a different transaction".into());
+	}
+
+	let mut rest = &payload[fields.len()..];
+	// `v` is ignored, since the recovery id is verified with the hash
+	let _v = rlp_take_bytes(&mut rest)?;
+	let r = rlp_take_bytes(&mut rest)?;
+	let s = rlp_take_bytes(&mut rest)?;
+	if !rest.is_empty() || r.len() > 32 || s.len() > 32 {
+		return Err("Unexpected signature of the signed transaction".into());
+	}
+
+	let mut signature = [0u8; 64];
+	signature[32 - r.len()..32].copy_from_slice(r);
+	signature[64 - s.len()..].copy_from_slice(s);
+	Ok(signature)
+}
+
+fn parse_result<T: ::serde::de::DeserializeOwned>(value: rpc::Value) -> Result<T, Error> {
+	serde_json::from_value(value).map_err(|err| format!("Unexpected response of the remote signer: {}", err).into())
+}
+
+/// Remote signer holding the key of `address`, which signs for the chain `chain_id`.
+struct Remote {
+	signer: RemoteSigner,
+	address: Address,
+	chain_id: u64,
+	core: Core,
+	timer: Timer,
+	connection: Option<Connection>,
+}
+
+impl Remote {
+	/// Returns the method and its params, which sign the `payload`.
+	fn request(&self, payload: &Payload, data: &[u8]) -> (&'static str, Vec<rpc::Value>) {
+		let address = helpers::serialize(&self.address);
+		let data = helpers::serialize(&Bytes(data.to_vec()));
+		match (payload, self.signer.protocol) {
+			(&Payload::Transaction(ref transaction), protocol) => {
+				let mut params = helpers::serialize(transaction);
+				if let rpc::Value::Object(ref mut params) = params {
+					params.insert("chainId".into(), helpers::serialize(&U256::from(self.chain_id)));
+				}
+				let method = match protocol {
+					RemoteProtocol::Eth => "eth_signTransaction",
+					RemoteProtocol::Clef => "account_signTransaction",
+				};
+				(method, vec![params])
+			},
+			(&Payload::Message, RemoteProtocol::Eth) => ("eth_sign", vec![address, data]),
+			(&Payload::Message, RemoteProtocol::Clef) => ("account_signData", vec!["text/plain".into(), address, data]),
+		}
+	}
+
+	fn call(&mut self, method: &str, params: Vec<rpc::Value>) -> Result<rpc::Value, Error> {
+		if self.connection.is_none() {
+			self.connection = Some(Connection::open(&self.signer, &self.core)?);
+		}
+
+		let call = self.connection.as_ref().expect("connection has just been opened; qed")
+			.execute(method, params)
+			.map_err(|err| Error::from(ErrorKind::Web3(err)));
+		let timeout = self.timer.sleep(self.signer.timeout)
+			.then(|_| -> Result<rpc::Value, Error> { Err(ErrorKind::Timeout("remote signature").into()) });
+		self.core.run(call.select(timeout).map(|(result, _)| result).map_err(|(err, _)| err))
+	}
+
+	fn sign(&mut self, payload: &Payload, data: &[u8], hash: &[u8; 32]) -> Result<Signature, Error> {
+		let (method, params) = self.request(payload, data);
+		info!(target: "bridge", "Requesting {} from remote signer {:?}", method, self.signer.endpoint);
+		let result = self.call(method, params);
+		if result.is_err() {
+			// the connection is opened again, since it may have been lost
+			self.connection = None;
+		}
+
+		let signature = match (payload, result?) {
+			// Clef and geth return the transaction along with its raw encoding
+			(&Payload::Transaction(ref transaction), rpc::Value::Object(mut result)) => {
+				let raw: Bytes = parse_result(result.remove("raw").unwrap_or(rpc::Value::Null))?;
+				transaction_signature(&raw.0, transaction, self.chain_id)?
+			},
+			(&Payload::Transaction(ref transaction), result) => {
+				let raw: Bytes = parse_result(result)?;
+				transaction_signature(&raw.0, transaction, self.chain_id)?
+			},
+			(&Payload::Message, result) => {
+				let signature: H520 = parse_result(result)?;
+				let mut result = [0u8; 64];
+				result.copy_from_slice(&signature.0[..64]);
+				result
+			},
+		};
+		Ok((recovery_id(hash, &signature, self.address)?, signature))
+	}
+}
+
+/// Starts the thread signing the requests with the `signer` holding the key of `address`.
+pub fn spawn(signer: RemoteSigner, address: Address, chain_id: u64) -> Result<mpsc::Sender<Request>, Error> {
+	let (sender, receiver) = mpsc::channel::<Request>();
+	thread::Builder::new()
+		.name("remote-signer".into())
+		.spawn(move || {
+			let core = match Core::new() {
+				Ok(core) => core,
+				Err(err) => {
+					error!(target: "bridge", "Cannot start the event loop of the remote signer: {}", err);
+					return;
+				},
+			};
+			let mut remote = Remote {
+				signer,
+				address,
+				chain_id,
+				core,
+				timer: Timer::default(),
+				connection: None,
+			};
+
+			for request in receiver {
+				let result = remote.sign(&request.payload, &request.data, &request.hash);
+				if let Err(ref err) = result {
+					error!(target: "bridge", "Remote signer has failed to sign: {}", err);
+				}
+				if request.response.send(result).is_err() {
+					warn!(target: "bridge", "Remote signature has been made after the timeout and is discarded");
+				}
+			}
+		})
+		.chain_err(|| "Cannot start the remote signer thread")?;
+	Ok(sender)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+	use std::net::TcpListener;
+	use std::thread;
+	use std::time::Duration;
+	use futures::Future;
+	use serde_json;
+	use tokio_timer::Timer;
+	use web3::types::Bytes;
+	use config::{Endpoint, HttpAuth};
+	use transaction::FeeModel;
+	use super::super::Signer;
+	use super::{RemoteSigner, RemoteProtocol, transaction_signature};
+
+	/// Responds to a single request with the `result`.
+	fn serve_once(listener: TcpListener, result: String) {
+		thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = Vec::new();
+			let mut buffer = [0u8; 1024];
+			while !request.ends_with(b"}") {
+				let read = stream.read(&mut buffer).unwrap();
+				request.extend_from_slice(&buffer[..read]);
+			}
+
+			let response = format!(r#"{{"jsonrpc":"2.0","id":1,"result":{}}}"#, result);
+			write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", response.len(), response).unwrap();
+		});
+	}
+
+	fn remote_signer(listener: &TcpListener, protocol: RemoteProtocol) -> RemoteSigner {
+		RemoteSigner {
+			endpoint: Endpoint::Http(format!("http://{}", listener.local_addr().unwrap())),
+			protocol,
+			http_auth: HttpAuth::default(),
+			timeout: Duration::from_secs(5),
+		}
+	}
+
+	#[test]
+	fn test_transaction_signature() {
+		let local = Signer::new(&[0x46; 32], 1).unwrap();
+		let to = Some("0x3535353535353535353535353535353535353535".parse().unwrap());
+		let mut transaction = FeeModel::Legacy.transaction(local.address, to, Bytes(Vec::new()), 21000, 20_000_000_000);
+		transaction.nonce = Some(9.into());
+		let raw = local.sign_transaction(&Timer::default(), &transaction, 9.into()).wait().unwrap();
+		assert!(transaction_signature(&raw.0, &transaction, 1).is_ok());
+		// signature of another transaction or chain is rejected
+		assert!(transaction_signature(&raw.0, &transaction, 2).is_err());
+		transaction.nonce = Some(10.into());
+		assert!(transaction_signature(&raw.0, &transaction, 1).is_err());
+
+		let mut transaction = FeeModel::Eip1559 { max_priority_fee_per_gas: 2 }.transaction(local.address, None, vec![1; 100].into(), 21000, 20);
+		transaction.nonce = Some(1.into());
+		let raw = local.sign_transaction(&Timer::default(), &transaction, 1.into()).wait().unwrap();
+		assert!(transaction_signature(&raw.0, &transaction, 1).is_ok());
+		assert!(transaction_signature(&raw.0[1..], &transaction, 1).is_err());
+	}
+
+	#[test]
+	fn test_remote_signer() {
+		let local = Signer::new(&[1u8; 32], 42).unwrap();
+		let timer = Timer::default();
+
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let remote = Signer::remote(local.address, remote_signer(&listener, RemoteProtocol::Eth), 42).unwrap();
+		let signature = local.sign_message(&timer, b"withdraw").wait().unwrap();
+		serve_once(listener, serde_json::to_string(&signature).unwrap());
+		assert_eq!(signature, remote.sign_message(&timer, b"withdraw").wait().unwrap());
+
+		// clef returns the raw transaction along with its fields
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let remote = Signer::remote(local.address, remote_signer(&listener, RemoteProtocol::Clef), 42).unwrap();
+		let transaction = FeeModel::Legacy.transaction(local.address, Some(1.into()), vec![2].into(), 100_000, 1_000_000_000);
+		let raw = local.sign_transaction(&timer, &transaction, 3.into()).wait().unwrap();
+		serve_once(listener, format!(r#"{{"raw":{},"tx":{{}}}}"#, serde_json::to_string(&raw).unwrap()));
+		assert_eq!(raw, remote.sign_transaction(&timer, &transaction, 3.into()).wait().unwrap());
+
+		// signature of another key is rejected
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let other = Signer::new(&[2u8; 32], 42).unwrap();
+		let remote = Signer::remote(other.address, remote_signer(&listener, RemoteProtocol::Eth), 42).unwrap();
+		serve_once(listener, serde_json::to_string(&signature).unwrap());
+		assert!(remote.sign_message(&timer, b"withdraw").wait().is_err());
+	}
+}
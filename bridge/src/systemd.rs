@@ -0,0 +1,164 @@
+//! Notifications of systemd, which supervises the bridge run as a service of `Type=notify`.
+
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use futures::{Future, Stream};
+use tokio_timer::Timer;
+use error::{Error, ResultExt};
+
+/// Number of times the progress is checked within the watchdog interval, so that the ping
+/// following the progress is sent well before systemd considers the bridge hung.
+const WATCHDOG_CHECKS: u32 = 4;
+
+/// Sends notifications to the socket given by `NOTIFY_SOCKET`.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+	socket: Arc<UnixDatagram>,
+	path: String,
+}
+
+impl Notifier {
+	/// Returns the notifier, if the bridge is run by systemd, which expects notifications.
+	pub fn from_env() -> Result<Option<Self>, Error> {
+		match env::var("NOTIFY_SOCKET") {
+			Ok(path) => Notifier::new(path).map(Some),
+			Err(_) => Ok(None),
+		}
+	}
+
+	fn new(path: String) -> Result<Self, Error> {
+		if path.starts_with('@') {
+			return Err(format!("Abstract socket {} of systemd notifications is not supported", path).into());
+		}
+
+		let socket = UnixDatagram::unbound().chain_err(|| "Cannot create socket of systemd notifications")?;
+		Ok(Notifier {
+			socket: Arc::new(socket),
+			path,
+		})
+	}
+
+	fn notify(&self, state: &str) -> Result<(), Error> {
+		self.socket.send_to(state.as_bytes(), &self.path)
+			.chain_err(|| format!("Cannot notify systemd at {}", self.path))?;
+		Ok(())
+	}
+
+	/// Tells systemd that the bridge has started.
+	pub fn ready(&self) -> Result<(), Error> {
+		self.notify("READY=1")
+	}
+
+	/// Tells systemd that the bridge is alive, resetting its watchdog timer.
+	pub fn watchdog(&self) -> Result<(), Error> {
+		self.notify("WATCHDOG=1")
+	}
+}
+
+/// Returns the interval, within which systemd expects the watchdog pings, if `WatchdogSec` is set.
+pub fn watchdog_interval() -> Option<Duration> {
+	parse_watchdog_interval(env::var("WATCHDOG_USEC").ok().as_deref(), env::var("WATCHDOG_PID").ok().as_deref(), process::id())
+}
+
+fn parse_watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+	// the watchdog may be meant for another process, e.g. the shell starting the bridge
+	if let Some(pid) = pid {
+		if pid.parse::<u32>().ok() != Some(own_pid) {
+			return None;
+		}
+	}
+
+	match usec?.parse::<u64>() {
+		Ok(0) | Err(_) => None,
+		Ok(usec) => Some(Duration::from_micros(usec)),
+	}
+}
+
+/// Number of items yielded by a bridge, i.e. of the checked blocks, shared with the watchdog.
+#[derive(Debug, Clone, Default)]
+pub struct Progress(Arc<AtomicUsize>);
+
+impl Progress {
+	pub fn advance(&self) {
+		self.0.fetch_add(1, Ordering::SeqCst);
+	}
+
+	fn get(&self) -> usize {
+		self.0.load(Ordering::SeqCst)
+	}
+}
+
+/// Returns true if every bridge has made progress since `seen`, updating `seen` if it has.
+fn advanced(progress: &[Progress], seen: &mut Vec<usize>) -> bool {
+	let current = progress.iter().map(Progress::get).collect::<Vec<_>>();
+	if current.iter().zip(seen.iter()).any(|(current, seen)| current == seen) {
+		return false;
+	}
+
+	*seen = current;
+	true
+}
+
+/// Pings the watchdog of systemd, as long as every bridge has made progress since the previous ping,
+/// so that a bridge, which is stuck, is restarted once `interval` passes without any checked block.
+pub fn watchdog(notifier: Notifier, progress: Vec<Progress>, timer: &Timer, interval: Duration) -> Box<Future<Item = (), Error = ()>> {
+	let mut seen = progress.iter().map(Progress::get).collect::<Vec<_>>();
+	let watchdog = timer.interval(interval / WATCHDOG_CHECKS)
+		.map_err(Error::from)
+		.for_each(move |_| {
+			if advanced(&progress, &mut seen) {
+				notifier.watchdog()?;
+			}
+			Ok(())
+		})
+		.map_err(|err| warn!(target: "bridge", "Watchdog of systemd stopped: {}", err));
+	Box::new(watchdog)
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	use std::os::unix::net::UnixDatagram;
+	use std::time::Duration;
+	use self::tempdir::TempDir;
+	use super::{Notifier, Progress, advanced, parse_watchdog_interval};
+
+	#[test]
+	fn test_parse_watchdog_interval() {
+		assert_eq!(Some(Duration::from_secs(30)), parse_watchdog_interval(Some("30000000"), None, 10));
+		assert_eq!(Some(Duration::from_secs(30)), parse_watchdog_interval(Some("30000000"), Some("10"), 10));
+		assert_eq!(None, parse_watchdog_interval(Some("30000000"), Some("11"), 10));
+		assert_eq!(None, parse_watchdog_interval(Some("0"), None, 10));
+		assert_eq!(None, parse_watchdog_interval(None, None, 10));
+	}
+
+	#[test]
+	fn test_watchdog_requires_progress_of_every_bridge() {
+		let progress = vec![Progress::default(), Progress::default()];
+		let mut seen = vec![0, 0];
+		assert!(!advanced(&progress, &mut seen));
+		progress[0].advance();
+		assert!(!advanced(&progress, &mut seen));
+		progress[1].advance();
+		assert!(advanced(&progress, &mut seen));
+		assert!(!advanced(&progress, &mut seen));
+	}
+
+	#[test]
+	fn test_notifier_sends_state() {
+		let tempdir = TempDir::new("test_systemd_notifier").unwrap();
+		let path = tempdir.path().join("notify");
+		let socket = UnixDatagram::bind(&path).unwrap();
+		let notifier = Notifier::new(path.to_str().unwrap().to_owned()).unwrap();
+
+		notifier.ready().unwrap();
+		let mut buffer = [0; 16];
+		let received = socket.recv(&mut buffer).unwrap();
+		assert_eq!(b"READY=1", &buffer[..received]);
+		assert!(Notifier::new("@notify".to_owned()).is_err());
+	}
+}
@@ -0,0 +1,151 @@
+//! Transactions sent by the bridge, built according to the fee model of the chain they are sent to.
+
+use serde::{Serialize, Serializer};
+use web3::types::{Address, Bytes, U256};
+
+/// How transactions pay for their gas on a chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeeModel {
+	/// Legacy transactions paying the configured `gas_price`.
+	Legacy,
+	/// EIP-1559 (type 2) transactions paying at most the configured `gas_price` per gas,
+	/// of which at most `max_priority_fee_per_gas` goes to the block producer.
+	Eip1559 {
+		max_priority_fee_per_gas: u64,
+	},
+}
+
+impl FeeModel {
+	/// Builds transaction from `from` with `gas` and fees paying at most `gas_price` per gas.
+	pub fn transaction(&self, from: Address, to: Option<Address>, data: Bytes, gas: u64, gas_price: u64) -> Transaction {
+		let fees = match *self {
+			FeeModel::Legacy => Fees::Legacy {
+				gas_price: gas_price.into(),
+			},
+			FeeModel::Eip1559 { max_priority_fee_per_gas } => Fees::Eip1559 {
+				max_fee_per_gas: gas_price.into(),
+				// the priority fee can't exceed the max fee
+				max_priority_fee_per_gas: max_priority_fee_per_gas.min(gas_price).into(),
+			},
+		};
+
+		Transaction {
+			from,
+			to,
+			gas: gas.into(),
+			fees,
+			value: None,
+			data,
+			nonce: None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fees {
+	Legacy {
+		gas_price: U256,
+	},
+	Eip1559 {
+		max_fee_per_gas: U256,
+		max_priority_fee_per_gas: U256,
+	},
+}
+
+impl Fees {
+	/// Returns the highest price per gas, which the transaction may pay.
+	pub fn max_gas_price(&self) -> U256 {
+		match *self {
+			Fees::Legacy { gas_price } => gas_price,
+			Fees::Eip1559 { max_fee_per_gas, .. } => max_fee_per_gas,
+		}
+	}
+}
+
+/// Transaction, which is either sent with `eth_sendTransaction` and signed by the node, or signed locally.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+	pub from: Address,
+	/// `None` for contract creation.
+	pub to: Option<Address>,
+	pub gas: U256,
+	pub fees: Fees,
+	pub value: Option<U256>,
+	pub data: Bytes,
+	/// `None` for the next nonce assigned by the node.
+	pub nonce: Option<U256>,
+}
+
+/// Parameters of `eth_sendTransaction`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionRequest<'a> {
+	from: &'a Address,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	to: Option<&'a Address>,
+	gas: &'a U256,
+	#[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+	transaction_type: Option<U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	gas_price: Option<&'a U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	max_fee_per_gas: Option<&'a U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	max_priority_fee_per_gas: Option<&'a U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	value: Option<&'a U256>,
+	data: &'a Bytes,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	nonce: Option<&'a U256>,
+}
+
+impl Serialize for Transaction {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let (transaction_type, gas_price, max_fee_per_gas, max_priority_fee_per_gas) = match self.fees {
+			Fees::Legacy { ref gas_price } => (None, Some(gas_price), None, None),
+			Fees::Eip1559 { ref max_fee_per_gas, ref max_priority_fee_per_gas } =>
+				(Some(2.into()), None, Some(max_fee_per_gas), Some(max_priority_fee_per_gas)),
+		};
+
+		TransactionRequest {
+			from: &self.from,
+			to: self.to.as_ref(),
+			gas: &self.gas,
+			transaction_type,
+			gas_price,
+			max_fee_per_gas,
+			max_priority_fee_per_gas,
+			value: self.value.as_ref(),
+			data: &self.data,
+			nonce: self.nonce.as_ref(),
+		}.serialize(serializer)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json;
+	use super::{FeeModel, Fees};
+
+	#[test]
+	fn test_transaction_fees() {
+		let legacy = FeeModel::Legacy.transaction(1.into(), Some(2.into()), vec![3].into(), 0x10, 0x20);
+		assert_eq!(Fees::Legacy { gas_price: 0x20.into() }, legacy.fees);
+		assert_eq!(
+			r#"{"from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000002","gas":"0x10","gasPrice":"0x20","data":"0x03"}"#,
+			serde_json::to_string(&legacy).unwrap()
+		);
+
+		let model = FeeModel::Eip1559 { max_priority_fee_per_gas: 0x30 };
+		let tx = model.transaction(1.into(), None, vec![3].into(), 0x10, 0x40);
+		assert_eq!(Fees::Eip1559 { max_fee_per_gas: 0x40.into(), max_priority_fee_per_gas: 0x30.into() }, tx.fees);
+		assert_eq!(
+			r#"{"from":"0x0000000000000000000000000000000000000001","gas":"0x10","type":"0x2","maxFeePerGas":"0x40","maxPriorityFeePerGas":"0x30","data":"0x03"}"#,
+			serde_json::to_string(&tx).unwrap()
+		);
+
+		// priority fee is capped by the max fee
+		let tx = model.transaction(1.into(), None, vec![3].into(), 0x10, 0x20);
+		assert_eq!(Fees::Eip1559 { max_fee_per_gas: 0x20.into(), max_priority_fee_per_gas: 0x20.into() }, tx.fees);
+	}
+}
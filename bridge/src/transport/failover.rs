@@ -0,0 +1,230 @@
+//! Transport switching between multiple endpoints of the same chain.
+
+use std::sync::{atomic, Arc, Mutex};
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use tokio_timer::{Sleep, Timer};
+use web3::{self, Transport, BatchTransport, RequestId};
+use rpc;
+use super::{PubSub, is_connection_error};
+
+struct State {
+	/// Index of the endpoint currently in use.
+	current: usize,
+	/// Number of consecutive failures of the current endpoint.
+	errors: u32,
+}
+
+struct Shared {
+	state: Mutex<State>,
+	endpoints: usize,
+	max_errors: u32,
+	timer: Timer,
+	/// Endpoint is considered failed if there is no response within this time.
+	response_timeout: Duration,
+}
+
+impl Shared {
+	fn current(&self) -> usize {
+		self.state.lock().expect("lock is never poisoned; qed").current
+	}
+
+	fn on_success(&self, endpoint: usize) {
+		let mut state = self.state.lock().expect("lock is never poisoned; qed");
+		if state.current == endpoint {
+			state.errors = 0;
+		}
+	}
+
+	fn on_failure(&self, endpoint: usize) {
+		let mut state = self.state.lock().expect("lock is never poisoned; qed");
+		if state.current != endpoint {
+			// we've already switched to another endpoint
+			return;
+		}
+
+		state.errors += 1;
+		if state.errors >= self.max_errors && self.endpoints > 1 {
+			state.current = (state.current + 1) % self.endpoints;
+			state.errors = 0;
+			warn!(target: "bridge", "Endpoint #{} failed {} times in a row, switching to endpoint #{}", endpoint, self.max_errors, state.current);
+		}
+	}
+}
+
+/// Transport sending requests to one of the endpoints and switching to the next one
+/// after `max_errors` consecutive connection errors or requests without response within `response_timeout`.
+pub struct Failover<T> {
+	id: atomic::AtomicUsize,
+	transports: Vec<T>,
+	shared: Arc<Shared>,
+}
+
+impl<T> Failover<T> {
+	/// Creates new transport. Panics if there are no `transports`.
+	pub fn new(transports: Vec<T>, max_errors: u32, timer: Timer, response_timeout: Duration) -> Self {
+		assert!(!transports.is_empty(), "failover requires at least one transport");
+		Failover {
+			id: atomic::AtomicUsize::new(1),
+			shared: Arc::new(Shared {
+				state: Mutex::new(State {
+					current: 0,
+					errors: 0,
+				}),
+				endpoints: transports.len(),
+				max_errors,
+				timer,
+				response_timeout,
+			}),
+			transports,
+		}
+	}
+
+	fn request<F, S>(&self, send: S) -> Request<F> where S: FnOnce(&T) -> F {
+		let endpoint = self.shared.current();
+		Request {
+			future: send(&self.transports[endpoint]),
+			endpoint,
+			timeout: Some(self.shared.timer.sleep(self.shared.response_timeout)),
+			shared: self.shared.clone(),
+		}
+	}
+}
+
+impl<T: Transport> Transport for Failover<T> {
+	type Out = Request<T::Out>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		// ids have to be unique across endpoints, so they can't come from the inner transports
+		let id = self.id.fetch_add(1, atomic::Ordering::Relaxed);
+		(id, web3::helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		self.request(|transport| transport.send(id, request))
+	}
+}
+
+impl<T: BatchTransport> BatchTransport for Failover<T> {
+	type Batch = Request<T::Batch>;
+
+	fn send_batch<I>(&self, requests: I) -> Self::Batch where I: IntoIterator<Item = (RequestId, rpc::Call)> {
+		self.request(|transport| transport.send_batch(requests))
+	}
+}
+
+impl<T: PubSub> PubSub for Failover<T> {
+	type Notifications = T::Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		self.transports.iter().all(PubSub::supports_subscriptions)
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		self.transports[self.shared.current()].notifications(subscription)
+	}
+}
+
+/// A future representing a response from one of the endpoints.
+///
+/// Request without response within the timeout is counted as a failure of the endpoint, but it's still awaited.
+/// Request dropped before receiving response, e.g. because it's no longer needed, is not counted.
+pub struct Request<F> {
+	future: F,
+	endpoint: usize,
+	shared: Arc<Shared>,
+	/// `None` once the request has been counted as a failure.
+	timeout: Option<Sleep>,
+}
+
+impl<F: Future<Error = web3::Error>> Future for Request<F> {
+	type Item = F::Item;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let result = self.future.poll();
+		match result {
+			Ok(Async::NotReady) => {
+				let expired = match self.timeout {
+					Some(ref mut timeout) => timeout.poll().map(|timeout| timeout.is_ready()).unwrap_or(true),
+					None => false,
+				};
+				if expired {
+					self.timeout = None;
+					self.shared.on_failure(self.endpoint);
+				}
+				return Ok(Async::NotReady);
+			},
+			Err(ref err) if is_connection_error(err) => if self.timeout.is_some() {
+				self.shared.on_failure(self.endpoint);
+			},
+			// any other response means that the node is alive
+			_ => self.shared.on_success(self.endpoint),
+		}
+
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use futures::{self, Future};
+	use tokio_timer::Timer;
+	use web3::{self, Transport, RequestId};
+	use rpc;
+	use super::Failover;
+
+	/// Endpoint responding with the value, failing with connection error or never responding if it's `None`.
+	struct Endpoint(Option<Result<&'static str, ()>>);
+
+	impl Transport for Endpoint {
+		type Out = web3::Result<rpc::Value>;
+
+		fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+			(0, web3::helpers::build_request(0, method, params))
+		}
+
+		fn send(&self, _id: RequestId, _request: rpc::Call) -> Self::Out {
+			match self.0 {
+				Some(Ok(response)) => Box::new(futures::finished(rpc::Value::String(response.into()))),
+				Some(Err(_)) => Box::new(futures::failed(web3::Error::Transport("connection refused".into()))),
+				None => Box::new(futures::empty()),
+			}
+		}
+	}
+
+	fn failover(endpoints: Vec<Endpoint>, max_errors: u32) -> Failover<Endpoint> {
+		Failover::new(endpoints, max_errors, Timer::default(), Duration::from_millis(50))
+	}
+
+	#[test]
+	fn test_failover_switches_endpoint_after_consecutive_errors() {
+		let transport = failover(vec![Endpoint(Some(Err(()))), Endpoint(Some(Ok("0x2")))], 2);
+
+		assert!(transport.execute("eth_blockNumber", vec![]).wait().is_err());
+		assert!(transport.execute("eth_blockNumber", vec![]).wait().is_err());
+		assert_eq!(rpc::Value::String("0x2".into()), transport.execute("eth_blockNumber", vec![]).wait().unwrap());
+	}
+
+	#[test]
+	fn test_failover_switches_endpoint_after_timeouts() {
+		let transport = failover(vec![Endpoint(None), Endpoint(Some(Ok("0x2")))], 1);
+
+		// the request is given up only after the endpoint has been counted as failed
+		let request = transport.execute("eth_blockNumber", vec![]);
+		assert!(request.select2(Timer::default().sleep(Duration::from_millis(200))).wait().is_ok());
+		assert_eq!(rpc::Value::String("0x2".into()), transport.execute("eth_blockNumber", vec![]).wait().unwrap());
+	}
+
+	#[test]
+	fn test_failover_does_not_count_dropped_requests() {
+		let transport = failover(vec![Endpoint(Some(Ok("0x1"))), Endpoint(Some(Ok("0x2")))], 2);
+
+		// requests which are no longer needed before their response
+		drop(transport.execute("eth_blockNumber", vec![]));
+		drop(transport.execute("eth_blockNumber", vec![]));
+		drop(transport.execute("eth_blockNumber", vec![]));
+		assert_eq!(rpc::Value::String("0x1".into()), transport.execute("eth_blockNumber", vec![]).wait().unwrap());
+	}
+}
@@ -0,0 +1,277 @@
+//! HTTP transport.
+//!
+//! Unlike the web3 one, it sends custom headers and credentials with every request,
+//! which are required by hosted node providers.
+
+use std::io;
+use std::sync::atomic;
+use futures::{Future, Stream, future};
+use futures::sync::{mpsc, oneshot};
+use hyper::{self, Client, Headers, Method, Uri};
+use hyper::client::HttpConnector;
+use hyper::header::{Authorization, Basic, Bearer, ContentLength, ContentType};
+use openssl::ssl::{SslConnector, SslMethod};
+use tokio_core::reactor::Handle;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_service::Service;
+use web3::{self, Transport, BatchTransport, RequestId};
+use web3::helpers;
+use rpc;
+use config::{HttpAuth, HttpAuthorization, Proxy, CertificateFingerprint};
+use error::{Error, ResultExt};
+use super::{proxy, tls};
+
+type PendingRequest = (String, oneshot::Sender<Result<hyper::Chunk, web3::Error>>);
+
+/// HTTP transport.
+pub struct Http {
+	id: atomic::AtomicUsize,
+	requests: mpsc::UnboundedSender<PendingRequest>,
+}
+
+impl Http {
+	/// Creates new HTTP transport within existing event loop.
+	/// Connections are tunneled through the `proxy` if specified.
+	/// Certificates of `https` urls must match one of the `pins`, unless there are none.
+	pub fn with_event_loop(url: &str, auth: &HttpAuth, proxy: Option<&Proxy>, pins: &[CertificateFingerprint], handle: &Handle) -> Result<Self, Error> {
+		let uri: Uri = url.parse().chain_err(|| format!("Invalid http url {}", url))?;
+		match uri.scheme() {
+			Some("http") | Some("https") => {},
+			_ => return Err(format!("Unsupported http url {}", url).into()),
+		}
+
+		let headers = headers(auth);
+		let client = Client::configure()
+			.connector(HttpsConnector::new(proxy, pins, handle)?)
+			.build(handle);
+		let (requests_tx, requests_rx) = mpsc::unbounded::<PendingRequest>();
+
+		let spawn_handle = handle.clone();
+		handle.spawn(requests_rx.for_each(move |(body, response_tx)| {
+			let mut request = hyper::Request::new(Method::Post, uri.clone());
+			request.headers_mut().extend(headers.iter());
+			// some nodes do not accept chunked requests
+			request.headers_mut().set(ContentLength(body.len() as u64));
+			request.set_body(body);
+
+			let response = client.request(request)
+				.map_err(http_error)
+				.and_then(|response| {
+					if !response.status().is_success() {
+						let err = web3::Error::Transport(format!("Unexpected response status code: {}", response.status()));
+						return future::Either::A(future::err(err));
+					}
+					future::Either::B(response.body().concat2().map_err(http_error))
+				})
+				.then(move |result| {
+					let _ = response_tx.send(result);
+					Ok(())
+				});
+
+			// requests are sent concurrently
+			spawn_handle.spawn(response);
+			Ok(())
+		}));
+
+		let result = Http {
+			id: atomic::AtomicUsize::new(1),
+			requests: requests_tx,
+		};
+		Ok(result)
+	}
+
+	fn request(&self, id: RequestId, request: &rpc::Request) -> Box<Future<Item = hyper::Chunk, Error = web3::Error>> {
+		trace!(target: "bridge", "[{}] http request", id);
+		let (tx, rx) = oneshot::channel();
+		if self.requests.unbounded_send((helpers::to_string(request), tx)).is_err() {
+			return Box::new(future::err(web3::Error::Transport("http client is gone".into())));
+		}
+
+		Box::new(rx.then(|result| match result {
+			Ok(result) => result,
+			Err(_) => Err(web3::Error::Transport("http request canceled".into())),
+		}))
+	}
+}
+
+impl Transport for Http {
+	type Out = Box<Future<Item = rpc::Value, Error = web3::Error>>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		let id = self.id.fetch_add(1, atomic::Ordering::AcqRel);
+		(id, helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		Box::new(self.request(id, &rpc::Request::Single(request)).and_then(|body| {
+			match helpers::to_response_from_slice(&body)? {
+				rpc::Response::Single(output) => helpers::to_result_from_output(output),
+				rpc::Response::Batch(_) => Err(web3::Error::InvalidResponse("Expected single, got batch.".into())),
+			}
+		}))
+	}
+}
+
+impl BatchTransport for Http {
+	type Batch = Box<Future<Item = Vec<Result<rpc::Value, web3::Error>>, Error = web3::Error>>;
+
+	fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (RequestId, rpc::Call)> {
+		let requests = requests.into_iter().collect::<Vec<_>>();
+		let id = requests.first().map(|&(id, _)| id).unwrap_or_default();
+		let request = rpc::Request::Batch(requests.into_iter().map(|(_, request)| request).collect());
+
+		Box::new(self.request(id, &request).and_then(|body| {
+			match helpers::to_response_from_slice(&body)? {
+				rpc::Response::Batch(outputs) => helpers::to_results_from_outputs(outputs),
+				rpc::Response::Single(_) => Err(web3::Error::InvalidResponse("Expected batch, got single.".into())),
+			}
+		}))
+	}
+}
+
+fn http_error(err: hyper::Error) -> web3::Error {
+	web3::Error::Transport(format!("{}", err))
+}
+
+/// Returns headers sent with every request.
+fn headers(auth: &HttpAuth) -> Headers {
+	let mut headers = Headers::new();
+	headers.set(ContentType::json());
+	for (name, value) in &auth.headers {
+		headers.set_raw(name.clone(), value.clone());
+	}
+
+	match auth.authorization {
+		Some(HttpAuthorization::Bearer(ref token)) => headers.set(Authorization(Bearer {
+			token: token.clone(),
+		})),
+		Some(HttpAuthorization::Basic { ref username, ref password }) => headers.set(Authorization(Basic {
+			username: username.clone(),
+			password: password.clone(),
+		})),
+		None => {},
+	}
+
+	headers
+}
+
+/// Either plain or encrypted connection.
+pub trait Io: AsyncRead + AsyncWrite {}
+
+impl<T: AsyncRead + AsyncWrite> Io for T {}
+
+/// Connector opening plain connections to `http` urls and encrypted ones to `https` urls.
+pub struct HttpsConnector {
+	http: HttpConnector,
+	tls: SslConnector,
+	pins: Vec<CertificateFingerprint>,
+	proxy: Option<Proxy>,
+	handle: Handle,
+}
+
+impl HttpsConnector {
+	pub fn new(proxy: Option<&Proxy>, pins: &[CertificateFingerprint], handle: &Handle) -> Result<Self, Error> {
+		let mut http = HttpConnector::new(1, handle);
+		http.enforce_http(false);
+		let tls = SslConnector::builder(SslMethod::tls()).chain_err(|| "Cannot initialize tls")?.build();
+
+		let result = HttpsConnector {
+			http,
+			tls,
+			pins: pins.to_vec(),
+			proxy: proxy.cloned(),
+			handle: handle.clone(),
+		};
+		Ok(result)
+	}
+}
+
+impl Service for HttpsConnector {
+	type Request = Uri;
+	type Response = Box<Io>;
+	type Error = io::Error;
+	type Future = Box<Future<Item = Self::Response, Error = Self::Error>>;
+
+	fn call(&self, uri: Uri) -> Self::Future {
+		let host = uri.host().unwrap_or_default().to_owned();
+		let https = uri.scheme() == Some("https");
+		let stream: Box<Future<Item = _, Error = _>> = match self.proxy {
+			// the tunnel is opened for plain connections as well, so the proxy does not need to understand rpc requests
+			Some(ref proxy) => {
+				let port = uri.port().unwrap_or(if https { 443 } else { 80 });
+				proxy::connect(&host, port, Some(proxy), &self.handle)
+			},
+			None => Box::new(self.http.call(uri)),
+		};
+
+		if https {
+			let tls = self.tls.clone();
+			let pins = self.pins.clone();
+			Box::new(stream
+				.and_then(move |stream| tls::connect(&tls, &host, stream, &pins))
+				.map(|stream| Box::new(stream) as Box<Io>))
+		} else {
+			Box::new(stream.map(|stream| Box::new(stream) as Box<Io>))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+	use std::net::TcpListener;
+	use std::sync::mpsc;
+	use std::thread;
+	use tokio_core::reactor::Core;
+	use web3::Transport;
+	use rpc;
+	use config::{HttpAuth, HttpAuthorization};
+	use super::Http;
+
+	/// Responds to a single request and returns it.
+	fn serve_once(listener: TcpListener, response: &'static str) -> mpsc::Receiver<String> {
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let mut request = Vec::new();
+			let mut buffer = [0u8; 1024];
+			// the request body is a single json object
+			while !request.ends_with(b"}") {
+				let read = stream.read(&mut buffer).unwrap();
+				request.extend_from_slice(&buffer[..read]);
+			}
+
+			write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", response.len(), response).unwrap();
+			tx.send(String::from_utf8(request).unwrap()).unwrap();
+		});
+		rx
+	}
+
+	#[test]
+	fn test_http_sends_credentials() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		let requests = serve_once(listener, r#"{"jsonrpc":"2.0","id":1,"result":"0x10"}"#);
+
+		let mut auth = HttpAuth::default();
+		auth.headers.insert("X-Api-Key".into(), "key".into());
+		auth.authorization = Some(HttpAuthorization::Bearer("secret".into()));
+
+		let mut core = Core::new().unwrap();
+		let http = Http::with_event_loop(&url, &auth, None, &[], &core.handle()).unwrap();
+		let result = core.run(http.execute("eth_blockNumber", vec![])).unwrap();
+		assert_eq!(rpc::Value::String("0x10".into()), result);
+
+		let request = requests.recv().unwrap().to_lowercase();
+		assert!(request.starts_with("post / http/1.1"), "unexpected request: {}", request);
+		assert!(request.contains("authorization: bearer secret"), "missing authorization: {}", request);
+		assert!(request.contains("x-api-key: key"), "missing custom header: {}", request);
+		assert!(request.contains(r#""method":"eth_blockNumber""#.to_lowercase().as_str()), "missing body: {}", request);
+	}
+
+	#[test]
+	fn test_http_rejects_unsupported_url() {
+		let core = Core::new().unwrap();
+		assert!(Http::with_event_loop("ftp://127.0.0.1", &HttpAuth::default(), None, &[], &core.handle()).is_err());
+	}
+}
@@ -0,0 +1,172 @@
+//! Transport wrapper recording statistics of sent requests.
+
+use std::sync::Arc;
+use std::time::Instant;
+use futures::{Future, Poll, Async};
+use web3::{self, Transport, BatchTransport, RequestId};
+use rpc;
+use metrics::{RequestMetrics, Outcome};
+use super::PubSub;
+
+fn method(call: &rpc::Call) -> &str {
+	match *call {
+		rpc::Call::MethodCall(ref call) => &call.method,
+		rpc::Call::Notification(ref call) => &call.method,
+		rpc::Call::Invalid(_) => "invalid",
+	}
+}
+
+/// Transport recording number, outcome and latency of requests sent with every method.
+pub struct Metered<T> {
+	transport: T,
+	metrics: Arc<RequestMetrics>,
+}
+
+impl<T> Metered<T> {
+	pub fn new(transport: T, metrics: Arc<RequestMetrics>) -> Self {
+		Metered {
+			transport,
+			metrics,
+		}
+	}
+
+	fn request<F>(&self, methods: Vec<String>, future: F) -> Request<F> {
+		Request {
+			future,
+			methods,
+			metrics: self.metrics.clone(),
+			start: Instant::now(),
+			done: false,
+		}
+	}
+}
+
+impl<T: Transport> Transport for Metered<T> {
+	type Out = Request<T::Out>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		self.transport.prepare(method, params)
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		let methods = vec![method(&request).to_owned()];
+		self.request(methods, self.transport.send(id, request))
+	}
+}
+
+impl<T: BatchTransport> BatchTransport for Metered<T> {
+	type Batch = Request<T::Batch>;
+
+	fn send_batch<I>(&self, requests: I) -> Self::Batch where I: IntoIterator<Item = (RequestId, rpc::Call)> {
+		let requests = requests.into_iter().collect::<Vec<_>>();
+		// each call of the batch is accounted separately
+		let methods = requests.iter().map(|request| method(&request.1).to_owned()).collect();
+		self.request(methods, self.transport.send_batch(requests))
+	}
+}
+
+impl<T: PubSub> PubSub for Metered<T> {
+	type Notifications = T::Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		self.transport.supports_subscriptions()
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		self.transport.notifications(subscription)
+	}
+}
+
+/// A future representing a response to a metered request.
+///
+/// Request dropped before receiving response is counted as a timeout.
+pub struct Request<F> {
+	future: F,
+	methods: Vec<String>,
+	metrics: Arc<RequestMetrics>,
+	start: Instant,
+	done: bool,
+}
+
+impl<F> Request<F> {
+	fn record(&mut self, outcome: Outcome) {
+		self.done = true;
+		let latency = self.start.elapsed();
+		for method in &self.methods {
+			self.metrics.record(method, outcome, latency);
+		}
+	}
+}
+
+impl<F: Future<Error = web3::Error>> Future for Request<F> {
+	type Item = F::Item;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let result = self.future.poll();
+		match result {
+			Ok(Async::NotReady) => return Ok(Async::NotReady),
+			Ok(Async::Ready(_)) => self.record(Outcome::Success),
+			Err(_) => self.record(Outcome::Error),
+		}
+
+		result
+	}
+}
+
+impl<F> Drop for Request<F> {
+	fn drop(&mut self) {
+		if !self.done {
+			self.record(Outcome::Timeout);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use futures::{self, Future};
+	use web3::{self, Transport, RequestId};
+	use rpc;
+	use metrics::RequestMetrics;
+	use super::Metered;
+
+	struct Endpoint;
+
+	impl Transport for Endpoint {
+		type Out = web3::Result<rpc::Value>;
+
+		fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+			(0, web3::helpers::build_request(0, method, params))
+		}
+
+		fn send(&self, _id: RequestId, request: rpc::Call) -> Self::Out {
+			match super::method(&request) {
+				"eth_call" => Box::new(futures::failed(web3::Error::Transport("connection refused".into()))),
+				"eth_getLogs" => Box::new(futures::empty()),
+				_ => Box::new(futures::finished(rpc::Value::String("0x1".into()))),
+			}
+		}
+	}
+
+	#[test]
+	fn test_metered_transport_records_outcomes() {
+		let metrics = Arc::new(RequestMetrics::default());
+		let transport = Metered::new(Endpoint, metrics.clone());
+
+		assert!(transport.execute("eth_blockNumber", vec![]).wait().is_ok());
+		assert!(transport.execute("eth_blockNumber", vec![]).wait().is_ok());
+		assert!(transport.execute("eth_call", vec![]).wait().is_err());
+		// request which never completes, e.g. because of timeout
+		drop(transport.execute("eth_getLogs", vec![]));
+
+		let counts = metrics.snapshot().into_iter()
+			.map(|method| (method.method, method.requests, method.errors, method.timeouts))
+			.collect::<Vec<_>>();
+		assert_eq!(vec![
+			("eth_blockNumber".to_owned(), 2, 0, 0),
+			("eth_call".to_owned(), 1, 1, 0),
+			("eth_getLogs".to_owned(), 1, 0, 1),
+		], counts);
+	}
+}
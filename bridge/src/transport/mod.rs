@@ -0,0 +1,222 @@
+//! Transports used to connect to the nodes.
+
+mod failover;
+mod http;
+mod metered;
+mod proxy;
+mod rate_limit;
+mod reconnect;
+mod tls;
+mod ws;
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::{Future, Stream, stream};
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use web3::{self, Transport, BatchTransport, RequestId};
+use web3::transports::ipc::Ipc;
+use config::{Node, Endpoint};
+use metrics::RequestMetrics;
+use error::{Error, ErrorKind};
+use rpc;
+
+pub use self::failover::Failover;
+pub use self::http::{Http, HttpsConnector};
+pub use self::metered::Metered;
+pub use self::rate_limit::RateLimited;
+pub use self::reconnect::Reconnecting;
+pub use self::ws::{WebSocket, Notifications};
+
+/// Transport able to deliver notifications of `eth_subscribe` subscriptions.
+pub trait PubSub: Transport {
+	type Notifications: Stream<Item = rpc::Value, Error = web3::Error>;
+
+	/// Returns false if notifications can't be delivered using this transport.
+	fn supports_subscriptions(&self) -> bool;
+
+	/// Returns stream of notifications of subscription with given id.
+	/// The stream ends when the subscription is lost, e.g. after reconnecting.
+	fn notifications(&self, subscription: &str) -> Self::Notifications;
+}
+
+impl<'a, T: PubSub + ?Sized> PubSub for &'a T {
+	type Notifications = T::Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		(**self).supports_subscriptions()
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		(**self).notifications(subscription)
+	}
+}
+
+impl<T: PubSub + ?Sized> PubSub for Arc<T> {
+	type Notifications = T::Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		(**self).supports_subscriptions()
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		(**self).notifications(subscription)
+	}
+}
+
+/// Delay between consecutive connection attempts.
+const RECONNECT_INTERVAL_SECS: u64 = 1;
+
+/// Returns false for requests which must not be sent twice, e.g. transaction submissions.
+fn is_resendable(call: &rpc::Call) -> bool {
+	match *call {
+		rpc::Call::MethodCall(ref call) => call.method != "eth_sendTransaction" && call.method != "eth_sendRawTransaction",
+		_ => true,
+	}
+}
+
+/// Returns true if the error means that the node could not be reached.
+fn is_connection_error(err: &web3::Error) -> bool {
+	match *err {
+		web3::Error::Transport(_) | web3::Error::Unreachable => true,
+		_ => false,
+	}
+}
+
+/// `rpc::Call` does not implement `Clone`.
+fn clone_call(call: &rpc::Call) -> rpc::Call {
+	match *call {
+		rpc::Call::MethodCall(ref call) => rpc::Call::MethodCall(rpc::MethodCall {
+			jsonrpc: call.jsonrpc,
+			method: call.method.clone(),
+			params: call.params.clone(),
+			id: call.id.clone(),
+		}),
+		rpc::Call::Notification(ref call) => rpc::Call::Notification(rpc::Notification {
+			jsonrpc: call.jsonrpc,
+			method: call.method.clone(),
+			params: call.params.clone(),
+		}),
+		rpc::Call::Invalid(ref id) => rpc::Call::Invalid(id.clone()),
+	}
+}
+
+/// Time within which the slowest requests to the `node` are allowed to run,
+/// so that the connection is not considered lost before the response is due.
+fn response_timeout(node: &Node) -> Duration {
+	let timeouts = &node.request_timeouts;
+	[node.request_timeout, timeouts.logs, timeouts.call, timeouts.send_transaction]
+		.iter()
+		.max()
+		.cloned()
+		.unwrap_or(node.request_timeout)
+}
+
+/// Transport used to communicate with the node.
+pub type NodeTransport = RateLimited<Metered<Failover<AnyTransport>>>;
+
+/// Connects to all given `endpoints` of the node. Fails only if none of them can be connected.
+/// Requests sent to the node are recorded in `metrics`.
+pub fn connect(node: &Node, endpoints: &[Endpoint], handle: &Handle, timer: &Timer, metrics: Arc<RequestMetrics>) -> Result<NodeTransport, Error> {
+	let mut transports = Vec::new();
+	let mut last_error = None;
+	for endpoint in endpoints {
+		match AnyTransport::new(endpoint, node, handle, timer) {
+			Ok(transport) => transports.push(transport),
+			Err(err) => {
+				warn!(target: "bridge", "Cannot connect to {:?}: {}", endpoint, err);
+				last_error = Some(err);
+			},
+		}
+	}
+
+	match last_error {
+		Some(err) if transports.is_empty() => Err(err),
+		_ if transports.is_empty() => Err("No node endpoints specified".into()),
+		_ => {
+			let failover = Failover::new(transports, node.failover_errors, timer.clone(), response_timeout(node));
+			let transport = Metered::new(failover, metrics);
+			Ok(RateLimited::new(transport, node.max_rps, timer.clone()))
+		},
+	}
+}
+
+/// Transport connected to one of the supported node endpoints.
+pub enum AnyTransport {
+	Ipc(Reconnecting<Ipc>),
+	Ws(WebSocket),
+	Http(Http),
+}
+
+impl AnyTransport {
+	/// Connects to the `node` endpoint within existing event loop.
+	pub fn new(endpoint: &Endpoint, node: &Node, handle: &Handle, timer: &Timer) -> Result<Self, Error> {
+		let result = match *endpoint {
+			Endpoint::Ipc(ref path) => {
+				// connection is reconnected only after a transport error or after the whole timeout of the slowest request
+				let response_timeout = response_timeout(node);
+				let path = path.clone();
+				let remote = handle.remote().clone();
+				let ipc = Reconnecting::new(move || {
+					let handle = remote.handle().expect("requests are always sent from within the event loop; qed");
+					Ipc::with_event_loop(&path, &handle)
+				}, timer.clone(), response_timeout).map_err(ErrorKind::Web3)?;
+				AnyTransport::Ipc(ipc)
+			},
+			Endpoint::Ws(ref url) => AnyTransport::Ws(WebSocket::with_event_loop(url, node.proxy.as_ref(), &node.pinned_certificates, handle, timer.clone())?),
+			Endpoint::Http(ref url) => AnyTransport::Http(Http::with_event_loop(url, &node.http_auth, node.proxy.as_ref(), &node.pinned_certificates, handle)?),
+		};
+
+		Ok(result)
+	}
+}
+
+impl Transport for AnyTransport {
+	type Out = Box<Future<Item = rpc::Value, Error = web3::Error>>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		match *self {
+			AnyTransport::Ipc(ref ipc) => ipc.prepare(method, params),
+			AnyTransport::Ws(ref ws) => ws.prepare(method, params),
+			AnyTransport::Http(ref http) => http.prepare(method, params),
+		}
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		match *self {
+			AnyTransport::Ipc(ref ipc) => Box::new(ipc.send(id, request)),
+			AnyTransport::Ws(ref ws) => Box::new(ws.send(id, request)),
+			AnyTransport::Http(ref http) => http.send(id, request),
+		}
+	}
+}
+
+impl BatchTransport for AnyTransport {
+	type Batch = Box<Future<Item = Vec<Result<rpc::Value, web3::Error>>, Error = web3::Error>>;
+
+	fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (RequestId, rpc::Call)> {
+		match *self {
+			AnyTransport::Ipc(ref ipc) => Box::new(ipc.send_batch(requests)),
+			AnyTransport::Ws(ref ws) => Box::new(ws.send_batch(requests)),
+			AnyTransport::Http(ref http) => http.send_batch(requests),
+		}
+	}
+}
+
+impl PubSub for AnyTransport {
+	type Notifications = Box<Stream<Item = rpc::Value, Error = web3::Error>>;
+
+	fn supports_subscriptions(&self) -> bool {
+		match *self {
+			AnyTransport::Ipc(_) | AnyTransport::Http(_) => false,
+			AnyTransport::Ws(ref ws) => ws.supports_subscriptions(),
+		}
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		match *self {
+			AnyTransport::Ipc(_) | AnyTransport::Http(_) => Box::new(stream::empty()),
+			AnyTransport::Ws(ref ws) => Box::new(ws.notifications(subscription)),
+		}
+	}
+}
@@ -0,0 +1,191 @@
+//! Transport wrapper limiting the number of requests sent to the node.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use futures::{Future, Poll, Async};
+use tokio_timer::{Timer, Sleep};
+use web3::{self, Transport, BatchTransport, RequestId};
+use rpc;
+use super::PubSub;
+
+/// Token bucket, which allows bursts of up to `rate` requests.
+struct Bucket {
+	/// Requests per second.
+	rate: f64,
+	/// Available tokens. Negative if tokens have already been reserved by waiting requests.
+	tokens: f64,
+	last_update: Instant,
+}
+
+fn as_secs(duration: Duration) -> f64 {
+	duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+impl Bucket {
+	fn new(rate: u32, now: Instant) -> Self {
+		Bucket {
+			rate: rate as f64,
+			tokens: rate as f64,
+			last_update: now,
+		}
+	}
+
+	/// Reserves `n` tokens. Returns how long to wait before they are available.
+	fn reserve(&mut self, n: usize, now: Instant) -> Duration {
+		let elapsed = now.duration_since(self.last_update);
+		self.last_update = now;
+		self.tokens = (self.tokens + as_secs(elapsed) * self.rate).min(self.rate);
+		self.tokens -= n as f64;
+
+		if self.tokens >= 0.0 {
+			return Duration::from_secs(0);
+		}
+
+		let wait = -self.tokens / self.rate;
+		Duration::new(wait.trunc() as u64, (wait.fract() * 1_000_000_000.0) as u32)
+	}
+}
+
+/// Transport which sends at most `max_rps` requests per second.
+/// Without the limit, requests are passed directly to the inner transport.
+pub struct RateLimited<T> {
+	transport: Arc<T>,
+	bucket: Option<Mutex<Bucket>>,
+	timer: Timer,
+}
+
+impl<T> RateLimited<T> {
+	pub fn new(transport: T, max_rps: Option<u32>, timer: Timer) -> Self {
+		RateLimited {
+			transport: Arc::new(transport),
+			bucket: max_rps.map(|rate| Mutex::new(Bucket::new(rate, Instant::now()))),
+			timer,
+		}
+	}
+
+	fn request<P, F>(&self, calls: usize, payload: P, send: fn(&T, P) -> F) -> Request<T, P, F> {
+		let wait = match self.bucket {
+			Some(ref bucket) => bucket.lock().expect("lock is never poisoned; qed").reserve(calls, Instant::now()),
+			None => Duration::from_secs(0),
+		};
+
+		let state = if wait == Duration::from_secs(0) {
+			RequestState::Sending(send(&self.transport, payload))
+		} else {
+			trace!(target: "bridge", "Rate limit reached, delaying request by {:?}", wait);
+			RequestState::Wait {
+				sleep: self.timer.sleep(wait),
+				payload: Some(payload),
+			}
+		};
+
+		Request {
+			transport: self.transport.clone(),
+			send,
+			state,
+		}
+	}
+}
+
+impl<T: Transport> Transport for RateLimited<T> {
+	type Out = Request<T, (RequestId, rpc::Call), T::Out>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		self.transport.prepare(method, params)
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		self.request(1, (id, request), |transport, (id, request)| transport.send(id, request))
+	}
+}
+
+impl<T: BatchTransport> BatchTransport for RateLimited<T> {
+	type Batch = Request<T, Vec<(RequestId, rpc::Call)>, T::Batch>;
+
+	fn send_batch<I>(&self, requests: I) -> Self::Batch where I: IntoIterator<Item = (RequestId, rpc::Call)> {
+		let requests = requests.into_iter().collect::<Vec<_>>();
+		// each call of the batch counts against the limit
+		self.request(requests.len(), requests, |transport, requests| transport.send_batch(requests))
+	}
+}
+
+impl<T: PubSub> PubSub for RateLimited<T> {
+	type Notifications = T::Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		self.transport.supports_subscriptions()
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		self.transport.notifications(subscription)
+	}
+}
+
+enum RequestState<P, F> {
+	/// Waiting for the rate limit.
+	Wait {
+		sleep: Sleep,
+		payload: Option<P>,
+	},
+	/// Waiting for the response.
+	Sending(F),
+}
+
+/// A future representing a response to a rate limited request.
+pub struct Request<T, P, F> {
+	transport: Arc<T>,
+	send: fn(&T, P) -> F,
+	state: RequestState<P, F>,
+}
+
+impl<T, P, F: Future<Error = web3::Error>> Future for Request<T, P, F> {
+	type Item = F::Item;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				RequestState::Wait { ref mut sleep, ref mut payload } => {
+					if let Ok(Async::NotReady) = sleep.poll() {
+						return Ok(Async::NotReady);
+					}
+
+					let payload = payload.take().expect("payload is taken only once, when leaving this state; qed");
+					RequestState::Sending((self.send)(&self.transport, payload))
+				},
+				RequestState::Sending(ref mut future) => return future.poll(),
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use super::Bucket;
+
+	#[test]
+	fn test_bucket_allows_burst_up_to_rate() {
+		let now = Instant::now();
+		let mut bucket = Bucket::new(2, now);
+
+		assert_eq!(Duration::from_secs(0), bucket.reserve(1, now));
+		assert_eq!(Duration::from_secs(0), bucket.reserve(1, now));
+		assert_eq!(Duration::from_millis(500), bucket.reserve(1, now));
+		assert_eq!(Duration::from_secs(1), bucket.reserve(1, now));
+	}
+
+	#[test]
+	fn test_bucket_refills_over_time() {
+		let now = Instant::now();
+		let mut bucket = Bucket::new(10, now);
+
+		assert_eq!(Duration::from_secs(0), bucket.reserve(10, now));
+		assert_eq!(Duration::from_secs(0), bucket.reserve(5, now + Duration::from_millis(500)));
+		// bucket never holds more than `rate` tokens
+		assert_eq!(Duration::from_secs(0), bucket.reserve(10, now + Duration::from_secs(60)));
+		assert_eq!(Duration::from_millis(200), bucket.reserve(2, now + Duration::from_secs(60)));
+	}
+}
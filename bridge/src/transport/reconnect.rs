@@ -0,0 +1,291 @@
+//! Transport wrapper which reconnects to the node after the connection is lost.
+
+use std::sync::{atomic, Arc, Mutex};
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use tokio_timer::{Timer, Sleep};
+use web3::{self, Transport, BatchTransport, RequestId};
+use rpc;
+use super::{RECONNECT_INTERVAL_SECS, clone_call, is_resendable, is_connection_error};
+
+struct Shared<T> {
+	/// Current connection together with its generation, `None` if it has been lost.
+	transport: Mutex<Option<(usize, T)>>,
+	/// Incremented after every successful reconnection.
+	generation: atomic::AtomicUsize,
+	connect: Box<Fn() -> Result<T, web3::Error> + Send + Sync>,
+	timer: Timer,
+	/// Connection is considered lost if there is no response within this time.
+	response_timeout: Duration,
+}
+
+impl<T> Shared<T> {
+	/// Sends request using current connection, reconnecting first if needed.
+	fn send<F>(&self, send: &Fn(&T) -> F) -> RequestState<F> {
+		let mut transport = self.transport.lock().expect("lock is never poisoned; qed");
+		if transport.is_none() {
+			match (self.connect)() {
+				Ok(t) => {
+					info!(target: "bridge", "Reconnected to the node");
+					let generation = self.generation.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+					*transport = Some((generation, t));
+				},
+				Err(err) => {
+					warn!(target: "bridge", "Cannot reconnect to the node: {:?}", err);
+					return self.wait();
+				},
+			}
+		}
+
+		let (generation, ref transport) = *transport.as_ref().expect("transport has been reconnected above; qed");
+		RequestState::Sending {
+			generation,
+			future: send(transport),
+			timeout: self.timer.sleep(self.response_timeout),
+		}
+	}
+
+	fn wait<F>(&self) -> RequestState<F> {
+		RequestState::Wait(self.timer.sleep(Duration::from_secs(RECONNECT_INTERVAL_SECS)))
+	}
+
+	/// Drops the connection, unless it has already been reestablished.
+	fn disconnect(&self, generation: usize) {
+		let mut transport = self.transport.lock().expect("lock is never poisoned; qed");
+		let lost = match *transport {
+			Some((current, _)) => current == generation,
+			None => false,
+		};
+
+		if lost {
+			warn!(target: "bridge", "Connection to the node lost");
+			*transport = None;
+		}
+	}
+}
+
+/// Transport which transparently reconnects to the node.
+pub struct Reconnecting<T> {
+	id: atomic::AtomicUsize,
+	shared: Arc<Shared<T>>,
+}
+
+impl<T: Transport> Reconnecting<T> {
+	/// Creates new transport. Fails if the initial connection cannot be established.
+	pub fn new<F>(connect: F, timer: Timer, response_timeout: Duration) -> Result<Self, web3::Error> where F: Fn() -> Result<T, web3::Error> + Send + Sync + 'static {
+		let transport = connect()?;
+		let result = Reconnecting {
+			id: atomic::AtomicUsize::new(1),
+			shared: Arc::new(Shared {
+				transport: Mutex::new(Some((0, transport))),
+				generation: atomic::AtomicUsize::new(0),
+				connect: Box::new(connect),
+				timer,
+				response_timeout,
+			}),
+		};
+		Ok(result)
+	}
+
+	fn request<F>(&self, id: RequestId, resend: bool, send: Box<Fn(&T) -> F>) -> Request<T, F> {
+		Request {
+			id,
+			state: self.shared.send(&*send),
+			send,
+			resend,
+			shared: self.shared.clone(),
+		}
+	}
+}
+
+impl<T: Transport + 'static> Transport for Reconnecting<T> {
+	type Out = Request<T, T::Out>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		// ids have to be unique across reconnections, so they can't come from the inner transport
+		let id = self.id.fetch_add(1, atomic::Ordering::Relaxed);
+		(id, web3::helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		let resend = is_resendable(&request);
+		self.request(id, resend, Box::new(move |transport: &T| transport.send(id, clone_call(&request))))
+	}
+}
+
+impl<T: BatchTransport + 'static> BatchTransport for Reconnecting<T> {
+	type Batch = Request<T, T::Batch>;
+
+	fn send_batch<I>(&self, requests: I) -> Self::Batch where I: IntoIterator<Item = (RequestId, rpc::Call)> {
+		let requests = requests.into_iter().collect::<Vec<_>>();
+		let id = requests.first().map(|&(id, _)| id).unwrap_or_default();
+		let resend = requests.iter().all(|request| is_resendable(&request.1));
+		self.request(id, resend, Box::new(move |transport: &T| {
+			transport.send_batch(requests.iter().map(|request| (request.0, clone_call(&request.1))))
+		}))
+	}
+}
+
+enum RequestState<F> {
+	/// Waiting for the response.
+	Sending {
+		generation: usize,
+		future: F,
+		timeout: Sleep,
+	},
+	/// Waiting before the next reconnection attempt.
+	Wait(Sleep),
+}
+
+/// A future representing a response to a request, which is resent after reconnecting.
+pub struct Request<T, F> {
+	id: RequestId,
+	send: Box<Fn(&T) -> F>,
+	resend: bool,
+	shared: Arc<Shared<T>>,
+	state: RequestState<F>,
+}
+
+impl<T, F: Future<Error = web3::Error>> Future for Request<T, F> {
+	type Item = F::Item;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				RequestState::Sending { generation, ref mut future, ref mut timeout } => {
+					match future.poll() {
+						Err(ref err) if is_connection_error(err) => {},
+						Ok(Async::NotReady) => if let Ok(Async::NotReady) = timeout.poll() {
+							return Ok(Async::NotReady);
+						},
+						result => return result,
+					}
+
+					self.shared.disconnect(generation);
+					if !self.resend {
+						return Err(web3::Error::Transport("Connection to the node lost".into()));
+					}
+
+					self.shared.wait()
+				},
+				RequestState::Wait(ref mut sleep) => {
+					if let Ok(Async::NotReady) = sleep.poll() {
+						return Ok(Async::NotReady);
+					}
+
+					trace!(target: "bridge", "[{}] resending request", self.id);
+					self.shared.send(&*self.send)
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use std::time::Duration;
+	use futures::{self, Future};
+	use tokio_timer::Timer;
+	use web3::{self, Transport, RequestId};
+	use rpc;
+	use super::Reconnecting;
+
+	#[derive(Clone, Copy)]
+	enum Behaviour {
+		Fail,
+		Hang,
+		Slow,
+		Respond,
+	}
+
+	struct FlakyTransport {
+		behaviour: Behaviour,
+		requests: Arc<AtomicUsize>,
+	}
+
+	impl Transport for FlakyTransport {
+		type Out = web3::Result<rpc::Value>;
+
+		fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+			(0, web3::helpers::build_request(0, method, params))
+		}
+
+		fn send(&self, _id: RequestId, _request: rpc::Call) -> Self::Out {
+			self.requests.fetch_add(1, Ordering::SeqCst);
+			match self.behaviour {
+				Behaviour::Fail => Box::new(futures::failed(web3::Error::Transport("broken pipe".into()))),
+				Behaviour::Hang => Box::new(futures::empty()),
+				Behaviour::Slow => Box::new(Timer::default().sleep(Duration::from_millis(150)).then(|_| Ok::<_, web3::Error>(rpc::Value::String("0x2".into())))),
+				Behaviour::Respond => Box::new(futures::finished(rpc::Value::String("0x1".into()))),
+			}
+		}
+	}
+
+	/// Returns transport whose first connection behaves as `first` and all following ones respond.
+	fn flaky_transport(first: Behaviour, connections: Arc<AtomicUsize>, requests: Arc<AtomicUsize>) -> Reconnecting<FlakyTransport> {
+		Reconnecting::new(move || {
+			let behaviour = match connections.fetch_add(1, Ordering::SeqCst) {
+				0 => first,
+				_ => Behaviour::Respond,
+			};
+
+			Ok(FlakyTransport {
+				behaviour,
+				requests: requests.clone(),
+			})
+		}, Default::default(), Duration::from_millis(200)).unwrap()
+	}
+
+	#[test]
+	fn test_reconnecting_transport_resends_request_after_error() {
+		let connections = Arc::new(AtomicUsize::new(0));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let transport = flaky_transport(Behaviour::Fail, connections.clone(), requests.clone());
+
+		let result = transport.execute("eth_blockNumber", vec![]).wait().unwrap();
+		assert_eq!(rpc::Value::String("0x1".into()), result);
+		assert_eq!(2, connections.load(Ordering::SeqCst));
+		assert_eq!(2, requests.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn test_reconnecting_transport_resends_request_after_timeout() {
+		let connections = Arc::new(AtomicUsize::new(0));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let transport = flaky_transport(Behaviour::Hang, connections.clone(), requests.clone());
+
+		let result = transport.execute("eth_getLogs", vec![]).wait().unwrap();
+		assert_eq!(rpc::Value::String("0x1".into()), result);
+		assert_eq!(2, connections.load(Ordering::SeqCst));
+		assert_eq!(2, requests.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn test_reconnecting_transport_waits_for_slow_response() {
+		let connections = Arc::new(AtomicUsize::new(0));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let transport = flaky_transport(Behaviour::Slow, connections.clone(), requests.clone());
+
+		// the response comes after most of the timeout, which doesn't mean that the connection is lost
+		let result = transport.execute("eth_getLogs", vec![]).wait().unwrap();
+		assert_eq!(rpc::Value::String("0x2".into()), result);
+		assert_eq!(1, connections.load(Ordering::SeqCst));
+		assert_eq!(1, requests.load(Ordering::SeqCst));
+	}
+
+	#[test]
+	fn test_reconnecting_transport_does_not_resend_transaction() {
+		let connections = Arc::new(AtomicUsize::new(0));
+		let requests = Arc::new(AtomicUsize::new(0));
+		let transport = flaky_transport(Behaviour::Hang, connections.clone(), requests.clone());
+
+		assert!(transport.execute("eth_sendTransaction", vec![]).wait().is_err());
+		assert_eq!(1, connections.load(Ordering::SeqCst));
+		assert_eq!(1, requests.load(Ordering::SeqCst));
+	}
+}
@@ -0,0 +1,137 @@
+//! TLS connections with optional certificate pinning.
+
+use std::io;
+use futures::Future;
+use openssl::hash::MessageDigest;
+use openssl::ssl::SslConnector;
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_openssl::{SslConnectorExt, SslStream};
+use config::CertificateFingerprint;
+
+fn tls_error<E: ::std::fmt::Display>(err: E) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+/// Returns true if the certificate of the peer matches one of the `pins`.
+/// Any certificate is accepted if there are no pins.
+fn is_pinned<S>(stream: &SslStream<S>, pins: &[CertificateFingerprint]) -> bool {
+	if pins.is_empty() {
+		return true;
+	}
+
+	let certificate = match stream.get_ref().ssl().peer_certificate() {
+		Some(certificate) => certificate,
+		None => return false,
+	};
+
+	match certificate.digest(MessageDigest::sha256()) {
+		Ok(digest) => pins.iter().any(|pin| pin.0[..] == digest[..]),
+		Err(_) => false,
+	}
+}
+
+/// Opens TLS connection with the `host` over `stream`.
+///
+/// The certificate is verified as usual and if there are any `pins`,
+/// it must also match one of them, so that it cannot be replaced by a certificate issued by a compromised CA.
+pub fn connect<S>(connector: &SslConnector, host: &str, stream: S, pins: &[CertificateFingerprint]) -> Box<Future<Item = SslStream<S>, Error = io::Error>>
+	where S: AsyncRead + AsyncWrite + 'static
+{
+	let host = host.to_owned();
+	let pins = pins.to_vec();
+	let stream = connector.connect_async(&host, stream)
+		.map_err(tls_error)
+		.and_then(move |stream| if is_pinned(&stream, &pins) {
+			Ok(stream)
+		} else {
+			Err(tls_error(format!("Certificate of {} does not match any of the pinned certificates", host)))
+		});
+	Box::new(stream)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{self, Write};
+	use std::net::{SocketAddr, TcpListener};
+	use std::thread;
+	use futures::Future;
+	use openssl::asn1::Asn1Time;
+	use openssl::ec::{EcGroup, EcKey};
+	use openssl::hash::MessageDigest;
+	use openssl::nid::Nid;
+	use openssl::pkey::PKey;
+	use openssl::ssl::{SslAcceptor, SslConnector, SslMethod, SslVerifyMode};
+	use openssl::x509::{X509, X509NameBuilder};
+	use tokio_core::net::TcpStream;
+	use tokio_core::reactor::Core;
+	use tokio_io::io::read_to_end;
+	use config::CertificateFingerprint;
+	use super::connect;
+
+	/// Serves a single connection using self-signed certificate.
+	/// Returns address of the server and fingerprint of the certificate.
+	fn serve_once() -> (SocketAddr, CertificateFingerprint) {
+		let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+		let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+		let mut name = X509NameBuilder::new().unwrap();
+		name.append_entry_by_text("CN", "localhost").unwrap();
+		let name = name.build();
+		let mut certificate = X509::builder().unwrap();
+		certificate.set_version(2).unwrap();
+		certificate.set_subject_name(&name).unwrap();
+		certificate.set_issuer_name(&name).unwrap();
+		certificate.set_pubkey(&key).unwrap();
+		certificate.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+		certificate.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+		certificate.sign(&key, MessageDigest::sha256()).unwrap();
+		let certificate = certificate.build();
+
+		let mut fingerprint = [0u8; 32];
+		fingerprint.copy_from_slice(&certificate.digest(MessageDigest::sha256()).unwrap());
+
+		let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+		acceptor.set_private_key(&key).unwrap();
+		acceptor.set_certificate(&certificate).unwrap();
+		let acceptor = acceptor.build();
+
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		thread::spawn(move || {
+			let (stream, _) = listener.accept().unwrap();
+			// the handshake succeeds even if the client rejects the certificate afterwards
+			if let Ok(mut stream) = acceptor.accept(stream) {
+				let _ = stream.write_all(b"ok");
+			}
+		});
+
+		(address, CertificateFingerprint(fingerprint))
+	}
+
+	fn connect_pinned(address: SocketAddr, pins: &[CertificateFingerprint]) -> Result<Vec<u8>, io::Error> {
+		// the certificate is self-signed, so only the pin is verified
+		let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+		connector.set_verify(SslVerifyMode::NONE);
+		let connector = connector.build();
+
+		let mut core = Core::new().unwrap();
+		let pins = pins.to_vec();
+		let future = TcpStream::connect(&address, &core.handle())
+			.and_then(move |stream| connect(&connector, "localhost", stream, &pins))
+			.and_then(|stream| read_to_end(stream, Vec::new()))
+			.map(|(_, data)| data);
+		core.run(future)
+	}
+
+	#[test]
+	fn test_tls_accepts_pinned_certificate() {
+		let (address, fingerprint) = serve_once();
+		let pins = [CertificateFingerprint([1u8; 32]), fingerprint];
+		assert_eq!(b"ok".to_vec(), connect_pinned(address, &pins).unwrap());
+	}
+
+	#[test]
+	fn test_tls_rejects_certificate_which_is_not_pinned() {
+		let (address, _) = serve_once();
+		assert!(connect_pinned(address, &[CertificateFingerprint([1u8; 32])]).is_err());
+	}
+}
@@ -0,0 +1,678 @@
+//! WebSocket transport.
+//!
+//! Connection is established in the background and automatically reestablished
+//! when the socket drops. Requests which are still waiting for a response are
+//! resent after reconnecting, except for transaction submissions, which fail
+//! instead, because we cannot know if the node has already processed them.
+//! Subscriptions do not survive reconnecting, their notification streams end instead.
+//! Notifications received before their stream is created are buffered, since the node may send them
+//! right after the response to `eth_subscribe`.
+//! Batches are written as a single JSON-RPC array, but their requests are resent one by one.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{atomic, Arc, Mutex};
+use std::time::Duration;
+use std::io;
+use futures::{Future, Stream, Sink, Poll, Async, AsyncSink, future};
+use futures::sync::{mpsc, oneshot};
+use openssl::ssl::{SslConnector, SslMethod};
+use tokio_core::reactor::Handle;
+use tokio_timer::{Timer, Sleep};
+use serde_json;
+use websocket::{ClientBuilder, OwnedMessage, WebSocketError};
+use websocket::async::Client;
+use websocket::async::Stream as AsyncStream;
+use websocket::url::Url;
+use web3::{self, Transport, BatchTransport, RequestId};
+use rpc;
+use config::{Proxy, CertificateFingerprint};
+use error::{Error, ResultExt};
+use super::{RECONNECT_INTERVAL_SECS, is_resendable, proxy, tls, PubSub};
+
+type Socket = Box<AsyncStream + Send>;
+
+type PendingRequests = Arc<Mutex<BTreeMap<RequestId, PendingRequest>>>;
+
+type Subscriptions = Arc<Mutex<SubscriptionsState>>;
+
+/// Maximum number of notifications buffered for a single subscription without a stream.
+const MAX_BUFFERED_NOTIFICATIONS: usize = 1024;
+
+#[derive(Default)]
+struct SubscriptionsState {
+	/// Streams of notifications by subscription id.
+	subscribers: HashMap<String, mpsc::UnboundedSender<rpc::Value>>,
+	/// Notifications of subscriptions, which streams have not been created yet.
+	buffered: HashMap<String, Vec<rpc::Value>>,
+}
+
+struct PendingRequest {
+	/// Serialized request.
+	request: String,
+	/// Set to true if it's safe to send this request again after reconnecting.
+	resend: bool,
+	/// Set to true once the request has been written to the socket.
+	sent: bool,
+	response: oneshot::Sender<Result<rpc::Value, web3::Error>>,
+}
+
+/// WebSocket transport.
+pub struct WebSocket {
+	id: atomic::AtomicUsize,
+	pending: PendingRequests,
+	subscriptions: Subscriptions,
+	requests: mpsc::UnboundedSender<Vec<RequestId>>,
+}
+
+impl WebSocket {
+	/// Creates new WebSocket transport within existing event loop.
+	///
+	/// Connection is established lazily, so this function never fails
+	/// if the node is not reachable yet. Connections are tunneled through the `proxy` if specified.
+	/// Certificates of `wss` urls must match one of the `pins`, unless there are none.
+	pub fn with_event_loop(url: &str, proxy: Option<&Proxy>, pins: &[CertificateFingerprint], handle: &Handle, timer: Timer) -> Result<Self, Error> {
+		let url = Url::parse(url).chain_err(|| format!("Invalid websocket url {}", url))?;
+		match url.scheme() {
+			"ws" | "wss" => {},
+			scheme => return Err(format!("Unsupported websocket url scheme {}", scheme).into()),
+		}
+
+		let pending = PendingRequests::default();
+		let subscriptions = Subscriptions::default();
+		let (requests_tx, requests_rx) = mpsc::unbounded();
+
+		let connection = Connection {
+			state: ConnectionState::Connecting(connect(&url, proxy, pins, handle)),
+			context: ConnectionContext {
+				url,
+				proxy: proxy.cloned(),
+				pins: pins.to_vec(),
+				handle: handle.clone(),
+				timer,
+				pending: pending.clone(),
+				subscriptions: subscriptions.clone(),
+				requests: requests_rx,
+				queue: VecDeque::new(),
+				closed: false,
+			},
+		};
+
+		handle.spawn(connection);
+
+		let result = WebSocket {
+			id: atomic::AtomicUsize::new(1),
+			pending,
+			subscriptions,
+			requests: requests_tx,
+		};
+		Ok(result)
+	}
+}
+
+impl Transport for WebSocket {
+	type Out = Response;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		let id = self.id.fetch_add(1, atomic::Ordering::AcqRel);
+		let request = web3::helpers::build_request(id, method, params);
+		(id, request)
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		let response = self.register(id, request);
+		self.write(vec![id]);
+		response
+	}
+}
+
+impl BatchTransport for WebSocket {
+	type Batch = Box<Future<Item = Vec<Result<rpc::Value, web3::Error>>, Error = web3::Error>>;
+
+	/// Requests are written to the socket as a single message.
+	fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (RequestId, rpc::Call)> {
+		let (ids, responses): (Vec<_>, Vec<_>) = requests.into_iter()
+			.map(|(id, request)| (id, self.register(id, request).then(Ok)))
+			.unzip();
+		self.write(ids);
+		Box::new(future::join_all(responses))
+	}
+}
+
+impl WebSocket {
+	/// Adds the request to the pending ones, which are waiting for the response.
+	fn register(&self, id: RequestId, request: rpc::Call) -> Response {
+		let resend = is_resendable(&request);
+		let (tx, rx) = oneshot::channel();
+		trace!(target: "bridge", "[{}] websocket request", id);
+		self.pending.lock().expect("pending requests lock is never poisoned; qed").insert(id, PendingRequest {
+			request: serde_json::to_string(&request).expect("request serialization can't fail; qed"),
+			resend,
+			sent: false,
+			response: tx,
+		});
+		Response(rx)
+	}
+
+	/// Passes the pending requests to the connection task, which writes them as a single message.
+	fn write(&self, ids: Vec<RequestId>) {
+		if let Err(err) = self.requests.unbounded_send(ids) {
+			// connection task is gone, dropping the senders cancels the responses
+			let mut pending = self.pending.lock().expect("pending requests lock is never poisoned; qed");
+			for id in err.into_inner() {
+				pending.remove(&id);
+			}
+		}
+	}
+}
+
+impl PubSub for WebSocket {
+	type Notifications = Notifications;
+
+	fn supports_subscriptions(&self) -> bool {
+		true
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		let (tx, rx) = mpsc::unbounded();
+		let mut subscriptions = self.subscriptions.lock().expect("subscriptions lock is never poisoned; qed");
+		for notification in subscriptions.buffered.remove(subscription).unwrap_or_default() {
+			let _ = tx.unbounded_send(notification);
+		}
+		subscriptions.subscribers.insert(subscription.to_owned(), tx);
+		Notifications(rx)
+	}
+}
+
+/// A stream of subscription notifications.
+pub struct Notifications(mpsc::UnboundedReceiver<rpc::Value>);
+
+impl Stream for Notifications {
+	type Item = rpc::Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		self.0.poll().map_err(|_| web3::Error::Unreachable)
+	}
+}
+
+/// A future representing a response to a pending request.
+pub struct Response(oneshot::Receiver<Result<rpc::Value, web3::Error>>);
+
+impl Future for Response {
+	type Item = rpc::Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.0.poll() {
+			Ok(Async::Ready(result)) => result.map(Async::Ready),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(_) => Err(web3::Error::Transport("websocket connection closed".into())),
+		}
+	}
+}
+
+fn tls_error<E: ::std::fmt::Display>(err: E) -> WebSocketError {
+	io::Error::new(io::ErrorKind::Other, err.to_string()).into()
+}
+
+/// Opens new WebSocket connection.
+fn connect(url: &Url, proxy: Option<&Proxy>, pins: &[CertificateFingerprint], handle: &Handle) -> Box<Future<Item = Client<Socket>, Error = WebSocketError>> {
+	let host = match url.host_str() {
+		Some(host) => host.to_owned(),
+		None => return Box::new(future::err(WebSocketError::ProtocolError("missing websocket host"))),
+	};
+	let port = url.port_or_known_default().unwrap_or(80);
+	let stream = proxy::connect(&host, port, proxy, handle).map_err(WebSocketError::from);
+	let socket: Box<Future<Item = Socket, Error = WebSocketError>> = if url.scheme() == "wss" {
+		let connector = match SslConnector::builder(SslMethod::tls()) {
+			Ok(builder) => builder.build(),
+			Err(err) => return Box::new(future::err(tls_error(err))),
+		};
+		let pins = pins.to_vec();
+		Box::new(stream.and_then(move |stream| tls::connect(&connector, &host, stream, &pins).map_err(WebSocketError::from))
+			.map(|stream| Box::new(stream) as Socket))
+	} else {
+		Box::new(stream.map(|stream| Box::new(stream) as Socket))
+	};
+
+	let url = url.clone();
+	let client = socket
+		.and_then(move |socket| ClientBuilder::from_url(&url).async_connect_on(socket))
+		.map(|(client, _headers)| client);
+	Box::new(client)
+}
+
+/// WebSocket connection state.
+enum ConnectionState {
+	/// Establishing connection.
+	Connecting(Box<Future<Item = Client<Socket>, Error = WebSocketError>>),
+	/// Connection is established.
+	Connected(Client<Socket>),
+	/// Waiting before reconnecting.
+	Reconnect(Sleep),
+}
+
+/// Background task which drives the WebSocket connection.
+struct Connection {
+	state: ConnectionState,
+	context: ConnectionContext,
+}
+
+struct ConnectionContext {
+	url: Url,
+	proxy: Option<Proxy>,
+	pins: Vec<CertificateFingerprint>,
+	handle: Handle,
+	timer: Timer,
+	pending: PendingRequests,
+	subscriptions: Subscriptions,
+	requests: mpsc::UnboundedReceiver<Vec<RequestId>>,
+	/// Requests and control messages waiting to be written to the socket.
+	queue: VecDeque<Outgoing>,
+	/// Set to true when all transport handles are dropped.
+	closed: bool,
+}
+
+enum Outgoing {
+	/// Requests written as a single message.
+	Requests(Vec<RequestId>),
+	Pong(Vec<u8>),
+}
+
+impl ConnectionContext {
+	/// Collects new requests. Returns true if there are no more requests to serve.
+	fn poll_requests(&mut self) -> bool {
+		while !self.closed {
+			match self.requests.poll() {
+				Ok(Async::Ready(Some(ids))) => self.queue.push_back(Outgoing::Requests(ids)),
+				Ok(Async::NotReady) => break,
+				Ok(Async::Ready(None)) | Err(_) => self.closed = true,
+			}
+		}
+
+		self.closed && self.pending.lock().expect("pending requests lock is never poisoned; qed").is_empty()
+	}
+
+	/// Called after the connection has been (re)established.
+	fn on_connected(&mut self) {
+		let mut pending = self.pending.lock().expect("pending requests lock is never poisoned; qed");
+		let lost = pending.iter()
+			.filter(|&(_, request)| request.response.is_canceled() || (request.sent && !request.resend))
+			.map(|(id, _)| *id)
+			.collect::<Vec<_>>();
+
+		for id in lost {
+			if let Some(request) = pending.remove(&id) {
+				if request.sent && !request.resend {
+					warn!(target: "bridge", "[{}] websocket connection dropped after submitting request", id);
+				}
+				let _ = request.response.send(Err(web3::Error::Transport("websocket connection dropped".into())));
+			}
+		}
+
+		self.queue = pending.keys().map(|id| Outgoing::Requests(vec![*id])).collect();
+		// subscriptions are bound to the connection, dropping the senders ends the notification streams
+		let mut subscriptions = self.subscriptions.lock().expect("subscriptions lock is never poisoned; qed");
+		subscriptions.subscribers.clear();
+		subscriptions.buffered.clear();
+	}
+
+	/// Forwards `eth_subscription` notification to its subscriber.
+	fn on_notification(&mut self, params: rpc::Value) {
+		let (subscription, result) = match params {
+			rpc::Value::Object(mut params) => match (params.remove("subscription"), params.remove("result")) {
+				(Some(rpc::Value::String(subscription)), Some(result)) => (subscription, result),
+				_ => {
+					warn!(target: "bridge", "invalid websocket notification");
+					return;
+				},
+			},
+			_ => {
+				warn!(target: "bridge", "invalid websocket notification");
+				return;
+			},
+		};
+
+		let mut subscriptions = self.subscriptions.lock().expect("subscriptions lock is never poisoned; qed");
+		let SubscriptionsState { ref mut subscribers, ref mut buffered } = *subscriptions;
+		let closed = match subscribers.get(&subscription) {
+			Some(subscriber) => subscriber.unbounded_send(result).is_err(),
+			None => {
+				// the stream is created once the response to `eth_subscribe` is handled
+				let buffered = buffered.entry(subscription.clone()).or_insert_with(Vec::new);
+				if buffered.len() < MAX_BUFFERED_NOTIFICATIONS {
+					buffered.push(result);
+				} else {
+					trace!(target: "bridge", "dropping notification of unknown subscription {}", subscription);
+				}
+				false
+			},
+		};
+
+		if closed {
+			subscribers.remove(&subscription);
+		}
+	}
+
+	fn on_message(&mut self, message: OwnedMessage) -> Result<(), WebSocketError> {
+		let response = match message {
+			OwnedMessage::Text(text) => text.into_bytes(),
+			OwnedMessage::Binary(bytes) => bytes,
+			OwnedMessage::Ping(data) => {
+				self.queue.push_front(Outgoing::Pong(data));
+				return Ok(());
+			},
+			OwnedMessage::Pong(_) => return Ok(()),
+			OwnedMessage::Close(_) => return Err(WebSocketError::ProtocolError("websocket closed by the node")),
+		};
+
+		let mut response: rpc::Value = match serde_json::from_slice(&response) {
+			Ok(response) => response,
+			Err(err) => {
+				warn!(target: "bridge", "invalid websocket response: {}", err);
+				return Ok(());
+			},
+		};
+
+		let notification = match response {
+			rpc::Value::Object(ref mut object) if object.get("method") == Some(&"eth_subscription".into()) => {
+				Some(object.remove("params").unwrap_or(rpc::Value::Null))
+			},
+			_ => None,
+		};
+
+		if let Some(params) = notification {
+			self.on_notification(params);
+			return Ok(());
+		}
+
+		let outputs = match serde_json::from_value(response) {
+			Ok(rpc::Response::Single(output)) => vec![output],
+			Ok(rpc::Response::Batch(outputs)) => outputs,
+			Err(err) => {
+				warn!(target: "bridge", "invalid websocket response: {}", err);
+				return Ok(());
+			},
+		};
+
+		let mut pending = self.pending.lock().expect("pending requests lock is never poisoned; qed");
+		for output in outputs {
+			let (id, result) = match output {
+				rpc::Output::Success(success) => (success.id, Ok(success.result)),
+				rpc::Output::Failure(failure) => (failure.id, Err(web3::Error::Rpc(failure.error))),
+			};
+
+			match id {
+				rpc::Id::Num(id) => match pending.remove(&(id as RequestId)) {
+					Some(request) => {
+						let _ = request.response.send(result);
+					},
+					None => warn!(target: "bridge", "[{}] unexpected websocket response", id),
+				},
+				id => warn!(target: "bridge", "unexpected websocket response id: {:?}", id),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Returns the message with the requests, which have not been answered yet,
+	/// a JSON-RPC array if there are more of them. `None` if all have been answered.
+	fn requests_message(&self, ids: &[RequestId]) -> Option<OwnedMessage> {
+		let pending = self.pending.lock().expect("pending requests lock is never poisoned; qed");
+		let requests = ids.iter()
+			.filter_map(|id| pending.get(id))
+			.map(|request| request.request.as_str())
+			.collect::<Vec<_>>();
+
+		match requests.len() {
+			0 => None,
+			1 => Some(OwnedMessage::Text(requests[0].to_owned())),
+			_ => Some(OwnedMessage::Text(format!("[{}]", requests.join(",")))),
+		}
+	}
+
+	fn poll_connected(&mut self, client: &mut Client<Socket>) -> Poll<(), WebSocketError> {
+		while let Async::Ready(message) = client.poll()? {
+			match message {
+				Some(message) => self.on_message(message)?,
+				None => return Err(WebSocketError::ProtocolError("websocket connection closed")),
+			}
+		}
+
+		if self.poll_requests() {
+			return Ok(Async::Ready(()));
+		}
+
+		while let Some(outgoing) = self.queue.pop_front() {
+			let message = match outgoing {
+				Outgoing::Requests(ref ids) => match self.requests_message(ids) {
+					Some(message) => message,
+					// requests have already been answered
+					None => continue,
+				},
+				Outgoing::Pong(ref data) => OwnedMessage::Pong(data.clone()),
+			};
+
+			if let AsyncSink::NotReady(_) = client.start_send(message)? {
+				self.queue.push_front(outgoing);
+				break;
+			}
+
+			if let Outgoing::Requests(ids) = outgoing {
+				let mut pending = self.pending.lock().expect("pending requests lock is never poisoned; qed");
+				for id in ids {
+					if let Some(request) = pending.get_mut(&id) {
+						request.sent = true;
+					}
+				}
+			}
+		}
+
+		client.poll_complete()?;
+		Ok(Async::NotReady)
+	}
+
+	fn reconnect(&self) -> ConnectionState {
+		ConnectionState::Reconnect(self.timer.sleep(Duration::from_secs(RECONNECT_INTERVAL_SECS)))
+	}
+}
+
+impl Future for Connection {
+	type Item = ();
+	type Error = ();
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let context = &mut self.context;
+			let next_state = match self.state {
+				ConnectionState::Reconnect(ref mut sleep) => {
+					if context.poll_requests() {
+						return Ok(Async::Ready(()));
+					}
+					match sleep.poll() {
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						_ => ConnectionState::Connecting(connect(&context.url, context.proxy.as_ref(), &context.pins, &context.handle)),
+					}
+				},
+				ConnectionState::Connecting(ref mut future) => {
+					if context.poll_requests() {
+						return Ok(Async::Ready(()));
+					}
+					match future.poll() {
+						Ok(Async::Ready(client)) => {
+							info!(target: "bridge", "Connected to {}", context.url);
+							context.on_connected();
+							ConnectionState::Connected(client)
+						},
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						Err(err) => {
+							warn!(target: "bridge", "Cannot connect to {}: {}", context.url, err);
+							context.reconnect()
+						},
+					}
+				},
+				ConnectionState::Connected(ref mut client) => match context.poll_connected(client) {
+					Ok(Async::Ready(())) => return Ok(Async::Ready(())),
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Err(err) => {
+						warn!(target: "bridge", "Connection to {} dropped: {}", context.url, err);
+						context.reconnect()
+					},
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, VecDeque};
+	use std::sync::{Arc, Mutex};
+	use futures::{Future, Stream};
+	use futures::sync::{mpsc, oneshot};
+	use tokio_core::reactor::Core;
+	use serde_json;
+	use websocket::OwnedMessage;
+	use websocket::url::Url;
+	use rpc;
+	use super::{ConnectionContext, PendingRequest, Outgoing, SubscriptionsState, WebSocket, PubSub};
+
+	#[test]
+	fn test_websocket_response_dispatch() {
+		let core = Core::new().unwrap();
+		let (_requests_tx, requests_rx) = mpsc::unbounded();
+		let (tx1, rx1) = oneshot::channel();
+		let (tx2, rx2) = oneshot::channel();
+
+		let mut pending = BTreeMap::new();
+		pending.insert(1, PendingRequest { request: String::new(), resend: true, sent: true, response: tx1 });
+		pending.insert(2, PendingRequest { request: String::new(), resend: true, sent: true, response: tx2 });
+
+		let mut context = ConnectionContext {
+			url: Url::parse("ws://127.0.0.1:8546").unwrap(),
+			proxy: None,
+			pins: vec![],
+			handle: core.handle(),
+			timer: Default::default(),
+			pending: Arc::new(Mutex::new(pending)),
+			subscriptions: Default::default(),
+			requests: requests_rx,
+			queue: VecDeque::new(),
+			closed: false,
+		};
+
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","id":2,"result":"0x10"}"#.into())).unwrap();
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"err"}}"#.into())).unwrap();
+		context.on_message(OwnedMessage::Ping(vec![1])).unwrap();
+
+		assert_eq!(rpc::Value::String("0x10".into()), rx2.wait().unwrap().unwrap());
+		assert!(rx1.wait().unwrap().is_err());
+		assert!(context.pending.lock().unwrap().is_empty());
+		match context.queue.pop_front() {
+			Some(Outgoing::Pong(data)) => assert_eq!(vec![1], data),
+			_ => panic!("expected pong"),
+		}
+	}
+
+	#[test]
+	fn test_websocket_notification_dispatch() {
+		let core = Core::new().unwrap();
+		let (_requests_tx, requests_rx) = mpsc::unbounded();
+		let (tx, rx) = mpsc::unbounded();
+
+		let mut subscriptions = SubscriptionsState::default();
+		subscriptions.subscribers.insert("0xab".to_owned(), tx);
+
+		let mut context = ConnectionContext {
+			url: Url::parse("ws://127.0.0.1:8546").unwrap(),
+			proxy: None,
+			pins: vec![],
+			handle: core.handle(),
+			timer: Default::default(),
+			pending: Default::default(),
+			subscriptions: Arc::new(Mutex::new(subscriptions)),
+			requests: requests_rx,
+			queue: VecDeque::new(),
+			closed: false,
+		};
+
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xab","result":{"number":"0x10"}}}"#.into())).unwrap();
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xcd","result":{"number":"0x11"}}}"#.into())).unwrap();
+		// subscriptions are lost after reconnecting
+		context.on_connected();
+
+		let notifications = rx.collect().wait().unwrap();
+		let expected: rpc::Value = serde_json::from_str(r#"{"number":"0x10"}"#).unwrap();
+		assert_eq!(vec![expected], notifications);
+	}
+
+	#[test]
+	fn test_websocket_buffers_early_notifications() {
+		let core = Core::new().unwrap();
+		let (requests_tx, requests_rx) = mpsc::unbounded();
+
+		let mut context = ConnectionContext {
+			url: Url::parse("ws://127.0.0.1:8546").unwrap(),
+			proxy: None,
+			pins: vec![],
+			handle: core.handle(),
+			timer: Default::default(),
+			pending: Default::default(),
+			subscriptions: Default::default(),
+			requests: requests_rx,
+			queue: VecDeque::new(),
+			closed: false,
+		};
+
+		let transport = WebSocket {
+			id: Default::default(),
+			pending: context.pending.clone(),
+			subscriptions: context.subscriptions.clone(),
+			requests: requests_tx,
+		};
+
+		// the notification is sent before the response to `eth_subscribe` is handled
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xab","result":{"number":"0x10"}}}"#.into())).unwrap();
+		let notifications = transport.notifications("0xab");
+		context.on_message(OwnedMessage::Text(r#"{"jsonrpc":"2.0","method":"eth_subscription","params":{"subscription":"0xab","result":{"number":"0x11"}}}"#.into())).unwrap();
+		context.on_connected();
+
+		let notifications = notifications.collect().wait().unwrap();
+		let expected: Vec<rpc::Value> = serde_json::from_str(r#"[{"number":"0x10"},{"number":"0x11"}]"#).unwrap();
+		assert_eq!(expected, notifications);
+		assert!(context.subscriptions.lock().unwrap().buffered.is_empty());
+	}
+
+	#[test]
+	fn test_websocket_batch_message() {
+		let core = Core::new().unwrap();
+		let (_requests_tx, requests_rx) = mpsc::unbounded();
+		let (tx1, _rx1) = oneshot::channel();
+		let (tx2, _rx2) = oneshot::channel();
+
+		let mut pending = BTreeMap::new();
+		pending.insert(1, PendingRequest { request: r#"{"id":1}"#.into(), resend: true, sent: false, response: tx1 });
+		pending.insert(2, PendingRequest { request: r#"{"id":2}"#.into(), resend: true, sent: false, response: tx2 });
+
+		let context = ConnectionContext {
+			url: Url::parse("ws://127.0.0.1:8546").unwrap(),
+			proxy: None,
+			pins: vec![],
+			handle: core.handle(),
+			timer: Default::default(),
+			pending: Arc::new(Mutex::new(pending)),
+			subscriptions: Default::default(),
+			requests: requests_rx,
+			queue: VecDeque::new(),
+			closed: false,
+		};
+
+		// the batch is written as a single array, without the requests answered already
+		assert_eq!(Some(OwnedMessage::Text(r#"[{"id":1},{"id":2}]"#.into())), context.requests_message(&[1, 2, 3]));
+		assert_eq!(Some(OwnedMessage::Text(r#"{"id":2}"#.into())), context.requests_message(&[2, 3]));
+		assert_eq!(None, context.requests_message(&[3]));
+	}
+}
@@ -1,5 +1,10 @@
-use web3::types::{H256, Address, FilterBuilder};
+use web3::types::{H256, U256, Address, FilterBuilder};
 use ethabi;
+use tiny_keccak::Keccak;
+use secp256k1::{Secp256k1, Message};
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use config::LogFilter;
+use error::Error;
 
 fn web3_topic(topic: ethabi::Topic<ethabi::Hash>) -> Option<Vec<H256>> {
 	let t: Vec<ethabi::Hash> = topic.into();
@@ -15,3 +20,116 @@ pub fn web3_filter(filter: ethabi::TopicFilter, address: Address) -> FilterBuild
 		.address(vec![address])
 		.topics(t0, t1, t2, t3)
 }
+
+fn restrict_topic(topic: ethabi::Topic<ethabi::Hash>, values: &[H256]) -> ethabi::Topic<ethabi::Hash> {
+	if values.is_empty() {
+		topic
+	} else {
+		ethabi::Topic::OneOf(values.iter().map(|value| value.0).collect())
+	}
+}
+
+/// Restricts the indexed event parameters matched by `filter` to the values configured in `log_filter`.
+pub fn restrict_topics(filter: ethabi::TopicFilter, log_filter: &LogFilter) -> ethabi::TopicFilter {
+	ethabi::TopicFilter {
+		topic0: filter.topic0,
+		topic1: restrict_topic(filter.topic1, &log_filter.topics[0]),
+		topic2: restrict_topic(filter.topic2, &log_filter.topics[1]),
+		topic3: restrict_topic(filter.topic3, &log_filter.topics[2]),
+	}
+}
+
+/// Returns hash of the `message` signed with `eth_sign`, as computed by the `Signer` contract library.
+pub fn signed_message_hash(message: &[u8]) -> H256 {
+	let mut keccak = Keccak::new_keccak256();
+	keccak.update(b"\x19Ethereum Signed Message:\n");
+	keccak.update(message.len().to_string().as_bytes());
+	keccak.update(message);
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+	H256(hash)
+}
+
+/// Recovers address of the account, which signed the `message` with `eth_sign`.
+pub fn recover_signer(signature: &[u8], message: &[u8]) -> Result<Address, Error> {
+	if signature.len() != 65 {
+		return Err(format!("Invalid signature length {}", signature.len()).into());
+	}
+
+	// `v` is either 27 or 28, or the recovery id itself
+	let v = signature[64];
+	let recovery_id = RecoveryId::from_i32(i32::from(if v >= 27 { v - 27 } else { v }))
+		.map_err(|err| format!("Invalid signature: {}", err))?;
+	let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+		.map_err(|err| format!("Invalid signature: {}", err))?;
+	let hash = Message::from_slice(&signed_message_hash(message).0).expect("hash is 32 bytes long; qed");
+	let public = Secp256k1::verification_only().recover(&hash, &signature)
+		.map_err(|err| format!("Invalid signature: {}", err))?;
+
+	let mut keccak = Keccak::new_keccak256();
+	keccak.update(&public.serialize_uncompressed()[1..]);
+	let mut hash = [0u8; 32];
+	keccak.finalize(&mut hash);
+	Ok(Address::from(&hash[12..]))
+}
+
+/// Formats `wei` as ether, without trailing zeros.
+pub fn format_ether(wei: U256) -> String {
+	let ether = U256::from(1_000_000_000_000_000_000u64);
+	let fraction = format!("{:018}", (wei % ether).low_u64());
+	let fraction = fraction.trim_end_matches('0');
+	if fraction.is_empty() {
+		format!("{}", wei / ether)
+	} else {
+		format!("{}.{}", wei / ether, fraction)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ethabi::Topic;
+	use secp256k1::{Secp256k1, Message, SecretKey};
+	use web3::types::H256;
+	use config::LogFilter;
+	use contracts::home;
+	use super::{signed_message_hash, recover_signer, restrict_topics, format_ether};
+
+	#[test]
+	fn test_restrict_topics() {
+		let filter = || home::EthereumBridge::default().events().deposit().create_filter();
+		assert_eq!(filter(), restrict_topics(filter(), &LogFilter::default()));
+
+		let recipients: Vec<H256> = vec![1.into(), 2.into()];
+		let log_filter = LogFilter {
+			topics: [vec![], recipients.clone(), vec![]],
+		};
+		let restricted = restrict_topics(filter(), &log_filter);
+		assert_eq!(filter().topic0, restricted.topic0);
+		assert_eq!(Topic::Any, restricted.topic1);
+		assert_eq!(Topic::OneOf(recipients.iter().map(|recipient| recipient.0).collect()), restricted.topic2);
+		assert_eq!(Topic::Any, restricted.topic3);
+	}
+
+	#[test]
+	fn test_recover_signer() {
+		let secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+		let message = b"withdraw";
+		let hash = Message::from_slice(&signed_message_hash(message).0).unwrap();
+		let (recovery_id, compact) = Secp256k1::new().sign_recoverable(&hash, &secret).serialize_compact();
+		let mut signature = compact.to_vec();
+		signature.push(recovery_id.to_i32() as u8 + 27);
+
+		let expected = "0x1a642f0e3c3af545e7acbd38b07251b3990914f1".parse().unwrap();
+		assert_eq!(expected, recover_signer(&signature, message).unwrap());
+		assert!(recover_signer(&signature, b"other").unwrap() != expected);
+		assert!(recover_signer(&signature[..64], message).is_err());
+	}
+
+	#[test]
+	fn test_format_ether() {
+		assert_eq!("0", format_ether(0.into()));
+		assert_eq!("1", format_ether(1_000_000_000_000_000_000u64.into()));
+		assert_eq!("0.000000000000000001", format_ether(1.into()));
+		assert_eq!("12.0345", format_ether(12_034_500_000_000_000_000u64.into()));
+	}
+}
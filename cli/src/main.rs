@@ -1,6 +1,7 @@
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate docopt;
 extern crate futures;
 extern crate tokio_core;
@@ -9,43 +10,232 @@ extern crate log;
 extern crate env_logger;
 extern crate bridge;
 
-use std::{env, fs};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
 use std::sync::Arc;
 use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use docopt::Docopt;
-use futures::{Stream, future};
+use serde::Serialize;
+use env_logger::LogBuilder;
+use log::LogRecord;
+use futures::{Future, Stream, future};
 use tokio_core::reactor::Core;
 
-use bridge::app::App;
-use bridge::bridge::{create_bridge, create_deploy, Deployed};
-use bridge::config::Config;
-use bridge::error::Error;
+use bridge::api;
+use bridge::app::{App, DryRun};
+use bridge::bridge::{create_bridge, create_deploy, deposit_relay_entry, fetch_status, verify_contracts, export_events, create_watch, events_csv, events_json, manual_relay, probe_nodes, checkpoints, checked_blocks, Deployed, BridgeChecked, Status, ContractCheck, ExportRange, ExportedEvent, ManualRelay, NodeProbe};
+use bridge::config::{Config, Logging, LogFormat, Streams};
+use bridge::contracts::foreign;
+use bridge::database::{self, Database, DatabaseBackend, MemoryBackend, ProcessedEvent, RelayFees, CheckpointChange, Chain};
+use bridge::error::{Error, ResultExt};
+use bridge::metrics;
+use bridge::server::{self, Registry};
+use bridge::systemd::{self, Notifier, Progress};
+use bridge::util::format_ether;
+
+/// Interval of logging statistics of requests sent to the nodes.
+const METRICS_REPORT_INTERVAL_SECS: u64 = 60;
 
 const USAGE: &'static str = r#"
 Ethereum-Kovan bridge.
     Copyright 2017 Parity Technologies (UK) Limited
 
 Usage:
-    bridge --config <config> --database <database>
+    bridge init --config <config> --database <database>
+    bridge [--once] [--force-duplicates] [--dry-run [--simulate]] [--log-level=<filters>] [--log-format=<format>] --config <config> --database <database>
+    bridge config show [--bridge=<namespace>] --config <config>
+    bridge stats [--json] [--bridge=<namespace>] --config <config> --database <database>
+    bridge status [--json] [--bridge=<namespace>] --config <config> --database <database>
+    bridge verify-contracts [--bridge=<namespace>] --config <config> --database <database>
+    bridge export-events [--from-block=<block>] [--to-block=<block>] [--chain=<chain>] [--format=<format> | --json] [--bridge=<namespace>] --config <config> --database <database>
+    bridge watch [--from-block=<block>] [--bridge=<namespace>] --config <config> --database <database>
+    bridge rewind <stream> <block> [--yes] [--bridge=<namespace>] --config <config> --database <database>
+    bridge replay --stream=<stream> --from=<block> --to=<block> [--dry-run [--simulate]] [--bridge=<namespace>] --config <config> --database <database>
+    bridge relay-deposit <transaction> [--bridge=<namespace>] --config <config> --database <database>
+    bridge relay-withdraw <message-hash> [--transaction=<hash>] [--bridge=<namespace>] --config <config> --database <database>
+    bridge db export [--bridge=<namespace>] --config <config> --database <database>
+    bridge db import <file> [--bridge=<namespace>] --config <config> --database <database>
+    bridge db verify [--bridge=<namespace>] --config <config> --database <database>
+    bridge db set-checkpoint <stream> <block> [--bridge=<namespace>] --config <config> --database <database>
+    bridge db prune [--max-age-days=<days>] [--max-relays=<relays>] [--checked] [--bridge=<namespace>] --config <config> --database <database>
+    bridge db approve-deposit <transaction> <log-index> [--bridge=<namespace>] --config <config> --database <database>
+    bridge db cancel-relay <transaction> <log-index> [--bridge=<namespace>] --config <config> --database <database>
     bridge -h | --help
 
 Options:
-    -h, --help           Display help message and exit.
+    -h, --help               Display help message and exit.
+    --max-age-days=<days>    Prune relays older than <days>, overrides `database_retention`.
+    --max-relays=<relays>    Keep only <relays> most recent relays, overrides `database_retention`.
+    --checked                Prune relays of the events in the blocks checked by every component reading their chain.
+    --bridge=<namespace>     Bridge, which the command applies to, if the config defines several ones.
+    --from-block=<block>     First block, which events are exported or watched, the one with the contract
+                             deployment by default, or the ones confirmed at start when watching.
+    --to-block=<block>       Last block, which events are exported, the head of the chain by default.
+    --stream=<stream>        Stream, which is replayed, one of `deposit_relay`, `withdraw_relay`, `withdraw_confirm`,
+                             `mainnet_message_relay` and `testnet_message_relay`.
+    --from=<block>           First block, which events are replayed.
+    --to=<block>             Last block, which events are replayed.
+    --chain=<chain>          Export only the events of <chain>, `home` or `foreign`.
+    --format=<format>        Format of the exported events, `csv` or `json` [default: csv].
+    --json                   Print the output as JSON, e.g. to be read by scripts and dashboards.
+                             Balances, fees and values are in wei, encoded as hex strings.
+    --yes                    Rewind without asking for confirmation.
+    --transaction=<hash>     Transaction, which has collected the signatures of the withdraw,
+                             required unless the withdraw is assigned to this authority.
+    --once                   Check the blocks confirmed at start with every stream, relay their events
+                             and exit, e.g. when the bridge is run by a scheduler. The exit status
+                             is non-zero if any relay has failed, failed relays are retried on the next run.
+    --force-duplicates       Send relay transactions with the same payload as one sent within `duplicate_window`.
+    --dry-run                Run all components, but log the relay transactions instead of sending them.
+                             The database is not changed, so it has to exist already.
+    --simulate               Simulate the relay transactions with `eth_call` before they are logged,
+                             skipping those, which would be reverted.
+    --log-level=<filters>    Levels of the log records, e.g. `info,bridge::transport=debug`, overrides `log.level`
+                             and `log.modules` of the config, which override `RUST_LOG`.
+    --log-format=<format>    Format of the log, `human` or `json`, overrides `log.format` of the config.
+
+<config> may `include` other configs, e.g. `include = ["common.toml"]` with the contracts and confirmations
+shared by the authorities, which are resolved relative to it. Each config overrides the ones included before it,
+and <config> overrides all of them, then `secrets_file` and the environment variables prefixed with `BRIDGE_`
+override the result.
+
+If the config defines several `bridges`, all of them are run by the same process, each using its own
+`database_namespace` of <database>.
+
+<database> is the database file or, if `database_backend` is `postgres`, the connection string,
+e.g. `postgresql://bridge@localhost/bridge`. Postgres database may be shared by a standby bridge,
+which waits until the active one stops and then takes over.
+
+If `metrics_address` is configured, e.g. `metrics_address = "127.0.0.1:9100"`, the metrics of the bridges are served
+to Prometheus at `/metrics`: checkpoints and lag of the components, relay transactions, their failures, gas and fees,
+balances of the authority accounts if `min_balance` is configured, and requests sent to the nodes.
+`/ready` answers 200 once the database of every bridge is loaded and both chains are reachable, and 503 before.
+`/health` answers 200 as long as every component has checked a new block within `health_staleness` seconds
+(600 by default), and 503 listing the stale components otherwise.
+
+If the bridge is run by systemd as a service of `Type=notify`, it reports that it's ready once the database
+is loaded and both chains are reachable. With `WatchdogSec`, it pings the watchdog as long as every bridge
+checks new blocks, so `WatchdogSec` should be longer than the time between the blocks and the `poll_intervals`.
+
+Commands:
+    config show          Print the config of the bridges, or of the one given by --bridge, with the defaults,
+                         the fields of the included configs and the ones overridden by the environment
+                         or set by `secrets_file`.
+                         Credentials and keys are redacted.
+    init                 Ask for the nodes, accounts, authorities and confirmations, check them
+                         with the nodes, and write <config>. The tables of an empty `sqlite`
+                         or `postgres` <database> are created, while the contracts are deployed
+                         and recorded in the database once the bridge is started.
+    stats                Print gas used and fees paid for the mined relay transactions
+                         by each component and chain.
+    status               Print head blocks of the chains, contract addresses and balances
+                         of the authority accounts, checkpoints of the components and how far
+                         they are behind, and numbers of the events, which are not relayed yet.
+    verify-contracts     Check that the code, authorities and required signatures of the contracts
+                         in the database match the configured contracts and authorities.
+    export-events        Print the deposits, withdraws and collected signatures emitted by the contracts
+                         in the range of blocks, with the relay transactions recorded in the database.
+                         The range applies to the blocks of both chains, unless --chain is given.
+    watch                Print the deposits, withdraws and collected signatures, and the deposits and withdraws
+                         relayed by any authority, with the state of the transfer, as they are confirmed.
+                         Nothing is signed or sent and the database is not changed, e.g. to monitor
+                         or audit the bridge without being an authority.
+    rewind               Set last block checked by <stream> back to <block>, so that the following
+                         blocks are checked again once the bridge is started, e.g. after a bug
+                         of the contract. The change is recorded in the database. The bridge
+                         should be stopped.
+    replay               Run --stream over the blocks from --from to --to again, without changing
+                         its checkpoint, e.g. to investigate missed events or to check a fix. Events recorded
+                         as relayed are skipped. Relays are recorded in the database, unless --dry-run is given,
+                         and then the bridge should be stopped.
+    relay-deposit        Relay the deposits made by <transaction> once, regardless of the checkpoints,
+                         e.g. to push through a deposit, which is stuck, without rewinding a stream.
+                         Deposits, which would be reverted, e.g. because they have been relayed
+                         already, are skipped. The relays are recorded in the database.
+    relay-withdraw       Relay the withdraw with <message-hash>, which signatures have been collected,
+                         once, regardless of the checkpoints. The relay is recorded in the database.
+
+Database commands:
+    export               Print the database as JSON.
+    import               Replace the database with the one exported to <file>.
+    verify               Check that the database is consistent.
+    set-checkpoint       Set last block checked by <stream>, one of `deposit_relay`,
+                         `withdraw_relay`, `withdraw_confirm`, `mainnet_message_relay`
+                         and `testnet_message_relay`, without confirmation. The change is
+                         recorded in the database.
+    prune                Remove relays, which should not be retained, and compact the database.
+    approve-deposit      Relay the deposit emitted by log <log-index> of <transaction>, sent to
+                         mainnet (or testnet if `direction` is `testnet_to_mainnet`), which is held
+                         because it's above `max_deposit`, once the bridge is started.
+    cancel-relay         Drop the relay of the deposit or withdraw emitted by log <log-index> of
+                         <transaction>, which is held because of `relay_delay`, so that it's never
+                         relayed. The bridge should be stopped.
 "#;
 
 #[derive(Debug, Deserialize)]
 pub struct Args {
 	arg_config: PathBuf,
 	arg_database: PathBuf,
+	cmd_init: bool,
+	cmd_config: bool,
+	cmd_show: bool,
+	cmd_stats: bool,
+	cmd_status: bool,
+	cmd_verify_contracts: bool,
+	cmd_export_events: bool,
+	cmd_watch: bool,
+	cmd_rewind: bool,
+	cmd_replay: bool,
+	cmd_relay_deposit: bool,
+	cmd_relay_withdraw: bool,
+	cmd_db: bool,
+	cmd_export: bool,
+	cmd_import: bool,
+	cmd_verify: bool,
+	cmd_set_checkpoint: bool,
+	cmd_prune: bool,
+	cmd_approve_deposit: bool,
+	cmd_cancel_relay: bool,
+	flag_max_age_days: Option<u64>,
+	flag_max_relays: Option<usize>,
+	flag_checked: bool,
+	flag_bridge: Option<String>,
+	flag_yes: bool,
+	flag_from_block: Option<String>,
+	flag_to_block: Option<String>,
+	flag_stream: Option<String>,
+	flag_from: Option<String>,
+	flag_to: Option<String>,
+	flag_chain: Option<String>,
+	flag_transaction: Option<String>,
+	flag_format: String,
+	flag_json: bool,
+	flag_once: bool,
+	flag_force_duplicates: bool,
+	flag_dry_run: bool,
+	flag_simulate: bool,
+	flag_log_level: Option<String>,
+	flag_log_format: Option<String>,
+	arg_file: Option<PathBuf>,
+	arg_stream: Option<String>,
+	arg_block: Option<String>,
+	arg_transaction: Option<String>,
+	arg_message_hash: Option<String>,
+	arg_log_index: Option<u64>,
 }
 
 fn main() {
-	let _ = env_logger::init();
 	let result = execute(env::args());
 
 	match result {
 		Ok(s) => println!("{}", s),
-		Err(err) => print_err(err),
+		Err(err) => {
+			print_err(err);
+			process::exit(1);
+		},
 	}
 }
 
@@ -55,44 +245,1014 @@ fn print_err(err: Error) {
 }
 
 fn execute<S, I>(command: I) -> Result<String, Error> where I: IntoIterator<Item=S>, S: AsRef<str> {
-	info!(target: "bridge", "Parsing cli arguments");
 	let args: Args = Docopt::new(USAGE)
 		.and_then(|d| d.argv(command).deserialize()).map_err(|e| e.to_string())?;
 
-	info!(target: "bridge", "Loading config");
-	let config = Config::load(args.arg_config)?;
+	if args.cmd_init {
+		init_logging(&Logging::default(), None, None)?;
+		return execute_init(&args);
+	}
+
+	// the log is configured by the config, so it is initialized before an invalid config is reported
+	let configs = Config::load_bridges(&args.arg_config);
+	// all bridges have the same log
+	let logging = configs.as_ref().ok().and_then(|configs| configs.first()).map(|config| config.logging.clone()).unwrap_or_default();
+	init_logging(&logging, args.flag_log_level.as_deref(), args.flag_log_format.as_deref())?;
+	let configs = configs?;
+	info!(target: "bridge", "Loaded config");
+
+	if args.cmd_config {
+		assert!(args.cmd_show, "all config commands are handled; qed");
+		let configs = match args.flag_bridge {
+			Some(_) => vec![select_bridge(configs, args.flag_bridge.as_deref())?],
+			None => configs,
+		};
+		// secrets are redacted by the formatting of the config
+		let printed = configs.iter().map(|config| format!("{:#?}", config)).collect::<Vec<_>>();
+		return Ok(printed.join("\n\n"));
+	}
+
+	if args.cmd_db || args.cmd_rewind || args.cmd_replay || args.cmd_relay_deposit || args.cmd_relay_withdraw || args.cmd_export_events || args.cmd_watch || args.cmd_stats || args.cmd_status || args.cmd_verify_contracts {
+		let config = select_bridge(configs, args.flag_bridge.as_deref())?;
+		return execute_command(&args, config);
+	}
 
 	info!(target: "bridge", "Starting event loop");
 	let mut event_loop = Core::new().unwrap();
 
-	info!(target: "bridge", "Establishing ipc connection");
-	let app = App::new_ipc(config, &args.arg_database, &event_loop.handle())?;
-	let app_ref = Arc::new(app.as_ref());
+	// the notifier is created before connecting, so that a misconfigured service fails early
+	let notifier = Notifier::from_env()?;
 
-	info!(target: "bridge", "Deploying contracts (if needed)");
-	let deployed = event_loop.run(create_deploy(app_ref.clone()))?;
+	// bridges defined by the config share the event loop, but each has its own connections and database
+	let metrics_address = configs.first().and_then(|config| config.metrics_address);
+	let registry = Registry::default();
+	if let Some(address) = metrics_address {
+		// the server is started first, so that the health is reported while the bridges are starting
+		server::serve(&address, &event_loop.handle(), registry.clone())?;
+	}
+	let mut bridges = Vec::new();
+	let mut progress = Vec::new();
+	let mut timer = None;
+	for config in configs {
+		let namespace = config.database_namespace.clone();
+		if let Some(ref namespace) = namespace {
+			info!(target: "bridge", "Starting bridge {}", namespace);
+		}
 
-	let database = match deployed {
-		Deployed::New(database) => {
-			info!(target: "bridge", "Deployed new bridge contracts");
-			info!(target: "bridge", "\n\n{}\n", database);
-			database.save(fs::File::create(&app_ref.database_path)?)?;
-			database
-		},
-		Deployed::Existing(database) => {
-			info!(target: "bridge", "Loaded database");
-			database
+		info!(target: "bridge", "Establishing connections");
+		let mut app = App::new(config, &event_loop.handle())?;
+		if args.flag_dry_run {
+			app.dry_run = Some(DryRun {
+				simulate: args.flag_simulate,
+			});
+		}
+		app.once = args.flag_once;
+		let app = Arc::new(app);
+		if args.flag_force_duplicates {
+			app.payloads.force_duplicates();
+		}
+		registry.register(namespace.clone(), app.metrics.clone(), app.config.health_staleness);
+		event_loop.handle().spawn(metrics::report(app.metrics.clone(), namespace, &app.timer, Duration::from_secs(METRICS_REPORT_INTERVAL_SECS)));
+
+		info!(target: "bridge", "Loading database");
+		let mut backend = database::open(&app.config, &args.arg_database)?;
+		if args.flag_dry_run {
+			// the bridge is run against a copy of the database, and new contracts are never deployed
+			let database = backend.load()?.ok_or_else(|| Error::from("Dry run requires an existing database"))?;
+			backend = Box::new(MemoryBackend::new(Some(database)));
+		}
+		backend.lock()?;
+		let existing = backend.load()?;
+
+		info!(target: "bridge", "Deploying contracts (if needed)");
+		let deployed = event_loop.run(create_deploy(app.clone(), existing))?;
+
+		let database = match deployed {
+			Deployed::New(database) => {
+				info!(target: "bridge", "Deployed new bridge contracts");
+				info!(target: "bridge", "\n\n{}\n", database);
+				backend.save(&database)?;
+				database
+			},
+			Deployed::Existing(database) => {
+				info!(target: "bridge", "Loaded database");
+				database
+			},
+			Deployed::Discovered(database) => {
+				info!(target: "bridge", "Loaded database, the checkpoints start at the deployments of the contracts");
+				backend.save(&database)?;
+				database
+			},
+		};
+
+		if notifier.is_some() || metrics_address.is_some() {
+			// the bridge is reported ready only once both chains are reachable
+			let head = |chain| api::block_number(app.connection(chain).clone())
+				.retry(&app.timer, app.node(chain).request_timeouts.call, &app.node(chain).retry);
+			event_loop.run(head(Chain::Mainnet).join(head(Chain::Testnet)))?;
+		}
+
+		let checked = Progress::default();
+		progress.push(checked.clone());
+		timer = Some(app.timer.clone());
+		bridges.push(create_bridge(app, &database, backend).for_each(move |_| {
+			checked.advance();
+			Ok(())
+		}));
+	}
+
+	registry.set_ready();
+
+	if let Some(notifier) = notifier {
+		notifier.ready()?;
+		info!(target: "bridge", "Notified systemd that the bridge is ready");
+		if let (Some(interval), Some(timer)) = (systemd::watchdog_interval(), timer) {
+			event_loop.handle().spawn(systemd::watchdog(notifier, progress, &timer, interval));
+		}
+	}
+
+	info!(target: "bridge", "Starting listening to events");
+	if !args.flag_once {
+		event_loop.run(future::join_all(bridges))?;
+		return Ok("Done".into());
+	}
+
+	// every bridge completes its cycle, even if another one fails
+	let results = event_loop.run(future::join_all(bridges.into_iter().map(|bridge| bridge.then(Ok::<_, Error>))))?;
+	let failed = results.into_iter().filter_map(Result::err).collect::<Vec<_>>();
+	match failed.len() {
+		0 => Ok("Done".into()),
+		1 => Err(failed.into_iter().next().expect("one bridge has failed; qed")),
+		n => {
+			for err in failed {
+				print_err(err);
+			}
+			Err(format!("{} bridges have failed", n).into())
 		},
+	}
+}
+
+/// Record of the log in the JSON format.
+#[derive(Serialize)]
+struct JsonLogRecord<'a> {
+	/// Unix time in milliseconds.
+	timestamp: u64,
+	level: &'a str,
+	target: &'a str,
+	message: &'a str,
+}
+
+/// Formats the record of the log as a line of JSON.
+fn json_log_line(timestamp: u64, level: &str, target: &str, message: &str) -> String {
+	let record = JsonLogRecord {
+		timestamp,
+		level,
+		target,
+		message,
 	};
+	serde_json::to_string(&record).expect("serialization can't fail; qed")
+}
 
-	info!(target: "bridge", "Starting listening to events");
-	let bridge = create_bridge(app_ref, &database).and_then(|_| future::ok(true)).collect();
-	event_loop.run(bridge)?;
+/// Initializes the log with the levels and format of the config, unless they are overridden by the flags.
+fn init_logging(logging: &Logging, level: Option<&str>, format: Option<&str>) -> Result<(), Error> {
+	let format = format.map(LogFormat::parse).transpose()?.unwrap_or(logging.format);
+	let mut builder = LogBuilder::new();
+	match format {
+		LogFormat::Human => builder.format(|record: &LogRecord| format!("{}:{}: {}", record.level(), record.location().module_path(), record.args())),
+		LogFormat::Json => builder.format(|record: &LogRecord| {
+			let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() * 1000 + u64::from(time.subsec_millis())).unwrap_or(0);
+			json_log_line(now, &record.level().to_string(), record.target(), &record.args().to_string())
+		}),
+	};
+	let filters = level.map(str::to_owned)
+		.or_else(|| logging.filters())
+		.or_else(|| env::var("RUST_LOG").ok());
+	if let Some(filters) = filters {
+		builder.parse(&filters);
+	}
+	// the log is initialized once per process
+	let _ = builder.init();
+	Ok(())
+}
+
+/// Node answered by the operator of the init command.
+struct InitNode {
+	/// IPC path, or `http://` or `ws://` url.
+	endpoint: String,
+	account: String,
+	required_confirmations: u64,
+}
+
+/// Config answered by the operator of the init command.
+struct InitAnswers {
+	home: InitNode,
+	foreign: InitNode,
+	authorities: Vec<String>,
+	required_signatures: u32,
+	/// Directory with the compiled contracts.
+	contracts: String,
+}
+
+/// Returns the field of the node config, which `endpoint` is set to.
+fn endpoint_field(endpoint: &str) -> &'static str {
+	if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+		"http"
+	} else if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+		"ws"
+	} else {
+		"ipc"
+	}
+}
+
+/// Formats the config answered by the operator.
+fn init_config(answers: &InitAnswers) -> String {
+	let mut lines = Vec::new();
+	for &(role, node, contract) in &[("home", &answers.home, "EthereumBridge"), ("foreign", &answers.foreign, "KovanBridge")] {
+		lines.push(format!("[{}]", role));
+		lines.push(format!("account = {:?}", node.account));
+		lines.push(format!("{} = {:?}", endpoint_field(&node.endpoint), node.endpoint));
+		lines.push(format!("required_confirmations = {}", node.required_confirmations));
+		lines.push(String::new());
+		lines.push(format!("[{}.contract]", role));
+		lines.push(format!("bin = {:?}", format!("{}/{}.bin", answers.contracts, contract)));
+		lines.push(String::new());
+	}
+
+	lines.push("[authorities]".into());
+	lines.push("accounts = [".into());
+	lines.extend(answers.authorities.iter().map(|authority| format!("\t{:?},", authority)));
+	lines.push("]".into());
+	lines.push(format!("required_signatures = {}", answers.required_signatures));
+	lines.push(String::new());
+	lines.push("[transactions]".into());
+	lines.push("home_deploy = { gas = 500000 }".into());
+	lines.push("foreign_deploy = { gas = 500000 }".into());
+	lines.join("\n") + "\n"
+}
+
+/// Asks the `question` on the terminal until it's answered, unless it has a `default` answer.
+fn ask(question: &str, default: Option<&str>) -> Result<String, Error> {
+	loop {
+		match default {
+			Some(default) => print!("{} [{}]: ", question, default),
+			None => print!("{}: ", question),
+		}
+		io::stdout().flush().chain_err(|| "Cannot write to stdout")?;
+		let mut answer = String::new();
+		if io::stdin().read_line(&mut answer).chain_err(|| "Cannot read from stdin")? == 0 {
+			return Err("Setup has been interrupted".into());
+		}
+		match (answer.trim(), default) {
+			("", Some(default)) => return Ok(default.to_owned()),
+			("", None) => continue,
+			(answer, _) => return Ok(answer.to_owned()),
+		}
+	}
+}
+
+/// Asks the `question` on the terminal until it's answered with a number.
+fn ask_number<N: std::str::FromStr + ToString>(question: &str, default: N) -> Result<N, Error> {
+	loop {
+		match ask(question, Some(&default.to_string()))?.parse() {
+			Ok(number) => return Ok(number),
+			Err(_) => println!("Answer should be a number"),
+		}
+	}
+}
+
+fn ask_node(role: &str) -> Result<InitNode, Error> {
+	Ok(InitNode {
+		endpoint: ask(&format!("Endpoint of the {} node, IPC path or http:// or ws:// url", role), None)?,
+		account: ask(&format!("Account of the authority on the {} chain", role), None)?,
+		required_confirmations: ask_number(&format!("Confirmations required by the events of the {} chain", role), 12)?,
+	})
+}
+
+/// Formats what the nodes report, warning about the problems of the config.
+fn format_probes(config: &Config, probes: &[NodeProbe]) -> String {
+	let mut lines = Vec::new();
+	for probe in probes {
+		let role = if probe.chain == config.direction.home() { "home" } else { "foreign" };
+		lines.push(format!("{} node: chain ID {}, head block {}", role, probe.chain_id, probe.head));
+		if !probe.account_unlocked {
+			lines.push(format!("    warning: account {:?} is not unlocked by the node, its `signer` has to be configured", config.node(probe.chain).account));
+		}
+	}
+	if probes.windows(2).any(|pair| pair[0].chain_id == pair[1].chain_id) {
+		lines.push("warning: both nodes are connected to the same chain".into());
+	}
+	lines.join("\n")
+}
+
+/// Sets up a new bridge, as answered by the operator.
+fn execute_init(args: &Args) -> Result<String, Error> {
+	if args.arg_config.exists() {
+		return Err(format!("Config {:?} already exists", args.arg_config).into());
+	}
+
+	let home = ask_node("home")?;
+	let foreign = ask_node("foreign")?;
+	let authorities = ask("Accounts of the authorities, separated by commas", Some(&home.account))?
+		.split(',')
+		.map(|authority| authority.trim().to_owned())
+		.filter(|authority| !authority.is_empty())
+		.collect();
+	let answers = InitAnswers {
+		authorities,
+		required_signatures: ask_number("Signatures required by a withdraw", 1)?,
+		contracts: ask("Directory with the compiled contracts", Some("contracts"))?,
+		home,
+		foreign,
+	};
+	let toml = init_config(&answers);
+	let config = Config::load_from_str(&toml).chain_err(|| "Answers are not a valid config")?;
+
+	let mut backend = database::open(&config, &args.arg_database)?;
+	if backend.load()?.is_some() {
+		return Err(format!("Database {:?} already exists", args.arg_database).into());
+	}
 
-	Ok("Done".into())
+	println!("Checking the nodes");
+	let mut event_loop = Core::new().unwrap();
+	let app = Arc::new(App::new(config.clone(), &event_loop.handle())?);
+	let probes = event_loop.run(probe_nodes(app))?;
+	println!("{}", format_probes(&config, &probes));
+	if !confirm("Write the config?")? {
+		return Ok("Config has not been written".into());
+	}
+
+	fs::write(&args.arg_config, toml).chain_err(|| format!("Cannot write config to {:?}", args.arg_config))?;
+	Ok(format!("Config has been written to {:?}, start the bridge to deploy the contracts", args.arg_config))
+}
+
+/// Picks the config of the bridge with `namespace`, or the only bridge if it's not given.
+fn select_bridge(configs: Vec<Config>, namespace: Option<&str>) -> Result<Config, Error> {
+	match namespace {
+		Some(namespace) => configs.into_iter()
+			.find(|config| config.database_namespace.as_deref() == Some(namespace))
+			.ok_or_else(|| format!("Config doesn't define bridge {}", namespace).into()),
+		None if configs.len() == 1 => Ok(configs.into_iter().next().expect("there is a single config; qed")),
+		None => Err("Config defines several bridges, select one with --bridge".into()),
+	}
 }
 
+/// Executes a command other than running the bridges.
+fn execute_command(args: &Args, config: Config) -> Result<String, Error> {
+	if args.cmd_db {
+		return execute_db(args, &config);
+	}
+
+	if args.cmd_rewind {
+		return execute_rewind(args, &config);
+	}
+
+	if args.cmd_replay {
+		return execute_replay(args, config);
+	}
+
+	if args.cmd_stats {
+		let database = database::open(&config, &args.arg_database)?.load()?.ok_or_else(|| Error::from("Database is empty"))?;
+		if args.flag_json {
+			return Ok(to_json(&database.relay_fees));
+		}
+		return Ok(format_fees(&database.relay_fees));
+	}
+
+	info!(target: "bridge", "Starting event loop");
+	let mut event_loop = Core::new().unwrap();
+	let database = database::open(&config, &args.arg_database)?.load()?.ok_or_else(|| Error::from("Database is empty"))?;
+	let app = Arc::new(App::new(config, &event_loop.handle())?);
+
+	if args.cmd_status {
+		let status = event_loop.run(fetch_status(app, database))?;
+		if args.flag_json {
+			return Ok(to_json(&status));
+		}
+		return Ok(format_status(&status));
+	}
+
+	if args.cmd_export_events {
+		let format: fn(&[ExportedEvent]) -> String = match args.flag_format.as_str() {
+			_ if args.flag_json => events_json,
+			"csv" => events_csv,
+			"json" => events_json,
+			_ => return Err(format!("Invalid format `{}`, should be `csv` or `json`", args.flag_format).into()),
+		};
+		let direction = app.config.direction;
+		let range = ExportRange {
+			from: args.flag_from_block.as_deref().map(parse_block).transpose()?,
+			to: args.flag_to_block.as_deref().map(parse_block).transpose()?,
+			chain: match args.flag_chain.as_deref() {
+				None => None,
+				Some("home") => Some(direction.home()),
+				Some("foreign") => Some(direction.foreign()),
+				Some(chain) => return Err(format!("Invalid chain `{}`, should be `home` or `foreign`", chain).into()),
+			},
+		};
+		let events = event_loop.run(export_events(app, database, range))?;
+		return Ok(format(&events));
+	}
+
+	if args.cmd_watch {
+		let from = args.flag_from_block.as_deref().map(parse_block).transpose()?;
+		event_loop.run(create_watch(app, database, from).for_each(|event| {
+			println!("{}", format_watched_event(&event));
+			Ok(())
+		}))?;
+		return Ok("Watch stopped".into());
+	}
+
+	if args.cmd_relay_deposit || args.cmd_relay_withdraw {
+		let relay = if args.cmd_relay_deposit {
+			let transaction = args.arg_transaction.as_ref().expect("<transaction> is required by the relay-deposit command; qed");
+			ManualRelay::Deposit(transaction.parse().map_err(|_| Error::from(format!("Invalid transaction hash `{}`", transaction)))?)
+		} else {
+			let message_hash = args.arg_message_hash.as_ref().expect("<message-hash> is required by the relay-withdraw command; qed");
+			withdraw_relay(&database, message_hash, args.flag_transaction.as_deref())?
+		};
+		let relays = event_loop.run(manual_relay(app.clone(), &database, relay))?;
+		if relays.is_empty() {
+			return Ok("Nothing has been relayed".into());
+		}
+
+		// the relayed events are not relayed again once the bridge is started
+		let events = relays.iter().map(|relay| relay.event).collect::<Vec<_>>();
+		let mut backend = database::open(&app.config, &args.arg_database)?;
+		backend.record_relays(&relays)?;
+		backend.discard_assignments(&events)?;
+		backend.release_relays(&events)?;
+		let lines = relays.iter()
+			.map(|relay| format!("Relayed {:?} to {} by {:?}", relay.event.transaction_hash, relay.chain.as_str(), relay.transaction_hash))
+			.collect::<Vec<_>>();
+		return Ok(lines.join("\n"));
+	}
+
+	let checks = event_loop.run(verify_contracts(app, &database))?;
+	let report = format_contract_checks(&checks);
+	if checks.iter().any(|check| !check.problems.is_empty()) {
+		return Err(report.into());
+	}
+	Ok(report)
+}
+
+/// Parses block number, either decimal or `0x` prefixed hex.
+fn parse_block(block: &str) -> Result<u64, Error> {
+	let result = match block.strip_prefix("0x") {
+		Some(hex) => u64::from_str_radix(hex, 16),
+		None => block.parse(),
+	};
+	result.chain_err(|| format!("Invalid block number `{}`", block))
+}
+
+/// Creates the relay of the withdraw with `message_hash`, which signatures have been collected by `transaction`,
+/// or by the transaction of its assignment to this authority.
+fn withdraw_relay(database: &Database, message_hash: &str, transaction: Option<&str>) -> Result<ManualRelay, Error> {
+	let message_hash = message_hash.parse().map_err(|_| Error::from(format!("Invalid message hash `{}`", message_hash)))?;
+	let transaction = match transaction {
+		Some(transaction) => transaction.parse().map_err(|_| Error::from(format!("Invalid transaction hash `{}`", transaction)))?,
+		None => database.pending_assignments.iter()
+			.find(|assignment| assignment.message_hash == message_hash)
+			.map(|assignment| assignment.event.transaction_hash)
+			.ok_or_else(|| Error::from(format!("Withdraw {:?} is not assigned to this authority, pass the transaction, which has collected its signatures, with --transaction", message_hash)))?,
+	};
+	Ok(ManualRelay::Withdraw {
+		message_hash,
+		transaction,
+	})
+}
+
+/// Creates the change of the last block checked by `stream` to `block`.
+fn checkpoint_change(database: &Database, stream: &str, block: u64, timestamp: u64) -> Result<CheckpointChange, Error> {
+	let check = BridgeChecked::from_stream(stream, block)
+		.ok_or_else(|| Error::from(format!("Invalid stream `{}`, should be one of `deposit_relay`, `withdraw_relay`, `withdraw_confirm`, `mainnet_message_relay` and `testnet_message_relay`", stream)))?;
+	Ok(CheckpointChange {
+		stream: stream.into(),
+		from: database.checked(check),
+		to: block,
+		timestamp,
+	})
+}
+
+/// Reads the change of the checkpoint requested by the set-checkpoint or rewind command.
+fn requested_change(args: &Args, database: &Database) -> Result<CheckpointChange, Error> {
+	let stream = args.arg_stream.as_ref().expect("<stream> is required by the set-checkpoint and rewind commands; qed");
+	let block = parse_block(args.arg_block.as_ref().expect("<block> is required by the set-checkpoint and rewind commands; qed"))?;
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+	checkpoint_change(database, stream, block, now)
+}
+
+/// Asks the `question` on the terminal, returning `true` if it's answered with yes.
+fn confirm(question: &str) -> Result<bool, Error> {
+	print!("{} [y/N] ", question);
+	io::stdout().flush().chain_err(|| "Cannot write to stdout")?;
+	let mut answer = String::new();
+	io::stdin().read_line(&mut answer).chain_err(|| "Cannot read from stdin")?;
+	Ok(match answer.trim().to_lowercase().as_str() {
+		"y" | "yes" => true,
+		_ => false,
+	})
+}
+
+fn execute_rewind(args: &Args, config: &Config) -> Result<String, Error> {
+	let mut backend = database::open(config, &args.arg_database)?;
+	let mut database = backend.load()?.ok_or_else(|| Error::from("Database is empty"))?;
+	let change = requested_change(args, &database)?;
+	if change.to >= change.from {
+		return Err(format!("Last block checked by {} is {}, it can be rewound only to an earlier block", change.stream, change.from).into());
+	}
+
+	let question = format!("Blocks {} to {} will be checked again by {}, continue?", change.to + 1, change.from, change.stream);
+	if !args.flag_yes && !confirm(&question)? {
+		return Ok("Rewind cancelled".into());
+	}
+	database.change_checkpoint(&change);
+	database.verify()?;
+	backend.change_checkpoint(&change)?;
+	Ok(format!("Last block checked by {} rewound from {} to {}", change.stream, change.from, change.to))
+}
+
+/// Returns the streams, in which only the replayed `stream` is enabled.
+fn replayed_streams(stream: &str) -> Result<Streams, Error> {
+	let mut streams = Streams {
+		deposit_relay: false,
+		withdraw_relay: false,
+		withdraw_confirm: false,
+		mainnet_message_relay: false,
+		testnet_message_relay: false,
+	};
+	match stream {
+		"deposit_relay" => streams.deposit_relay = true,
+		"withdraw_relay" => streams.withdraw_relay = true,
+		"withdraw_confirm" => streams.withdraw_confirm = true,
+		"mainnet_message_relay" => streams.mainnet_message_relay = true,
+		"testnet_message_relay" => streams.testnet_message_relay = true,
+		_ => return Err(format!("Unknown stream `{}`", stream).into()),
+	}
+	Ok(streams)
+}
+
+fn execute_replay(args: &Args, mut config: Config) -> Result<String, Error> {
+	let stream = args.flag_stream.as_ref().expect("--stream is required by the replay command; qed");
+	let from = parse_block(args.flag_from.as_ref().expect("--from is required by the replay command; qed"))?;
+	let to = parse_block(args.flag_to.as_ref().expect("--to is required by the replay command; qed"))?;
+	if from > to {
+		return Err(format!("Block {} is after block {}", from, to).into());
+	}
+	config.streams = replayed_streams(stream)?;
+	if config.message_bridge.is_none() && (config.streams.mainnet_message_relay || config.streams.testnet_message_relay) {
+		return Err("Messages can be relayed only if `message_bridge` is configured".into());
+	}
+
+	let mut backend = database::open(&config, &args.arg_database)?;
+	let mut database = backend.load()?.ok_or_else(|| Error::from("Database is empty"))?;
+	if args.flag_dry_run {
+		backend = Box::new(MemoryBackend::new(Some(database.clone())));
+	}
+	backend.lock()?;
+	// the stream starts after the checkpoint of the copy, which is never written
+	let checkpoint = BridgeChecked::from_stream(stream, from.saturating_sub(1)).expect("the stream is known; qed");
+	database.update_checkpoint(checkpoint);
+
+	info!(target: "bridge", "Starting event loop");
+	let mut event_loop = Core::new().unwrap();
+	let mut app = App::new(config, &event_loop.handle())?;
+	if args.flag_dry_run {
+		app.dry_run = Some(DryRun {
+			simulate: args.flag_simulate,
+		});
+	}
+	app.once = true;
+	app.until = Some(to);
+	let app = Arc::new(app);
+
+	info!(target: "bridge", "Replaying blocks {} to {} with {}", from, to, stream);
+	event_loop.run(create_bridge(app, &database, backend).for_each(|_| Ok(())))?;
+	Ok(format!("Replayed blocks {} to {} with {}", from, to, stream))
+}
+
+/// Formats the output of a command as JSON.
+fn to_json<T: Serialize>(value: &T) -> String {
+	serde_json::to_string_pretty(value).expect("serialization can't fail; qed")
+}
+
+/// Formats fees of every component and chain, followed by the totals of the chains.
+fn format_fees(fees: &[RelayFees]) -> String {
+	if fees.is_empty() {
+		return "No relay fees recorded".into();
+	}
+
+	let mut totals: Vec<RelayFees> = Vec::new();
+	for fee in fees {
+		match totals.iter_mut().find(|total| total.chain == fee.chain) {
+			Some(total) => total.add(fee),
+			None => totals.push(RelayFees {
+				stream: "total".into(),
+				..fee.clone()
+			}),
+		}
+	}
+
+	let mut lines = vec![format!("{:<24}{:<10}{:>14}{:>16}  {}", "component", "chain", "transactions", "gas used", "fees (ETH)")];
+	lines.extend(fees.iter().chain(&totals).map(|fee| {
+		format!("{:<24}{:<10}{:>14}{:>16}  {}", fee.stream, fee.chain.as_str(), fee.transactions, fee.gas_used, format_ether(fee.fees))
+	}));
+	lines.join("\n")
+}
+
+/// Formats the event observed by the watch as a single line.
+fn format_watched_event(event: &ExportedEvent) -> String {
+	let mut line = format!("{} block {}: {} {:?}", event.chain.as_str(), event.block, event.kind.as_str(), event.account);
+	if let Some(value) = event.value {
+		line.push_str(&format!(" {} ETH", format_ether(value)));
+	}
+	if let Some(hash) = event.message_hash {
+		line.push_str(&format!(" message {:?}", hash));
+	}
+	line.push_str(&format!(" in {:?} log {}, {}", event.transaction_hash, event.log_index, event.kind.status()));
+	if let Some(hash) = event.relay_transaction {
+		line.push_str(&format!(", relayed by {:?}", hash));
+	}
+	line
+}
+
+/// Formats the state of every chain, followed by the checkpoints of the components and the numbers of pending events.
+fn format_status(status: &Status) -> String {
+	let mut lines = Vec::new();
+	for chain in &status.chains {
+		lines.push(format!("{}: head block {}, contract {:?}, journaled relays: {}", chain.chain.as_str(), chain.head, chain.contract, chain.journaled));
+		lines.extend(chain.balances.iter().map(|&(account, balance)| format!("    {:?}  {} ETH", account, format_ether(balance))));
+	}
+
+	lines.push(format!("{:<24}{:<10}{:>12}{:>10}", "component", "chain", "checkpoint", "lag"));
+	lines.extend(status.streams.iter().map(|stream| {
+		format!("{:<24}{:<10}{:>12}{:>10}", stream.checkpoint.stream(), stream.chain.as_str(), stream.checkpoint.block(), stream.lag)
+	}));
+
+	lines.push(format!("held relays: {}", status.held));
+	lines.push(format!("assigned withdraws: {}", status.assignments));
+	lines.push(format!("deposits awaiting approval: {}", status.unapproved_deposits));
+	lines.push(format!("quarantined logs: {}", status.quarantined));
+	lines.join("\n")
+}
+
+/// Formats results of the checks of the contracts, listing the problems of each.
+fn format_contract_checks(checks: &[ContractCheck]) -> String {
+	let mut lines = Vec::new();
+	for check in checks {
+		if check.problems.is_empty() {
+			lines.push(format!("{} contract {:?}: OK", check.chain.as_str(), check.address));
+		} else {
+			lines.push(format!("{} contract {:?}:", check.chain.as_str(), check.address));
+			lines.extend(check.problems.iter().map(|problem| format!("    {}", problem)));
+		}
+	}
+	lines.join("\n")
+}
+
+fn execute_db(args: &Args, config: &Config) -> Result<String, Error> {
+	let mut backend = database::open(config, &args.arg_database)?;
+
+	if args.cmd_import {
+		let path = args.arg_file.as_ref().expect("<file> is required by the import command; qed");
+		let mut json = String::new();
+		fs::File::open(path)
+			.and_then(|mut file| file.read_to_string(&mut json))
+			.chain_err(|| format!("Cannot read {:?}", path))?;
+		let database = Database::from_json(&json)?;
+		database.verify()?;
+		backend.save(&database)?;
+		return Ok("Database imported".into());
+	}
+
+	let mut database = backend.load()?.ok_or_else(|| Error::from("Database is empty"))?;
+
+	if args.cmd_export {
+		Ok(database.to_json())
+	} else if args.cmd_verify {
+		database.verify()?;
+		Ok("Database is consistent".into())
+	} else if args.cmd_prune {
+		let mut policy = config.database_retention.clone();
+		if let Some(days) = args.flag_max_age_days {
+			policy.max_age = Some(Duration::from_secs(days * 24 * 60 * 60));
+		}
+		if args.flag_max_relays.is_some() {
+			policy.max_relays = args.flag_max_relays;
+		}
+		if args.flag_checked {
+			policy.prune_checked = true;
+		}
+		if policy.is_empty() {
+			return Err("Nothing to prune, set `database_retention` or pass --max-age-days, --max-relays or --checked".into());
+		}
+
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+		let checked = checked_blocks(&checkpoints(config, &database));
+		let pruned = backend.prune(&policy, now, &checked)?;
+		backend.compact()?;
+		Ok(format!("Pruned {} relays", pruned))
+	} else if args.cmd_approve_deposit {
+		let transaction = args.arg_transaction.as_ref().expect("<transaction> is required by the approve-deposit command; qed");
+		let event = ProcessedEvent {
+			chain: config.direction.home(),
+			transaction_hash: transaction.parse().map_err(|_| Error::from(format!("Invalid transaction hash `{}`", transaction)))?,
+			log_index: args.arg_log_index.expect("<log-index> is required by the approve-deposit command; qed"),
+		};
+		let deposit = database.filtered_deposits.iter()
+			.find(|deposit| deposit.event == event)
+			.copied()
+			.ok_or_else(|| Error::from(format!("Deposit {:?} has not been filtered", event)))?;
+		let entry = deposit_relay_entry(config, &foreign::KovanBridge::default(), database.foreign_contract_address, deposit.recipient, deposit.value, event);
+		database.approve_deposit(entry)?;
+		database.verify()?;
+		backend.save(&database)?;
+		Ok(format!("Deposit of {} to {:?} will be relayed once the bridge is started", deposit.value, deposit.recipient))
+	} else if args.cmd_cancel_relay {
+		let transaction = args.arg_transaction.as_ref().expect("<transaction> is required by the cancel-relay command; qed");
+		let transaction_hash = transaction.parse().map_err(|_| Error::from(format!("Invalid transaction hash `{}`", transaction)))?;
+		let log_index = args.arg_log_index.expect("<log-index> is required by the cancel-relay command; qed");
+		let relay = database.held_relays.iter()
+			.find(|relay| relay.entry.event.transaction_hash == transaction_hash && relay.entry.event.log_index == log_index)
+			.cloned()
+			.ok_or_else(|| Error::from(format!("Relay of log {} of {:?} is not held", log_index, transaction_hash)))?;
+		backend.release_relays(&[relay.entry.event])?;
+		Ok(format!("Relay of {:?} to {} has been cancelled", relay.entry.event.transaction_hash, relay.entry.chain.as_str()))
+	} else {
+		assert!(args.cmd_set_checkpoint, "all database commands are handled; qed");
+		let change = requested_change(args, &database)?;
+		database.change_checkpoint(&change);
+		database.verify()?;
+		backend.change_checkpoint(&change)?;
+		Ok(format!("Last block checked by {} set to {}", change.stream, change.to))
+	}
+}
 
 #[cfg(test)]
 mod tests {
+	use serde_json;
+	use bridge::bridge::{BridgeChecked, Status, ExportedEvent, EventKind, ChainStatus, StreamStatus, ContractCheck, ManualRelay};
+	use bridge::database::{Database, RelayFees, PendingAssignment, ProcessedEvent, Chain};
+	use bridge::bridge::NodeProbe;
+	use bridge::config::Config;
+	use super::{InitAnswers, InitNode, parse_block, checkpoint_change, withdraw_relay, replayed_streams, json_log_line, init_config, endpoint_field, format_probes, format_fees, format_status, format_contract_checks, format_watched_event};
+
+	#[test]
+	fn test_parse_block() {
+		assert_eq!(0x1234, parse_block("0x1234").unwrap());
+		assert_eq!(1234, parse_block("1234").unwrap());
+		assert!(parse_block("0x").is_err());
+		assert!(parse_block("block").is_err());
+	}
+
+	#[test]
+	fn test_init_config() {
+		let answers = InitAnswers {
+			home: InitNode {
+				endpoint: "/home.ipc".into(),
+				account: "0x0000000000000000000000000000000000000001".into(),
+				required_confirmations: 12,
+			},
+			foreign: InitNode {
+				endpoint: "ws://localhost:8546".into(),
+				account: "0x0000000000000000000000000000000000000002".into(),
+				required_confirmations: 0,
+			},
+			authorities: vec!["0x0000000000000000000000000000000000000001".into(), "0x0000000000000000000000000000000000000003".into()],
+			required_signatures: 2,
+			contracts: "../contracts".into(),
+		};
+		let config = Config::load_from_str(&init_config(&answers)).unwrap();
+		assert_eq!("0x0000000000000000000000000000000000000001", format!("{:?}", config.home.account));
+		assert_eq!(12, config.home.required_confirmations);
+		assert_eq!("0x0000000000000000000000000000000000000002", format!("{:?}", config.foreign.account));
+		assert_eq!(0, config.foreign.required_confirmations);
+		assert_eq!(answers.authorities, config.authorities.accounts.iter().map(|account| format!("{:?}", account)).collect::<Vec<_>>());
+		assert_eq!(2, config.authorities.required_signatures);
+		assert_eq!(500000, config.txs.mainnet_deploy.gas);
+
+		assert_eq!("http", endpoint_field("https://node.example.com"));
+		assert_eq!("ws", endpoint_field("ws://localhost:8546"));
+		assert_eq!("ipc", endpoint_field("/home.ipc"));
+	}
+
+	#[test]
+	fn test_format_probes() {
+		let config = Config::load_from_str(&init_config(&InitAnswers {
+			home: InitNode {
+				endpoint: "/home.ipc".into(),
+				account: "0x0000000000000000000000000000000000000001".into(),
+				required_confirmations: 12,
+			},
+			foreign: InitNode {
+				endpoint: "/foreign.ipc".into(),
+				account: "0x0000000000000000000000000000000000000002".into(),
+				required_confirmations: 0,
+			},
+			authorities: vec!["0x0000000000000000000000000000000000000001".into()],
+			required_signatures: 1,
+			contracts: "../contracts".into(),
+		})).unwrap();
+		let probes = vec![
+			NodeProbe {
+				chain: Chain::Mainnet,
+				chain_id: 1,
+				head: 100,
+				account_unlocked: true,
+			},
+			NodeProbe {
+				chain: Chain::Testnet,
+				chain_id: 1,
+				head: 200,
+				account_unlocked: false,
+			},
+		];
+		let expected = "\
+home node: chain ID 1, head block 100
+foreign node: chain ID 1, head block 200
+    warning: account 0x0000000000000000000000000000000000000002 is not unlocked by the node, its `signer` has to be configured
+warning: both nodes are connected to the same chain";
+		assert_eq!(expected, format_probes(&config, &probes));
+	}
+
+	#[test]
+	fn test_json_log_line() {
+		let line = json_log_line(1_500_000_000_000, "INFO", "bridge", "Relayed \"deposit\"\nto testnet");
+		assert_eq!(r#"{"timestamp":1500000000000,"level":"INFO","target":"bridge","message":"Relayed \"deposit\"\nto testnet"}"#, line);
+	}
+
+	#[test]
+	fn test_checkpoint_change() {
+		let database = Database {
+			checked_withdraw_relay: 300,
+			..Database::default()
+		};
+		let change = checkpoint_change(&database, "withdraw_relay", 250, 1_500_000_000).unwrap();
+		assert_eq!("withdraw_relay", change.stream);
+		assert_eq!(300, change.from);
+		assert_eq!(250, change.to);
+		assert_eq!(1_500_000_000, change.timestamp);
+		assert!(checkpoint_change(&database, "relay", 250, 1_500_000_000).is_err());
+	}
+
+	#[test]
+	fn test_withdraw_relay() {
+		let message_hash = "0x0000000000000000000000000000000000000000000000000000000000000001";
+		let transaction = "0x0000000000000000000000000000000000000000000000000000000000000002";
+		let expected = ManualRelay::Withdraw {
+			message_hash: 1.into(),
+			transaction: 2.into(),
+		};
+		assert_eq!(expected, withdraw_relay(&Database::default(), message_hash, Some(transaction)).unwrap());
+		// the transaction has to be given unless the withdraw is assigned to this authority
+		assert!(withdraw_relay(&Database::default(), message_hash, None).is_err());
+
+		let database = Database {
+			pending_assignments: vec![PendingAssignment {
+				message_hash: 1.into(),
+				authority: 3.into(),
+				block: Some(100),
+				failures: 0,
+				due: 0,
+				event: ProcessedEvent {
+					chain: Chain::Testnet,
+					transaction_hash: 2.into(),
+					log_index: 0,
+				},
+			}],
+			..Database::default()
+		};
+		assert_eq!(expected, withdraw_relay(&database, message_hash, None).unwrap());
+		assert!(withdraw_relay(&database, "message", None).is_err());
+	}
+
+	#[test]
+	fn test_replayed_streams() {
+		let streams = replayed_streams("withdraw_relay").unwrap();
+		assert!(streams.withdraw_relay);
+		assert!(!streams.deposit_relay && !streams.withdraw_confirm && !streams.mainnet_message_relay && !streams.testnet_message_relay);
+		assert!(replayed_streams("testnet_message_relay").unwrap().testnet_message_relay);
+		assert!(replayed_streams("relay").is_err());
+	}
+
+	#[test]
+	fn test_format_fees() {
+		let fees = |stream: &str, chain, gas_used| RelayFees {
+			stream: stream.into(),
+			chain,
+			transactions: 2,
+			gas_used,
+			fees: (gas_used * 1_000_000_000).into(),
+		};
+		let expected = "\
+component               chain       transactions        gas used  fees (ETH)
+deposit_relay           testnet                2          100000  0.0001
+withdraw_confirm        testnet                2           50000  0.00005
+withdraw_relay          mainnet                2          150000  0.00015
+total                   testnet                4          150000  0.00015
+total                   mainnet                2          150000  0.00015";
+		assert_eq!(expected, format_fees(&[
+			fees("deposit_relay", Chain::Testnet, 100_000),
+			fees("withdraw_confirm", Chain::Testnet, 50_000),
+			fees("withdraw_relay", Chain::Mainnet, 150_000),
+		]));
+		assert_eq!("No relay fees recorded", format_fees(&[]));
+	}
+
+	#[test]
+	fn test_format_watched_event() {
+		let mut event = ExportedEvent {
+			kind: EventKind::Deposit,
+			chain: Chain::Mainnet,
+			block: 100,
+			transaction_hash: 1.into(),
+			log_index: 2,
+			account: 0xa.into(),
+			value: Some(1_500_000_000_000_000_000u64.into()),
+			message_hash: None,
+			relay_transaction: None,
+		};
+		assert_eq!(format!("mainnet block 100: deposit {:?} 1.5 ETH in {:?} log 2, waiting for the relay to foreign", event.account, event.transaction_hash), format_watched_event(&event));
+
+		event.kind = EventKind::CollectedSignatures;
+		event.chain = Chain::Testnet;
+		event.value = None;
+		event.message_hash = Some(3.into());
+		event.relay_transaction = Some(4.into());
+		assert_eq!(
+			format!("testnet block 100: collected_signatures {:?} message {:?} in {:?} log 2, waiting for the relay to home, relayed by {:?}", event.account, event.message_hash.unwrap(), event.transaction_hash, event.relay_transaction.unwrap()),
+			format_watched_event(&event)
+		);
+	}
+
+	#[test]
+	fn test_format_status() {
+		let status = Status {
+			chains: vec![
+				ChainStatus {
+					chain: Chain::Mainnet,
+					head: 0x100,
+					contract: 1.into(),
+					balances: vec![(0xa.into(), 1_500_000_000_000_000_000u64.into())],
+					journaled: 0,
+				},
+				ChainStatus {
+					chain: Chain::Testnet,
+					head: 300,
+					contract: 2.into(),
+					balances: vec![],
+					journaled: 1,
+				},
+			],
+			streams: vec![
+				StreamStatus {
+					checkpoint: BridgeChecked::DepositRelay(250),
+					chain: Chain::Mainnet,
+					lag: 6,
+				},
+				StreamStatus {
+					checkpoint: BridgeChecked::WithdrawConfirm(300),
+					chain: Chain::Testnet,
+					lag: 0,
+				},
+			],
+			held: 2,
+			assignments: 0,
+			unapproved_deposits: 1,
+			quarantined: 0,
+		};
+		let expected = "\
+mainnet: head block 256, contract 0x0000000000000000000000000000000000000001, journaled relays: 0
+    0x000000000000000000000000000000000000000a  1.5 ETH
+testnet: head block 300, contract 0x0000000000000000000000000000000000000002, journaled relays: 1
+component               chain       checkpoint       lag
+deposit_relay           mainnet            250         6
+withdraw_confirm        testnet            300         0
+held relays: 2
+assigned withdraws: 0
+deposits awaiting approval: 1
+quarantined logs: 0";
+		assert_eq!(expected, format_status(&status));
+		assert_eq!(r#"{"stream":"deposit_relay","block":250,"chain":"mainnet","lag":6}"#, serde_json::to_string(&status.streams[0]).unwrap());
+	}
+
+	#[test]
+	fn test_format_contract_checks() {
+		let checks = vec![
+			ContractCheck {
+				chain: Chain::Mainnet,
+				address: 1.into(),
+				problems: vec![],
+			},
+			ContractCheck {
+				chain: Chain::Testnet,
+				address: 2.into(),
+				problems: vec!["Contract requires 1 signatures, but `required_signatures` is 2".into()],
+			},
+		];
+		let expected = "\
+mainnet contract 0x0000000000000000000000000000000000000001: OK
+testnet contract 0x0000000000000000000000000000000000000002:
+    Contract requires 1 signatures, but `required_signatures` is 2";
+		assert_eq!(expected, format_contract_checks(&checks));
+	}
 }
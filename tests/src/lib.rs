@@ -7,7 +7,9 @@ extern crate bridge;
 extern crate pretty_assertions;
 
 use std::cell::Cell;
-use web3::Transport;
+use futures::{Future, Stream};
+use web3::{Transport, BatchTransport};
+use bridge::transport::PubSub;
 
 pub struct MockedRequest {
 	pub method: String,
@@ -26,9 +28,28 @@ impl From<(&'static str, &'static str)> for MockedRequest {
 pub struct MockedTransport {
 	pub requests: Cell<usize>,
 	pub expected_requests: Vec<MockedRequest>,
+	/// Results of the requests. Objects with the single `error` field are returned as rpc errors.
 	pub mocked_responses: Vec<&'static str>,
 }
 
+fn mocked_response(response: &str) -> Result<rpc::Value, web3::Error> {
+	let value: rpc::Value = serde_json::from_str(response).expect("invalid response");
+	match value.as_object() {
+		Some(object) if object.len() == 1 && object.contains_key("error") => {
+			let error = serde_json::from_value(object["error"].clone()).expect("invalid error");
+			return Err(web3::Error::Rpc(error));
+		},
+		_ => {},
+	}
+
+	Ok(value)
+}
+
+/// Parses `0x`-prefixed hex encoded bytes.
+pub fn bytes(hex: &str) -> web3::types::Bytes {
+	serde_json::from_value(serde_json::Value::String(hex.to_owned())).expect("invalid bytes")
+}
+
 impl Transport for MockedTransport {
 	type Out = web3::Result<rpc::Value>;
 
@@ -44,8 +65,76 @@ impl Transport for MockedTransport {
 
 	fn send(&self, _id: usize, _request: rpc::Call) -> web3::Result<rpc::Value> {
 		let response = self.mocked_responses.iter().nth(self.requests.get() - 1).expect("missing response");
-		let f = futures::finished(serde_json::from_str(response).expect("invalid response"));
-		Box::new(f)
+		Box::new(futures::done(mocked_response(response)))
+	}
+}
+
+impl PubSub for MockedTransport {
+	type Notifications = futures::stream::Empty<rpc::Value, web3::Error>;
+
+	fn supports_subscriptions(&self) -> bool {
+		false
+	}
+
+	fn notifications(&self, _subscription: &str) -> Self::Notifications {
+		futures::stream::empty()
+	}
+}
+
+impl BatchTransport for MockedTransport {
+	type Batch = web3::Result<Vec<Result<rpc::Value, web3::Error>>>;
+
+	fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (usize, rpc::Call)> {
+		let responses = requests.into_iter()
+			.map(|(id, _)| self.mocked_responses.get(id - 1).expect("missing response"))
+			.map(|response| mocked_response(response))
+			.collect();
+		Box::new(futures::finished(responses))
+	}
+}
+
+/// Mocked transport, which delivers the notifications of its subscriptions, e.g. like a websocket.
+#[derive(Clone)]
+pub struct MockedSubscriptions<'a> {
+	pub transport: &'a MockedTransport,
+	/// Ids of the subscriptions with the results of their notifications.
+	pub notifications: Vec<(&'static str, &'static str)>,
+}
+
+impl<'a> Transport for MockedSubscriptions<'a> {
+	type Out = web3::Result<rpc::Value>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (usize, rpc::Call) {
+		self.transport.prepare(method, params)
+	}
+
+	fn send(&self, id: usize, request: rpc::Call) -> web3::Result<rpc::Value> {
+		self.transport.send(id, request)
+	}
+}
+
+impl<'a> BatchTransport for MockedSubscriptions<'a> {
+	type Batch = web3::Result<Vec<Result<rpc::Value, web3::Error>>>;
+
+	fn send_batch<T>(&self, requests: T) -> Self::Batch where T: IntoIterator<Item = (usize, rpc::Call)> {
+		self.transport.send_batch(requests)
+	}
+}
+
+impl<'a> PubSub for MockedSubscriptions<'a> {
+	type Notifications = Box<Stream<Item = rpc::Value, Error = web3::Error>>;
+
+	fn supports_subscriptions(&self) -> bool {
+		true
+	}
+
+	fn notifications(&self, subscription: &str) -> Self::Notifications {
+		let notifications = self.notifications.iter()
+			.filter(|&&(id, _)| id == subscription)
+			.map(|&(_, result)| Ok(serde_json::from_str(result).expect("invalid notification")))
+			.collect::<Vec<_>>();
+		// the subscription is never lost
+		Box::new(futures::stream::iter_result(notifications).chain(futures::future::empty().into_stream()))
 	}
 }
 
@@ -80,23 +169,43 @@ macro_rules! test_app_stream {
 		database => $db: expr,
 		mainnet => account => $mainnet_acc: expr, confirmations => $mainnet_conf: expr;
 		testnet => account => $testnet_acc: expr, confirmations => $testnet_conf: expr;
-		authorities => accounts => $authorities_accs: expr, signatures => $signatures: expr;
+		authorities => accounts => $authorities_accs: expr, signatures => $signatures: expr $(, assignment => $assignment: expr, fallback => $fallback: expr)?;
+		$(deposits => min => $min_deposit: expr, max => $max_deposit: expr;)?
+		$(message_bridge => mainnet => $message_mainnet: expr, testnet => $message_testnet: expr;)?
+		$(log_filters => $log_filters: expr;)?
+		$(verify_relays_after => $verify_relays_after: expr;)?
+		$(pause_switch => $pause_switch: expr;)?
+		$(min_balance => $min_balance: expr;)?
+		$(direction => $direction: expr;)?
+		$(confirmations => $confirmations: expr;)?
+		$(relay_delay => $relay_delay: expr;)?
+		$(signers => mainnet => $mainnet_signer: expr, testnet => $testnet_signer: expr;)?
+		$(manage_nonces => mainnet => $mainnet_nonces: expr, testnet => $testnet_nonces: expr;)?
+		$(fee_models => mainnet => $mainnet_fees: expr, testnet => $testnet_fees: expr;)?
+		$(max_gas_price => mainnet => $mainnet_max_gas_price: expr, testnet => $testnet_max_gas_price: expr;)?
 		txs => $txs: expr,
 		init => $init_stream: expr,
 		expected => $expected: expr,
 		mainnet_transport => [$($mainnet_method: expr => req => $mainnet_req: expr, res => $mainnet_res: expr ;)*],
 		testnet_transport => [$($testnet_method: expr => req => $testnet_req: expr, res => $testnet_res: expr ;)*]
+		$(, mainnet_submission_transport => [$($mainnet_submission_method: expr => req => $mainnet_submission_req: expr, res => $mainnet_submission_res: expr ;)*])?
+		$(, testnet_submission_transport => [$($testnet_submission_method: expr => req => $testnet_submission_req: expr, res => $testnet_submission_res: expr ;)*])?
 	) => {
 		#[test]
-		#[allow(unused_imports)]
+		#[allow(unused_imports, unused_mut)]
 		fn $name() {
 			use self::std::sync::Arc;
 			use self::std::time::Duration;
 			use self::futures::{Future, Stream};
+			use self::bridge::api::{BlockWatcher, BlockWatcherInit};
 			use self::bridge::app::{App, Connections};
-			use self::bridge::contracts::{testnet, mainnet};
-			use self::bridge::config::{Config, Authorities, Node, ContractConfig, Transactions, TransactionConfig};
-			use self::bridge::database::Database;
+			use self::bridge::bridge::{PauseWatcher, GasPriceGuard, Funds, Nonces, SentPayloads};
+			use self::bridge::metrics::Metrics;
+			use self::bridge::signer::Signer;
+			use self::bridge::transaction::FeeModel;
+			use self::bridge::contracts::{home, foreign, message};
+			use self::bridge::config::{Config, DatabaseBackendKind, Authorities, WithdrawRelayAssignment, MessageBridge, PauseSwitch, MinBalance, RelayDelay, Direction, Confirmations, PollIntervals, Logging, LargeDeposits, LogFilters, LogFilter, Streams, Node, ContractConfig, Transactions, TransactionConfig, Endpoint, RetryPolicies, HttpAuth, RequestTimeouts};
+			use self::bridge::database::{Database, ProcessedEvent, RelayOutcome, JournalEntry, PendingAssignment, HeldRelay, Chain};
 
 			let mainnet = $crate::MockedTransport {
 				requests: Default::default(),
@@ -110,50 +219,222 @@ macro_rules! test_app_stream {
 				mocked_responses: vec![$($testnet_res),*],
 			};
 
-			let config = Config {
+			// transactions are sent through the query transports, unless the submission transports are given
+			let mainnet_submission: Option<$crate::MockedTransport> = None $(.or(Some($crate::MockedTransport {
+				requests: Default::default(),
+				expected_requests: vec![$($mainnet_submission_method),*].into_iter().zip(vec![$($mainnet_submission_req),*].into_iter()).map(Into::into).collect(),
+				mocked_responses: vec![$($mainnet_submission_res),*],
+			})))?;
+			let testnet_submission: Option<$crate::MockedTransport> = None $(.or(Some($crate::MockedTransport {
+				requests: Default::default(),
+				expected_requests: vec![$($testnet_submission_method),*].into_iter().zip(vec![$($testnet_submission_req),*].into_iter()).map(Into::into).collect(),
+				mocked_responses: vec![$($testnet_submission_res),*],
+			})))?;
+
+			let mut config = Config {
 				txs: $txs,
-				mainnet: Node {
+				database_backend: DatabaseBackendKind::Toml,
+				database_namespace: None,
+				database_key: None,
+				database_retention: Default::default(),
+				min_deposit: None,
+				max_deposit: None,
+				deposit_log_index: false,
+				message_bridge: None,
+				log_filters: LogFilters::default(),
+				streams: Streams::default(),
+				verify_relays_after: None,
+				expire_relays_after: None,
+				pause_switch: None,
+				min_balance: None,
+				relay_delay: None,
+				duplicate_window: None,
+				direction: Direction::MainnetToTestnet,
+				confirmations: Confirmations::default(),
+				poll_intervals: PollIntervals::default(),
+				logging: Logging::default(),
+				metrics_address: None,
+				health_staleness: Duration::from_secs(600),
+				// the nodes are swapped below if the bridge is mirrored
+				home: Node {
 					account: $mainnet_acc.parse().unwrap(),
-					ipc: "".into(),
+					endpoints: vec![Endpoint::Ipc("".into())],
+					submission_endpoints: vec![],
+					failover_errors: 3,
+					max_rps: None,
+					max_concurrent_requests: 16,
+					http_auth: HttpAuth::default(),
+					proxy: None,
+					pinned_certificates: vec![],
 					contract: ContractConfig {
 						bin: Default::default(),
 					},
 					poll_interval: Duration::from_secs(0),
+					adaptive_polling: None,
 					request_timeout: Duration::from_secs(5),
+					request_timeouts: RequestTimeouts::new(Duration::from_secs(5)),
+					retry: RetryPolicies::default(),
 					required_confirmations: $mainnet_conf,
+					block_tag: None,
+					max_blocks_per_query: None,
+					catch_up: None,
+					signer: None,
+					pool: vec![],
+					manage_nonces: false,
+					fee_model: FeeModel::Legacy,
+					max_gas_price: None,
 				},
-				testnet: Node {
+				foreign: Node {
 					account: $testnet_acc.parse().unwrap(),
-					ipc: "".into(),
+					endpoints: vec![Endpoint::Ipc("".into())],
+					submission_endpoints: vec![],
+					failover_errors: 3,
+					max_rps: None,
+					max_concurrent_requests: 16,
+					http_auth: HttpAuth::default(),
+					proxy: None,
+					pinned_certificates: vec![],
 					contract: ContractConfig {
 						bin: Default::default(),
 					},
 					poll_interval: Duration::from_secs(0),
+					adaptive_polling: None,
 					request_timeout: Duration::from_secs(5),
+					request_timeouts: RequestTimeouts::new(Duration::from_secs(5)),
+					retry: RetryPolicies::default(),
 					required_confirmations: $testnet_conf,
+					block_tag: None,
+					max_blocks_per_query: None,
+					catch_up: None,
+					signer: None,
+					pool: vec![],
+					manage_nonces: false,
+					fee_model: FeeModel::Legacy,
+					max_gas_price: None,
 				},
 				authorities: Authorities {
 					accounts: $authorities_accs.iter().map(|a: &&str| a.parse().unwrap()).collect(),
 					required_signatures: $signatures,
+					withdraw_relay_assignment: WithdrawRelayAssignment::Contract,
+					withdraw_relay_fallback: None,
 				}
 			};
+			$(
+				config.authorities.withdraw_relay_assignment = $assignment;
+				config.authorities.withdraw_relay_fallback = $fallback;
+			)?
+			$(
+				config.min_deposit = $min_deposit;
+				config.max_deposit = $max_deposit;
+			)?
+			$(
+				config.message_bridge = Some(MessageBridge {
+					mainnet: $message_mainnet.parse().unwrap(),
+					testnet: $message_testnet.parse().unwrap(),
+				});
+			)?
+			$(
+				config.log_filters = $log_filters;
+			)?
+			$(
+				config.verify_relays_after = Some($verify_relays_after);
+			)?
+			$(
+				config.pause_switch = Some($pause_switch);
+			)?
+			$(
+				config.min_balance = Some($min_balance);
+			)?
+			$(
+				config.direction = $direction;
+			)?
+			$(
+				config.confirmations = $confirmations;
+			)?
+			$(
+				config.relay_delay = Some($relay_delay);
+			)?
+			$(
+				config.home.signer = $mainnet_signer;
+				config.foreign.signer = $testnet_signer;
+			)?
+			$(
+				config.home.manage_nonces = $mainnet_nonces;
+				config.foreign.manage_nonces = $testnet_nonces;
+			)?
+			$(
+				config.home.fee_model = $mainnet_fees;
+				config.foreign.fee_model = $testnet_fees;
+			)?
+			$(
+				config.home.max_gas_price = $mainnet_max_gas_price;
+				config.foreign.max_gas_price = $testnet_max_gas_price;
+			)?
+			let (home, foreign) = match config.direction {
+				Direction::MainnetToTestnet => (&mainnet, &testnet),
+				Direction::TestnetToMainnet => {
+					::std::mem::swap(&mut config.home, &mut config.foreign);
+					(&testnet, &mainnet)
+				},
+			};
+			let (mainnet_submission_transport, testnet_submission_transport) = (mainnet_submission.as_ref().unwrap_or(&mainnet), testnet_submission.as_ref().unwrap_or(&testnet));
+			let (home_submission, foreign_submission) = match config.direction {
+				Direction::MainnetToTestnet => (mainnet_submission_transport, testnet_submission_transport),
+				Direction::TestnetToMainnet => (testnet_submission_transport, mainnet_submission_transport),
+			};
+
+			let home_blocks = BlockWatcher::new(home, Default::default(), BlockWatcherInit {
+				request_timeout: config.home.request_timeout,
+				retry: config.home.retry.clone(),
+				poll_interval: config.home.poll_interval,
+				adaptive_polling: config.home.adaptive_polling,
+				block_tag: config.home.block_tag,
+			});
+			let foreign_blocks = BlockWatcher::new(foreign, Default::default(), BlockWatcherInit {
+				request_timeout: config.foreign.request_timeout,
+				retry: config.foreign.retry.clone(),
+				poll_interval: config.foreign.poll_interval,
+				adaptive_polling: config.foreign.adaptive_polling,
+				block_tag: config.foreign.block_tag,
+			});
 
+			let pause = PauseWatcher::new(&config, &mainnet, &testnet, Default::default());
+			let metrics = Metrics::default();
+			let gas_prices = GasPriceGuard::new(&config, &mainnet, &testnet, Default::default(), &metrics);
+			let funds = Funds::new(&config);
+			let payloads = SentPayloads::new(&config);
 			let app = App {
 				config,
-				database_path: "".into(),
 				connections: Connections {
-					mainnet: &mainnet,
-					testnet: &testnet,
+					home,
+					foreign,
+					home_submission,
+					foreign_submission,
 				},
-				mainnet_bridge: mainnet::EthereumBridge::default(),
-				testnet_bridge: testnet::KovanBridge::default(),
+				home_blocks,
+				foreign_blocks,
+				home_bridge: home::EthereumBridge::default(),
+				foreign_bridge: foreign::KovanBridge::default(),
+				message_bridge: message::MessageBridge::default(),
+				pause,
+				gas_prices,
+				funds,
+				nonces: Nonces::default(),
+				payloads,
 				timer: Default::default(),
+				metrics,
+				dry_run: None,
+				once: false,
+				until: None,
 			};
 
 			let app = Arc::new(app);
 			let stream = $init_stream(app, &$db);
 			let res = stream.collect().wait();
 			assert_eq!($expected, res.unwrap());
+			for submission in mainnet_submission.iter().chain(testnet_submission.iter()) {
+				assert_eq!(submission.expected_requests.len(), submission.requests.get(), "submission requests not sent");
+			}
 		}
 	}
 }
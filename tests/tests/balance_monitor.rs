@@ -0,0 +1,98 @@
+extern crate futures;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+
+use bridge::bridge::{create_balance_monitor, AccountBalance};
+
+test_app_stream! {
+	name => balance_monitor_checks_both_chains,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000002",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	min_balance => MinBalance {
+		relays: 10,
+		poll_interval: Duration::from_secs(0),
+	};
+	txs => Transactions {
+		withdraw_relay: TransactionConfig {
+			gas: 100,
+			gas_price: 2,
+			..Default::default()
+		},
+		deposit_relay: TransactionConfig {
+			gas: 50,
+			..Default::default()
+		},
+		withdraw_confirm: TransactionConfig {
+			gas: 200,
+			..Default::default()
+		},
+		..Default::default()
+	},
+	init => |app, _db| create_balance_monitor(app).take(4),
+	expected => vec![
+		AccountBalance {
+			chain: Chain::Mainnet,
+			account: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			balance: 10000,
+			required: 3000,
+		},
+		AccountBalance {
+			chain: Chain::Testnet,
+			account: "0x0000000000000000000000000000000000000002".parse().unwrap(),
+			balance: 1000,
+			required: 2000,
+		},
+		AccountBalance {
+			chain: Chain::Mainnet,
+			account: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			balance: 10000,
+			required: 2000,
+		},
+		AccountBalance {
+			chain: Chain::Testnet,
+			account: "0x0000000000000000000000000000000000000002".parse().unwrap(),
+			balance: 2000,
+			required: 2000,
+		},
+	],
+	mainnet_transport => [
+		"eth_getBalance" =>
+			req => r#"["0x0000000000000000000000000000000000000001","latest"]"#,
+			res => r#""0x2710""#;
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x3""#;
+		"eth_getBalance" =>
+			req => r#"["0x0000000000000000000000000000000000000001","latest"]"#,
+			res => r#""0x2710""#;
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x1""#;
+	],
+	testnet_transport => [
+		"eth_getBalance" =>
+			req => r#"["0x0000000000000000000000000000000000000002","latest"]"#,
+			res => r#""0x3e8""#;
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x1""#;
+		"eth_getBalance" =>
+			req => r#"["0x0000000000000000000000000000000000000002","latest"]"#,
+			res => r#""0x7d0""#;
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x1""#;
+	]
+}
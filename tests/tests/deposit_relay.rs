@@ -30,12 +30,18 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	],
 	testnet_transport => []
 }
@@ -67,13 +73,19 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	],
 	testnet_transport => [
 		"eth_sendTransaction" =>
@@ -104,6 +116,9 @@ test_app_stream! {
 		deposit_relay: TransactionConfig {
 			gas: 0xfd,
 			gas_price: 0xa0,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
 		},
 		..Default::default()
 	},
@@ -115,7 +130,10 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 	],
 	testnet_transport => [
 		"eth_sendTransaction" =>
@@ -127,8 +145,8 @@ test_app_stream! {
 test_app_stream! {
 	name => deposit_relay_contract_address,
 	database => Database {
-		mainnet_contract_address: "0x0000000000000000000000000000000000000cc1".parse().unwrap(),
-		testnet_contract_address: "0x0000000000000000000000000000000000000dd1".parse().unwrap(),
+		home_contract_address: "0x0000000000000000000000000000000000000cc1".parse().unwrap(),
+		foreign_contract_address: "0x0000000000000000000000000000000000000dd1".parse().unwrap(),
 		..Default::default()
 	},
 	mainnet =>
@@ -152,7 +170,10 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000cc1"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000cc1","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000cc1","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 	],
 	testnet_transport => [
 		"eth_sendTransaction" =>
@@ -164,8 +185,8 @@ test_app_stream! {
 test_app_stream! {
 	name => deposit_relay_accounts,
 	database => Database {
-		mainnet_contract_address: "0x0000000000000000000000000000000000000cc1".parse().unwrap(),
-		testnet_contract_address: "0x0000000000000000000000000000000000000dd1".parse().unwrap(),
+		home_contract_address: "0x0000000000000000000000000000000000000cc1".parse().unwrap(),
+		foreign_contract_address: "0x0000000000000000000000000000000000000dd1".parse().unwrap(),
 		..Default::default()
 	},
 	mainnet =>
@@ -189,7 +210,10 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000cc1"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000cc1","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000cc1","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 	],
 	testnet_transport => [
 		"eth_sendTransaction" =>
@@ -222,7 +246,49 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_checkpoints_every_relay,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(3),
+	expected => vec![0xf, 0x1f, 0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0","blockNumber":"0x10"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f","logIndex":"0x0","blockNumber":"0x20"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 	],
 	testnet_transport => [
 		"eth_sendTransaction" =>
@@ -233,3 +299,933 @@ test_app_stream! {
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
 	]
 }
+
+test_app_stream! {
+	name => deposit_relay_skips_processed_deposit,
+	database => Database {
+		checked_deposit_relay: 5,
+		relays: vec![RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0,
+			timestamp: 1_500_000_000,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => []
+}
+
+test_app_stream! {
+	name => deposit_relay_replays_journal,
+	database => Database {
+		checked_deposit_relay: 5,
+		journal: vec![JournalEntry {
+			chain: Chain::Testnet,
+			to: "0x0000000000000000000000000000000000000000".parse().unwrap(),
+			data: tests::bytes("0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"),
+			gas: 0,
+			gas_price: 0,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_preflight,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			preflight: true,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0xfd","gasPrice":"0xa0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_preflight_skips_relayed_deposit,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			preflight: true,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_waits_for_receipt_confirmations,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			preflight: false,
+			receipt_confirmations: Some(2),
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0xfd","gasPrice":"0xa0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x20""#;
+		"eth_getTransactionReceipt" =>
+			req => r#"["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]"#,
+			res => r#"null"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x21""#;
+		"eth_getTransactionReceipt" =>
+			req => r#"["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]"#,
+			res => r#"{"blockNumber":"0x21","status":"0x1","transactionHash":"0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b","logIndex":"0x0"}"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x23""#;
+		"eth_getTransactionReceipt" =>
+			req => r#"["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]"#,
+			res => r#"{"blockNumber":"0x21","status":"0x1","transactionHash":"0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b","logIndex":"0x0"}"#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_continues_after_failed_relay,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0xfd","gasPrice":"0xa0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#"{"error":{"code":-32000,"message":"transaction underpriced"}}"#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_quarantines_malformed_log,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc","type":"","transactionHash":"0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_filters_deposit_values,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	// deposits below the minimum are skipped and the ones above the maximum are held
+	deposits =>
+		min => Some(0x10.into()),
+		max => Some(0x100.into());
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc0000000000000000000000000000000000000000000000000000000000000001","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x1"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc0000000000000000000000000000000000000000000000000000000000001000","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x2"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_log_filters,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	log_filters => LogFilters {
+		deposit_relay: LogFilter {
+			topics: [
+				vec![
+					"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc".parse().unwrap(),
+					"0x000000000000000000000000001da5bcab735024168f00b43abcc9ef522392e9".parse().unwrap(),
+				],
+				vec![],
+				vec![],
+			],
+		},
+		..Default::default()
+	};
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],["0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc","0x000000000000000000000000001da5bcab735024168f00b43abcc9ef522392e9"],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => []
+}
+
+test_app_stream! {
+	name => deposit_relay_waits_until_unpaused,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	pause_switch => PauseSwitch {
+		mainnet: None,
+		testnet: Some("0x0000000000000000000000000000000000000010".parse().unwrap()),
+		poll_interval: Duration::from_secs(0),
+	};
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x5c975abb","to":"0x0000000000000000000000000000000000000010"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000001""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x5c975abb","to":"0x0000000000000000000000000000000000000010"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000000""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_defers_while_gas_price_exceeds_maximum,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	max_gas_price => mainnet => None, testnet => Some(50);
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x64""#;
+		"eth_gasPrice" =>
+			req => r#"[]"#,
+			res => r#""0x32""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_testnet_to_mainnet,
+	database => Database {
+		home_contract_address: "0x00000000000000000000000000000000000000aa".parse().unwrap(),
+		foreign_contract_address: "0x00000000000000000000000000000000000000bb".parse().unwrap(),
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	direction => Direction::TestnetToMainnet;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x00000000000000000000000000000000000000bb"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x00000000000000000000000000000000000000aa"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x00000000000000000000000000000000000000aa","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_waits_for_large_deposit_confirmations,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	confirmations => Confirmations {
+		deposit_relay: Some(10),
+		large_deposits: vec![LargeDeposits {
+			min_value: 0xf0.into(),
+			confirmations: 20,
+		}],
+		..Default::default()
+	};
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0xfff],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1007","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0","blockNumber":"0x1000"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		// the deposit of block 0x1000 is relayed once it has 20 confirmations
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1013""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1014""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_holds_large_deposit,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	relay_delay => RelayDelay {
+		min_deposit: Some(0xf0.into()),
+		min_withdraw: None,
+		delay: Duration::from_secs(3600),
+	};
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(2),
+	// the held deposit is persisted, so the checkpoint passes it
+	expected => vec![0x1005, 0x1006],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+	],
+	testnet_transport => []
+}
+
+test_app_stream! {
+	name => deposit_relay_releases_held_deposit,
+	database => Database {
+		checked_deposit_relay: 5,
+		held_relays: vec![HeldRelay {
+			release: 0,
+			entry: JournalEntry {
+				chain: Chain::Testnet,
+				to: "0x0000000000000000000000000000000000000000".parse().unwrap(),
+				data: tests::bytes("0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"),
+				gas: 0,
+				gas_price: 0,
+				event: ProcessedEvent {
+					chain: Chain::Mainnet,
+					transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+					log_index: 0,
+				},
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	relay_delay => RelayDelay {
+		min_deposit: Some(0xf0.into()),
+		min_withdraw: None,
+		delay: Duration::from_secs(3600),
+	};
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	// relay held before restart is released with the next logs
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_signs_locally,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	signers =>
+		mainnet => None,
+		testnet => Some(Signer::new(&[1u8; 32], 42).unwrap());
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			..Default::default()
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_getTransactionCount" =>
+			req => r#"["0x1a642f0e3c3af545e7acbd38b07251b3990914f1","pending"]"#,
+			res => r#""0x7""#;
+		"eth_sendRawTransaction" =>
+			req => r#"["0xf8c40781a081fd94000000000000000000000000000000000000000080b86426b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436478a039fbfbc606b4b7452ddd1fea988702f91b6f25f9636eafbea12266685bb241c6a0346e948aaba8f0f45e9c8240bf5e0412ad36622e411c8e0b8f656a8272684ba0"]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_eip1559,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	fee_models =>
+		mainnet => FeeModel::Legacy,
+		testnet => FeeModel::Eip1559 { max_priority_fee_per_gas: 0x10 };
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			..Default::default()
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x1a642f0e3c3af545e7acbd38b07251b3990914f1","gas":"0xfd","maxFeePerGas":"0xa0","maxPriorityFeePerGas":"0x10","to":"0x0000000000000000000000000000000000000000","type":"0x2"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_signs_eip1559_locally,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	signers =>
+		mainnet => None,
+		testnet => Some(Signer::new(&[1u8; 32], 42).unwrap());
+	fee_models =>
+		mainnet => FeeModel::Legacy,
+		testnet => FeeModel::Eip1559 { max_priority_fee_per_gas: 0x10 };
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+			..Default::default()
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_getTransactionCount" =>
+			req => r#"["0x1a642f0e3c3af545e7acbd38b07251b3990914f1","pending"]"#,
+			res => r#""0x7""#;
+		"eth_sendRawTransaction" =>
+			req => r#"["0x02f8c72a071081a081fd94000000000000000000000000000000000000000080b86426b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364c001a0b071a0d9ae56be95a016f1a62bd545b943cf8fbe045c360b16b818fff4c4f613a06e748f0915ddb378d401e9ab98a7b504b0eb14ad61ff5a83324f7cc230a508f3"]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_manages_nonces,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	manage_nonces =>
+		mainnet => false,
+		testnet => true;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		// nonces are synchronized once, then relays submitted at once get consecutive nonces
+		"eth_getTransactionCount" =>
+			req => r#"["0x0000000000000000000000000000000000000001","pending"]"#,
+			res => r#""0x7""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","nonce":"0x7","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","nonce":"0x8","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
@@ -6,7 +6,24 @@ extern crate tests;
 
 use std::time::Duration;
 use web3::types::{FilterBuilder, H160, H256, Log};
-use bridge::api::{LogStreamInit, log_stream, LogStreamItem};
+use bridge::api::{LogStreamInit, log_stream, LogStreamItem, BlockWatcher, BlockWatcherInit};
+use bridge::config::{AdaptivePolling, BlockTag, CatchUp, RetryPolicies, RetryPolicy};
+use bridge::transport::PubSub;
+use tests::MockedSubscriptions;
+
+fn block_watcher<T: PubSub + Clone>(transport: T) -> BlockWatcher<T> {
+	tagged_block_watcher(transport, None)
+}
+
+fn tagged_block_watcher<T: PubSub + Clone>(transport: T, block_tag: Option<BlockTag>) -> BlockWatcher<T> {
+	BlockWatcher::new(transport, Default::default(), BlockWatcherInit {
+		request_timeout: Duration::from_secs(5),
+		retry: Default::default(),
+		poll_interval: Duration::from_secs(0),
+		adaptive_polling: None,
+		block_tag,
+	})
+}
 
 test_transport_stream! {
 	name => log_stream_basic,
@@ -14,12 +31,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(2)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(2)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -39,26 +62,327 @@ test_transport_stream! {
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x1010""#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x1011""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0x1007","limit":null,"toBlock":"0x1007","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1011""#;
 }
 
 test_transport_stream! {
-	name => log_stream_rollback,
+	name => log_stream_uses_block_number_fetched_with_logs,
 	init => |transport| {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(2)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x1007,
+		to: 0x1007,
+		logs: vec![],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	// the new block is learnt from the batch with the logs, without polling for it
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1011""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x1007","limit":null,"toBlock":"0x1007","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1011""#;
+}
+
+test_transport_stream! {
+	name => log_stream_backs_off_while_there_are_no_logs,
+	init => |transport| {
+		let polling = AdaptivePolling {
+			min_interval: Duration::from_millis(10),
+			max_interval: Duration::from_millis(40),
+		};
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: Some(polling),
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+		let blocks = BlockWatcher::new(transport, Default::default(), BlockWatcherInit {
 			request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			poll_interval: Duration::from_millis(10),
+			adaptive_polling: Some(polling),
+			block_tag: None,
+		});
+
+		log_stream(transport, Default::default(), blocks, init).take(2)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x1007,
+		to: 0x1008,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x10].into(),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	// the stream is idle after fetching no logs, blocks mined in the meantime are fetched together
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1012""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x1007","limit":null,"toBlock":"0x1008","topics":null}]"#,
+		res => r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1012""#;
+}
+
+test_transport_stream! {
+	name => log_stream_subscription,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		let transport = MockedSubscriptions {
+			transport,
+			notifications: vec![
+				("0x1", r#"{"number":"0x101b"}"#),
+				// logs of the blocks up to the subscribed one are fetched
+				("0x2", r#"{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","blockNumber":"0x1010","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000010","logIndex":"0x0","type":""}"#),
+				("0x2", r#"{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x11","blockNumber":"0x1011","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000011","logIndex":"0x0","type":""}"#),
+				("0x2", r#"{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x12","blockNumber":"0x1011","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000012","logIndex":"0x1","type":""}"#),
+				// the log is removed by a reorganization
+				("0x2", r#"{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x12","blockNumber":"0x1011","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000012","logIndex":"0x1","type":"","removed":true}"#),
+				// logs of the blocks, which are not confirmed, are yielded later
+				("0x2", r#"{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x13","blockNumber":"0x1012","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000000013","logIndex":"0x0","type":""}"#),
+			],
 		};
 
-		log_stream(transport, Default::default(), init).take(2)
+		log_stream(transport.clone(), Default::default(), block_watcher(transport), init).take(2)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x1007,
+		to: 0x1011,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x11].into(),
+			block_number: Some(0x1011.into()),
+			transaction_hash: Some("0x0000000000000000000000000000000000000000000000000000000000000011".parse().unwrap()),
+			log_index: Some(0.into()),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_subscribe" =>
+		req => r#"["newHeads"]"#,
+		res => r#""0x1""#;
+	"eth_subscribe" =>
+		req => r#"["logs",{"address":null,"topics":null}]"#,
+		res => r#""0x2""#;
+	// logs of the blocks mined before subscribing are fetched
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	// new block notification
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x101b""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x1007","limit":null,"toBlock":"0x1010","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x101b""#;
+}
+
+test_transport_stream! {
+	name => log_stream_subscription_rejected,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		let transport = MockedSubscriptions {
+			transport,
+			notifications: vec![],
+		};
+
+		log_stream(transport.clone(), Default::default(), block_watcher(transport), init).take(1)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x10].into(),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_subscribe" =>
+		req => r#"["newHeads"]"#,
+		res => r#"{"error":{"code":-32601,"message":"Method not found"}}"#;
+	"eth_subscribe" =>
+		req => r#"["logs",{"address":null,"topics":null}]"#,
+		res => r#"{"error":{"code":-32601,"message":"Method not found"}}"#;
+	// the logs are fetched instead
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
+		res => r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+}
+
+test_transport_stream! {
+	name => log_stream_once,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: Some(0x800),
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: true,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x80a","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x80b","limit":null,"toBlock":"0x1006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
+}
+
+test_transport_stream! {
+	name => log_stream_rollback,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(2)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -75,6 +399,9 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0xd","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x17""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x16""#;
@@ -87,6 +414,9 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xe","limit":null,"toBlock":"0xf","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
 }
 
 test_transport_stream! {
@@ -95,12 +425,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(1)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(1)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -119,6 +455,9 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0xd","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x17""#;
 }
 
 test_transport_stream! {
@@ -127,12 +466,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 0,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(3)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(3)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -153,6 +498,9 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x13","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x13""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x14""#;
@@ -162,12 +510,18 @@ test_transport_stream! {
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x14""#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x14""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x17""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0x15","limit":null,"toBlock":"0x17","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x17""#;
 }
 
 test_transport_stream! {
@@ -176,12 +530,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 11,
 			filter: FilterBuilder::default().address(vec![H160([0x11u8; 20])]),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 0,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(2)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(2)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xc,
@@ -198,12 +558,18 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":["0x1111111111111111111111111111111111111111"],"fromBlock":"0xc","limit":null,"toBlock":"0x13","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x13""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x14""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":["0x1111111111111111111111111111111111111111"],"fromBlock":"0x14","limit":null,"toBlock":"0x14","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x14""#;
 }
 
 test_transport_stream! {
@@ -212,12 +578,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 11,
 			filter: FilterBuilder::default().topics(Some(vec![H256([0x22; 32])]), None, None, None),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 0,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(2)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(2)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xc,
@@ -234,12 +606,18 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xc","limit":null,"toBlock":"0x13","topics":[["0x2222222222222222222222222222222222222222222222222222222222222222"],null,null,null]}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x13""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x14""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0x14","limit":null,"toBlock":"0x14","topics":[["0x2222222222222222222222222222222222222222222222222222222222222222"],null,null,null]}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x14""#;
 }
 
 test_transport_stream! {
@@ -248,12 +626,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(1)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(1)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -273,6 +657,9 @@ test_transport_stream! {
 		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
 		res =>
 			r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
 }
 
 test_transport_stream! {
@@ -281,12 +668,18 @@ test_transport_stream! {
 		let init = LogStreamInit {
 			after: 10,
 			filter: FilterBuilder::default(),
-			poll_interval: Duration::from_secs(0),
-			request_timeout: Duration::from_secs(5),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
 			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
 		};
 
-		log_stream(transport, Default::default(), init).take(3)
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(3)
 	},
 	expected => vec![LogStreamItem {
 		from: 0xb,
@@ -325,16 +718,420 @@ test_transport_stream! {
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1006","topics":null}]"#,
 		res => r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1010""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x1011""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0x1007","limit":null,"toBlock":"0x1007","topics":null}]"#,
 		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1011""#;
 	"eth_blockNumber" =>
 		req => r#"[]"#,
 		res => r#""0x1012""#;
 	"eth_getLogs" =>
 		req => r#"[{"address":null,"fromBlock":"0x1008","limit":null,"toBlock":"0x1008","topics":null}]"#,
 		res => r#"[{"address":"0x0000000000000000000000000000000000000002","topics":[],"data":"0x20","type":""},{"address":"0x0000000000000000000000000000000000000002","topics":[],"data":"0x30","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x1012""#;
+}
+
+test_transport_stream! {
+	name => log_stream_chunked_logs,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: Some(0x1000),
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(1)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x2006,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x10].into(),
+			log_type: "".into(),
+			..Default::default()
+		}, Log {
+			address: "0x0000000000000000000000000000000000000002".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x20].into(),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x100a","topics":null}]"#,
+		res =>
+			r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x100b","limit":null,"toBlock":"0x2006","topics":null}]"#,
+		res =>
+			r#"[{"address":"0x0000000000000000000000000000000000000002","topics":[],"data":"0x20","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+}
+
+test_transport_stream! {
+	name => log_streams_share_block_number,
+	init => |transport| {
+		let init = |after| LogStreamInit {
+			after,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		let blocks = block_watcher(transport);
+		let first = log_stream(transport, Default::default(), blocks.clone(), init(10));
+		let second = log_stream(transport, Default::default(), blocks, init(12));
+		first.zip(second).take(1)
+	},
+	expected => vec![(LogStreamItem {
+		from: 0xb,
+		to: 0xf,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0xd,
+		to: 0xf,
+		logs: vec![],
+	})],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0xf","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xd","limit":null,"toBlock":"0xf","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
+}
+
+test_transport_stream! {
+	name => log_stream_bounded_by_finalized_block,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 2,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), tagged_block_watcher(transport, Some(BlockTag::Finalized)), init).take(2)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x12,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x13,
+		to: 0x17,
+		logs: vec![],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x20""#;
+	"eth_getBlockByNumber" =>
+		req => r#"["finalized",false]"#,
+		res => r#"{"number":"0x12","hash":"0x0000000000000000000000000000000000000000000000000000000000000012"}"#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x12","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x20""#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
+	"eth_getBlockByNumber" =>
+		req => r#"["finalized",false]"#,
+		res => r#"{"number":"0x18"}"#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x13","limit":null,"toBlock":"0x17","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x19""#;
+}
+
+test_transport_stream! {
+	name => log_stream_retries_finalized_block_after_failure,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 2,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+		// failed requests are not retried by the call itself, but by the watcher
+		let blocks = BlockWatcher::new(transport, Default::default(), BlockWatcherInit {
+			request_timeout: Duration::from_secs(5),
+			retry: RetryPolicies::new(RetryPolicy {
+				max_attempts: 1,
+				backoff: Duration::from_millis(0),
+				jitter: Duration::from_millis(0),
+			}),
+			poll_interval: Duration::from_secs(0),
+			adaptive_polling: None,
+			block_tag: Some(BlockTag::Finalized),
+		});
+
+		log_stream(transport, Default::default(), blocks, init).take(1)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x12,
+		logs: vec![],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x20""#;
+	"eth_getBlockByNumber" =>
+		req => r#"["finalized",false]"#,
+		res => r#"{"error":{"code":-32000,"message":"request failed"}}"#;
+	"eth_getBlockByNumber" =>
+		req => r#"["finalized",false]"#,
+		res => r#"{"number":"0x12"}"#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x12","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x20""#;
+}
+
+test_transport_stream! {
+	name => log_stream_bisects_range_with_too_many_logs,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: None,
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(1)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x1a,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x10].into(),
+			log_type: "".into(),
+			..Default::default()
+		}, Log {
+			address: "0x0000000000000000000000000000000000000002".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x20].into(),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x1a","topics":null}]"#,
+		res => r#"{"error":{"code":-32005,"message":"query returned more than 10000 results"}}"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x12","topics":null}]"#,
+		res => r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x13","limit":null,"toBlock":"0x1a","topics":null}]"#,
+		res => r#"{"error":{"code":-32005,"message":"query returned more than 10000 results"}}"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x13","limit":null,"toBlock":"0x16","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x17","limit":null,"toBlock":"0x1a","topics":null}]"#,
+		res => r#"[{"address":"0x0000000000000000000000000000000000000002","topics":[],"data":"0x20","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x24""#;
+}
+
+test_transport_stream! {
+	name => log_stream_catch_up,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: Some(0x10),
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: Some(CatchUp {
+				threshold: 0x100,
+				max_blocks_per_query: Some(0x1000),
+				defer_relays: false,
+			}),
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(3)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x100a,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x100b,
+		to: 0x2006,
+		logs: vec![],
+	}, LogStreamItem {
+		from: 0x2007,
+		to: 0x2007,
+		logs: vec![],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x100a","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x100b","limit":null,"toBlock":"0x2006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2011""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x2007","limit":null,"toBlock":"0x2007","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2011""#;
+}
+
+test_transport_stream! {
+	name => log_stream_catch_up_defers_logs,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			logs_request_timeout: Duration::from_secs(5),
+			retry: Default::default(),
+			confirmations: 10,
+			max_blocks_per_query: None,
+			adaptive_polling: None,
+			poll_interval: None,
+			catch_up: Some(CatchUp {
+				threshold: 0x100,
+				max_blocks_per_query: Some(0x1000),
+				defer_relays: true,
+			}),
+			once: false,
+			until: None,
+		};
+
+		log_stream(transport, Default::default(), block_watcher(transport), init).take(1)
+	},
+	expected => vec![LogStreamItem {
+		from: 0xb,
+		to: 0x2006,
+		logs: vec![Log {
+			address: "0x0000000000000000000000000000000000000001".parse().unwrap(),
+			topics: vec![],
+			data: vec![0x10].into(),
+			log_type: "".into(),
+			..Default::default()
+		}],
+	}],
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0xb","limit":null,"toBlock":"0x100a","topics":null}]"#,
+		res =>
+			r#"[{"address":"0x0000000000000000000000000000000000000001","topics":[],"data":"0x10","type":""}]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
+	"eth_getLogs" =>
+		req => r#"[{"address":null,"fromBlock":"0x100b","limit":null,"toBlock":"0x2006","topics":null}]"#,
+		res => r#"[]"#;
+	"eth_blockNumber" =>
+		req => r#"[]"#,
+		res => r#""0x2010""#;
 }
@@ -0,0 +1,177 @@
+extern crate futures;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+
+use bridge::bridge::create_message_relay;
+
+test_app_stream! {
+	name => message_relay_from_mainnet,
+	// logs are checked from the contract deployment if there is no checkpoint
+	database => Database {
+		home_deploy: 5,
+		checked_deposit_relay: 0x10,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	message_bridge =>
+		mainnet => "0x0000000000000000000000000000000000000002",
+		testnet => "0x0000000000000000000000000000000000000003";
+	txs => Transactions::default(),
+	init => |app, db| create_message_relay(app, db, Chain::Mainnet).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000002"],"fromBlock":"0x6","limit":null,"toBlock":"0x1005","topics":[["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000002","topics":["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],"data":"0x000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000003"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
+
+test_app_stream! {
+	name => message_relay_from_testnet_skips_reverted_relay,
+	database => Database {
+		checked_testnet_message_relay: 0x10,
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	message_bridge =>
+		mainnet => "0x0000000000000000000000000000000000000002",
+		testnet => "0x0000000000000000000000000000000000000003";
+	txs => Transactions {
+		message_relay: TransactionConfig {
+			gas: 0x10,
+			gas_price: 0x20,
+			preflight: true,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_message_relay(app, db, Chain::Testnet).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000002"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_call" =>
+			req => r#"[{"data":"0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942439900000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000002"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x10","gasPrice":"0x20","to":"0x0000000000000000000000000000000000000002"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000003"],"fromBlock":"0x11","limit":null,"toBlock":"0x1005","topics":[["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000003","topics":["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],"data":"0x000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000003","topics":["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],"data":"0x000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+	]
+}
+
+test_app_stream! {
+	name => message_relay_replays_own_journal_entries,
+	database => Database {
+		foreign_contract_address: "0x0000000000000000000000000000000000000004".parse().unwrap(),
+		checked_mainnet_message_relay: 0x1005,
+		journal: vec![
+			// deposit relays are replayed by the deposit relay
+			JournalEntry {
+				chain: Chain::Testnet,
+				to: "0x0000000000000000000000000000000000000004".parse().unwrap(),
+				data: vec![1, 2, 3].into(),
+				gas: 0,
+				gas_price: 0,
+				event: ProcessedEvent {
+					chain: Chain::Mainnet,
+					transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399".parse().unwrap(),
+					log_index: 0,
+				},
+			},
+			JournalEntry {
+				chain: Chain::Testnet,
+				to: "0x0000000000000000000000000000000000000003".parse().unwrap(),
+				data: tests::bytes("0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000"),
+				gas: 0,
+				gas_price: 0,
+				event: ProcessedEvent {
+					chain: Chain::Mainnet,
+					transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+					log_index: 0,
+				},
+			},
+		],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	message_bridge =>
+		mainnet => "0x0000000000000000000000000000000000000002",
+		testnet => "0x0000000000000000000000000000000000000003";
+	txs => Transactions::default(),
+	init => |app, db| create_message_relay(app, db, Chain::Mainnet).take(1),
+	expected => vec![0x1006],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000002"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000002","topics":["0x69331a26390a78389351d9264561047b757948e9b0a1a9dfc6bec2b230b50953"],"data":"0x000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+	],
+	testnet_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0xef6ccdd00000000000000000000000000000000000000000000000000000000000000040884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000000000000000000000000000000000000000000031234560000000000000000000000000000000000000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000003"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	]
+}
@@ -0,0 +1,139 @@
+extern crate futures;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+
+use bridge::bridge::{create_relay_verification, Verified};
+
+test_app_stream! {
+	name => relay_verification_reports_deposit_without_effect,
+	database => Database {
+		journal: vec![JournalEntry {
+			chain: Chain::Testnet,
+			to: "0x0000000000000000000000000000000000000000".parse().unwrap(),
+			data: tests::bytes("0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"),
+			gas: 0xfd,
+			gas_price: 0xa0,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	verify_relays_after => 2;
+	txs => Transactions::default(),
+	init => |app, db| {
+		let mut verification = create_relay_verification(app, db);
+		verification.submitted().relayed(&[RelayOutcome {
+			chain: Chain::Testnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0xa0,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Mainnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}]);
+		verification.take(1).map(|verified| match verified {
+			Verified::Relayed(relay) => Ok(relay.transaction_hash),
+			Verified::Discrepancy(discrepancy) => Err(discrepancy.transaction_hash),
+		})
+	},
+	expected => vec![Err("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap())],
+	mainnet_transport => [],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x10""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x11""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x12""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+	]
+}
+
+test_app_stream! {
+	name => relay_verification_accepts_executed_withdraw,
+	database => Database {
+		journal: vec![JournalEntry {
+			chain: Chain::Mainnet,
+			to: "0x0000000000000000000000000000000000000000".parse().unwrap(),
+			data: tests::bytes("0x1234"),
+			gas: 0xfd,
+			gas_price: 0xa0,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 1,
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	verify_relays_after => 1;
+	txs => Transactions::default(),
+	init => |app, db| {
+		let mut verification = create_relay_verification(app, db);
+		verification.submitted().relayed(&[RelayOutcome {
+			chain: Chain::Mainnet,
+			transaction_hash: "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			gas_price: 0xa0,
+			timestamp: 0,
+			block: None,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 1,
+			},
+		}]);
+		verification.take(1).map(|verified| match verified {
+			Verified::Relayed(relay) => Ok(relay.transaction_hash),
+			Verified::Discrepancy(discrepancy) => Err(discrepancy.transaction_hash),
+		})
+	},
+	expected => vec![Ok("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap())],
+	mainnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x20""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x21""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1234","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
+	testnet_transport => []
+}
@@ -31,12 +31,18 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	]
 }
 
@@ -72,12 +78,18 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0xf6","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	]
 }
 
@@ -85,8 +97,8 @@ test_app_stream! {
 	name => withdraw_confirm_contract_address,
 	database => Database {
 		checked_withdraw_confirm: 0x00F5,
-		mainnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
-		testnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+		home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+		foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
 		..Database::default()
 	},
 	mainnet =>
@@ -115,12 +127,18 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0xf6","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	]
 }
 
@@ -128,8 +146,8 @@ test_app_stream! {
 	name => withdraw_confirm_payload_gas,
 	database => Database {
 		checked_withdraw_confirm: 0x00F5,
-		mainnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
-		testnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+		home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+		foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
 		..Database::default()
 	},
 	mainnet =>
@@ -148,6 +166,9 @@ test_app_stream! {
 		withdraw_confirm: TransactionConfig {
 			gas: 0xfe,
 			gas_price: 0xa1,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
 		},
 		..Default::default()
 	},
@@ -163,10 +184,16 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0xf6","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_sign" =>
 			req => r#"["0x0000000000000000000000000000000000000001","0xaff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"]"#,
 			res => r#""0x8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"},"latest"]"#,
+			res => r#""0x""#;
 		"eth_sendTransaction" =>
 			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0xfe","gasPrice":"0xa1","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"}]"#,
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
@@ -176,14 +203,17 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	]
 }
 
 test_app_stream! {
 	name => withdraw_confirm_payload_multiple,
 	database => Database {
-		mainnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
-		testnet_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+		home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+		foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
 		..Database::default()
 	},
 	mainnet =>
@@ -202,6 +232,9 @@ test_app_stream! {
 		withdraw_confirm: TransactionConfig {
 			gas: 0xff,
 			gas_price: 0xaa,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
 		},
 		..Default::default()
 	},
@@ -214,13 +247,22 @@ test_app_stream! {
 			res => r#""0xe""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0x1","limit":null,"toBlock":"0x2","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"},{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000001da5bcab735024168f00b43abcc9ef522392e90000000000000000000000000000000000000000000000000000000000000099","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399"}]"#;
+			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000001da5bcab735024168f00b43abcc9ef522392e90000000000000000000000000000000000000000000000000000000000000099","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0xe""#;
 		"eth_sign" =>
 			req => r#"["0x0000000000000000000000000000000000000001","0xaff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"]"#,
 			res => r#""0x8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc""#;
 		"eth_sign" =>
 			req => r#"["0x0000000000000000000000000000000000000001","0x001da5bcab735024168f00b43abcc9ef522392e90000000000000000000000000000000000000000000000000000000000000099884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399"]"#,
 			res => r#""0x8a3b24c56e46f6fc9fa7ed14795745348059b8ac84d6ee93323e83a429e760ae6e89510834ee4d65eefacd74cddca53df61b5eba1c3007ed88d2eebff2e0e2151b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418a3b24c56e46f6fc9fa7ed14795745348059b8ac84d6ee93323e83a429e760ae6e89510834ee4d65eefacd74cddca53df61b5eba1c3007ed88d2eebff2e0e2151b000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054001da5bcab735024168f00b43abcc9ef522392e90000000000000000000000000000000000000000000000000000000000000099884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424399000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"},"latest"]"#,
+			res => r#""0x""#;
 		"eth_sendTransaction" =>
 			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0xff","gasPrice":"0xaa","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"}]"#,
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
@@ -233,5 +275,134 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0x3","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_confirm_skips_confirmed_withdraws,
+	database => Database {
+		checked_withdraw_confirm: 0x00F5,
+		home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+		foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+		..Database::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x00000000000000000000000000000000000000F1",
+			"0x00000000000000000000000000000000000000F2",
+		],
+		signatures => 1;
+	txs => Transactions {
+		withdraw_confirm: TransactionConfig {
+			gas: 0xfe,
+			gas_price: 0xa1,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_withdraw_confirm(app, db).take(2),
+	expected => vec![0x1005, 0x1006],
+	mainnet_transport => [],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x0100""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0xf6","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_sign" =>
+			req => r#"["0x0000000000000000000000000000000000000001","0xaff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"]"#,
+			res => r#""0x8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c000000000000000000000000000000000000000000000000000000000000000418697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_confirm_signs_locally,
+	database => Database {
+		checked_withdraw_confirm: 0x00F5,
+		home_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db7".parse().unwrap(),
+		foreign_contract_address: "0x49edf201c1e139282643d5e7c6fb0c7219ad1db8".parse().unwrap(),
+		..Database::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x00000000000000000000000000000000000000F2",
+		],
+		signatures => 1;
+	signers =>
+		mainnet => None,
+		testnet => Some(Signer::new(&[1u8; 32], 42).unwrap());
+	txs => Transactions {
+		withdraw_confirm: TransactionConfig {
+			gas: 0xfe,
+			gas_price: 0xa1,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_withdraw_confirm(app, db).take(1),
+	// the withdraw is signed without `eth_sign`
+	expected => vec![0x1005],
+	mainnet_transport => [],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x0100""#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"],"fromBlock":"0xf6","limit":null,"toBlock":"0x1005","topics":[["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8","topics":["0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000004125d573daad74c9186c75845bb1e94699ddd4aa4381b8d9d6e698747f16ab11dd159eafcbfae1985004468ac3650f5ee972045c8253b5cfb85a4095ce85ff10f81c000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364000000000000000000000000","from":"0x1a642f0e3c3af545e7acbd38b07251b3990914f1","to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_getTransactionCount" =>
+			req => r#"["0x1a642f0e3c3af545e7acbd38b07251b3990914f1","pending"]"#,
+			res => r#""0x0""#;
+		"eth_sendRawTransaction" =>
+			req => r#"["0xf901a58081a181fe9449edf201c1e139282643d5e7c6fb0c7219ad1db880b90144630cea8e000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000004125d573daad74c9186c75845bb1e94699ddd4aa4381b8d9d6e698747f16ab11dd159eafcbfae1985004468ac3650f5ee972045c8253b5cfb85a4095ce85ff10f81c000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000054aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436400000000000000000000000077a0bf4319d9d8cae35ced08f336349372b5f58d2b75eb5e805a6baaffb577d328cda014c0334b9f223b4b65d6a9d3158d1b975c7f11b0277c6402c6ba0758630569aa"]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
 	]
 }
@@ -31,12 +31,18 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1012""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1006","limit":null,"toBlock":"0x1006","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
 			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1012""#;
 	]
 }
 
@@ -58,14 +64,30 @@ test_app_stream! {
 	txs => Transactions::default(),
 	init => |app, db| create_withdraw_relay(app, db).take(1),
 	expected => vec![0x1005],
-	mainnet_transport => [],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000001""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
 	testnet_transport => [
 		"eth_blockNumber" =>
 			req => r#"[]"#,
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000001""#;
 	]
 }
 
@@ -80,16 +102,28 @@ test_app_stream! {
 		confirmations => 12;
 	authorities =>
 		accounts => [
-			"0x0000000000000000000000000000000000000001",
-			"0x0000000000000000000000000000000000000002",
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
 		],
 		signatures => 2;
 	txs => Transactions::default(),
 	init => |app, db| create_withdraw_relay(app, db).take(1),
 	expected => vec![0x1005],
 	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
 		"eth_sendTransaction" =>
-			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001100000000000000000000000000000000000000000000000000000000000000220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
 	],
 	testnet_transport => [
@@ -98,19 +132,88 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
 			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
-			res => r#""0x1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111""#;
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
-			res => r#""0x2222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222""#;
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
 	]
 }
 
+test_app_stream! {
+	name => withdraw_relay_sends_transactions_to_submission_endpoint,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	],
+	mainnet_submission_transport => [
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_submission_transport => []
+}
+
 test_app_stream! {
 	name => withdraw_relay_check_gas,
 	database => Database::default(),
@@ -122,22 +225,37 @@ test_app_stream! {
 		confirmations => 12;
 	authorities =>
 		accounts => [
-			"0x0000000000000000000000000000000000000001",
-			"0x0000000000000000000000000000000000000002",
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
 		],
 		signatures => 2;
 	txs => Transactions {
 		withdraw_relay: TransactionConfig {
 			gas: 0x10,
 			gas_price: 0x20,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: false,
 		},
 		..Default::default()
 	},
 	init => |app, db| create_withdraw_relay(app, db).take(1),
 	expected => vec![0x1005],
 	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
 		"eth_sendTransaction" =>
-			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001100000000000000000000000000000000000000000000000000000000000000220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x10","gasPrice":"0x20","to":"0x0000000000000000000000000000000000000000"}]"#,
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x10","gasPrice":"0x20","to":"0x0000000000000000000000000000000000000000"}]"#,
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
 	],
 	testnet_transport => [
@@ -146,24 +264,30 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
 			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
-			res => r#""0x1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111""#;
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
-			res => r#""0x2222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222""#;
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
 	]
 }
 
 test_app_stream! {
 	name => withdraw_relay_single_contract_addresses,
 	database => Database {
-		mainnet_contract_address: "0x00000000000000000000000000000000000000dd".parse().unwrap(),
-		testnet_contract_address: "0x00000000000000000000000000000000000000ee".parse().unwrap(),
+		home_contract_address: "0x00000000000000000000000000000000000000dd".parse().unwrap(),
+		foreign_contract_address: "0x00000000000000000000000000000000000000ee".parse().unwrap(),
 		..Default::default()
 	},
 	mainnet =>
@@ -174,16 +298,28 @@ test_app_stream! {
 		confirmations => 12;
 	authorities =>
 		accounts => [
-			"0x0000000000000000000000000000000000000001",
-			"0x0000000000000000000000000000000000000002",
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
 		],
 		signatures => 2;
 	txs => Transactions::default(),
 	init => |app, db| create_withdraw_relay(app, db).take(1),
 	expected => vec![0x1005],
 	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x""#;
 		"eth_sendTransaction" =>
-			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001100000000000000000000000000000000000000000000000000000000000000220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000002111111111111111111111111111111111111111111111111111111111111111122222222222222222222222222222222222222222222222222222222222222220000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x00000000000000000000000000000000000000dd"}]"#,
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x00000000000000000000000000000000000000dd"}]"#,
 			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
 	],
 	testnet_transport => [
@@ -192,15 +328,576 @@ test_app_stream! {
 			res => r#""0x1011""#;
 		"eth_getLogs" =>
 			req => r#"[{"address":["0x00000000000000000000000000000000000000ee"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
-			res => r#"[{"address":"0x00000000000000000000000000000000000000ee","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"}]"#;
+			res => r#"[{"address":"0x00000000000000000000000000000000000000ee","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
 			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
-			res => r#""0x1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111""#;
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
 		"eth_call" =>
 			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
-			res => r#""0x2222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222222""#;
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_skips_processed_withdraw,
+	database => Database {
+		home_contract_address: "0x00000000000000000000000000000000000000dd".parse().unwrap(),
+		foreign_contract_address: "0x00000000000000000000000000000000000000ee".parse().unwrap(),
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x00000000000000000000000000000000000000ee"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x00000000000000000000000000000000000000ee","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+// the withdraw assigned before restart is fetched, even though its log is not read again
+test_app_stream! {
+	name => withdraw_relay_fetches_restored_assignment,
+	database => Database {
+		home_contract_address: "0x00000000000000000000000000000000000000dd".parse().unwrap(),
+		foreign_contract_address: "0x00000000000000000000000000000000000000ee".parse().unwrap(),
+		checked_withdraw_relay: 0x1000,
+		pending_assignments: vec![PendingAssignment {
+			message_hash: "0x00000000000000000000000000000000000000000000000000000000000000f0".parse().unwrap(),
+			authority: "0xaff3454fce5edbc8cca8697c15331677e6ebcccc".parse().unwrap(),
+			block: Some(0xfff),
+			failures: 1,
+			due: 0,
+			event: ProcessedEvent {
+				chain: Chain::Testnet,
+				transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+				log_index: 0,
+			},
+		}],
+		..Default::default()
+	},
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000dd"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x00000000000000000000000000000000000000ee"],"fromBlock":"0x1001","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x00000000000000000000000000000000000000ee"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_skips_invalid_signatures,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_defers_missing_signatures,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","blockNumber":"0x1000","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_required_signatures_from_contract,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000001400000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000001ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4000000000000000000000000000000000000000000000000000000000000000129001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523070000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000c0000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000001400000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000001ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4000000000000000000000000000000000000000000000000000000000000000129001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523070000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000001""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_authorities_from_contract,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_round_robin_assignment,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 2,
+		// 0xf0 % 2 == 0, so the withdraw is relayed by the first authority
+		assignment => WithdrawRelayAssignment::RoundRobin,
+		fallback => None;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_sequences_nonces,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0xaff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x1a642f0e3c3af545e7acbd38b07251b3990914f1",
+			"0x5050a4f4b3f9338c3472dcc01a87c76a144b3c9c",
+		],
+		signatures => 2;
+	txs => Transactions {
+		withdraw_relay: TransactionConfig {
+			gas: 0,
+			gas_price: 0,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: true,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000001a642f0e3c3af545e7acbd38b07251b3990914f1""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000005050a4f4b3f9338c3472dcc01a87c76a144b3c9c""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x494503d40000000000000000000000000000000000000000000000000000000000000002","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#"{"error":{"code":-32015,"message":"VM execution error."}}"#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x""#;
+		"eth_getTransactionCount" =>
+			req => r#"["0x0000000000000000000000000000000000000001","pending"]"#,
+			res => r#""0x5""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","nonce":"0x5","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b""#;
+		"eth_sendTransaction" =>
+			req => r#"[{"data":"0x9ce318f6000000000000000000000000000000000000000000000000000000000000008000000000000000000000000000000000000000000000000000000000000000e0000000000000000000000000000000000000000000000000000000000000014000000000000000000000000000000000000000000000000000000000000001a00000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000001b000000000000000000000000000000000000000000000000000000000000001b0000000000000000000000000000000000000000000000000000000000000002ff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a4a5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f000000000000000000000000000000000000000000000000000000000000000229001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a60000000000000000000000000000000000000000000000000000000000000054333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333000000000000000000000000","from":"0x0000000000000000000000000000000000000001","gas":"0x0","gasPrice":"0x0","nonce":"0x6","to":"0x0000000000000000000000000000000000000000"}]"#,
+			res => r#""0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c""#;
+	],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364","logIndex":"0x0"},{"address":"0x0000000000000000000000000000000000000000","topics":["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],"data":"0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424365","logIndex":"0x0"}]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x8d068043","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x0000000000000000000000000000000000000000000000000000000000000002""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x490a32c600000000000000000000000000000000000000000000000000000000000000f0","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0x333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333333""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xff5c4cef1fa8201d779668e7986128d676ed7ac9980f50bdd2278b9ce2bfb7a429001319c9698f32f8eceb342af15668a480c4ce251a23768206ea2449d523071b""#;
+		"eth_call" =>
+			req => r#"[{"data":"0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001","to":"0x0000000000000000000000000000000000000000"},"latest"]"#,
+			res => r#""0xa5378a9aaf62275473f0332b41a0dddc6ce8cf705ad1acac8cfcb0109e42512f1485ca83d1a260971d41d316eb69baa603e784dcffd7e9819086f042048a76a61b""#;
+	]
+}
+
+test_app_stream! {
+	name => withdraw_relay_sequences_nonces_without_logs,
+	database => Database::default(),
+	mainnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	testnet =>
+		account => "0x0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0x0000000000000000000000000000000000000001",
+			"0x0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	manage_nonces =>
+		mainnet => true,
+		testnet => false;
+	txs => Transactions {
+		withdraw_relay: TransactionConfig {
+			gas: 0,
+			gas_price: 0,
+			preflight: false,
+			receipt_confirmations: None,
+			sequence_nonces: true,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	mainnet_transport => [],
+	testnet_transport => [
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
+		"eth_getLogs" =>
+			req => r#"[{"address":["0x0000000000000000000000000000000000000000"],"fromBlock":"0x1","limit":null,"toBlock":"0x1005","topics":[["0xeb043d149eedb81369bec43d4c3a3a53087debc88d2525f13bfaa3eecda28b5c"],[],[],[]]}]"#,
+			res => r#"[]"#;
+		"eth_blockNumber" =>
+			req => r#"[]"#,
+			res => r#""0x1011""#;
 	]
 }